@@ -22,11 +22,101 @@ pub mod syscall {
     pub const SYS_SOCKET: u64 = 6;
     pub const SYS_SENDTO: u64 = 7;
     pub const SYS_RECVFROM: u64 = 8;
+    pub const SYS_SETSOCKOPT: u64 = 9;
+    pub const SYS_BRK: u64 = 10;
+    pub const SYS_BATCH: u64 = 11;
+    pub const SYS_KILL: u64 = 12;
+    pub const SYS_SIGPENDING: u64 = 13;
+    pub const SYS_TLS_GET: u64 = 14;
+    pub const SYS_TLS_SET: u64 = 15;
+    pub const SYS_INPUT_SUBSCRIBE: u64 = 16;
+    pub const SYS_INPUT_READ: u64 = 17;
+    pub const SYS_SURFACE: u64 = 18;
+    pub const SYS_POLL: u64 = 19;
+    pub const SYS_TIMER_SET: u64 = 20;
+    pub const SYS_CONNECT: u64 = 21;
+    pub const SYS_BIND: u64 = 22;
+    pub const SYS_LISTEN: u64 = 23;
+    pub const SYS_ACCEPT: u64 = 24;
+    pub const SYS_CLOSE: u64 = 25;
+
+    /// Most records one SYS_BATCH call accepts.
+    pub const BATCH_MAX_RECORDS: usize = 16;
+    /// SYS_BATCH flag: stop after the first record that returns a negative errno.
+    pub const BATCH_STOP_ON_ERROR: u64 = 1 << 0;
+
+    /// 64-bit task-local slots behind SYS_TLS_GET/SYS_TLS_SET.
+    pub const TLS_SLOTS: u64 = 8;
+    /// Seeded by the kernel with a per-task random value at spawn; `rng::TlsRng` keeps its
+    /// state here.
+    pub const TLS_SLOT_RNG: u64 = 0;
+    /// Errno emulation for C ports; starts at 0.
+    pub const TLS_SLOT_ERRNO: u64 = 1;
+    /// First slot with no assigned meaning.
+    pub const TLS_SLOT_USER: u64 = 2;
+
+    /// SYS_INPUT_SUBSCRIBE flags: the event kinds to receive, plus `INPUT_CAPTURE` to take
+    /// them ahead of every other consumer until ESC is pressed.
+    pub const INPUT_KEYS: u8 = 1 << 0;
+    pub const INPUT_BYTES: u8 = 1 << 1;
+    pub const INPUT_MOUSE: u8 = 1 << 2;
+    pub const INPUT_CAPTURE: u64 = 1 << 8;
+    /// `InputRecord::code` values above the byte range for keys without an ASCII byte.
+    pub const INPUT_KEY_UP: u16 = 0x0100;
+    pub const INPUT_KEY_DOWN: u16 = 0x0101;
+    pub const INPUT_KEY_LEFT: u16 = 0x0102;
+    pub const INPUT_KEY_RIGHT: u16 = 0x0103;
+
+    /// SYS_SURFACE operations, passed as its first argument.
+    pub const SURFACE_OPEN: u64 = 0;
+    pub const SURFACE_ATTACH: u64 = 1;
+    pub const SURFACE_DAMAGE: u64 = 2;
+    pub const SURFACE_DETACH: u64 = 3;
+    pub const SURFACE_CLOSE: u64 = 4;
+    pub const SURFACE_EVENTS: u64 = 5;
+    /// SURFACE_EVENTS bit: the window's close button was pressed.
+    pub const SURFACE_EVENT_CLOSE: u64 = 1 << 0;
+    /// Window id SURFACE_OPEN returns: the desktop's one app window.
+    pub const SURFACE_WINDOW_APP: u32 = 4;
+    /// Largest surface SURFACE_ATTACH accepts, in pixels.
+    pub const SURFACE_MAX_WIDTH: u32 = 640;
+    pub const SURFACE_MAX_HEIGHT: u32 = 480;
+
+    /// Fixed fds SYS_POLL understands besides `UDP_SOCKET_FD`: the task's input subscription
+    /// and its SYS_TIMER_SET timer.
+    pub const INPUT_FD: u64 = 2;
+    pub const TIMER_FD: u64 = 3;
+    /// `PollFd` event bits, with the Linux values. `POLLERR` and `POLLNVAL` are only reported.
+    pub const POLLIN: u16 = 0x0001;
+    pub const POLLOUT: u16 = 0x0004;
+    pub const POLLERR: u16 = 0x0008;
+    pub const POLLNVAL: u16 = 0x0020;
+    /// Most fds one SYS_POLL call accepts.
+    pub const POLL_MAX_FDS: usize = 8;
+    /// SYS_POLL timeout that waits until an fd is ready.
+    pub const POLL_FOREVER: u64 = u64::MAX;
+
+    /// SYS_KILL signal 0 only checks that the pid exists.
+    pub const SIGINT: u64 = 2;
+    pub const SIGKILL: u64 = 9;
+    pub const SIGTERM: u64 = 15;
 
     pub const AF_INET: u64 = 2;
+    pub const SOCK_STREAM: u64 = 1;
     pub const SOCK_DGRAM: u64 = 2;
+    pub const IPPROTO_TCP: u64 = 6;
     pub const IPPROTO_UDP: u64 = 17;
     pub const UDP_SOCKET_FD: u64 = 1;
+    /// SYS_SOCKET hands out TCP fds from `TCP_FD_FIRST` on, `TCP_FDS` per task.
+    pub const TCP_FD_FIRST: u64 = 4;
+    pub const TCP_FDS: u64 = 4;
+
+    pub const SOL_SOCKET: u32 = 1;
+    pub const SO_BROADCAST: u32 = 6;
+    pub const SO_TIMESTAMP: u32 = 29;
+    pub const IPPROTO_IP: u32 = 0;
+    pub const IP_ADD_MEMBERSHIP: u32 = 35;
+    pub const IP_DROP_MEMBERSHIP: u32 = 36;
 
     #[repr(C)]
     #[derive(Clone, Copy)]
@@ -64,8 +154,15 @@ pub mod syscall {
         pub dst_port: u16,
         pub payload_ptr: u64,
         pub payload_cap: u64,
+        /// PIT tick the kernel received the datagram at; 0 unless `SO_TIMESTAMP` is on.
+        pub rx_tick: u64,
     }
 
+    /// `UdpRecvReq` without `rx_tick`, as callers built before `SO_TIMESTAMP` pass it;
+    /// `recvfrom` still accepts this size and leaves the timestamp out.
+    pub const UDP_RECV_REQ_BASE_LEN: usize = core::mem::offset_of!(UdpRecvReq, rx_tick);
+    const _: () = assert!(UDP_RECV_REQ_BASE_LEN == 24);
+
     impl UdpRecvReq {
         pub const fn new(payload_ptr: u64, payload_cap: u64) -> Self {
             Self {
@@ -74,6 +171,263 @@ pub mod syscall {
                 dst_port: 0,
                 payload_ptr,
                 payload_cap,
+                rx_tick: 0,
+            }
+        }
+    }
+
+    /// SYS_CONNECT and SYS_BIND address; SYS_ACCEPT fills one in with the peer's.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct SockAddrIn {
+        pub ip: [u8; 4],
+        pub port: u16,
+        pub reserved: u16,
+    }
+
+    impl SockAddrIn {
+        pub const fn new(ip: [u8; 4], port: u16) -> Self {
+            Self {
+                ip,
+                port,
+                reserved: 0,
+            }
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct SockOptReq {
+        pub level: u32,
+        pub option: u32,
+        pub value: u64,
+    }
+
+    impl SockOptReq {
+        pub const fn new(level: u32, option: u32, value: u64) -> Self {
+            Self {
+                level,
+                option,
+                value,
+            }
+        }
+
+        /// Builds an `IPPROTO_IP` membership request; the group travels big-endian in `value`.
+        pub const fn membership(option: u32, group: [u8; 4]) -> Self {
+            Self::new(IPPROTO_IP, option, u32::from_be_bytes(group) as u64)
+        }
+    }
+
+    /// One syscall inside a SYS_BATCH array; the kernel fills `result` with what the syscall
+    /// would have returned on its own.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct SyscallRecord {
+        pub number: u64,
+        pub args: [u64; 3],
+        pub result: i64,
+    }
+
+    impl SyscallRecord {
+        pub const fn new(number: u64, arg0: u64, arg1: u64, arg2: u64) -> Self {
+            Self {
+                number,
+                args: [arg0, arg1, arg2],
+                result: 0,
+            }
+        }
+    }
+
+    /// Fixed-capacity SYS_BATCH builder. Records run in push order; SYS_BATCH returns how many
+    /// ran, which is fewer than `len()` when one exits or sleeps the task or (with
+    /// `BATCH_STOP_ON_ERROR`) fails.
+    pub struct Batch {
+        records: [SyscallRecord; BATCH_MAX_RECORDS],
+        len: usize,
+    }
+
+    impl Batch {
+        pub const fn new() -> Self {
+            Self {
+                records: [SyscallRecord::new(0, 0, 0, 0); BATCH_MAX_RECORDS],
+                len: 0,
+            }
+        }
+
+        /// Queues a syscall and returns its index, or `None` when the batch is full.
+        pub fn push(&mut self, number: u64, arg0: u64, arg1: u64, arg2: u64) -> Option<usize> {
+            let index = self.len;
+            *self.records.get_mut(index)? = SyscallRecord::new(number, arg0, arg1, arg2);
+            self.len += 1;
+            Some(index)
+        }
+
+        pub const fn len(&self) -> usize {
+            self.len
+        }
+
+        pub const fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        pub fn clear(&mut self) {
+            self.len = 0;
+        }
+
+        /// Result of record `index` after `submit`.
+        pub fn result(&self, index: usize) -> Option<i64> {
+            self.records[..self.len]
+                .get(index)
+                .map(|record| record.result)
+        }
+
+        /// Issues SYS_BATCH through `syscall(number, arg0, arg1, arg2)` and returns its result.
+        pub fn submit(
+            &mut self,
+            flags: u64,
+            syscall: impl FnOnce(u64, u64, u64, u64) -> isize,
+        ) -> isize {
+            syscall(
+                SYS_BATCH,
+                self.records.as_mut_ptr() as u64,
+                self.len as u64,
+                flags,
+            )
+        }
+    }
+
+    impl Default for Batch {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// One event from SYS_INPUT_READ; `kind` is one of the `INPUT_KEYS`/`INPUT_BYTES`/
+    /// `INPUT_MOUSE` bits.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct InputRecord {
+        pub kind: u8,
+        pub pressed: u8,
+        /// Mouse buttons: bit 0 left, bit 1 right, bit 2 middle.
+        pub buttons: u8,
+        pub reserved0: u8,
+        /// Key code or text byte.
+        pub code: u16,
+        pub dx: i16,
+        pub dy: i16,
+        pub reserved1: [u8; 6],
+        /// PIT tick at which the event was queued.
+        pub tick: u64,
+    }
+
+    impl InputRecord {
+        pub const EMPTY: Self = Self {
+            kind: 0,
+            pressed: 0,
+            buttons: 0,
+            reserved0: 0,
+            code: 0,
+            dx: 0,
+            dy: 0,
+            reserved1: [0; 6],
+            tick: 0,
+        };
+
+        pub const fn key(code: u16, pressed: bool, tick: u64) -> Self {
+            Self {
+                kind: INPUT_KEYS,
+                pressed: pressed as u8,
+                code,
+                tick,
+                ..Self::EMPTY
+            }
+        }
+
+        pub const fn byte(byte: u8, tick: u64) -> Self {
+            Self {
+                kind: INPUT_BYTES,
+                code: byte as u16,
+                tick,
+                ..Self::EMPTY
+            }
+        }
+
+        pub const fn mouse(dx: i16, dy: i16, buttons: u8, tick: u64) -> Self {
+            Self {
+                kind: INPUT_MOUSE,
+                buttons,
+                dx,
+                dy,
+                tick,
+                ..Self::EMPTY
+            }
+        }
+    }
+
+    /// SURFACE_ATTACH request: `height` rows of `width` `0x00RRGGBB` pixels, `stride` pixels
+    /// apart, starting at `pixels` in the task's memory.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct SurfaceDesc {
+        pub window: u32,
+        pub width: u32,
+        pub height: u32,
+        pub stride: u32,
+        pub pixels: u64,
+    }
+
+    impl SurfaceDesc {
+        pub const fn new(window: u32, width: u32, height: u32, pixels: u64) -> Self {
+            Self {
+                window,
+                width,
+                height,
+                stride: width,
+                pixels,
+            }
+        }
+    }
+
+    /// SURFACE_DAMAGE request: the part of the attached surface that changed, in surface
+    /// pixels.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct SurfaceRect {
+        pub window: u32,
+        pub x: u32,
+        pub y: u32,
+        pub width: u32,
+        pub height: u32,
+    }
+
+    impl SurfaceRect {
+        pub const fn new(window: u32, x: u32, y: u32, width: u32, height: u32) -> Self {
+            Self {
+                window,
+                x,
+                y,
+                width,
+                height,
+            }
+        }
+    }
+
+    /// One SYS_POLL entry: the caller sets `fd` and `events`, the kernel fills `revents`.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct PollFd {
+        pub fd: u32,
+        pub events: u16,
+        pub revents: u16,
+    }
+
+    impl PollFd {
+        pub const fn new(fd: u64, events: u16) -> Self {
+            Self {
+                fd: fd as u32,
+                events,
+                revents: 0,
             }
         }
     }
@@ -88,7 +442,496 @@ pub mod syscall {
             SYS_SOCKET => "socket",
             SYS_SENDTO => "sendto",
             SYS_RECVFROM => "recvfrom",
+            SYS_SETSOCKOPT => "setsockopt",
+            SYS_BRK => "brk",
+            SYS_BATCH => "batch",
+            SYS_KILL => "kill",
+            SYS_SIGPENDING => "sigpending",
+            SYS_TLS_GET => "tls_get",
+            SYS_TLS_SET => "tls_set",
+            SYS_INPUT_SUBSCRIBE => "input_subscribe",
+            SYS_INPUT_READ => "input_read",
+            SYS_SURFACE => "surface",
+            SYS_POLL => "poll",
+            SYS_TIMER_SET => "timer_set",
+            SYS_CONNECT => "connect",
+            SYS_BIND => "bind",
+            SYS_LISTEN => "listen",
+            SYS_ACCEPT => "accept",
+            SYS_CLOSE => "close",
             _ => "unknown",
         }
     }
 }
+
+pub mod rng {
+    use super::syscall::{SYS_TLS_GET, SYS_TLS_SET, TLS_SLOT_RNG};
+
+    /// SplitMix64 finalizer; turns any seed (a pid, a tick count) into well-spread non-zero
+    /// xorshift state.
+    pub const fn mix(seed: u64) -> u64 {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        let z = z ^ (z >> 31);
+        if z == 0 { 0x9e37_79b9_7f4a_7c15 } else { z }
+    }
+
+    /// xorshift64*: returns the next state and the output drawn from it. Not cryptographic.
+    pub const fn step(state: u64) -> (u64, u64) {
+        let mut x = if state == 0 { mix(0) } else { state };
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        (x, x.wrapping_mul(0x2545_f491_4f6c_dd1d))
+    }
+
+    /// Per-task generator whose state lives in TLS slot `TLS_SLOT_RNG`, so library code can
+    /// draw numbers without a global. `syscall(number, arg0, arg1, arg2)` is the app's wrapper.
+    pub struct TlsRng;
+
+    impl TlsRng {
+        /// Next value, or the negative errno of the failing TLS syscall.
+        pub fn next_u64(
+            mut syscall: impl FnMut(u64, u64, u64, u64) -> isize,
+        ) -> Result<u64, isize> {
+            let mut state = 0u64;
+            let rc = syscall(SYS_TLS_GET, TLS_SLOT_RNG, &mut state as *mut u64 as u64, 0);
+            if rc < 0 {
+                return Err(rc);
+            }
+            let (state, value) = step(state);
+            let rc = syscall(SYS_TLS_SET, TLS_SLOT_RNG, state, 0);
+            if rc < 0 {
+                return Err(rc);
+            }
+            Ok(value)
+        }
+
+        /// Uniform value below `bound` (rejection sampling); `bound == 0` returns 0.
+        pub fn below(
+            bound: u64,
+            mut syscall: impl FnMut(u64, u64, u64, u64) -> isize,
+        ) -> Result<u64, isize> {
+            if bound == 0 {
+                return Ok(0);
+            }
+            let zone = u64::MAX - u64::MAX % bound;
+            loop {
+                let value = Self::next_u64(&mut syscall)?;
+                if value < zone {
+                    return Ok(value % bound);
+                }
+            }
+        }
+    }
+}
+
+pub mod heap {
+    use core::alloc::{GlobalAlloc, Layout};
+    use core::cell::UnsafeCell;
+    use core::ptr::null_mut;
+
+    /// The break grows in multiples of this so small allocations do not each cost a syscall.
+    pub const BRK_STEP: u64 = 4096;
+
+    /// Bump arena on top of a program break. `brk(addr)` follows SYS_BRK: it returns the new
+    /// break on success, the current break for `addr == 0`, and a negative errno on failure.
+    #[derive(Clone, Copy)]
+    pub struct BrkHeap {
+        start: u64,
+        next: u64,
+        end: u64,
+    }
+
+    impl BrkHeap {
+        pub const fn new() -> Self {
+            Self {
+                start: 0,
+                next: 0,
+                end: 0,
+            }
+        }
+
+        /// Bytes handed out since the first allocation, including alignment padding.
+        pub const fn used(&self) -> u64 {
+            self.next - self.start
+        }
+
+        /// Current program break as last reported by the kernel.
+        pub const fn brk(&self) -> u64 {
+            self.end
+        }
+
+        /// Returns the address of `size` bytes aligned to `align`, moving the break as needed.
+        /// Memory is never returned to the kernel; `None` means the break could not grow.
+        pub fn alloc(
+            &mut self,
+            size: u64,
+            align: u64,
+            mut brk: impl FnMut(u64) -> isize,
+        ) -> Option<u64> {
+            if self.end == 0 {
+                let current = u64::try_from(brk(0)).ok()?;
+                self.start = current;
+                self.next = current;
+                self.end = current;
+            }
+            let align = align.max(1);
+            let addr = self.next.checked_add(align - 1)? & !(align - 1);
+            let top = addr.checked_add(size)?;
+            if top > self.end {
+                let want = top.checked_add(BRK_STEP - 1)? & !(BRK_STEP - 1);
+                let granted = u64::try_from(brk(want)).ok()?;
+                if granted < top {
+                    return None;
+                }
+                self.end = granted;
+            }
+            self.next = top;
+            Some(addr)
+        }
+    }
+
+    impl Default for BrkHeap {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// `#[global_allocator]` for single-threaded apps; `brk` is the app's SYS_BRK wrapper.
+    pub struct BrkAllocator {
+        heap: UnsafeCell<BrkHeap>,
+        brk: fn(u64) -> isize,
+    }
+
+    impl BrkAllocator {
+        pub const fn new(brk: fn(u64) -> isize) -> Self {
+            Self {
+                heap: UnsafeCell::new(BrkHeap::new()),
+                brk,
+            }
+        }
+    }
+
+    // SAFETY: arrOSt user tasks are single-threaded, so the arena is never touched concurrently.
+    unsafe impl Sync for BrkAllocator {}
+
+    // SAFETY: `BrkHeap::alloc` returns aligned, non-overlapping ranges below the granted break.
+    unsafe impl GlobalAlloc for BrkAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            // SAFETY: see the `Sync` impl; no other reference to the arena is live.
+            let heap = unsafe { &mut *self.heap.get() };
+            heap.alloc(layout.size() as u64, layout.align() as u64, self.brk)
+                .map_or(null_mut(), |addr| addr as *mut u8)
+        }
+
+        unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+    }
+}
+
+pub mod args {
+    /// Most words one command line splits into.
+    pub const MAX_ARGS: usize = 32;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum ArgsError {
+        /// A `'` or `"` was still open at the end of the input.
+        UnterminatedQuote,
+        /// The input ended in an unescaped `\`.
+        TrailingBackslash,
+        TooManyArgs,
+        /// `buf` was shorter than the unescaped words; `input.len()` bytes always suffice.
+        BufferTooSmall,
+    }
+
+    impl ArgsError {
+        pub const fn as_str(self) -> &'static str {
+            match self {
+                Self::UnterminatedQuote => "unterminated_quote",
+                Self::TrailingBackslash => "trailing_backslash",
+                Self::TooManyArgs => "too_many_args",
+                Self::BufferTooSmall => "buffer_too_small",
+            }
+        }
+
+        /// What a continuation line is joined with: the trailing `\` is replaced by nothing
+        /// (it escaped the line break), an open quote keeps the line break. `None` when more
+        /// input cannot complete the command.
+        pub const fn continuation_joiner(self) -> Option<&'static str> {
+            match self {
+                Self::TrailingBackslash => Some(""),
+                Self::UnterminatedQuote => Some("\n"),
+                Self::TooManyArgs | Self::BufferTooSmall => None,
+            }
+        }
+    }
+
+    /// Words of one command line, unquoted and unescaped into a caller buffer with one space
+    /// between words, so `rest(i)` can hand out "everything from word `i` on" as one `&str`.
+    pub struct Args<'a> {
+        text: &'a str,
+        spans: [(usize, usize); MAX_ARGS],
+        count: usize,
+        /// Bit `i` is set when word `i` contained a quote or an escape.
+        quoted: u32,
+    }
+
+    impl<'a> Args<'a> {
+        pub const fn len(&self) -> usize {
+            self.count
+        }
+
+        pub const fn is_empty(&self) -> bool {
+            self.count == 0
+        }
+
+        pub fn get(&self, index: usize) -> Option<&'a str> {
+            let &(start, end) = self.spans[..self.count].get(index)?;
+            Some(&self.text[start..end])
+        }
+
+        /// True when word `index` was (partly) quoted or escaped, so `'>'` is text and not a
+        /// redirect.
+        pub const fn is_quoted(&self, index: usize) -> bool {
+            index < self.count && self.quoted & (1 << index) != 0
+        }
+
+        /// Words `index..` joined by single spaces; `None` past the last word.
+        pub fn rest(&self, index: usize) -> Option<&'a str> {
+            self.join(index..self.count).filter(|_| index < self.count)
+        }
+
+        /// Words in `range` joined by single spaces; empty for an empty range.
+        pub fn join(&self, range: core::ops::Range<usize>) -> Option<&'a str> {
+            if range.is_empty() {
+                return (range.start <= self.count).then_some("");
+            }
+            let (start, _) = *self.spans[..self.count].get(range.start)?;
+            let (_, end) = *self.spans[..self.count].get(range.end - 1)?;
+            Some(&self.text[start..end])
+        }
+
+        pub fn iter(&self) -> impl Iterator<Item = &'a str> + '_ {
+            (0..self.count).filter_map(|index| self.get(index))
+        }
+    }
+
+    struct Writer<'b> {
+        buf: &'b mut [u8],
+        len: usize,
+    }
+
+    impl Writer<'_> {
+        fn push(&mut self, ch: char) -> Result<(), ArgsError> {
+            let width = ch.len_utf8();
+            let slot = self
+                .buf
+                .get_mut(self.len..self.len + width)
+                .ok_or(ArgsError::BufferTooSmall)?;
+            ch.encode_utf8(slot);
+            self.len += width;
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Quote {
+        None,
+        Single,
+        Double,
+    }
+
+    /// `\n` and `\t` are a line feed and a tab; any other escaped character stands for itself.
+    const fn unescape(ch: char) -> char {
+        match ch {
+            'n' => '\n',
+            't' => '\t',
+            other => other,
+        }
+    }
+
+    /// Splits `input` on unquoted whitespace. `'...'` is literal, `"..."` and bare words take
+    /// backslash escapes, and quoted parts glue onto their neighbours (`a"b c"d` is one word).
+    pub fn split<'a>(input: &str, buf: &'a mut [u8]) -> Result<Args<'a>, ArgsError> {
+        let mut out = Writer { buf, len: 0 };
+        let mut spans = [(0, 0); MAX_ARGS];
+        let mut count = 0;
+        let mut quoted = 0u32;
+        let mut in_word = false;
+        let mut quote = Quote::None;
+        let mut chars = input.chars();
+
+        while let Some(ch) = chars.next() {
+            if quote == Quote::None && ch.is_whitespace() {
+                if in_word {
+                    spans[count - 1].1 = out.len;
+                    in_word = false;
+                }
+                continue;
+            }
+            if !in_word {
+                if count == MAX_ARGS {
+                    return Err(ArgsError::TooManyArgs);
+                }
+                if count > 0 {
+                    out.push(' ')?;
+                }
+                spans[count] = (out.len, out.len);
+                count += 1;
+                in_word = true;
+            }
+            let word_bit = 1 << (count - 1);
+            match (quote, ch) {
+                (Quote::None, '\'') => {
+                    quote = Quote::Single;
+                    quoted |= word_bit;
+                }
+                (Quote::None, '"') => {
+                    quote = Quote::Double;
+                    quoted |= word_bit;
+                }
+                (Quote::Single, '\'') | (Quote::Double, '"') => quote = Quote::None,
+                (Quote::None | Quote::Double, '\\') => {
+                    let Some(next) = chars.next() else {
+                        return Err(if quote == Quote::None {
+                            ArgsError::TrailingBackslash
+                        } else {
+                            ArgsError::UnterminatedQuote
+                        });
+                    };
+                    quoted |= word_bit;
+                    out.push(unescape(next))?;
+                }
+                _ => out.push(ch)?,
+            }
+        }
+        if quote != Quote::None {
+            return Err(ArgsError::UnterminatedQuote);
+        }
+        if in_word {
+            spans[count - 1].1 = out.len;
+        }
+        let Writer { buf, len } = out;
+        // Only whole chars of `input` and ASCII separators were copied.
+        let text = core::str::from_utf8(&buf[..len]).map_err(|_| ArgsError::BufferTooSmall)?;
+        Ok(Args {
+            text,
+            spans,
+            count,
+            quoted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::args::{self, ArgsError, MAX_ARGS};
+
+    fn split_err(input: &str) -> ArgsError {
+        let mut buf = [0u8; 128];
+        match args::split(input, &mut buf) {
+            Ok(args) => panic!("input {input:?} split into {} words", args.len()),
+            Err(error) => error,
+        }
+    }
+
+    fn assert_words(input: &str, expected: &[&str]) {
+        let mut buf = [0u8; 128];
+        let args = args::split(input, &mut buf)
+            .unwrap_or_else(|error| panic!("input {input:?} failed: {}", error.as_str()));
+        assert_eq!(args.len(), expected.len(), "input {input:?}");
+        for (word, expected) in args.iter().zip(expected) {
+            assert_eq!(word, *expected, "input {input:?}");
+        }
+    }
+
+    #[test]
+    fn splits_on_runs_of_whitespace() {
+        assert_words("", &[]);
+        assert_words("   ", &[]);
+        assert_words(
+            "udp send 10.0.2.2 9000 hi",
+            &["udp", "send", "10.0.2.2", "9000", "hi"],
+        );
+        assert_words("  a \t b  ", &["a", "b"]);
+    }
+
+    #[test]
+    fn quotes_keep_spaces_and_glue_to_neighbours() {
+        assert_words("send 'hello  world'", &["send", "hello  world"]);
+        assert_words("echo \"a > b\" > f", &["echo", "a > b", ">", "f"]);
+        assert_words("a\"b c\"d", &["ab cd"]);
+        assert_words("'' \"\"", &["", ""]);
+        assert_words("'it''s'", &["its"]);
+        assert_words("\"it's\" 'say \"hi\"'", &["it's", "say \"hi\""]);
+    }
+
+    #[test]
+    fn backslash_escapes() {
+        assert_words("a\\ b", &["a b"]);
+        assert_words("\\'x\\'", &["'x'"]);
+        assert_words("\"a\\\"b\\\\c\"", &["a\"b\\c"]);
+        assert_words("line\\nnext\\ttab", &["line\nnext\ttab"]);
+        // Single quotes are literal, backslashes included.
+        assert_words("'a\\nb'", &["a\\nb"]);
+        assert_words("caf\\é", &["café"]);
+    }
+
+    #[test]
+    fn incomplete_input_asks_for_continuation() {
+        assert_eq!(split_err("echo 'open"), ArgsError::UnterminatedQuote);
+        assert_eq!(split_err("echo \"open\\"), ArgsError::UnterminatedQuote);
+        assert_eq!(split_err("echo more\\"), ArgsError::TrailingBackslash);
+        assert_eq!(ArgsError::TrailingBackslash.continuation_joiner(), Some(""));
+        assert_eq!(
+            ArgsError::UnterminatedQuote.continuation_joiner(),
+            Some("\n")
+        );
+        assert_eq!(ArgsError::TooManyArgs.continuation_joiner(), None);
+        // An open quote's continuation keeps the line break; an escaped one is kept too.
+        assert_words("echo 'open\nquote'", &["echo", "open\nquote"]);
+        assert_words("echo \\\nhere", &["echo", "\nhere"]);
+    }
+
+    #[test]
+    fn quoted_flag_and_rest() {
+        let mut buf = [0u8; 64];
+        let args = args::split("echo  a   '>' > \"f\"", &mut buf).unwrap();
+        assert_eq!(args.len(), 5);
+        assert!(!args.is_quoted(0));
+        assert!(args.is_quoted(2));
+        assert!(!args.is_quoted(3));
+        assert!(args.is_quoted(4));
+        assert!(!args.is_quoted(9));
+        assert_eq!(args.rest(1), Some("a > > f"));
+        assert_eq!(args.rest(4), Some("f"));
+        assert_eq!(args.rest(5), None);
+        assert_eq!(args.join(1..3), Some("a >"));
+        assert_eq!(args.join(1..1), Some(""));
+        assert_eq!(args.join(4..6), None);
+        assert_eq!(args.get(5), None);
+    }
+
+    #[test]
+    fn limits() {
+        const FULL: &str = "a b c d e f g h i j k l m n o p q r s t u v w x y z 0 1 2 3 4 5";
+        let mut buf = [0u8; 128];
+        assert_eq!(
+            args::split(FULL, &mut buf).map(|args| args.len()),
+            Ok(MAX_ARGS)
+        );
+        assert_eq!(
+            split_err(concat!(
+                "a b c d e f g h i j k l m n o p q r s t u v w x y z 0 1 2 3 4 5",
+                " 6"
+            )),
+            ArgsError::TooManyArgs
+        );
+        let mut small = [0u8; 3];
+        assert!(matches!(
+            args::split("abcd", &mut small),
+            Err(ArgsError::BufferTooSmall)
+        ));
+    }
+}
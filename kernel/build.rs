@@ -51,8 +51,14 @@ fn main() {
     let stub = c_dir.join("doomgeneric_runner_stub.c");
     let shim_include = c_dir.join("freestanding_include");
 
-    let use_real_bridge =
-        doomgeneric_ready && header_present && keys_present && core_present && makefile_present;
+    // Kernels built without the `doom` feature never start the engine, so they link the stub.
+    let doom_feature = env::var_os("CARGO_FEATURE_DOOM").is_some();
+    let use_real_bridge = doom_feature
+        && doomgeneric_ready
+        && header_present
+        && keys_present
+        && core_present
+        && makefile_present;
     if use_real_bridge {
         let mut core_files = parse_makefile_sources(&makefile_soso, &include_dir);
         core_files.retain(|path| {
@@ -82,6 +88,8 @@ fn main() {
             println!("cargo:rerun-if-changed={}", file.display());
         }
         println!("cargo:rustc-cfg=arrost_doomgeneric_bridge");
+    } else if !doom_feature {
+        build.file(&stub);
     } else {
         build.file(&stub);
         println!("cargo:warning=building doomgeneric bridge stub (missing ready headers/env)");
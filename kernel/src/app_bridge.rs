@@ -0,0 +1,578 @@
+// kernel/src/app_bridge.rs: per-app bridge between native (C) apps and the kernel: video
+// surface submission, key and mouse queues, virtual-time timer callbacks, and audio push.
+use crate::audio::{self, AudioOwner};
+use crate::time;
+use core::cell::UnsafeCell;
+
+const KEY_QUEUE_CAP: usize = 256;
+const MOUSE_QUEUE_CAP: usize = 64;
+/// Title bytes an app may set, including room for a C terminator.
+pub const TITLE_CAP: usize = 64;
+/// Largest frame `submit_frame` scales down to the surface.
+pub const MAX_SOURCE_PIXELS: usize = 1024 * 768;
+const AUDIO_QUEUE_CAP_SAMPLES: u32 = 32_768;
+/// Most PCM frames one `submit_pcm16` call forwards.
+const MAX_PCM_FRAMES: usize = 4096;
+
+/// One kernel-owned `W` x `H` XRGB frame, row by row.
+type Surface<const W: usize, const H: usize> = [[u32; W]; H];
+
+const MOUSE_LEFT: u8 = 1 << 0;
+const MOUSE_RIGHT: u8 = 1 << 1;
+const MOUSE_MIDDLE: u8 = 1 << 2;
+
+/// One mouse report: relative motion and the buttons held after it.
+#[derive(Clone, Copy)]
+pub struct MouseEvent {
+    pub dx: i16,
+    pub dy: i16,
+    buttons: u8,
+}
+
+impl MouseEvent {
+    const EMPTY: Self = Self {
+        dx: 0,
+        dy: 0,
+        buttons: 0,
+    };
+
+    pub const fn new(dx: i16, dy: i16, left: bool, right: bool, middle: bool) -> Self {
+        let mut buttons = 0;
+        if left {
+            buttons |= MOUSE_LEFT;
+        }
+        if right {
+            buttons |= MOUSE_RIGHT;
+        }
+        if middle {
+            buttons |= MOUSE_MIDDLE;
+        }
+        Self { dx, dy, buttons }
+    }
+
+    pub const fn left(self) -> bool {
+        self.buttons & MOUSE_LEFT != 0
+    }
+
+    pub const fn right(self) -> bool {
+        self.buttons & MOUSE_RIGHT != 0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct AppStats {
+    pub draw_calls: u64,
+    pub nonzero_pixels: u32,
+    pub key_events: u64,
+    pub key_polls: u64,
+    pub key_dropped: u64,
+    pub mouse_events: u64,
+    pub mouse_dropped: u64,
+    pub sleep_calls: u64,
+    pub last_sleep_ms: u32,
+    pub audio_mix_calls: u64,
+    pub audio_samples: u64,
+    pub audio_queue_samples: u32,
+    pub audio_dropped_samples: u64,
+    pub title_len: usize,
+    pub has_frame: bool,
+    /// Frames presented by flipping surfaces, without a copy.
+    pub zero_copy_frames: u64,
+}
+
+struct BridgeState {
+    /// Index of the surface the app renders into; the other one is the front.
+    back: usize,
+    has_frame: bool,
+    zero_copy_frames: u64,
+    key_queue: [u16; KEY_QUEUE_CAP],
+    key_head: usize,
+    key_tail: usize,
+    key_events: u64,
+    key_polls: u64,
+    key_dropped: u64,
+    mouse_queue: [MouseEvent; MOUSE_QUEUE_CAP],
+    mouse_head: usize,
+    mouse_tail: usize,
+    mouse_events: u64,
+    mouse_dropped: u64,
+    draw_calls: u64,
+    last_nonzero_pixels: u32,
+    sleep_calls: u64,
+    last_sleep_ms: u32,
+    audio_mix_calls: u64,
+    audio_samples: u64,
+    audio_queue_samples: u32,
+    audio_dropped_samples: u64,
+    virtual_ms: u64,
+    title: [u8; TITLE_CAP],
+    title_len: usize,
+}
+
+impl BridgeState {
+    const fn new() -> Self {
+        Self {
+            back: 0,
+            has_frame: false,
+            zero_copy_frames: 0,
+            key_queue: [0; KEY_QUEUE_CAP],
+            key_head: 0,
+            key_tail: 0,
+            key_events: 0,
+            key_polls: 0,
+            key_dropped: 0,
+            mouse_queue: [MouseEvent::EMPTY; MOUSE_QUEUE_CAP],
+            mouse_head: 0,
+            mouse_tail: 0,
+            mouse_events: 0,
+            mouse_dropped: 0,
+            draw_calls: 0,
+            last_nonzero_pixels: 0,
+            sleep_calls: 0,
+            last_sleep_ms: 0,
+            audio_mix_calls: 0,
+            audio_samples: 0,
+            audio_queue_samples: 0,
+            audio_dropped_samples: 0,
+            virtual_ms: 0,
+            title: [0; TITLE_CAP],
+            title_len: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.has_frame = false;
+        self.zero_copy_frames = 0;
+        self.key_head = 0;
+        self.key_tail = 0;
+        self.key_events = 0;
+        self.key_polls = 0;
+        self.key_dropped = 0;
+        self.mouse_head = 0;
+        self.mouse_tail = 0;
+        self.mouse_events = 0;
+        self.mouse_dropped = 0;
+        self.draw_calls = 0;
+        self.last_nonzero_pixels = 0;
+        self.sleep_calls = 0;
+        self.last_sleep_ms = 0;
+        self.audio_mix_calls = 0;
+        self.audio_samples = 0;
+        self.audio_queue_samples = 0;
+        self.audio_dropped_samples = 0;
+        self.virtual_ms = current_tick_millis();
+        self.title_len = 0;
+    }
+
+    fn queue_next(index: usize) -> usize {
+        (index + 1) % KEY_QUEUE_CAP
+    }
+
+    /// Queues a key event; when the queue is full the oldest event is dropped.
+    fn queue_push(&mut self, key: u8, pressed: bool) {
+        let next = Self::queue_next(self.key_head);
+        if next == self.key_tail {
+            self.key_tail = Self::queue_next(self.key_tail);
+            self.key_dropped = self.key_dropped.saturating_add(1);
+        }
+        let encoded = u16::from(key) | (u16::from(u8::from(pressed)) << 8);
+        self.key_queue[self.key_head] = encoded;
+        self.key_head = next;
+        self.key_events = self.key_events.saturating_add(1);
+    }
+
+    fn queue_pop(&mut self) -> Option<(bool, u8)> {
+        if self.key_head == self.key_tail {
+            return None;
+        }
+        let encoded = self.key_queue[self.key_tail];
+        self.key_tail = Self::queue_next(self.key_tail);
+        let key = (encoded & 0x00ff) as u8;
+        let pressed = ((encoded >> 8) & 1) != 0;
+        Some((pressed, key))
+    }
+
+    /// Queues a mouse report; when the queue is full the oldest one is dropped.
+    fn mouse_push(&mut self, event: MouseEvent) {
+        let next = (self.mouse_head + 1) % MOUSE_QUEUE_CAP;
+        if next == self.mouse_tail {
+            self.mouse_tail = (self.mouse_tail + 1) % MOUSE_QUEUE_CAP;
+            self.mouse_dropped = self.mouse_dropped.saturating_add(1);
+        }
+        self.mouse_queue[self.mouse_head] = event;
+        self.mouse_head = next;
+        self.mouse_events = self.mouse_events.saturating_add(1);
+    }
+
+    fn mouse_pop(&mut self) -> Option<MouseEvent> {
+        if self.mouse_head == self.mouse_tail {
+            return None;
+        }
+        let event = self.mouse_queue[self.mouse_tail];
+        self.mouse_tail = (self.mouse_tail + 1) % MOUSE_QUEUE_CAP;
+        Some(event)
+    }
+
+    /// Brings the virtual clock up to real time if it fell behind.
+    fn catch_up_clock(&mut self) {
+        let real_millis = current_tick_millis();
+        if self.virtual_ms < real_millis {
+            self.virtual_ms = real_millis;
+        }
+    }
+
+    fn stats(&self) -> AppStats {
+        AppStats {
+            draw_calls: self.draw_calls,
+            nonzero_pixels: self.last_nonzero_pixels,
+            key_events: self.key_events,
+            key_polls: self.key_polls,
+            key_dropped: self.key_dropped,
+            mouse_events: self.mouse_events,
+            mouse_dropped: self.mouse_dropped,
+            sleep_calls: self.sleep_calls,
+            last_sleep_ms: self.last_sleep_ms,
+            audio_mix_calls: self.audio_mix_calls,
+            audio_samples: self.audio_samples,
+            audio_queue_samples: self.audio_queue_samples,
+            audio_dropped_samples: self.audio_dropped_samples,
+            title_len: self.title_len,
+            has_frame: self.has_frame,
+            zero_copy_frames: self.zero_copy_frames,
+        }
+    }
+
+    /// Makes the back surface the new front and records the frame.
+    fn present(&mut self, nonzero_pixels: u32) {
+        self.back ^= 1;
+        self.has_frame = true;
+        self.draw_calls = self.draw_calls.saturating_add(1);
+        self.last_nonzero_pixels = nonzero_pixels;
+    }
+}
+
+/// One native app's bridge, with `W` x `H` surfaces. Each app owns a `static` instance; its C
+/// port's callbacks forward to it, and gfx/audio/input read from it.
+pub struct AppBridge<const W: usize, const H: usize> {
+    audio_owner: AudioOwner,
+    state: UnsafeCell<BridgeState>,
+    /// Double-buffered frames: the app renders straight into the back surface, `submit_frame`
+    /// flips, and gfx reads the front one in place.
+    surfaces: UnsafeCell<[Surface<W, H>; 2]>,
+}
+
+// SAFETY: bridges are only touched from the single kernel thread: the app writes the back
+// surface only while it runs (inside its tick), and gfx reads the front surface only outside it.
+unsafe impl<const W: usize, const H: usize> Sync for AppBridge<W, H> {}
+
+impl<const W: usize, const H: usize> AppBridge<W, H> {
+    pub const PIXELS: usize = W * H;
+
+    pub const fn new(audio_owner: AudioOwner) -> Self {
+        Self {
+            audio_owner,
+            state: UnsafeCell::new(BridgeState::new()),
+            surfaces: UnsafeCell::new([[[0; W]; H]; 2]),
+        }
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut BridgeState) -> R) -> R {
+        // SAFETY: single kernel thread, and no caller re-enters the bridge from inside `f`.
+        unsafe { f(&mut *self.state.get()) }
+    }
+
+    /// Clears frames, queued input, counters, and the title; restarts the virtual clock at now.
+    pub fn reset(&self) {
+        self.with_state(BridgeState::reset);
+    }
+
+    pub fn stats(&self) -> AppStats {
+        self.with_state(|state| state.stats())
+    }
+
+    pub fn has_frame(&self) -> bool {
+        self.with_state(|state| state.has_frame)
+    }
+
+    /// Drops the current frame without touching the surfaces, e.g. when the app reinitializes.
+    pub fn clear_frame(&self) {
+        self.with_state(|state| state.has_frame = false);
+    }
+
+    /// Runs `f` on the last presented frame, `W * H` pixels read in place from the front
+    /// surface.
+    pub fn with_front_surface<R>(&self, f: impl FnOnce(&[u32]) -> R) -> R {
+        let front = self.with_state(|state| state.back ^ 1);
+        // SAFETY: the app only writes the back surface, and only while it runs, which cannot
+        // happen while `f` does on the single kernel thread.
+        unsafe { f((*self.surfaces.get())[front].as_flattened()) }
+    }
+
+    /// Surface the app should render its next frame into; it changes after every present.
+    pub fn back_surface(&self) -> *mut u32 {
+        let back = self.with_state(|state| state.back);
+        self.surface_ptr(back)
+    }
+
+    fn surface_ptr(&self, index: usize) -> *mut u32 {
+        // SAFETY: only the address is taken; no reference to the surface is created.
+        unsafe { (&raw mut (*self.surfaces.get())[index]).cast() }
+    }
+
+    /// Presents a `width` x `height` XRGB frame. The back surface itself is presented with a
+    /// flip; any other buffer is copied, or bilinear-scaled when its size differs.
+    ///
+    /// # Safety
+    ///
+    /// A non-null `frame` must point to `width * height` readable pixels.
+    pub unsafe fn submit_frame(&self, frame: *const u32, width: u32, height: u32) {
+        if frame.is_null() || width == 0 || height == 0 {
+            return;
+        }
+        let width = width as usize;
+        let height = height as usize;
+        let Some(source_len) = width.checked_mul(height) else {
+            return;
+        };
+        if source_len > MAX_SOURCE_PIXELS {
+            return;
+        }
+
+        let back = self.with_state(|state| state.back);
+        let back_ptr = self.surface_ptr(back);
+        if frame == back_ptr.cast_const() && width == W && height == H {
+            // Rendered in place: the frame already is the back surface, so presenting it is
+            // a flip. Gfx masks the X byte when it reads.
+            // SAFETY: `back_ptr` is a live surface of `W * H` pixels.
+            let pixels = unsafe { core::slice::from_raw_parts(frame, Self::PIXELS) };
+            let nonzero_pixels = pixels
+                .iter()
+                .filter(|pixel| **pixel & 0x00FF_FFFF != 0)
+                .count() as u32;
+            self.with_state(|state| {
+                state.zero_copy_frames = state.zero_copy_frames.saturating_add(1);
+                state.present(nonzero_pixels);
+            });
+            return;
+        }
+
+        // SAFETY: the caller guarantees `width * height` pixels, and a foreign frame cannot
+        // overlap the back surface (checked above for the one that can).
+        let source = unsafe { core::slice::from_raw_parts(frame, source_len) };
+        // SAFETY: the back surface is only written here and by the app, which is blocked in
+        // this call.
+        let target = unsafe { (*self.surfaces.get())[back].as_flattened_mut() };
+        let nonzero_pixels = if width == W && height == H {
+            copy_frame(source, target)
+        } else {
+            scale_frame(source, width, height, target, W, H)
+        };
+        self.with_state(|state| state.present(nonzero_pixels));
+    }
+
+    /// Queues an app key code; the oldest event is dropped when the queue is full.
+    pub fn push_key(&self, key: u8, pressed: bool) {
+        self.with_state(|state| state.queue_push(key, pressed));
+    }
+
+    /// Next `(pressed, key)` event; every call counts as a key poll.
+    pub fn pop_key(&self) -> Option<(bool, u8)> {
+        self.with_state(|state| {
+            state.key_polls = state.key_polls.saturating_add(1);
+            state.queue_pop()
+        })
+    }
+
+    /// Queues a mouse report; the oldest one is dropped when the queue is full.
+    pub fn push_mouse(&self, event: MouseEvent) {
+        self.with_state(|state| state.mouse_push(event));
+    }
+
+    pub fn pop_mouse(&self) -> Option<MouseEvent> {
+        self.with_state(BridgeState::mouse_pop)
+    }
+
+    /// Drops queued mouse reports, e.g. when input capture ends.
+    pub fn clear_mouse(&self) {
+        self.with_state(|state| state.mouse_tail = state.mouse_head);
+    }
+
+    /// App clock in ms: real PIT time, pushed ahead by `sleep_ms` so a busy-waiting app keeps
+    /// moving between ticks.
+    pub fn ticks_ms(&self) -> u32 {
+        self.with_state(|state| {
+            state.catch_up_clock();
+            state.virtual_ms.min(u64::from(u32::MAX)) as u32
+        })
+    }
+
+    /// Records a sleep request and advances the app clock instead of blocking the kernel.
+    pub fn sleep_ms(&self, ms: u32) {
+        self.with_state(|state| {
+            state.sleep_calls = state.sleep_calls.saturating_add(1);
+            state.last_sleep_ms = ms;
+            state.catch_up_clock();
+            state.virtual_ms = state.virtual_ms.saturating_add(u64::from(ms.max(1)));
+        });
+    }
+
+    /// Accounts `samples` the app mixed into its software queue; what does not fit is dropped.
+    pub fn audio_mix(&self, samples: u32) {
+        self.with_state(|state| {
+            state.audio_mix_calls = state.audio_mix_calls.saturating_add(1);
+            state.audio_samples = state.audio_samples.saturating_add(u64::from(samples));
+            if samples > 0 {
+                let free = AUDIO_QUEUE_CAP_SAMPLES.saturating_sub(state.audio_queue_samples);
+                let queued = samples.min(free);
+                state.audio_queue_samples = state.audio_queue_samples.saturating_add(queued);
+                let dropped = samples.saturating_sub(queued);
+                state.audio_dropped_samples = state
+                    .audio_dropped_samples
+                    .saturating_add(u64::from(dropped));
+            }
+        });
+    }
+
+    /// Drains `samples` from the software queue as the audio device plays them.
+    pub fn consume_audio_samples(&self, samples: u32) {
+        if samples == 0 {
+            return;
+        }
+        self.with_state(|state| {
+            let drained = samples.min(state.audio_queue_samples);
+            state.audio_queue_samples -= drained;
+        });
+    }
+
+    /// Pushes interleaved PCM to the audio device under the app's route owner; at most
+    /// 4096 frames and 2 channels per call. Returns the frames accepted.
+    pub fn submit_pcm16(&self, samples: &[i16], sample_rate: u32, channels: u8) -> usize {
+        let channels = channels.clamp(1, 2);
+        let frames = (samples.len() / usize::from(channels)).min(MAX_PCM_FRAMES);
+        if frames == 0 {
+            return 0;
+        }
+        let pcm = &samples[..frames * usize::from(channels)];
+        audio::submit_pcm_i16(self.audio_owner, pcm, sample_rate, channels)
+    }
+
+    /// Stores up to 63 bytes of the app's window title.
+    pub fn set_title(&self, title: &[u8]) {
+        self.with_state(|state| {
+            let len = title.len().min(TITLE_CAP - 1);
+            state.title[..len].copy_from_slice(&title[..len]);
+            state.title_len = len;
+        });
+    }
+}
+
+fn current_tick_millis() -> u64 {
+    time::ticks().saturating_mul(10)
+}
+
+/// Real PIT time in ms, shared by every app.
+pub fn realtime_ms() -> u32 {
+    current_tick_millis().min(u64::from(u32::MAX)) as u32
+}
+
+fn copy_frame(source: &[u32], target: &mut [u32]) -> u32 {
+    let mut nonzero_pixels = 0u32;
+    for (index, pixel) in source.iter().take(target.len()).enumerate() {
+        let rgb = *pixel & 0x00FF_FFFF;
+        if rgb != 0 {
+            nonzero_pixels = nonzero_pixels.saturating_add(1);
+        }
+        target[index] = rgb;
+    }
+    nonzero_pixels
+}
+
+/// Bilinear scale of a `width` x `height` frame to the `target_w` x `target_h` `target`.
+fn scale_frame(
+    source: &[u32],
+    width: usize,
+    height: usize,
+    target: &mut [u32],
+    target_w: usize,
+    target_h: usize,
+) -> u32 {
+    let mut nonzero_pixels = 0u32;
+    for y in 0..target_h {
+        let sy_fp = if target_h > 1 {
+            ((y as u64)
+                .saturating_mul((height.saturating_sub(1)) as u64)
+                .saturating_mul(1u64 << 16)
+                / ((target_h - 1) as u64)) as u32
+        } else {
+            0
+        };
+        let y0 = ((sy_fp >> 16) as usize).min(height.saturating_sub(1));
+        let y1 = (y0 + 1).min(height.saturating_sub(1));
+        let wy = sy_fp & 0xFFFF;
+        for x in 0..target_w {
+            let sx_fp = if target_w > 1 {
+                ((x as u64)
+                    .saturating_mul((width.saturating_sub(1)) as u64)
+                    .saturating_mul(1u64 << 16)
+                    / ((target_w - 1) as u64)) as u32
+            } else {
+                0
+            };
+            let x0 = ((sx_fp >> 16) as usize).min(width.saturating_sub(1));
+            let x1 = (x0 + 1).min(width.saturating_sub(1));
+            let wx = sx_fp & 0xFFFF;
+
+            let c00 = source[y0.saturating_mul(width).saturating_add(x0)] & 0x00FF_FFFF;
+            let c10 = source[y0.saturating_mul(width).saturating_add(x1)] & 0x00FF_FFFF;
+            let c01 = source[y1.saturating_mul(width).saturating_add(x0)] & 0x00FF_FFFF;
+            let c11 = source[y1.saturating_mul(width).saturating_add(x1)] & 0x00FF_FFFF;
+
+            let r = bilinear_channel(
+                ((c00 >> 16) & 0xFF) as u8,
+                ((c10 >> 16) & 0xFF) as u8,
+                ((c01 >> 16) & 0xFF) as u8,
+                ((c11 >> 16) & 0xFF) as u8,
+                wx,
+                wy,
+            );
+            let g = bilinear_channel(
+                ((c00 >> 8) & 0xFF) as u8,
+                ((c10 >> 8) & 0xFF) as u8,
+                ((c01 >> 8) & 0xFF) as u8,
+                ((c11 >> 8) & 0xFF) as u8,
+                wx,
+                wy,
+            );
+            let b = bilinear_channel(
+                (c00 & 0xFF) as u8,
+                (c10 & 0xFF) as u8,
+                (c01 & 0xFF) as u8,
+                (c11 & 0xFF) as u8,
+                wx,
+                wy,
+            );
+            let rgb = ((r as u32) << 16) | ((g as u32) << 8) | (b as u32);
+            if rgb != 0 {
+                nonzero_pixels = nonzero_pixels.saturating_add(1);
+            }
+            target[y * target_w + x] = rgb;
+        }
+    }
+    nonzero_pixels
+}
+
+fn bilinear_channel(c00: u8, c10: u8, c01: u8, c11: u8, wx: u32, wy: u32) -> u8 {
+    let one = 1u64 << 16;
+    let inv_wx = one.saturating_sub(wx as u64);
+    let inv_wy = one.saturating_sub(wy as u64);
+
+    let top = ((u64::from(c00).saturating_mul(inv_wx) + u64::from(c10).saturating_mul(wx as u64))
+        .saturating_add(1u64 << 15))
+        >> 16;
+    let bottom = ((u64::from(c01).saturating_mul(inv_wx)
+        + u64::from(c11).saturating_mul(wx as u64))
+    .saturating_add(1u64 << 15))
+        >> 16;
+    (((top.saturating_mul(inv_wy) + bottom.saturating_mul(wy as u64)).saturating_add(1u64 << 15))
+        >> 16) as u8
+}
@@ -0,0 +1,332 @@
+// kernel/src/arch/x86_64/cpuid.rs: CPUID feature detection, read once at boot, for gating optional paths.
+use super::entropy;
+use crate::serial;
+use core::arch::x86_64::{__cpuid, __cpuid_count, CpuidResult};
+use core::cell::UnsafeCell;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Fpu,
+    Tsc,
+    Apic,
+    Fxsr,
+    Sse,
+    Sse2,
+    Sse3,
+    Ssse3,
+    Sse41,
+    Sse42,
+    Popcnt,
+    Xsave,
+    Osxsave,
+    Avx,
+    Avx2,
+    Rdrand,
+    Rdseed,
+    X2apic,
+    TscDeadline,
+    Rdtscp,
+    InvariantTsc,
+    Nx,
+    Page1Gb,
+    Hypervisor,
+}
+
+impl Feature {
+    pub const ALL: [Self; 24] = [
+        Self::Fpu,
+        Self::Tsc,
+        Self::Apic,
+        Self::Fxsr,
+        Self::Sse,
+        Self::Sse2,
+        Self::Sse3,
+        Self::Ssse3,
+        Self::Sse41,
+        Self::Sse42,
+        Self::Popcnt,
+        Self::Xsave,
+        Self::Osxsave,
+        Self::Avx,
+        Self::Avx2,
+        Self::Rdrand,
+        Self::Rdseed,
+        Self::X2apic,
+        Self::TscDeadline,
+        Self::Rdtscp,
+        Self::InvariantTsc,
+        Self::Nx,
+        Self::Page1Gb,
+        Self::Hypervisor,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Fpu => "fpu",
+            Self::Tsc => "tsc",
+            Self::Apic => "apic",
+            Self::Fxsr => "fxsr",
+            Self::Sse => "sse",
+            Self::Sse2 => "sse2",
+            Self::Sse3 => "sse3",
+            Self::Ssse3 => "ssse3",
+            Self::Sse41 => "sse4.1",
+            Self::Sse42 => "sse4.2",
+            Self::Popcnt => "popcnt",
+            Self::Xsave => "xsave",
+            Self::Osxsave => "osxsave",
+            Self::Avx => "avx",
+            Self::Avx2 => "avx2",
+            Self::Rdrand => "rdrand",
+            Self::Rdseed => "rdseed",
+            Self::X2apic => "x2apic",
+            Self::TscDeadline => "tsc_deadline",
+            Self::Rdtscp => "rdtscp",
+            Self::InvariantTsc => "invariant_tsc",
+            Self::Nx => "nx",
+            Self::Page1Gb => "1g_pages",
+            Self::Hypervisor => "hypervisor",
+        }
+    }
+
+    /// (leaf, register, bit) the feature is reported in; register 0..=3 is eax, ebx, ecx, edx.
+    const fn location(self) -> (u32, u8, u8) {
+        match self {
+            Self::Fpu => (1, 3, 0),
+            Self::Tsc => (1, 3, 4),
+            Self::Apic => (1, 3, 9),
+            Self::Fxsr => (1, 3, 24),
+            Self::Sse => (1, 3, 25),
+            Self::Sse2 => (1, 3, 26),
+            Self::Sse3 => (1, 2, 0),
+            Self::Ssse3 => (1, 2, 9),
+            Self::Sse41 => (1, 2, 19),
+            Self::Sse42 => (1, 2, 20),
+            Self::X2apic => (1, 2, 21),
+            Self::Popcnt => (1, 2, 23),
+            Self::TscDeadline => (1, 2, 24),
+            Self::Xsave => (1, 2, 26),
+            Self::Osxsave => (1, 2, 27),
+            Self::Avx => (1, 2, 28),
+            Self::Rdrand => (1, 2, 30),
+            Self::Hypervisor => (1, 2, 31),
+            Self::Avx2 => (7, 1, 5),
+            Self::Rdseed => (7, 1, 18),
+            Self::Nx => (0x8000_0001, 3, 20),
+            Self::Page1Gb => (0x8000_0001, 3, 26),
+            Self::Rdtscp => (0x8000_0001, 3, 27),
+            Self::InvariantTsc => (0x8000_0007, 3, 8),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CpuInfo {
+    vendor: [u8; 12],
+    brand: [u8; 48],
+    pub family: u32,
+    pub model: u32,
+    pub stepping: u32,
+    pub max_leaf: u32,
+    pub max_extended_leaf: u32,
+    features: u32,
+}
+
+impl CpuInfo {
+    const fn empty() -> Self {
+        Self {
+            vendor: [0; 12],
+            brand: [0; 48],
+            family: 0,
+            model: 0,
+            stepping: 0,
+            max_leaf: 0,
+            max_extended_leaf: 0,
+            features: 0,
+        }
+    }
+
+    pub fn has(&self, feature: Feature) -> bool {
+        self.features & (1 << feature as u32) != 0
+    }
+
+    pub fn vendor(&self) -> &str {
+        core::str::from_utf8(&self.vendor).unwrap_or("unknown")
+    }
+
+    pub fn brand(&self) -> &str {
+        let len = self
+            .brand
+            .iter()
+            .position(|byte| *byte == 0)
+            .unwrap_or(self.brand.len());
+        core::str::from_utf8(&self.brand[..len])
+            .map(str::trim)
+            .unwrap_or("")
+    }
+
+    /// The TSC ticks at a fixed rate, so cycle counts convert to wall time. Hypervisors
+    /// present a fixed-rate TSC even when they do not advertise the invariant bit.
+    pub fn tsc_is_stable(&self) -> bool {
+        self.has(Feature::Tsc) && (self.has(Feature::InvariantTsc) || self.has(Feature::Hypervisor))
+    }
+}
+
+struct CpuInfoCell(UnsafeCell<CpuInfo>);
+
+// SAFETY: written once by `init` during single-threaded boot, read-only afterwards.
+unsafe impl Sync for CpuInfoCell {}
+
+static CPU_INFO: CpuInfoCell = CpuInfoCell(UnsafeCell::new(CpuInfo::empty()));
+
+fn register(result: CpuidResult, index: u8) -> u32 {
+    match index {
+        0 => result.eax,
+        1 => result.ebx,
+        2 => result.ecx,
+        _ => result.edx,
+    }
+}
+
+fn detect() -> CpuInfo {
+    let mut info = CpuInfo::empty();
+    let leaf0 = __cpuid(0);
+    info.max_leaf = leaf0.eax;
+    for (chunk, value) in info
+        .vendor
+        .as_chunks_mut::<4>()
+        .0
+        .iter_mut()
+        .zip([leaf0.ebx, leaf0.edx, leaf0.ecx])
+    {
+        *chunk = value.to_le_bytes();
+    }
+    info.max_extended_leaf = __cpuid(0x8000_0000).eax;
+
+    let leaf1 = __cpuid(1);
+    let base_family = (leaf1.eax >> 8) & 0xf;
+    let base_model = (leaf1.eax >> 4) & 0xf;
+    info.stepping = leaf1.eax & 0xf;
+    info.family = if base_family == 0xf {
+        base_family + ((leaf1.eax >> 20) & 0xff)
+    } else {
+        base_family
+    };
+    info.model = if base_family == 0x6 || base_family == 0xf {
+        base_model | (((leaf1.eax >> 16) & 0xf) << 4)
+    } else {
+        base_model
+    };
+
+    for feature in Feature::ALL {
+        let (leaf, index, bit) = feature.location();
+        let supported = if leaf >= 0x8000_0000 {
+            leaf <= info.max_extended_leaf
+        } else {
+            leaf <= info.max_leaf
+        };
+        if !supported {
+            continue;
+        }
+        let result = if leaf == 7 {
+            __cpuid_count(7, 0)
+        } else {
+            __cpuid(leaf)
+        };
+        if register(result, index) & (1 << bit) != 0 {
+            info.features |= 1 << feature as u32;
+        }
+    }
+
+    if info.max_extended_leaf >= 0x8000_0004 {
+        for (chunk, leaf) in info
+            .brand
+            .as_chunks_mut::<16>()
+            .0
+            .iter_mut()
+            .zip(0x8000_0002u32..)
+        {
+            let result = __cpuid(leaf);
+            for (bytes, value) in chunk
+                .as_chunks_mut::<4>()
+                .0
+                .iter_mut()
+                .zip([result.eax, result.ebx, result.ecx, result.edx])
+            {
+                *bytes = value.to_le_bytes();
+            }
+        }
+    }
+    info
+}
+
+pub fn init() -> CpuInfo {
+    let info = detect();
+    // SAFETY: called once during single-threaded boot before any reader.
+    unsafe { *CPU_INFO.0.get() = info };
+    info
+}
+
+pub fn info() -> CpuInfo {
+    // SAFETY: the cell is only written by `init` during boot.
+    unsafe { *CPU_INFO.0.get() }
+}
+
+pub fn has(feature: Feature) -> bool {
+    info().has(feature)
+}
+
+fn write_feature_list(info: &CpuInfo) {
+    let mut first = true;
+    for feature in Feature::ALL
+        .into_iter()
+        .filter(|feature| info.has(*feature))
+    {
+        if !first {
+            serial::write_str(",");
+        }
+        serial::write_str(feature.as_str());
+        first = false;
+    }
+}
+
+/// One-line boot summary: `CPU: vendor=.. family=.. model=.. stepping=.. features=a,b,..`.
+pub fn log_boot_line(info: &CpuInfo) {
+    serial::write_fmt(format_args!(
+        "CPU: vendor={} family={:#x} model={:#x} stepping={} tsc_stable={} entropy={} features=",
+        info.vendor(),
+        info.family,
+        info.model,
+        info.stepping,
+        info.tsc_is_stable(),
+        entropy::source().as_str()
+    ));
+    write_feature_list(info);
+    serial::write_str("\n");
+}
+
+pub fn log_features() {
+    let info = info();
+    serial::write_fmt(format_args!(
+        "cpu: vendor={} brand=\"{}\" family={:#x} model={:#x} stepping={} max_leaf={:#x} max_ext_leaf={:#x}\n",
+        info.vendor(),
+        info.brand(),
+        info.family,
+        info.model,
+        info.stepping,
+        info.max_leaf,
+        info.max_extended_leaf
+    ));
+    serial::write_fmt(format_args!(
+        "cpu: tsc_stable={} entropy={}\n",
+        info.tsc_is_stable(),
+        entropy::source().as_str()
+    ));
+    for feature in Feature::ALL {
+        serial::write_fmt(format_args!(
+            "cpu: {:<14} {}\n",
+            feature.as_str(),
+            if info.has(feature) { "yes" } else { "no" }
+        ));
+    }
+}
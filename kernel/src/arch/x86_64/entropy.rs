@@ -0,0 +1,67 @@
+// kernel/src/arch/x86_64/entropy.rs: non-cryptographic seed source for ids and sequence numbers.
+use super::cpuid::{self, Feature};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// RDRAND can transiently run dry; Intel recommends ten retries before giving up.
+const RDRAND_RETRIES: usize = 10;
+
+/// Fallback generator state, stirred with the TSC on every draw.
+static FALLBACK_STATE: AtomicU64 = AtomicU64::new(0x853c_49e6_748f_ea9b);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Rdrand,
+    Tsc,
+}
+
+impl Source {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Rdrand => "rdrand",
+            Self::Tsc => "tsc",
+        }
+    }
+}
+
+pub fn source() -> Source {
+    if cpuid::has(Feature::Rdrand) {
+        Source::Rdrand
+    } else {
+        Source::Tsc
+    }
+}
+
+pub fn next_u64() -> u64 {
+    if source() == Source::Rdrand {
+        // SAFETY: CPUID reported RDRAND support.
+        if let Some(value) = unsafe { rdrand64() } {
+            return value;
+        }
+    }
+    tsc_mix()
+}
+
+#[target_feature(enable = "rdrand")]
+unsafe fn rdrand64() -> Option<u64> {
+    let mut value = 0u64;
+    for _ in 0..RDRAND_RETRIES {
+        if core::arch::x86_64::_rdrand64_step(&mut value) == 1 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// SplitMix64 over a counter perturbed by the TSC: unpredictable enough for DHCP xids and
+/// TCP initial sequence numbers on a single-user kernel, not for anything secret.
+fn tsc_mix() -> u64 {
+    // SAFETY: RDTSC is available on every x86_64 CPU and only reads the timestamp counter.
+    let tsc = unsafe { core::arch::x86_64::_rdtsc() };
+    let state = FALLBACK_STATE
+        .fetch_add(0x9e37_79b9_7f4a_7c15 ^ tsc, Ordering::Relaxed)
+        .wrapping_add(tsc);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,225 @@
+// kernel/src/arch/x86_64/fpu.rs: x87/SSE enablement and per-context XSAVE/FXSAVE state areas.
+// Rust kernel code is soft-float; the C doom code is built for baseline x86-64 and uses SSE, so
+// its register state must be set up explicitly and kept apart from other contexts.
+use super::cpuid::{self, Feature};
+use crate::serial;
+use core::arch::asm;
+use core::arch::x86_64::__cpuid_count;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::registers::control::{Cr0, Cr0Flags, Cr4, Cr4Flags};
+use x86_64::registers::xcontrol::{XCr0, XCr0Flags};
+
+/// Room for x87 + SSE XSAVE state (576 bytes) with headroom; FXSAVE needs 512.
+pub const FPU_AREA_BYTES: usize = 1024;
+const FXSAVE_BYTES: usize = 512;
+/// Power-on MXCSR: all SIMD exceptions masked, round to nearest.
+const MXCSR_DEFAULT: u32 = 0x1f80;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FpuMode {
+    /// No usable SSE; state areas are left untouched.
+    Off,
+    Fxsave,
+    Xsave,
+}
+
+impl FpuMode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Fxsave => "fxsave",
+            Self::Xsave => "xsave",
+        }
+    }
+}
+
+/// One context's saved x87/SSE registers, in the layout FXSAVE/XSAVE expect.
+#[derive(Clone, Copy)]
+#[repr(C, align(64))]
+pub struct FpuArea([u8; FPU_AREA_BYTES]);
+
+impl FpuArea {
+    /// Placeholder until `clean()` is available; never restored before being overwritten.
+    pub const ZERO: Self = Self([0; FPU_AREA_BYTES]);
+}
+
+#[derive(Clone, Copy)]
+pub struct FpuReport {
+    pub mode: FpuMode,
+    pub xcr0: u64,
+    pub area_bytes: usize,
+}
+
+struct FpuState {
+    mode: FpuMode,
+    xcr0: u64,
+    area_bytes: usize,
+    clean: FpuArea,
+}
+
+struct FpuCell(UnsafeCell<FpuState>);
+
+// SAFETY: written once by `init` during single-threaded boot, read-only afterwards.
+unsafe impl Sync for FpuCell {}
+
+static FPU: FpuCell = FpuCell(UnsafeCell::new(FpuState {
+    mode: FpuMode::Off,
+    xcr0: 0,
+    area_bytes: 0,
+    clean: FpuArea::ZERO,
+}));
+static SWITCHES: AtomicU64 = AtomicU64::new(0);
+
+fn state() -> &'static FpuState {
+    // SAFETY: the cell is only written by `init` during boot.
+    unsafe { &*FPU.0.get() }
+}
+
+/// Enables x87/SSE (CR0.EM off, MP/NE on, CR4.OSFXSR/OSXMMEXCPT on, plus OSXSAVE and XCR0 when
+/// XSAVE exists), resets the unit and captures the clean state new contexts start from.
+/// UEFI usually leaves SSE on already; this makes it explicit and not firmware-dependent.
+pub fn init() -> FpuReport {
+    let info = cpuid::info();
+    let mode = if !(info.has(Feature::Fpu) && info.has(Feature::Sse) && info.has(Feature::Fxsr)) {
+        FpuMode::Off
+    } else if info.has(Feature::Xsave) {
+        FpuMode::Xsave
+    } else {
+        FpuMode::Fxsave
+    };
+    if mode == FpuMode::Off {
+        return report();
+    }
+
+    // SAFETY: CPUID reported x87, SSE and FXSR; these bits only change how FPU/SSE
+    // instructions and #XM exceptions behave, and no FPU state is live yet.
+    unsafe {
+        Cr0::update(|flags| {
+            flags.remove(Cr0Flags::EMULATE_COPROCESSOR | Cr0Flags::TASK_SWITCHED);
+            flags.insert(Cr0Flags::MONITOR_COPROCESSOR | Cr0Flags::NUMERIC_ERROR);
+        });
+        Cr4::update(|flags| {
+            flags.insert(Cr4Flags::OSFXSR | Cr4Flags::OSXMMEXCPT_ENABLE);
+            if mode == FpuMode::Xsave {
+                flags.insert(Cr4Flags::OSXSAVE);
+            }
+        });
+    }
+
+    let (xcr0, area_bytes) = if mode == FpuMode::Xsave {
+        // x87 + SSE is all the C code uses; AVX state would only grow every area.
+        // SAFETY: CR4.OSXSAVE is set and X87|SSE is always a valid XCR0 value.
+        unsafe { XCr0::write(XCr0Flags::X87 | XCr0Flags::SSE) };
+        // Leaf 0xD, subleaf 0: EBX is the XSAVE area size for the features enabled in XCR0.
+        let size = __cpuid_count(0xd, 0).ebx as usize;
+        (XCr0::read_raw(), size)
+    } else {
+        (0, FXSAVE_BYTES)
+    };
+    let mode = if area_bytes > FPU_AREA_BYTES {
+        FpuMode::Fxsave
+    } else {
+        mode
+    };
+    let area_bytes = if mode == FpuMode::Fxsave {
+        FXSAVE_BYTES
+    } else {
+        area_bytes
+    };
+
+    // SAFETY: single-threaded boot; nothing reads the cell until `init` returns.
+    let fpu = unsafe { &mut *FPU.0.get() };
+    fpu.mode = mode;
+    fpu.xcr0 = xcr0;
+    fpu.area_bytes = area_bytes;
+    // SAFETY: SSE is enabled above; `fninit`/`ldmxcsr` only reset FPU/SSE control state.
+    unsafe {
+        asm!("fninit", options(nomem, nostack, preserves_flags));
+        asm!(
+            "ldmxcsr [{}]",
+            in(reg) &MXCSR_DEFAULT,
+            options(readonly, nostack, preserves_flags)
+        );
+    }
+    save(&mut fpu.clean);
+    report()
+}
+
+pub fn report() -> FpuReport {
+    let fpu = state();
+    FpuReport {
+        mode: fpu.mode,
+        xcr0: fpu.xcr0,
+        area_bytes: fpu.area_bytes,
+    }
+}
+
+/// Initial state for a new context: default control words, empty registers.
+pub fn clean() -> FpuArea {
+    state().clean
+}
+
+pub fn save(area: &mut FpuArea) {
+    let ptr = area.0.as_mut_ptr();
+    match state().mode {
+        FpuMode::Off => {}
+        // SAFETY: `area` is 64-byte aligned and large enough for the enabled state; the mode
+        // was chosen from CPUID, so the instruction exists.
+        FpuMode::Fxsave => unsafe {
+            asm!("fxsave64 [{}]", in(reg) ptr, options(nostack, preserves_flags));
+        },
+        // SAFETY: as above; EDX:EAX selects every feature enabled in XCR0.
+        FpuMode::Xsave => unsafe {
+            asm!(
+                "xsave64 [{}]",
+                in(reg) ptr,
+                in("eax") u32::MAX,
+                in("edx") u32::MAX,
+                options(nostack, preserves_flags)
+            );
+        },
+    }
+}
+
+pub fn restore(area: &FpuArea) {
+    let ptr = area.0.as_ptr();
+    match state().mode {
+        FpuMode::Off => {}
+        // SAFETY: `area` holds state written by `save` (or `clean`) in the same mode.
+        FpuMode::Fxsave => unsafe {
+            asm!("fxrstor64 [{}]", in(reg) ptr, options(readonly, nostack, preserves_flags));
+        },
+        // SAFETY: as above; the XSAVE header in `area` came from a real `xsave64`.
+        FpuMode::Xsave => unsafe {
+            asm!(
+                "xrstor64 [{}]",
+                in(reg) ptr,
+                in("eax") u32::MAX,
+                in("edx") u32::MAX,
+                options(readonly, nostack, preserves_flags)
+            );
+        },
+    }
+}
+
+/// Parks the running context's registers in `prev` and loads `next`.
+pub fn switch(prev: &mut FpuArea, next: &FpuArea) {
+    if state().mode == FpuMode::Off {
+        return;
+    }
+    save(prev);
+    restore(next);
+    SWITCHES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn log_status() {
+    let report = report();
+    serial::write_fmt(format_args!(
+        "cpu: fpu mode={} xcr0={:#x} area_bytes={} switches={}\n",
+        report.mode.as_str(),
+        report.xcr0,
+        report.area_bytes,
+        SWITCHES.load(Ordering::Relaxed)
+    ));
+}
@@ -1,4 +1,5 @@
 // kernel/src/arch/x86_64/gdt.rs: GDT/TSS setup with dedicated IST stack for double faults.
+use crate::arch::x86_64::stack;
 use core::sync::atomic::{AtomicBool, Ordering};
 use x86_64::VirtAddr;
 use x86_64::instructions::segmentation::{CS, SS, Segment};
@@ -13,7 +14,9 @@ const DOUBLE_FAULT_STACK_SIZE: usize = 5 * 4096;
 
 static GDT_READY: AtomicBool = AtomicBool::new(false);
 
-static mut DOUBLE_FAULT_STACK: [u8; DOUBLE_FAULT_STACK_SIZE] = [0; DOUBLE_FAULT_STACK_SIZE];
+/// Painted from the start, so `stack usage` can show how deep a double fault went.
+static mut DOUBLE_FAULT_STACK: [u64; DOUBLE_FAULT_STACK_SIZE / 8] =
+    [stack::PAINT; DOUBLE_FAULT_STACK_SIZE / 8];
 static mut DOUBLE_FAULT_STACK_TOP: u64 = 0;
 static mut TSS: TaskStateSegment = TaskStateSegment::new();
 static mut GDT: GlobalDescriptorTable = GlobalDescriptorTable::new();
@@ -68,3 +71,11 @@ pub fn init() -> GdtInitReport {
         }
     }
 }
+
+/// Bottom address and size of the double-fault IST stack.
+pub fn double_fault_stack() -> (u64, u64) {
+    (
+        core::ptr::addr_of!(DOUBLE_FAULT_STACK) as u64,
+        DOUBLE_FAULT_STACK_SIZE as u64,
+    )
+}
@@ -1,5 +1,7 @@
 // kernel/src/arch/x86_64/interrupts.rs: IDT and interrupt handlers for M3.
+use crate::arch::x86_64::stack::{self, Irq};
 use crate::arch::x86_64::{gdt, pic, pit, port};
+use crate::input_replay::{self, InputSource};
 use crate::{keyboard, mouse, serial, time};
 use core::mem::MaybeUninit;
 use core::sync::atomic::{AtomicBool, Ordering};
@@ -104,20 +106,25 @@ extern "x86-interrupt" fn double_fault_handler(
 }
 
 extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    stack::sample(Irq::Timer);
     time::on_timer_tick();
     pic::end_of_interrupt(InterruptIndex::Timer.as_u8());
 }
 
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    stack::sample(Irq::Keyboard);
     // SAFETY: reading port 0x60 acknowledges and consumes the current PS/2 scancode byte.
     let scancode = unsafe { port::inb(0x60) };
+    input_replay::capture(InputSource::Keyboard, scancode);
     keyboard::handle_scancode(scancode);
     pic::end_of_interrupt(InterruptIndex::Keyboard.as_u8());
 }
 
 extern "x86-interrupt" fn mouse_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    stack::sample(Irq::Mouse);
     // SAFETY: reading port 0x60 acknowledges and consumes the current PS/2 mouse data byte.
     let byte = unsafe { port::inb(0x60) };
+    input_replay::capture(InputSource::Mouse, byte);
     mouse::handle_data_byte(byte);
     pic::end_of_interrupt(InterruptIndex::Mouse.as_u8());
 }
@@ -1,6 +1,11 @@
 // kernel/src/arch/x86_64/mod.rs: x86_64-specific boot/runtime support.
+pub mod cpuid;
+pub mod entropy;
+pub mod fpu;
 pub mod gdt;
 pub mod interrupts;
 pub mod pic;
 pub mod pit;
 pub mod port;
+pub mod power;
+pub mod stack;
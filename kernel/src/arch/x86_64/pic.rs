@@ -13,6 +13,7 @@ const ICW1_ICW4: u8 = 0x01;
 const ICW1_INIT: u8 = 0x10;
 const ICW4_8086: u8 = 0x01;
 const EOI: u8 = 0x20;
+const OCW3_READ_IRR: u8 = 0x0A;
 
 const MASTER_IRQ_MASK: u8 = 0b1111_1000; // enable IRQ0(timer), IRQ1(keyboard), IRQ2(cascade)
 const SLAVE_IRQ_MASK: u8 = 0b1110_1111; // enable IRQ12(PS/2 mouse), keep other slave IRQs masked
@@ -69,3 +70,18 @@ pub fn end_of_interrupt(vector: u8) {
         port::outb(PIC_1_COMMAND, EOI);
     }
 }
+
+/// Reports whether `irq` is latched in the interrupt request register but not yet serviced.
+pub fn irq_pending(irq: u8) -> bool {
+    let (command, bit) = if irq < 8 {
+        (PIC_1_COMMAND, irq)
+    } else {
+        (PIC_2_COMMAND, irq - 8)
+    };
+    // SAFETY: OCW3 selects the IRR for the next command-port read; no PIC state is changed.
+    let irr = unsafe {
+        port::outb(command, OCW3_READ_IRR);
+        port::inb(command)
+    };
+    irr & (1 << bit) != 0
+}
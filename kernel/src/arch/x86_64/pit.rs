@@ -1,15 +1,27 @@
-// kernel/src/arch/x86_64/pit.rs: 8253/8254 PIT timer setup for periodic IRQ0 ticks.
+// kernel/src/arch/x86_64/pit.rs: 8253/8254 PIT timer setup for periodic and one-shot IRQ0 ticks.
 use crate::arch::x86_64::port;
 
 const PIT_COMMAND: u16 = 0x43;
 const PIT_CHANNEL_0: u16 = 0x40;
 const PIT_INPUT_HZ: u32 = 1_193_182;
 const PIT_MODE_RATE_GENERATOR: u8 = 0x36; // channel 0, low/high byte, mode 2, binary
+const PIT_MODE_ONESHOT: u8 = 0x30; // channel 0, low/high byte, mode 0, binary
+const PIT_LATCH_CHANNEL_0: u8 = 0x00;
 
-pub fn init(hz: u32) -> u16 {
+pub const fn divisor_for(hz: u32) -> u16 {
     let requested_hz = if hz == 0 { 1 } else { hz };
     let raw_divisor = PIT_INPUT_HZ / requested_hz;
-    let divisor = raw_divisor.clamp(1, u16::MAX as u32) as u16;
+    if raw_divisor == 0 {
+        1
+    } else if raw_divisor > u16::MAX as u32 {
+        u16::MAX
+    } else {
+        raw_divisor as u16
+    }
+}
+
+pub fn init(hz: u32) -> u16 {
+    let divisor = divisor_for(hz);
 
     // SAFETY: PIT programming uses fixed x86 timer ports.
     unsafe {
@@ -20,3 +32,25 @@ pub fn init(hz: u32) -> u16 {
 
     divisor
 }
+
+/// Raises IRQ0 once after `counts` input clocks; the caller restores periodic mode with `init`.
+pub fn arm_oneshot(counts: u16) {
+    let counts = counts.max(1);
+    // SAFETY: PIT programming uses fixed x86 timer ports.
+    unsafe {
+        port::outb(PIT_COMMAND, PIT_MODE_ONESHOT);
+        port::outb(PIT_CHANNEL_0, (counts & 0x00ff) as u8);
+        port::outb(PIT_CHANNEL_0, ((counts >> 8) & 0x00ff) as u8);
+    }
+}
+
+/// Latches and returns the channel 0 down-counter (input clocks left in the current period).
+pub fn read_counter() -> u16 {
+    // SAFETY: latch command plus two data reads is the documented PIT readback sequence.
+    unsafe {
+        port::outb(PIT_COMMAND, PIT_LATCH_CHANNEL_0);
+        let low = port::inb(PIT_CHANNEL_0);
+        let high = port::inb(PIT_CHANNEL_0);
+        u16::from_le_bytes([low, high])
+    }
+}
@@ -0,0 +1,40 @@
+// kernel/src/arch/x86_64/power.rs: machine reset and ACPI soft-off for the q35 guest.
+use crate::arch::x86_64::port;
+use core::arch::asm;
+
+/// ICH9 reset control register; 0x06 asks for a full (CPU + platform) reset.
+const RESET_CONTROL: u16 = 0xcf9;
+const RESET_FULL: u8 = 0x06;
+/// 8042 command port; 0xfe pulses the CPU reset line on machines without `0xcf9`.
+const KBC_COMMAND: u16 = 0x64;
+const KBC_PULSE_RESET: u8 = 0xfe;
+/// PM1a control block that SeaBIOS and OVMF program on q35; SLP_TYP=5 with SLP_EN is S5.
+const PM1A_CONTROL: u16 = 0x604;
+const PM1A_SLEEP_S5: u16 = 0x2000;
+
+pub fn reboot() -> ! {
+    // SAFETY: both are fixed platform reset ports; writing them only resets the machine.
+    unsafe {
+        port::outb(RESET_CONTROL, RESET_FULL);
+        port::outb(KBC_COMMAND, KBC_PULSE_RESET);
+    }
+    halt()
+}
+
+pub fn power_off() -> ! {
+    // SAFETY: a fixed ACPI PM1a port; the write powers the machine off.
+    unsafe {
+        port::outw(PM1A_CONTROL, PM1A_SLEEP_S5);
+    }
+    halt()
+}
+
+/// Parks the CPU with interrupts off when the platform ignored the request.
+fn halt() -> ! {
+    loop {
+        // SAFETY: nothing runs after a failed reset or power-off request.
+        unsafe {
+            asm!("cli; hlt", options(nomem, nostack));
+        }
+    }
+}
@@ -0,0 +1,230 @@
+// kernel/src/arch/x86_64/stack.rs: stack paint and high-water marks for the boot and double-fault
+// stacks, plus per-interrupt and per-task depths sampled from interrupt handlers.
+use crate::arch::x86_64::gdt;
+use crate::serial::{self, Severity};
+use core::arch::asm;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Fill value for unused stack; a word still holding it was never written.
+pub const PAINT: u64 = 0x57ac_57ac_57ac_57ac;
+/// Left unpainted below the painter's own `rsp`.
+const PAINT_MARGIN: u64 = 256;
+/// `stack usage` warns once a painted stack is fuller than this.
+const WARN_PERCENT: u64 = 75;
+const MAX_OWNERS: usize = 16;
+/// Owner 0 is the run loop itself, outside any task or service.
+const MAIN_OWNER: usize = 0;
+
+static BOOT_BOTTOM: AtomicU64 = AtomicU64::new(0);
+static BOOT_LEN: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy)]
+pub enum Irq {
+    Timer,
+    Keyboard,
+    Mouse,
+}
+
+impl Irq {
+    const ALL: [Self; 3] = [Self::Timer, Self::Keyboard, Self::Mouse];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Timer => "timer",
+            Self::Keyboard => "keyboard",
+            Self::Mouse => "mouse",
+        }
+    }
+}
+
+/// Deepest sample: bytes from the top of the boot stack down to `rsp`.
+struct DepthStat {
+    samples: AtomicU64,
+    max_depth: AtomicU64,
+    /// Below the owner's own entry point, i.e. what the task itself added.
+    max_own: AtomicU64,
+}
+
+impl DepthStat {
+    const fn new() -> Self {
+        Self {
+            samples: AtomicU64::new(0),
+            max_depth: AtomicU64::new(0),
+            max_own: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, depth: u64, own: u64) {
+        self.samples.fetch_add(1, Ordering::Relaxed);
+        self.max_depth.fetch_max(depth, Ordering::Relaxed);
+        self.max_own.fetch_max(own, Ordering::Relaxed);
+    }
+}
+
+static IRQ_STATS: [DepthStat; Irq::ALL.len()] = [const { DepthStat::new() }; Irq::ALL.len()];
+static OWNER_STATS: [DepthStat; MAX_OWNERS] = [const { DepthStat::new() }; MAX_OWNERS];
+static OWNER: AtomicUsize = AtomicUsize::new(MAIN_OWNER);
+static OWNER_ENTRY_RSP: AtomicU64 = AtomicU64::new(0);
+
+struct OwnerNames(UnsafeCell<[&'static str; MAX_OWNERS]>);
+
+// SAFETY: names are registered and read only on the main loop; interrupt handlers only touch the
+// atomic owner index and counters.
+unsafe impl Sync for OwnerNames {}
+
+static OWNER_NAMES: OwnerNames = OwnerNames(UnsafeCell::new([""; MAX_OWNERS]));
+static OWNER_COUNT: AtomicUsize = AtomicUsize::new(1);
+/// Tasks that found the owner table full; their samples go to the run loop.
+static OWNER_OVERFLOW: AtomicU64 = AtomicU64::new(0);
+
+fn rsp() -> u64 {
+    let value: u64;
+    // SAFETY: reading the stack pointer has no side effects.
+    unsafe {
+        asm!("mov {}, rsp", out(reg) value, options(nomem, nostack, preserves_flags));
+    }
+    value
+}
+
+/// Fills the unused part of the boot stack with `PAINT`. Call first thing in `kernel_main`,
+/// before interrupts are enabled.
+pub fn paint_boot_stack(bottom: u64, len: u64) {
+    BOOT_BOTTOM.store(bottom, Ordering::Relaxed);
+    BOOT_LEN.store(len, Ordering::Relaxed);
+    // SAFETY: main loop only; nothing else registers names this early.
+    unsafe { (*OWNER_NAMES.0.get())[MAIN_OWNER] = "main" };
+    let sp = rsp();
+    OWNER_ENTRY_RSP.store(sp, Ordering::Relaxed);
+    let end = sp.saturating_sub(PAINT_MARGIN) & !7;
+    let mut addr = bottom;
+    while addr < end {
+        // SAFETY: `[bottom, end)` is the mapped, currently unused part of the boot stack.
+        unsafe { (addr as *mut u64).write_volatile(PAINT) };
+        addr += 8;
+    }
+}
+
+/// Bytes between the bottom of a painted stack and the first word that was ever written.
+fn untouched_bytes(bottom: u64, len: u64) -> u64 {
+    let mut addr = bottom;
+    while addr < bottom + len {
+        // SAFETY: `[bottom, bottom + len)` is a mapped stack; reads are side-effect free.
+        if unsafe { (addr as *const u64).read_volatile() } != PAINT {
+            break;
+        }
+        addr += 8;
+    }
+    addr - bottom
+}
+
+/// Records the current depth for `irq` and for whichever owner it interrupted.
+pub fn sample(irq: Irq) {
+    let bottom = BOOT_BOTTOM.load(Ordering::Relaxed);
+    let top = bottom + BOOT_LEN.load(Ordering::Relaxed);
+    let sp = rsp();
+    if !(bottom..top).contains(&sp) {
+        return;
+    }
+    let depth = top - sp;
+    let own = OWNER_ENTRY_RSP
+        .load(Ordering::Relaxed)
+        .saturating_sub(sp)
+        .min(depth);
+    IRQ_STATS[irq as usize].record(depth, own);
+    OWNER_STATS[OWNER.load(Ordering::Relaxed)].record(depth, own);
+}
+
+/// Attributes samples to `name` until dropped; proc tasks and executor services wrap each step.
+pub struct OwnerGuard {
+    previous: usize,
+    previous_entry: u64,
+}
+
+impl Drop for OwnerGuard {
+    fn drop(&mut self) {
+        OWNER.store(self.previous, Ordering::Relaxed);
+        OWNER_ENTRY_RSP.store(self.previous_entry, Ordering::Relaxed);
+    }
+}
+
+pub fn enter(name: &'static str) -> OwnerGuard {
+    let guard = OwnerGuard {
+        previous: OWNER.load(Ordering::Relaxed),
+        previous_entry: OWNER_ENTRY_RSP.load(Ordering::Relaxed),
+    };
+    // SAFETY: owners are registered only on the main loop.
+    let names = unsafe { &mut *OWNER_NAMES.0.get() };
+    let count = OWNER_COUNT.load(Ordering::Relaxed);
+    let index = match names[..count].iter().position(|known| *known == name) {
+        Some(index) => index,
+        None if count < MAX_OWNERS => {
+            names[count] = name;
+            OWNER_COUNT.store(count + 1, Ordering::Relaxed);
+            count
+        }
+        None => {
+            OWNER_OVERFLOW.fetch_add(1, Ordering::Relaxed);
+            MAIN_OWNER
+        }
+    };
+    OWNER_ENTRY_RSP.store(rsp(), Ordering::Relaxed);
+    OWNER.store(index, Ordering::Relaxed);
+    guard
+}
+
+fn log_painted(name: &str, bottom: u64, len: u64) {
+    let used = len - untouched_bytes(bottom, len);
+    let free = len - used;
+    let percent = used * 100 / len.max(1);
+    let args = format_args!(
+        "stack: {} bottom={:#x} size={} used={} free={} used_pct={}\n",
+        name, bottom, len, used, free, percent
+    );
+    if percent > WARN_PERCENT {
+        serial::write_severity_fmt(Severity::Warning, args);
+    } else {
+        serial::write_fmt(args);
+    }
+}
+
+/// `stack usage`: exact high-water marks for painted stacks, then sampled depths. Interrupts,
+/// proc tasks and services all run on the boot stack, so their depths include whatever was
+/// below them; `own=` is the part added after the task was entered.
+pub fn log_usage() {
+    log_painted(
+        "boot",
+        BOOT_BOTTOM.load(Ordering::Relaxed),
+        BOOT_LEN.load(Ordering::Relaxed),
+    );
+    let (df_bottom, df_len) = gdt::double_fault_stack();
+    log_painted("double-fault", df_bottom, df_len);
+    for irq in Irq::ALL {
+        let stat = &IRQ_STATS[irq as usize];
+        serial::write_fmt(format_args!(
+            "stack: irq {} samples={} max_depth={}\n",
+            irq.as_str(),
+            stat.samples.load(Ordering::Relaxed),
+            stat.max_depth.load(Ordering::Relaxed)
+        ));
+    }
+    // SAFETY: owner names are read on the main loop, where they are registered.
+    let names = unsafe { &*OWNER_NAMES.0.get() };
+    let count = OWNER_COUNT.load(Ordering::Relaxed);
+    for (name, stat) in names[..count].iter().zip(&OWNER_STATS) {
+        serial::write_fmt(format_args!(
+            "stack: task {} samples={} max_depth={} max_own={}\n",
+            name,
+            stat.samples.load(Ordering::Relaxed),
+            stat.max_depth.load(Ordering::Relaxed),
+            stat.max_own.load(Ordering::Relaxed)
+        ));
+    }
+    let overflow = OWNER_OVERFLOW.load(Ordering::Relaxed);
+    if overflow > 0 {
+        serial::write_fmt(format_args!(
+            "stack: owner table full, {} entries counted as main\n",
+            overflow
+        ));
+    }
+}
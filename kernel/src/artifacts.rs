@@ -0,0 +1,436 @@
+// kernel/src/artifacts.rs: the build's artifact manifest (initramfs `manifest=` line) and boot-time
+// verification of the artifacts the kernel links or embeds.
+mod sha256;
+
+#[cfg(feature = "doom")]
+use crate::doom_bridge;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+use crate::time;
+use core::cell::UnsafeCell;
+use core::fmt;
+
+const MANIFEST_VERSION: u64 = 1;
+const MANIFEST_KEY: &[u8] = b"manifest=";
+const MAX_ARTIFACTS: usize = 8;
+const NAME_CAP: usize = 16;
+const PATH_CAP: usize = 96;
+
+const DOOM_CORE_SHA256: &str = match option_env!("ARROST_DOOM_GENERIC_CORE_SHA256") {
+    Some(value) => value,
+    None => "",
+};
+const DOOM_PORT_SHA256: &str = match option_env!("ARROST_DOOM_GENERIC_PORT_SHA256") {
+    Some(value) => value,
+    None => "",
+};
+const DOOM_WAD_SHA256: &str = match option_env!("ARROST_DOOM_WAD_SHA256") {
+    Some(value) => value,
+    None => "",
+};
+
+#[derive(Clone, Copy)]
+struct Artifact {
+    name: [u8; NAME_CAP],
+    name_len: usize,
+    path: [u8; PATH_CAP],
+    path_len: usize,
+    size: u64,
+    sha256: [u8; 32],
+}
+
+impl Artifact {
+    const EMPTY: Self = Self {
+        name: [0; NAME_CAP],
+        name_len: 0,
+        path: [0; PATH_CAP],
+        path_len: 0,
+        size: 0,
+        sha256: [0; 32],
+    };
+
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("?")
+    }
+
+    fn path(&self) -> &str {
+        core::str::from_utf8(&self.path[..self.path_len]).unwrap_or("?")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ManifestState {
+    /// No ramdisk, or a ramdisk without a `manifest=` line (an older xtask).
+    Absent,
+    /// A `manifest=` line this kernel cannot read: another version or malformed JSON.
+    Invalid,
+    Loaded,
+}
+
+impl ManifestState {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Absent => "absent",
+            Self::Invalid => "invalid",
+            Self::Loaded => "loaded",
+        }
+    }
+}
+
+struct Manifest {
+    state: ManifestState,
+    entries: [Artifact; MAX_ARTIFACTS],
+    count: usize,
+    /// Entries past `MAX_ARTIFACTS`, or with a field that did not fit.
+    skipped: usize,
+}
+
+impl Manifest {
+    fn entries(&self) -> &[Artifact] {
+        &self.entries[..self.count]
+    }
+
+    fn find(&self, name: &str) -> Option<&Artifact> {
+        self.entries().iter().find(|entry| entry.name() == name)
+    }
+}
+
+struct ManifestCell(UnsafeCell<Manifest>);
+
+// SAFETY: written once by `init` during single-threaded early boot, only read afterwards.
+unsafe impl Sync for ManifestCell {}
+
+static MANIFEST: ManifestCell = ManifestCell(UnsafeCell::new(Manifest {
+    state: ManifestState::Absent,
+    entries: [Artifact::EMPTY; MAX_ARTIFACTS],
+    count: 0,
+    skipped: 0,
+}));
+
+fn with_manifest<R>(f: impl FnOnce(&Manifest) -> R) -> R {
+    // SAFETY: see `ManifestCell`; `init` holds the only mutable borrow and runs before readers.
+    unsafe { f(&*MANIFEST.0.get()) }
+}
+
+/// Doom artifacts checked at boot. xtask compiles each one's digest into the kernel, so a ramdisk
+/// from another build shows up as `mismatch`; the WAD is also embedded, so its length is checked
+/// against the bytes the kernel actually carries.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DoomArtifact {
+    Core,
+    Port,
+    Wad,
+}
+
+impl DoomArtifact {
+    pub const ALL: [Self; 3] = [Self::Core, Self::Port, Self::Wad];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Core => "doom_core",
+            Self::Port => "doom_port",
+            Self::Wad => "doom_wad",
+        }
+    }
+
+    const fn expected_sha256(self) -> &'static str {
+        match self {
+            Self::Core => DOOM_CORE_SHA256,
+            Self::Port => DOOM_PORT_SHA256,
+            Self::Wad => DOOM_WAD_SHA256,
+        }
+    }
+
+    /// Bytes of the artifact the kernel carries, when it carries more than a link-time copy.
+    /// A kernel built without `doom` carries none.
+    fn embedded(self) -> Option<&'static [u8]> {
+        match self {
+            #[cfg(feature = "doom")]
+            Self::Wad => Some(doom_bridge::wad_bytes()),
+            _ => None,
+        }
+    }
+
+    pub fn verify(self) -> Verify {
+        let Some(expected) = parse_sha256(self.expected_sha256()) else {
+            return Verify::Unchecked;
+        };
+        with_manifest(|manifest| {
+            let Some(entry) = manifest.find(self.as_str()) else {
+                return Verify::Missing;
+            };
+            let size_ok = self
+                .embedded()
+                .is_none_or(|bytes| bytes.len() as u64 == entry.size);
+            if entry.sha256 == expected && size_ok {
+                Verify::Ok
+            } else {
+                Verify::Mismatch
+            }
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Verify {
+    Ok,
+    Mismatch,
+    /// The kernel expects the artifact but the manifest does not list it.
+    Missing,
+    /// Built without xtask digests (plain `cargo build`, or the artifact did not exist).
+    Unchecked,
+}
+
+impl Verify {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Mismatch => "mismatch",
+            Self::Missing => "missing",
+            Self::Unchecked => "unchecked",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ManifestReport {
+    pub state: ManifestState,
+    pub entries: usize,
+    pub skipped: usize,
+}
+
+/// Loads the manifest from the initramfs; runs once during early boot.
+pub fn init(ramdisk: Option<&[u8]>) -> ManifestReport {
+    // SAFETY: see `ManifestCell`.
+    let manifest = unsafe { &mut *MANIFEST.0.get() };
+    manifest.count = 0;
+    manifest.skipped = 0;
+    manifest.state = match ramdisk.and_then(manifest_line) {
+        None => ManifestState::Absent,
+        Some(json) => {
+            if load(manifest, json).is_some() {
+                ManifestState::Loaded
+            } else {
+                manifest.count = 0;
+                ManifestState::Invalid
+            }
+        }
+    };
+    ManifestReport {
+        state: manifest.state,
+        entries: manifest.count,
+        skipped: manifest.skipped,
+    }
+}
+
+fn manifest_line(ramdisk: &[u8]) -> Option<&str> {
+    let line = ramdisk
+        .split(|&byte| byte == b'\n')
+        .find_map(|line| line.strip_prefix(MANIFEST_KEY))?;
+    core::str::from_utf8(line).ok()
+}
+
+fn load(manifest: &mut Manifest, json: &str) -> Option<()> {
+    let version = json_field(json, "version")?.parse::<u64>().ok()?;
+    if version != MANIFEST_VERSION {
+        return None;
+    }
+    let start = json.find("\"artifacts\":[")? + "\"artifacts\":[".len();
+    for object in JsonObjects::new(&json[start..]) {
+        let parsed = parse_artifact(object?);
+        match parsed {
+            Some(entry) if manifest.count < MAX_ARTIFACTS => {
+                manifest.entries[manifest.count] = entry;
+                manifest.count += 1;
+            }
+            _ => manifest.skipped = manifest.skipped.saturating_add(1),
+        }
+    }
+    Some(())
+}
+
+fn parse_artifact(object: &str) -> Option<Artifact> {
+    let name = json_field(object, "name")?;
+    let path = json_field(object, "path")?;
+    if name.len() > NAME_CAP {
+        return None;
+    }
+    let mut entry = Artifact::EMPTY;
+    entry.name[..name.len()].copy_from_slice(name.as_bytes());
+    entry.name_len = name.len();
+    // Long host paths keep their tail, which names the file.
+    let path = &path.as_bytes()[path.len().saturating_sub(PATH_CAP)..];
+    entry.path[..path.len()].copy_from_slice(path);
+    entry.path_len = path.len();
+    entry.size = json_field(object, "size")?.parse().ok()?;
+    entry.sha256 = parse_sha256(json_field(object, "sha256")?)?;
+    Some(entry)
+}
+
+/// Top-level `{...}` objects of a JSON array body, up to its closing `]`. Yields `None` for an
+/// unterminated object.
+struct JsonObjects<'a> {
+    rest: &'a str,
+}
+
+impl<'a> JsonObjects<'a> {
+    fn new(rest: &'a str) -> Self {
+        Self { rest }
+    }
+}
+
+impl<'a> Iterator for JsonObjects<'a> {
+    type Item = Option<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.rest.find(['{', ']'])?;
+        if self.rest.as_bytes()[start] == b']' {
+            self.rest = "";
+            return None;
+        }
+        let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+        for (offset, byte) in self.rest.bytes().enumerate().skip(start) {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' if in_string => escaped = true,
+                b'"' => in_string = !in_string,
+                b'{' if !in_string => depth += 1,
+                b'}' if !in_string => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let object = &self.rest[start..=offset];
+                        self.rest = &self.rest[offset + 1..];
+                        return Some(Some(object));
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.rest = "";
+        Some(None)
+    }
+}
+
+/// Raw value of `"key":` in a flat JSON object: string contents (escapes left as-is) or the
+/// bare number.
+fn json_field<'a>(object: &'a str, key: &str) -> Option<&'a str> {
+    let mut pattern = [0u8; NAME_CAP + 3];
+    let pattern_len = key.len() + 3;
+    if pattern_len > pattern.len() {
+        return None;
+    }
+    pattern[0] = b'"';
+    pattern[1..=key.len()].copy_from_slice(key.as_bytes());
+    pattern[key.len() + 1] = b'"';
+    pattern[key.len() + 2] = b':';
+    let pattern = core::str::from_utf8(&pattern[..pattern_len]).ok()?;
+
+    let value = object[object.find(pattern)? + pattern.len()..].trim_start();
+    if let Some(text) = value.strip_prefix('"') {
+        let mut escaped = false;
+        for (offset, byte) in text.bytes().enumerate() {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => return Some(&text[..offset]),
+                _ => {}
+            }
+        }
+        return None;
+    }
+    let end = value
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(value.len());
+    (end > 0).then(|| &value[..end])
+}
+
+fn parse_sha256(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 {
+        return None;
+    }
+    let mut digest = [0u8; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().as_chunks::<2>().0) {
+        let pair = core::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(digest)
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// `artifacts`: the manifest and the boot verification results.
+pub fn log_to_serial() {
+    with_manifest(|manifest| {
+        serial::write_fmt(format_args!(
+            "artifacts: manifest={} entries={} skipped={}\n",
+            manifest.state.as_str(),
+            manifest.count,
+            manifest.skipped
+        ));
+        for entry in manifest.entries() {
+            serial::write_fmt(format_args!(
+                "artifacts: name={} size={} sha256={} path={}\n",
+                entry.name(),
+                entry.size,
+                Hex(&entry.sha256),
+                entry.path()
+            ));
+        }
+    });
+    for artifact in DoomArtifact::ALL {
+        serial::write_fmt(format_args!(
+            "artifacts: verify {}={}\n",
+            artifact.as_str(),
+            artifact.verify().as_str()
+        ));
+    }
+}
+
+/// `artifacts verify`: hashes every artifact the kernel embeds and compares the digest with
+/// the manifest. Slow (the WAD is 4 MiB), so boot only checks sizes and build digests. Fails
+/// on a mismatch.
+pub fn verify_to_serial() -> Status {
+    let mut mismatched = false;
+    for artifact in DoomArtifact::ALL {
+        let Some(bytes) = artifact.embedded() else {
+            continue;
+        };
+        let start = time::ticks();
+        let digest = sha256::sha256(bytes);
+        let ticks = time::ticks().saturating_sub(start);
+        let listed = with_manifest(|manifest| {
+            manifest
+                .find(artifact.as_str())
+                .map(|entry| entry.sha256 == digest && entry.size == bytes.len() as u64)
+        });
+        let result = match listed {
+            Some(true) => Verify::Ok,
+            Some(false) => Verify::Mismatch,
+            None => Verify::Missing,
+        };
+        let line = format_args!(
+            "artifacts: {} bytes={} sha256={} ticks={} -> {}\n",
+            artifact.as_str(),
+            bytes.len(),
+            Hex(&digest),
+            ticks,
+            result.as_str()
+        );
+        if result == Verify::Mismatch {
+            serial::write_severity_fmt(Severity::Error, line);
+            mismatched = true;
+        } else {
+            serial::write_fmt(line);
+        }
+    }
+    Status::check(!mismatched)
+}
@@ -2,26 +2,42 @@
 use crate::arch::x86_64::port;
 use core::cell::UnsafeCell;
 
+mod beep;
+#[cfg(feature = "net")]
+mod notes;
+#[cfg(feature = "doom")]
+mod pcm;
+#[cfg(feature = "doom")]
+mod resample;
+mod route;
+mod tap;
 mod virtio_sound;
-
+#[cfg(feature = "doom")]
+mod wav;
+
+pub use beep::Envelope;
+use beep::{BeepQueue, BeepRequest};
+#[cfg(feature = "net")]
+pub use notes::{note_off, note_on};
+#[cfg(feature = "doom")]
+pub use pcm::{
+    PcmFormat, pcm_status, play_test_tone, play_wav, reset_runtime_metrics, submit_pcm_i16,
+};
+use route::Routes;
+pub use route::{AudioOwner, Route, RouteStatus};
+pub use tap::{DEFAULT_TAP_SECONDS, MAX_TAP_SECONDS, TapError, TapSaved, TapStatus};
+use virtio_sound::VirtioSoundInitReport;
+pub use virtio_sound::{VirtioSoundDevices, format_name, rate_hz};
+
+#[cfg(feature = "desktop")]
+pub const MAX_VOLUME: u8 = 100;
 const PIT_INPUT_HZ: u32 = 1_193_182;
 const PIT_COMMAND: u16 = 0x43;
 const PIT_CHANNEL_2: u16 = 0x42;
 const SPEAKER_PORT: u16 = 0x61;
 const PIT_MODE_CHANNEL2_SQUARE: u8 = 0xB6;
 const PCM_MAX_HOLD_TICKS: u64 = 24;
-const PCM_MIN_HOLD_TICKS: u64 = 3;
 const PCM_TONE_RETUNE_MIN_TICKS: u64 = 2;
-const PCM_MIN_ENERGY: u64 = 240;
-const PCM_MIN_EST_HZ: u16 = 90;
-const PCM_MAX_EST_HZ: u16 = 2400;
-const PCM_DYNAMIC_THRESHOLD_DIV: u64 = 10;
-const PCM_DYNAMIC_THRESHOLD_MIN: i16 = 40;
-const PCM_DYNAMIC_THRESHOLD_MAX: i16 = 1400;
-const PCM_ENERGY_FALLBACK_HZ_MIN: u16 = 160;
-const PCM_ENERGY_FALLBACK_HZ_MAX: u16 = 920;
-const PCM_ENERGY_FALLBACK_REF: u64 = 14_000;
-
 struct AudioCell(UnsafeCell<AudioState>);
 
 // SAFETY: audio state is mutated only on the kernel main loop thread in current milestones.
@@ -51,45 +67,50 @@ pub struct AudioInitReport {
     pub backend: &'static str,
     pub ready: bool,
     pub detail: &'static str,
+    /// Device-side stream parameters negotiated at init (zero for the pc-speaker fallback).
+    pub rate_hz: u32,
+    pub channels: u8,
+    pub format: &'static str,
 }
 
 #[derive(Clone, Copy)]
 pub struct AudioStatus {
     pub mode: AudioMode,
     pub active: bool,
-    pub tone_hz: u16,
-    pub pcm_mix_events: u64,
-    pub pcm_samples: u64,
-    pub pcm_tone_switches: u64,
-    pub pcm_hz_min: u16,
-    pub pcm_hz_max: u16,
     pub pcm_backend: &'static str,
-    pub pcm_queue_pending: u16,
-    pub pcm_buffered_frames: u32,
-    pub pcm_packets_submitted: u64,
-    pub pcm_packets_completed: u64,
-    pub pcm_packets_dropped: u64,
-    pub pcm_frames_completed: u64,
-    pub pcm_frames_dropped: u64,
     pub pcm_rate_hz: u32,
     pub pcm_channels: u8,
-    pub pcm_stream_id: u32,
-    pub pcm_last_ctrl_status: u32,
+    pub muted: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct BeepStatus {
+    pub queued: u64,
+    pub played: u64,
+    /// Beeps that started while another was still sounding and were mixed with it.
+    pub overlapped: u64,
+    /// Beeps refused because the queue was full.
+    pub dropped: u64,
+    pub voices: u8,
 }
 
 struct AudioState {
     initialized: bool,
     mode: AudioMode,
+    /// Drops submitted PCM without touching `mode`, so a paused client can go quiet and resume.
+    muted: bool,
     active: bool,
     tone_hz: u16,
     next_tone_update_tick: u64,
     stop_tick: u64,
-    pcm_mix_events: u64,
-    pcm_samples: u64,
-    pcm_tone_switches: u64,
-    pcm_hz_min: u16,
-    pcm_hz_max: u16,
-    pcm_last_est_hz: u16,
+    #[cfg(feature = "doom")]
+    pcm: pcm::PcmState,
+    routes: Routes,
+    beeps: BeepQueue,
+    #[cfg(feature = "desktop")]
+    volume: u8,
+    #[cfg(feature = "net")]
+    held: notes::HeldNotes,
 }
 
 impl AudioState {
@@ -97,16 +118,19 @@ impl AudioState {
         Self {
             initialized: false,
             mode: AudioMode::PcSpeaker,
+            muted: false,
             active: false,
             tone_hz: 0,
             next_tone_update_tick: 0,
             stop_tick: 0,
-            pcm_mix_events: 0,
-            pcm_samples: 0,
-            pcm_tone_switches: 0,
-            pcm_hz_min: 0,
-            pcm_hz_max: 0,
-            pcm_last_est_hz: 0,
+            #[cfg(feature = "doom")]
+            pcm: pcm::PcmState::new(),
+            routes: Routes::new(),
+            beeps: BeepQueue::new(),
+            #[cfg(feature = "desktop")]
+            volume: MAX_VOLUME,
+            #[cfg(feature = "net")]
+            held: notes::HeldNotes::new(),
         }
     }
 }
@@ -117,33 +141,58 @@ pub fn init() -> AudioInitReport {
             state.initialized = true;
             disable_speaker();
         }
-        let virtio = virtio_sound::init();
-        let _ = (
-            virtio.stream_id,
-            virtio.sample_rate_hz,
-            virtio.channels,
-            virtio.device_id,
-            virtio.reason,
-        );
-        if virtio.ready {
-            state.mode = AudioMode::Virtio;
-            virtio_sound::set_enabled(true);
-            AudioInitReport {
-                backend: "virtio-snd",
-                ready: true,
-                detail: "ok",
-            }
-        } else {
-            state.mode = AudioMode::PcSpeaker;
-            AudioInitReport {
-                backend: "pc-speaker",
-                ready: true,
-                detail: virtio.reason,
-            }
+        select_backend(state, virtio_sound::init())
+    })
+}
+
+/// `restart audio`: silences the speaker, drops queued beeps and metrics and probes the
+/// virtio device again. Routes, mute and volume are kept.
+pub fn restart() -> AudioInitReport {
+    with_state_mut(|state| {
+        disable_speaker();
+        state.initialized = true;
+        state.active = false;
+        state.tone_hz = 0;
+        state.stop_tick = 0;
+        state.next_tone_update_tick = 0;
+        state.beeps = BeepQueue::new();
+        #[cfg(feature = "net")]
+        {
+            state.held = notes::HeldNotes::new();
         }
+        #[cfg(feature = "doom")]
+        state.pcm.reset_metrics();
+        state.routes.reset_metrics();
+        select_backend(state, virtio_sound::restart())
     })
 }
 
+fn select_backend(state: &mut AudioState, virtio: VirtioSoundInitReport) -> AudioInitReport {
+    let _ = (virtio.stream_id, virtio.device_id);
+    if virtio.ready {
+        state.mode = AudioMode::Virtio;
+        virtio_sound::set_enabled(true);
+        AudioInitReport {
+            backend: "virtio-snd",
+            ready: true,
+            detail: "ok",
+            rate_hz: virtio.sample_rate_hz,
+            channels: virtio.channels,
+            format: "s16",
+        }
+    } else {
+        state.mode = AudioMode::PcSpeaker;
+        AudioInitReport {
+            backend: "pc-speaker",
+            ready: true,
+            detail: virtio.reason,
+            rate_hz: 0,
+            channels: 1,
+            format: "square",
+        }
+    }
+}
+
 pub fn status() -> AudioStatus {
     with_state_mut(|state| {
         let virtio = virtio_sound::status();
@@ -154,86 +203,106 @@ pub fn status() -> AudioStatus {
             } else {
                 state.active
             },
-            tone_hz: if state.mode == AudioMode::Virtio {
-                0
-            } else {
-                state.tone_hz
-            },
-            pcm_mix_events: state.pcm_mix_events,
-            pcm_samples: state.pcm_samples,
-            pcm_tone_switches: state.pcm_tone_switches,
-            pcm_hz_min: state.pcm_hz_min,
-            pcm_hz_max: state.pcm_hz_max,
             pcm_backend: if virtio.ready {
                 "virtio-snd"
             } else {
                 "pc-speaker"
             },
-            pcm_queue_pending: virtio.pending_packets,
-            pcm_buffered_frames: virtio.buffered_frames,
-            pcm_packets_submitted: virtio.submitted_packets,
-            pcm_packets_completed: virtio.completed_packets,
-            pcm_packets_dropped: virtio.dropped_packets,
-            pcm_frames_completed: virtio.completed_frames,
-            pcm_frames_dropped: virtio.dropped_frames,
             pcm_rate_hz: virtio.sample_rate_hz,
             pcm_channels: virtio.channels,
-            pcm_stream_id: virtio.stream_id,
-            pcm_last_ctrl_status: virtio.last_ctrl_status,
+            muted: state.muted,
         }
     })
 }
 
-pub fn reset_runtime_metrics() {
-    with_state_mut(|state| {
-        state.pcm_mix_events = 0;
-        state.pcm_samples = 0;
-        state.pcm_tone_switches = 0;
-        state.pcm_hz_min = 0;
-        state.pcm_hz_max = 0;
-        state.pcm_last_est_hz = 0;
-        virtio_sound::reset_runtime_metrics();
-    });
+/// Jacks and streams the virtio-snd device reported, for `audio devices`.
+pub fn devices() -> VirtioSoundDevices {
+    virtio_sound::devices()
 }
 
-pub fn play_test_tone() -> bool {
-    if status().mode == AudioMode::Off {
-        return false;
-    }
+/// Starts mirroring the mixed virtio output into a `seconds` window saved to `path` on stop.
+pub fn tap_start(path: &str, seconds: u32) -> Result<(), TapError> {
+    tap::start(path, seconds)
+}
 
-    const TEST_RATE_HZ: u32 = 44_100;
-    const TEST_FRAMES: usize = 1024;
-    let mut stereo = [0i16; TEST_FRAMES * 2];
-    let mut phase_fp = 0u64;
-
-    for frame in 0..TEST_FRAMES {
-        let freq_hz =
-            440u32.saturating_add((330u32.saturating_mul(frame as u32)) / TEST_FRAMES as u32);
-        let step_fp = ((u64::from(freq_hz)) << 32) / u64::from(TEST_RATE_HZ);
-        phase_fp = phase_fp.wrapping_add(step_fp.max(1));
-
-        let tri_phase = ((phase_fp >> 16) & 0xFFFF) as i32;
-        let tri = if tri_phase < 0x8000 {
-            tri_phase - 0x4000
-        } else {
-            0xC000 - tri_phase
-        } * 2;
-        let fade_in = ((frame as i32) * 32767) / 96;
-        let fade_out = (((TEST_FRAMES - frame) as i32) * 32767) / 128;
-        let envelope = fade_in.min(fade_out).clamp(0, 32767);
-        let sample_num = i64::from(tri) * i64::from(envelope) * 7_000i64;
-        let sample = (sample_num / i64::from(32767 * 32767))
-            .clamp(i64::from(i16::MIN), i64::from(i16::MAX)) as i16;
-
-        let index = frame * 2;
-        stereo[index] = sample;
-        stereo[index + 1] = sample;
-    }
+pub fn tap_stop() -> Result<TapSaved, TapError> {
+    tap::stop()
+}
 
-    let submitted = submit_pcm_i16(&stereo, TEST_RATE_HZ, 2);
-    submitted > 0
+pub fn tap_status() -> TapStatus {
+    tap::status()
 }
 
+/// Queues a UI feedback tone; it starts on the next `poll`. Returns `false` when the queue
+/// is full. On virtio, overlapping beeps are summed with each other and with any queued PCM;
+/// the pc speaker can only hold one square wave, so the newest beep replaces the current tone.
+pub fn beep(freq_hz: u32, ms: u32, envelope: Envelope) -> bool {
+    let request = BeepRequest {
+        freq_hz: freq_hz.clamp(beep::MIN_FREQ_HZ, beep::MAX_FREQ_HZ),
+        ms: ms.clamp(1, beep::MAX_MS),
+        envelope,
+    };
+    with_state_mut(|state| state.beeps.push(request))
+}
+
+/// Shell bell (Ctrl-G): a short high pluck.
+pub fn bell() -> bool {
+    beep(1_320, 90, Envelope::Pluck)
+}
+
+/// Task crash: two overlapping swells, mixed into one chord on virtio.
+pub fn alert() -> bool {
+    beep(660, 260, Envelope::Swell) & beep(440, 260, Envelope::Swell)
+}
+
+pub fn beep_status() -> BeepStatus {
+    with_state_mut(|state| BeepStatus {
+        queued: state.beeps.queued,
+        played: state.beeps.played,
+        overlapped: state.beeps.overlapped,
+        dropped: state.beeps.dropped,
+        voices: virtio_sound::status().beep_voices,
+    })
+}
+
+fn start_queued_beeps(state: &mut AudioState, now_ticks: u64) {
+    while let Some(request) = state.beeps.pop() {
+        // Counted in device frames; the pc speaker has no sample rate, so one per millisecond.
+        let rate_hz = match virtio_sound::status().sample_rate_hz {
+            0 => 1000,
+            rate => rate,
+        };
+        let frames = u64::from(request.ms) * u64::from(rate_hz) / 1000;
+        let route = state.routes.account(AudioOwner::Beep, frames as usize);
+        if state.muted || route == Route::Off {
+            continue;
+        }
+        match state.mode {
+            AudioMode::Off => continue,
+            AudioMode::Virtio => {
+                let Some(overlapped) = virtio_sound::start_beep(request, route == Route::Duck)
+                else {
+                    continue;
+                };
+                if overlapped {
+                    state.beeps.overlapped = state.beeps.overlapped.saturating_add(1);
+                }
+            }
+            AudioMode::PcSpeaker => {
+                if state.active && now_ticks < state.stop_tick {
+                    state.beeps.overlapped = state.beeps.overlapped.saturating_add(1);
+                }
+                let hold_ticks = (u64::from(request.ms) * u64::from(crate::time::PIT_HZ))
+                    .div_ceil(1000)
+                    .max(1);
+                apply_tone(state, request.freq_hz as u16, hold_ticks);
+            }
+        }
+        state.beeps.played = state.beeps.played.saturating_add(1);
+    }
+}
+
+#[cfg(feature = "doom")]
 pub fn set_mode(mode: AudioMode) -> AudioMode {
     with_state_mut(|state| {
         if !state.initialized {
@@ -277,57 +346,76 @@ pub fn set_mode(mode: AudioMode) -> AudioMode {
     })
 }
 
-pub fn submit_pcm_i16(samples: &[i16], sample_rate: u32, channels: u8) -> usize {
-    if samples.is_empty() {
-        return 0;
-    }
-    let src_channels = channels.clamp(1, 2);
-
+/// Silences output while keeping the selected mode; the virtio stream is stopped so queued
+/// packets are flushed rather than played out. Returns false when already in that state.
+#[cfg(feature = "doom")]
+pub fn set_muted(muted: bool) -> bool {
     with_state_mut(|state| {
-        state.pcm_mix_events = state.pcm_mix_events.saturating_add(1);
-        state.pcm_samples = state.pcm_samples.saturating_add(samples.len() as u64);
-
-        match state.mode {
-            AudioMode::Off => samples.len(),
-            AudioMode::Virtio => {
-                let queued = virtio_sound::submit_pcm_i16(samples, sample_rate, src_channels);
-                state.active = queued > 0 || virtio_sound::status().pending_packets > 0;
-                samples.len()
+        if state.muted == muted {
+            return false;
+        }
+        state.muted = muted;
+        if muted {
+            virtio_sound::set_enabled(false);
+            if state.active {
+                disable_speaker();
             }
-            AudioMode::PcSpeaker => {
-                let Some(tone_hz) = estimate_tone_from_pcm(samples, sample_rate, src_channels)
-                else {
-                    return samples.len();
-                };
-                if state.pcm_hz_min == 0 || tone_hz < state.pcm_hz_min {
-                    state.pcm_hz_min = tone_hz;
-                }
-                if tone_hz > state.pcm_hz_max {
-                    state.pcm_hz_max = tone_hz;
-                }
-                if state.pcm_last_est_hz != 0 && state.pcm_last_est_hz != tone_hz {
-                    state.pcm_tone_switches = state.pcm_tone_switches.saturating_add(1);
-                }
-                state.pcm_last_est_hz = tone_hz;
+            state.active = false;
+            state.tone_hz = 0;
+            state.next_tone_update_tick = 0;
+        } else if state.mode == AudioMode::Virtio {
+            virtio_sound::set_enabled(true);
+        }
+        true
+    })
+}
 
-                let frame_count = samples
-                    .len()
-                    .checked_div(src_channels as usize)
-                    .unwrap_or(0)
-                    .max(1);
-                let safe_rate = sample_rate.max(1);
-                let hold_ticks = ((frame_count as u64) * u64::from(crate::time::PIT_HZ))
-                    .div_ceil(u64::from(safe_rate))
-                    .clamp(PCM_MIN_HOLD_TICKS, PCM_MAX_HOLD_TICKS);
-                apply_tone(state, tone_hz, hold_ticks);
-                samples.len()
-            }
+/// Master volume in percent; scales virtio PCM and beeps, while the pc speaker can only be on
+/// or off. Returns false when unchanged.
+#[cfg(feature = "desktop")]
+pub fn set_volume(percent: u8) -> bool {
+    let percent = percent.min(MAX_VOLUME);
+    with_state_mut(|state| {
+        if state.volume == percent {
+            return false;
         }
+        state.volume = percent;
+        virtio_sound::set_volume(percent);
+        true
     })
 }
 
+#[cfg(feature = "desktop")]
+pub fn volume() -> u8 {
+    with_state_mut(|state| state.volume)
+}
+
+pub fn set_route(owner: AudioOwner, route: Route) {
+    with_state_mut(|state| state.routes.set(owner, route));
+}
+
+pub fn route_status(owner: AudioOwner) -> RouteStatus {
+    with_state_mut(|state| state.routes.status(owner))
+}
+
+/// Enables or disables ducking doom while its window is open but unfocused.
+pub fn set_focus_duck(enabled: bool) {
+    with_state_mut(|state| state.routes.focus_duck = enabled);
+}
+
+pub fn focus_duck() -> bool {
+    with_state_mut(|state| state.routes.focus_duck)
+}
+
+/// Reported by the doom runtime whenever it re-checks its window.
+#[cfg(feature = "doom")]
+pub fn set_doom_unfocused(unfocused: bool) {
+    with_state_mut(|state| state.routes.doom_unfocused = unfocused);
+}
+
 pub fn poll(now_ticks: u64) {
     with_state_mut(|state| {
+        start_queued_beeps(state, now_ticks);
         virtio_sound::poll();
         if state.mode == AudioMode::Virtio {
             let virt = virtio_sound::status();
@@ -349,83 +437,26 @@ pub fn poll(now_ticks: u64) {
     });
 }
 
-fn estimate_tone_from_pcm(samples: &[i16], sample_rate: u32, channels: u8) -> Option<u16> {
-    let stride = channels.clamp(1, 2) as usize;
-    let frame_count = samples.len() / stride;
-    if frame_count < 8 || sample_rate < 2_000 {
-        return None;
-    }
-
-    let first_sample = if stride == 1 {
-        samples[0]
-    } else {
-        let left = i32::from(samples[0]);
-        let right = i32::from(samples[1]);
-        ((left + right) / 2) as i16
-    };
-    let mut abs_sum = 0u64;
-    let mut zero_crossings = 0u32;
-    let mut prev_sign = sample_sign(first_sample, PCM_DYNAMIC_THRESHOLD_MIN);
-
-    for frame in 0..frame_count {
-        let idx = frame * stride;
-        let value = if stride == 1 {
-            samples[idx]
-        } else {
-            let left = i32::from(samples[idx]);
-            let right = i32::from(samples[idx + 1]);
-            ((left + right) / 2) as i16
-        };
-        abs_sum = abs_sum.saturating_add(u64::from(value.unsigned_abs()));
-    }
-
-    let avg_energy = abs_sum / (frame_count as u64);
-    if avg_energy < PCM_MIN_ENERGY {
-        return None;
-    }
-    let dynamic_threshold = ((avg_energy / PCM_DYNAMIC_THRESHOLD_DIV) as i16)
-        .clamp(PCM_DYNAMIC_THRESHOLD_MIN, PCM_DYNAMIC_THRESHOLD_MAX);
-
-    for frame in 1..frame_count {
-        let idx = frame * stride;
-        let sample = if stride == 1 {
-            samples[idx]
-        } else {
-            let left = i32::from(samples[idx]);
-            let right = i32::from(samples[idx + 1]);
-            ((left + right) / 2) as i16
-        };
-        let sign = sample_sign(sample, dynamic_threshold);
-        if sign != 0 && prev_sign != 0 && sign != prev_sign {
-            zero_crossings = zero_crossings.saturating_add(1);
+/// Next tick the audio path needs polling: now while beeps wait to start, every tick while
+/// virtio packets, FIFO samples or beep voices are in flight, or when the pc-speaker tone is
+/// due to stop. While `audio-refill` runs, its own timer covers the virtio stream.
+pub fn next_deadline(now_ticks: u64) -> Option<u64> {
+    with_state_mut(|state| {
+        if state.beeps.pending() {
+            return Some(now_ticks);
         }
-        if sign != 0 {
-            prev_sign = sign;
+        match state.mode {
+            #[cfg(feature = "doom")]
+            AudioMode::Virtio if virtio_sound::refill_active() => None,
+            AudioMode::Virtio => {
+                let virtio = virtio_sound::status();
+                (virtio.pending_packets > 0 || virtio.buffered_frames > 0 || virtio.beep_voices > 0)
+                    .then_some(now_ticks.saturating_add(1))
+            }
+            AudioMode::PcSpeaker => state.active.then_some(state.stop_tick),
+            AudioMode::Off => None,
         }
-    }
-
-    if zero_crossings < 2 {
-        let span = u32::from(PCM_ENERGY_FALLBACK_HZ_MAX - PCM_ENERGY_FALLBACK_HZ_MIN);
-        let scaled = avg_energy.min(PCM_ENERGY_FALLBACK_REF) as u32;
-        let mapped = u32::from(PCM_ENERGY_FALLBACK_HZ_MIN)
-            .saturating_add(span.saturating_mul(scaled) / (PCM_ENERGY_FALLBACK_REF as u32));
-        return Some(mapped as u16);
-    }
-
-    let estimate_hz = ((u64::from(zero_crossings) * u64::from(sample_rate))
-        / (2 * (frame_count as u64)))
-        .clamp(u64::from(PCM_MIN_EST_HZ), u64::from(PCM_MAX_EST_HZ)) as u16;
-    Some(estimate_hz)
-}
-
-fn sample_sign(sample: i16, threshold: i16) -> i8 {
-    if sample >= threshold {
-        1
-    } else if sample <= -threshold {
-        -1
-    } else {
-        0
-    }
+    })
 }
 
 fn apply_tone(state: &mut AudioState, tone_hz: u16, hold_ticks: u64) {
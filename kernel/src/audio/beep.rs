@@ -0,0 +1,291 @@
+// kernel/src/audio/beep.rs: queued UI feedback tones (bell, alerts) with amplitude envelopes.
+
+/// Requests waiting for the next `audio::poll`; beeps can be raised from anywhere, including
+/// paths that already hold other subsystem state, and are rendered on the main loop.
+const QUEUE_CAPACITY: usize = 8;
+pub const MIN_FREQ_HZ: u32 = 40;
+pub const MAX_FREQ_HZ: u32 = 8_000;
+/// Longest beep; the whole tone is mixed into the device FIFO at once, which holds ~0.5 s.
+pub const MAX_MS: u32 = 400;
+/// Peak amplitude of one voice; several overlapping beeps still leave headroom.
+const PEAK: i64 = 7_000;
+/// Beeps sounding at once; a new beep beyond this replaces the one closest to finishing.
+const MAX_VOICES: usize = 4;
+/// Longest a note sounds without its note-off, so a lost one cannot hold a voice for good.
+#[cfg(feature = "net")]
+pub const NOTE_MAX_MS: u32 = 5_000;
+/// C4..B4 (MIDI keys 60..71) in hundredths of a hertz; other octaves are shifts of these.
+#[cfg(feature = "net")]
+const OCTAVE4_CENTI_HZ: [u32; 12] = [
+    26_163, 27_718, 29_366, 31_113, 32_963, 34_923, 36_999, 39_200, 41_530, 44_000, 46_616, 49_388,
+];
+
+/// Equal-tempered frequency of MIDI key `key` (69 = A4 = 440 Hz), clamped to the beep range.
+#[cfg(feature = "net")]
+pub fn midi_key_hz(key: u8) -> u32 {
+    let octave = u32::from(key / 12);
+    let centi_hz = OCTAVE4_CENTI_HZ[usize::from(key % 12)];
+    let centi_hz = if octave >= 5 {
+        centi_hz << (octave - 5)
+    } else {
+        centi_hz >> (5 - octave)
+    };
+    (centi_hz / 100).clamp(MIN_FREQ_HZ, MAX_FREQ_HZ)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Envelope {
+    /// Constant level with 5 ms ramps so the edges do not click.
+    Flat,
+    /// Instant attack, linear decay to silence: bell and key feedback.
+    Pluck,
+    /// Rises to the midpoint and falls back: alerts.
+    Swell,
+}
+
+impl Envelope {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Flat => "flat",
+            Self::Pluck => "pluck",
+            Self::Swell => "swell",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "flat" => Some(Self::Flat),
+            "pluck" => Some(Self::Pluck),
+            "swell" => Some(Self::Swell),
+            _ => None,
+        }
+    }
+
+    /// Gain in 0..=32767 at frame `pos` of `total`.
+    pub(super) fn gain(self, pos: usize, total: usize, rate_hz: u32) -> i32 {
+        let total = total.max(1) as i64;
+        let pos = pos as i64;
+        let gain = match self {
+            Self::Flat => {
+                let ramp = (i64::from(rate_hz) / 200).max(1);
+                (pos * 32767 / ramp).min((total - pos) * 32767 / ramp)
+            }
+            Self::Pluck => {
+                let attack = (i64::from(rate_hz) / 500).max(1);
+                (pos * 32767 / attack).min((total - pos) * 32767 / total)
+            }
+            Self::Swell => (pos.min(total - pos) * 2 * 32767) / total,
+        };
+        gain.clamp(0, 32767) as i32
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct BeepRequest {
+    pub freq_hz: u32,
+    pub ms: u32,
+    pub envelope: Envelope,
+}
+
+/// Triangle oscillator in 32.32 fixed point; shared with the `doom audio test` sweep.
+#[derive(Clone, Copy)]
+pub(super) struct Oscillator {
+    phase_fp: u64,
+}
+
+impl Oscillator {
+    pub(super) const fn new() -> Self {
+        Self { phase_fp: 0 }
+    }
+
+    /// Next sample in -32768..=32767 for `freq_hz` at `rate_hz`.
+    pub(super) fn next(&mut self, freq_hz: u32, rate_hz: u32) -> i32 {
+        let step_fp = (u64::from(freq_hz) << 32) / u64::from(rate_hz.max(1));
+        self.phase_fp = self.phase_fp.wrapping_add(step_fp.max(1));
+        let tri_phase = ((self.phase_fp >> 16) & 0xFFFF) as i32;
+        (if tri_phase < 0x8000 {
+            tri_phase - 0x4000
+        } else {
+            0xC000 - tri_phase
+        }) * 2
+    }
+}
+
+pub(super) struct BeepQueue {
+    pending: [Option<BeepRequest>; QUEUE_CAPACITY],
+    pub(super) queued: u64,
+    pub(super) played: u64,
+    /// Beeps that overlapped one already sounding and were summed with it.
+    pub(super) overlapped: u64,
+    pub(super) dropped: u64,
+}
+
+impl BeepQueue {
+    pub(super) const fn new() -> Self {
+        Self {
+            pending: [None; QUEUE_CAPACITY],
+            queued: 0,
+            played: 0,
+            overlapped: 0,
+            dropped: 0,
+        }
+    }
+
+    pub(super) fn push(&mut self, request: BeepRequest) -> bool {
+        match self.pending.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(request);
+                self.queued = self.queued.saturating_add(1);
+                true
+            }
+            None => {
+                self.dropped = self.dropped.saturating_add(1);
+                false
+            }
+        }
+    }
+
+    pub(super) fn pop(&mut self) -> Option<BeepRequest> {
+        self.pending.iter_mut().find_map(Option::take)
+    }
+
+    pub(super) fn pending(&self) -> bool {
+        self.pending.iter().any(Option::is_some)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Voice {
+    request: BeepRequest,
+    oscillator: Oscillator,
+    rate_hz: u32,
+    pos: usize,
+    frames: usize,
+    ducked: bool,
+    /// MIDI key of a held note; `None` for a beep.
+    #[cfg(feature = "net")]
+    key: Option<u8>,
+    peak: i64,
+}
+
+impl Voice {
+    fn new(request: BeepRequest, rate_hz: u32, ducked: bool, peak: i64) -> Self {
+        let frames = ((u64::from(request.ms) * u64::from(rate_hz)) / 1000) as usize;
+        Self {
+            request,
+            oscillator: Oscillator::new(),
+            rate_hz,
+            pos: 0,
+            frames: frames.max(1),
+            ducked,
+            #[cfg(feature = "net")]
+            key: None,
+            peak,
+        }
+    }
+}
+
+/// Beeps currently sounding on the virtio stream. The driver sums them into each TX packet as
+/// it is built, on top of whatever PCM the FIFO holds, so overlapping beeps and game audio mix
+/// instead of queueing behind each other.
+pub(super) struct VoiceBank {
+    voices: [Option<Voice>; MAX_VOICES],
+}
+
+impl VoiceBank {
+    pub(super) const fn new() -> Self {
+        Self {
+            voices: [None; MAX_VOICES],
+        }
+    }
+
+    /// Returns `true` when the beep joined at least one voice that was already sounding.
+    pub(super) fn start(&mut self, request: BeepRequest, rate_hz: u32, ducked: bool) -> bool {
+        self.start_voice(Voice::new(request, rate_hz, ducked, PEAK))
+    }
+
+    /// Starts a held note for `key` at `velocity` (1..=127), replacing one already sounding
+    /// for that key. It plays with a flat envelope until `release` or `NOTE_MAX_MS`.
+    #[cfg(feature = "net")]
+    pub(super) fn start_note(&mut self, key: u8, velocity: u8, rate_hz: u32, ducked: bool) -> bool {
+        for slot in &mut self.voices {
+            if slot.is_some_and(|voice| voice.key == Some(key)) {
+                *slot = None;
+            }
+        }
+        let request = BeepRequest {
+            freq_hz: midi_key_hz(key),
+            ms: NOTE_MAX_MS,
+            envelope: Envelope::Flat,
+        };
+        let peak = PEAK * i64::from(velocity.min(127)) / 127;
+        self.start_voice(Voice {
+            key: Some(key),
+            ..Voice::new(request, rate_hz, ducked, peak)
+        })
+    }
+
+    /// Ends the note for `key` with the flat envelope's 5 ms ramp.
+    #[cfg(feature = "net")]
+    pub(super) fn release(&mut self, key: u8) {
+        for voice in self.voices.iter_mut().flatten() {
+            if voice.key == Some(key) {
+                let ramp = (voice.rate_hz / 200).max(1) as usize;
+                voice.frames = voice.frames.min(voice.pos + ramp);
+                voice.key = None;
+            }
+        }
+    }
+
+    fn start_voice(&mut self, voice: Voice) -> bool {
+        let overlapped = self.active() > 0;
+        let slot = match self.voices.iter().position(Option::is_none) {
+            Some(free) => free,
+            None => (0..MAX_VOICES)
+                .max_by_key(|&index| self.voices[index].map_or(0, |v| v.pos * 1000 / v.frames))
+                .unwrap_or(0),
+        };
+        self.voices[slot] = Some(voice);
+        overlapped
+    }
+
+    pub(super) fn active(&self) -> usize {
+        self.voices.iter().filter(|voice| voice.is_some()).count()
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.voices = [None; MAX_VOICES];
+    }
+
+    /// Adds every voice onto interleaved `out` with saturation and retires finished voices.
+    pub(super) fn mix_into(&mut self, out: &mut [i16], channels: usize) {
+        let channels = channels.clamp(1, 2);
+        for slot in &mut self.voices {
+            let Some(voice) = slot else {
+                continue;
+            };
+            for frame in out.chunks_exact_mut(channels) {
+                if voice.pos >= voice.frames {
+                    break;
+                }
+                let wave = voice.oscillator.next(voice.request.freq_hz, voice.rate_hz);
+                let gain = voice
+                    .request
+                    .envelope
+                    .gain(voice.pos, voice.frames, voice.rate_hz);
+                let mut sample =
+                    (i64::from(wave) * i64::from(gain) * voice.peak / (32767 * 32767)) as i16;
+                if voice.ducked {
+                    sample = super::route::duck_sample(sample);
+                }
+                for out in frame {
+                    *out = out.saturating_add(sample);
+                }
+                voice.pos += 1;
+            }
+            if voice.pos >= voice.frames {
+                *slot = None;
+            }
+        }
+    }
+}
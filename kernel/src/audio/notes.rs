@@ -0,0 +1,119 @@
+// kernel/src/audio/notes.rs: held MIDI notes for the `midi` net service, which is their only
+// source, so this builds with `net`.
+use super::beep;
+use super::route::{AudioOwner, Route};
+use super::{
+    AudioMode, AudioState, disable_speaker, enable_speaker, program_channel2, virtio_sound,
+    with_state_mut,
+};
+
+/// Keys `note_on` can hold at once; a further key replaces the one held longest.
+const MAX_HELD_NOTES: usize = 8;
+
+/// A key between `note_on` and `note_off`; its length is counted against the `midi` route
+/// on release.
+#[derive(Clone, Copy)]
+struct HeldNote {
+    key: u8,
+    started_tick: u64,
+}
+
+pub(super) struct HeldNotes {
+    notes: [Option<HeldNote>; MAX_HELD_NOTES],
+    /// Key whose tone the pc speaker is playing, so only its note-off silences it.
+    speaker_key: Option<u8>,
+}
+
+impl HeldNotes {
+    pub(super) const fn new() -> Self {
+        Self {
+            notes: [None; MAX_HELD_NOTES],
+            speaker_key: None,
+        }
+    }
+}
+
+/// Starts MIDI key `key` at `velocity` (1..=127) on the `midi` route. It sounds until
+/// `note_off` for the key, or at most `beep::NOTE_MAX_MS`. On virtio notes are voices mixed with
+/// beeps and PCM; the pc speaker plays only the newest one. False when it is not played.
+pub fn note_on(key: u8, velocity: u8) -> bool {
+    let now_ticks = crate::time::ticks();
+    with_state_mut(|state| {
+        release_held_note(state, key, now_ticks);
+        let slot = match state.held.notes.iter().position(Option::is_none) {
+            Some(free) => free,
+            None => {
+                let oldest = (0..MAX_HELD_NOTES)
+                    .min_by_key(|&index| {
+                        state.held.notes[index].map_or(0, |note| note.started_tick)
+                    })
+                    .unwrap_or(0);
+                if let Some(note) = state.held.notes[oldest] {
+                    release_held_note(state, note.key, now_ticks);
+                }
+                oldest
+            }
+        };
+        state.held.notes[slot] = Some(HeldNote {
+            key,
+            started_tick: now_ticks,
+        });
+        let route = state.routes.effective(AudioOwner::Midi);
+        if state.muted || route == Route::Off {
+            return false;
+        }
+        match state.mode {
+            AudioMode::Off => false,
+            AudioMode::Virtio => virtio_sound::start_note(key, velocity, route == Route::Duck),
+            AudioMode::PcSpeaker => {
+                let tone_hz = beep::midi_key_hz(key) as u16;
+                program_channel2(tone_hz);
+                enable_speaker();
+                state.tone_hz = tone_hz;
+                state.active = true;
+                state.stop_tick = now_ticks.saturating_add(
+                    u64::from(beep::NOTE_MAX_MS) * u64::from(crate::time::PIT_HZ) / 1000,
+                );
+                state.held.speaker_key = Some(key);
+                true
+            }
+        }
+    })
+}
+
+/// Releases MIDI key `key`; false when it was not held.
+pub fn note_off(key: u8) -> bool {
+    let now_ticks = crate::time::ticks();
+    with_state_mut(|state| release_held_note(state, key, now_ticks))
+}
+
+fn release_held_note(state: &mut AudioState, key: u8, now_ticks: u64) -> bool {
+    let Some(slot) = state
+        .held
+        .notes
+        .iter_mut()
+        .find(|slot| slot.is_some_and(|note| note.key == key))
+    else {
+        return false;
+    };
+    let Some(note) = slot.take() else {
+        return false;
+    };
+    let rate_hz = match virtio_sound::status().sample_rate_hz {
+        0 => 1000,
+        rate => rate,
+    };
+    let held_ticks = now_ticks.saturating_sub(note.started_tick);
+    let frames = held_ticks * u64::from(rate_hz) / u64::from(crate::time::PIT_HZ);
+    state.routes.account(AudioOwner::Midi, frames as usize);
+    virtio_sound::release_note(key);
+    if state.held.speaker_key == Some(key) {
+        state.held.speaker_key = None;
+        if state.mode == AudioMode::PcSpeaker && state.active {
+            disable_speaker();
+            state.active = false;
+            state.tone_hz = 0;
+        }
+    }
+    true
+}
@@ -0,0 +1,385 @@
+// kernel/src/audio/pcm.rs: PCM submission (format widening, routing, pc-speaker tone estimate)
+// and its counters; doom and its app bridge are the only clients, so this builds with `doom`.
+use super::beep::Oscillator;
+use super::route::{self, AudioOwner, Route};
+use super::wav::{self, WavClip, WavError};
+use super::{
+    AudioMode, PCM_MAX_HOLD_TICKS, apply_tone, resample, status, virtio_sound, with_state_mut,
+};
+
+const PCM_MIN_HOLD_TICKS: u64 = 3;
+const PCM_MIN_ENERGY: u64 = 240;
+const PCM_MIN_EST_HZ: u16 = 90;
+const PCM_MAX_EST_HZ: u16 = 2400;
+const PCM_DYNAMIC_THRESHOLD_DIV: u64 = 10;
+const PCM_DYNAMIC_THRESHOLD_MIN: i16 = 40;
+const PCM_DYNAMIC_THRESHOLD_MAX: i16 = 1400;
+const PCM_ENERGY_FALLBACK_HZ_MIN: u16 = 160;
+const PCM_ENERGY_FALLBACK_HZ_MAX: u16 = 920;
+const PCM_ENERGY_FALLBACK_REF: u64 = 14_000;
+const PCM_CONVERT_CHUNK_SAMPLES: usize = 1024;
+
+/// Sample encodings accepted at the submit boundary; everything is widened to S16 before mixing.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PcmFormat {
+    S16,
+    S8,
+    U8,
+}
+
+impl PcmFormat {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::S16 => "s16",
+            Self::S8 => "s8",
+            Self::U8 => "u8",
+        }
+    }
+
+    pub const fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::S16 => 2,
+            Self::S8 | Self::U8 => 1,
+        }
+    }
+}
+
+/// `doom audio status` counters; the device-side ones count every TX packet, beeps included.
+#[derive(Clone, Copy)]
+pub struct PcmStatus {
+    pub tone_hz: u16,
+    pub mix_events: u64,
+    pub samples: u64,
+    pub tone_switches: u64,
+    pub hz_min: u16,
+    pub hz_max: u16,
+    pub queue_pending: u16,
+    pub buffered_frames: u32,
+    pub packets_submitted: u64,
+    pub packets_completed: u64,
+    pub packets_dropped: u64,
+    pub frames_completed: u64,
+    pub frames_dropped: u64,
+    pub stream_id: u32,
+    pub last_ctrl_status: u32,
+    pub src_rate_hz: u32,
+    pub src_channels: u8,
+    pub src_format: PcmFormat,
+}
+
+pub(super) struct PcmState {
+    mix_events: u64,
+    samples: u64,
+    tone_switches: u64,
+    hz_min: u16,
+    hz_max: u16,
+    last_est_hz: u16,
+    src_rate_hz: u32,
+    src_channels: u8,
+    src_format: PcmFormat,
+}
+
+impl PcmState {
+    pub(super) const fn new() -> Self {
+        Self {
+            mix_events: 0,
+            samples: 0,
+            tone_switches: 0,
+            hz_min: 0,
+            hz_max: 0,
+            last_est_hz: 0,
+            src_rate_hz: 0,
+            src_channels: 0,
+            src_format: PcmFormat::S16,
+        }
+    }
+
+    /// Zeroes the counters; the last source format is kept.
+    pub(super) fn reset_metrics(&mut self) {
+        *self = Self {
+            src_format: self.src_format,
+            ..Self::new()
+        };
+    }
+}
+
+pub fn pcm_status() -> PcmStatus {
+    with_state_mut(|state| {
+        let virtio = virtio_sound::status();
+        let counters = virtio_sound::counters();
+        PcmStatus {
+            tone_hz: if state.mode == AudioMode::Virtio {
+                0
+            } else {
+                state.tone_hz
+            },
+            mix_events: state.pcm.mix_events,
+            samples: state.pcm.samples,
+            tone_switches: state.pcm.tone_switches,
+            hz_min: state.pcm.hz_min,
+            hz_max: state.pcm.hz_max,
+            queue_pending: virtio.pending_packets,
+            buffered_frames: virtio.buffered_frames,
+            packets_submitted: counters.submitted_packets,
+            packets_completed: counters.completed_packets,
+            packets_dropped: counters.dropped_packets,
+            frames_completed: counters.completed_frames,
+            frames_dropped: counters.dropped_frames,
+            stream_id: counters.stream_id,
+            last_ctrl_status: counters.last_ctrl_status,
+            src_rate_hz: state.pcm.src_rate_hz,
+            src_channels: state.pcm.src_channels,
+            src_format: state.pcm.src_format,
+        }
+    })
+}
+
+pub fn reset_runtime_metrics() {
+    with_state_mut(|state| {
+        state.pcm.reset_metrics();
+        state.routes.reset_metrics();
+        virtio_sound::reset_runtime_metrics();
+    });
+}
+
+/// Queues a short sweep; 8-bit formats are rendered mono so both the channel and
+/// sample-width converters are exercised.
+pub fn play_test_tone(format: PcmFormat, rate_hz: u32) -> bool {
+    if status().mode == AudioMode::Off || rate_hz == 0 {
+        return false;
+    }
+
+    const TEST_FRAMES: usize = 1024;
+    let test_rate_hz = rate_hz;
+    let mut stereo = [0i16; TEST_FRAMES * 2];
+    let mut oscillator = Oscillator::new();
+
+    for frame in 0..TEST_FRAMES {
+        let freq_hz =
+            440u32.saturating_add((330u32.saturating_mul(frame as u32)) / TEST_FRAMES as u32);
+        let tri = oscillator.next(freq_hz, test_rate_hz);
+        let fade_in = ((frame as i32) * 32767) / 96;
+        let fade_out = (((TEST_FRAMES - frame) as i32) * 32767) / 128;
+        let envelope = fade_in.min(fade_out).clamp(0, 32767);
+        let sample_num = i64::from(tri) * i64::from(envelope) * 7_000i64;
+        let sample = (sample_num / i64::from(32767 * 32767))
+            .clamp(i64::from(i16::MIN), i64::from(i16::MAX)) as i16;
+
+        let index = frame * 2;
+        stereo[index] = sample;
+        stereo[index + 1] = sample;
+    }
+
+    let submitted = match format {
+        PcmFormat::S16 => submit_pcm_i16(AudioOwner::Tone, &stereo, test_rate_hz, 2),
+        PcmFormat::S8 | PcmFormat::U8 => {
+            let mut mono = [0u8; TEST_FRAMES];
+            for (byte, frame) in mono.iter_mut().zip(stereo.as_chunks::<2>().0) {
+                let high = (frame[0] >> 8) as i8;
+                *byte = if format == PcmFormat::U8 {
+                    (high as u8) ^ 0x80
+                } else {
+                    high as u8
+                };
+            }
+            submit_pcm(AudioOwner::Tone, &mono, format, test_rate_hz, 1)
+        }
+    };
+    submitted > 0
+}
+
+/// Parses an uncompressed PCM WAV clip and queues it through the format/rate converter.
+pub fn play_wav(bytes: &[u8]) -> Result<WavClip<'_>, WavError> {
+    let clip = wav::parse(bytes)?;
+    let _ = submit_pcm(
+        AudioOwner::Player,
+        clip.data,
+        clip.format,
+        clip.sample_rate,
+        clip.channels,
+    );
+    Ok(clip)
+}
+
+/// Submits interleaved PCM in any `PcmFormat`; 8-bit input is widened to S16 in small chunks
+/// and then follows the same resampling path as `submit_pcm_i16`. Returns bytes consumed.
+pub fn submit_pcm(
+    owner: AudioOwner,
+    data: &[u8],
+    format: PcmFormat,
+    sample_rate: u32,
+    channels: u8,
+) -> usize {
+    let width = format.bytes_per_sample();
+    let src_channels = usize::from(channels.clamp(1, 2));
+    let frame_bytes = width * src_channels;
+    let usable = data.len() - data.len() % frame_bytes;
+    if usable == 0 {
+        return 0;
+    }
+
+    let mut wide = [0i16; PCM_CONVERT_CHUNK_SAMPLES];
+    let chunk_bytes = PCM_CONVERT_CHUNK_SAMPLES / src_channels * frame_bytes;
+    for chunk in data[..usable].chunks(chunk_bytes) {
+        let count = chunk.len() / width;
+        for (index, sample) in wide[..count].iter_mut().enumerate() {
+            *sample = resample::decode_sample(format, &chunk[index * width..]);
+        }
+        let _ = submit_pcm_i16(owner, &wide[..count], sample_rate, channels);
+    }
+    with_state_mut(|state| state.pcm.src_format = format);
+    usable
+}
+
+/// Applies `owner`'s route: `off` drops the samples, `duck` attenuates them in chunks.
+pub fn submit_pcm_i16(owner: AudioOwner, samples: &[i16], sample_rate: u32, channels: u8) -> usize {
+    if samples.is_empty() {
+        return 0;
+    }
+    match with_state_mut(|state| state.routes.account(owner, samples.len())) {
+        Route::Off => samples.len(),
+        Route::On => queue_pcm_i16(samples, sample_rate, channels),
+        Route::Duck => {
+            let mut ducked = [0i16; PCM_CONVERT_CHUNK_SAMPLES];
+            for chunk in samples.chunks(PCM_CONVERT_CHUNK_SAMPLES) {
+                for (out, &sample) in ducked.iter_mut().zip(chunk) {
+                    *out = route::duck_sample(sample);
+                }
+                let _ = queue_pcm_i16(&ducked[..chunk.len()], sample_rate, channels);
+            }
+            samples.len()
+        }
+    }
+}
+
+fn queue_pcm_i16(samples: &[i16], sample_rate: u32, channels: u8) -> usize {
+    let src_channels = channels.clamp(1, 2);
+
+    with_state_mut(|state| {
+        state.pcm.mix_events = state.pcm.mix_events.saturating_add(1);
+        state.pcm.src_rate_hz = sample_rate;
+        state.pcm.src_channels = src_channels;
+        state.pcm.src_format = PcmFormat::S16;
+        state.pcm.samples = state.pcm.samples.saturating_add(samples.len() as u64);
+        if state.muted {
+            return samples.len();
+        }
+
+        match state.mode {
+            AudioMode::Off => samples.len(),
+            AudioMode::Virtio => {
+                let queued = virtio_sound::submit_pcm_i16(samples, sample_rate, src_channels);
+                state.active = queued > 0 || virtio_sound::status().pending_packets > 0;
+                samples.len()
+            }
+            AudioMode::PcSpeaker if state.volume == 0 => samples.len(),
+            AudioMode::PcSpeaker => {
+                let Some(tone_hz) = estimate_tone_from_pcm(samples, sample_rate, src_channels)
+                else {
+                    return samples.len();
+                };
+                if state.pcm.hz_min == 0 || tone_hz < state.pcm.hz_min {
+                    state.pcm.hz_min = tone_hz;
+                }
+                if tone_hz > state.pcm.hz_max {
+                    state.pcm.hz_max = tone_hz;
+                }
+                if state.pcm.last_est_hz != 0 && state.pcm.last_est_hz != tone_hz {
+                    state.pcm.tone_switches = state.pcm.tone_switches.saturating_add(1);
+                }
+                state.pcm.last_est_hz = tone_hz;
+
+                let frame_count = samples
+                    .len()
+                    .checked_div(src_channels as usize)
+                    .unwrap_or(0)
+                    .max(1);
+                let safe_rate = sample_rate.max(1);
+                let hold_ticks = ((frame_count as u64) * u64::from(crate::time::PIT_HZ))
+                    .div_ceil(u64::from(safe_rate))
+                    .clamp(PCM_MIN_HOLD_TICKS, PCM_MAX_HOLD_TICKS);
+                apply_tone(state, tone_hz, hold_ticks);
+                samples.len()
+            }
+        }
+    })
+}
+
+fn estimate_tone_from_pcm(samples: &[i16], sample_rate: u32, channels: u8) -> Option<u16> {
+    let stride = channels.clamp(1, 2) as usize;
+    let frame_count = samples.len() / stride;
+    if frame_count < 8 || sample_rate < 2_000 {
+        return None;
+    }
+
+    let first_sample = if stride == 1 {
+        samples[0]
+    } else {
+        let left = i32::from(samples[0]);
+        let right = i32::from(samples[1]);
+        ((left + right) / 2) as i16
+    };
+    let mut abs_sum = 0u64;
+    let mut zero_crossings = 0u32;
+    let mut prev_sign = sample_sign(first_sample, PCM_DYNAMIC_THRESHOLD_MIN);
+
+    for frame in 0..frame_count {
+        let idx = frame * stride;
+        let value = if stride == 1 {
+            samples[idx]
+        } else {
+            let left = i32::from(samples[idx]);
+            let right = i32::from(samples[idx + 1]);
+            ((left + right) / 2) as i16
+        };
+        abs_sum = abs_sum.saturating_add(u64::from(value.unsigned_abs()));
+    }
+
+    let avg_energy = abs_sum / (frame_count as u64);
+    if avg_energy < PCM_MIN_ENERGY {
+        return None;
+    }
+    let dynamic_threshold = ((avg_energy / PCM_DYNAMIC_THRESHOLD_DIV) as i16)
+        .clamp(PCM_DYNAMIC_THRESHOLD_MIN, PCM_DYNAMIC_THRESHOLD_MAX);
+
+    for frame in 1..frame_count {
+        let idx = frame * stride;
+        let sample = if stride == 1 {
+            samples[idx]
+        } else {
+            let left = i32::from(samples[idx]);
+            let right = i32::from(samples[idx + 1]);
+            ((left + right) / 2) as i16
+        };
+        let sign = sample_sign(sample, dynamic_threshold);
+        if sign != 0 && prev_sign != 0 && sign != prev_sign {
+            zero_crossings = zero_crossings.saturating_add(1);
+        }
+        if sign != 0 {
+            prev_sign = sign;
+        }
+    }
+
+    if zero_crossings < 2 {
+        let span = u32::from(PCM_ENERGY_FALLBACK_HZ_MAX - PCM_ENERGY_FALLBACK_HZ_MIN);
+        let scaled = avg_energy.min(PCM_ENERGY_FALLBACK_REF) as u32;
+        let mapped = u32::from(PCM_ENERGY_FALLBACK_HZ_MIN)
+            .saturating_add(span.saturating_mul(scaled) / (PCM_ENERGY_FALLBACK_REF as u32));
+        return Some(mapped as u16);
+    }
+
+    let estimate_hz = ((u64::from(zero_crossings) * u64::from(sample_rate))
+        / (2 * (frame_count as u64)))
+        .clamp(u64::from(PCM_MIN_EST_HZ), u64::from(PCM_MAX_EST_HZ)) as u16;
+    Some(estimate_hz)
+}
+
+fn sample_sign(sample: i16, threshold: i16) -> i8 {
+    if sample >= threshold {
+        1
+    } else if sample <= -threshold {
+        -1
+    } else {
+        0
+    }
+}
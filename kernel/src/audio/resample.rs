@@ -0,0 +1,120 @@
+// kernel/src/audio/resample.rs: linear-interpolation rate conversion plus sample/channel format mapping.
+use super::PcmFormat;
+
+/// Streaming resampler; the 32.32 fixed-point phase and the last input frame carry across
+/// calls, so the last frame of a packet is interpolated against the first of the next one and
+/// packet boundaries do not click.
+pub(super) struct LinearResampler {
+    /// Position relative to `prev` when set, else to the first frame of the next input.
+    phase_fp: u64,
+    /// Last frame of the previous input as (left, right); it leads the next input.
+    prev: Option<(i16, i16)>,
+}
+
+impl LinearResampler {
+    pub(super) const fn new() -> Self {
+        Self {
+            phase_fp: 0,
+            prev: None,
+        }
+    }
+
+    pub(super) fn reset(&mut self) {
+        self.phase_fp = 0;
+        self.prev = None;
+    }
+
+    /// Converts interleaved `input` into `output` until either side runs out. The frame
+    /// under the phase is held back until the one after it arrives, so the output trails the
+    /// input by up to one frame.
+    /// Returns `(consumed_frames, produced_frames)`.
+    pub(super) fn process(
+        &mut self,
+        input: &[i16],
+        in_channels: usize,
+        output: &mut [i16],
+        out_channels: usize,
+        step_fp: u64,
+    ) -> (usize, usize) {
+        let in_channels = in_channels.clamp(1, 2);
+        let out_channels = out_channels.clamp(1, 2);
+        let frames = input.len() / in_channels;
+        let max_out = output.len() / out_channels;
+        if frames == 0 {
+            return (0, 0);
+        }
+        // Frames seen this call: `prev` (when set) followed by `input`.
+        let lead = usize::from(self.prev.is_some());
+        let total = frames + lead;
+        let prev = self.prev;
+        let frame = |index: usize| match index.checked_sub(lead) {
+            None => prev.unwrap_or((0, 0)),
+            Some(index) => {
+                let base = index * in_channels;
+                let left = input[base];
+                let right = if in_channels > 1 {
+                    input[base + 1]
+                } else {
+                    left
+                };
+                (left, right)
+            }
+        };
+
+        let mut produced = 0usize;
+        while produced < max_out {
+            let src_idx = (self.phase_fp >> 32) as usize;
+            if src_idx + 1 >= total {
+                break;
+            }
+            let frac = ((self.phase_fp & 0xFFFF_FFFF) >> 16) as u32;
+            let (src_left, src_right) = frame(src_idx);
+            let (next_left, next_right) = frame(src_idx + 1);
+            let left = lerp_i16(src_left, next_left, frac);
+            let right = lerp_i16(src_right, next_right, frac);
+            let out_base = produced * out_channels;
+            if out_channels > 1 {
+                output[out_base] = left;
+                output[out_base + 1] = right;
+            } else {
+                output[out_base] = ((i32::from(left) + i32::from(right)) / 2) as i16;
+            }
+
+            produced += 1;
+            self.phase_fp = self.phase_fp.saturating_add(step_fp.max(1));
+        }
+
+        // The frame under the phase becomes `prev`; everything up to it is consumed.
+        let held = ((self.phase_fp >> 32) as usize).min(total - 1);
+        let consumed = held + 1 - lead;
+        if consumed > 0 {
+            self.prev = Some(frame(held));
+        }
+        self.phase_fp -= (held as u64) << 32;
+        (consumed, produced)
+    }
+}
+
+/// Source frames advanced per output frame, in 32.32 fixed point.
+pub(super) fn step_fp(src_rate: u32, dst_rate: u32) -> u64 {
+    (u64::from(src_rate) << 32) / u64::from(dst_rate.max(1))
+}
+
+/// Widens one sample of `format` starting at `bytes[0]` to signed 16-bit.
+pub(super) fn decode_sample(format: PcmFormat, bytes: &[u8]) -> i16 {
+    match format {
+        PcmFormat::S16 => i16::from_le_bytes([bytes[0], bytes[1]]),
+        PcmFormat::S8 => i16::from(bytes[0] as i8) << 8,
+        PcmFormat::U8 => (i16::from(bytes[0]) - 128) << 8,
+    }
+}
+
+fn lerp_i16(a: i16, b: i16, frac: u32) -> i16 {
+    let start = i32::from(a);
+    let delta = i32::from(b).saturating_sub(start);
+    let scaled =
+        ((i64::from(delta).saturating_mul(i64::from(frac))).saturating_add(1i64 << 15)) >> 16;
+    start
+        .saturating_add(scaled.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32)
+        .clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16
+}
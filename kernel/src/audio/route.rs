@@ -0,0 +1,155 @@
+// kernel/src/audio/route.rs: per-owner routing rules (off/duck/on) and doom focus ducking.
+
+/// Producer a PCM submission is tagged with at the `submit_pcm*` boundary.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AudioOwner {
+    Doom,
+    /// `doom audio play <file.wav>` clips.
+    Player,
+    /// `doom audio test` sweeps.
+    Tone,
+    /// `audio::beep` UI feedback (shell bell, task crash alerts).
+    Beep,
+    /// `audio::note_on` notes from the `midi-udp` service.
+    Midi,
+}
+
+impl AudioOwner {
+    pub const ALL: [Self; 5] = [Self::Doom, Self::Player, Self::Tone, Self::Beep, Self::Midi];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Doom => "doom",
+            Self::Player => "play",
+            Self::Tone => "tone",
+            Self::Beep => "beep",
+            Self::Midi => "midi",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|owner| owner.as_str() == name)
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    Off,
+    Duck,
+    On,
+}
+
+impl Route {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Duck => "duck",
+            Self::On => "on",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Self::Off),
+            "duck" => Some(Self::Duck),
+            "on" => Some(Self::On),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RouteStatus {
+    pub owner: AudioOwner,
+    pub route: Route,
+    /// Route after focus ducking is applied.
+    pub effective: Route,
+    pub samples: u64,
+    pub dropped: u64,
+    pub ducked: u64,
+}
+
+pub(super) struct Routes {
+    routes: [Route; AudioOwner::ALL.len()],
+    samples: [u64; AudioOwner::ALL.len()],
+    dropped: [u64; AudioOwner::ALL.len()],
+    ducked: [u64; AudioOwner::ALL.len()],
+    /// Policy: duck doom while its window is open but unfocused.
+    pub(super) focus_duck: bool,
+    /// Last focus state reported by the doom runtime.
+    pub(super) doom_unfocused: bool,
+}
+
+impl Routes {
+    pub(super) const fn new() -> Self {
+        Self {
+            routes: [Route::On; AudioOwner::ALL.len()],
+            samples: [0; AudioOwner::ALL.len()],
+            dropped: [0; AudioOwner::ALL.len()],
+            ducked: [0; AudioOwner::ALL.len()],
+            focus_duck: true,
+            doom_unfocused: false,
+        }
+    }
+
+    pub(super) fn set(&mut self, owner: AudioOwner, route: Route) {
+        self.routes[owner.index()] = route;
+    }
+
+    /// A focus duck never raises an explicit `off`.
+    pub(super) fn effective(&self, owner: AudioOwner) -> Route {
+        let route = self.routes[owner.index()];
+        if route == Route::On && owner == AudioOwner::Doom && self.focus_duck && self.doom_unfocused
+        {
+            Route::Duck
+        } else {
+            route
+        }
+    }
+
+    /// Counts `samples` against `owner` and returns the route they take.
+    pub(super) fn account(&mut self, owner: AudioOwner, samples: usize) -> Route {
+        let route = self.effective(owner);
+        let index = owner.index();
+        let samples = samples as u64;
+        self.samples[index] = self.samples[index].saturating_add(samples);
+        match route {
+            Route::Off => self.dropped[index] = self.dropped[index].saturating_add(samples),
+            Route::Duck => self.ducked[index] = self.ducked[index].saturating_add(samples),
+            Route::On => {}
+        }
+        route
+    }
+
+    pub(super) fn status(&self, owner: AudioOwner) -> RouteStatus {
+        let index = owner.index();
+        RouteStatus {
+            owner,
+            route: self.routes[index],
+            effective: self.effective(owner),
+            samples: self.samples[index],
+            dropped: self.dropped[index],
+            ducked: self.ducked[index],
+        }
+    }
+
+    pub(super) fn reset_metrics(&mut self) {
+        self.samples = [0; AudioOwner::ALL.len()];
+        self.dropped = [0; AudioOwner::ALL.len()];
+        self.ducked = [0; AudioOwner::ALL.len()];
+    }
+}
+
+/// Scales a sample by the duck gain (-12 dB).
+pub(super) const fn duck_sample(sample: i16) -> i16 {
+    sample >> 2
+}
+
+/// Scales a sample by the master volume, 0..=100 percent.
+pub(super) const fn volume_sample(sample: i16, percent: u8) -> i16 {
+    (sample as i32 * percent as i32 / 100) as i16
+}
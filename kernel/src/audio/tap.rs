@@ -0,0 +1,318 @@
+// kernel/src/audio/tap.rs: output tap that mirrors every virtio TX packet, after beep mixing and
+// volume, into a rolling RAM window and saves it to the fs as a WAV on stop.
+use crate::fs::{self, FsError, MAX_FILE_NAME_BYTES};
+use crate::time;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+
+pub const DEFAULT_TAP_SECONDS: u32 = 5;
+pub const MAX_TAP_SECONDS: u32 = 10;
+const WAV_HEADER_BYTES: usize = 44;
+const MICROS_PER_TICK: u64 = 1_000_000 / time::PIT_HZ as u64;
+
+struct Tap {
+    active: bool,
+    path: [u8; MAX_FILE_NAME_BYTES],
+    path_len: usize,
+    seconds: u32,
+    rate_hz: u32,
+    channels: usize,
+    /// Interleaved device samples; `write` is the next slot once the ring has wrapped.
+    ring: Vec<i16>,
+    write: usize,
+    filled: usize,
+    packets: u64,
+    frames: u64,
+    gaps: u64,
+    gap_us: u64,
+    /// When the device runs out of tapped audio, assuming it plays at the stream rate.
+    drained_at_us: Option<u64>,
+    alloc_failed: bool,
+}
+
+impl Tap {
+    const fn new() -> Self {
+        Self {
+            active: false,
+            path: [0; MAX_FILE_NAME_BYTES],
+            path_len: 0,
+            seconds: DEFAULT_TAP_SECONDS,
+            rate_hz: 0,
+            channels: 0,
+            ring: Vec::new(),
+            write: 0,
+            filled: 0,
+            packets: 0,
+            frames: 0,
+            gaps: 0,
+            gap_us: 0,
+            drained_at_us: None,
+            alloc_failed: false,
+        }
+    }
+
+    /// Sizes the ring for the stream layout; an earlier window in another layout is dropped.
+    /// The ring never holds more than one fs file can store, so on ramfs it stays a few ms.
+    fn ensure_layout(&mut self, rate_hz: u32, channels: usize) -> bool {
+        if self.rate_hz == rate_hz && self.channels == channels && !self.ring.is_empty() {
+            return true;
+        }
+        self.rate_hz = rate_hz;
+        self.channels = channels;
+        self.ring = Vec::new();
+        self.write = 0;
+        self.filled = 0;
+        let fit_frames = fs::max_file_bytes().saturating_sub(WAV_HEADER_BYTES) / (channels * 2);
+        let samples = (self.seconds as usize)
+            .saturating_mul(rate_hz as usize)
+            .min(fit_frames)
+            .saturating_mul(channels);
+        if samples == 0 || self.ring.try_reserve_exact(samples).is_err() {
+            self.alloc_failed = true;
+            return false;
+        }
+        self.ring.resize(samples, 0);
+        true
+    }
+
+    fn push(&mut self, samples: &[i16]) {
+        let capacity = self.ring.len();
+        // Only the newest window's worth of a packet larger than the ring can survive.
+        let samples = &samples[samples.len().saturating_sub(capacity)..];
+        let first = samples.len().min(capacity - self.write);
+        self.ring[self.write..self.write + first].copy_from_slice(&samples[..first]);
+        self.ring[..samples.len() - first].copy_from_slice(&samples[first..]);
+        self.write = (self.write + samples.len()) % capacity;
+        self.filled = (self.filled + samples.len()).min(capacity);
+    }
+
+    /// Ring contents oldest first.
+    fn window(&self) -> impl Iterator<Item = i16> + '_ {
+        let start = if self.filled < self.ring.len() {
+            0
+        } else {
+            self.write
+        };
+        self.ring[start..]
+            .iter()
+            .chain(self.ring[..start].iter())
+            .take(self.filled)
+            .copied()
+    }
+
+    fn status(&self) -> TapStatus {
+        let channels = self.channels.max(1);
+        let mut silent_frames = 0u64;
+        let mut peak = 0u16;
+        let mut frame_silent = true;
+        for (index, sample) in self.window().enumerate() {
+            peak = peak.max(sample.unsigned_abs());
+            frame_silent &= sample == 0;
+            if index % channels == channels - 1 {
+                silent_frames += u64::from(frame_silent);
+                frame_silent = true;
+            }
+        }
+        // Until the first packet sizes the ring, the requested window is all there is to show.
+        let capacity_ms = if self.rate_hz == 0 {
+            u64::from(self.seconds) * 1_000
+        } else {
+            (self.ring.len() / channels) as u64 * 1_000 / u64::from(self.rate_hz)
+        };
+        TapStatus {
+            active: self.active,
+            capacity_ms,
+            rate_hz: self.rate_hz,
+            channels: self.channels as u8,
+            packets: self.packets,
+            frames: self.frames,
+            window_frames: (self.filled / channels) as u64,
+            silent_frames,
+            peak,
+            gaps: self.gaps,
+            gap_ms: self.gap_us / 1_000,
+            alloc_failed: self.alloc_failed,
+        }
+    }
+}
+
+struct TapCell(UnsafeCell<Tap>);
+
+// SAFETY: the tap is fed from the virtio pump and driven by the shell, both on the main loop thread.
+unsafe impl Sync for TapCell {}
+
+static TAP: TapCell = TapCell(UnsafeCell::new(Tap::new()));
+
+fn with_tap<R>(f: impl FnOnce(&mut Tap) -> R) -> R {
+    // SAFETY: see `TapCell`; the mutable reference does not escape the closure.
+    unsafe { f(&mut *TAP.0.get()) }
+}
+
+/// What `audio tap` reports. Window figures cover the frames still in the ring.
+#[derive(Clone, Copy)]
+pub struct TapStatus {
+    pub active: bool,
+    /// How much audio the ring holds: the requested seconds, cut to one fs file.
+    pub capacity_ms: u64,
+    pub rate_hz: u32,
+    pub channels: u8,
+    pub packets: u64,
+    pub frames: u64,
+    pub window_frames: u64,
+    pub silent_frames: u64,
+    pub peak: u16,
+    /// Times a packet arrived after the device had played everything tapped before it.
+    pub gaps: u64,
+    pub gap_ms: u64,
+    pub alloc_failed: bool,
+}
+
+impl TapStatus {
+    pub fn frames_to_ms(&self, frames: u64) -> u64 {
+        if self.rate_hz == 0 {
+            return 0;
+        }
+        frames.saturating_mul(1_000) / u64::from(self.rate_hz)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum TapError {
+    AlreadyActive,
+    NotActive,
+    BadPath,
+    NoMemory,
+    Fs(FsError),
+}
+
+impl TapError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::AlreadyActive => "already_active",
+            Self::NotActive => "not_active",
+            Self::BadPath => "bad_path",
+            Self::NoMemory => "no_memory",
+            Self::Fs(err) => err.as_str(),
+        }
+    }
+}
+
+/// Result of `audio tap stop`.
+#[derive(Clone, Copy)]
+pub struct TapSaved {
+    pub status: TapStatus,
+    path: [u8; MAX_FILE_NAME_BYTES],
+    path_len: usize,
+    pub saved_frames: u64,
+    pub bytes: usize,
+    pub truncated: bool,
+}
+
+impl TapSaved {
+    pub fn path(&self) -> &str {
+        core::str::from_utf8(&self.path[..self.path_len]).unwrap_or("?")
+    }
+}
+
+pub fn start(path: &str, seconds: u32) -> Result<(), TapError> {
+    if path.is_empty() || path.len() > MAX_FILE_NAME_BYTES {
+        return Err(TapError::BadPath);
+    }
+    with_tap(|tap| {
+        if tap.active {
+            return Err(TapError::AlreadyActive);
+        }
+        *tap = Tap::new();
+        tap.path[..path.len()].copy_from_slice(path.as_bytes());
+        tap.path_len = path.len();
+        tap.seconds = seconds.clamp(1, MAX_TAP_SECONDS);
+        tap.active = true;
+        Ok(())
+    })
+}
+
+pub fn status() -> TapStatus {
+    with_tap(|tap| tap.status())
+}
+
+/// Mirrors one packet exactly as it was handed to the device.
+pub(super) fn record(samples: &[i16], rate_hz: u32, channels: usize) {
+    with_tap(|tap| {
+        if !tap.active || rate_hz == 0 || channels == 0 {
+            return;
+        }
+        let frames = (samples.len() / channels) as u64;
+        let now_us = time::ticks().saturating_mul(MICROS_PER_TICK);
+        if let Some(drained_at) = tap.drained_at_us
+            && now_us > drained_at
+        {
+            tap.gaps = tap.gaps.saturating_add(1);
+            tap.gap_us = tap.gap_us.saturating_add(now_us - drained_at);
+        }
+        let played_from = tap.drained_at_us.map_or(now_us, |at| at.max(now_us));
+        tap.drained_at_us =
+            Some(played_from.saturating_add(frames * 1_000_000 / u64::from(rate_hz)));
+        tap.packets = tap.packets.saturating_add(1);
+        tap.frames = tap.frames.saturating_add(frames);
+        if tap.ensure_layout(rate_hz, channels) {
+            tap.push(samples);
+        }
+    });
+}
+
+/// Ends the tap and writes the window as a 16-bit PCM WAV. The ring was sized to fit one
+/// file, so only a backend switch since then (diskfs lost on `reload`) cuts it short.
+pub fn stop() -> Result<TapSaved, TapError> {
+    let mut file = Vec::new();
+    let saved = with_tap(|tap| {
+        if !tap.active {
+            return Err(TapError::NotActive);
+        }
+        tap.active = false;
+        let status = tap.status();
+        let channels = tap.channels.max(1);
+        let frame_bytes = channels * 2;
+        let fit_frames =
+            (fs::max_file_bytes().saturating_sub(WAV_HEADER_BYTES) / frame_bytes) as u64;
+        let saved_frames = status.window_frames.min(fit_frames);
+        let data_len = saved_frames as usize * frame_bytes;
+        if file.try_reserve_exact(WAV_HEADER_BYTES + data_len).is_err() {
+            tap.ring = Vec::new();
+            return Err(TapError::NoMemory);
+        }
+        file.resize(WAV_HEADER_BYTES, 0);
+        write_wav_header(&mut file, tap.rate_hz, channels as u16, data_len);
+        let skip = tap.filled - saved_frames as usize * channels;
+        for sample in tap.window().skip(skip) {
+            file.extend_from_slice(&sample.to_le_bytes());
+        }
+        tap.ring = Vec::new();
+        Ok(TapSaved {
+            status,
+            path: tap.path,
+            path_len: tap.path_len,
+            saved_frames,
+            bytes: file.len(),
+            truncated: saved_frames < status.window_frames,
+        })
+    })?;
+    fs::write_file(saved.path(), &file).map_err(TapError::Fs)?;
+    Ok(saved)
+}
+
+fn write_wav_header(file: &mut [u8], rate_hz: u32, channels: u16, data_len: usize) {
+    let block_align = channels * 2;
+    file[0..4].copy_from_slice(b"RIFF");
+    file[4..8].copy_from_slice(&((WAV_HEADER_BYTES - 8 + data_len) as u32).to_le_bytes());
+    file[8..12].copy_from_slice(b"WAVE");
+    file[12..16].copy_from_slice(b"fmt ");
+    file[16..20].copy_from_slice(&16u32.to_le_bytes());
+    file[20..22].copy_from_slice(&1u16.to_le_bytes());
+    file[22..24].copy_from_slice(&channels.to_le_bytes());
+    file[24..28].copy_from_slice(&rate_hz.to_le_bytes());
+    file[28..32].copy_from_slice(&(rate_hz * u32::from(block_align)).to_le_bytes());
+    file[32..34].copy_from_slice(&block_align.to_le_bytes());
+    file[34..36].copy_from_slice(&16u16.to_le_bytes());
+    file[36..40].copy_from_slice(b"data");
+    file[40..44].copy_from_slice(&(data_len as u32).to_le_bytes());
+}
@@ -1,6 +1,13 @@
 // kernel/src/audio/virtio_sound.rs: modern virtio-sound playback backend (PCM TX queue).
+use super::beep::{BeepRequest, VoiceBank};
+#[cfg(feature = "doom")]
+use super::resample::{self, LinearResampler};
+use super::{route, tap};
 use crate::arch::x86_64::port;
+use crate::evlog::{self, Event};
 use crate::mem;
+#[cfg(feature = "doom")]
+use crate::proc::executor;
 use core::cell::UnsafeCell;
 use core::hint::spin_loop;
 use core::mem::size_of;
@@ -40,17 +47,33 @@ const TX_SLOT_COUNT: usize = 10;
 const TX_DESC_PER_SLOT: usize = 3;
 
 const MAX_CONTROL_SPINS: usize = 2_000_000;
-const MAX_TX_CHUNK_FRAMES: usize = 1024;
+#[cfg(feature = "doom")]
 const MAX_RESAMPLE_FRAMES: usize = 2048;
 const MAX_STREAM_CHANNELS: usize = 2;
+#[cfg(feature = "doom")]
 const MAX_RESAMPLE_SAMPLES: usize = MAX_RESAMPLE_FRAMES * MAX_STREAM_CHANNELS;
+/// Largest period; a TX packet holds exactly one period.
 const TX_PACKET_FRAMES: usize = 1024;
 const TX_PACKET_SAMPLES: usize = TX_PACKET_FRAMES * 2;
+/// Periods are sized from the negotiated rate to last about this long, rounded up to a power
+/// of two: 1024 frames at 44.1k/48k, 512 at 22.05k, 256 at 11.025k.
+const PERIOD_TARGET_MS: u32 = 23;
+const MIN_PERIOD_FRAMES: usize = 256;
 const PCM_BUFFER_PERIODS: u32 = 8;
 const PCM_FIFO_FRAMES: usize = TX_PACKET_FRAMES * 24;
 const PCM_FIFO_SAMPLES: usize = PCM_FIFO_FRAMES * MAX_STREAM_CHANNELS;
+#[cfg(feature = "doom")]
 const PCM_FIFO_TARGET_FRAMES: u32 = TX_PACKET_FRAMES as u32 * 6;
+#[cfg(feature = "doom")]
 const PCM_FIFO_HIGH_WATER_FRAMES: u32 = TX_PACKET_FRAMES as u32 * 10;
+/// Beep-only packets are built just this many periods ahead of the device, so a beep raised a
+/// moment later still lands on frames that have not been handed over yet and mixes with the first.
+const BEEP_LEAD_PERIODS: u32 = 2;
+/// Jacks and PCM streams kept from the info queries; extra ones are counted but not listed.
+pub const MAX_JACKS: usize = 4;
+pub const MAX_STREAMS: usize = 8;
+
+const VIRTIO_SND_R_JACK_INFO: u32 = 0x0001;
 
 const VIRTIO_SND_R_PCM_INFO: u32 = 0x0100;
 const VIRTIO_SND_R_PCM_SET_PARAMS: u32 = 0x0101;
@@ -244,6 +267,28 @@ impl VirtioSndPcmInfo {
     };
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct VirtioSndJackInfo {
+    hda_fn_nid: u32,
+    features: u32,
+    hda_reg_defconf: u32,
+    hda_reg_caps: u32,
+    connected: u8,
+    _padding: [u8; 7],
+}
+
+impl VirtioSndJackInfo {
+    const EMPTY: Self = Self {
+        hda_fn_nid: 0,
+        features: 0,
+        hda_reg_defconf: 0,
+        hda_reg_caps: 0,
+        connected: 0,
+        _padding: [0; 7],
+    };
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 struct VirtioSndPcmSetParams {
@@ -357,14 +402,57 @@ pub struct VirtioSoundInitReport {
     pub reason: &'static str,
 }
 
+#[derive(Clone, Copy)]
+pub struct JackInfo {
+    pub hda_fn_nid: u32,
+    pub connected: bool,
+    /// HDA pin default configuration and capabilities, as the device reports them.
+    pub defconf: u32,
+    pub caps: u32,
+}
+
+#[derive(Clone, Copy)]
+pub struct StreamInfo {
+    pub hda_fn_nid: u32,
+    pub output: bool,
+    /// Bit `n` set for `VIRTIO_SND_PCM_FMT_*` / `VIRTIO_SND_PCM_RATE_*` value `n`.
+    pub formats: u64,
+    pub rates: u64,
+    pub channels_min: u8,
+    pub channels_max: u8,
+}
+
+/// What the device reported through its config space and the jack/PCM info queries.
+#[derive(Clone, Copy)]
+pub struct VirtioSoundDevices {
+    pub present: bool,
+    pub jacks: u32,
+    pub streams: u32,
+    pub chmaps: u32,
+    pub jack_infos: [Option<JackInfo>; MAX_JACKS],
+    /// `ok`, `none` or the control-queue error of the jack query; it does not fail init.
+    pub jack_query: &'static str,
+    pub stream_infos: [Option<StreamInfo>; MAX_STREAMS],
+    pub selected_stream: Option<u32>,
+    pub period_frames: usize,
+    pub buffer_periods: u32,
+}
+
 #[derive(Clone, Copy)]
 pub struct VirtioSoundStatus {
     pub ready: bool,
-    pub stream_id: u32,
     pub sample_rate_hz: u32,
     pub channels: u8,
     pub pending_packets: u16,
     pub buffered_frames: u32,
+    pub beep_voices: u8,
+}
+
+/// TX packet and control counters for the PCM status line.
+#[cfg(feature = "doom")]
+#[derive(Clone, Copy)]
+pub struct VirtioSoundCounters {
+    pub stream_id: u32,
     pub submitted_packets: u64,
     pub completed_packets: u64,
     pub dropped_packets: u64,
@@ -396,17 +484,30 @@ struct DriverState {
     stream_rate_enum: u8,
     channels: u8,
     started: bool,
-    resample_phase_fp: u64,
+    #[cfg(feature = "doom")]
+    resampler: LinearResampler,
     tx_slot_busy: [bool; TX_SLOT_COUNT],
     tx_slot_frames: [u16; TX_SLOT_COUNT],
-    resample_tmp: [i16; MAX_RESAMPLE_SAMPLES],
     pcm_fifo: [i16; PCM_FIFO_SAMPLES],
     pcm_fifo_read: usize,
     pcm_fifo_write: usize,
     pcm_fifo_samples: usize,
     pending_hw_frames: u32,
+    /// Set while the `audio-refill` service moves FIFO frames into TX slots; `poll` leaves
+    /// the pump to it meanwhile.
+    #[cfg(feature = "doom")]
+    refill_active: bool,
+    voices: VoiceBank,
+    /// Master gain in percent, applied to each packet after beeps are mixed in.
+    volume: u8,
     ctrl_status: VirtioSndHdr,
-    pcm_infos: [VirtioSndPcmInfo; TX_SLOT_COUNT],
+    device_cfg_snapshot: VirtioSndConfig,
+    jack_infos: [VirtioSndJackInfo; MAX_JACKS],
+    jack_count: usize,
+    jack_query: &'static str,
+    pcm_infos: [VirtioSndPcmInfo; MAX_STREAMS],
+    pcm_count: usize,
+    period_frames: usize,
     pending_packets: u16,
     submitted_packets: u64,
     completed_packets: u64,
@@ -442,17 +543,31 @@ impl DriverState {
             stream_rate_enum: 0,
             channels: 0,
             started: false,
-            resample_phase_fp: 0,
+            #[cfg(feature = "doom")]
+            resampler: LinearResampler::new(),
             tx_slot_busy: [false; TX_SLOT_COUNT],
             tx_slot_frames: [0; TX_SLOT_COUNT],
-            resample_tmp: [0; MAX_RESAMPLE_SAMPLES],
             pcm_fifo: [0; PCM_FIFO_SAMPLES],
             pcm_fifo_read: 0,
             pcm_fifo_write: 0,
             pcm_fifo_samples: 0,
             pending_hw_frames: 0,
+            #[cfg(feature = "doom")]
+            refill_active: false,
+            voices: VoiceBank::new(),
+            volume: 100,
             ctrl_status: VirtioSndHdr { code: 0 },
-            pcm_infos: [VirtioSndPcmInfo::EMPTY; TX_SLOT_COUNT],
+            device_cfg_snapshot: VirtioSndConfig {
+                jacks: 0,
+                streams: 0,
+                chmaps: 0,
+            },
+            jack_infos: [VirtioSndJackInfo::EMPTY; MAX_JACKS],
+            jack_count: 0,
+            jack_query: "none",
+            pcm_infos: [VirtioSndPcmInfo::EMPTY; MAX_STREAMS],
+            pcm_count: 0,
+            period_frames: TX_PACKET_FRAMES,
             pending_packets: 0,
             submitted_packets: 0,
             completed_packets: 0,
@@ -477,11 +592,18 @@ impl DriverState {
     fn status(&self) -> VirtioSoundStatus {
         VirtioSoundStatus {
             ready: self.ready,
-            stream_id: self.stream_id,
             sample_rate_hz: self.stream_rate_hz,
             channels: self.channels,
             pending_packets: self.pending_packets,
             buffered_frames: self.total_buffered_frames(),
+            beep_voices: self.voices.active() as u8,
+        }
+    }
+
+    #[cfg(feature = "doom")]
+    fn counters(&self) -> VirtioSoundCounters {
+        VirtioSoundCounters {
+            stream_id: self.stream_id,
             submitted_packets: self.submitted_packets,
             completed_packets: self.completed_packets,
             dropped_packets: self.dropped_packets,
@@ -491,6 +613,47 @@ impl DriverState {
         }
     }
 
+    fn devices(&self) -> VirtioSoundDevices {
+        let mut jack_infos = [None; MAX_JACKS];
+        for (slot, info) in jack_infos
+            .iter_mut()
+            .zip(&self.jack_infos[..self.jack_count])
+        {
+            *slot = Some(JackInfo {
+                hda_fn_nid: info.hda_fn_nid,
+                connected: info.connected != 0,
+                defconf: info.hda_reg_defconf,
+                caps: info.hda_reg_caps,
+            });
+        }
+        let mut stream_infos = [None; MAX_STREAMS];
+        for (slot, info) in stream_infos
+            .iter_mut()
+            .zip(&self.pcm_infos[..self.pcm_count])
+        {
+            *slot = Some(StreamInfo {
+                hda_fn_nid: info.hda_fn_nid,
+                output: info.direction == VIRTIO_SND_D_OUTPUT,
+                formats: info.formats,
+                rates: info.rates,
+                channels_min: info.channels_min,
+                channels_max: info.channels_max,
+            });
+        }
+        VirtioSoundDevices {
+            present: !self.device_cfg.is_null(),
+            jacks: self.device_cfg_snapshot.jacks,
+            streams: self.device_cfg_snapshot.streams,
+            chmaps: self.device_cfg_snapshot.chmaps,
+            jack_infos,
+            jack_query: self.jack_query,
+            stream_infos,
+            selected_stream: self.ready.then_some(self.stream_id),
+            period_frames: self.period_frames,
+            buffer_periods: PCM_BUFFER_PERIODS,
+        }
+    }
+
     fn init_once(&mut self) -> VirtioSoundInitReport {
         if self.initialized {
             return self.report();
@@ -507,6 +670,25 @@ impl DriverState {
         self.report()
     }
 
+    /// Resets the device and probes it again from scratch; only the master volume survives,
+    /// along with the note that an `audio-refill` service is still around.
+    fn restart(&mut self) -> VirtioSoundInitReport {
+        if !self.common_cfg.is_null() {
+            // SAFETY: if common_cfg is non-null it points to valid mapped common cfg.
+            unsafe { write_volatile(addr_of_mut!((*self.common_cfg).device_status), 0) };
+        }
+        let volume = self.volume;
+        #[cfg(feature = "doom")]
+        let refill_active = self.refill_active;
+        *self = Self::new();
+        self.volume = volume;
+        #[cfg(feature = "doom")]
+        {
+            self.refill_active = refill_active;
+        }
+        self.init_once()
+    }
+
     fn reset_runtime_metrics(&mut self) {
         self.pending_packets = 0;
         self.submitted_packets = 0;
@@ -514,12 +696,14 @@ impl DriverState {
         self.dropped_packets = 0;
         self.completed_frames = 0;
         self.dropped_frames = 0;
-        self.resample_phase_fp = 0;
+        #[cfg(feature = "doom")]
+        self.resampler.reset();
         self.pending_hw_frames = 0;
         self.pcm_fifo_read = 0;
         self.pcm_fifo_write = 0;
         self.pcm_fifo_samples = 0;
         self.pcm_fifo.fill(0);
+        self.voices.clear();
     }
 
     fn try_init(&mut self) -> Result<(), &'static str> {
@@ -579,6 +763,14 @@ impl DriverState {
             })?;
 
         let cfg = self.read_device_cfg();
+        self.device_cfg_snapshot = cfg;
+        if cfg.jacks > 0 {
+            let jack_count = cfg.jacks.min(MAX_JACKS as u32);
+            self.jack_query = match self.query_jack_info(jack_count) {
+                Ok(()) => "ok",
+                Err(reason) => self.ctrl_error_reason(reason),
+            };
+        }
         if cfg.streams == 0 {
             return Err("virtio_snd_no_streams");
         }
@@ -785,8 +977,32 @@ impl DriverState {
         }
     }
 
+    fn query_jack_info(&mut self, count: u32) -> Result<(), &'static str> {
+        self.jack_infos.fill(VirtioSndJackInfo::EMPTY);
+        self.jack_count = 0;
+        let query = VirtioSndQueryInfo {
+            hdr: VirtioSndHdr {
+                code: VIRTIO_SND_R_JACK_INFO,
+            },
+            start_id: 0,
+            count,
+            size: size_of::<VirtioSndJackInfo>() as u32,
+        };
+        let count = (count as usize).min(self.jack_infos.len());
+        let info_ptr = core::ptr::addr_of_mut!(self.jack_infos) as *mut VirtioSndJackInfo;
+        self.send_ctrl(
+            addr_of!(query) as *const u8,
+            size_of::<VirtioSndQueryInfo>(),
+            info_ptr as *mut u8,
+            count.saturating_mul(size_of::<VirtioSndJackInfo>()),
+        )?;
+        self.jack_count = count;
+        Ok(())
+    }
+
     fn query_pcm_info(&mut self, count: u32) -> Result<(), &'static str> {
         self.pcm_infos.fill(VirtioSndPcmInfo::EMPTY);
+        self.pcm_count = 0;
         let query = VirtioSndQueryInfo {
             hdr: VirtioSndHdr {
                 code: VIRTIO_SND_R_PCM_INFO,
@@ -805,6 +1021,7 @@ impl DriverState {
             info_ptr as *mut u8,
             resp_len,
         )?;
+        self.pcm_count = (count as usize).min(self.pcm_infos.len());
         Ok(())
     }
 
@@ -844,12 +1061,13 @@ impl DriverState {
         self.stream_id = stream_id;
         self.stream_rate_enum = rate_enum;
         self.stream_rate_hz = rate_hz;
+        self.period_frames = period_frames(rate_hz);
         Some(())
     }
 
     fn send_set_params(&mut self) -> Result<(), &'static str> {
         let frame_bytes = usize::from(self.channels).saturating_mul(size_of::<i16>());
-        let period_bytes = TX_PACKET_FRAMES.saturating_mul(frame_bytes) as u32;
+        let period_bytes = self.period_frames.saturating_mul(frame_bytes) as u32;
         let buffer_bytes = period_bytes.saturating_mul(PCM_BUFFER_PERIODS);
         let params = VirtioSndPcmSetParams {
             hdr: VirtioSndHdr {
@@ -909,6 +1127,10 @@ impl DriverState {
                 self.completed_packets = self.completed_packets.saturating_add(1);
                 let frame_count = u32::from(self.tx_slot_frames[slot]);
                 self.pending_hw_frames = self.pending_hw_frames.saturating_sub(frame_count);
+                if self.started && self.pending_hw_frames == 0 && frame_count > 0 {
+                    let fifo_frames = self.pcm_fifo_samples / usize::from(self.channels.max(1));
+                    evlog::record(Event::PcmUnderrun, fifo_frames as u32, frame_count);
+                }
                 self.completed_frames =
                     self.completed_frames.saturating_add(u64::from(frame_count));
                 self.tx_slot_frames[slot] = 0;
@@ -942,13 +1164,16 @@ impl DriverState {
                 self.pcm_fifo_read = 0;
                 self.pcm_fifo_write = 0;
                 self.pcm_fifo_samples = 0;
-                self.resample_phase_fp = 0;
+                #[cfg(feature = "doom")]
+                self.resampler.reset();
+                self.voices.clear();
             } else {
                 self.pump_fifo_to_tx();
             }
         }
     }
 
+    #[cfg(feature = "doom")]
     fn submit_pcm_i16(&mut self, samples: &[i16], src_rate: u32, src_channels: u8) -> usize {
         if !self.ready || !self.started || samples.is_empty() || src_rate == 0 {
             return 0;
@@ -960,66 +1185,33 @@ impl DriverState {
         }
         self.pump_fifo_to_tx();
 
-        let mut consumed_frames = 0usize;
-        let src_step_fp = ((u64::from(src_rate)) << 32) / u64::from(self.stream_rate_hz.max(1));
-        let mut phase = self.resample_phase_fp;
+        let step_fp = resample::step_fp(src_rate, self.stream_rate_hz);
         let output_channels = usize::from(self.channels.clamp(1, 2));
-        let source_samples = &samples[..src_frames.saturating_mul(input_channels)];
-
-        for chunk in source_samples.chunks(MAX_TX_CHUNK_FRAMES.saturating_mul(input_channels)) {
-            if chunk.is_empty() {
-                continue;
-            }
-            let chunk_frames = chunk.len() / input_channels;
-            if chunk_frames == 0 {
-                continue;
-            }
-
-            let mut produced_frames = 0usize;
-            while produced_frames < MAX_RESAMPLE_FRAMES {
-                let src_idx = (phase >> 32) as usize;
-                if src_idx >= chunk_frames {
-                    break;
-                }
-
-                let next_idx = (src_idx + 1).min(chunk_frames.saturating_sub(1));
-                let src_base = src_idx.saturating_mul(input_channels);
-                let next_base = next_idx.saturating_mul(input_channels);
-                let frac = ((phase & 0xFFFF_FFFF) >> 16) as u32;
-
-                let src_l = lerp_i16(chunk[src_base], chunk[next_base], frac);
-                let src_r = if input_channels > 1 {
-                    lerp_i16(chunk[src_base + 1], chunk[next_base + 1], frac)
-                } else {
-                    src_l
-                };
-                let out_base = produced_frames.saturating_mul(output_channels);
-                self.resample_tmp[out_base] = src_l;
-                if output_channels > 1 {
-                    self.resample_tmp[out_base + 1] = src_r;
-                }
-
-                produced_frames += 1;
-                phase = phase.saturating_add(src_step_fp.max(1));
-            }
-            let chunk_limit = (chunk_frames as u64) << 32;
-            if phase >= chunk_limit {
-                phase = phase.saturating_sub(chunk_limit);
-            }
-
-            consumed_frames = consumed_frames.saturating_add(chunk_frames);
+        let mut remaining = &samples[..src_frames.saturating_mul(input_channels)];
+
+        while !remaining.is_empty() {
+            let mut local = [0i16; MAX_RESAMPLE_SAMPLES];
+            let out_len = MAX_RESAMPLE_FRAMES.saturating_mul(output_channels);
+            let (consumed_frames, produced_frames) = self.resampler.process(
+                remaining,
+                input_channels,
+                &mut local[..out_len],
+                output_channels,
+                step_fp,
+            );
             let produced_samples = produced_frames.saturating_mul(output_channels);
             if produced_samples > 0 {
-                let mut local = [0i16; MAX_RESAMPLE_SAMPLES];
-                local[..produced_samples].copy_from_slice(&self.resample_tmp[..produced_samples]);
                 self.push_fifo_samples(&local[..produced_samples], output_channels);
                 self.trim_fifo_if_needed(output_channels);
                 self.pump_fifo_to_tx();
             }
+            if consumed_frames == 0 && produced_frames == 0 {
+                break;
+            }
+            remaining = &remaining[consumed_frames.saturating_mul(input_channels)..];
         }
 
-        self.resample_phase_fp = phase;
-        consumed_frames.saturating_mul(input_channels)
+        src_frames.saturating_mul(input_channels)
     }
 
     fn enqueue_tx_packet(
@@ -1133,6 +1325,7 @@ impl DriverState {
         self.tx_slot_frames[slot] = frame_count as u16;
         self.pending_packets = self.pending_packets.saturating_add(1);
         self.pending_hw_frames = self.pending_hw_frames.saturating_add(frame_count as u32);
+        evlog::record(Event::PcmSubmit, frame_count as u32, self.pending_hw_frames);
         self.submitted_packets = self.submitted_packets.saturating_add(1);
         true
     }
@@ -1154,6 +1347,7 @@ impl DriverState {
         PCM_FIFO_FRAMES.saturating_mul(channels.clamp(1, 2))
     }
 
+    #[cfg(feature = "doom")]
     fn fifo_drop_oldest_samples(&mut self, drop_samples: usize, channels: usize) {
         if drop_samples == 0 || self.pcm_fifo_samples == 0 {
             return;
@@ -1176,6 +1370,7 @@ impl DriverState {
             .saturating_add((dropped / channels) as u64);
     }
 
+    #[cfg(feature = "doom")]
     fn push_fifo_samples(&mut self, samples: &[i16], channels: usize) {
         if samples.is_empty() {
             return;
@@ -1260,6 +1455,7 @@ impl DriverState {
         self.pcm_fifo_samples = self.pcm_fifo_samples.saturating_sub(consumed);
     }
 
+    #[cfg(feature = "doom")]
     fn trim_fifo_if_needed(&mut self, channels: usize) {
         let channels = channels.clamp(1, 2);
         let total = self.total_buffered_frames();
@@ -1274,6 +1470,11 @@ impl DriverState {
         drop_frames = drop_frames.min(fifo_frames);
         let drop_samples = (drop_frames as usize).saturating_mul(channels);
         self.fifo_drop_oldest_samples(drop_samples, channels);
+        evlog::record(
+            Event::PcmTrim,
+            drop_frames,
+            (self.pcm_fifo_samples / channels) as u32,
+        );
     }
 
     fn pump_fifo_to_tx(&mut self) {
@@ -1293,38 +1494,137 @@ impl DriverState {
                 break;
             }
             let available_frames = self.pcm_fifo_samples / channels;
-            if available_frames == 0 {
+            let beep_only = available_frames == 0;
+            if beep_only
+                && (self.voices.active() == 0
+                    || self.pending_hw_frames >= self.period_frames as u32 * BEEP_LEAD_PERIODS)
+            {
                 break;
             }
 
-            let frame_count = available_frames.min(TX_PACKET_FRAMES);
+            let frame_count = if beep_only {
+                self.period_frames
+            } else {
+                available_frames.min(self.period_frames)
+            };
             let sample_count = frame_count.saturating_mul(channels);
             if sample_count == 0 || sample_count > local.len() {
                 break;
             }
-            if !self.copy_fifo_prefix(&mut local, sample_count, channels) {
+            if beep_only {
+                local[..sample_count].fill(0);
+            } else if !self.copy_fifo_prefix(&mut local, sample_count, channels) {
                 break;
             }
+            self.voices.mix_into(&mut local[..sample_count], channels);
+            if self.volume < 100 {
+                for sample in &mut local[..sample_count] {
+                    *sample = route::volume_sample(*sample, self.volume);
+                }
+            }
             if !self.enqueue_tx_packet(&local[..sample_count], frame_count, channels) {
                 break;
             }
-            self.consume_fifo_samples(sample_count, channels);
+            tap::record(&local[..sample_count], self.stream_rate_hz, channels);
+            if !beep_only {
+                self.consume_fifo_samples(sample_count, channels);
+            }
         }
     }
 
+    fn start_beep(&mut self, request: BeepRequest, ducked: bool) -> Option<bool> {
+        if !self.ready || !self.started {
+            return None;
+        }
+        let overlapped = self.voices.start(request, self.stream_rate_hz, ducked);
+        self.pump_fifo_to_tx();
+        Some(overlapped)
+    }
+
+    #[cfg(feature = "net")]
+    fn start_note(&mut self, key: u8, velocity: u8, ducked: bool) -> bool {
+        if !self.ready || !self.started {
+            return false;
+        }
+        self.voices
+            .start_note(key, velocity, self.stream_rate_hz, ducked);
+        self.pump_fifo_to_tx();
+        true
+    }
+
     fn poll(&mut self) {
+        #[cfg(feature = "doom")]
+        if self.refill_active {
+            return;
+        }
         self.pump_fifo_to_tx();
     }
+
+    /// One `audio-refill` pass: takes back finished TX slots and fills them from the FIFO.
+    /// Returns the ticks until the next slot is due back, or `None` once nothing is queued.
+    #[cfg(feature = "doom")]
+    fn refill(&mut self) -> Option<u64> {
+        self.pump_fifo_to_tx();
+        if !self.ready || !self.started || (self.pcm_fifo_samples == 0 && self.pending_packets == 0)
+        {
+            return None;
+        }
+        let period_ticks = self.period_frames as u64 * u64::from(crate::time::PIT_HZ)
+            / u64::from(self.stream_rate_hz.max(1));
+        Some(period_ticks.max(1))
+    }
+}
+
+/// Doom PCM refill as an executor service, started by the first `submit_pcm_i16` that queues
+/// frames: it wakes once a period instead of on every run-loop pass, and ends when the FIFO
+/// and the device queue have drained.
+#[cfg(feature = "doom")]
+async fn refill_service() {
+    let _active = RefillActive;
+    while let Some(ticks) = with_state_mut(DriverState::refill) {
+        // SIGTERM/SIGINT hand the pump back to `poll`; the stream keeps playing.
+        if executor::unless_terminated(executor::sleep_ticks(ticks))
+            .await
+            .is_none()
+        {
+            return;
+        }
+    }
+}
+
+/// Hands the pump back to `poll` however the service ends, `kill -KILL` included.
+#[cfg(feature = "doom")]
+struct RefillActive;
+
+#[cfg(feature = "doom")]
+impl Drop for RefillActive {
+    fn drop(&mut self) {
+        with_state_mut(|state| state.refill_active = false);
+    }
 }
 
 pub fn init() -> VirtioSoundInitReport {
     with_state_mut(DriverState::init_once)
 }
 
+pub fn restart() -> VirtioSoundInitReport {
+    with_state_mut(DriverState::restart)
+}
+
 pub fn status() -> VirtioSoundStatus {
     with_state_mut(|state| state.status())
 }
 
+pub fn devices() -> VirtioSoundDevices {
+    with_state_mut(|state| state.devices())
+}
+
+#[cfg(feature = "doom")]
+pub fn counters() -> VirtioSoundCounters {
+    with_state_mut(|state| state.counters())
+}
+
+#[cfg(feature = "doom")]
 pub fn reset_runtime_metrics() {
     with_state_mut(DriverState::reset_runtime_metrics);
 }
@@ -1337,8 +1637,48 @@ pub fn set_enabled(enabled: bool) {
     with_state_mut(|state| state.set_started(enabled));
 }
 
+#[cfg(feature = "desktop")]
+pub fn set_volume(percent: u8) {
+    with_state_mut(|state| state.volume = percent);
+}
+
+#[cfg(feature = "doom")]
 pub fn submit_pcm_i16(samples: &[i16], sample_rate: u32, channels: u8) -> usize {
-    with_state_mut(|state| state.submit_pcm_i16(samples, sample_rate, channels))
+    let (queued, start_refill) = with_state_mut(|state| {
+        let queued = state.submit_pcm_i16(samples, sample_rate, channels);
+        let start_refill = queued > 0 && !state.refill_active;
+        state.refill_active |= start_refill;
+        (queued, start_refill)
+    });
+    // Without a free slot `poll` keeps pumping from the run loop.
+    if start_refill && !executor::spawn("audio-refill", refill_service()) {
+        with_state_mut(|state| state.refill_active = false);
+    }
+    queued
+}
+
+/// Whether the `audio-refill` service is pumping, so `audio::next_deadline` can leave the
+/// wake-ups to its timer.
+#[cfg(feature = "doom")]
+pub fn refill_active() -> bool {
+    with_state_mut(|state| state.refill_active)
+}
+
+/// Starts a beep voice on the stream; `None` when the device is not playing, otherwise
+/// whether the beep overlapped one that was already sounding.
+pub fn start_beep(request: BeepRequest, ducked: bool) -> Option<bool> {
+    with_state_mut(|state| state.start_beep(request, ducked))
+}
+
+/// Starts a held note voice; false when the device is not playing.
+#[cfg(feature = "net")]
+pub fn start_note(key: u8, velocity: u8, ducked: bool) -> bool {
+    with_state_mut(|state| state.start_note(key, velocity, ducked))
+}
+
+#[cfg(feature = "net")]
+pub fn release_note(key: u8) {
+    with_state_mut(|state| state.voices.release(key));
 }
 
 fn with_state_mut<R>(f: impl FnOnce(&mut DriverState) -> R) -> R {
@@ -1346,16 +1686,6 @@ fn with_state_mut<R>(f: impl FnOnce(&mut DriverState) -> R) -> R {
     unsafe { f(&mut *DRIVER_STATE.0.get()) }
 }
 
-fn lerp_i16(a: i16, b: i16, frac: u32) -> i16 {
-    let start = i32::from(a);
-    let delta = i32::from(b).saturating_sub(start);
-    let scaled =
-        ((i64::from(delta).saturating_mul(i64::from(frac))).saturating_add(1i64 << 15)) >> 16;
-    start
-        .saturating_add(scaled.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32)
-        .clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16
-}
-
 fn choose_rate(rates_mask: u64) -> Option<(u8, u32)> {
     let candidates: &[(u8, u32)] = &[
         (RATE_ENUM_44100, 44_100),
@@ -1371,6 +1701,62 @@ fn choose_rate(rates_mask: u64) -> Option<(u8, u32)> {
     None
 }
 
+/// `VIRTIO_SND_PCM_RATE_*` value to Hz.
+pub const fn rate_hz(rate: u32) -> Option<u32> {
+    const RATES: [u32; 14] = [
+        5_512, 8_000, 11_025, 16_000, 22_050, 32_000, 44_100, 48_000, 64_000, 88_200, 96_000,
+        176_400, 192_000, 384_000,
+    ];
+    if (rate as usize) < RATES.len() {
+        Some(RATES[rate as usize])
+    } else {
+        None
+    }
+}
+
+/// `VIRTIO_SND_PCM_FMT_*` value to a short name.
+pub const fn format_name(format: u32) -> Option<&'static str> {
+    const FORMATS: [&str; 25] = [
+        "ima_adpcm",
+        "mu_law",
+        "a_law",
+        "s8",
+        "u8",
+        "s16",
+        "u16",
+        "s18_3",
+        "u18_3",
+        "s20_3",
+        "u20_3",
+        "s24_3",
+        "u24_3",
+        "s20",
+        "u20",
+        "s24",
+        "u24",
+        "s32",
+        "u32",
+        "float",
+        "float64",
+        "dsd_u8",
+        "dsd_u16",
+        "dsd_u32",
+        "iec958",
+    ];
+    if (format as usize) < FORMATS.len() {
+        Some(FORMATS[format as usize])
+    } else {
+        None
+    }
+}
+
+fn period_frames(rate_hz: u32) -> usize {
+    let target = (rate_hz.saturating_mul(PERIOD_TARGET_MS) / 1000) as usize;
+    target
+        .next_power_of_two()
+        .clamp(MIN_PERIOD_FRAMES, TX_PACKET_FRAMES)
+}
+
 fn floor_pow2(value: u16) -> u16 {
     if value == 0 {
         return 0;
@@ -0,0 +1,98 @@
+// kernel/src/audio/wav.rs: minimal RIFF/WAVE parser for uncompressed PCM clips.
+use super::PcmFormat;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+
+#[derive(Clone, Copy)]
+pub struct WavClip<'a> {
+    pub format: PcmFormat,
+    pub sample_rate: u32,
+    pub channels: u8,
+    pub data: &'a [u8],
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum WavError {
+    NotRiff,
+    MissingFmt,
+    MissingData,
+    UnsupportedEncoding,
+    UnsupportedLayout,
+}
+
+impl WavError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::NotRiff => "not_riff_wave",
+            Self::MissingFmt => "missing_fmt",
+            Self::MissingData => "missing_data",
+            Self::UnsupportedEncoding => "unsupported_encoding",
+            Self::UnsupportedLayout => "unsupported_layout",
+        }
+    }
+}
+
+pub fn parse(bytes: &[u8]) -> Result<WavClip<'_>, WavError> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(WavError::NotRiff);
+    }
+
+    let mut fmt: Option<(PcmFormat, u32, u8)> = None;
+    let mut offset = 12usize;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let declared = u32::from_le_bytes([
+            bytes[offset + 4],
+            bytes[offset + 5],
+            bytes[offset + 6],
+            bytes[offset + 7],
+        ]) as usize;
+        let body_start = offset + 8;
+        // Clips saved into the 512-byte fs are often truncated; clamp the last chunk.
+        let body_end = body_start.saturating_add(declared).min(bytes.len());
+        let body = &bytes[body_start..body_end];
+
+        match id {
+            b"fmt " => fmt = Some(parse_fmt(body)?),
+            b"data" => {
+                let (format, sample_rate, channels) = fmt.ok_or(WavError::MissingFmt)?;
+                return Ok(WavClip {
+                    format,
+                    sample_rate,
+                    channels,
+                    data: body,
+                });
+            }
+            _ => {}
+        }
+        offset = body_end + (declared & 1);
+    }
+    Err(if fmt.is_some() {
+        WavError::MissingData
+    } else {
+        WavError::MissingFmt
+    })
+}
+
+fn parse_fmt(body: &[u8]) -> Result<(PcmFormat, u32, u8), WavError> {
+    if body.len() < 16 {
+        return Err(WavError::MissingFmt);
+    }
+    let encoding = u16::from_le_bytes([body[0], body[1]]);
+    let channels = u16::from_le_bytes([body[2], body[3]]);
+    let sample_rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+    let bits = u16::from_le_bytes([body[14], body[15]]);
+    if encoding != WAVE_FORMAT_PCM {
+        return Err(WavError::UnsupportedEncoding);
+    }
+    // WAV stores 8-bit PCM unsigned and 16-bit PCM signed little-endian.
+    let format = match bits {
+        8 => PcmFormat::U8,
+        16 => PcmFormat::S16,
+        _ => return Err(WavError::UnsupportedLayout),
+    };
+    if !(1..=2).contains(&channels) || sample_rate == 0 {
+        return Err(WavError::UnsupportedLayout);
+    }
+    Ok((format, sample_rate, channels as u8))
+}
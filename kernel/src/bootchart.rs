@@ -0,0 +1,195 @@
+// kernel/src/bootchart.rs: boot milestone timestamps (TSC + PIT tick) and per-subsystem boot cost.
+use crate::arch::x86_64::cpuid;
+use crate::{serial, time};
+use core::cell::UnsafeCell;
+
+const MAX_MILESTONES: usize = 16;
+/// Calibrating the TSC against fewer PIT ticks than this is too coarse to report microseconds.
+const MIN_CALIBRATION_TICKS: u64 = 10;
+const BAR_WIDTH: u64 = 40;
+
+#[derive(Clone, Copy)]
+struct Milestone {
+    name: &'static str,
+    tsc: u64,
+    tick: u64,
+}
+
+impl Milestone {
+    const EMPTY: Self = Self {
+        name: "",
+        tsc: 0,
+        tick: 0,
+    };
+}
+
+struct Bootchart {
+    milestones: [Milestone; MAX_MILESTONES],
+    len: usize,
+    dropped: usize,
+    /// Milestone from which the PIT is known to be ticking; the TSC is calibrated from here.
+    calibration_base: Option<usize>,
+}
+
+struct BootchartCell(UnsafeCell<Bootchart>);
+
+// SAFETY: milestones are recorded during single-threaded boot and read from the shell loop.
+unsafe impl Sync for BootchartCell {}
+
+static BOOTCHART: BootchartCell = BootchartCell(UnsafeCell::new(Bootchart {
+    milestones: [Milestone::EMPTY; MAX_MILESTONES],
+    len: 0,
+    dropped: 0,
+    calibration_base: None,
+}));
+
+fn with_chart<R>(f: impl FnOnce(&mut Bootchart) -> R) -> R {
+    // SAFETY: single-threaded kernel paths are the only callers.
+    unsafe { f(&mut *BOOTCHART.0.get()) }
+}
+
+fn read_tsc() -> u64 {
+    // SAFETY: RDTSC is available on every x86_64 CPU and only reads the timestamp counter.
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// Records that `name` finished initializing; the stage cost is the time since the previous
+/// milestone, so call order must follow boot order.
+pub fn mark(name: &'static str) {
+    let milestone = Milestone {
+        name,
+        tsc: read_tsc(),
+        tick: time::ticks(),
+    };
+    with_chart(|chart| {
+        if chart.len == MAX_MILESTONES {
+            chart.dropped += 1;
+            return;
+        }
+        chart.milestones[chart.len] = milestone;
+        chart.len += 1;
+    });
+}
+
+/// Like `mark`, and also notes that the PIT is running from this point on.
+pub fn mark_timer_started(name: &'static str) {
+    mark(name);
+    with_chart(|chart| {
+        if chart.calibration_base.is_none() && chart.len > 0 {
+            chart.calibration_base = Some(chart.len - 1);
+        }
+    });
+}
+
+/// TSC cycles per PIT tick measured from the calibration milestone to now.
+fn cycles_per_tick(chart: &Bootchart) -> Option<u64> {
+    if !cpuid::info().tsc_is_stable() {
+        return None;
+    }
+    let base = chart.milestones[chart.calibration_base?];
+    let ticks = time::ticks().saturating_sub(base.tick);
+    if ticks < MIN_CALIBRATION_TICKS {
+        return None;
+    }
+    Some(read_tsc().wrapping_sub(base.tsc) / ticks)
+}
+
+fn stage_cycles(chart: &Bootchart, index: usize) -> u64 {
+    chart.milestones[index]
+        .tsc
+        .wrapping_sub(chart.milestones[index - 1].tsc)
+}
+
+/// One key=value line: `bootchart: v1 stages=N total_kcycles=T <stage>=<kcycles> ...`.
+pub fn log_summary() {
+    with_chart(|chart| {
+        if chart.len < 2 {
+            serial::write_line("bootchart: v1 stages=0");
+            return;
+        }
+        let total = chart.milestones[chart.len - 1]
+            .tsc
+            .wrapping_sub(chart.milestones[0].tsc);
+        serial::write_fmt(format_args!(
+            "bootchart: v1 stages={} total_kcycles={}",
+            chart.len - 1,
+            total / 1000
+        ));
+        for index in 1..chart.len {
+            serial::write_fmt(format_args!(
+                " {}={}",
+                chart.milestones[index].name,
+                stage_cycles(chart, index) / 1000
+            ));
+        }
+        serial::write_line("");
+    });
+}
+
+pub fn log_chart() {
+    with_chart(|chart| {
+        if chart.len < 2 {
+            serial::write_line("bootchart: no milestones recorded");
+            return;
+        }
+        let total = chart.milestones[chart.len - 1]
+            .tsc
+            .wrapping_sub(chart.milestones[0].tsc)
+            .max(1);
+        let cycles_per_tick = cycles_per_tick(chart);
+        let cycles_per_us = cycles_per_tick
+            .map(|cycles| cycles * u64::from(time::PIT_HZ) / 1_000_000)
+            .filter(|&cycles| cycles > 0);
+        match cycles_per_us {
+            Some(cycles) => serial::write_fmt(format_args!(
+                "bootchart: stages={} total={} us tsc_mhz={} dropped={}\n",
+                chart.len - 1,
+                total / cycles,
+                cycles,
+                chart.dropped
+            )),
+            None => serial::write_fmt(format_args!(
+                "bootchart: stages={} total_kcycles={} tsc={} dropped={}\n",
+                chart.len - 1,
+                total / 1000,
+                if cpuid::info().tsc_is_stable() {
+                    "uncalibrated"
+                } else {
+                    "unstable"
+                },
+                chart.dropped
+            )),
+        }
+        for index in 1..chart.len {
+            let milestone = chart.milestones[index];
+            let cycles = stage_cycles(chart, index);
+            let percent_x10 = cycles.saturating_mul(1000) / total;
+            let bar = (cycles.saturating_mul(BAR_WIDTH) / total) as usize;
+            let rest = BAR_WIDTH as usize - bar;
+            match cycles_per_us {
+                Some(per_us) => serial::write_fmt(format_args!(
+                    "bootchart: {:<10} {:>10} kcyc {:>8} us {:>3}.{}% tick={:<4} |{:#<bar$}{:<rest$}|\n",
+                    milestone.name,
+                    cycles / 1000,
+                    cycles / per_us,
+                    percent_x10 / 10,
+                    percent_x10 % 10,
+                    milestone.tick,
+                    "",
+                    "",
+                )),
+                None => serial::write_fmt(format_args!(
+                    "bootchart: {:<10} {:>10} kcyc {:>3}.{}% tick={:<4} |{:#<bar$}{:<rest$}|\n",
+                    milestone.name,
+                    cycles / 1000,
+                    percent_x10 / 10,
+                    percent_x10 % 10,
+                    milestone.tick,
+                    "",
+                    "",
+                )),
+            }
+        }
+    });
+    log_summary();
+}
@@ -1,7 +1,10 @@
 // kernel/src/doom.rs: M10.6 Doom runtime (fallback + DoomGeneric C bridge loop).
+use crate::app_bridge::MouseEvent;
+use crate::artifacts;
 use crate::audio;
 use crate::doom_bridge;
-use crate::gfx;
+use crate::evlog::{self, Event};
+use crate::gfx::{self, DoomViewSource};
 use crate::serial;
 use crate::time;
 use alloc::string::String;
@@ -12,6 +15,26 @@ const DOOM_APP: &str = match option_env!("ARROST_DOOM_APP") {
     Some(value) => value,
     None => "doom",
 };
+const DOOM_ARTIFACT_SIZE: &str = match option_env!("ARROST_DOOM_ARTIFACT_SIZE") {
+    Some(value) => value,
+    None => "0",
+};
+const DOOM_ARTIFACT_HINT: &str = match option_env!("ARROST_DOOM_ARTIFACT_HINT") {
+    Some(value) => value,
+    None => "<none>",
+};
+const DOOM_C_BACKEND_SIZE: &str = match option_env!("ARROST_DOOM_C_BACKEND_SIZE") {
+    Some(value) => value,
+    None => "0",
+};
+const DOOM_C_BACKEND_READY: &str = match option_env!("ARROST_DOOM_C_BACKEND_READY") {
+    Some(value) => value,
+    None => "false",
+};
+const DOOM_C_BACKEND_OBJECT: &str = match option_env!("ARROST_DOOM_C_BACKEND_OBJECT") {
+    Some(value) => value,
+    None => "<none>",
+};
 const DOOM_GENERIC_READY: &str = match option_env!("ARROST_DOOM_GENERIC_READY") {
     Some(value) => value,
     None => "false",
@@ -63,11 +86,32 @@ const PLAY_RATE_DEN: u64 = time::PIT_HZ as u64;
 const PLAY_AUDIO_OUTPUT_HZ: u64 = 44_100;
 const PLAY_AUDIO_DRAIN_PER_FRAME: u64 = PLAY_AUDIO_OUTPUT_HZ / PLAY_RATE_NUM;
 const PLAY_MAX_STEPS_PER_POLL: u64 = 6;
-const PLAY_VIEW_STEP_TICKS: u64 = 4;
+/// Viewport blits per second while playing; `settings` adjusts it within these bounds.
+pub const DEFAULT_VIEW_FPS: u32 = 25;
+pub const MIN_VIEW_FPS: u32 = 5;
+pub const MAX_VIEW_FPS: u32 = 50;
 const PLAY_STATUS_STEP_TICKS: u64 = 20;
 const AUDIO_STEP_TICKS: u64 = 5;
 const PHYSICS_STEP_TICKS: u64 = 2;
 const UI_STEP_TICKS: u64 = FRAME_STEP_TICKS;
+/// Serial ASCII viewer cadence (2 s) and grid; each cell averages a 5x10 pixel block,
+/// roughly matching the 1:2 aspect of terminal glyphs.
+const ASCII_VIEW_STEP_TICKS: u64 = 2 * time::PIT_HZ as u64;
+const ASCII_VIEW_COLS: usize = 64;
+const ASCII_VIEW_ROWS: usize = 20;
+const ASCII_VIEW_RAMP: &[u8] = b" .:-=+*#%@";
+/// `doom view map` surface: the automap area above the status bar pooled to 96x56 and the
+/// status bar pooled to 96x16, each cell keeping its brightest pixel so one-pixel map lines
+/// survive the shrink. The ASCII viewer reads it as 48x18 cells of 2x4 pixels.
+const MAP_VIEW_W: usize = 96;
+const MAP_VIEW_H: usize = 72;
+const MAP_VIEW_PIXELS: usize = MAP_VIEW_W * MAP_VIEW_H;
+const MAP_VIEW_HUD_ROWS: usize = 16;
+const MAP_VIEW_ASCII_COLS: usize = 48;
+const MAP_VIEW_ASCII_ROWS: usize = 18;
+const STATUS_BAR_Y: usize = 168;
+/// Ticks to let the engine act on a queued automap toggle before queueing another.
+const MAP_TOGGLE_RETRY_TICKS: u64 = 20;
 const VIEW_W: usize = 30;
 const VIEW_H: usize = 6;
 const VIEWPORT_W: usize = doom_bridge::VIEWPORT_W;
@@ -123,6 +167,44 @@ pub enum PlayStart {
     AlreadyRunning,
 }
 
+/// When the runtime pauses itself based on the doom window's visibility.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AutoPause {
+    Off,
+    Minimized,
+    /// Also pauses while another window holds focus.
+    Unfocused,
+}
+
+impl AutoPause {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Minimized => "minimized",
+            Self::Unfocused => "unfocused",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    Running,
+    Manual,
+    Minimized,
+    Unfocused,
+}
+
+impl PauseReason {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Running => "none",
+            Self::Manual => "manual",
+            Self::Minimized => "minimized",
+            Self::Unfocused => "unfocused",
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct DoomStatus {
     pub app: &'static str,
@@ -159,23 +241,39 @@ pub struct DoomStatus {
     pub dg_audio_queue_samples: u32,
     pub dg_audio_dropped_samples: u64,
     pub dg_has_frame: bool,
+    pub dg_zero_copy_frames: u64,
     pub play_pace_clamps: u64,
+    pub view_fps: u32,
+    pub ascii_view: bool,
+    pub ascii_frames: u64,
+    pub map_view: bool,
+    pub map_frames: u64,
     pub capture_mode: bool,
     pub mouse_events: u64,
     pub mouse_turn_threshold: i16,
     pub mouse_move_threshold: i16,
     pub mouse_y_enabled: bool,
+    pub pause_reason: PauseReason,
+    pub auto_pause: AutoPause,
+    pub paused_ticks: u64,
+    pub pause_events: u64,
 }
 
 struct DoomState {
     running: bool,
     play_mode: bool,
+    manual_pause: bool,
+    auto_pause: AutoPause,
+    auto_pause_reason: PauseReason,
+    paused_ticks: u64,
+    pause_events: u64,
     started_tick: u64,
     last_poll_tick: u64,
     runtime_ticks: u64,
     frame_remainder: u64,
     play_rate_accumulator: u64,
     play_view_remainder: u64,
+    view_step_ticks: u64,
     audio_remainder: u64,
     physics_remainder: u64,
     ui_remainder: u64,
@@ -204,7 +302,16 @@ struct DoomState {
     dg_audio_queue_samples: u32,
     dg_audio_dropped_samples: u64,
     dg_has_frame: bool,
+    dg_zero_copy_frames: u64,
     play_pace_clamps: u64,
+    ascii_view: bool,
+    ascii_remainder: u64,
+    ascii_frames: u64,
+    map_view: bool,
+    /// The automap is open because `doom view map` opened it.
+    map_opened: bool,
+    map_toggle_tick: u64,
+    map_frames: u64,
     capture_mode: bool,
     mouse_events: u64,
     mouse_left_button: bool,
@@ -216,6 +323,7 @@ struct DoomState {
     mouse_y_enabled: bool,
     viewport_rgb: [u32; VIEWPORT_PIXELS],
     fallback_indexed: [u8; VIEWPORT_PIXELS],
+    map_rgb: [u32; MAP_VIEW_PIXELS],
 }
 
 impl DoomState {
@@ -223,12 +331,18 @@ impl DoomState {
         Self {
             running: false,
             play_mode: false,
+            manual_pause: false,
+            auto_pause: AutoPause::Minimized,
+            auto_pause_reason: PauseReason::Running,
+            paused_ticks: 0,
+            pause_events: 0,
             started_tick: 0,
             last_poll_tick: 0,
             runtime_ticks: 0,
             frame_remainder: 0,
             play_rate_accumulator: 0,
             play_view_remainder: 0,
+            view_step_ticks: (time::PIT_HZ / DEFAULT_VIEW_FPS) as u64,
             audio_remainder: 0,
             physics_remainder: 0,
             ui_remainder: 0,
@@ -257,7 +371,15 @@ impl DoomState {
             dg_audio_queue_samples: 0,
             dg_audio_dropped_samples: 0,
             dg_has_frame: false,
+            dg_zero_copy_frames: 0,
             play_pace_clamps: 0,
+            ascii_view: false,
+            ascii_remainder: 0,
+            ascii_frames: 0,
+            map_view: false,
+            map_opened: false,
+            map_toggle_tick: 0,
+            map_frames: 0,
             capture_mode: false,
             mouse_events: 0,
             mouse_left_button: false,
@@ -269,6 +391,7 @@ impl DoomState {
             mouse_y_enabled: false,
             viewport_rgb: [0; VIEWPORT_PIXELS],
             fallback_indexed: [0; VIEWPORT_PIXELS],
+            map_rgb: [0; MAP_VIEW_PIXELS],
         }
     }
 
@@ -277,6 +400,8 @@ impl DoomState {
         self.started_tick = now_ticks;
         self.last_poll_tick = now_ticks;
         self.runtime_ticks = 0;
+        self.paused_ticks = 0;
+        self.pause_events = 0;
         self.frame_remainder = 0;
         self.play_rate_accumulator = 0;
         self.play_view_remainder = 0;
@@ -306,7 +431,11 @@ impl DoomState {
         self.dg_audio_queue_samples = 0;
         self.dg_audio_dropped_samples = 0;
         self.dg_has_frame = false;
+        self.dg_zero_copy_frames = 0;
         self.play_pace_clamps = 0;
+        self.map_opened = false;
+        self.map_toggle_tick = 0;
+        self.map_frames = 0;
         self.capture_mode = false;
         self.mouse_events = 0;
         self.mouse_left_button = false;
@@ -333,7 +462,14 @@ impl DoomState {
 
         let delta = now_ticks.saturating_sub(self.last_poll_tick);
         self.last_poll_tick = now_ticks;
+        self.update_auto_pause();
+        if self.pause_reason() != PauseReason::Running {
+            // Tics, audio and blits all stop; the engine stays resident for `doom resume`.
+            self.paused_ticks = self.paused_ticks.saturating_add(delta);
+            return;
+        }
         self.runtime_ticks = self.runtime_ticks.saturating_add(delta);
+        self.poll_ascii_view(delta);
 
         if self.play_mode {
             let weighted_delta = delta.saturating_mul(PLAY_RATE_NUM);
@@ -350,6 +486,9 @@ impl DoomState {
                 .play_rate_accumulator
                 .saturating_sub(frame_steps.saturating_mul(PLAY_RATE_DEN));
 
+            if frame_steps > 0 {
+                self.apply_bridge_mouse();
+            }
             for _ in 0..frame_steps {
                 doom_bridge::tick_engine();
             }
@@ -359,12 +498,16 @@ impl DoomState {
 
             self.frames = self.frames.saturating_add(frame_steps);
             self.audio_mixes = self.audio_mixes.saturating_add(frame_steps / 2);
+            if frame_steps > 0 {
+                evlog::record(Event::DoomTick, frame_steps as u32, self.frames as u32);
+            }
             self.sync_bridge_stats();
+            self.sync_map_view(now_ticks);
 
             let view_acc = self.play_view_remainder.saturating_add(delta);
-            let should_render_view = view_acc >= PLAY_VIEW_STEP_TICKS;
+            let should_render_view = view_acc >= self.view_step_ticks;
             self.play_view_remainder = if should_render_view {
-                view_acc % PLAY_VIEW_STEP_TICKS
+                view_acc % self.view_step_ticks
             } else {
                 view_acc
             };
@@ -416,9 +559,106 @@ impl DoomState {
         }
     }
 
+    /// Emits the last composed viewport frame as ASCII art on the serial console; the gfx
+    /// viewport keeps updating independently.
+    fn poll_ascii_view(&mut self, delta: u64) {
+        if !self.ascii_view {
+            return;
+        }
+        self.ascii_remainder = self.ascii_remainder.saturating_add(delta);
+        if self.ascii_remainder < ASCII_VIEW_STEP_TICKS {
+            return;
+        }
+        self.ascii_remainder %= ASCII_VIEW_STEP_TICKS;
+        let mut text = String::new();
+        if text
+            .try_reserve((ASCII_VIEW_COLS + 3) * (ASCII_VIEW_ROWS + 1) + 64)
+            .is_err()
+        {
+            return;
+        }
+        self.ascii_frames = self.ascii_frames.saturating_add(1);
+        let map = self.map_view && self.bridge_frame_ready();
+        let (cols, rows) = if map {
+            (MAP_VIEW_ASCII_COLS, MAP_VIEW_ASCII_ROWS)
+        } else {
+            (ASCII_VIEW_COLS, ASCII_VIEW_ROWS)
+        };
+        let _ = writeln!(
+            text,
+            "doom-ascii: frame={} view={}x{}{}",
+            self.frames,
+            cols,
+            rows,
+            if map { " map" } else { "" }
+        );
+        if map {
+            self.render_map_view();
+            let grid = AsciiGrid::brightest(MAP_VIEW_W, MAP_VIEW_H, cols, rows);
+            render_ascii_view(&self.map_rgb, grid, &mut text);
+        } else if self.bridge_frame_ready() {
+            let grid = AsciiGrid::mean(VIEWPORT_W, VIEWPORT_H, cols, rows);
+            doom_bridge::with_front_surface(|pixels| render_ascii_view(pixels, grid, &mut text));
+        } else {
+            let grid = AsciiGrid::mean(VIEWPORT_W, VIEWPORT_H, cols, rows);
+            render_ascii_view(&self.viewport_rgb, grid, &mut text);
+        }
+        serial::write_str_unmirrored(&text);
+    }
+
+    fn set_ascii_view(&mut self, enabled: bool) -> bool {
+        if self.ascii_view == enabled {
+            return false;
+        }
+        self.ascii_view = enabled;
+        // The first frame goes out on the next poll instead of a full interval later.
+        self.ascii_remainder = ASCII_VIEW_STEP_TICKS;
+        true
+    }
+
+    fn set_map_view(&mut self, enabled: bool) -> bool {
+        if self.map_view == enabled {
+            return false;
+        }
+        self.map_view = enabled;
+        // A toggle for the previous setting may still be in flight; act on the next poll.
+        self.map_toggle_tick = 0;
+        true
+    }
+
+    /// Keeps the engine's automap open while `doom view map` is on, and closes it again when
+    /// the view is turned off if this view was what opened it.
+    fn sync_map_view(&mut self, now_ticks: u64) {
+        if !self.map_view && !self.map_opened {
+            return;
+        }
+        let automap = doom_bridge::automap_state();
+        if automap.open == self.map_view {
+            if !automap.open {
+                self.map_opened = false;
+            }
+            return;
+        }
+        if !automap.toggleable
+            || now_ticks.saturating_sub(self.map_toggle_tick) < MAP_TOGGLE_RETRY_TICKS
+        {
+            return;
+        }
+        if doom_bridge::toggle_automap() {
+            self.map_toggle_tick = now_ticks;
+            self.map_opened = self.map_view;
+        }
+    }
+
+    fn render_map_view(&mut self) {
+        let map_rgb = &mut self.map_rgb;
+        doom_bridge::with_front_surface(|pixels| render_map_view(pixels, map_rgb));
+        self.map_frames = self.map_frames.saturating_add(1);
+    }
+
     fn sync_bridge_stats(&mut self) {
         let bridge = doom_bridge::stats();
-        self.dg_frames = bridge.frames;
+        self.dg_frames = doom_bridge::engine_frames();
         self.dg_draw_calls = bridge.draw_calls;
         self.dg_nonzero_pixels = bridge.nonzero_pixels;
         self.dg_key_events = bridge.key_events;
@@ -431,6 +671,7 @@ impl DoomState {
         self.dg_audio_queue_samples = bridge.audio_queue_samples;
         self.dg_audio_dropped_samples = bridge.audio_dropped_samples;
         self.dg_has_frame = bridge.has_frame;
+        self.dg_zero_copy_frames = bridge.zero_copy_frames;
     }
 
     fn step_physics(&mut self) {
@@ -485,7 +726,78 @@ impl DoomState {
         queued
     }
 
+    fn pause_reason(&self) -> PauseReason {
+        if self.manual_pause {
+            PauseReason::Manual
+        } else {
+            self.auto_pause_reason
+        }
+    }
+
+    fn update_auto_pause(&mut self) {
+        let before = self.pause_reason();
+        let window = gfx::doom_window_state();
+        audio::set_doom_unfocused(
+            window.is_some_and(|window| window.open && !window.minimized && !window.focused),
+        );
+        // Until the first render opens the window there is nothing to be hidden behind.
+        self.auto_pause_reason = match window {
+            Some(window) if window.open && self.auto_pause != AutoPause::Off => {
+                if window.minimized {
+                    PauseReason::Minimized
+                } else if self.auto_pause == AutoPause::Unfocused && !window.focused {
+                    PauseReason::Unfocused
+                } else {
+                    PauseReason::Running
+                }
+            }
+            _ => PauseReason::Running,
+        };
+        let after = self.pause_reason();
+        if before != after && !self.manual_pause {
+            if after == PauseReason::Running {
+                serial::write_line("doom: auto-resumed");
+            } else {
+                serial::write_fmt(format_args!("doom: auto-paused ({})\n", after.as_str()));
+            }
+        }
+        self.apply_pause_transition(before);
+    }
+
+    /// Mutes audio and releases held buttons when the runtime crosses between paused and running.
+    fn apply_pause_transition(&mut self, before: PauseReason) {
+        let was_paused = before != PauseReason::Running;
+        let paused = self.pause_reason() != PauseReason::Running;
+        if was_paused == paused {
+            return;
+        }
+        let _ = audio::set_muted(paused);
+        if paused {
+            self.pause_events = self.pause_events.saturating_add(1);
+            self.release_capture_buttons();
+        }
+    }
+
+    fn set_manual_pause(&mut self, paused: bool) -> bool {
+        if !self.running || self.manual_pause == paused {
+            return false;
+        }
+        let before = self.pause_reason();
+        self.manual_pause = paused;
+        self.apply_pause_transition(before);
+        true
+    }
+
+    fn clear_pause(&mut self) {
+        let before = self.pause_reason();
+        self.manual_pause = false;
+        self.auto_pause_reason = PauseReason::Running;
+        audio::set_doom_unfocused(false);
+        self.apply_pause_transition(before);
+    }
+
     fn release_capture_buttons(&mut self) {
+        doom_bridge::clear_mouse();
         if self.mouse_left_button {
             let _ = self.enqueue_bridge_key(b' ', false);
             self.mouse_left_button = false;
@@ -518,50 +830,64 @@ impl DoomState {
         dy: i16,
         left_button: bool,
         right_button: bool,
-        _middle_button: bool,
+        middle_button: bool,
     ) -> bool {
         if !self.running || !self.play_mode || !self.capture_mode {
             return false;
         }
 
         self.mouse_events = self.mouse_events.saturating_add(1);
+        doom_bridge::enqueue_mouse(MouseEvent::new(
+            dx,
+            dy,
+            left_button,
+            right_button,
+            middle_button,
+        ));
+        true
+    }
 
-        if left_button != self.mouse_left_button {
-            let _ = self.enqueue_bridge_key(b' ', left_button);
-            self.mouse_left_button = left_button;
-        }
-        if right_button != self.mouse_right_button {
-            let _ = self.enqueue_bridge_key(b'e', right_button);
-            self.mouse_right_button = right_button;
-        }
-
-        self.mouse_motion_x_acc = self.mouse_motion_x_acc.saturating_add(dx);
-        while self.mouse_motion_x_acc >= self.mouse_turn_threshold {
-            let _ = self.enqueue_bridge_key(b'd', true);
-            let _ = self.enqueue_bridge_key(b'd', false);
-            self.mouse_motion_x_acc -= self.mouse_turn_threshold;
-        }
-        while self.mouse_motion_x_acc <= -self.mouse_turn_threshold {
-            let _ = self.enqueue_bridge_key(b'a', true);
-            let _ = self.enqueue_bridge_key(b'a', false);
-            self.mouse_motion_x_acc += self.mouse_turn_threshold;
-        }
+    /// Turns the mouse reports queued on the bridge into doom keys before the engine runs:
+    /// buttons fire and use, motion past the thresholds turns (and with `mouse y` moves).
+    fn apply_bridge_mouse(&mut self) {
+        while let Some(event) = doom_bridge::pop_mouse() {
+            let (left_button, right_button) = (event.left(), event.right());
+            let (dx, dy) = (event.dx, event.dy);
+            if left_button != self.mouse_left_button {
+                let _ = self.enqueue_bridge_key(b' ', left_button);
+                self.mouse_left_button = left_button;
+            }
+            if right_button != self.mouse_right_button {
+                let _ = self.enqueue_bridge_key(b'e', right_button);
+                self.mouse_right_button = right_button;
+            }
 
-        if self.mouse_y_enabled {
-            self.mouse_motion_y_acc = self.mouse_motion_y_acc.saturating_add(dy);
-            while self.mouse_motion_y_acc >= self.mouse_move_threshold {
-                let _ = self.enqueue_bridge_key(b'w', true);
-                let _ = self.enqueue_bridge_key(b'w', false);
-                self.mouse_motion_y_acc -= self.mouse_move_threshold;
+            self.mouse_motion_x_acc = self.mouse_motion_x_acc.saturating_add(dx);
+            while self.mouse_motion_x_acc >= self.mouse_turn_threshold {
+                let _ = self.enqueue_bridge_key(b'd', true);
+                let _ = self.enqueue_bridge_key(b'd', false);
+                self.mouse_motion_x_acc -= self.mouse_turn_threshold;
             }
-            while self.mouse_motion_y_acc <= -self.mouse_move_threshold {
-                let _ = self.enqueue_bridge_key(b's', true);
-                let _ = self.enqueue_bridge_key(b's', false);
-                self.mouse_motion_y_acc += self.mouse_move_threshold;
+            while self.mouse_motion_x_acc <= -self.mouse_turn_threshold {
+                let _ = self.enqueue_bridge_key(b'a', true);
+                let _ = self.enqueue_bridge_key(b'a', false);
+                self.mouse_motion_x_acc += self.mouse_turn_threshold;
             }
-        }
 
-        true
+            if self.mouse_y_enabled {
+                self.mouse_motion_y_acc = self.mouse_motion_y_acc.saturating_add(dy);
+                while self.mouse_motion_y_acc >= self.mouse_move_threshold {
+                    let _ = self.enqueue_bridge_key(b'w', true);
+                    let _ = self.enqueue_bridge_key(b'w', false);
+                    self.mouse_motion_y_acc -= self.mouse_move_threshold;
+                }
+                while self.mouse_motion_y_acc <= -self.mouse_move_threshold {
+                    let _ = self.enqueue_bridge_key(b's', true);
+                    let _ = self.enqueue_bridge_key(b's', false);
+                    self.mouse_motion_y_acc += self.mouse_move_threshold;
+                }
+            }
+        }
     }
 
     fn set_mouse_turn_threshold(&mut self, threshold: i16) -> bool {
@@ -632,6 +958,12 @@ impl DoomState {
             snapshot.audio_mixes,
             snapshot.ui_updates
         );
+        let _ = writeln!(
+            text,
+            "pause:{} auto:{}",
+            snapshot.pause_reason.as_str(),
+            snapshot.auto_pause.as_str()
+        );
         let _ = writeln!(
             text,
             "pos:({}, {}) vel:({}, {}) inp:{} hit:{}",
@@ -686,18 +1018,37 @@ impl DoomState {
         text
     }
 
-    fn refresh_viewport_rgb(&mut self) {
-        let has_bridge_frame = self.play_mode && doom_bridge::copy_pixels(&mut self.viewport_rgb);
-        if !has_bridge_frame {
-            self.render_viewport_pixels();
-            self.convert_fallback_view_to_rgb();
+    fn bridge_frame_ready(&self) -> bool {
+        self.play_mode && doom_bridge::has_frame()
+    }
+
+    /// Engine frames stay in the bridge's front surface and gfx reads them in place; the map
+    /// view is pooled into `map_rgb` and the fallback scene is rendered into `viewport_rgb`.
+    fn viewport_source(&mut self) -> (usize, usize, DoomViewSource<'_>) {
+        if self.bridge_frame_ready() {
+            if self.map_view {
+                self.render_map_view();
+                return (
+                    MAP_VIEW_W,
+                    MAP_VIEW_H,
+                    DoomViewSource::Pixels(&self.map_rgb),
+                );
+            }
+            return (VIEWPORT_W, VIEWPORT_H, DoomViewSource::BridgeFront);
         }
+        self.render_viewport_pixels();
+        self.convert_fallback_view_to_rgb();
+        (
+            VIEWPORT_W,
+            VIEWPORT_H,
+            DoomViewSource::Pixels(&self.viewport_rgb),
+        )
     }
 
     fn render_viewport_locked(&mut self) {
         self.ui_updates = self.ui_updates.saturating_add(1);
-        self.refresh_viewport_rgb();
-        gfx::set_file_manager_doom_view(VIEWPORT_W, VIEWPORT_H, &self.viewport_rgb);
+        let (width, height, source) = self.viewport_source();
+        gfx::set_file_manager_doom_view(width, height, source);
     }
 
     fn render_status_text_locked(&mut self) {
@@ -709,8 +1060,8 @@ impl DoomState {
     fn render_ui_status_locked(&mut self) {
         self.ui_updates = self.ui_updates.saturating_add(1);
         let text = self.status_text();
-        self.refresh_viewport_rgb();
-        gfx::set_file_manager_doom_overlay(&text, VIEWPORT_W, VIEWPORT_H, &self.viewport_rgb);
+        let (width, height, source) = self.viewport_source();
+        gfx::set_file_manager_doom_overlay(&text, width, height, source);
     }
 
     fn render_viewport_pixels(&mut self) {
@@ -944,20 +1295,68 @@ impl DoomState {
             dg_audio_queue_samples: self.dg_audio_queue_samples,
             dg_audio_dropped_samples: self.dg_audio_dropped_samples,
             dg_has_frame: self.dg_has_frame,
+            dg_zero_copy_frames: self.dg_zero_copy_frames,
             play_pace_clamps: self.play_pace_clamps,
+            view_fps: (time::PIT_HZ as u64 / self.view_step_ticks) as u32,
+            ascii_view: self.ascii_view,
+            ascii_frames: self.ascii_frames,
+            map_view: self.map_view,
+            map_frames: self.map_frames,
             capture_mode: self.capture_mode,
             mouse_events: self.mouse_events,
             mouse_turn_threshold: self.mouse_turn_threshold,
             mouse_move_threshold: self.mouse_move_threshold,
             mouse_y_enabled: self.mouse_y_enabled,
+            pause_reason: self.pause_reason(),
+            auto_pause: self.auto_pause,
+            paused_ticks: self.paused_ticks,
+            pause_events: self.pause_events,
         }
     }
 }
 
+/// Boot lines naming the doom artifacts this kernel was built with and whether they verify.
+pub fn log_build_info() {
+    serial::write_fmt(format_args!(
+        "Doom: app={} rust_artifact={} rust_artifact_size={} c_backend_size={} c_backend_ready={} c_backend_object={}\n",
+        DOOM_APP,
+        DOOM_ARTIFACT_HINT,
+        DOOM_ARTIFACT_SIZE,
+        DOOM_C_BACKEND_SIZE,
+        DOOM_C_BACKEND_READY,
+        DOOM_C_BACKEND_OBJECT
+    ));
+    serial::write_fmt(format_args!(
+        "DoomGeneric: ready={} root={} core={} core_obj={} core_size={} core_ready={} port={} port_size={} port_ready={} wad={} wad_present={} core_verify={} port_verify={} wad_verify={}\n",
+        DOOM_GENERIC_READY,
+        DOOM_GENERIC_ROOT,
+        DOOM_GENERIC_CORE_SOURCE,
+        DOOM_GENERIC_CORE_OBJECT,
+        DOOM_GENERIC_CORE_SIZE,
+        DOOM_GENERIC_CORE_READY,
+        DOOM_GENERIC_PORT_OBJECT,
+        DOOM_GENERIC_PORT_SIZE,
+        DOOM_GENERIC_PORT_READY,
+        DOOM_WAD_HINT,
+        DOOM_WAD_PRESENT,
+        artifacts::DoomArtifact::Core.verify().as_str(),
+        artifacts::DoomArtifact::Port.verify().as_str(),
+        artifacts::DoomArtifact::Wad.verify().as_str()
+    ));
+}
+
 pub fn poll(now_ticks: u64) {
     with_state_mut(|state| state.poll(now_ticks));
 }
 
+/// A running, unpaused runtime steps every tick; paused or stopped it has no deadline.
+pub fn next_deadline(now_ticks: u64) -> Option<u64> {
+    with_state_mut(|state| {
+        (state.running && state.pause_reason() == PauseReason::Running)
+            .then_some(now_ticks.saturating_add(1))
+    })
+}
+
 pub fn inject_key(byte: u8) -> bool {
     with_state_mut(|state| {
         if !state.running {
@@ -988,6 +1387,116 @@ pub fn capture_enabled() -> bool {
     with_state_mut(|state| state.capture_mode)
 }
 
+pub fn set_ascii_view(enabled: bool) -> bool {
+    with_state_mut(|state| state.set_ascii_view(enabled))
+}
+
+/// Turns the automap-only viewport on or off; `false` when already in that state.
+pub fn set_map_view(enabled: bool) -> bool {
+    with_state_mut(|state| state.set_map_view(enabled))
+}
+
+/// Rec.601 luma of an `0x00RRGGBB` pixel.
+fn luma(pixel: u32) -> u32 {
+    let r = (pixel >> 16) & 0xFF;
+    let g = (pixel >> 8) & 0xFF;
+    let b = pixel & 0xFF;
+    (r * 77 + g * 150 + b * 29) >> 8
+}
+
+/// Pools the automap area and the status bar of an engine frame into the `doom view map`
+/// surface, keeping the brightest pixel of every cell.
+fn render_map_view(pixels: &[u32], out: &mut [u32; MAP_VIEW_PIXELS]) {
+    let map_rows = MAP_VIEW_H - MAP_VIEW_HUD_ROWS;
+    for y in 0..MAP_VIEW_H {
+        let (src_top, src_h, row, rows) = if y < map_rows {
+            (0, STATUS_BAR_Y, y, map_rows)
+        } else {
+            (
+                STATUS_BAR_Y,
+                VIEWPORT_H - STATUS_BAR_Y,
+                y - map_rows,
+                MAP_VIEW_HUD_ROWS,
+            )
+        };
+        let y0 = src_top + row * src_h / rows;
+        let y1 = src_top + (row + 1) * src_h / rows;
+        for x in 0..MAP_VIEW_W {
+            let x0 = x * VIEWPORT_W / MAP_VIEW_W;
+            let x1 = (x + 1) * VIEWPORT_W / MAP_VIEW_W;
+            let mut brightest = (0, 0);
+            for sy in y0..y1 {
+                for &pixel in &pixels[sy * VIEWPORT_W + x0..sy * VIEWPORT_W + x1] {
+                    let level = luma(pixel);
+                    if level >= brightest.0 {
+                        brightest = (level, pixel);
+                    }
+                }
+            }
+            out[y * MAP_VIEW_W + x] = brightest.1;
+        }
+    }
+}
+
+/// How `render_ascii_view` cuts a `width`-pixel-wide image into cells.
+#[derive(Clone, Copy)]
+struct AsciiGrid {
+    width: usize,
+    cols: usize,
+    rows: usize,
+    cell_w: usize,
+    cell_h: usize,
+    /// Keep each cell's brightest pixel instead of its mean, for line art.
+    brightest: bool,
+}
+
+impl AsciiGrid {
+    const fn mean(width: usize, height: usize, cols: usize, rows: usize) -> Self {
+        Self {
+            width,
+            cols,
+            rows,
+            cell_w: width / cols,
+            cell_h: height / rows,
+            brightest: false,
+        }
+    }
+
+    const fn brightest(width: usize, height: usize, cols: usize, rows: usize) -> Self {
+        Self {
+            brightest: true,
+            ..Self::mean(width, height, cols, rows)
+        }
+    }
+}
+
+/// Appends one `|...|` line per grid row, mapping the Rec.601 luma of each cell onto
+/// `ASCII_VIEW_RAMP`.
+fn render_ascii_view(pixels: &[u32], grid: AsciiGrid, text: &mut String) {
+    for row in 0..grid.rows {
+        text.push('|');
+        for col in 0..grid.cols {
+            let mut luma_sum = 0u32;
+            let mut luma_max = 0u32;
+            for y in row * grid.cell_h..(row + 1) * grid.cell_h {
+                for &pixel in &pixels[y * grid.width + col * grid.cell_w..][..grid.cell_w] {
+                    let level = luma(pixel);
+                    luma_sum += level;
+                    luma_max = luma_max.max(level);
+                }
+            }
+            let level = if grid.brightest {
+                luma_max
+            } else {
+                luma_sum / (grid.cell_w * grid.cell_h) as u32
+            };
+            let level = level as usize * ASCII_VIEW_RAMP.len() / 256;
+            text.push(char::from(ASCII_VIEW_RAMP[level]));
+        }
+        text.push_str("|\n");
+    }
+}
+
 pub fn inject_mouse(
     dx: i16,
     dy: i16,
@@ -1008,10 +1517,42 @@ pub fn set_mouse_move_threshold(threshold: i16) -> bool {
     with_state_mut(|state| state.set_mouse_move_threshold(threshold))
 }
 
+/// Target viewport refresh while playing, clamped to `MIN_VIEW_FPS..=MAX_VIEW_FPS` and rounded
+/// to whole PIT ticks per blit. Returns false when unchanged.
+pub fn set_view_fps(fps: u32) -> bool {
+    let step = u64::from(time::PIT_HZ / fps.clamp(MIN_VIEW_FPS, MAX_VIEW_FPS));
+    with_state_mut(|state| {
+        if state.view_step_ticks == step {
+            return false;
+        }
+        state.view_step_ticks = step;
+        state.play_view_remainder = 0;
+        true
+    })
+}
+
 pub fn set_mouse_y_enabled(enabled: bool) {
     with_state_mut(|state| state.set_mouse_y_enabled(enabled));
 }
 
+pub fn pause() -> bool {
+    with_state_mut(|state| state.set_manual_pause(true))
+}
+
+/// Clears a manual pause; the runtime may stay paused if the auto-pause policy still applies.
+pub fn resume() -> bool {
+    with_state_mut(|state| state.set_manual_pause(false))
+}
+
+pub fn set_auto_pause(policy: AutoPause) {
+    with_state_mut(|state| {
+        state.auto_pause = policy;
+        if state.running {
+            state.update_auto_pause();
+        }
+    });
+}
+
 pub fn start(now_ticks: u64) -> bool {
     with_state_mut(|state| {
         state.shell_commands = state.shell_commands.saturating_add(1);
@@ -1053,6 +1594,7 @@ pub fn stop(now_ticks: u64) -> bool {
             return false;
         }
         state.poll(now_ticks);
+        state.clear_pause();
         state.running = false;
         state.play_mode = false;
         state.capture_mode = false;
@@ -1093,15 +1635,20 @@ pub fn status() -> DoomStatus {
 
 pub fn log_status() {
     let status = status();
-    let pcm = audio::status();
+    let audio_status = audio::status();
+    let pcm = audio::pcm_status();
     serial::write_fmt(format_args!(
-        "doom: app={} engine={} bridge={} running={} play_mode={} capture={} started_tick={} runtime_ticks={} frames={} audio_mixes={} key_events={} mouse_events={} mouse_cfg=(turn:{} move:{} y:{}) inputs={} collisions={} pos=({}, {}) vel=({}, {}) wad_present={} shell_cmds={} ui_updates={} dg_frames={} dg_draw={} dg_nonzero={} dg_key={} dg_poll={} dg_drop={} dg_sleep={}({}ms) dg_audio={} dg_audio_samples={} dg_audio_q={} dg_audio_drop={} dg_frame={} dg_pace={} pcm_mode={} pcm_backend={} pcm_active={} pcm_hz={} pcm_evt={} pcm_samples={} pcm_sw={} pcm_min={} pcm_max={} pcm_q={} pcm_buf={} pcm_tx={} pcm_done={} pcm_drop={} pcm_frames={} pcm_drop_frames={} pcm_rate={} pcm_ch={} pcm_stream={} pcm_ctrl={:#x} last_key={:#04x}\n",
+        "doom: app={} engine={} bridge={} running={} play_mode={} capture={} paused={} autopause={} paused_ticks={} pause_events={} started_tick={} runtime_ticks={} frames={} audio_mixes={} key_events={} mouse_events={} mouse_cfg=(turn:{} move:{} y:{}) inputs={} collisions={} pos=({}, {}) vel=({}, {}) wad_present={} shell_cmds={} ui_updates={} dg_frames={} dg_draw={} dg_nonzero={} dg_key={} dg_poll={} dg_drop={} dg_sleep={}({}ms) dg_audio={} dg_audio_samples={} dg_audio_q={} dg_audio_drop={} dg_frame={} dg_zero_copy={} dg_pace={} view_fps={} ascii_view={} ascii_frames={} map_view={} map_frames={} pcm_mode={} pcm_backend={} pcm_active={} pcm_hz={} pcm_evt={} pcm_samples={} pcm_sw={} pcm_min={} pcm_max={} pcm_q={} pcm_buf={} pcm_tx={} pcm_done={} pcm_drop={} pcm_frames={} pcm_drop_frames={} pcm_rate={} pcm_ch={} pcm_stream={} pcm_ctrl={:#x} last_key={:#04x}\n",
         status.app,
         status.engine,
         status.dg_bridge,
         status.running,
         status.play_mode,
         status.capture_mode,
+        status.pause_reason.as_str(),
+        status.auto_pause.as_str(),
+        status.paused_ticks,
+        status.pause_events,
         status.started_tick,
         status.runtime_ticks,
         status.frames,
@@ -1133,27 +1680,33 @@ pub fn log_status() {
         status.dg_audio_queue_samples,
         status.dg_audio_dropped_samples,
         status.dg_has_frame,
+        status.dg_zero_copy_frames,
         status.play_pace_clamps,
-        pcm.mode.as_str(),
-        pcm.pcm_backend,
-        pcm.active,
+        status.view_fps,
+        if status.ascii_view { "on" } else { "off" },
+        status.ascii_frames,
+        if status.map_view { "on" } else { "off" },
+        status.map_frames,
+        audio_status.mode.as_str(),
+        audio_status.pcm_backend,
+        audio_status.active,
         pcm.tone_hz,
-        pcm.pcm_mix_events,
-        pcm.pcm_samples,
-        pcm.pcm_tone_switches,
-        pcm.pcm_hz_min,
-        pcm.pcm_hz_max,
-        pcm.pcm_queue_pending,
-        pcm.pcm_buffered_frames,
-        pcm.pcm_packets_submitted,
-        pcm.pcm_packets_completed,
-        pcm.pcm_packets_dropped,
-        pcm.pcm_frames_completed,
-        pcm.pcm_frames_dropped,
-        pcm.pcm_rate_hz,
-        pcm.pcm_channels,
-        pcm.pcm_stream_id,
-        pcm.pcm_last_ctrl_status,
+        pcm.mix_events,
+        pcm.samples,
+        pcm.tone_switches,
+        pcm.hz_min,
+        pcm.hz_max,
+        pcm.queue_pending,
+        pcm.buffered_frames,
+        pcm.packets_submitted,
+        pcm.packets_completed,
+        pcm.packets_dropped,
+        pcm.frames_completed,
+        pcm.frames_dropped,
+        audio_status.pcm_rate_hz,
+        audio_status.pcm_channels,
+        pcm.stream_id,
+        pcm.last_ctrl_status,
         status.last_key
     ));
 }
@@ -1161,7 +1714,7 @@ pub fn log_status() {
 pub fn log_doomgeneric_info() {
     let bridge = doom_bridge::stats();
     serial::write_fmt(format_args!(
-        "doomgeneric: ready={} root={} core={} core_obj={} ({} bytes) core_ready={} port={} ({} bytes) port_ready={} wad={} wad_present={} bridge={} dg_frames={} dg_draw={} dg_key={} dg_poll={} dg_drop={} dg_sleep={}({}ms) dg_audio={} dg_audio_samples={} dg_audio_q={} dg_audio_drop={} dg_title_len={} dg_frame={}\n",
+        "doomgeneric: ready={} root={} core={} core_obj={} ({} bytes) core_ready={} port={} ({} bytes) port_ready={} wad={} wad_present={} bridge={} dg_frames={} dg_draw={} dg_key={} dg_poll={} dg_drop={} dg_mouse={} dg_mouse_drop={} dg_sleep={}({}ms) dg_audio={} dg_audio_samples={} dg_audio_q={} dg_audio_drop={} dg_title_len={} dg_frame={}\n",
         DOOM_GENERIC_READY,
         DOOM_GENERIC_ROOT,
         DOOM_GENERIC_CORE_SOURCE,
@@ -1174,11 +1727,13 @@ pub fn log_doomgeneric_info() {
         DOOM_WAD_HINT,
         DOOM_WAD_PRESENT,
         DOOM_GENERIC_BRIDGE_MODE,
-        bridge.frames,
+        doom_bridge::engine_frames(),
         bridge.draw_calls,
         bridge.key_events,
         bridge.key_polls,
         bridge.key_dropped,
+        bridge.mouse_events,
+        bridge.mouse_dropped,
         bridge.sleep_calls,
         bridge.last_sleep_ms,
         bridge.audio_mix_calls,
@@ -1205,7 +1760,7 @@ pub fn log_doomgeneric_doctor() {
             " - core compile missing/failing: source={} object={} ({} bytes)\n",
             DOOM_GENERIC_CORE_SOURCE, DOOM_GENERIC_CORE_OBJECT, DOOM_GENERIC_CORE_SIZE
         ));
-        serial::write_line("   hint: run scripts/vendor_doomgeneric.sh");
+        serial::write_line("   hint: run cargo xtask doom-setup");
     }
     if DOOM_GENERIC_PORT_READY != "true" {
         serial::write_fmt(format_args!(
@@ -1215,6 +1770,7 @@ pub fn log_doomgeneric_doctor() {
     }
     if DOOM_WAD_PRESENT != "true" {
         serial::write_fmt(format_args!(" - missing wad: {}\n", DOOM_WAD_HINT));
+        serial::write_line("   hint: run cargo xtask doom-setup");
     }
     serial::write_line("doom doctor: `doom play` will use fallback runtime until ready=true");
 }
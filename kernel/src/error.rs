@@ -0,0 +1,265 @@
+// kernel/src/error.rs: kernel-wide error kinds with the Linux-style errno table used by syscalls.
+#[cfg(feature = "net")]
+use crate::net;
+use crate::shell::Status;
+use crate::{fs, serial, storage};
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum KernelError {
+    NotFound,
+    NoSuchProcess,
+    Io,
+    BadFd,
+    WouldBlock,
+    OutOfMemory,
+    PermissionDenied,
+    BadAddress,
+    NoDevice,
+    InvalidArgument,
+    TooManyFiles,
+    NoSpace,
+    NameTooLong,
+    NoSys,
+    MessageTooLong,
+    NoProtocolOption,
+    ProtocolNotSupported,
+    AddressFamilyNotSupported,
+    AddressInUse,
+    ConnectionReset,
+    NoBufferSpace,
+    NotConnected,
+    TimedOut,
+    ConnectionRefused,
+    HostUnreachable,
+}
+
+/// Every kind in errno order; `from_errno` and the `errno` shell listing walk this table.
+pub const ERRNO_TABLE: [KernelError; 25] = [
+    KernelError::NotFound,
+    KernelError::NoSuchProcess,
+    KernelError::Io,
+    KernelError::BadFd,
+    KernelError::WouldBlock,
+    KernelError::OutOfMemory,
+    KernelError::PermissionDenied,
+    KernelError::BadAddress,
+    KernelError::NoDevice,
+    KernelError::InvalidArgument,
+    KernelError::TooManyFiles,
+    KernelError::NoSpace,
+    KernelError::NameTooLong,
+    KernelError::NoSys,
+    KernelError::MessageTooLong,
+    KernelError::NoProtocolOption,
+    KernelError::ProtocolNotSupported,
+    KernelError::AddressFamilyNotSupported,
+    KernelError::AddressInUse,
+    KernelError::ConnectionReset,
+    KernelError::NoBufferSpace,
+    KernelError::NotConnected,
+    KernelError::TimedOut,
+    KernelError::ConnectionRefused,
+    KernelError::HostUnreachable,
+];
+
+impl KernelError {
+    pub const fn errno(self) -> i32 {
+        match self {
+            Self::NotFound => 2,
+            Self::NoSuchProcess => 3,
+            Self::Io => 5,
+            Self::BadFd => 9,
+            Self::WouldBlock => 11,
+            Self::OutOfMemory => 12,
+            Self::PermissionDenied => 13,
+            Self::BadAddress => 14,
+            Self::NoDevice => 19,
+            Self::InvalidArgument => 22,
+            Self::TooManyFiles => 24,
+            Self::NoSpace => 28,
+            Self::NameTooLong => 36,
+            Self::NoSys => 38,
+            Self::MessageTooLong => 90,
+            Self::NoProtocolOption => 92,
+            Self::ProtocolNotSupported => 93,
+            Self::AddressFamilyNotSupported => 97,
+            Self::AddressInUse => 98,
+            Self::ConnectionReset => 104,
+            Self::NoBufferSpace => 105,
+            Self::NotConnected => 107,
+            Self::TimedOut => 110,
+            Self::ConnectionRefused => 111,
+            Self::HostUnreachable => 113,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::NotFound => "ENOENT",
+            Self::NoSuchProcess => "ESRCH",
+            Self::Io => "EIO",
+            Self::BadFd => "EBADF",
+            Self::WouldBlock => "EAGAIN",
+            Self::OutOfMemory => "ENOMEM",
+            Self::PermissionDenied => "EACCES",
+            Self::BadAddress => "EFAULT",
+            Self::NoDevice => "ENODEV",
+            Self::InvalidArgument => "EINVAL",
+            Self::TooManyFiles => "EMFILE",
+            Self::NoSpace => "ENOSPC",
+            Self::NameTooLong => "ENAMETOOLONG",
+            Self::NoSys => "ENOSYS",
+            Self::MessageTooLong => "EMSGSIZE",
+            Self::NoProtocolOption => "ENOPROTOOPT",
+            Self::ProtocolNotSupported => "EPROTONOSUPPORT",
+            Self::AddressFamilyNotSupported => "EAFNOSUPPORT",
+            Self::AddressInUse => "EADDRINUSE",
+            Self::ConnectionReset => "ECONNRESET",
+            Self::NoBufferSpace => "ENOBUFS",
+            Self::NotConnected => "ENOTCONN",
+            Self::TimedOut => "ETIMEDOUT",
+            Self::ConnectionRefused => "ECONNREFUSED",
+            Self::HostUnreachable => "EHOSTUNREACH",
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::NotFound => "no such file or directory",
+            Self::NoSuchProcess => "no such process",
+            Self::Io => "i/o error",
+            Self::BadFd => "bad file descriptor",
+            Self::WouldBlock => "resource temporarily unavailable",
+            Self::OutOfMemory => "out of memory",
+            Self::PermissionDenied => "permission denied",
+            Self::BadAddress => "bad address",
+            Self::NoDevice => "no such device",
+            Self::InvalidArgument => "invalid argument",
+            Self::TooManyFiles => "too many open files",
+            Self::NoSpace => "no space left on device",
+            Self::NameTooLong => "name too long",
+            Self::NoSys => "function not implemented",
+            Self::MessageTooLong => "message too long",
+            Self::NoProtocolOption => "protocol option not available",
+            Self::ProtocolNotSupported => "protocol not supported",
+            Self::AddressFamilyNotSupported => "address family not supported",
+            Self::AddressInUse => "address already in use",
+            Self::ConnectionReset => "connection reset by peer",
+            Self::NoBufferSpace => "no buffer space available",
+            Self::NotConnected => "not connected",
+            Self::TimedOut => "timed out",
+            Self::ConnectionRefused => "connection refused",
+            Self::HostUnreachable => "host unreachable",
+        }
+    }
+
+    /// Negative errno as returned in the syscall result register.
+    pub const fn syscall_return(self) -> isize {
+        -(self.errno() as isize)
+    }
+
+    pub fn from_errno(errno: i32) -> Option<Self> {
+        ERRNO_TABLE
+            .iter()
+            .copied()
+            .find(|error| error.errno() == errno)
+    }
+
+    /// Resolves a negative syscall return back to its kind; non-negative values are successes.
+    pub fn from_syscall_return(rc: isize) -> Option<Self> {
+        if rc >= 0 {
+            return None;
+        }
+        i32::try_from(rc.unsigned_abs())
+            .ok()
+            .and_then(Self::from_errno)
+    }
+}
+
+#[cfg(feature = "net")]
+impl From<net::NetError> for KernelError {
+    fn from(error: net::NetError) -> Self {
+        match error {
+            net::NetError::NotReady => Self::NotConnected,
+            net::NetError::NotFound => Self::NoDevice,
+            net::NetError::QueueUnavailable => Self::NoDevice,
+            net::NetError::QueueTooLarge => Self::MessageTooLong,
+            net::NetError::FrameTooLarge => Self::MessageTooLong,
+            net::NetError::IoTimeout => Self::TimedOut,
+            net::NetError::ArpTimeout => Self::HostUnreachable,
+            net::NetError::UdpPayloadTooLarge => Self::MessageTooLong,
+            net::NetError::InvalidMtu => Self::InvalidArgument,
+            net::NetError::DmaUnavailable => Self::OutOfMemory,
+            net::NetError::InvalidGroup => Self::InvalidArgument,
+            net::NetError::GroupTableFull => Self::NoBufferSpace,
+            net::NetError::InvalidAddress => Self::InvalidArgument,
+            net::NetError::NoLease => Self::NotConnected,
+            net::NetError::WouldBlock => Self::WouldBlock,
+            net::NetError::NotConnected => Self::NotConnected,
+            net::NetError::ConnectionReset => Self::ConnectionReset,
+            net::NetError::ConnectionRefused => Self::ConnectionRefused,
+            net::NetError::InvalidSocket => Self::BadFd,
+            net::NetError::SocketTableFull => Self::NoBufferSpace,
+            net::NetError::PortInUse => Self::AddressInUse,
+        }
+    }
+}
+
+impl From<fs::FsError> for KernelError {
+    fn from(error: fs::FsError) -> Self {
+        match error {
+            fs::FsError::InvalidPath | fs::FsError::InvalidName => Self::InvalidArgument,
+            fs::FsError::NameTooLong => Self::NameTooLong,
+            fs::FsError::NotFound => Self::NotFound,
+            fs::FsError::NoSpace | fs::FsError::StorageNoSpace => Self::NoSpace,
+            fs::FsError::FileTooLarge => Self::MessageTooLong,
+            fs::FsError::BufferTooSmall => Self::InvalidArgument,
+            fs::FsError::DiskCorrupt | fs::FsError::StorageIo => Self::Io,
+            fs::FsError::StorageUnavailable => Self::NoDevice,
+        }
+    }
+}
+
+impl From<storage::StorageError> for KernelError {
+    fn from(error: storage::StorageError) -> Self {
+        match error {
+            storage::StorageError::NotReady => Self::NoDevice,
+            storage::StorageError::NotFound => Self::NoDevice,
+            storage::StorageError::QueueTooSmall => Self::NoDevice,
+            storage::StorageError::QueueUnavailable => Self::NoDevice,
+            storage::StorageError::AddressTranslationFailed => Self::BadAddress,
+            storage::StorageError::OutOfRange => Self::InvalidArgument,
+            storage::StorageError::IoTimeout => Self::TimedOut,
+            storage::StorageError::DeviceFailure => Self::Io,
+        }
+    }
+}
+
+pub fn log_errno_table() {
+    for error in ERRNO_TABLE {
+        serial::write_fmt(format_args!(
+            "errno: {:>3} {:<15} {}\n",
+            error.errno(),
+            error.name(),
+            error.as_str()
+        ));
+    }
+}
+
+pub fn log_errno_to_serial(errno: i32) -> Status {
+    match KernelError::from_errno(errno.saturating_abs()) {
+        Some(error) => {
+            serial::write_fmt(format_args!(
+                "errno: {} {} ({})\n",
+                error.errno(),
+                error.name(),
+                error.as_str()
+            ));
+            Status::Ok
+        }
+        None => {
+            serial::write_fmt(format_args!("errno: {} unknown\n", errno));
+            Status::Failed
+        }
+    }
+}
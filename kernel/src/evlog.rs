@@ -0,0 +1,292 @@
+// kernel/src/evlog.rs: binary event log, a ring of tick-stamped 16-byte records written by hot
+// paths (two relaxed stores each) and dumped as `~ev1` lines for `cargo xtask evlog-decode`.
+use crate::fs::{base64, crc32::crc32};
+use crate::log::Subsystem;
+use crate::{serial, time};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Records kept; older ones are overwritten.
+const CAPACITY: usize = 2048;
+const RECORD_BYTES: usize = 16;
+/// Records per `~ev1` line: 256 bytes, 344 base64 characters.
+const RECORDS_PER_LINE: usize = 16;
+const LINE_PREFIX: &str = "~ev1 ";
+/// Dump format; bump when the record layout changes.
+const VERSION: u32 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// `a` = frame bytes, `b` = ethertype.
+    NetRx,
+    /// `a` = bytes handed to the device, `b` = TX slot.
+    NetTx,
+    /// `a` = `x << 16 | y`, `b` = `w << 16 | h` of the presented region.
+    GfxPresent,
+    /// `a` = engine ticks run this poll, `b` = frames so far.
+    DoomTick,
+    /// `a` = frames queued, `b` = frames pending on the device afterwards.
+    PcmSubmit,
+    /// The device queue ran dry while started: `a` = frames waiting in the FIFO (non-zero means
+    /// the pump ran late, zero means the producer did), `b` = frames of the last completion.
+    PcmUnderrun,
+    /// `a` = frames dropped from the FIFO over its high-water mark, `b` = frames left.
+    PcmTrim,
+    /// `a` = sector (low 32 bits), `b` = virtio-blk request type.
+    DiskIo,
+    /// `a` = pid, `b` = `number << 16 | rc as u16`.
+    Syscall,
+}
+
+impl Event {
+    pub const ALL: [Self; 9] = [
+        Self::NetRx,
+        Self::NetTx,
+        Self::GfxPresent,
+        Self::DoomTick,
+        Self::PcmSubmit,
+        Self::PcmUnderrun,
+        Self::PcmTrim,
+        Self::DiskIo,
+        Self::Syscall,
+    ];
+
+    pub const fn subsystem(self) -> Subsystem {
+        match self {
+            Self::NetRx | Self::NetTx => Subsystem::Net,
+            Self::GfxPresent => Subsystem::Gfx,
+            Self::DoomTick => Subsystem::Doom,
+            Self::PcmSubmit | Self::PcmUnderrun | Self::PcmTrim => Subsystem::Audio,
+            Self::DiskIo => Subsystem::Storage,
+            Self::Syscall => Subsystem::Proc,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::NetRx => "rx",
+            Self::NetTx => "tx",
+            Self::GfxPresent => "present",
+            Self::DoomTick => "tick",
+            Self::PcmSubmit => "pcm_submit",
+            Self::PcmUnderrun => "pcm_underrun",
+            Self::PcmTrim => "pcm_trim",
+            Self::DiskIo => "io",
+            Self::Syscall => "syscall",
+        }
+    }
+
+    /// Names of the two payload words, as printed by `evlog tail` and the xtask decoder.
+    pub const fn payload_names(self) -> (&'static str, &'static str) {
+        match self {
+            Self::NetRx => ("len", "ethertype"),
+            Self::NetTx => ("len", "slot"),
+            Self::GfxPresent => ("xy", "wh"),
+            Self::DoomTick => ("steps", "frames"),
+            Self::PcmSubmit => ("frames", "pending"),
+            Self::PcmUnderrun => ("fifo_frames", "completed"),
+            Self::PcmTrim => ("dropped", "left"),
+            Self::DiskIo => ("sector", "type"),
+            Self::Syscall => ("pid", "number_rc"),
+        }
+    }
+
+    const fn id(self) -> u8 {
+        self as u8
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(id)).copied()
+    }
+}
+
+/// `w0` = tick (low 32 bits) | subsystem << 32 | event << 40 | valid << 48; `w1` = a | b << 32.
+struct Slot {
+    w0: AtomicU64,
+    w1: AtomicU64,
+}
+
+const VALID_BIT: u64 = 1 << 48;
+
+static RING: [Slot; CAPACITY] = [const {
+    Slot {
+        w0: AtomicU64::new(0),
+        w1: AtomicU64::new(0),
+    }
+}; CAPACITY];
+static NEXT: AtomicU64 = AtomicU64::new(0);
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Appends one record. Safe from interrupt context; a record being overwritten while it is
+/// dumped can come out torn, which the dump cannot tell apart from a real one.
+#[inline]
+pub fn record(event: Event, a: u32, b: u32) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let index = NEXT.fetch_add(1, Ordering::Relaxed) as usize % CAPACITY;
+    let slot = &RING[index];
+    let w0 = (time::ticks() & 0xffff_ffff)
+        | (event.subsystem() as u64) << 32
+        | u64::from(event.id()) << 40
+        | VALID_BIT;
+    slot.w0.store(w0, Ordering::Relaxed);
+    slot.w1
+        .store(u64::from(a) | u64::from(b) << 32, Ordering::Relaxed);
+}
+
+#[derive(Clone, Copy)]
+struct Record {
+    tick: u32,
+    event: Event,
+    a: u32,
+    b: u32,
+}
+
+impl Record {
+    fn load(slot: &Slot) -> Option<Self> {
+        let w0 = slot.w0.load(Ordering::Relaxed);
+        let w1 = slot.w1.load(Ordering::Relaxed);
+        if w0 & VALID_BIT == 0 {
+            return None;
+        }
+        Some(Self {
+            tick: w0 as u32,
+            event: Event::from_id((w0 >> 40) as u8)?,
+            a: w1 as u32,
+            b: (w1 >> 32) as u32,
+        })
+    }
+
+    /// Wire layout: subsystem, event, two reserved bytes, then tick, `a` and `b` little endian.
+    fn encode(&self, out: &mut [u8]) {
+        out[0] = self.event.subsystem() as u8;
+        out[1] = self.event.id();
+        out[2..4].fill(0);
+        out[4..8].copy_from_slice(&self.tick.to_le_bytes());
+        out[8..12].copy_from_slice(&self.a.to_le_bytes());
+        out[12..16].copy_from_slice(&self.b.to_le_bytes());
+    }
+}
+
+/// Records oldest first, with how many were overwritten before the oldest kept one.
+fn snapshot(mut f: impl FnMut(Record)) -> u64 {
+    let written = NEXT.load(Ordering::Relaxed);
+    let kept = written.min(CAPACITY as u64);
+    let first = written - kept;
+    for seq in first..written {
+        if let Some(record) = Record::load(&RING[seq as usize % CAPACITY]) {
+            f(record);
+        }
+    }
+    first
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn clear() {
+    for slot in &RING {
+        slot.w0.store(0, Ordering::Relaxed);
+    }
+    NEXT.store(0, Ordering::Relaxed);
+}
+
+/// `evlog`: state and the per-event counts of what the ring holds now.
+pub fn log_status() {
+    let mut counts = [0u32; Event::ALL.len()];
+    let mut kept = 0u32;
+    let overwritten = snapshot(|record| {
+        counts[usize::from(record.event.id())] += 1;
+        kept += 1;
+    });
+    serial::write_fmt(format_args!(
+        "evlog: enabled={} recorded={} kept={kept} overwritten={overwritten} capacity={CAPACITY} record_bytes={RECORD_BYTES}\n",
+        ENABLED.load(Ordering::Relaxed),
+        NEXT.load(Ordering::Relaxed),
+    ));
+    serial::write_str("evlog: events");
+    for event in Event::ALL {
+        serial::write_fmt(format_args!(
+            " {}.{}={}",
+            event.subsystem().as_str(),
+            event.as_str(),
+            counts[usize::from(event.id())]
+        ));
+    }
+    serial::write_line("");
+}
+
+/// `evlog tail [n]`: the newest `n` records as text.
+pub fn log_tail(count: usize) {
+    let written = NEXT.load(Ordering::Relaxed);
+    let kept = written.min(CAPACITY as u64) as usize;
+    let count = count.min(kept);
+    let mut skip = kept - count;
+    snapshot(|record| {
+        if skip > 0 {
+            skip -= 1;
+            return;
+        }
+        let (a_name, b_name) = record.event.payload_names();
+        serial::write_fmt(format_args!(
+            "evlog: tick={} {}.{} {a_name}={:#x} {b_name}={:#x}\n",
+            record.tick,
+            record.event.subsystem().as_str(),
+            record.event.as_str(),
+            record.a,
+            record.b
+        ));
+    });
+}
+
+/// `evlog dump`: header, one `def` line per event, then the records as `~ev1 <count> <crc32>
+/// <base64>` lines, oldest first. Recording is paused meanwhile so the dump is consistent.
+pub fn dump_to_serial() {
+    let was_enabled = ENABLED.swap(false, Ordering::Relaxed);
+    let written = NEXT.load(Ordering::Relaxed);
+    serial::write_fmt(format_args!(
+        "evlog: dump version={VERSION} records={} overwritten={} capacity={CAPACITY} hz={} now={}\n",
+        written.min(CAPACITY as u64),
+        written.saturating_sub(CAPACITY as u64),
+        time::PIT_HZ,
+        time::ticks()
+    ));
+    for event in Event::ALL {
+        let (a_name, b_name) = event.payload_names();
+        serial::write_fmt(format_args!(
+            "evlog: def {} {} {}.{} {a_name} {b_name}\n",
+            event.subsystem() as u8,
+            event.id(),
+            event.subsystem().as_str(),
+            event.as_str()
+        ));
+    }
+
+    let mut chunk = [0u8; RECORDS_PER_LINE * RECORD_BYTES];
+    let mut pending = 0usize;
+    let mut lines = 0u32;
+    let mut flush = |chunk: &[u8], records: usize| {
+        let mut text = [0u8; base64::encoded_len(RECORDS_PER_LINE * RECORD_BYTES)];
+        let len = base64::encode(chunk, &mut text);
+        serial::write_fmt(format_args!(
+            "{LINE_PREFIX}{records} {:08x} {}\n",
+            crc32(chunk),
+            core::str::from_utf8(&text[..len]).unwrap_or("")
+        ));
+        lines += 1;
+    };
+    snapshot(|record| {
+        record.encode(&mut chunk[pending * RECORD_BYTES..(pending + 1) * RECORD_BYTES]);
+        pending += 1;
+        if pending == RECORDS_PER_LINE {
+            flush(&chunk, pending);
+            pending = 0;
+        }
+    });
+    if pending > 0 {
+        flush(&chunk[..pending * RECORD_BYTES], pending);
+    }
+    serial::write_fmt(format_args!("evlog: dump end lines={lines}\n"));
+    ENABLED.store(was_enabled, Ordering::Relaxed);
+}
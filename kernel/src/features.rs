@@ -0,0 +1,96 @@
+// kernel/src/features.rs: optional subsystems selected by Cargo features (net, audio, doom,
+// desktop). A disabled one is not compiled, so only its command names are left here: the shell
+// turns those commands away instead of reporting a device that was never probed.
+use crate::serial;
+use core::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Net,
+    Audio,
+    Doom,
+    Desktop,
+}
+
+impl Feature {
+    pub const ALL: [Self; 4] = [Self::Net, Self::Audio, Self::Doom, Self::Desktop];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Net => "net",
+            Self::Audio => "audio",
+            Self::Doom => "doom",
+            Self::Desktop => "desktop",
+        }
+    }
+
+    pub const fn enabled(self) -> bool {
+        match self {
+            Self::Net => cfg!(feature = "net"),
+            Self::Audio => cfg!(feature = "audio"),
+            Self::Doom => cfg!(feature = "doom"),
+            Self::Desktop => cfg!(feature = "desktop"),
+        }
+    }
+}
+
+/// First words of the shell commands each subsystem owns. `fm` stays available without the
+/// desktop because it also lists and copies files over serial.
+const COMMANDS: [(&str, Feature); 18] = [
+    ("net", Feature::Net),
+    ("ping", Feature::Net),
+    ("dhcp", Feature::Net),
+    ("udp", Feature::Net),
+    ("tcp", Feature::Net),
+    ("wol", Feature::Net),
+    ("ifconfig", Feature::Net),
+    ("arp", Feature::Net),
+    ("netstat", Feature::Net),
+    ("curl", Feature::Net),
+    ("fw", Feature::Net),
+    ("service", Feature::Net),
+    ("netconsole", Feature::Net),
+    ("telemetry", Feature::Net),
+    ("audio", Feature::Audio),
+    ("doom", Feature::Doom),
+    ("ui", Feature::Desktop),
+    ("settings", Feature::Desktop),
+];
+
+/// The disabled feature that owns `command`, if any.
+pub fn disabled_for(command: &str) -> Option<Feature> {
+    let word = command.split_whitespace().next()?;
+    COMMANDS
+        .iter()
+        .find(|(name, feature)| *name == word && !feature.enabled())
+        .map(|(_, feature)| *feature)
+}
+
+/// Boot line listing every optional subsystem, e.g. `Features: net=on audio=off ...`.
+pub fn log_boot_line() {
+    let [net, audio, doom, desktop] = Feature::ALL.map(|feature| on_off(feature.enabled()));
+    serial::write_fmt(format_args!(
+        "Features: net={net} audio={audio} doom={doom} desktop={desktop}\n"
+    ));
+}
+
+/// Comma-separated enabled features for the `sysinfo: begin` line, `none` if there are none.
+pub struct EnabledList;
+
+impl fmt::Display for EnabledList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut enabled = Feature::ALL.into_iter().filter(|feature| feature.enabled());
+        let Some(first) = enabled.next() else {
+            return f.write_str("none");
+        };
+        f.write_str(first.as_str())?;
+        for feature in enabled {
+            write!(f, ",{}", feature.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+const fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
@@ -0,0 +1,121 @@
+// kernel/src/fs/append.rs: append-only log files with per-file size caps (tail rotation).
+use super::{FsError, MAX_FILE_BYTES, MAX_FILE_NAME_BYTES, name};
+
+/// Files that can carry a rotation cap at once (dmesg, telemetry, a couple of spares).
+pub const MAX_ROTATED_FILES: usize = 4;
+
+#[derive(Clone, Copy)]
+pub struct RotationRule {
+    name: [u8; MAX_FILE_NAME_BYTES],
+    name_len: usize,
+    max_bytes: usize,
+}
+
+impl RotationRule {
+    const fn empty() -> Self {
+        Self {
+            name: [0; MAX_FILE_NAME_BYTES],
+            name_len: 0,
+            max_bytes: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("<invalid-name>")
+    }
+
+    pub const fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+}
+
+pub(super) struct RotationTable {
+    rules: [RotationRule; MAX_ROTATED_FILES],
+    appends: u64,
+    rotations: u64,
+    rotated_bytes: u64,
+}
+
+impl RotationTable {
+    pub(super) const fn new() -> Self {
+        Self {
+            rules: [RotationRule::empty(); MAX_ROTATED_FILES],
+            appends: 0,
+            rotations: 0,
+            rotated_bytes: 0,
+        }
+    }
+
+    /// Cap for `path`; files without a rule may still grow to `MAX_FILE_BYTES`.
+    pub(super) fn limit(&self, path: &str) -> usize {
+        let name = rule_key(path);
+        self.rules
+            .iter()
+            .find(|rule| rule.max_bytes != 0 && name::matches(rule.name(), name))
+            .map_or(MAX_FILE_BYTES, |rule| rule.max_bytes)
+    }
+
+    pub(super) fn set(&mut self, path: &str, max_bytes: usize) -> Result<(), FsError> {
+        if max_bytes == 0 || max_bytes > MAX_FILE_BYTES {
+            return Err(FsError::FileTooLarge);
+        }
+        let name = name::validate(path)?;
+        let index = match self
+            .rules
+            .iter()
+            .position(|rule| rule.max_bytes != 0 && name::matches(rule.name(), name))
+        {
+            Some(index) => index,
+            None => self
+                .rules
+                .iter()
+                .position(|rule| rule.max_bytes == 0)
+                .ok_or(FsError::NoSpace)?,
+        };
+        let rule = &mut self.rules[index];
+        rule.name[..name.len()].copy_from_slice(name.as_bytes());
+        rule.name_len = name.len();
+        rule.max_bytes = max_bytes;
+        Ok(())
+    }
+
+    pub(super) fn rules(&self) -> impl Iterator<Item = &RotationRule> {
+        self.rules.iter().filter(|rule| rule.max_bytes != 0)
+    }
+
+    pub(super) fn record(&mut self, appended: bool, dropped: usize) {
+        if appended {
+            self.appends = self.appends.saturating_add(1);
+        }
+        if dropped > 0 {
+            self.rotations = self.rotations.saturating_add(1);
+            self.rotated_bytes = self.rotated_bytes.saturating_add(dropped as u64);
+        }
+    }
+
+    pub(super) fn stats(&self) -> (u64, u64, u64) {
+        (self.appends, self.rotations, self.rotated_bytes)
+    }
+}
+
+fn rule_key(path: &str) -> &str {
+    let trimmed = path.trim();
+    trimmed.strip_prefix('/').unwrap_or(trimmed)
+}
+
+/// Offset where the kept tail of `data` starts so at most `max_bytes` remain. The cut moves
+/// forward to the next line start when one exists inside the tail, so a rotated log never
+/// opens with half a line.
+pub(super) fn tail_start(data: &[u8], max_bytes: usize) -> usize {
+    if data.len() <= max_bytes {
+        return 0;
+    }
+    let start = data.len() - max_bytes;
+    if start > 0 && data[start - 1] == b'\n' {
+        return start;
+    }
+    match data[start..].iter().position(|byte| *byte == b'\n') {
+        Some(offset) if start + offset + 1 < data.len() => start + offset + 1,
+        _ => start,
+    }
+}
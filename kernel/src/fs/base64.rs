@@ -0,0 +1,69 @@
+// kernel/src/fs/base64.rs: standard base64 (RFC 4648, `=` padding) for serial file transfer.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encoded length of `len` input bytes.
+pub const fn encoded_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+/// Encodes `input` into `out`, which must hold `encoded_len(input.len())` bytes.
+pub fn encode(input: &[u8], out: &mut [u8]) -> usize {
+    let mut written = 0;
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = chunk.get(1).copied().unwrap_or(0);
+        let b2 = chunk.get(2).copied().unwrap_or(0);
+        out[written] = ALPHABET[usize::from(b0 >> 2)];
+        out[written + 1] = ALPHABET[usize::from((b0 & 0x03) << 4 | b1 >> 4)];
+        out[written + 2] = if chunk.len() > 1 {
+            ALPHABET[usize::from((b1 & 0x0f) << 2 | b2 >> 6)]
+        } else {
+            b'='
+        };
+        out[written + 3] = if chunk.len() > 2 {
+            ALPHABET[usize::from(b2 & 0x3f)]
+        } else {
+            b'='
+        };
+        written += 4;
+    }
+    written
+}
+
+/// Decodes one line of whole 4-character groups into `out`; padding may only end the line.
+/// Returns `None` on a bad character, misplaced padding, a partial group or a short `out`.
+pub fn decode(text: &[u8], out: &mut [u8]) -> Option<usize> {
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut written = 0;
+    let groups = text.len() / 4;
+    for (index, group) in text.as_chunks::<4>().0.iter().enumerate() {
+        let padding = group.iter().rev().take_while(|byte| **byte == b'=').count();
+        if padding > 2 || (padding > 0 && index + 1 != groups) {
+            return None;
+        }
+        let mut bits = 0u32;
+        for byte in &group[..4 - padding] {
+            bits = bits << 6 | u32::from(sextet(*byte)?);
+        }
+        bits <<= 6 * padding as u32;
+        let bytes = bits.to_be_bytes();
+        let len = 3 - padding;
+        out.get_mut(written..written + len)?
+            .copy_from_slice(&bytes[1..1 + len]);
+        written += len;
+    }
+    Some(written)
+}
+
+const fn sextet(byte: u8) -> Option<u8> {
+    match byte {
+        b'A'..=b'Z' => Some(byte - b'A'),
+        b'a'..=b'z' => Some(byte - b'a' + 26),
+        b'0'..=b'9' => Some(byte - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
@@ -1,15 +1,35 @@
 // kernel/src/fs/diskfs.rs: M6.1 fixed-layout block filesystem over virtio-blk sectors.
-use super::{DirEntry, FsError, MAX_FILE_BYTES, MAX_FILE_NAME_BYTES, MAX_FILES, Vfs};
+use super::crc32::crc32;
+use super::name;
+use super::{DirEntry, FileStat, FsError, MAX_FILE_NAME_BYTES, MAX_FILES, Vfs};
 use crate::storage;
+use alloc::vec::Vec;
 
 const MAGIC: &[u8; 8] = b"AROSTFS1";
-const VERSION: u16 = 1;
+/// Version 2 allows files of more than one sector. Version 1 volumes are mounted as they are:
+/// their files are one sector at most and read the same, and the next metadata write
+/// upgrades them.
+const VERSION: u16 = 2;
+const VERSION_SINGLE_SECTOR: u16 = 1;
 const SUPERBLOCK_SECTOR: u64 = 0;
 const DIR_START_SECTOR: u64 = 1;
 const DIR_ENTRY_BYTES: usize = 72;
 const DIR_BYTES: usize = DIR_ENTRY_BYTES * MAX_FILES;
 const DIR_SECTORS: usize = DIR_BYTES.div_ceil(storage::SECTOR_SIZE);
 const DATA_START_SECTOR: u64 = DIR_START_SECTOR + DIR_SECTORS as u64;
+/// Directory entry flag (byte 2): bytes 20..24 hold the CRC-32 of the file contents.
+/// Volumes written before checksums leave it clear; the next write of the file sets it.
+const ENTRY_FLAG_CRC32: u8 = 1 << 0;
+/// Directory entry flag (byte 2): byte 3 maps which spans of the file are stored, bit `i`
+/// for span `i` (see `span_sectors`); the stored ones are packed in order into the extent and
+/// the rest read as zeros. Files without holes leave it clear and keep the dense layout.
+const ENTRY_FLAG_SPARSE: u8 = 1 << 1;
+/// Spans the one-byte sparse map can tell apart.
+const MAP_SPANS: usize = 8;
+/// Sectors one file can span; the superblock records it in bytes 12..14.
+const MAX_FILE_SECTORS: usize = 4096;
+/// Largest file diskfs stores: 2 MiB, enough for a 10 s 48k stereo WAV.
+pub const MAX_DISK_FILE_BYTES: usize = MAX_FILE_SECTORS * storage::SECTOR_SIZE;
 
 #[derive(Clone, Copy)]
 struct DiskEntry {
@@ -19,6 +39,9 @@ struct DiskEntry {
     size_bytes: u32,
     start_sector: u64,
     sector_count: u32,
+    crc32: Option<u32>,
+    /// Bit `i` set when logical sector `i` is stored; see `ENTRY_FLAG_SPARSE`.
+    map: u8,
 }
 
 impl DiskEntry {
@@ -30,15 +53,34 @@ impl DiskEntry {
             size_bytes: 0,
             start_sector: 0,
             sector_count: 0,
+            crc32: None,
+            map: 0,
         }
     }
 
+    fn logical_sectors(&self) -> usize {
+        (self.size_bytes as usize).div_ceil(storage::SECTOR_SIZE)
+    }
+
+    fn sparse(&self) -> bool {
+        self.map != dense_map(map_spans(self.logical_sectors()))
+    }
+
+    /// Extent offset of logical sector `sector`, or `None` for a hole. Every span before the
+    /// last is full, so the stored spans before this one each take `span` sectors.
+    fn stored_at(&self, sector: usize) -> Option<u64> {
+        let span = span_sectors(self.logical_sectors());
+        let bit = sector / span;
+        (self.map & (1 << bit) != 0).then(|| {
+            ((self.map & dense_map(bit)).count_ones() as usize * span + sector % span) as u64
+        })
+    }
+
     fn set_name(&mut self, name: &str) {
         self.name.fill(0);
-        let bytes = name.as_bytes();
-        let len = bytes.len().min(MAX_FILE_NAME_BYTES);
-        self.name[..len].copy_from_slice(&bytes[..len]);
-        self.name_len = len;
+        let bytes = name::truncate(name, MAX_FILE_NAME_BYTES).as_bytes();
+        self.name[..bytes.len()].copy_from_slice(bytes);
+        self.name_len = bytes.len();
     }
 
     fn name(&self) -> &str {
@@ -46,6 +88,24 @@ impl DiskEntry {
     }
 }
 
+/// Sector accounting: extents are bump-allocated and never reused, so sectors freed by
+/// deletes and relocating rewrites stay dead (discarded on the host) until a reformat.
+#[derive(Clone, Copy)]
+pub struct DiskSpace {
+    pub total_sectors: u64,
+    pub data_start_sector: u64,
+    pub next_free_sector: u64,
+    pub live_sectors: u64,
+}
+
+impl DiskSpace {
+    pub const fn dead_sectors(&self) -> u64 {
+        self.next_free_sector
+            .saturating_sub(self.data_start_sector)
+            .saturating_sub(self.live_sectors)
+    }
+}
+
 pub struct DiskFs {
     mounted: bool,
     total_sectors: u64,
@@ -71,7 +131,7 @@ impl DiskFs {
         if self.mounted {
             return Ok(());
         }
-        self.total_sectors = storage::capacity_sectors();
+        self.total_sectors = storage::fs_capacity_sectors();
         self.mount_or_format()
     }
 
@@ -109,7 +169,7 @@ impl DiskFs {
         }
 
         let version = u16::from_le_bytes([super_sector[8], super_sector[9]]);
-        if version != VERSION {
+        if version != VERSION && version != VERSION_SINGLE_SECTOR {
             return Err(FsError::DiskCorrupt);
         }
 
@@ -138,14 +198,19 @@ impl DiskFs {
 
     fn load_directory(&mut self) -> Result<(), FsError> {
         self.dir_bytes.fill(0);
-        let mut sector_buf = [0u8; storage::SECTOR_SIZE];
-        for sector_idx in 0..DIR_SECTORS {
-            storage::read_sector(DIR_START_SECTOR + sector_idx as u64, &mut sector_buf)
-                .map_err(|_| FsError::StorageIo)?;
-            let start = sector_idx * storage::SECTOR_SIZE;
-            let end = (start + storage::SECTOR_SIZE).min(DIR_BYTES);
-            let len = end.saturating_sub(start);
-            self.dir_bytes[start..end].copy_from_slice(&sector_buf[..len]);
+        let mut sectors = [[0u8; storage::SECTOR_SIZE]; DIR_SECTORS];
+        read_batch(
+            sectors
+                .iter_mut()
+                .enumerate()
+                .map(|(sector_idx, out)| (DIR_START_SECTOR + sector_idx as u64, out)),
+        )?;
+        for (chunk, sector) in self
+            .dir_bytes
+            .chunks_mut(storage::SECTOR_SIZE)
+            .zip(&sectors)
+        {
+            chunk.copy_from_slice(&sector[..chunk.len()]);
         }
 
         self.entries = [DiskEntry::empty(); MAX_FILES];
@@ -162,25 +227,46 @@ impl DiskFs {
             let size_bytes = read_u32(&self.dir_bytes, base + 4)?;
             let start_sector = read_u64(&self.dir_bytes, base + 8)?;
             let sector_count = read_u32(&self.dir_bytes, base + 16)?;
+            let crc32 = if self.dir_bytes[base + 2] & ENTRY_FLAG_CRC32 != 0 {
+                Some(read_u32(&self.dir_bytes, base + 20)?)
+            } else {
+                None
+            };
+            let logical_sectors = (size_bytes as usize).div_ceil(storage::SECTOR_SIZE);
+            if logical_sectors > MAX_FILE_SECTORS {
+                return Err(FsError::DiskCorrupt);
+            }
+            let sparse = self.dir_bytes[base + 2] & ENTRY_FLAG_SPARSE != 0;
+            let map = if sparse {
+                self.dir_bytes[base + 3]
+            } else {
+                dense_map(map_spans(logical_sectors))
+            };
             if sector_count > 0 {
                 let end = start_sector.saturating_add(sector_count as u64);
                 if start_sector < DATA_START_SECTOR || end > self.total_sectors {
                     return Err(FsError::DiskCorrupt);
                 }
-                if (size_bytes as usize) > (sector_count as usize * storage::SECTOR_SIZE) {
+            }
+            if sparse {
+                if map & !dense_map(map_spans(logical_sectors)) != 0
+                    || stored_sectors(map, logical_sectors) != sector_count
+                {
                     return Err(FsError::DiskCorrupt);
                 }
-            } else if size_bytes != 0 {
+            } else if (size_bytes as usize) > (sector_count as usize * storage::SECTOR_SIZE) {
                 return Err(FsError::DiskCorrupt);
             }
 
             entry.used = true;
-            entry.name_len = name_len;
             entry.name[..name_len]
                 .copy_from_slice(&self.dir_bytes[base + 24..base + 24 + name_len]);
+            entry.name_len = name::repair(&mut entry.name, name_len);
             entry.size_bytes = size_bytes;
             entry.start_sector = start_sector;
             entry.sector_count = sector_count;
+            entry.crc32 = crc32;
+            entry.map = map;
             used_count = used_count.saturating_add(1);
         }
 
@@ -197,7 +283,7 @@ impl DiskFs {
         super_sector[..MAGIC.len()].copy_from_slice(MAGIC);
         super_sector[8..10].copy_from_slice(&VERSION.to_le_bytes());
         super_sector[10..12].copy_from_slice(&(MAX_FILES as u16).to_le_bytes());
-        super_sector[12..14].copy_from_slice(&(MAX_FILE_BYTES as u16).to_le_bytes());
+        super_sector[12..14].copy_from_slice(&(MAX_FILE_SECTORS as u16).to_le_bytes());
         super_sector[14..16].copy_from_slice(&(DIR_SECTORS as u16).to_le_bytes());
         super_sector[16..24].copy_from_slice(&self.next_free_sector.to_le_bytes());
         super_sector[24..26].copy_from_slice(&self.file_count.to_le_bytes());
@@ -215,6 +301,14 @@ impl DiskFs {
             self.dir_bytes[base + 4..base + 8].copy_from_slice(&entry.size_bytes.to_le_bytes());
             self.dir_bytes[base + 8..base + 16].copy_from_slice(&entry.start_sector.to_le_bytes());
             self.dir_bytes[base + 16..base + 20].copy_from_slice(&entry.sector_count.to_le_bytes());
+            if let Some(crc32) = entry.crc32 {
+                self.dir_bytes[base + 2] |= ENTRY_FLAG_CRC32;
+                self.dir_bytes[base + 20..base + 24].copy_from_slice(&crc32.to_le_bytes());
+            }
+            if entry.sparse() {
+                self.dir_bytes[base + 2] |= ENTRY_FLAG_SPARSE;
+                self.dir_bytes[base + 3] = entry.map;
+            }
             self.dir_bytes[base + 24..base + 24 + entry.name_len]
                 .copy_from_slice(&entry.name[..entry.name_len]);
         }
@@ -232,26 +326,11 @@ impl DiskFs {
         Ok(())
     }
 
-    fn normalize_name(path: &str) -> Result<&str, FsError> {
-        let trimmed = path.trim();
-        let name = match trimmed.strip_prefix('/') {
-            Some(rest) => rest,
-            None => trimmed,
-        };
-        if name.is_empty() || name.contains('/') {
-            return Err(FsError::InvalidPath);
-        }
-        if name.len() > MAX_FILE_NAME_BYTES {
-            return Err(FsError::NameTooLong);
-        }
-        Ok(name)
-    }
-
     fn find_index(&self, name: &str) -> Option<usize> {
         self.entries
             .iter()
             .enumerate()
-            .find(|(_, entry)| entry.used && entry.name() == name)
+            .find(|(_, entry)| entry.used && name::matches(entry.name(), name))
             .map(|(idx, _)| idx)
     }
 
@@ -259,6 +338,29 @@ impl DiskFs {
         self.entries.iter().position(|entry| !entry.used)
     }
 
+    pub fn space(&self) -> DiskSpace {
+        DiskSpace {
+            total_sectors: self.total_sectors,
+            data_start_sector: DATA_START_SECTOR,
+            next_free_sector: self.next_free_sector,
+            live_sectors: self
+                .entries
+                .iter()
+                .filter(|entry| entry.used)
+                .map(|entry| u64::from(entry.sector_count))
+                .sum(),
+        }
+    }
+
+    /// Hands sectors no entry references any more back to the device. Runs after the
+    /// metadata is persisted; a failed discard only leaves stale data behind, so it is
+    /// counted by the storage layer instead of failing the fs operation.
+    fn release_extent(start_sector: u64, sectors: u32) {
+        if sectors > 0 && start_sector >= DATA_START_SECTOR {
+            let _ = storage::discard(start_sector, sectors);
+        }
+    }
+
     fn allocate_extent(&mut self, sectors: u32) -> Result<u64, FsError> {
         let needed = sectors as u64;
         let end = self.next_free_sector.saturating_add(needed);
@@ -291,7 +393,7 @@ impl Vfs for DiskFs {
         if !self.mounted {
             return Err(FsError::StorageUnavailable);
         }
-        let name = Self::normalize_name(path)?;
+        let name = name::normalize(path)?;
         let index = self.find_index(name).ok_or(FsError::NotFound)?;
         let entry = self.entries[index];
         let size = entry.size_bytes as usize;
@@ -301,43 +403,60 @@ impl Vfs for DiskFs {
         if size == 0 {
             return Ok(0);
         }
-        if entry.sector_count == 0 || entry.start_sector < DATA_START_SECTOR {
+        if entry.sector_count > 0 && entry.start_sector < DATA_START_SECTOR {
             return Err(FsError::DiskCorrupt);
         }
 
-        let mut sector_buf = [0u8; storage::SECTOR_SIZE];
-        let sectors = entry.sector_count as usize;
-        for sector_idx in 0..sectors {
-            storage::read_sector(entry.start_sector + sector_idx as u64, &mut sector_buf)
-                .map_err(|_| FsError::StorageIo)?;
-            let start = sector_idx * storage::SECTOR_SIZE;
-            let end = (start + storage::SECTOR_SIZE).min(size);
-            let len = end.saturating_sub(start);
-            out[start..end].copy_from_slice(&sector_buf[..len]);
-        }
+        // Sectors are read straight into `out`; holes are never read, so they keep these zeros.
+        let out = &mut out[..size];
+        out.fill(0);
+        let (sectors, tail) = out.as_chunks_mut::<{ storage::SECTOR_SIZE }>();
+        let mut last = [0u8; storage::SECTOR_SIZE];
+        let last_sector = (!tail.is_empty()).then_some(&mut last);
+        read_batch(
+            sectors
+                .iter_mut()
+                .chain(last_sector)
+                .enumerate()
+                .filter_map(|(sector_idx, out)| {
+                    let offset = entry.stored_at(sector_idx)?;
+                    Some((entry.start_sector + offset, out))
+                }),
+        )?;
+        let tail_len = tail.len();
+        tail.copy_from_slice(&last[..tail_len]);
         Ok(size)
     }
 
     fn write(&mut self, path: &str, data: &[u8]) -> Result<usize, FsError> {
         self.ensure_mounted()?;
-        if data.len() > MAX_FILE_BYTES {
+        if data.len() > MAX_DISK_FILE_BYTES {
             return Err(FsError::FileTooLarge);
         }
-        let name = Self::normalize_name(path)?;
+        let name = name::normalize(path)?;
+        let existing = self.find_index(name);
+        if existing.is_none() {
+            name::validate(name)?;
+        }
 
-        let needed_sectors = if data.is_empty() {
-            0
-        } else {
-            data.len().div_ceil(storage::SECTOR_SIZE) as u32
-        };
+        // Spans that are all zeros are holes: nothing is allocated or written for them.
+        let logical_sectors = data.len().div_ceil(storage::SECTOR_SIZE);
+        let span = span_sectors(logical_sectors);
+        let mut map = 0u8;
+        for (span_idx, chunk) in data.chunks(span * storage::SECTOR_SIZE).enumerate() {
+            if chunk.iter().any(|&byte| byte != 0) {
+                map |= 1 << span_idx;
+            }
+        }
+        let needed_sectors = stored_sectors(map, logical_sectors);
 
-        let entry_index = if let Some(index) = self.find_index(name) {
-            index
-        } else {
-            self.find_free_index().ok_or(FsError::NoSpace)?
+        let entry_index = match existing {
+            Some(index) => index,
+            None => self.find_free_index().ok_or(FsError::NoSpace)?,
         };
 
         let mut entry = self.entries[entry_index];
+        let old_extent = (entry.start_sector, entry.sector_count);
         let start_sector = if needed_sectors == 0 {
             0
         } else if entry.used && entry.sector_count >= needed_sectors && entry.start_sector != 0 {
@@ -347,41 +466,73 @@ impl Vfs for DiskFs {
         };
 
         let mut sector_buf = [0u8; storage::SECTOR_SIZE];
-        for sector_idx in 0..needed_sectors as usize {
+        let stored = data
+            .chunks(storage::SECTOR_SIZE)
+            .enumerate()
+            .filter(|(sector_idx, _)| map & (1 << (sector_idx / span)) != 0);
+        for (offset, (_, chunk)) in stored.enumerate() {
             sector_buf.fill(0);
-            let start = sector_idx * storage::SECTOR_SIZE;
-            let end = (start + storage::SECTOR_SIZE).min(data.len());
-            let len = end.saturating_sub(start);
-            sector_buf[..len].copy_from_slice(&data[start..end]);
-            storage::write_sector(start_sector + sector_idx as u64, &sector_buf)
+            sector_buf[..chunk.len()].copy_from_slice(chunk);
+            storage::write_sector(start_sector + offset as u64, &sector_buf)
                 .map_err(|_| FsError::StorageIo)?;
         }
 
         if !entry.used {
             self.file_count = self.file_count.saturating_add(1);
+            entry.set_name(name);
         }
         entry.used = true;
-        entry.set_name(name);
         entry.size_bytes = data.len() as u32;
         entry.start_sector = start_sector;
         entry.sector_count = needed_sectors;
+        entry.map = map;
+        entry.crc32 = Some(crc32(data));
         self.entries[entry_index] = entry;
         self.persist_metadata()?;
+        if old_extent.0 != start_sector {
+            Self::release_extent(old_extent.0, old_extent.1);
+        } else if old_extent.1 > needed_sectors {
+            Self::release_extent(
+                start_sector + u64::from(needed_sectors),
+                old_extent.1 - needed_sectors,
+            );
+        }
         Ok(data.len())
     }
 
     fn delete(&mut self, path: &str) -> Result<(), FsError> {
         self.ensure_mounted()?;
-        let name = Self::normalize_name(path)?;
+        let name = name::normalize(path)?;
         let index = self.find_index(name).ok_or(FsError::NotFound)?;
-        if self.entries[index].used {
+        let entry = self.entries[index];
+        if entry.used {
             self.entries[index] = DiskEntry::empty();
             self.file_count = self.file_count.saturating_sub(1);
             self.persist_metadata()?;
+            Self::release_extent(entry.start_sector, entry.sector_count);
         }
         Ok(())
     }
 
+    fn stored_checksum(&self, path: &str) -> Result<Option<u32>, FsError> {
+        let name = name::normalize(path)?;
+        let index = self.find_index(name).ok_or(FsError::NotFound)?;
+        Ok(self.entries[index].crc32)
+    }
+
+    fn stat(&self, path: &str) -> Result<FileStat, FsError> {
+        let name = name::normalize(path)?;
+        let index = self.find_index(name).ok_or(FsError::NotFound)?;
+        let entry = self.entries[index];
+        Ok(FileStat {
+            size: entry.size_bytes as usize,
+            allocated_bytes: entry.sector_count as usize * storage::SECTOR_SIZE,
+            holes: entry
+                .logical_sectors()
+                .saturating_sub(entry.sector_count as usize),
+        })
+    }
+
     fn file_count(&self) -> usize {
         self.entries.iter().filter(|entry| entry.used).count()
     }
@@ -395,6 +546,49 @@ impl Vfs for DiskFs {
     }
 }
 
+/// Sectors one bit of the sparse map covers: single sectors for files of up to 8 sectors,
+/// an eighth of the file (rounded up) for larger ones.
+const fn span_sectors(logical_sectors: usize) -> usize {
+    if logical_sectors <= MAP_SPANS {
+        1
+    } else {
+        logical_sectors.div_ceil(MAP_SPANS)
+    }
+}
+
+/// Spans a file of `logical_sectors` sectors has; the last one may be short.
+const fn map_spans(logical_sectors: usize) -> usize {
+    logical_sectors.div_ceil(span_sectors(logical_sectors))
+}
+
+/// Sectors the extent of a file with `map` holds.
+fn stored_sectors(map: u8, logical_sectors: usize) -> u32 {
+    let span = span_sectors(logical_sectors);
+    (0..MAP_SPANS)
+        .filter(|span_idx| map & (1 << span_idx) != 0)
+        .map(|span_idx| span.min(logical_sectors.saturating_sub(span_idx * span)))
+        .sum::<usize>() as u32
+}
+
+/// Map of a file whose first `spans` spans are all stored.
+const fn dense_map(spans: usize) -> u8 {
+    if spans >= MAP_SPANS {
+        u8::MAX
+    } else {
+        (1 << spans) - 1
+    }
+}
+
+/// Hands the `(sector, buffer)` pairs to storage as one batch, which orders the reads.
+fn read_batch<'a>(
+    reads: impl Iterator<Item = (u64, &'a mut [u8; storage::SECTOR_SIZE])>,
+) -> Result<(), FsError> {
+    let mut batch: Vec<_> = reads
+        .map(|(sector, out)| storage::SectorRead { sector, out })
+        .collect();
+    storage::read_sectors(&mut batch).map_err(|_| FsError::StorageIo)
+}
+
 fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, FsError> {
     if offset + 2 > bytes.len() {
         return Err(FsError::DiskCorrupt);
@@ -1,15 +1,26 @@
 // kernel/src/fs/mod.rs: M6.1 VFS facade with diskfs backend and ramfs fallback.
+mod append;
+pub mod base64;
+pub mod crc32;
 mod diskfs;
+mod name;
 mod ramfs;
+mod transfer;
 
-use crate::serial;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
 use crate::storage;
+use alloc::vec::Vec;
 use core::cell::UnsafeCell;
 use core::hint::spin_loop;
 use core::sync::atomic::{AtomicBool, Ordering};
-use diskfs::DiskFs;
+use diskfs::{DiskFs, MAX_DISK_FILE_BYTES};
 
+#[cfg(feature = "fuzz")]
+pub use name::validate as validate_name;
+pub use name::{case_insensitive, set_case_insensitive};
 pub use ramfs::{MAX_FILE_BYTES, MAX_FILE_NAME_BYTES, MAX_FILES, RamFs};
+pub use transfer::{Import, ImportStep, export_to_serial};
 
 #[derive(Clone, Copy)]
 pub struct FsInitReport {
@@ -19,11 +30,45 @@ pub struct FsInitReport {
     pub used_bytes: usize,
     pub max_files: usize,
     pub max_file_bytes: usize,
+    pub integrity: IntegritySummary,
+}
+
+/// Outcome of re-reading one file and comparing its CRC-32 with the stored one.
+#[derive(Clone, Copy)]
+pub enum Integrity {
+    Verified(u32),
+    Mismatch {
+        stored: u32,
+        actual: u32,
+    },
+    /// Written before checksums existed; `actual` is what the next write will store.
+    Unchecked(u32),
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct IntegritySummary {
+    pub verified: usize,
+    pub mismatched: usize,
+    pub unchecked: usize,
+    pub unreadable: usize,
+}
+
+impl IntegritySummary {
+    fn record(&mut self, result: Result<Integrity, FsError>) {
+        match result {
+            Ok(Integrity::Verified(_)) => self.verified += 1,
+            Ok(Integrity::Mismatch { .. }) => self.mismatched += 1,
+            Ok(Integrity::Unchecked(_)) => self.unchecked += 1,
+            Err(_) => self.unreadable += 1,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Eq, PartialEq)]
 pub enum FsError {
     InvalidPath,
+    /// Characters outside `A-Z a-z 0-9 . _ -`, or `.`/`..`.
+    InvalidName,
     NameTooLong,
     NotFound,
     NoSpace,
@@ -39,6 +84,7 @@ impl FsError {
     pub const fn as_str(self) -> &'static str {
         match self {
             Self::InvalidPath => "invalid_path",
+            Self::InvalidName => "invalid_name",
             Self::NameTooLong => "name_too_long",
             Self::NotFound => "not_found",
             Self::NoSpace => "no_space",
@@ -52,6 +98,32 @@ impl FsError {
     }
 }
 
+/// Result of an append: the file size afterwards and how many of the oldest bytes rotation
+/// dropped to stay under the file's cap.
+#[derive(Clone, Copy)]
+pub struct AppendOutcome {
+    pub len: usize,
+    pub rotated: usize,
+}
+
+/// Logical size against what the backend actually stores; diskfs leaves all-zero sectors
+/// unallocated.
+#[derive(Clone, Copy)]
+pub struct FileStat {
+    pub size: usize,
+    pub allocated_bytes: usize,
+    /// Sectors inside the file that read as zeros without being stored.
+    pub holes: usize,
+}
+
+/// Result of a hole punch: the zeroed byte range and how much storage it gave back.
+#[derive(Clone, Copy)]
+pub struct PunchOutcome {
+    pub zeroed: usize,
+    pub released_bytes: usize,
+    pub stat: FileStat,
+}
+
 #[derive(Clone, Copy)]
 pub struct DirEntry {
     name: [u8; MAX_FILE_NAME_BYTES],
@@ -69,10 +141,9 @@ impl DirEntry {
     }
 
     pub fn set_name(&mut self, name: &str) {
-        let bytes = name.as_bytes();
-        let len = bytes.len().min(MAX_FILE_NAME_BYTES);
-        self.name[..len].copy_from_slice(&bytes[..len]);
-        self.name_len = len;
+        let bytes = name::truncate(name, MAX_FILE_NAME_BYTES).as_bytes();
+        self.name[..bytes.len()].copy_from_slice(bytes);
+        self.name_len = bytes.len();
     }
 
     pub fn set_size(&mut self, size: usize) {
@@ -93,6 +164,9 @@ pub trait Vfs {
     fn read(&self, path: &str, out: &mut [u8]) -> Result<usize, FsError>;
     fn write(&mut self, path: &str, data: &[u8]) -> Result<usize, FsError>;
     fn delete(&mut self, path: &str) -> Result<(), FsError>;
+    /// CRC-32 recorded at the last write; `None` when the backend has none for this file.
+    fn stored_checksum(&self, path: &str) -> Result<Option<u32>, FsError>;
+    fn stat(&self, path: &str) -> Result<FileStat, FsError>;
     fn file_count(&self) -> usize;
     fn used_bytes(&self) -> usize;
 }
@@ -116,6 +190,8 @@ struct FsState {
     backend: FsBackend,
     ramfs: RamFs,
     diskfs: DiskFs,
+    integrity: IntegritySummary,
+    rotation: append::RotationTable,
 }
 
 impl FsState {
@@ -125,9 +201,46 @@ impl FsState {
             backend: FsBackend::RamFs,
             ramfs: RamFs::new(),
             diskfs: DiskFs::new(),
+            integrity: IntegritySummary {
+                verified: 0,
+                mismatched: 0,
+                unchecked: 0,
+                unreadable: 0,
+            },
+            rotation: append::RotationTable::new(),
         }
     }
 
+    /// Read-modify-write append through the active backend. Bytes past the file's cap are
+    /// dropped from the front, so the file always holds the most recent output.
+    fn append(&mut self, path: &str, data: &[u8]) -> Result<AppendOutcome, FsError> {
+        let limit = self.rotation.limit(path);
+        let clamped = data.len().saturating_sub(limit);
+        let data = &data[clamped..];
+        let vfs: &mut dyn Vfs = match self.backend {
+            FsBackend::RamFs => &mut self.ramfs,
+            FsBackend::DiskFs => &mut self.diskfs,
+        };
+        let mut buffer = [0u8; MAX_FILE_BYTES * 2];
+        let existing = match vfs.read(path, &mut buffer) {
+            Ok(len) => len,
+            Err(FsError::NotFound) if !data.is_empty() => 0,
+            Err(err) => return Err(err),
+        };
+        let total = existing + data.len();
+        buffer[existing..total].copy_from_slice(data);
+        let start = append::tail_start(&buffer[..total], limit);
+        if !data.is_empty() || start > 0 {
+            vfs.write(path, &buffer[start..total])?;
+        }
+        let rotated = clamped + start;
+        self.rotation.record(!data.is_empty(), rotated);
+        Ok(AppendOutcome {
+            len: total - start,
+            rotated,
+        })
+    }
+
     fn init(&mut self) -> FsInitReport {
         if self.initialized {
             return self.report();
@@ -140,6 +253,17 @@ impl FsState {
                     if self.diskfs.file_count() == 0 {
                         self.seed_defaults_diskfs();
                     }
+                    // The block layer is young; catch silent corruption before anything reads it.
+                    self.integrity = verify_all(&self.diskfs, |name, result| {
+                        if let Ok(Integrity::Mismatch { stored, actual }) = result {
+                            serial::write_severity_fmt(
+                                Severity::Error,
+                                format_args!(
+                                    "FS: checksum mismatch {name} stored={stored:08x} actual={actual:08x}\n"
+                                ),
+                            );
+                        }
+                    });
                 }
                 Err(err) => {
                     serial::write_fmt(format_args!(
@@ -168,6 +292,7 @@ impl FsState {
                 used_bytes: self.ramfs.used_bytes(),
                 max_files: MAX_FILES,
                 max_file_bytes: MAX_FILE_BYTES,
+                integrity: self.integrity,
             },
             FsBackend::DiskFs => FsInitReport {
                 backend: "diskfs-v0",
@@ -175,7 +300,8 @@ impl FsState {
                 file_count: self.diskfs.file_count(),
                 used_bytes: self.diskfs.used_bytes(),
                 max_files: MAX_FILES,
-                max_file_bytes: MAX_FILE_BYTES,
+                max_file_bytes: MAX_DISK_FILE_BYTES,
+                integrity: self.integrity,
             },
         }
     }
@@ -219,10 +345,10 @@ pub fn list_entries(out: &mut [DirEntry]) -> usize {
     with_vfs(|vfs| vfs.list(out))
 }
 
-pub fn cat_to_serial(path: &str) {
-    let mut data = [0u8; MAX_FILE_BYTES];
-    match read_file(path, &mut data) {
-        Ok(len) => {
+pub fn cat_to_serial(path: &str) -> Status {
+    match read_all(path) {
+        Ok(data) => {
+            let len = data.len();
             serial::write_fmt(format_args!("cat: {} bytes from {}\n", len, path.trim()));
             for byte in data.iter().take(len) {
                 if *byte == b'\n' {
@@ -233,8 +359,12 @@ pub fn cat_to_serial(path: &str) {
             if len == 0 || data[len.saturating_sub(1)] != b'\n' {
                 serial::write_str("\n");
             }
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!("cat: {} ({})\n", path.trim(), err.as_str()));
+            Status::Failed
         }
-        Err(err) => serial::write_fmt(format_args!("cat: {} ({})\n", path.trim(), err.as_str())),
     }
 }
 
@@ -242,14 +372,42 @@ pub fn read_file(path: &str, out: &mut [u8]) -> Result<usize, FsError> {
     with_vfs(|vfs| vfs.read(path, out))
 }
 
-pub fn write_from_echo(path: &str, text: &str) {
+/// Reads the whole of `path` into a buffer sized from its stat, for files that may be larger
+/// than `MAX_FILE_BYTES`.
+pub fn read_all(path: &str) -> Result<Vec<u8>, FsError> {
+    with_vfs(|vfs| read_all_in(vfs, path))
+}
+
+fn read_all_in(vfs: &dyn Vfs, path: &str) -> Result<Vec<u8>, FsError> {
+    let size = vfs.stat(path)?.size;
+    let mut data = Vec::new();
+    data.try_reserve_exact(size)
+        .map_err(|_| FsError::FileTooLarge)?;
+    data.resize(size, 0);
+    let len = vfs.read(path, &mut data)?;
+    data.truncate(len);
+    Ok(data)
+}
+
+/// Largest file the active backend stores: `MAX_FILE_BYTES` on ramfs, more on diskfs.
+pub fn max_file_bytes() -> usize {
+    with_fs_mut(|state| state.report().max_file_bytes)
+}
+
+pub fn write_from_echo(path: &str, text: &str) -> Status {
     match write_file(path, text.as_bytes()) {
-        Ok(written) => serial::write_fmt(format_args!(
-            "echo: wrote {} bytes to {}\n",
-            written,
-            path.trim()
-        )),
-        Err(err) => serial::write_fmt(format_args!("echo: {} ({})\n", path.trim(), err.as_str())),
+        Ok(written) => {
+            serial::write_fmt(format_args!(
+                "echo: wrote {} bytes to {}\n",
+                written,
+                path.trim()
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!("echo: {} ({})\n", path.trim(), err.as_str()));
+            Status::Failed
+        }
     }
 }
 
@@ -257,26 +415,190 @@ pub fn write_file(path: &str, data: &[u8]) -> Result<usize, FsError> {
     with_vfs_mut(|vfs| vfs.write(path, data))
 }
 
+pub fn append_from_echo(path: &str, text: &str) -> Status {
+    // Log-style appends: each echo lands on its own line.
+    let mut line = [0u8; MAX_FILE_BYTES + 1];
+    let len = text.len().min(MAX_FILE_BYTES);
+    line[..len].copy_from_slice(&text.as_bytes()[..len]);
+    line[len] = b'\n';
+    match append_file(path, &line[..=len]) {
+        Ok(outcome) => {
+            serial::write_fmt(format_args!(
+                "echo: appended {} bytes to {} (size={} rotated={})\n",
+                len + 1,
+                path.trim(),
+                outcome.len,
+                outcome.rotated
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!("echo: {} ({})\n", path.trim(), err.as_str()));
+            Status::Failed
+        }
+    }
+}
+
+/// Appends `data`, then trims the oldest bytes if the file's rotation cap (default
+/// `MAX_FILE_BYTES`) is exceeded.
+pub fn append_file(path: &str, data: &[u8]) -> Result<AppendOutcome, FsError> {
+    with_fs_mut(|state| state.append(path, data))
+}
+
+/// Caps `path` at `max_bytes` for all later appends and trims it to that size right away.
+/// A missing file keeps the rule and is created by its first append.
+pub fn set_rotation(path: &str, max_bytes: usize) -> Result<AppendOutcome, FsError> {
+    with_fs_mut(|state| {
+        state.rotation.set(path, max_bytes)?;
+        match state.append(path, &[]) {
+            Err(FsError::NotFound) => Ok(AppendOutcome { len: 0, rotated: 0 }),
+            result => result,
+        }
+    })
+}
+
+pub fn set_rotation_to_serial(path: &str, max_bytes: usize) -> Status {
+    match set_rotation(path, max_bytes) {
+        Ok(outcome) => {
+            serial::write_fmt(format_args!(
+                "log: rotate {} max_bytes={} size={} rotated={}\n",
+                path.trim(),
+                max_bytes,
+                outcome.len,
+                outcome.rotated
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("log: rotate {} ({})\n", path.trim(), err.as_str()),
+            );
+            Status::Failed
+        }
+    }
+}
+
+pub fn rotation_to_serial() {
+    with_fs_mut(|state| {
+        let (appends, rotations, rotated_bytes) = state.rotation.stats();
+        serial::write_fmt(format_args!(
+            "log: rotation rules={} appends={} rotations={} rotated_bytes={}\n",
+            state.rotation.rules().count(),
+            appends,
+            rotations,
+            rotated_bytes
+        ));
+        for rule in state.rotation.rules() {
+            serial::write_fmt(format_args!(
+                "log: {} max_bytes={}\n",
+                rule.name(),
+                rule.max_bytes()
+            ));
+        }
+    });
+}
+
 pub fn copy_file(source: &str, destination: &str) -> Result<usize, FsError> {
-    let mut data = [0u8; MAX_FILE_BYTES];
-    let len = read_file(source, &mut data)?;
-    write_file(destination, &data[..len])
+    let data = read_all(source)?;
+    write_file(destination, &data)
 }
 
-pub fn copy_file_to_serial(source: &str, destination: &str) {
+pub fn copy_file_to_serial(source: &str, destination: &str) -> Status {
     match copy_file(source, destination) {
-        Ok(written) => serial::write_fmt(format_args!(
-            "fm: copied {} bytes {} -> {}\n",
-            written,
-            source.trim(),
-            destination.trim()
-        )),
-        Err(err) => serial::write_fmt(format_args!(
-            "fm: copy {} -> {} ({})\n",
-            source.trim(),
-            destination.trim(),
-            err.as_str()
-        )),
+        Ok(written) => {
+            serial::write_fmt(format_args!(
+                "fm: copied {} bytes {} -> {}\n",
+                written,
+                source.trim(),
+                destination.trim()
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!(
+                "fm: copy {} -> {} ({})\n",
+                source.trim(),
+                destination.trim(),
+                err.as_str()
+            ));
+            Status::Failed
+        }
+    }
+}
+
+pub fn stat_file(path: &str) -> Result<FileStat, FsError> {
+    with_vfs(|vfs| vfs.stat(path))
+}
+
+/// `fm stat <file>`.
+pub fn stat_to_serial(path: &str) -> Status {
+    match stat_file(path) {
+        Ok(stat) => {
+            serial::write_fmt(format_args!(
+                "fm: stat {} size={} allocated={} holes={}\n",
+                path.trim(),
+                stat.size,
+                stat.allocated_bytes,
+                stat.holes
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!(
+                "fm: stat {} ({})\n",
+                path.trim(),
+                err.as_str()
+            ));
+            Status::Failed
+        }
+    }
+}
+
+/// Zeroes `len` bytes at `offset` (clamped to the file) without changing its size; sectors
+/// that end up all zeros are released. Read-modify-write under the FS lock, like appends.
+pub fn punch_hole(path: &str, offset: usize, len: usize) -> Result<PunchOutcome, FsError> {
+    with_vfs_mut(|vfs| {
+        let before = vfs.stat(path)?;
+        let mut data = read_all_in(vfs, path)?;
+        let size = data.len();
+        let start = offset.min(size);
+        let end = offset.saturating_add(len).min(size);
+        if start < end {
+            data[start..end].fill(0);
+            vfs.write(path, &data)?;
+        }
+        let stat = vfs.stat(path)?;
+        Ok(PunchOutcome {
+            zeroed: end - start,
+            released_bytes: before.allocated_bytes.saturating_sub(stat.allocated_bytes),
+            stat,
+        })
+    })
+}
+
+/// `fs punch <file> <offset> <len>`.
+pub fn punch_to_serial(path: &str, offset: usize, len: usize) -> Status {
+    match punch_hole(path, offset, len) {
+        Ok(outcome) => {
+            serial::write_fmt(format_args!(
+                "fs: punch {} zeroed={} released={} size={} allocated={} holes={}\n",
+                path.trim(),
+                outcome.zeroed,
+                outcome.released_bytes,
+                outcome.stat.size,
+                outcome.stat.allocated_bytes,
+                outcome.stat.holes
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("fs: punch {} ({})\n", path.trim(), err.as_str()),
+            );
+            Status::Failed
+        }
     }
 }
 
@@ -284,35 +606,174 @@ pub fn delete_file(path: &str) -> Result<(), FsError> {
     with_vfs_mut(|vfs| vfs.delete(path))
 }
 
-pub fn delete_file_to_serial(path: &str) {
+pub fn delete_file_to_serial(path: &str) -> Status {
     match delete_file(path) {
-        Ok(()) => serial::write_fmt(format_args!("fm: deleted {}\n", path.trim())),
-        Err(err) => serial::write_fmt(format_args!(
-            "fm: delete {} ({})\n",
-            path.trim(),
-            err.as_str()
-        )),
+        Ok(()) => {
+            serial::write_fmt(format_args!("fm: deleted {}\n", path.trim()));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!(
+                "fm: delete {} ({})\n",
+                path.trim(),
+                err.as_str()
+            ));
+            Status::Failed
+        }
     }
 }
 
-pub fn sync_to_disk_to_serial() {
+fn verify_in(vfs: &dyn Vfs, path: &str) -> Result<Integrity, FsError> {
+    let data = read_all_in(vfs, path)?;
+    let actual = crc32::crc32(&data);
+    Ok(match vfs.stored_checksum(path)? {
+        Some(stored) if stored == actual => Integrity::Verified(actual),
+        Some(stored) => Integrity::Mismatch { stored, actual },
+        None => Integrity::Unchecked(actual),
+    })
+}
+
+fn verify_all(
+    vfs: &dyn Vfs,
+    mut on_file: impl FnMut(&str, Result<Integrity, FsError>),
+) -> IntegritySummary {
+    let mut entries = [DirEntry::empty(); MAX_FILES];
+    let count = vfs.list(&mut entries);
+    let mut summary = IntegritySummary::default();
+    for entry in entries.iter().take(count) {
+        let result = verify_in(vfs, entry.name());
+        summary.record(result);
+        on_file(entry.name(), result);
+    }
+    summary
+}
+
+pub fn verify_file(path: &str) -> Result<Integrity, FsError> {
+    with_vfs(|vfs| verify_in(vfs, path))
+}
+
+fn log_integrity(name: &str, result: Result<Integrity, FsError>) {
+    match result {
+        Ok(Integrity::Verified(crc)) => {
+            serial::write_fmt(format_args!("fm: verify {name} ok crc={crc:08x}\n"));
+        }
+        Ok(Integrity::Mismatch { stored, actual }) => serial::write_severity_fmt(
+            Severity::Error,
+            format_args!("fm: verify {name} MISMATCH stored={stored:08x} actual={actual:08x}\n"),
+        ),
+        Ok(Integrity::Unchecked(crc)) => serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!("fm: verify {name} unchecked (no stored crc, current={crc:08x})\n"),
+        ),
+        Err(err) => serial::write_severity_fmt(
+            Severity::Error,
+            format_args!("fm: verify {name} ({})\n", err.as_str()),
+        ),
+    }
+}
+
+/// Verifies one file, or every file when `path` is `None`, and prints a summary. Fails on a
+/// mismatch or an unreadable file; unchecked files pass.
+pub fn verify_to_serial(path: Option<&str>) -> Status {
+    let summary = match path {
+        Some(path) => {
+            let result = verify_file(path);
+            log_integrity(path.trim(), result);
+            let mut summary = IntegritySummary::default();
+            summary.record(result);
+            summary
+        }
+        None => with_vfs(|vfs| verify_all(vfs, log_integrity)),
+    };
+    serial::write_fmt(format_args!(
+        "fm: verify ok={} mismatch={} unchecked={} unreadable={}\n",
+        summary.verified, summary.mismatched, summary.unchecked, summary.unreadable
+    ));
+    Status::check(summary.mismatched == 0 && summary.unreadable == 0)
+}
+
+pub fn sync_to_disk_to_serial() -> Status {
     match with_fs_mut(|state| match state.backend {
         FsBackend::DiskFs => state.diskfs.sync_metadata(),
         FsBackend::RamFs => Err(FsError::StorageUnavailable),
     }) {
-        Ok(()) => serial::write_line("sync: diskfs metadata saved"),
-        Err(err) => serial::write_fmt(format_args!("sync: failed ({})\n", err.as_str())),
+        Ok(()) => {
+            serial::write_line("sync: diskfs metadata saved");
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("sync: failed ({})\n", err.as_str())),
     }
 }
 
-pub fn reload_from_disk_to_serial() {
+pub fn reload_from_disk_to_serial() -> Status {
     match with_fs_mut(|state| match state.backend {
         FsBackend::DiskFs => state.diskfs.remount(),
         FsBackend::RamFs => Err(FsError::StorageUnavailable),
     }) {
-        Ok(()) => serial::write_line("reload: diskfs remounted"),
-        Err(err) => serial::write_fmt(format_args!("reload: failed ({})\n", err.as_str())),
+        Ok(()) => {
+            serial::write_line("reload: diskfs remounted");
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("reload: failed ({})\n", err.as_str())),
+    }
+}
+
+/// `sysinfo` line: backend and usage; `crc_mismatch` is the boot-time verification result.
+pub fn log_sysinfo() {
+    let report = with_fs_mut(|state| state.report());
+    serial::write_fmt(format_args!(
+        "sysinfo: fs backend={} storage_backed={} files={} used_bytes={} capacity_files={} capacity_file_bytes={} crc_mismatch={}\n",
+        report.backend,
+        report.storage_backed,
+        report.file_count,
+        report.used_bytes,
+        report.max_files,
+        report.max_file_bytes,
+        report.integrity.mismatched
+    ));
+}
+
+pub fn disk_stats_to_serial() {
+    let space = with_fs_mut(|state| match state.backend {
+        FsBackend::DiskFs => Some(state.diskfs.space()),
+        FsBackend::RamFs => None,
+    });
+    let discard = storage::discard_stats();
+    match space {
+        Some(space) => serial::write_fmt(format_args!(
+            "disk: stats sectors={} data_start={} next_free={} live_sectors={} dead_sectors={} reclaimable_bytes={} ",
+            space.total_sectors,
+            space.data_start_sector,
+            space.next_free_sector,
+            space.live_sectors,
+            space.dead_sectors(),
+            space.dead_sectors() * storage::SECTOR_SIZE as u64
+        )),
+        None => serial::write_str("disk: stats backend=ramfs "),
     }
+    serial::write_fmt(format_args!(
+        "discard_mode={} discard_max={} discard_requests={} discarded_sectors={} discard_failures={}\n",
+        discard.mode.as_str(),
+        discard.max_sectors,
+        discard.requests,
+        discard.sectors,
+        discard.failures
+    ));
+    let readahead = storage::readahead_stats();
+    let queue = storage::queue_stats();
+    serial::write_fmt(format_args!(
+        "disk: readahead window={} hits={} misses={} hit_pct={} fills={} fill_sectors={} unused_sectors={} batches={} reordered={} seek_sectors={}\n",
+        readahead.window,
+        readahead.hits,
+        readahead.misses,
+        readahead.hit_percent(),
+        readahead.fills,
+        readahead.fill_sectors,
+        readahead.unused_sectors,
+        queue.batches,
+        queue.reordered,
+        queue.seek_sectors
+    ));
 }
 
 fn with_vfs<R>(f: impl FnOnce(&dyn Vfs) -> R) -> R {
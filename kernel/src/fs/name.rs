@@ -0,0 +1,82 @@
+// kernel/src/fs/name.rs: file-name validation and the case-insensitive lookup switch shared by
+// every backend.
+use super::{FsError, MAX_FILE_NAME_BYTES};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// On by default so `Readme` and `README` name the same file instead of two file manager rows.
+static CASE_INSENSITIVE: AtomicBool = AtomicBool::new(true);
+
+/// Strips surrounding spaces and one leading `/`. Only the shape is checked, so a file stored
+/// under a name `validate` would now reject can still be read and deleted.
+pub(super) fn normalize(path: &str) -> Result<&str, FsError> {
+    let trimmed = path.trim();
+    let name = trimmed.strip_prefix('/').unwrap_or(trimmed);
+    if name.is_empty() || name.contains('/') {
+        return Err(FsError::InvalidPath);
+    }
+    if name.len() > MAX_FILE_NAME_BYTES {
+        return Err(FsError::NameTooLong);
+    }
+    Ok(name)
+}
+
+/// Names a new file may take: ASCII letters, digits, `.`, `_` and `-`, not `.` or `..`.
+pub fn validate(path: &str) -> Result<&str, FsError> {
+    let name = normalize(path)?;
+    if name == "."
+        || name == ".."
+        || !name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'_' | b'-'))
+    {
+        return Err(FsError::InvalidName);
+    }
+    Ok(name)
+}
+
+/// `name` cut to at most `max` bytes on a character boundary, so a stored name always reads
+/// back as UTF-8.
+pub(super) fn truncate(name: &str, max: usize) -> &str {
+    let mut end = name.len().min(max);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    &name[..end]
+}
+
+/// Rewrites each invalid UTF-8 sequence in a name read from disk as `?` and returns the new
+/// length. Such names predate validation or come from a damaged directory; without this they
+/// list as `<invalid-name>` and no lookup reaches them.
+pub(super) fn repair(name: &mut [u8; MAX_FILE_NAME_BYTES], len: usize) -> usize {
+    let original = *name;
+    let mut out = 0usize;
+    for chunk in original[..len].utf8_chunks() {
+        let valid = chunk.valid().as_bytes();
+        name[out..out + valid.len()].copy_from_slice(valid);
+        out += valid.len();
+        if !chunk.invalid().is_empty() {
+            name[out] = b'?';
+            out += 1;
+        }
+    }
+    name[out..].fill(0);
+    out
+}
+
+/// Whether a stored name matches a looked-up one under the current case rule.
+pub(super) fn matches(stored: &str, name: &str) -> bool {
+    if CASE_INSENSITIVE.load(Ordering::Relaxed) {
+        stored.eq_ignore_ascii_case(name)
+    } else {
+        stored == name
+    }
+}
+
+pub fn case_insensitive() -> bool {
+    CASE_INSENSITIVE.load(Ordering::Relaxed)
+}
+
+/// Returns true when the mode changed.
+pub fn set_case_insensitive(enabled: bool) -> bool {
+    CASE_INSENSITIVE.swap(enabled, Ordering::Relaxed) != enabled
+}
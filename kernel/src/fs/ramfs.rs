@@ -1,5 +1,7 @@
 // kernel/src/fs/ramfs.rs: fixed-capacity in-memory filesystem for M5.
-use super::{DirEntry, FsError, Vfs};
+use super::crc32::crc32;
+use super::name;
+use super::{DirEntry, FileStat, FsError, Vfs};
 
 pub const MAX_FILES: usize = 16;
 pub const MAX_FILE_NAME_BYTES: usize = 48;
@@ -12,6 +14,7 @@ struct RamFile {
     name_len: usize,
     data: [u8; MAX_FILE_BYTES],
     data_len: usize,
+    crc32: u32,
 }
 
 impl RamFile {
@@ -22,6 +25,7 @@ impl RamFile {
             name_len: 0,
             data: [0; MAX_FILE_BYTES],
             data_len: 0,
+            crc32: 0,
         }
     }
 
@@ -43,32 +47,12 @@ impl RamFs {
         }
     }
 
-    fn normalize_name(path: &str) -> Result<&str, FsError> {
-        let trimmed = path.trim();
-        let name = match trimmed.strip_prefix('/') {
-            Some(rest) => rest,
-            None => trimmed,
-        };
-        if name.is_empty() || name.contains('/') {
-            return Err(FsError::InvalidPath);
-        }
-        if name.len() > MAX_FILE_NAME_BYTES {
-            return Err(FsError::NameTooLong);
-        }
-        Ok(name)
-    }
-
     fn find_index(&self, name: &str) -> Option<usize> {
-        let bytes = name.as_bytes();
-        for (idx, file) in self.files.iter().enumerate() {
-            if !file.used || file.name_len != bytes.len() {
-                continue;
-            }
-            if file.name[..file.name_len] == bytes[..] {
-                return Some(idx);
-            }
-        }
-        None
+        self.files.iter().position(|file| {
+            file.used
+                && core::str::from_utf8(&file.name[..file.name_len])
+                    .is_ok_and(|stored| name::matches(stored, name))
+        })
     }
 
     fn find_free_slot(&self) -> Option<usize> {
@@ -85,8 +69,15 @@ impl RamFs {
         file.used = true;
         file.name_len = name.len();
         file.name[..file.name_len].copy_from_slice(name.as_bytes());
+        Self::write_data(file, data);
+    }
+
+    /// Replaces the contents and keeps the stored name, so a case-insensitive overwrite does
+    /// not rename the file.
+    fn write_data(file: &mut RamFile, data: &[u8]) {
         file.data_len = data.len();
         file.data[..file.data_len].copy_from_slice(data);
+        file.crc32 = crc32(data);
     }
 }
 
@@ -109,7 +100,7 @@ impl Vfs for RamFs {
     }
 
     fn read(&self, path: &str, out: &mut [u8]) -> Result<usize, FsError> {
-        let name = Self::normalize_name(path)?;
+        let name = name::normalize(path)?;
         let Some(index) = self.find_index(name) else {
             return Err(FsError::NotFound);
         };
@@ -125,11 +116,12 @@ impl Vfs for RamFs {
         if data.len() > MAX_FILE_BYTES {
             return Err(FsError::FileTooLarge);
         }
-        let name = Self::normalize_name(path)?;
+        let name = name::normalize(path)?;
         if let Some(index) = self.find_index(name) {
-            Self::write_slot(&mut self.files[index], name, data);
+            Self::write_data(&mut self.files[index], data);
             return Ok(data.len());
         }
+        let name = name::validate(name)?;
         let Some(index) = self.find_free_slot() else {
             return Err(FsError::NoSpace);
         };
@@ -138,7 +130,7 @@ impl Vfs for RamFs {
     }
 
     fn delete(&mut self, path: &str) -> Result<(), FsError> {
-        let name = Self::normalize_name(path)?;
+        let name = name::normalize(path)?;
         let Some(index) = self.find_index(name) else {
             return Err(FsError::NotFound);
         };
@@ -146,6 +138,24 @@ impl Vfs for RamFs {
         Ok(())
     }
 
+    fn stored_checksum(&self, path: &str) -> Result<Option<u32>, FsError> {
+        let name = name::normalize(path)?;
+        let index = self.find_index(name).ok_or(FsError::NotFound)?;
+        Ok(Some(self.files[index].crc32))
+    }
+
+    /// Slots are fixed arrays, so nothing is sparse: the contents count as allocated.
+    fn stat(&self, path: &str) -> Result<FileStat, FsError> {
+        let name = name::normalize(path)?;
+        let index = self.find_index(name).ok_or(FsError::NotFound)?;
+        let size = self.files[index].data_len;
+        Ok(FileStat {
+            size,
+            allocated_bytes: size,
+            holes: 0,
+        })
+    }
+
     fn file_count(&self) -> usize {
         self.files.iter().filter(|file| file.used).count()
     }
@@ -0,0 +1,133 @@
+// kernel/src/fs/transfer.rs: `fs import` / `fs export` of whole files as base64 over the serial console.
+use super::{FsError, MAX_FILE_BYTES, MAX_FILE_NAME_BYTES, base64, crc32::crc32, name, read_all};
+use crate::serial;
+use crate::shell::Status;
+use alloc::vec::Vec;
+
+/// Payload bytes per exported line: 57 bytes encode to the classic 76 base64 characters.
+const EXPORT_LINE_BYTES: usize = 57;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TransferError {
+    InvalidBase64,
+    /// More data arrived than the announced size.
+    Overflow,
+    ChecksumMismatch,
+    Fs(FsError),
+}
+
+impl TransferError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidBase64 => "invalid_base64",
+            Self::Overflow => "overflow",
+            Self::ChecksumMismatch => "checksum_mismatch",
+            Self::Fs(err) => err.as_str(),
+        }
+    }
+}
+
+/// Where an import stands after one line of base64.
+pub enum ImportStep {
+    NeedMore {
+        received: usize,
+    },
+    /// All bytes arrived, matched the checksum and were written.
+    Done {
+        bytes: usize,
+        crc: u32,
+    },
+}
+
+/// An `fs import` in progress: the shell feeds it every line until it completes or fails.
+pub struct Import {
+    name: [u8; MAX_FILE_NAME_BYTES],
+    name_len: usize,
+    size: usize,
+    expected_crc: Option<u32>,
+    data: Vec<u8>,
+}
+
+impl Import {
+    pub fn begin(name: &str, size: usize, expected_crc: Option<u32>) -> Result<Self, FsError> {
+        let name = name.trim();
+        name::validate(name)?;
+        if size > super::max_file_bytes() {
+            return Err(FsError::FileTooLarge);
+        }
+        let mut data = Vec::new();
+        data.try_reserve_exact(size)
+            .map_err(|_| FsError::FileTooLarge)?;
+        let mut stored = [0u8; MAX_FILE_NAME_BYTES];
+        stored[..name.len()].copy_from_slice(name.as_bytes());
+        Ok(Self {
+            name: stored,
+            name_len: name.len(),
+            size,
+            expected_crc,
+            data,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Decodes one line; the file is written once exactly `size` bytes have arrived, so an
+    /// empty file completes on its first (empty) line.
+    pub fn push_line(&mut self, line: &str) -> Result<ImportStep, TransferError> {
+        let mut decoded = [0u8; MAX_FILE_BYTES];
+        let len = base64::decode(line.trim().as_bytes(), &mut decoded)
+            .ok_or(TransferError::InvalidBase64)?;
+        if self.data.len() + len > self.size {
+            return Err(TransferError::Overflow);
+        }
+        self.data.extend_from_slice(&decoded[..len]);
+        if self.data.len() < self.size {
+            return Ok(ImportStep::NeedMore {
+                received: self.data.len(),
+            });
+        }
+
+        let crc = crc32(&self.data);
+        if self.expected_crc.is_some_and(|expected| expected != crc) {
+            return Err(TransferError::ChecksumMismatch);
+        }
+        super::write_file(self.name(), &self.data).map_err(TransferError::Fs)?;
+        Ok(ImportStep::Done {
+            bytes: self.data.len(),
+            crc,
+        })
+    }
+}
+
+/// Prints `fs: export` header, `fs: b64 <line>` records and an end marker for `path`.
+pub fn export_to_serial(path: &str) -> Status {
+    let path = path.trim();
+    let data = match read_all(path) {
+        Ok(data) => data,
+        Err(err) => {
+            return Status::failed(format_args!("fs: export {path} ({})\n", err.as_str()));
+        }
+    };
+    serial::write_fmt(format_args!(
+        "fs: export name={path} size={} crc32={:08x} lines={}\n",
+        data.len(),
+        crc32(&data),
+        data.len().div_ceil(EXPORT_LINE_BYTES)
+    ));
+    let mut line = [0u8; base64::encoded_len(EXPORT_LINE_BYTES)];
+    for chunk in data.chunks(EXPORT_LINE_BYTES) {
+        let written = base64::encode(chunk, &mut line);
+        serial::write_fmt(format_args!(
+            "fs: b64 {}\n",
+            core::str::from_utf8(&line[..written]).unwrap_or("")
+        ));
+    }
+    serial::write_fmt(format_args!("fs: export end name={path}\n"));
+    Status::Ok
+}
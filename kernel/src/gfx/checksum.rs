@@ -0,0 +1,99 @@
+// kernel/src/gfx/checksum.rs: FNV-1a checksums of presented pixels for `ui checksum`, so headless
+// tests can tell whether the desktop or the doom view changed, or compare it to a golden value.
+use super::Rect;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+pub(super) const fn start() -> u64 {
+    FNV_OFFSET
+}
+
+/// FNV-1a over 32-bit words, one pixel each at 4 bytes per pixel; a trailing partial word is
+/// hashed byte by byte.
+pub(super) fn extend(mut hash: u64, bytes: &[u8]) -> u64 {
+    let (words, rest) = bytes.as_chunks::<4>();
+    for word in words {
+        hash ^= u64::from(u32::from_le_bytes(*word));
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    for &byte in rest {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Checksums of what `present_rect` copied to the framebuffer.
+pub(super) struct PresentChecksums {
+    presents: u64,
+    /// Every region checksum folded in order; changes whenever anything is presented.
+    rolling: u64,
+    last_rect: Rect,
+    last_region: u64,
+    full_presents: u64,
+    last_full: Option<u64>,
+}
+
+impl PresentChecksums {
+    pub(super) const fn new() -> Self {
+        Self {
+            presents: 0,
+            rolling: FNV_OFFSET,
+            last_rect: Rect::ZERO,
+            last_region: 0,
+            full_presents: 0,
+            last_full: None,
+        }
+    }
+
+    pub(super) fn record(&mut self, rect: Rect, hash: u64, full_frame: bool) {
+        self.presents = self.presents.saturating_add(1);
+        self.rolling = extend(self.rolling, &hash.to_le_bytes());
+        self.last_rect = rect;
+        self.last_region = hash;
+        if full_frame {
+            self.full_presents = self.full_presents.saturating_add(1);
+            self.last_full = Some(hash);
+        }
+    }
+
+    pub(super) fn report(&self) -> PresentReport {
+        PresentReport {
+            presents: self.presents,
+            rolling: self.rolling,
+            last_region: (
+                self.last_rect.x,
+                self.last_rect.y,
+                self.last_rect.w,
+                self.last_rect.h,
+            ),
+            last_region_checksum: self.last_region,
+            full_presents: self.full_presents,
+            last_full: self.last_full,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct PresentReport {
+    pub presents: u64,
+    pub rolling: u64,
+    /// `(x, y, w, h)` of the last presented region.
+    pub last_region: (usize, usize, usize, usize),
+    pub last_region_checksum: u64,
+    pub full_presents: u64,
+    /// Checksum of the last full-screen present (a full redraw).
+    pub last_full: Option<u64>,
+}
+
+#[derive(Clone, Copy)]
+pub struct ChecksumReport {
+    /// Checksum of the whole screen as it is now, cursor excluded when double buffered.
+    pub frame: u64,
+    /// `backbuffer`, or `framebuffer` (cursor included) without double buffering.
+    pub source: &'static str,
+    /// The doom view area while the doom window shows a frame.
+    pub doom: Option<u64>,
+    pub presents: PresentReport,
+}
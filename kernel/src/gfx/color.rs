@@ -0,0 +1,102 @@
+// kernel/src/gfx/color.rs: global color transform (brightness, contrast, warm night mode) applied
+// as every pixel is encoded; one lookup table per channel keeps the per-pixel cost flat.
+use super::Color;
+use core::cell::UnsafeCell;
+
+pub const BRIGHTNESS_MIN: i16 = -100;
+pub const BRIGHTNESS_MAX: i16 = 100;
+pub const CONTRAST_MIN: u16 = 50;
+pub const CONTRAST_MAX: u16 = 200;
+/// Night mode channel gains in 1/256: red kept, green dimmed, most of the blue cut.
+const NIGHT_GAIN: [u32; 3] = [256, 200, 140];
+
+/// What `ui brightness`, `ui contrast` and `ui night` set.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct ColorAdjust {
+    /// Offset in percent of full scale.
+    pub brightness: i16,
+    /// Spread around mid-gray in percent; 100 leaves it alone.
+    pub contrast: u16,
+    pub night: bool,
+}
+
+impl ColorAdjust {
+    pub const NEUTRAL: Self = Self {
+        brightness: 0,
+        contrast: 100,
+        night: false,
+    };
+
+    pub fn parse_brightness(text: &str) -> Option<i16> {
+        text.parse()
+            .ok()
+            .filter(|value| (BRIGHTNESS_MIN..=BRIGHTNESS_MAX).contains(value))
+    }
+
+    pub fn parse_contrast(text: &str) -> Option<u16> {
+        text.parse()
+            .ok()
+            .filter(|value| (CONTRAST_MIN..=CONTRAST_MAX).contains(value))
+    }
+
+    /// Contrast around mid-gray, then the brightness offset, then the night gain.
+    fn channel(self, channel: usize, value: u8) -> u8 {
+        let centered = i32::from(value) - 128;
+        let contrasted = centered * i32::from(self.contrast) / 100 + 128;
+        let brightened = contrasted + i32::from(self.brightness) * 255 / 100;
+        let level = brightened.clamp(0, 255) as u32;
+        let gain = if self.night { NIGHT_GAIN[channel] } else { 256 };
+        (level * gain / 256) as u8
+    }
+}
+
+struct Transform {
+    adjust: ColorAdjust,
+    lut: [[u8; 256]; 3],
+}
+
+struct TransformCell(UnsafeCell<Transform>);
+
+// SAFETY: the transform is only read and replaced from the main loop thread that draws.
+unsafe impl Sync for TransformCell {}
+
+static TRANSFORM: TransformCell = TransformCell(UnsafeCell::new(Transform {
+    adjust: ColorAdjust::NEUTRAL,
+    lut: [[0; 256]; 3],
+}));
+
+pub fn adjust() -> ColorAdjust {
+    // SAFETY: see `TransformCell`; no reference outlives this call.
+    unsafe { (*TRANSFORM.0.get()).adjust }
+}
+
+/// Rebuilds the tables; `false` when `adjust` is already in force.
+pub(super) fn set_adjust(adjust: ColorAdjust) -> bool {
+    // SAFETY: see `TransformCell`; no other reference to the transform is live while drawing
+    // is not in progress, which is the only time the shell gets to change it.
+    let transform = unsafe { &mut *TRANSFORM.0.get() };
+    if transform.adjust == adjust {
+        return false;
+    }
+    transform.adjust = adjust;
+    for (channel, table) in transform.lut.iter_mut().enumerate() {
+        for (value, entry) in table.iter_mut().enumerate() {
+            *entry = adjust.channel(channel, value as u8);
+        }
+    }
+    true
+}
+
+/// `color` as it should reach the framebuffer.
+pub(super) fn apply(color: Color) -> Color {
+    // SAFETY: see `TransformCell`; no reference outlives this call.
+    let transform = unsafe { &*TRANSFORM.0.get() };
+    if transform.adjust == ColorAdjust::NEUTRAL {
+        return color;
+    }
+    Color::rgb(
+        transform.lut[0][usize::from(color.r)],
+        transform.lut[1][usize::from(color.g)],
+        transform.lut[2][usize::from(color.b)],
+    )
+}
@@ -0,0 +1,295 @@
+// kernel/src/gfx/cursor.rs: software cursor plane composited onto the framebuffer after present.
+use super::rotate::Output;
+use super::{Color, GfxState, Rect};
+
+const CURSOR_MAX_W: usize = 12;
+const CURSOR_MAX_H: usize = 16;
+const CURSOR_MAX_BPP: usize = 4;
+const SAVE_BYTES: usize = CURSOR_MAX_W * CURSOR_MAX_H * CURSOR_MAX_BPP;
+
+// Bitmaps: '#' outline, '.' fill, anything else transparent.
+const ARROW: [&[u8]; 16] = [
+    b"#",
+    b"##",
+    b"#.#",
+    b"#..#",
+    b"#...#",
+    b"#....#",
+    b"#.....#",
+    b"#......#",
+    b"#.......#",
+    b"#........#",
+    b"#.....#####",
+    b"#..#..#",
+    b"#.# #..#",
+    b"##  #..#",
+    b"#    #..#",
+    b"     ####",
+];
+
+const MOVE: [&[u8]; 11] = [
+    b"     #",
+    b"    #.#",
+    b"   #...#",
+    b"    #.#",
+    b" #  #.#  #",
+    b"#.###.###.#",
+    b" #  #.#  #",
+    b"    #.#",
+    b"   #...#",
+    b"    #.#",
+    b"     #",
+];
+
+const RESIZE: [&[u8]; 11] = [
+    b"######",
+    b"#....#",
+    b"#...#",
+    b"#....#",
+    b"#.##..#",
+    b"##  #..#",
+    b"     #..##",
+    b"      #..#",
+    b"     #...#",
+    b"    #....#",
+    b"    ######",
+];
+
+#[rustfmt::skip]
+const TEXT: [&[u8]; 14] = [
+    b"### ###",
+    b"#..#..#",
+    b"###.###",
+    b"  #.#",
+    b"  #.#",
+    b"  #.#",
+    b"  #.#",
+    b"  #.#",
+    b"  #.#",
+    b"  #.#",
+    b"  #.#",
+    b"###.###",
+    b"#..#..#",
+    b"### ###",
+];
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum CursorShape {
+    Arrow,
+    Move,
+    Resize,
+    Text,
+}
+
+impl CursorShape {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Arrow => "arrow",
+            Self::Move => "move",
+            Self::Resize => "resize",
+            Self::Text => "text",
+        }
+    }
+
+    fn bitmap(self) -> &'static [&'static [u8]] {
+        match self {
+            Self::Arrow => &ARROW,
+            Self::Move => &MOVE,
+            Self::Resize => &RESIZE,
+            Self::Text => &TEXT,
+        }
+    }
+
+    /// Bitmap offset of the pixel that sits exactly on the pointer position.
+    const fn hotspot(self) -> (usize, usize) {
+        match self {
+            Self::Arrow => (0, 0),
+            Self::Move => (5, 5),
+            Self::Resize => (5, 5),
+            Self::Text => (3, 7),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum CursorTheme {
+    Light,
+    Dark,
+}
+
+impl CursorTheme {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Light => "light",
+            Self::Dark => "dark",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "light" => Some(Self::Light),
+            "dark" => Some(Self::Dark),
+            _ => None,
+        }
+    }
+
+    /// `(fill, outline)`; the fill turns amber while the left button is held.
+    const fn colors(self, pressed: bool) -> (Color, Color) {
+        let (fill, outline) = match self {
+            Self::Light => (Color::rgb(250, 250, 250), Color::rgb(8, 12, 18)),
+            Self::Dark => (Color::rgb(16, 20, 28), Color::rgb(236, 240, 246)),
+        };
+        if pressed {
+            (Color::rgb(255, 208, 90), outline)
+        } else {
+            (fill, outline)
+        }
+    }
+}
+
+/// Pixels under the cursor are saved before it is drawn and written back before anything
+/// else touches that part of the framebuffer, so pointer motion never produces scene damage.
+pub(super) struct CursorPlane {
+    shape: CursorShape,
+    theme: CursorTheme,
+    saved: [u8; SAVE_BYTES],
+    saved_rect: Option<Rect>,
+    moves: u64,
+    shape_changes: u64,
+}
+
+impl CursorPlane {
+    pub(super) const fn new() -> Self {
+        Self {
+            shape: CursorShape::Arrow,
+            theme: CursorTheme::Light,
+            saved: [0; SAVE_BYTES],
+            saved_rect: None,
+            moves: 0,
+            shape_changes: 0,
+        }
+    }
+
+    pub(super) const fn shape(&self) -> CursorShape {
+        self.shape
+    }
+
+    pub(super) const fn theme(&self) -> CursorTheme {
+        self.theme
+    }
+
+    pub(super) const fn moves(&self) -> u64 {
+        self.moves
+    }
+
+    pub(super) const fn shape_changes(&self) -> u64 {
+        self.shape_changes
+    }
+
+    pub(super) fn set_theme(&mut self, theme: CursorTheme) -> bool {
+        core::mem::replace(&mut self.theme, theme) != theme
+    }
+
+    pub(super) fn count_move(&mut self) {
+        self.moves = self.moves.saturating_add(1);
+    }
+
+    /// True while the cursor is on screen and covers part of the logical `rect`.
+    pub(super) fn overlaps(&self, rect: Rect) -> bool {
+        self.saved_rect
+            .is_some_and(|saved| saved.intersects_or_near(rect, 0))
+    }
+
+    /// Writes the saved scene pixels back, leaving the framebuffer as the compositor drew it.
+    pub(super) fn hide(&mut self, framebuffer: &mut [u8], output: &Output) {
+        let Some(rect) = self.saved_rect.take() else {
+            return;
+        };
+        let info = output.physical();
+        let rect = output.map_rect(rect);
+        let row_bytes = rect.w * info.bytes_per_pixel;
+        for row in 0..rect.h {
+            let offset = ((rect.y + row) * info.stride + rect.x) * info.bytes_per_pixel;
+            let saved = row * row_bytes;
+            if let Some(dst) = framebuffer.get_mut(offset..offset + row_bytes) {
+                dst.copy_from_slice(&self.saved[saved..saved + row_bytes]);
+            }
+        }
+    }
+
+    /// Saves the framebuffer under `shape` at the logical pointer and draws it on top, turned
+    /// with the output so it stays upright in the scene.
+    pub(super) fn show(
+        &mut self,
+        framebuffer: &mut [u8],
+        output: &Output,
+        pointer: (usize, usize),
+        shape: CursorShape,
+        pressed: bool,
+    ) {
+        let info = output.physical();
+        let (scene_w, scene_h) = output.logical_size();
+        if self.saved_rect.is_some()
+            || info.bytes_per_pixel == 0
+            || info.bytes_per_pixel > CURSOR_MAX_BPP
+        {
+            return;
+        }
+        if shape != self.shape {
+            self.shape = shape;
+            self.shape_changes = self.shape_changes.saturating_add(1);
+        }
+
+        let (hot_x, hot_y) = shape.hotspot();
+        let origin_x = pointer.0 as isize - hot_x as isize;
+        let origin_y = pointer.1 as isize - hot_y as isize;
+        let x0 = origin_x.max(0) as usize;
+        let y0 = origin_y.max(0) as usize;
+        let unclamped = Rect::new(
+            x0,
+            y0,
+            (origin_x + CURSOR_MAX_W as isize).max(0) as usize - x0,
+            (origin_y + CURSOR_MAX_H as isize).max(0) as usize - y0,
+        );
+        let Some(rect) = unclamped.clamped(scene_w, scene_h) else {
+            return;
+        };
+
+        let bpp = info.bytes_per_pixel;
+        let physical = output.map_rect(rect);
+        let row_bytes = physical.w * bpp;
+        for row in 0..physical.h {
+            let offset = ((physical.y + row) * info.stride + physical.x) * bpp;
+            let Some(src) = framebuffer.get(offset..offset + row_bytes) else {
+                return;
+            };
+            self.saved[row * row_bytes..(row + 1) * row_bytes].copy_from_slice(src);
+        }
+        self.saved_rect = Some(rect);
+
+        let (fill, outline) = self.theme.colors(pressed);
+        for (dy, line) in shape.bitmap().iter().enumerate() {
+            for (dx, &cell) in line.iter().enumerate() {
+                let color = match cell {
+                    b'#' => outline,
+                    b'.' => fill,
+                    _ => continue,
+                };
+                let x = origin_x + dx as isize;
+                let y = origin_y + dy as isize;
+                if x < rect.x as isize
+                    || y < rect.y as isize
+                    || x >= (rect.x + rect.w) as isize
+                    || y >= (rect.y + rect.h) as isize
+                {
+                    continue;
+                }
+                let (x, y) = output.map_point(x as usize, y as usize);
+                let offset = (y * info.stride + x) * bpp;
+                if let Some(pixel) = framebuffer.get_mut(offset..offset + bpp) {
+                    GfxState::encode_pixel(info.pixel_format, bpp, pixel, color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// kernel/src/gfx/damage.rs: `ui debug damage` overlay tinting each flushed damage rect for one frame.
+use super::{Color, DAMAGE_CAPACITY, GfxState, Rect};
+use crate::serial;
+use bootloader_api::info::FrameBufferInfo;
+
+/// Cycled per flush so back-to-back flushes over the same area stay distinguishable.
+const TINTS: [Color; 4] = [
+    Color::rgb(255, 48, 48),
+    Color::rgb(48, 220, 72),
+    Color::rgb(64, 120, 255),
+    Color::rgb(240, 64, 232),
+];
+
+pub(super) struct DamageDebug {
+    enabled: bool,
+    tinted: [Rect; DAMAGE_CAPACITY],
+    tinted_len: usize,
+    flushes: u64,
+    rects: u64,
+}
+
+impl DamageDebug {
+    pub(super) const fn new() -> Self {
+        Self {
+            enabled: false,
+            tinted: [Rect::ZERO; DAMAGE_CAPACITY],
+            tinted_len: 0,
+            flushes: 0,
+            rects: 0,
+        }
+    }
+
+    pub(super) const fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(super) const fn flushes(&self) -> u64 {
+        self.flushes
+    }
+
+    pub(super) const fn rects(&self) -> u64 {
+        self.rects
+    }
+
+    pub(super) fn set_enabled(&mut self, enabled: bool) -> bool {
+        core::mem::replace(&mut self.enabled, enabled) != enabled
+    }
+
+    /// Rects still carrying a tint from the previous flush; the caller re-presents them.
+    pub(super) fn take_tinted(&mut self) -> ([Rect; DAMAGE_CAPACITY], usize) {
+        let len = core::mem::replace(&mut self.tinted_len, 0);
+        (self.tinted, len)
+    }
+
+    /// Counts one flush of `rects` and logs it on COM1 only: mirroring the line into the
+    /// shell window would damage it again and feed the overlay its own output.
+    pub(super) fn record_flush(&mut self, rects: &[Rect]) -> Color {
+        let tint = TINTS[(self.flushes % TINTS.len() as u64) as usize];
+        self.flushes = self.flushes.saturating_add(1);
+        self.rects = self.rects.saturating_add(rects.len() as u64);
+        self.tinted[..rects.len()].copy_from_slice(rects);
+        self.tinted_len = rects.len();
+
+        let pixels: usize = rects.iter().map(|rect| rect.w * rect.h).sum();
+        serial::write_fmt_unmirrored(format_args!(
+            "ui-damage: flush={} rects={} px={}",
+            self.flushes,
+            rects.len(),
+            pixels
+        ));
+        for rect in rects {
+            serial::write_fmt_unmirrored(format_args!(
+                " {}x{}@{},{}",
+                rect.w, rect.h, rect.x, rect.y
+            ));
+        }
+        serial::write_str_unmirrored("\n");
+        tint
+    }
+}
+
+/// Blends `tint` 50/50 into `rect` straight on the framebuffer.
+pub(super) fn tint_rect(framebuffer: &mut [u8], info: &FrameBufferInfo, rect: Rect, tint: Color) {
+    let bpp = info.bytes_per_pixel;
+    if bpp == 0 || bpp > 4 {
+        return;
+    }
+    let Some(rect) = rect.clamped(info.width, info.height) else {
+        return;
+    };
+    let mut encoded = [0u8; 4];
+    GfxState::encode_pixel(info.pixel_format, bpp, &mut encoded[..bpp], tint);
+    for y in rect.y..rect.y + rect.h {
+        let offset = (y * info.stride + rect.x) * bpp;
+        let Some(row) = framebuffer.get_mut(offset..offset + rect.w * bpp) else {
+            return;
+        };
+        for pixel in row.chunks_exact_mut(bpp) {
+            for (byte, &tint_byte) in pixel.iter_mut().zip(&encoded) {
+                *byte = ((u16::from(*byte) + u16::from(tint_byte)) / 2) as u8;
+            }
+        }
+    }
+}
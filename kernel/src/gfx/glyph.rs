@@ -0,0 +1,118 @@
+// kernel/src/gfx/glyph.rs: pre-rendered glyph cells per (fg, bg) pair so text blits become row copies.
+use super::{CHAR_H, CHAR_W, glyph_rows};
+use alloc::{vec, vec::Vec};
+
+/// ASCII only; other bytes keep the per-pixel path.
+pub(super) const GLYPH_COUNT: usize = 128;
+/// Shell, file manager, doom and title text use a handful of color pairs; four covers them.
+const FACE_SLOTS: usize = 4;
+
+/// One encoded framebuffer pixel; only the first `bytes_per_pixel` bytes are meaningful.
+pub(super) type Pixel = [u8; 4];
+
+struct Face {
+    fg: Pixel,
+    bg: Pixel,
+    last_used: u64,
+    /// `GLYPH_COUNT` cells of `CHAR_H` rows by `CHAR_W` encoded pixels.
+    cells: Vec<u8>,
+}
+
+pub(super) struct GlyphCache {
+    enabled: bool,
+    faces: Vec<Face>,
+    clock: u64,
+    hits: u64,
+    renders: u64,
+}
+
+impl GlyphCache {
+    pub(super) const fn new() -> Self {
+        Self {
+            enabled: true,
+            faces: Vec::new(),
+            clock: 0,
+            hits: 0,
+            renders: 0,
+        }
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub(super) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub(super) fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Face (re)renders; a steadily growing count means more color pairs than slots.
+    pub(super) fn renders(&self) -> u64 {
+        self.renders
+    }
+
+    /// Returns the `CHAR_H * CHAR_W * bytes_per_pixel` cell for `byte` (< `GLYPH_COUNT`),
+    /// rendering the whole face the first time this color pair is seen. A full table recycles
+    /// the least recently used face's buffer in place, since the heap never gives memory back.
+    pub(super) fn cell(&mut self, byte: u8, fg: Pixel, bg: Pixel, bytes_per_pixel: usize) -> &[u8] {
+        self.clock = self.clock.wrapping_add(1);
+        let cell_bytes = CHAR_W * CHAR_H * bytes_per_pixel;
+        let index = match self
+            .faces
+            .iter()
+            .position(|face| face.fg == fg && face.bg == bg)
+        {
+            Some(index) => {
+                self.hits = self.hits.saturating_add(1);
+                index
+            }
+            None => {
+                let index = if self.faces.len() < FACE_SLOTS {
+                    self.faces.push(Face {
+                        fg,
+                        bg,
+                        last_used: 0,
+                        cells: vec![0; GLYPH_COUNT * cell_bytes],
+                    });
+                    self.faces.len() - 1
+                } else {
+                    let mut oldest = 0;
+                    for (index, face) in self.faces.iter().enumerate() {
+                        if face.last_used < self.faces[oldest].last_used {
+                            oldest = index;
+                        }
+                    }
+                    self.faces[oldest].fg = fg;
+                    self.faces[oldest].bg = bg;
+                    oldest
+                };
+                render_face(&mut self.faces[index].cells, fg, bg, bytes_per_pixel);
+                self.renders = self.renders.saturating_add(1);
+                index
+            }
+        };
+        let face = &mut self.faces[index];
+        face.last_used = self.clock;
+        let start = byte as usize * cell_bytes;
+        &face.cells[start..start + cell_bytes]
+    }
+}
+
+/// Same cell layout `draw_char` produces: 5x7 glyph bits, background in the spacing column
+/// and the bottom row.
+fn render_face(cells: &mut [u8], fg: Pixel, bg: Pixel, bytes_per_pixel: usize) {
+    let cell_bytes = CHAR_W * CHAR_H * bytes_per_pixel;
+    for (glyph, cell) in cells.chunks_exact_mut(cell_bytes).enumerate() {
+        let rows = glyph_rows(glyph as u8);
+        for (index, pixel) in cell.chunks_exact_mut(bytes_per_pixel).enumerate() {
+            let row = index / CHAR_W;
+            let col = index % CHAR_W;
+            let on = row < rows.len() && col < 5 && rows[row] & (1 << (4 - col)) != 0;
+            let source = if on { &fg } else { &bg };
+            pixel.copy_from_slice(&source[..bytes_per_pixel]);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+// kernel/src/gfx/lifecycle.rs: window close requests. The close button in the title bar of the
+// doom, settings and app windows does not close anything by itself; it asks the window's owner,
+// which cleans up and closes the window or vetoes. Kernel owners take requests from
+// `pop_close_request` in their poll loop and reply with `answer_close`. The app window's task
+// takes them with SURFACE_EVENTS and closes with SURFACE_CLOSE; a task that leaves its request
+// untaken for `APP_CLOSE_GRACE_TICKS` gets the window closed as `ui close app` would.
+use super::{
+    APP_WINDOW_INDEX, Color, DOOM_WINDOW_INDEX, GfxState, Rect, SETTINGS_WINDOW_INDEX,
+    TITLE_BAR_HEIGHT, UiWindow, WindowId, with_state_mut,
+};
+use crate::error::KernelError;
+use crate::time;
+
+/// 2 s, as long as `kill` waits between SIGTERM and SIGKILL.
+const APP_CLOSE_GRACE_TICKS: u64 = 200;
+const CLOSE_BUTTON_SIZE: usize = 12;
+/// Windows whose owner the kernel runs, in the order their requests are handed out.
+const KERNEL_OWNED: [WindowId; 2] = [WindowId::Doom, WindowId::Settings];
+
+/// What the owner of a window does with a close request.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CloseReply {
+    Close,
+    Veto,
+}
+
+/// Close request of the app window, which lives and dies with its owner.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum AppCloseRequest {
+    None,
+    /// Made at this tick; the task has not taken it yet.
+    Pending(u64),
+    /// The task took it and decides.
+    Taken,
+}
+
+pub(super) struct Lifecycle {
+    /// One bit per window index with a request its kernel owner has not taken.
+    pending: u8,
+    requests: u64,
+    closed: u64,
+    vetoed: u64,
+    /// App windows closed because the task never took its request.
+    timeouts: u64,
+}
+
+impl Lifecycle {
+    pub(super) const fn new() -> Self {
+        Self {
+            pending: 0,
+            requests: 0,
+            closed: 0,
+            vetoed: 0,
+            timeouts: 0,
+        }
+    }
+}
+
+/// Close request counters for `ui`.
+#[derive(Clone, Copy)]
+pub(super) struct LifecycleStatus {
+    pub(super) requests: u64,
+    pub(super) closed: u64,
+    pub(super) vetoed: u64,
+    pub(super) timeouts: u64,
+}
+
+impl GfxState {
+    pub(super) const fn closable(index: usize) -> bool {
+        matches!(
+            index,
+            DOOM_WINDOW_INDEX | SETTINGS_WINDOW_INDEX | APP_WINDOW_INDEX
+        )
+    }
+
+    pub(super) fn point_on_close_button(&self, index: usize, x: usize, y: usize) -> bool {
+        Self::closable(index)
+            && self.point_in_window(index, x, y)
+            && close_button_rect(self.windows[index]).contains(x, y)
+    }
+
+    /// Hands a close request to the owner of window `index`; false when it has none to ask.
+    pub(super) fn request_close(&mut self, index: usize, now_tick: u64) -> bool {
+        if !Self::closable(index) || !self.window_visible(index) {
+            return false;
+        }
+        if index == APP_WINDOW_INDEX {
+            // A second click does not restart the grace period.
+            if !matches!(self.app.close_request, AppCloseRequest::Pending(_)) {
+                self.app.close_request = AppCloseRequest::Pending(now_tick);
+            }
+        } else {
+            self.lifecycle.pending |= 1 << index;
+        }
+        self.lifecycle.requests = self.lifecycle.requests.saturating_add(1);
+        true
+    }
+
+    /// Next request of a kernel owner; one for a window that has closed since is dropped.
+    fn pop_close_request(&mut self) -> Option<WindowId> {
+        for window in KERNEL_OWNED {
+            let bit = 1 << window.index();
+            if self.lifecycle.pending & bit != 0 {
+                self.lifecycle.pending &= !bit;
+                if self.window_visible(window.index()) {
+                    return Some(window);
+                }
+            }
+        }
+        None
+    }
+
+    fn answer_close(&mut self, window: WindowId, reply: CloseReply) {
+        match reply {
+            CloseReply::Close => {
+                self.close_window(window.index());
+                self.lifecycle.closed = self.lifecycle.closed.saturating_add(1);
+            }
+            CloseReply::Veto => self.lifecycle.vetoed = self.lifecycle.vetoed.saturating_add(1),
+        }
+    }
+
+    fn close_window(&mut self, index: usize) {
+        match index {
+            DOOM_WINDOW_INDEX => self.close_doom_window(),
+            SETTINGS_WINDOW_INDEX => self.close_settings_window(),
+            APP_WINDOW_INDEX => {
+                let _ = self.close_app_window();
+            }
+            _ => {}
+        }
+    }
+
+    /// SURFACE_EVENTS: whether a close request waited for `pid`; taking it leaves the
+    /// decision to the task.
+    fn take_app_close_request(&mut self, pid: u32) -> Result<bool, KernelError> {
+        self.check_app_owner(pid)?;
+        if !matches!(self.app.close_request, AppCloseRequest::Pending(_)) {
+            return Ok(false);
+        }
+        self.app.close_request = AppCloseRequest::Taken;
+        Ok(true)
+    }
+
+    pub(super) fn expire_app_close_request(&mut self, now_tick: u64) {
+        let AppCloseRequest::Pending(since) = self.app.close_request else {
+            return;
+        };
+        if now_tick.saturating_sub(since) >= APP_CLOSE_GRACE_TICKS {
+            let _ = self.close_app_window();
+            self.lifecycle.timeouts = self.lifecycle.timeouts.saturating_add(1);
+        }
+    }
+
+    pub(super) const fn lifecycle_status(&self) -> LifecycleStatus {
+        LifecycleStatus {
+            requests: self.lifecycle.requests,
+            closed: self.lifecycle.closed,
+            vetoed: self.lifecycle.vetoed,
+            timeouts: self.lifecycle.timeouts,
+        }
+    }
+
+    pub(super) fn draw_close_button(&mut self, window: UiWindow, fg: Color) {
+        let rect = close_button_rect(window);
+        let fill = Color::rgb(168, 64, 60);
+        self.fill_rect(rect.x, rect.y, rect.w, rect.h, fill);
+        self.draw_char(rect.x + 3, rect.y + 2, b'x', fg, Some(fill));
+    }
+}
+
+/// Right end of the title bar, centered in its height.
+fn close_button_rect(window: UiWindow) -> Rect {
+    let inset = (TITLE_BAR_HEIGHT - CLOSE_BUTTON_SIZE) / 2;
+    Rect::new(
+        window
+            .x
+            .saturating_add(window.w)
+            .saturating_sub(1 + inset + CLOSE_BUTTON_SIZE),
+        window.y.saturating_add(1 + inset),
+        CLOSE_BUTTON_SIZE,
+        CLOSE_BUTTON_SIZE,
+    )
+}
+
+/// Next close request for a kernel-owned window (doom, settings), taken off the queue.
+pub fn pop_close_request() -> Option<WindowId> {
+    with_state_mut(|state| state.pop_close_request()).flatten()
+}
+
+/// The kernel owner's reply to a close request from `pop_close_request`.
+pub fn answer_close(window: WindowId, reply: CloseReply) {
+    let _ = with_state_mut(|state| {
+        state.answer_close(window, reply);
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+    });
+}
+
+/// `ui click close <window>`: the close button as a click on it would press it.
+pub fn click_close(window: WindowId) -> bool {
+    with_state_mut(|state| state.request_close(window.index(), time::ticks())).unwrap_or(false)
+}
+
+/// SURFACE_EVENTS for `pid`: `Ok(true)` once per close request on its app window.
+pub fn take_app_close_request(pid: u32) -> Result<bool, KernelError> {
+    with_state_mut(|state| state.take_app_close_request(pid)).unwrap_or(Err(KernelError::NoDevice))
+}
@@ -1,8 +1,17 @@
 // kernel/src/gfx/mod.rs: M8 framebuffer desktop with minimal compositor/event queue.
-use crate::doom;
+#[cfg(feature = "doom")]
+use crate::doom_bridge;
+use crate::error::KernelError;
+use crate::evlog::{self, Event};
+use crate::input_bus::{self, Consumer, Delivery, InputEvent, Subscription};
+use crate::keyboard::{self, KeyCode};
+use crate::log::Level;
 use crate::mouse;
 use crate::serial;
+use crate::shell::Status;
+use crate::soft_assert::soft_assert;
 use crate::time;
+use crate::utf8::Utf8Decoder;
 use alloc::vec::Vec;
 use bootloader_api::{
     BootInfo,
@@ -11,30 +20,62 @@ use bootloader_api::{
 use core::cell::UnsafeCell;
 use core::cmp::min;
 
-const WINDOW_COUNT: usize = 3;
+mod checksum;
+mod color;
+mod cursor;
+mod damage;
+mod glyph;
+mod lifecycle;
+mod rotate;
+mod surface;
+mod ui;
+
+pub use checksum::ChecksumReport;
+pub use color::ColorAdjust;
+use cursor::CursorShape;
+pub use cursor::CursorTheme;
+pub use lifecycle::{
+    CloseReply, answer_close, click_close, pop_close_request, take_app_close_request,
+};
+pub use rotate::{RotateError, Rotation};
+pub use surface::{AppWindowStatus, SurfaceMapping};
+pub use ui::{FileManagerAction, FileManagerButton, SettingsAction};
+
+const WINDOW_COUNT: usize = 5;
 const SHELL_WINDOW_INDEX: usize = 0;
 const FILE_MANAGER_WINDOW_INDEX: usize = 1;
 const DOOM_WINDOW_INDEX: usize = 2;
+const SETTINGS_WINDOW_INDEX: usize = 3;
+const APP_WINDOW_INDEX: usize = 4;
 const WINDOW_MAX_COLS: usize = 96;
 const WINDOW_MAX_ROWS: usize = 32;
 const INPUT_EVENT_CAPACITY: usize = 128;
+/// Log-ring bytes the shell mirror copies out per read.
+const MIRROR_CHUNK_BYTES: usize = 256;
 const DAMAGE_CAPACITY: usize = 24;
 const CHAR_W: usize = 6;
 const CHAR_H: usize = 8;
+/// Cell byte for characters the font has no glyph for (anything past ASCII); DEL has none
+/// either, so it draws the fallback box.
+const GLYPH_PLACEHOLDER: u8 = 0x7f;
 const TITLE_BAR_HEIGHT: usize = 18;
 const WINDOW_PADDING: usize = 8;
 const MIN_WINDOW_WIDTH: usize = 220;
 const MIN_WINDOW_HEIGHT: usize = 140;
 const RESIZE_HANDLE_SIZE: usize = 12;
 const DESKTOP_MARGIN: usize = 4;
+const DESKTOP_TOP: usize = 32;
 const MINIMIZED_WINDOW_HEIGHT: usize = TITLE_BAR_HEIGHT + 2;
 const DOUBLE_CLICK_TICKS: u64 = 25;
-const POINTER_RECT_SIZE: usize = 8;
 const DAMAGE_MERGE_PAD: usize = 12;
 const MAX_BACKBUFFER_BYTES: usize = 8 * 1024 * 1024;
 const DOOM_VIEW_MAX_W: usize = 320;
 const DOOM_VIEW_MAX_H: usize = 200;
 const DOOM_VIEW_MAX_PIXELS: usize = DOOM_VIEW_MAX_W * DOOM_VIEW_MAX_H;
+/// Pointer speed in percent of raw mouse motion; `settings` adjusts it.
+pub const DEFAULT_MOUSE_SPEED: u16 = 100;
+pub const MIN_MOUSE_SPEED: u16 = 25;
+pub const MAX_MOUSE_SPEED: u16 = 400;
 
 #[derive(Clone, Copy)]
 pub struct GfxInitReport {
@@ -48,6 +89,17 @@ pub struct GfxInitReport {
     pub windows: usize,
 }
 
+/// What `restart gfx` came back with.
+#[derive(Clone, Copy)]
+pub struct GfxRestartReport {
+    pub width: usize,
+    pub height: usize,
+    pub double_buffer: bool,
+    pub rotation: Rotation,
+    /// The scene was rotated but no backbuffer could be allocated, so it is upright again.
+    pub rotation_reset: bool,
+}
+
 #[derive(Clone, Copy)]
 struct Color {
     r: u8,
@@ -147,6 +199,13 @@ impl Rect {
         Some(Self::new(x0, y0, x1 - x0, y1 - y0))
     }
 
+    fn contains(self, x: usize, y: usize) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x.saturating_add(self.w)
+            && y < self.y.saturating_add(self.h)
+    }
+
     fn intersects_or_near(self, other: Self, pad: usize) -> bool {
         let a_x0 = self.x.saturating_sub(pad);
         let a_y0 = self.y.saturating_sub(pad);
@@ -176,6 +235,39 @@ impl Rect {
     }
 }
 
+/// Foreground attribute of one text cell, selected by SGR sequences in mirrored output.
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum TextAttr {
+    Default,
+    Red,
+    Green,
+    Yellow,
+}
+
+impl TextAttr {
+    fn color(self, default: Color) -> Color {
+        match self {
+            Self::Default => default,
+            Self::Red => Color::rgb(240, 104, 96),
+            Self::Green => Color::rgb(120, 206, 132),
+            Self::Yellow => Color::rgb(236, 200, 92),
+        }
+    }
+}
+
+const ANSI_MAX_PARAMS: usize = 4;
+
+/// Escape-sequence parser state; only CSI sequences are interpreted, others are dropped.
+#[derive(Clone, Copy)]
+enum AnsiState {
+    Ground,
+    Escape,
+    Csi {
+        params: [u16; ANSI_MAX_PARAMS],
+        index: usize,
+    },
+}
+
 #[derive(Clone, Copy)]
 struct UiWindow {
     x: usize,
@@ -185,13 +277,25 @@ struct UiWindow {
     saved_w: usize,
     saved_h: usize,
     minimized: bool,
+    tile: Option<TileMode>,
+    untiled_x: usize,
+    untiled_y: usize,
+    untiled_w: usize,
+    untiled_h: usize,
     title: &'static str,
+    /// Body height kept below the text grid for widgets.
+    footer: usize,
     lines: [[u8; WINDOW_MAX_COLS]; WINDOW_MAX_ROWS],
+    attrs: [[TextAttr; WINDOW_MAX_COLS]; WINDOW_MAX_ROWS],
     line_len: [usize; WINDOW_MAX_ROWS],
     cols: usize,
     rows: usize,
     cursor_row: usize,
     cursor_col: usize,
+    attr: TextAttr,
+    ansi: AnsiState,
+    /// The mirror reads the log in chunks, which can end inside a character.
+    utf8: Utf8Decoder,
 }
 
 #[derive(Clone, Copy)]
@@ -201,10 +305,23 @@ enum TextChange {
     FullText,
 }
 
+impl TextChange {
+    /// Both changes; two cells are rare enough (a broken sequence and the byte after it)
+    /// that the whole text stands in for them.
+    const fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::None, change) | (change, Self::None) => change,
+            _ => Self::FullText,
+        }
+    }
+}
+
 impl UiWindow {
-    const fn text_grid_for_size(width: usize, height: usize) -> (usize, usize) {
+    const fn text_grid_for_size(width: usize, height: usize, footer: usize) -> (usize, usize) {
         let body_w = width.saturating_sub(WINDOW_PADDING.saturating_mul(2));
-        let body_h = height.saturating_sub(TITLE_BAR_HEIGHT + WINDOW_PADDING.saturating_mul(2));
+        let body_h = height
+            .saturating_sub(TITLE_BAR_HEIGHT + WINDOW_PADDING.saturating_mul(2))
+            .saturating_sub(footer);
         let mut cols = body_w / CHAR_W;
         let mut rows = body_h / CHAR_H;
         if cols == 0 {
@@ -223,7 +340,7 @@ impl UiWindow {
     }
 
     const fn new(x: usize, y: usize, w: usize, h: usize, title: &'static str) -> Self {
-        let (cols, rows) = Self::text_grid_for_size(w, h);
+        let (cols, rows) = Self::text_grid_for_size(w, h, 0);
         Self {
             x,
             y,
@@ -232,18 +349,36 @@ impl UiWindow {
             saved_w: w,
             saved_h: h,
             minimized: false,
+            tile: None,
+            untiled_x: x,
+            untiled_y: y,
+            untiled_w: w,
+            untiled_h: h,
             title,
+            footer: 0,
             lines: [[0; WINDOW_MAX_COLS]; WINDOW_MAX_ROWS],
+            attrs: [[TextAttr::Default; WINDOW_MAX_COLS]; WINDOW_MAX_ROWS],
             line_len: [0; WINDOW_MAX_ROWS],
             cols,
             rows,
             cursor_row: 0,
             cursor_col: 0,
+            attr: TextAttr::Default,
+            ansi: AnsiState::Ground,
+            utf8: Utf8Decoder::new(),
         }
     }
 
+    const fn with_footer(mut self, footer: usize) -> Self {
+        let (cols, rows) = Self::text_grid_for_size(self.w, self.h, footer);
+        self.footer = footer;
+        self.cols = cols;
+        self.rows = rows;
+        self
+    }
+
     fn recalc_text_grid(&mut self) {
-        let (cols, rows) = Self::text_grid_for_size(self.w, self.h);
+        let (cols, rows) = Self::text_grid_for_size(self.w, self.h, self.footer);
         self.cols = cols;
         self.rows = rows;
 
@@ -269,23 +404,113 @@ impl UiWindow {
     }
 
     fn append_text(&mut self, text: &str) {
-        for byte in text.bytes() {
-            self.append_byte(byte);
+        for ch in text.chars() {
+            let _ = self.append_char(ch);
         }
     }
 
-    fn append_byte(&mut self, byte: u8) {
-        let _ = self.append_byte_with_change(byte);
-    }
-
     fn clear_text(&mut self) {
         self.lines = [[0; WINDOW_MAX_COLS]; WINDOW_MAX_ROWS];
+        self.attrs = [[TextAttr::Default; WINDOW_MAX_COLS]; WINDOW_MAX_ROWS];
         self.line_len = [0; WINDOW_MAX_ROWS];
         self.cursor_row = 0;
         self.cursor_col = 0;
     }
 
+    /// Consumes `byte` when it belongs to an escape sequence; `None` means plain text.
+    fn feed_ansi(&mut self, byte: u8) -> Option<TextChange> {
+        match self.ansi {
+            AnsiState::Ground if byte == 0x1b => {
+                self.ansi = AnsiState::Escape;
+                Some(TextChange::None)
+            }
+            AnsiState::Ground => None,
+            AnsiState::Escape => {
+                self.ansi = if byte == b'[' {
+                    AnsiState::Csi {
+                        params: [0; ANSI_MAX_PARAMS],
+                        index: 0,
+                    }
+                } else {
+                    AnsiState::Ground
+                };
+                Some(TextChange::None)
+            }
+            AnsiState::Csi { mut params, index } => {
+                match byte {
+                    b'0'..=b'9' => {
+                        params[index] = params[index]
+                            .saturating_mul(10)
+                            .saturating_add(u16::from(byte - b'0'));
+                        self.ansi = AnsiState::Csi { params, index };
+                    }
+                    b';' => {
+                        self.ansi = AnsiState::Csi {
+                            params,
+                            index: (index + 1).min(ANSI_MAX_PARAMS - 1),
+                        };
+                    }
+                    0x40..=0x7e => {
+                        self.ansi = AnsiState::Ground;
+                        return Some(self.apply_csi(byte, &params[..=index]));
+                    }
+                    _ => self.ansi = AnsiState::Ground,
+                }
+                Some(TextChange::None)
+            }
+        }
+    }
+
+    fn apply_csi(&mut self, command: u8, params: &[u16]) -> TextChange {
+        match command {
+            b'm' => {
+                for param in params {
+                    self.attr = match param {
+                        0 | 39 => TextAttr::Default,
+                        31 => TextAttr::Red,
+                        32 => TextAttr::Green,
+                        33 => TextAttr::Yellow,
+                        _ => self.attr,
+                    };
+                }
+                TextChange::None
+            }
+            b'J' if params[0] == 2 => {
+                self.clear_text();
+                TextChange::FullText
+            }
+            b'H' => {
+                self.cursor_row = 0;
+                self.cursor_col = 0;
+                TextChange::None
+            }
+            _ => TextChange::None,
+        }
+    }
+
+    /// Feeds one byte of a UTF-8 stream such as the console log.
     fn append_byte_with_change(&mut self, byte: u8) -> TextChange {
+        let mut utf8 = self.utf8;
+        let mut change = TextChange::None;
+        utf8.push(byte, |ch| change = change.merge(self.append_char(ch)));
+        self.utf8 = utf8;
+        change
+    }
+
+    /// One cell per character: ASCII as itself, anything else printable as the placeholder.
+    fn append_char(&mut self, ch: char) -> TextChange {
+        if !ch.is_ascii() {
+            self.ansi = AnsiState::Ground;
+            return if ch.is_control() {
+                TextChange::None
+            } else {
+                self.put_glyph(GLYPH_PLACEHOLDER)
+            };
+        }
+        let byte = ch as u8;
+        if let Some(change) = self.feed_ansi(byte) {
+            return change;
+        }
         match byte {
             b'\r' => TextChange::None,
             b'\n' => {
@@ -314,32 +539,35 @@ impl UiWindow {
                 }
                 TextChange::None
             }
-            0x20..=0x7e => {
-                let mut scrolled = false;
-                if self.cursor_col >= self.cols {
-                    scrolled = self.cursor_row + 1 >= self.rows;
-                    self.new_line();
-                }
-                if self.cursor_row >= self.rows {
-                    self.scroll_up();
-                    self.cursor_row = self.rows - 1;
-                    self.cursor_col = 0;
-                    scrolled = true;
-                }
+            0x20..=0x7e => self.put_glyph(byte),
+            _ => TextChange::None,
+        }
+    }
 
-                let row = self.cursor_row;
-                let col = self.cursor_col;
-                self.lines[row][col] = byte;
-                self.cursor_col += 1;
-                self.line_len[row] = self.line_len[row].max(self.cursor_col);
+    fn put_glyph(&mut self, byte: u8) -> TextChange {
+        let mut scrolled = false;
+        if self.cursor_col >= self.cols {
+            scrolled = self.cursor_row + 1 >= self.rows;
+            self.new_line();
+        }
+        if self.cursor_row >= self.rows {
+            self.scroll_up();
+            self.cursor_row = self.rows - 1;
+            self.cursor_col = 0;
+            scrolled = true;
+        }
 
-                if scrolled {
-                    TextChange::FullText
-                } else {
-                    TextChange::Cell { row, col }
-                }
-            }
-            _ => TextChange::None,
+        let row = self.cursor_row;
+        let col = self.cursor_col;
+        self.lines[row][col] = byte;
+        self.attrs[row][col] = self.attr;
+        self.cursor_col += 1;
+        self.line_len[row] = self.line_len[row].max(self.cursor_col);
+
+        if scrolled {
+            TextChange::FullText
+        } else {
+            TextChange::Cell { row, col }
         }
     }
 
@@ -355,9 +583,11 @@ impl UiWindow {
     fn scroll_up(&mut self) {
         for row in 1..self.rows {
             self.lines[row - 1] = self.lines[row];
+            self.attrs[row - 1] = self.attrs[row];
             self.line_len[row - 1] = self.line_len[row];
         }
         self.lines[self.rows - 1] = [0; WINDOW_MAX_COLS];
+        self.attrs[self.rows - 1] = [TextAttr::Default; WINDOW_MAX_COLS];
         self.line_len[self.rows - 1] = 0;
     }
 
@@ -413,15 +643,18 @@ struct GfxStatus {
     stride: usize,
     bytes_per_pixel: usize,
     pixel_format: &'static str,
+    rotation: Rotation,
+    color: ColorAdjust,
     focused_window: usize,
     events: u64,
     dropped: u64,
     stdout_events: u64,
-    stdout_dropped: u64,
+    stdout_skipped: u64,
     frames: u64,
     mouse_x: usize,
     mouse_y: usize,
     mouse_events: u64,
+    mouse_speed: u16,
     mouse_click_focus: u64,
     mouse_drag_steps: u64,
     mouse_resize_steps: u64,
@@ -430,6 +663,8 @@ struct GfxStatus {
     focused_minimized: bool,
     minimized_windows: usize,
     mouse_minimize_toggles: u64,
+    tile_ops: u64,
+    focused_tile: &'static str,
     partial_redraws: u64,
     full_redraws: u64,
     damage_dropped: u64,
@@ -437,6 +672,19 @@ struct GfxStatus {
     present_partial: u64,
     present_full: u64,
     double_buffer: bool,
+    cursor_shape: &'static str,
+    cursor_theme: &'static str,
+    cursor_moves: u64,
+    cursor_shape_changes: u64,
+    damage_debug: bool,
+    damage_debug_flushes: u64,
+    damage_debug_rects: u64,
+    glyph_hits: u64,
+    glyph_renders: u64,
+    button_clicks: u64,
+    button_drops: u64,
+    lifecycle: lifecycle::LifecycleStatus,
+    kiosk: bool,
 }
 
 #[derive(Clone, Copy)]
@@ -481,8 +729,12 @@ impl ResizeState {
     }
 }
 
+#[derive(Clone, Copy)]
 struct DoomViewLayer {
     active: bool,
+    /// Pixels live in the doom bridge's front surface instead of `DOOM_VIEW_PIXELS`.
+    #[cfg(feature = "doom")]
+    shared: bool,
     width: usize,
     height: usize,
     filter: DoomViewFilter,
@@ -492,37 +744,64 @@ impl DoomViewLayer {
     const fn new() -> Self {
         Self {
             active: false,
+            #[cfg(feature = "doom")]
+            shared: false,
             width: 0,
             height: 0,
             filter: DoomViewFilter::Nearest,
         }
     }
 
-    fn set(&mut self, width: usize, height: usize, pixels: &[u32]) -> bool {
+    #[cfg(feature = "doom")]
+    fn set(&mut self, width: usize, height: usize, source: DoomViewSource<'_>) -> bool {
         if width == 0 || height == 0 || width > DOOM_VIEW_MAX_W || height > DOOM_VIEW_MAX_H {
             return false;
         }
         let len = width.saturating_mul(height);
-        if pixels.len() < len {
-            return false;
+        match source {
+            DoomViewSource::Pixels(pixels) => {
+                if pixels.len() < len {
+                    return false;
+                }
+                with_doom_view_pixels_mut(|storage| {
+                    storage[..len].copy_from_slice(&pixels[..len]);
+                    storage[len..DOOM_VIEW_MAX_PIXELS].fill(0);
+                });
+                self.shared = false;
+            }
+            DoomViewSource::BridgeFront => {
+                if len != doom_bridge::VIEWPORT_PIXELS || !doom_bridge::has_frame() {
+                    return false;
+                }
+                self.shared = true;
+            }
         }
 
         self.active = true;
         self.width = width;
         self.height = height;
-        with_doom_view_pixels_mut(|storage| {
-            storage[..len].copy_from_slice(&pixels[..len]);
-            storage[len..DOOM_VIEW_MAX_PIXELS].fill(0);
-        });
         true
     }
 
+    fn with_pixels<R>(&self, f: impl FnOnce(&[u32]) -> R) -> R {
+        #[cfg(feature = "doom")]
+        if self.shared {
+            return doom_bridge::with_front_surface(|pixels| f(pixels));
+        }
+        with_doom_view_pixels(|pixels| f(pixels))
+    }
+
     fn clear(&mut self) {
         self.active = false;
+        #[cfg(feature = "doom")]
+        {
+            self.shared = false;
+        }
         self.width = 0;
         self.height = 0;
     }
 
+    #[cfg(feature = "doom")]
     fn set_filter(&mut self, filter: DoomViewFilter) -> bool {
         if self.filter == filter {
             return false;
@@ -532,13 +811,104 @@ impl DoomViewLayer {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TileMode {
+    Left,
+    Right,
+    /// Toggles between maximized and the geometry saved before the first tile.
+    Max,
+    Restore,
+}
+
+impl TileMode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Max => "max",
+            Self::Restore => "restore",
+        }
+    }
+}
+
+/// Windows addressable from the shell (`ui dump`).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WindowId {
+    Shell,
+    FileManager,
+    Doom,
+    Settings,
+    App,
+}
+
+impl WindowId {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Shell => "shell",
+            Self::FileManager => "fm",
+            Self::Doom => "doom",
+            Self::Settings => "settings",
+            Self::App => "app",
+        }
+    }
+
+    /// Accepts the short name or the window index shown as `focused=` in `ui`.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim() {
+            "shell" | "0" => Some(Self::Shell),
+            "fm" | "1" => Some(Self::FileManager),
+            "doom" | "2" => Some(Self::Doom),
+            "settings" | "3" => Some(Self::Settings),
+            "app" | "4" => Some(Self::App),
+            _ => None,
+        }
+    }
+
+    const fn index(self) -> usize {
+        match self {
+            Self::Shell => SHELL_WINDOW_INDEX,
+            Self::FileManager => FILE_MANAGER_WINDOW_INDEX,
+            Self::Doom => DOOM_WINDOW_INDEX,
+            Self::Settings => SETTINGS_WINDOW_INDEX,
+            Self::App => APP_WINDOW_INDEX,
+        }
+    }
+}
+
+/// Copy of one window's text grid, taken before printing so the dump does not read a shell
+/// mirror that its own output is scrolling.
+struct WindowTextSnapshot {
+    title: &'static str,
+    cols: usize,
+    rows: usize,
+    cursor_row: usize,
+    cursor_col: usize,
+    visible: bool,
+    minimized: bool,
+    lines: [[u8; WINDOW_MAX_COLS]; WINDOW_MAX_ROWS],
+    line_len: [usize; WINDOW_MAX_ROWS],
+    toolbar: Option<ui::ToolbarSnapshot>,
+}
+
+/// Where the doom window's pixels come from.
+#[cfg(feature = "doom")]
+#[derive(Clone, Copy)]
+pub enum DoomViewSource<'a> {
+    /// Copied into gfx-owned storage (the fallback scene).
+    Pixels(&'a [u32]),
+    /// Read in place from the doom bridge's front surface at every redraw.
+    BridgeFront,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum DoomViewFilter {
+    #[cfg(feature = "doom")]
     Bilinear,
     Nearest,
 }
 
 impl DoomViewFilter {
+    #[cfg(feature = "doom")]
     pub const fn as_str(self) -> &'static str {
         match self {
             Self::Bilinear => "bilinear",
@@ -547,6 +917,28 @@ impl DoomViewFilter {
     }
 }
 
+/// Average TSC cycles per frame for full compositor redraws and for bare backbuffer presents.
+#[derive(Clone, Copy)]
+pub struct GfxBench {
+    pub frames: u32,
+    pub redraw_cycles: u64,
+    /// Zero when the backbuffer is unavailable and redraws write the framebuffer directly.
+    pub present_cycles: u64,
+    pub bytes_per_frame: usize,
+    /// Full screen of opaque text through the glyph cache, and through the per-pixel path.
+    pub text_cycles: u64,
+    pub text_uncached_cycles: u64,
+}
+
+/// Doom window visibility as seen by the runtime's auto-pause policy.
+#[cfg(feature = "doom")]
+#[derive(Clone, Copy)]
+pub struct DoomWindowState {
+    pub open: bool,
+    pub minimized: bool,
+    pub focused: bool,
+}
+
 struct DoomViewPixelsCell(UnsafeCell<[u32; DOOM_VIEW_MAX_PIXELS]>);
 
 // SAFETY: doom view pixels are accessed only from the single-threaded graphics path.
@@ -560,6 +952,7 @@ fn with_doom_view_pixels<R>(f: impl FnOnce(&[u32; DOOM_VIEW_MAX_PIXELS]) -> R) -
     unsafe { f(&*DOOM_VIEW_PIXELS.0.get()) }
 }
 
+#[cfg(feature = "doom")]
 fn with_doom_view_pixels_mut<R>(f: impl FnOnce(&mut [u32; DOOM_VIEW_MAX_PIXELS]) -> R) -> R {
     // SAFETY: graphics rendering runs on one thread in current milestones.
     unsafe { f(&mut *DOOM_VIEW_PIXELS.0.get()) }
@@ -569,23 +962,33 @@ struct GfxState {
     buffer_ptr: *mut u8,
     buffer_len: usize,
     backbuffer: Option<Vec<u8>>,
+    /// Layout of the scene the compositor draws; differs from the framebuffer's when rotated.
     info: FrameBufferInfo,
+    output: rotate::Output,
     windows: [UiWindow; WINDOW_COUNT],
     focused_window: usize,
     input_queue: ByteQueue<INPUT_EVENT_CAPACITY>,
     events: u64,
     dropped: u64,
     stdout_events: u64,
+    /// Log-ring position the shell mirror has shown up to.
+    mirror_cursor: u64,
+    /// Lowest level the shell mirror shows.
+    mirror_level: Level,
+    /// Log-ring bytes overwritten before the mirror got to them.
+    stdout_skipped: u64,
     frames: u64,
     pointer_x: usize,
     pointer_y: usize,
     pointer_left: bool,
     pointer_right: bool,
+    cursor: cursor::CursorPlane,
     mouse_events: u64,
     mouse_click_focus: u64,
     mouse_drag_steps: u64,
     mouse_resize_steps: u64,
     mouse_minimize_toggles: u64,
+    tile_ops: u64,
     drag: DragState,
     resize: ResizeState,
     last_title_click_tick: u64,
@@ -600,8 +1003,21 @@ struct GfxState {
     damage_coalesced: u64,
     present_partial: u64,
     present_full: u64,
+    damage_debug: damage::DamageDebug,
+    checksums: checksum::PresentChecksums,
+    glyphs: glyph::GlyphCache,
     doom_window_open: bool,
     doom_view: DoomViewLayer,
+    file_manager: ui::FileManagerPanel,
+    settings_window_open: bool,
+    app: surface::AppSurface,
+    lifecycle: lifecycle::Lifecycle,
+    settings: ui::SettingsPanel,
+    mouse_speed: u16,
+    /// Sub-pixel motion carried between packets so slow speeds still move the pointer.
+    mouse_remainder: (isize, isize),
+    /// Only the doom window is drawn, focusable or hit-tested.
+    kiosk: bool,
 }
 
 impl GfxState {
@@ -615,6 +1031,8 @@ impl GfxState {
         let doom_h = min(420, info.height.saturating_sub(100)).max(260);
         let doom_x = info.width.saturating_sub(doom_w) / 2;
         let doom_y = info.height.saturating_sub(doom_h) / 2;
+        let settings_w = min(330, info.width.saturating_sub(120)).max(MIN_WINDOW_WIDTH);
+        let settings_h = min(170, info.height.saturating_sub(120)).max(MIN_WINDOW_HEIGHT);
 
         let windows = [
             UiWindow::new(32, 56, primary_w, primary_h, "ARR0ST SHELL MIRROR"),
@@ -624,8 +1042,24 @@ impl GfxState {
                 secondary_w,
                 secondary_h,
                 "ARR0ST FILE MANAGER",
-            ),
+            )
+            .with_footer(ui::TOOLBAR_HEIGHT),
             UiWindow::new(doom_x, doom_y, doom_w, doom_h, "ARR0ST DOOM"),
+            UiWindow::new(
+                info.width.saturating_sub(settings_w) / 2,
+                DESKTOP_TOP + 40,
+                settings_w,
+                settings_h,
+                "ARR0ST SETTINGS",
+            )
+            .with_footer(ui::TOOLBAR_HEIGHT),
+            UiWindow::new(
+                DESKTOP_MARGIN + 60,
+                DESKTOP_TOP + 60,
+                settings_w,
+                settings_h,
+                "ARR0ST APP",
+            ),
         ];
 
         Self {
@@ -633,22 +1067,28 @@ impl GfxState {
             buffer_len,
             backbuffer,
             info,
+            output: rotate::Output::new(info),
             windows,
             focused_window: 0,
             input_queue: ByteQueue::new(),
             events: 0,
             dropped: 0,
             stdout_events: 0,
+            mirror_cursor: serial::log_oldest(),
+            mirror_level: Level::Info,
+            stdout_skipped: 0,
             frames: 0,
             pointer_x: info.width / 2,
             pointer_y: info.height / 2,
             pointer_left: false,
             pointer_right: false,
+            cursor: cursor::CursorPlane::new(),
             mouse_events: 0,
             mouse_click_focus: 0,
             mouse_drag_steps: 0,
             mouse_resize_steps: 0,
             mouse_minimize_toggles: 0,
+            tile_ops: 0,
             drag: DragState::inactive(),
             resize: ResizeState::inactive(),
             last_title_click_tick: 0,
@@ -663,8 +1103,19 @@ impl GfxState {
             damage_coalesced: 0,
             present_partial: 0,
             present_full: 0,
+            damage_debug: damage::DamageDebug::new(),
+            checksums: checksum::PresentChecksums::new(),
+            glyphs: glyph::GlyphCache::new(),
             doom_window_open: false,
             doom_view: DoomViewLayer::new(),
+            file_manager: ui::FileManagerPanel::new(),
+            settings_window_open: false,
+            app: surface::AppSurface::new(),
+            lifecycle: lifecycle::Lifecycle::new(),
+            settings: ui::SettingsPanel::new(),
+            mouse_speed: DEFAULT_MOUSE_SPEED,
+            mouse_remainder: (0, 0),
+            kiosk: false,
         }
     }
 
@@ -729,30 +1180,34 @@ impl GfxState {
     }
 
     fn process_events(&mut self) {
+        self.clear_damage_tint();
         while let Some(byte) = self.input_queue.pop() {
             self.events = self.events.saturating_add(1);
             self.handle_key(byte);
         }
 
         let mut stdout_damage: Option<Rect> = None;
-        while let Some(byte) = serial::pop_mirror_byte() {
-            self.stdout_events = self.stdout_events.saturating_add(1);
-            if let Some(rect) = self.append_mirror_byte_damage(byte) {
-                stdout_damage = Some(match stdout_damage {
-                    Some(existing) => existing.union(rect),
-                    None => rect,
-                });
+        let mut chunk = [0u8; MIRROR_CHUNK_BYTES];
+        loop {
+            let read = serial::read_log(&mut self.mirror_cursor, self.mirror_level, &mut chunk);
+            self.stdout_skipped = self.stdout_skipped.saturating_add(read.skipped);
+            if read.len == 0 {
+                break;
+            }
+            for &byte in &chunk[..read.len] {
+                self.stdout_events = self.stdout_events.saturating_add(1);
+                if let Some(rect) = self.append_mirror_byte_damage(byte) {
+                    stdout_damage = Some(match stdout_damage {
+                        Some(existing) => existing.union(rect),
+                        None => rect,
+                    });
+                }
             }
         }
         if let Some(rect) = stdout_damage {
             self.invalidate_rect(rect);
         }
 
-        while let Some(event) = mouse::pop_event() {
-            self.mouse_events = self.mouse_events.saturating_add(1);
-            self.handle_mouse(event);
-        }
-
         if self.damage_len > 0 {
             self.flush_damage();
         }
@@ -771,6 +1226,46 @@ impl GfxState {
         if index == DOOM_WINDOW_INDEX {
             return self.doom_window_open;
         }
+        if index == SETTINGS_WINDOW_INDEX {
+            return self.settings_window_open && !self.kiosk;
+        }
+        if index == APP_WINDOW_INDEX {
+            return self.app.open() && !self.kiosk;
+        }
+        !self.kiosk
+    }
+
+    /// Opens, focuses and maximizes the doom window.
+    fn maximize_doom_window(&mut self) -> bool {
+        self.open_doom_window();
+        if self.windows[DOOM_WINDOW_INDEX].tile == Some(TileMode::Max) {
+            return false;
+        }
+        self.tile_window(DOOM_WINDOW_INDEX, TileMode::Max)
+    }
+
+    /// Re-renders the shell mirror from the oldest output the log ring holds, showing only
+    /// `level` and above from now on.
+    fn set_mirror_level(&mut self, level: Level) {
+        self.mirror_level = level;
+        self.mirror_cursor = serial::log_oldest();
+        self.windows[SHELL_WINDOW_INDEX].clear_text();
+        self.invalidate_window(SHELL_WINDOW_INDEX);
+    }
+
+    fn set_kiosk(&mut self, enabled: bool) -> bool {
+        if self.kiosk == enabled {
+            return false;
+        }
+        self.kiosk = enabled;
+        if enabled {
+            self.drag = DragState::inactive();
+            self.resize = ResizeState::inactive();
+            let _ = self.maximize_doom_window();
+        } else if self.doom_window_open {
+            let _ = self.tile_window(DOOM_WINDOW_INDEX, TileMode::Restore);
+        }
+        self.redraw();
         true
     }
 
@@ -830,6 +1325,38 @@ impl GfxState {
         self.invalidate_rect(previous);
     }
 
+    fn open_settings_window(&mut self) -> bool {
+        let was_open = self.settings_window_open;
+        self.settings_window_open = true;
+        if self.windows[SETTINGS_WINDOW_INDEX].minimized {
+            self.toggle_minimize(SETTINGS_WINDOW_INDEX);
+        }
+        let _ = self.set_focus(SETTINGS_WINDOW_INDEX);
+        if !was_open {
+            self.invalidate_window(SETTINGS_WINDOW_INDEX);
+        }
+        !was_open
+    }
+
+    fn close_settings_window(&mut self) {
+        if !self.settings_window_open {
+            return;
+        }
+        let previous = self.window_rect(SETTINGS_WINDOW_INDEX);
+        self.settings_window_open = false;
+        if self.focused_window == SETTINGS_WINDOW_INDEX {
+            self.focused_window = SHELL_WINDOW_INDEX;
+            self.invalidate_window_chrome(SHELL_WINDOW_INDEX);
+        }
+        if self.drag.active && self.drag.window_index == SETTINGS_WINDOW_INDEX {
+            self.drag = DragState::inactive();
+        }
+        if self.resize.active && self.resize.window_index == SETTINGS_WINDOW_INDEX {
+            self.resize = ResizeState::inactive();
+        }
+        self.invalidate_rect(previous);
+    }
+
     fn set_window_text(&mut self, index: usize, text: &str) {
         if index >= WINDOW_COUNT {
             return;
@@ -842,7 +1369,8 @@ impl GfxState {
         }
     }
 
-    fn set_doom_view(&mut self, width: usize, height: usize, pixels: &[u32]) {
+    #[cfg(feature = "doom")]
+    fn set_doom_view(&mut self, width: usize, height: usize, source: DoomViewSource<'_>) {
         self.open_doom_window();
         let window = self.windows[DOOM_WINDOW_INDEX];
         let previous_damage = if self.doom_view.active {
@@ -850,7 +1378,7 @@ impl GfxState {
         } else {
             None
         };
-        if self.doom_view.set(width, height, pixels) {
+        if self.doom_view.set(width, height, source) {
             let next_damage = self.doom_view_damage_rect(window);
             match (previous_damage, next_damage) {
                 (Some(previous), Some(next)) => self.invalidate_rect(previous.union(next)),
@@ -861,10 +1389,12 @@ impl GfxState {
         }
     }
 
+    #[cfg(feature = "doom")]
     fn clear_doom_view(&mut self) {
         self.close_doom_window();
     }
 
+    #[cfg(feature = "doom")]
     fn set_doom_view_filter(&mut self, filter: DoomViewFilter) -> bool {
         if !self.doom_view.set_filter(filter) {
             return false;
@@ -879,6 +1409,7 @@ impl GfxState {
         true
     }
 
+    #[cfg(feature = "doom")]
     fn doom_view_filter(&self) -> DoomViewFilter {
         self.doom_view.filter
     }
@@ -893,16 +1424,16 @@ impl GfxState {
         }
     }
 
-    fn handle_mouse(&mut self, event: mouse::MouseEvent) {
-        let previous_pointer_x = self.pointer_x;
-        let previous_pointer_y = self.pointer_y;
+    /// `captured` events went to a consumer that holds the input bus capture; they only move
+    /// the pointer.
+    fn handle_mouse(&mut self, event: mouse::MouseEvent, captured: bool) {
         let previous_pointer_left = self.pointer_left;
-        let previous_pointer_right = self.pointer_right;
 
         let max_x = self.info.width.saturating_sub(1) as isize;
         let max_y = self.info.height.saturating_sub(1) as isize;
-        let mut next_x = self.pointer_x as isize + event.dx as isize;
-        let mut next_y = self.pointer_y as isize - event.dy as isize;
+        let (dx, dy) = self.scale_mouse_motion(event.dx, event.dy);
+        let mut next_x = self.pointer_x as isize + dx;
+        let mut next_y = self.pointer_y as isize - dy;
         next_x = next_x.clamp(0, max_x);
         next_y = next_y.clamp(0, max_y);
 
@@ -915,22 +1446,12 @@ impl GfxState {
         let left_released = !event.left_button && self.pointer_left;
         let right_released = !event.right_button && self.pointer_right;
 
-        if doom::inject_mouse(
-            event.dx,
-            event.dy,
-            event.left_button,
-            event.right_button,
-            event.middle_button,
-        ) {
-            if moved
-                || previous_pointer_left != event.left_button
-                || previous_pointer_right != event.right_button
-            {
-                self.invalidate_pointer(previous_pointer_x, previous_pointer_y);
-                self.invalidate_pointer(self.pointer_x, self.pointer_y);
-            }
+        if captured {
             self.pointer_left = event.left_button;
             self.pointer_right = event.right_button;
+            if moved || previous_pointer_left != event.left_button {
+                self.move_cursor();
+            }
             return;
         }
 
@@ -942,7 +1463,10 @@ impl GfxState {
                     self.mouse_click_focus = self.mouse_click_focus.saturating_add(1);
                 }
 
-                if self.point_on_title_bar(index, self.pointer_x, self.pointer_y) {
+                if self.point_on_close_button(index, self.pointer_x, self.pointer_y) {
+                    let _ = self.request_close(index, now_tick);
+                    self.drag = DragState::inactive();
+                } else if self.point_on_title_bar(index, self.pointer_x, self.pointer_y) {
                     if self.is_title_double_click(index, now_tick) {
                         self.toggle_minimize(index);
                         self.mouse_minimize_toggles = self.mouse_minimize_toggles.saturating_add(1);
@@ -957,6 +1481,8 @@ impl GfxState {
                             offset_y: self.pointer_y.saturating_sub(window.y),
                         };
                     }
+                } else if !self.windows[index].minimized {
+                    self.widget_click(index, self.pointer_x, self.pointer_y);
                 }
             } else {
                 self.drag = DragState::inactive();
@@ -999,13 +1525,89 @@ impl GfxState {
             self.mouse_resize_steps = self.mouse_resize_steps.saturating_add(1);
         }
 
-        if moved || previous_pointer_left != event.left_button {
-            self.invalidate_pointer(previous_pointer_x, previous_pointer_y);
-            self.invalidate_pointer(self.pointer_x, self.pointer_y);
-        }
-
         self.pointer_left = event.left_button;
         self.pointer_right = event.right_button;
+        if moved || previous_pointer_left != event.left_button || left_released || right_released {
+            self.move_cursor();
+        }
+    }
+
+    /// Applies `mouse_speed`; the remainder of each axis carries into the next packet.
+    fn scale_mouse_motion(&mut self, dx: i16, dy: i16) -> (isize, isize) {
+        let speed = self.mouse_speed as isize;
+        let x = dx as isize * speed + self.mouse_remainder.0;
+        let y = dy as isize * speed + self.mouse_remainder.1;
+        self.mouse_remainder = (x % 100, y % 100);
+        (x / 100, y / 100)
+    }
+
+    /// Shape for the pointer's current position: active drags and resizes keep their shape
+    /// even when the pointer outruns the window edge.
+    fn cursor_shape_at(&self, x: usize, y: usize) -> CursorShape {
+        if self.resize.active {
+            return CursorShape::Resize;
+        }
+        if self.drag.active {
+            return CursorShape::Move;
+        }
+        let Some(index) = self.window_at(x, y) else {
+            return CursorShape::Arrow;
+        };
+        if self.point_on_resize_handle(index, x, y) {
+            return CursorShape::Resize;
+        }
+        if self.point_on_close_button(index, x, y) {
+            return CursorShape::Arrow;
+        }
+        if self.point_on_title_bar(index, x, y) {
+            return CursorShape::Move;
+        }
+        if self.window_text_area_rect(index).contains(x, y)
+            && index != DOOM_WINDOW_INDEX
+            && !(index == APP_WINDOW_INDEX && self.app.attached())
+            && !self.windows[index].minimized
+        {
+            return CursorShape::Text;
+        }
+        CursorShape::Arrow
+    }
+
+    fn framebuffer_mut(&self) -> &'static mut [u8] {
+        // SAFETY: framebuffer pointer/length come from bootloader and remain valid for kernel life;
+        // the slice is only used for the duration of one cursor hide/show.
+        unsafe { core::slice::from_raw_parts_mut(self.buffer_ptr, self.buffer_len) }
+    }
+
+    fn hide_cursor(&mut self) {
+        let framebuffer = self.framebuffer_mut();
+        self.cursor.hide(framebuffer, &self.output);
+    }
+
+    fn show_cursor(&mut self) {
+        let shape = self.cursor_shape_at(self.pointer_x, self.pointer_y);
+        let framebuffer = self.framebuffer_mut();
+        self.cursor.show(
+            framebuffer,
+            &self.output,
+            (self.pointer_x, self.pointer_y),
+            shape,
+            self.pointer_left,
+        );
+    }
+
+    /// Moves the cursor plane without touching the scene or the damage list.
+    fn move_cursor(&mut self) {
+        self.hide_cursor();
+        self.show_cursor();
+        self.cursor.count_move();
+    }
+
+    fn set_cursor_theme(&mut self, theme: CursorTheme) -> bool {
+        if !self.cursor.set_theme(theme) {
+            return false;
+        }
+        self.move_cursor();
+        true
     }
 
     fn window_at(&self, x: usize, y: usize) -> Option<usize> {
@@ -1106,6 +1708,72 @@ impl GfxState {
         self.invalidate_window(index);
     }
 
+    /// Outer geometry for a tiled window; `window_rect` adds 4px of shadow on each far edge.
+    fn tile_rect(&self, mode: TileMode) -> (usize, usize, usize, usize) {
+        let area_w = self
+            .info
+            .width
+            .saturating_sub(DESKTOP_MARGIN.saturating_mul(2));
+        let area_h = self
+            .info
+            .height
+            .saturating_sub(DESKTOP_TOP)
+            .saturating_sub(DESKTOP_MARGIN);
+        let half_w = area_w / 2;
+        let h = area_h.saturating_sub(4).max(MIN_WINDOW_HEIGHT);
+        let (x, w) = match mode {
+            TileMode::Left => (DESKTOP_MARGIN, half_w),
+            TileMode::Right => (DESKTOP_MARGIN + half_w, area_w - half_w),
+            TileMode::Max | TileMode::Restore => (DESKTOP_MARGIN, area_w),
+        };
+        (x, DESKTOP_TOP, w.saturating_sub(4).max(MIN_WINDOW_WIDTH), h)
+    }
+
+    fn tile_window(&mut self, index: usize, mode: TileMode) -> bool {
+        if !self.window_visible(index) {
+            return false;
+        }
+        let mut changed = false;
+        if self.windows[index].minimized {
+            self.toggle_minimize(index);
+            changed = true;
+        }
+        let current = self.windows[index].tile;
+        let target = match (mode, current) {
+            (TileMode::Restore, _) | (TileMode::Max, Some(TileMode::Max)) => None,
+            (mode, _) => Some(mode),
+        };
+        if target == current {
+            return changed;
+        }
+
+        let previous = self.window_rect(index);
+        let tiled = target.map(|mode| self.tile_rect(mode));
+        let window = &mut self.windows[index];
+        if current.is_none() {
+            window.untiled_x = window.x;
+            window.untiled_y = window.y;
+            window.untiled_w = window.w;
+            window.untiled_h = window.h;
+        }
+        let (x, y, w, h) = tiled.unwrap_or((
+            window.untiled_x,
+            window.untiled_y,
+            window.untiled_w,
+            window.untiled_h,
+        ));
+        window.x = x;
+        window.y = y;
+        window.w = w;
+        window.h = h;
+        window.tile = target;
+        window.recalc_text_grid();
+        self.tile_ops = self.tile_ops.saturating_add(1);
+        self.invalidate_rect(previous);
+        self.invalidate_window(index);
+        true
+    }
+
     fn apply_drag(&mut self) -> bool {
         let drag = self.drag;
         let index = drag.window_index;
@@ -1130,13 +1798,14 @@ impl GfxState {
             .saturating_sub(DESKTOP_MARGIN);
 
         new_x = new_x.clamp(DESKTOP_MARGIN, max_x.max(DESKTOP_MARGIN));
-        new_y = new_y.clamp(32, max_y.max(32));
+        new_y = new_y.clamp(DESKTOP_TOP, max_y.max(DESKTOP_TOP));
 
         if new_x == window.x && new_y == window.y {
             return false;
         }
         self.windows[index].x = new_x;
         self.windows[index].y = new_y;
+        self.windows[index].tile = None;
         self.invalidate_rect(previous);
         self.invalidate_window(index);
         true
@@ -1180,6 +1849,7 @@ impl GfxState {
         }
         self.windows[index].w = new_w;
         self.windows[index].h = new_h;
+        self.windows[index].tile = None;
         self.windows[index].recalc_text_grid();
         self.invalidate_rect(previous);
         self.invalidate_window(index);
@@ -1212,6 +1882,16 @@ impl GfxState {
         Rect::new(x, y, CHAR_W, CHAR_H)
     }
 
+    fn window_text_row_rect(&self, index: usize, row: usize) -> Rect {
+        let area = self.window_text_area_rect(index);
+        Rect::new(
+            area.x,
+            area.y.saturating_add(row.saturating_mul(CHAR_H)),
+            area.w,
+            CHAR_H,
+        )
+    }
+
     fn window_chrome_rects(&self, index: usize) -> [Rect; 5] {
         let window = self.windows[index];
         let top = Rect::new(
@@ -1248,15 +1928,6 @@ impl GfxState {
         [top, left, right, bottom, handle]
     }
 
-    fn pointer_rect_at(x: usize, y: usize) -> Rect {
-        Rect::new(
-            x.saturating_sub(1),
-            y.saturating_sub(1),
-            POINTER_RECT_SIZE,
-            POINTER_RECT_SIZE,
-        )
-    }
-
     fn invalidate_window(&mut self, index: usize) {
         if !self.window_visible(index) {
             return;
@@ -1264,10 +1935,6 @@ impl GfxState {
         self.invalidate_rect(self.window_rect(index));
     }
 
-    fn invalidate_pointer(&mut self, x: usize, y: usize) {
-        self.invalidate_rect(Self::pointer_rect_at(x, y));
-    }
-
     fn invalidate_window_chrome(&mut self, index: usize) {
         if !self.window_visible(index) {
             return;
@@ -1296,6 +1963,10 @@ impl GfxState {
             self.damage_len += 1;
             return;
         }
+        soft_assert!(
+            false,
+            "gfx damage queue full ({DAMAGE_CAPACITY} rects), merging into slot 0"
+        );
         self.damage_dropped = self.damage_dropped.saturating_add(1);
         if self.damage_len > 0 {
             self.damage[0] = self.damage[0].union(clamped);
@@ -1318,7 +1989,11 @@ impl GfxState {
     }
 
     fn remove_damage_at(&mut self, index: usize) {
-        if index >= self.damage_len {
+        if !soft_assert!(
+            index < self.damage_len,
+            "gfx damage remove index={index} len={}",
+            self.damage_len
+        ) {
             return;
         }
         let last = self.damage_len - 1;
@@ -1338,29 +2013,92 @@ impl GfxState {
     }
 
     fn flush_damage(&mut self) {
+        self.clear_damage_tint();
         self.coalesce_damage_queue();
         for index in 0..self.damage_len {
             self.redraw_region(self.damage[index]);
         }
+        if self.damage_debug.enabled() {
+            self.tint_damage();
+        }
         self.damage_len = 0;
     }
 
-    fn redraw_region(&mut self, rect: Rect) {
-        self.clip = Some(rect);
-        self.draw_desktop_background();
-        self.draw_top_bar();
+    fn tint_damage(&mut self) {
+        let rects = self.damage;
+        let rects = &rects[..self.damage_len];
+        let tint = self.damage_debug.record_flush(rects);
+        for &rect in rects {
+            let cursor_covered = self.cursor.overlaps(rect);
+            if cursor_covered {
+                self.hide_cursor();
+            }
+            if let Some(rect) = rect.clamped(self.info.width, self.info.height) {
+                let physical = self.output.map_rect(rect);
+                damage::tint_rect(
+                    self.framebuffer_mut(),
+                    self.output.physical(),
+                    physical,
+                    tint,
+                );
+            }
+            if cursor_covered {
+                self.show_cursor();
+            }
+        }
+    }
 
-        for index in 0..WINDOW_COUNT {
-            if !self.window_visible(index) {
+    /// Puts the scene back under rects tinted by the previous flush.
+    fn clear_damage_tint(&mut self) {
+        let (rects, len) = self.damage_debug.take_tinted();
+        for &rect in &rects[..len] {
+            if self.backbuffer.is_none() {
+                self.redraw_region(rect);
+                continue;
+            }
+            let cursor_covered = self.cursor.overlaps(rect);
+            if cursor_covered {
+                self.hide_cursor();
+            }
+            self.present_rect(rect);
+            if cursor_covered {
+                self.show_cursor();
+            }
+        }
+    }
+
+    fn set_damage_debug(&mut self, enabled: bool) -> bool {
+        if !self.damage_debug.set_enabled(enabled) {
+            return false;
+        }
+        if !enabled {
+            self.clear_damage_tint();
+        }
+        true
+    }
+
+    fn redraw_region(&mut self, rect: Rect) {
+        let cursor_covered = self.cursor.overlaps(rect);
+        if cursor_covered {
+            self.hide_cursor();
+        }
+        self.clip = Some(rect);
+        self.draw_desktop_background();
+        self.draw_top_bar();
+
+        for index in 0..WINDOW_COUNT {
+            if !self.window_visible(index) {
                 continue;
             }
             let focused = index == self.focused_window;
             self.draw_window(index, self.windows[index], focused);
         }
-        self.draw_pointer();
 
         self.clip = None;
         self.present_rect(rect);
+        if cursor_covered {
+            self.show_cursor();
+        }
         self.frames = self.frames.saturating_add(1);
         self.partial_redraws = self.partial_redraws.saturating_add(1);
         self.present_partial = self.present_partial.saturating_add(1);
@@ -1377,6 +2115,16 @@ impl GfxState {
         if row_bytes == 0 {
             return;
         }
+        evlog::record(
+            Event::GfxPresent,
+            (region.x as u32) << 16 | region.y as u32 & 0xffff,
+            (region.w as u32) << 16 | region.h as u32 & 0xffff,
+        );
+        if self.output.rotation() != Rotation::Deg0 {
+            self.present_rotated(region);
+            return;
+        }
+        let mut hash = checksum::start();
         for row in 0..region.h {
             let y = region.y + row;
             let pixel_index = y.saturating_mul(self.info.stride).saturating_add(region.x);
@@ -1395,6 +2143,218 @@ impl GfxState {
                     row_bytes,
                 );
             }
+            hash = checksum::extend(hash, &backbuffer[byte_offset..byte_offset + row_bytes]);
+        }
+        let full_frame = region.w == self.info.width && region.h == self.info.height;
+        self.checksums.record(region, hash, full_frame);
+    }
+
+    /// `present_rect` for a turned output: each logical pixel is copied to where the output
+    /// maps it, so the rows land as columns at 90 and 270 degrees.
+    fn present_rotated(&mut self, region: Rect) {
+        let Some(backbuffer) = self.backbuffer.as_ref() else {
+            return;
+        };
+        let bpp = self.info.bytes_per_pixel;
+        let physical = self.output.physical();
+        let row_bytes = region.w * bpp;
+        let mut hash = checksum::start();
+        for y in region.y..region.y + region.h {
+            let row_offset = (y * self.info.stride + region.x) * bpp;
+            let Some(row) = backbuffer.get(row_offset..row_offset + row_bytes) else {
+                break;
+            };
+            for (dx, pixel) in row.chunks_exact(bpp).enumerate() {
+                let (px, py) = self.output.map_point(region.x + dx, y);
+                let offset = (py * physical.stride + px) * bpp;
+                if offset + bpp > self.buffer_len {
+                    continue;
+                }
+                // SAFETY: the destination pixel is bounds-checked against the framebuffer, which
+                // never overlaps the backbuffer.
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        pixel.as_ptr(),
+                        self.buffer_ptr.add(offset),
+                        bpp,
+                    );
+                }
+            }
+            hash = checksum::extend(hash, row);
+        }
+        let full_frame = region.w == self.info.width && region.h == self.info.height;
+        self.checksums.record(region, hash, full_frame);
+    }
+
+    /// Turns the output and reflows the desktop into the new logical size. Rotation needs a
+    /// backbuffer of the turned layout; when none can be allocated nothing changes.
+    fn set_rotation(&mut self, rotation: Rotation) -> Result<bool, RotateError> {
+        if rotation == self.output.rotation() {
+            return Ok(false);
+        }
+        let mut output = self.output;
+        output.set_rotation(rotation);
+        let info = output.logical_info();
+        let len = if rotation == Rotation::Deg0 {
+            self.buffer_len
+        } else {
+            info.height * info.stride * info.bytes_per_pixel
+        };
+        let backbuffer = alloc_backbuffer(len);
+        if backbuffer.is_none() && rotation != Rotation::Deg0 {
+            return Err(RotateError::NoBackbuffer);
+        }
+
+        self.hide_cursor();
+        self.clear_damage_tint();
+        self.output = output;
+        self.info = info;
+        self.backbuffer = backbuffer;
+        self.damage_len = 0;
+        self.drag = DragState::inactive();
+        self.resize = ResizeState::inactive();
+        self.pointer_x = self.pointer_x.min(info.width.saturating_sub(1));
+        self.pointer_y = self.pointer_y.min(info.height.saturating_sub(1));
+        for index in 0..WINDOW_COUNT {
+            self.fit_window(index);
+        }
+        self.redraw();
+        Ok(true)
+    }
+
+    /// `restart gfx`: drops and re-probes the backbuffer, clears the glyph cache, damage and
+    /// counters, then redraws everything. Windows and their text stay. A rotated scene that
+    /// can no longer get a backbuffer falls back to 0 degrees.
+    fn restart(&mut self) -> GfxRestartReport {
+        self.hide_cursor();
+        self.clear_damage_tint();
+        self.backbuffer = None;
+        let rotation = self.output.rotation();
+        let mut rotation_reset = false;
+        if rotation == Rotation::Deg0 {
+            let _ = self.try_enable_backbuffer();
+        } else {
+            let len = self.info.height * self.info.stride * self.info.bytes_per_pixel;
+            self.backbuffer = alloc_backbuffer(len);
+            if self.backbuffer.is_none() {
+                self.output.set_rotation(Rotation::Deg0);
+                self.info = self.output.logical_info();
+                rotation_reset = true;
+                let _ = self.try_enable_backbuffer();
+            }
+        }
+
+        self.input_queue = ByteQueue::new();
+        self.events = 0;
+        self.dropped = 0;
+        self.stdout_events = 0;
+        self.stdout_skipped = 0;
+        self.frames = 0;
+        self.mouse_events = 0;
+        self.mouse_click_focus = 0;
+        self.mouse_drag_steps = 0;
+        self.mouse_resize_steps = 0;
+        self.mouse_minimize_toggles = 0;
+        self.tile_ops = 0;
+        self.drag = DragState::inactive();
+        self.resize = ResizeState::inactive();
+        self.last_title_click_valid = false;
+        self.clip = None;
+        self.damage_len = 0;
+        self.partial_redraws = 0;
+        self.full_redraws = 0;
+        self.damage_dropped = 0;
+        self.damage_coalesced = 0;
+        self.present_partial = 0;
+        self.present_full = 0;
+        self.checksums = checksum::PresentChecksums::new();
+        self.glyphs = glyph::GlyphCache::new();
+        self.pointer_x = self.pointer_x.min(self.info.width.saturating_sub(1));
+        self.pointer_y = self.pointer_y.min(self.info.height.saturating_sub(1));
+        for index in 0..WINDOW_COUNT {
+            self.fit_window(index);
+        }
+        self.redraw();
+        GfxRestartReport {
+            width: self.info.width,
+            height: self.info.height,
+            double_buffer: self.backbuffer.is_some(),
+            rotation: self.output.rotation(),
+            rotation_reset,
+        }
+    }
+
+    /// Pulls a window back inside the desktop after the logical size shrank; tiled windows are
+    /// re-tiled for the new size.
+    fn fit_window(&mut self, index: usize) {
+        let tiled = self.windows[index].tile.map(|mode| self.tile_rect(mode));
+        let max_w = self
+            .info
+            .width
+            .saturating_sub(DESKTOP_MARGIN * 2)
+            .max(MIN_WINDOW_WIDTH);
+        let max_h = self
+            .info
+            .height
+            .saturating_sub(DESKTOP_TOP + DESKTOP_MARGIN)
+            .max(MIN_WINDOW_HEIGHT);
+        let window = &mut self.windows[index];
+        if let Some((x, y, w, h)) = tiled {
+            window.x = x;
+            window.y = y;
+            window.w = w;
+            window.h = h;
+        } else {
+            window.w = window.w.min(max_w);
+            window.h = window.h.min(max_h);
+            let max_x = self.info.width.saturating_sub(window.w + DESKTOP_MARGIN);
+            let max_y = self.info.height.saturating_sub(window.h + DESKTOP_MARGIN);
+            window.x = window.x.clamp(DESKTOP_MARGIN, max_x.max(DESKTOP_MARGIN));
+            window.y = window.y.clamp(DESKTOP_TOP, max_y.max(DESKTOP_TOP));
+        }
+        window.recalc_text_grid();
+    }
+
+    /// Checksum of `rect` as presented: the backbuffer when double buffered, else the
+    /// framebuffer itself.
+    fn checksum_rect(&self, rect: Rect) -> u64 {
+        let mut hash = checksum::start();
+        let Some(region) = rect.clamped(self.info.width, self.info.height) else {
+            return hash;
+        };
+        let pixels: &[u8] = match self.backbuffer.as_ref() {
+            Some(backbuffer) => backbuffer,
+            None => self.framebuffer_mut(),
+        };
+        let row_bytes = region.w.saturating_mul(self.info.bytes_per_pixel);
+        for row in 0..region.h {
+            let pixel_index = (region.y + row)
+                .saturating_mul(self.info.stride)
+                .saturating_add(region.x);
+            let byte_offset = pixel_index.saturating_mul(self.info.bytes_per_pixel);
+            let Some(bytes) = pixels.get(byte_offset..byte_offset.saturating_add(row_bytes)) else {
+                break;
+            };
+            hash = checksum::extend(hash, bytes);
+        }
+        hash
+    }
+
+    fn checksum(&self) -> checksum::ChecksumReport {
+        let doom = self
+            .window_visible(DOOM_WINDOW_INDEX)
+            .then(|| self.doom_view_layout(self.windows[DOOM_WINDOW_INDEX]))
+            .flatten()
+            .map(|(x, y, w, h)| self.checksum_rect(Rect::new(x, y, w, h)));
+        checksum::ChecksumReport {
+            frame: self.checksum_rect(Rect::new(0, 0, self.info.width, self.info.height)),
+            source: if self.backbuffer.is_some() {
+                "backbuffer"
+            } else {
+                "framebuffer"
+            },
+            doom,
+            presents: self.checksums.report(),
         }
     }
 
@@ -1416,15 +2376,18 @@ impl GfxState {
             stride: self.info.stride,
             bytes_per_pixel: self.info.bytes_per_pixel,
             pixel_format: pixel_format_name(self.info.pixel_format),
+            rotation: self.output.rotation(),
+            color: color::adjust(),
             focused_window: self.focused_window + 1,
             events: self.events,
             dropped: self.dropped,
             stdout_events: self.stdout_events,
-            stdout_dropped: serial::mirror_dropped(),
+            stdout_skipped: self.stdout_skipped,
             frames: self.frames,
             mouse_x: self.pointer_x,
             mouse_y: self.pointer_y,
             mouse_events: self.mouse_events,
+            mouse_speed: self.mouse_speed,
             mouse_click_focus: self.mouse_click_focus,
             mouse_drag_steps: self.mouse_drag_steps,
             mouse_resize_steps: self.mouse_resize_steps,
@@ -1433,6 +2396,13 @@ impl GfxState {
             focused_minimized,
             minimized_windows,
             mouse_minimize_toggles: self.mouse_minimize_toggles,
+            tile_ops: self.tile_ops,
+            focused_tile: self
+                .windows
+                .get(self.focused_window)
+                .and_then(|window| window.tile)
+                .map(TileMode::as_str)
+                .unwrap_or("none"),
             partial_redraws: self.partial_redraws,
             full_redraws: self.full_redraws,
             damage_dropped: self.damage_dropped,
@@ -1440,10 +2410,24 @@ impl GfxState {
             present_partial: self.present_partial,
             present_full: self.present_full,
             double_buffer: self.backbuffer.is_some(),
+            cursor_shape: self.cursor.shape().as_str(),
+            cursor_theme: self.cursor.theme().as_str(),
+            cursor_moves: self.cursor.moves(),
+            cursor_shape_changes: self.cursor.shape_changes(),
+            damage_debug: self.damage_debug.enabled(),
+            damage_debug_flushes: self.damage_debug.flushes(),
+            damage_debug_rects: self.damage_debug.rects(),
+            glyph_hits: self.glyphs.hits(),
+            glyph_renders: self.glyphs.renders(),
+            button_clicks: self.button_clicks(),
+            button_drops: self.button_drops(),
+            lifecycle: self.lifecycle_status(),
+            kiosk: self.kiosk,
         }
     }
 
     fn redraw(&mut self) {
+        self.hide_cursor();
         self.clip = None;
         self.draw_desktop_background();
         self.draw_top_bar();
@@ -1455,9 +2439,9 @@ impl GfxState {
             let focused = index == self.focused_window;
             self.draw_window(index, self.windows[index], focused);
         }
-        self.draw_pointer();
 
         self.present_rect(Rect::new(0, 0, self.info.width, self.info.height));
+        self.show_cursor();
         self.frames = self.frames.saturating_add(1);
         self.full_redraws = self.full_redraws.saturating_add(1);
         self.present_full = self.present_full.saturating_add(1);
@@ -1504,7 +2488,7 @@ impl GfxState {
         self.draw_text(
             10,
             8,
-            "ARR0ST M9 APPS | TERMINAL + FILE MANAGER + DOOM | TAB/MOUSE FOCUS",
+            "ARR0ST M9 APPS | TERMINAL + FILE MANAGER + DOOM | TAB/MOUSE FOCUS | ARROWS TILE",
             Color::rgb(230, 235, 242),
             Some(bar),
         );
@@ -1565,6 +2549,9 @@ impl GfxState {
                 text,
                 Some(title),
             );
+            if Self::closable(index) {
+                self.draw_close_button(window, text);
+            }
             return;
         }
 
@@ -1575,6 +2562,9 @@ impl GfxState {
             text,
             Some(title),
         );
+        if Self::closable(index) {
+            self.draw_close_button(window, text);
+        }
 
         let origin_x = window.x.saturating_add(WINDOW_PADDING);
         let origin_y = window.y.saturating_add(TITLE_BAR_HEIGHT + WINDOW_PADDING);
@@ -1605,9 +2595,23 @@ impl GfxState {
             }
         }
 
+        let selected_row = self.selected_list_row(index);
+        let selected_bg = Color::rgb(58, 92, 136);
         if row_start < row_end && col_start < col_end {
             for row in row_start..row_end {
                 let draw_y = origin_y.saturating_add(row.saturating_mul(CHAR_H));
+                let row_bg = if selected_row == Some(row) {
+                    self.fill_rect(
+                        origin_x,
+                        draw_y,
+                        window.visible_cols().saturating_mul(CHAR_W),
+                        CHAR_H,
+                        selected_bg,
+                    );
+                    selected_bg
+                } else {
+                    body
+                };
                 let len = min(window.line_len[row], window.visible_cols());
                 if len == 0 || col_start >= len {
                     continue;
@@ -1615,14 +2619,19 @@ impl GfxState {
                 let draw_end = min(len, col_end);
                 for col in col_start..draw_end {
                     let draw_x = origin_x.saturating_add(col.saturating_mul(CHAR_W));
-                    self.draw_char(draw_x, draw_y, window.lines[row][col], text, Some(body));
+                    let fg = window.attrs[row][col].color(text);
+                    self.draw_char(draw_x, draw_y, window.lines[row][col], fg, Some(row_bg));
                 }
             }
         }
 
+        self.draw_widgets(index, body);
         if index == DOOM_WINDOW_INDEX && self.doom_view.active {
             self.draw_doom_view(window);
         }
+        if index == APP_WINDOW_INDEX {
+            self.draw_app_surface(window);
+        }
 
         self.draw_resize_handle(window, focused);
     }
@@ -1663,6 +2672,7 @@ impl GfxState {
         Some((draw_x, draw_y, draw_w, draw_h))
     }
 
+    #[cfg(feature = "doom")]
     fn doom_view_damage_rect(&self, window: UiWindow) -> Option<Rect> {
         let (draw_x, draw_y, draw_w, draw_h) = self.doom_view_layout(window)?;
         let title_y = draw_y.saturating_sub(11);
@@ -1700,7 +2710,8 @@ impl GfxState {
         );
         self.fill_rect(draw_x, draw_y, draw_w, draw_h, panel_color);
 
-        with_doom_view_pixels(|pixels| {
+        let layer = self.doom_view;
+        layer.with_pixels(|pixels| {
             if draw_w == src_w && draw_h == src_h {
                 for y in 0..src_h {
                     for x in 0..src_w {
@@ -1725,7 +2736,7 @@ impl GfxState {
                 let x_last_fp = (src_w_last as u32).saturating_mul(1u32 << 16);
                 let y_last_fp = (src_h_last as u32).saturating_mul(1u32 << 16);
                 let mut sy_fp = 0u32;
-                if self.doom_view.filter == DoomViewFilter::Nearest {
+                if layer.filter == DoomViewFilter::Nearest {
                     for y in 0..draw_h {
                         let sy_cur = if y + 1 == draw_h { y_last_fp } else { sy_fp };
                         let sy = (((sy_cur as u64).saturating_add(1u64 << 15)) >> 16) as usize;
@@ -1813,28 +2824,6 @@ impl GfxState {
         }
     }
 
-    fn draw_pointer(&mut self) {
-        let cursor = if self.pointer_left {
-            Color::rgb(255, 208, 90)
-        } else {
-            Color::rgb(250, 250, 250)
-        };
-        let outline = Color::rgb(8, 12, 18);
-
-        let x = self.pointer_x;
-        let y = self.pointer_y;
-        for offset in 0..7 {
-            self.write_pixel(x.saturating_add(offset), y, outline);
-            self.write_pixel(x, y.saturating_add(offset), outline);
-        }
-        for offset in 1..6 {
-            self.write_pixel(x.saturating_add(offset), y.saturating_add(1), cursor);
-            self.write_pixel(x.saturating_add(1), y.saturating_add(offset), cursor);
-        }
-        self.write_pixel(x.saturating_add(2), y.saturating_add(2), cursor);
-        self.write_pixel(x.saturating_add(3), y.saturating_add(3), cursor);
-    }
-
     fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color) {
         if width == 0 || height == 0 {
             return;
@@ -1877,19 +2866,24 @@ impl GfxState {
             }
             clip_x = Some((clip.x, clip.x.saturating_add(clip.w).min(self.info.width)));
         }
-        for byte in text.bytes() {
+        for ch in text.chars() {
             if let Some((clip_x0, clip_x1)) = clip_x
                 && (cursor.saturating_add(CHAR_W) <= clip_x0 || cursor >= clip_x1)
             {
                 cursor = cursor.saturating_add(CHAR_W);
                 continue;
             }
-            self.draw_char(cursor, y, byte, fg, bg);
+            self.draw_char(cursor, y, glyph_byte(ch), fg, bg);
             cursor = cursor.saturating_add(CHAR_W);
         }
     }
 
     fn draw_char(&mut self, x: usize, y: usize, byte: u8, fg: Color, bg: Option<Color>) {
+        if let Some(bg_color) = bg
+            && self.blit_cached_char(x, y, byte, fg, bg_color)
+        {
+            return;
+        }
         let glyph = glyph_rows(byte);
         for (row, bits) in glyph.iter().copied().enumerate() {
             for col in 0..5 {
@@ -1917,6 +2911,101 @@ impl GfxState {
         }
     }
 
+    /// Fills the render target with text `frames` times and returns cycles per fill; the caller
+    /// redraws afterwards.
+    fn bench_text(&mut self, frames: u32, cached: bool) -> u64 {
+        let was_enabled = self.glyphs.enabled();
+        let clip = self.clip.take();
+        self.glyphs.set_enabled(cached);
+        let cols = self.info.width / CHAR_W;
+        let rows = self.info.height / CHAR_H;
+        let start = read_tsc();
+        for frame in 0..frames as usize {
+            for row in 0..rows {
+                for col in 0..cols {
+                    let byte = b'!' + ((frame + row + col) % 94) as u8;
+                    self.draw_char(
+                        col * CHAR_W,
+                        row * CHAR_H,
+                        byte,
+                        Color::rgb(220, 220, 220),
+                        Some(Color::rgb(16, 16, 24)),
+                    );
+                }
+            }
+        }
+        let cycles = read_tsc().wrapping_sub(start) / u64::from(frames);
+        self.glyphs.set_enabled(was_enabled);
+        self.clip = clip;
+        cycles
+    }
+
+    /// Copies an opaque glyph cell from the glyph cache row by row. Returns false (and draws
+    /// nothing) when the cell is partly clipped or off-screen, leaving it to the per-pixel path.
+    fn blit_cached_char(&mut self, x: usize, y: usize, byte: u8, fg: Color, bg: Color) -> bool {
+        let bytes_per_pixel = self.info.bytes_per_pixel;
+        if !self.glyphs.enabled()
+            || usize::from(byte) >= glyph::GLYPH_COUNT
+            || !(1..=4).contains(&bytes_per_pixel)
+            || x.saturating_add(CHAR_W) > self.info.width
+            || y.saturating_add(CHAR_H) > self.info.height
+        {
+            return false;
+        }
+        if let Some(clip) = self.clip
+            && (x < clip.x
+                || y < clip.y
+                || x + CHAR_W > clip.x.saturating_add(clip.w)
+                || y + CHAR_H > clip.y.saturating_add(clip.h))
+        {
+            return false;
+        }
+        let row_bytes = CHAR_W * bytes_per_pixel;
+        let first = (y * self.info.stride + x) * bytes_per_pixel;
+        let end = first + (CHAR_H - 1) * self.info.stride * bytes_per_pixel + row_bytes;
+        let target_len = match self.backbuffer.as_ref() {
+            Some(backbuffer) => backbuffer.len(),
+            None => self.buffer_len,
+        };
+        if end > target_len {
+            return false;
+        }
+
+        let mut fg_pixel = [0u8; 4];
+        let mut bg_pixel = [0u8; 4];
+        Self::encode_pixel(
+            self.info.pixel_format,
+            bytes_per_pixel,
+            &mut fg_pixel[..bytes_per_pixel],
+            fg,
+        );
+        Self::encode_pixel(
+            self.info.pixel_format,
+            bytes_per_pixel,
+            &mut bg_pixel[..bytes_per_pixel],
+            bg,
+        );
+        let cell = self.glyphs.cell(byte, fg_pixel, bg_pixel, bytes_per_pixel);
+        let row_stride = self.info.stride * bytes_per_pixel;
+        for (row, source) in cell.chunks_exact(row_bytes).enumerate() {
+            let offset = first + row * row_stride;
+            if let Some(backbuffer) = self.backbuffer.as_mut() {
+                backbuffer[offset..offset + row_bytes].copy_from_slice(source);
+            } else {
+                // SAFETY: framebuffer pointer/length come from bootloader and remain valid for
+                // kernel life; the whole cell was bounds-checked against `buffer_len` above.
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        source.as_ptr(),
+                        self.buffer_ptr.add(offset),
+                        row_bytes,
+                    );
+                }
+            }
+        }
+        true
+    }
+
     fn write_pixel(&mut self, x: usize, y: usize, color: Color) {
         if x >= self.info.width || y >= self.info.height || self.info.bytes_per_pixel == 0 {
             return;
@@ -1970,6 +3059,7 @@ impl GfxState {
         pixel: &mut [u8],
         color: Color,
     ) {
+        let color = color::apply(color);
         match pixel_format {
             PixelFormat::Rgb => {
                 pixel[0] = color.r;
@@ -2050,6 +3140,18 @@ pub fn init(boot_info: &mut BootInfo) -> GfxInitReport {
     unsafe {
         *GFX_STATE.0.get() = Some(state);
     }
+    input_bus::subscribe(
+        Consumer::Desktop,
+        Subscription {
+            handler: desktop_input,
+            kinds: input_bus::INPUT_KEYS | input_bus::INPUT_MOUSE,
+            priority: input_bus::PRIORITY_DESKTOP,
+            capture: false,
+            escape_releases: false,
+            observe_captured: true,
+            on_release: None,
+        },
+    );
 
     GfxInitReport {
         backend: "uefi-gop",
@@ -2064,7 +3166,77 @@ pub fn init(boot_info: &mut BootInfo) -> GfxInitReport {
 }
 
 pub fn poll() {
-    let _ = with_state_mut(|state| state.process_events());
+    let _ = with_state_mut(|state| {
+        state.expire_app_close_request(time::ticks());
+        state.process_events();
+    });
+}
+
+/// Input bus handler: arrows drive the settings window or tile the focused window and the mouse
+/// works the desktop. Captured mouse input still moves the pointer.
+fn desktop_input(event: InputEvent, delivery: Delivery) -> bool {
+    match event {
+        InputEvent::Key(event) => {
+            if delivery == Delivery::Captured || !event.pressed {
+                return false;
+            }
+            let handled = settings_key(event.code)
+                || tile_shortcut(event.code).is_some_and(|mode| {
+                    let _ = tile_focused(mode);
+                    true
+                });
+            if handled {
+                keyboard::record_delivery(keyboard::InputSink::Gfx, event.tick);
+            }
+            handled
+        }
+        // Text, Tab included, goes through the shell line editor, which forwards Tab here.
+        InputEvent::Byte(_) => false,
+        InputEvent::Mouse(event) => with_state_mut(|state| {
+            state.mouse_events = state.mouse_events.saturating_add(1);
+            state.handle_mouse(event, delivery == Delivery::Captured);
+            true
+        })
+        .unwrap_or(false),
+    }
+}
+
+/// Arrows select and step settings while the settings window is focused.
+fn settings_key(code: KeyCode) -> bool {
+    match code {
+        KeyCode::ArrowUp => settings_select_step(-1),
+        KeyCode::ArrowDown => settings_select_step(1),
+        KeyCode::ArrowLeft => settings_adjust(-1),
+        KeyCode::ArrowRight => settings_adjust(1),
+        KeyCode::Byte(_) => false,
+    }
+}
+
+fn tile_shortcut(code: KeyCode) -> Option<TileMode> {
+    match code {
+        KeyCode::ArrowLeft => Some(TileMode::Left),
+        KeyCode::ArrowRight => Some(TileMode::Right),
+        KeyCode::ArrowUp => Some(TileMode::Max),
+        KeyCode::ArrowDown => Some(TileMode::Restore),
+        KeyCode::Byte(_) => None,
+    }
+}
+
+/// A zeroed backbuffer of `len` bytes; `None` when empty, over the cap or out of memory.
+fn alloc_backbuffer(len: usize) -> Option<Vec<u8>> {
+    if len == 0 || len > MAX_BACKBUFFER_BYTES {
+        return None;
+    }
+    let mut backbuffer = Vec::new();
+    backbuffer.try_reserve_exact(len).is_ok().then(|| {
+        backbuffer.resize(len, 0);
+        backbuffer
+    })
+}
+
+/// `None` without a framebuffer.
+pub fn restart() -> Option<GfxRestartReport> {
+    with_state_mut(|state| state.restart())
 }
 
 pub fn try_enable_backbuffer() -> bool {
@@ -2078,12 +3250,36 @@ pub fn on_input_byte(byte: u8) {
 pub fn set_file_manager_text(text: &str) {
     let _ = with_state_mut(|state| {
         state.set_window_text(FILE_MANAGER_WINDOW_INDEX, text);
+        state.clear_file_manager_list();
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+    });
+}
+
+/// Like `set_file_manager_text`, but text rows `first_row..first_row + entries` become
+/// selectable list rows and the toolbar's progress bar shows `used` of `capacity`.
+pub fn set_file_manager_list(
+    text: &str,
+    first_row: usize,
+    entries: usize,
+    used: usize,
+    capacity: usize,
+) {
+    let _ = with_state_mut(|state| {
+        state.set_file_manager_list(text, first_row, entries, used, capacity);
         if state.damage_len > 0 {
             state.flush_damage();
         }
     });
 }
 
+/// Next file manager toolbar click; the shell runs the matching `fm` command.
+pub fn pop_file_manager_action() -> Option<FileManagerAction> {
+    with_state_mut(|state| state.file_manager.pop_action()).flatten()
+}
+
+#[cfg(feature = "doom")]
 pub fn set_doom_window_text(text: &str) {
     let _ = with_state_mut(|state| {
         state.open_doom_window();
@@ -2094,26 +3290,34 @@ pub fn set_doom_window_text(text: &str) {
     });
 }
 
-pub fn set_file_manager_doom_overlay(text: &str, width: usize, height: usize, pixels: &[u32]) {
+#[cfg(feature = "doom")]
+pub fn set_file_manager_doom_overlay(
+    text: &str,
+    width: usize,
+    height: usize,
+    source: DoomViewSource<'_>,
+) {
     let _ = with_state_mut(|state| {
         state.open_doom_window();
         state.set_window_text(DOOM_WINDOW_INDEX, text);
-        state.set_doom_view(width, height, pixels);
+        state.set_doom_view(width, height, source);
         if state.damage_len > 0 {
             state.flush_damage();
         }
     });
 }
 
-pub fn set_file_manager_doom_view(width: usize, height: usize, pixels: &[u32]) {
+#[cfg(feature = "doom")]
+pub fn set_file_manager_doom_view(width: usize, height: usize, source: DoomViewSource<'_>) {
     let _ = with_state_mut(|state| {
-        state.set_doom_view(width, height, pixels);
+        state.set_doom_view(width, height, source);
         if state.damage_len > 0 {
             state.flush_damage();
         }
     });
 }
 
+#[cfg(feature = "doom")]
 pub fn set_file_manager_doom_filter(filter: DoomViewFilter) -> bool {
     with_state_mut(|state| {
         let changed = state.set_doom_view_filter(filter);
@@ -2125,10 +3329,22 @@ pub fn set_file_manager_doom_filter(filter: DoomViewFilter) -> bool {
     .unwrap_or(false)
 }
 
+#[cfg(feature = "doom")]
 pub fn file_manager_doom_filter() -> DoomViewFilter {
     with_state_mut(|state| state.doom_view_filter()).unwrap_or(DoomViewFilter::Bilinear)
 }
 
+/// Returns `None` when no framebuffer is active (serial-only boots).
+#[cfg(feature = "doom")]
+pub fn doom_window_state() -> Option<DoomWindowState> {
+    with_state_mut(|state| DoomWindowState {
+        open: state.doom_window_open,
+        minimized: state.windows[DOOM_WINDOW_INDEX].minimized,
+        focused: state.focused_window == DOOM_WINDOW_INDEX,
+    })
+}
+
+#[cfg(feature = "doom")]
 pub fn clear_file_manager_doom_view() {
     let _ = with_state_mut(|state| {
         state.clear_doom_view();
@@ -2138,6 +3354,163 @@ pub fn clear_file_manager_doom_view() {
     });
 }
 
+/// Opens and focuses the settings window; false when it was already open.
+pub fn open_settings_window() -> bool {
+    with_state_mut(|state| {
+        let opened = state.open_settings_window();
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        opened
+    })
+    .unwrap_or(false)
+}
+
+pub fn close_settings_window() {
+    let _ = with_state_mut(|state| {
+        state.close_settings_window();
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+    });
+}
+
+/// Opens the app window for `pid` (SURFACE_OPEN). `NoDevice` without a framebuffer.
+pub fn open_app_window(pid: u32) -> Result<(), KernelError> {
+    with_state_mut(|state| {
+        let result = state.open_app_window(pid);
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        result
+    })
+    .unwrap_or(Err(KernelError::NoDevice))
+}
+
+/// Shows `pixels`, the task memory described by `mapping`, in the app window `pid` owns.
+pub fn attach_app_surface(
+    pid: u32,
+    mapping: SurfaceMapping,
+    pixels: &[u32],
+) -> Result<(), KernelError> {
+    with_state_mut(|state| {
+        let result = state.attach_app_surface(pid, mapping, pixels);
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        result
+    })
+    .unwrap_or(Err(KernelError::NoDevice))
+}
+
+/// The surface `pid` attached, so the syscall can re-check and read it for a damage call.
+pub fn app_surface_mapping(pid: u32) -> Result<SurfaceMapping, KernelError> {
+    with_state_mut(|state| state.app_surface_mapping(pid)).unwrap_or(Err(KernelError::NoDevice))
+}
+
+/// Recopies one rect of the attached surface and redraws only that rect; returns the pixels
+/// copied after clipping.
+pub fn damage_app_surface(
+    pid: u32,
+    area: (usize, usize, usize, usize),
+    pixels: &[u32],
+) -> Result<usize, KernelError> {
+    with_state_mut(|state| {
+        let result = state.damage_app_surface(pid, area, pixels);
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        result
+    })
+    .unwrap_or(Err(KernelError::NoDevice))
+}
+
+pub fn detach_app_surface(pid: u32) -> Result<(), KernelError> {
+    with_state_mut(|state| {
+        let result = state.detach_app_surface(pid);
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        result
+    })
+    .unwrap_or(Err(KernelError::NoDevice))
+}
+
+/// Closes the app window whoever owns it (`ui close app`); returns the owner it had.
+pub fn close_app_window() -> Option<u32> {
+    with_state_mut(|state| {
+        let owner = state.close_app_window();
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        owner
+    })
+    .flatten()
+}
+
+/// Closes the app window if `pid` owns it; called when the task exits or is killed.
+pub fn release_app_window(pid: u32) {
+    let _ = with_state_mut(|state| {
+        if state.app_window_status().owner == Some(pid) {
+            let _ = state.close_app_window();
+            if state.damage_len > 0 {
+                state.flush_damage();
+            }
+        }
+    });
+}
+
+pub fn app_window_status() -> Option<AppWindowStatus> {
+    with_state_mut(|state| state.app_window_status())
+}
+
+/// Settings window text; rows `first_row..first_row + entries` are the adjustable settings.
+pub fn set_settings_list(text: &str, first_row: usize, entries: usize) {
+    let _ = with_state_mut(|state| {
+        state.set_settings_list(text, first_row, entries);
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+    });
+}
+
+/// Next settings change from a click or arrow key; the shell applies and saves it.
+pub fn pop_settings_action() -> Option<SettingsAction> {
+    with_state_mut(|state| state.settings.pop_action()).flatten()
+}
+
+/// Up/down arrows while the settings window is focused; false when it is not.
+pub fn settings_select_step(step: isize) -> bool {
+    with_state_mut(|state| {
+        let handled = state.settings_select_step(step);
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        handled
+    })
+    .unwrap_or(false)
+}
+
+/// Left/right arrows while the settings window is focused; false when it is not.
+pub fn settings_adjust(delta: i8) -> bool {
+    with_state_mut(|state| state.settings_adjust(delta)).unwrap_or(false)
+}
+
+pub fn set_mouse_speed(percent: u16) -> bool {
+    let percent = percent.clamp(MIN_MOUSE_SPEED, MAX_MOUSE_SPEED);
+    with_state_mut(|state| {
+        let changed = state.mouse_speed != percent;
+        state.mouse_speed = percent;
+        state.mouse_remainder = (0, 0);
+        changed
+    })
+    .unwrap_or(false)
+}
+
+pub fn mouse_speed() -> u16 {
+    with_state_mut(|state| state.mouse_speed).unwrap_or(DEFAULT_MOUSE_SPEED)
+}
+
 pub fn focus_next() {
     let _ = with_state_mut(|state| {
         state.focus_next_internal();
@@ -2158,26 +3531,229 @@ pub fn toggle_focused_minimize() {
     });
 }
 
+/// Snaps the focused window to a screen half or toggles maximize; the text grid follows.
+pub fn tile_focused(mode: TileMode) -> bool {
+    with_state_mut(|state| {
+        let index = state.focused_window;
+        let changed = state.tile_window(index, mode);
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        changed
+    })
+    .unwrap_or(false)
+}
+
 pub fn redraw() {
     let _ = with_state_mut(|state| state.redraw());
 }
 
+/// Opens the doom window focused and maximized (`doom autostart`'s fullscreen step).
+#[cfg(feature = "doom")]
+pub fn maximize_doom_window() -> bool {
+    with_state_mut(|state| {
+        let changed = state.maximize_doom_window();
+        if state.damage_len > 0 {
+            state.flush_damage();
+        }
+        changed
+    })
+    .unwrap_or(false)
+}
+
+/// Kiosk mode hides the shell mirror and file manager and keeps doom maximized; turning it
+/// off restores the doom window's previous geometry.
+/// Prints the text grid of `window` as one `ui: dump <window> <row>|<text>` line per row, so
+/// headless tests can assert on what is displayed. Trailing blank rows are left out.
+pub fn dump_window_to_serial(window: WindowId) -> Status {
+    let snapshot = with_state_mut(|state| {
+        let index = window.index();
+        let source = &state.windows[index];
+        WindowTextSnapshot {
+            title: source.title,
+            cols: source.cols,
+            rows: source.rows,
+            cursor_row: source.cursor_row,
+            cursor_col: source.cursor_col,
+            visible: state.window_visible(index),
+            minimized: source.minimized,
+            lines: source.lines,
+            line_len: source.line_len,
+            toolbar: state.toolbar_snapshot(index),
+        }
+    });
+    let Some(snapshot) = snapshot else {
+        serial::write_line("ui dump: framebuffer unavailable");
+        return Status::Failed;
+    };
+
+    serial::write_fmt(format_args!(
+        "ui: dump window={} title=\"{}\" cols={} rows={} cursor={},{} visible={} minimized={}\n",
+        window.as_str(),
+        snapshot.title,
+        snapshot.cols,
+        snapshot.rows,
+        snapshot.cursor_row,
+        snapshot.cursor_col,
+        snapshot.visible,
+        snapshot.minimized
+    ));
+    let used_rows = (0..snapshot.rows)
+        .rev()
+        .find(|row| snapshot.line_len[*row] > 0)
+        .map_or(0, |row| row + 1);
+    for row in 0..used_rows {
+        let len = snapshot.line_len[row].min(snapshot.cols);
+        let mut text = [b' '; WINDOW_MAX_COLS];
+        for (cell, byte) in text.iter_mut().zip(&snapshot.lines[row][..len]) {
+            *cell = match *byte {
+                0 => b' ',
+                0x20..=0x7e => *byte,
+                GLYPH_PLACEHOLDER => b'?',
+                _ => b'.',
+            };
+        }
+        let text = core::str::from_utf8(&text[..len]).unwrap_or("");
+        serial::write_fmt(format_args!(
+            "ui: dump {} {row:02}|{}\n",
+            window.as_str(),
+            text.trim_end()
+        ));
+    }
+    if let Some(toolbar) = &snapshot.toolbar {
+        toolbar.log();
+    }
+    serial::write_fmt(format_args!(
+        "ui: dump end window={} lines={used_rows}\n",
+        window.as_str()
+    ));
+    Status::Ok
+}
+
+/// `ui mirror <level>`; false when the desktop is not up.
+pub fn set_mirror_level(level: Level) -> bool {
+    with_state_mut(|state| state.set_mirror_level(level)).is_some()
+}
+
+/// Shell mirror level, log-ring position and bytes skipped, for `ui mirror`.
+pub fn mirror_status() -> Option<(Level, u64, u64)> {
+    with_state_mut(|state| {
+        (
+            state.mirror_level,
+            state.mirror_cursor,
+            state.stdout_skipped,
+        )
+    })
+}
+
+pub fn set_kiosk(enabled: bool) -> bool {
+    // Kiosk keeps the keyboard in the game; ESC opens doom's menu. Serial ESC still releases.
+    input_bus::set_escape_releases(Consumer::Doom, !enabled);
+    with_state_mut(|state| state.set_kiosk(enabled)).unwrap_or(false)
+}
+
+pub fn kiosk() -> bool {
+    with_state_mut(|state| state.kiosk).unwrap_or(false)
+}
+
+pub fn set_cursor_theme(theme: CursorTheme) -> bool {
+    with_state_mut(|state| state.set_cursor_theme(theme)).unwrap_or(false)
+}
+
+/// `ui brightness`, `ui contrast` and `ui night`; repaints the desktop so every pixel goes
+/// through the new transform. `false` when `adjust` is already in force.
+pub fn set_color_adjust(adjust: ColorAdjust) -> bool {
+    if !color::set_adjust(adjust) {
+        return false;
+    }
+    with_state_mut(|state| state.redraw());
+    true
+}
+
+pub fn color_adjust() -> ColorAdjust {
+    color::adjust()
+}
+
+/// `ui rotate <deg>` and the `gfx.rotate` setting; `Ok(false)` when already turned that way.
+pub fn set_rotation(rotation: Rotation) -> Result<bool, RotateError> {
+    with_state_mut(|state| state.set_rotation(rotation)).unwrap_or(Err(RotateError::NoFramebuffer))
+}
+
+pub fn rotation() -> Rotation {
+    with_state_mut(|state| state.output.rotation()).unwrap_or(Rotation::Deg0)
+}
+
+pub fn set_damage_debug(enabled: bool) -> bool {
+    with_state_mut(|state| state.set_damage_debug(enabled)).unwrap_or(false)
+}
+
+/// Checksums for `ui checksum`; `None` without a framebuffer.
+pub fn checksum() -> Option<ChecksumReport> {
+    with_state_mut(|state| state.checksum())
+}
+
+pub fn bench(frames: u32) -> Option<GfxBench> {
+    with_state_mut(|state| {
+        let frames = frames.max(1);
+        let start = read_tsc();
+        for _ in 0..frames {
+            state.redraw();
+        }
+        let redraw_cycles = read_tsc().wrapping_sub(start) / u64::from(frames);
+
+        let full = Rect::new(0, 0, state.info.width, state.info.height);
+        let present_cycles = if state.backbuffer.is_some() {
+            state.hide_cursor();
+            let start = read_tsc();
+            for _ in 0..frames {
+                state.present_rect(full);
+            }
+            let cycles = read_tsc().wrapping_sub(start) / u64::from(frames);
+            state.show_cursor();
+            cycles
+        } else {
+            0
+        };
+        state.hide_cursor();
+        let text_cycles = state.bench_text(frames, true);
+        let text_uncached_cycles = state.bench_text(frames, false);
+        state.redraw();
+        GfxBench {
+            frames,
+            redraw_cycles,
+            present_cycles,
+            bytes_per_frame: state.info.height * state.info.stride * state.info.bytes_per_pixel,
+            text_cycles,
+            text_uncached_cycles,
+        }
+    })
+}
+
+fn read_tsc() -> u64 {
+    // SAFETY: RDTSC is available on every x86_64 CPU and only reads the timestamp counter.
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
 pub fn log_info() {
     let status = with_state_mut(|state| state.status());
     match status {
         Some(status) => {
             serial::write_fmt(format_args!(
-                "ui: backend=uefi-gop ready=true {}x{} stride={} bpp={} fmt={} focused={} events={} dropped={} stdout_events={} stdout_dropped={} frames={} full_redraws={} partial_redraws={} present_full={} present_partial={} damage_dropped={} damage_coalesced={} double_buffer={} mouse=({}, {}) mouse_events={} mouse_focus_clicks={} drag_steps={} resize_steps={} minimize_toggles={} drag_active={} resize_active={} focused_minimized={} minimized_windows={}\n",
+                "ui: backend=uefi-gop ready=true {}x{} stride={} bpp={} fmt={} rotate={} night={} brightness={} contrast={} focused={} events={} dropped={} stdout_events={} stdout_skipped={} frames={} full_redraws={} partial_redraws={} present_full={} present_partial={} damage_dropped={} damage_coalesced={} double_buffer={} mouse=({}, {}) mouse_events={} mouse_speed={} mouse_focus_clicks={} drag_steps={} resize_steps={} minimize_toggles={} drag_active={} resize_active={} focused_minimized={} minimized_windows={} tile_ops={} focused_tile={} cursor={} cursor_theme={} cursor_moves={} cursor_shape_changes={} damage_debug={} damage_debug_flushes={} damage_debug_rects={} glyph_hits={} glyph_renders={} button_clicks={} button_drops={} close_requests={} closes={} close_vetoes={} close_timeouts={} kiosk={}\n",
                 status.width,
                 status.height,
                 status.stride,
                 status.bytes_per_pixel,
                 status.pixel_format,
+                status.rotation.degrees(),
+                if status.color.night { "on" } else { "off" },
+                status.color.brightness,
+                status.color.contrast,
                 status.focused_window,
                 status.events,
                 status.dropped,
                 status.stdout_events,
-                status.stdout_dropped,
+                status.stdout_skipped,
                 status.frames,
                 status.full_redraws,
                 status.partial_redraws,
@@ -2189,6 +3765,7 @@ pub fn log_info() {
                 status.mouse_x,
                 status.mouse_y,
                 status.mouse_events,
+                status.mouse_speed,
                 status.mouse_click_focus,
                 status.mouse_drag_steps,
                 status.mouse_resize_steps,
@@ -2196,13 +3773,73 @@ pub fn log_info() {
                 status.drag_active,
                 status.resize_active,
                 status.focused_minimized,
-                status.minimized_windows
+                status.minimized_windows,
+                status.tile_ops,
+                status.focused_tile,
+                status.cursor_shape,
+                status.cursor_theme,
+                status.cursor_moves,
+                status.cursor_shape_changes,
+                if status.damage_debug { "on" } else { "off" },
+                status.damage_debug_flushes,
+                status.damage_debug_rects,
+                status.glyph_hits,
+                status.glyph_renders,
+                status.button_clicks,
+                status.button_drops,
+                status.lifecycle.requests,
+                status.lifecycle.closed,
+                status.lifecycle.vetoed,
+                status.lifecycle.timeouts,
+                if status.kiosk { "on" } else { "off" }
             ));
         }
         None => serial::write_line("ui: backend=none ready=false"),
     }
 }
 
+/// `ui app`: who owns the app window, the attached surface and the SYS_SURFACE counters.
+pub fn log_app() {
+    let Some(status) = app_window_status() else {
+        serial::write_line("ui: app backend=none");
+        return;
+    };
+    match status.owner {
+        Some(pid) => serial::write_fmt(format_args!("ui: app owner={pid}")),
+        None => serial::write_str("ui: app owner=none"),
+    }
+    match status.mapping {
+        Some(mapping) => serial::write_fmt(format_args!(
+            " surface={}x{} stride={} ptr={:#x}",
+            mapping.width, mapping.height, mapping.stride, mapping.ptr
+        )),
+        None => serial::write_str(" surface=none"),
+    }
+    serial::write_fmt(format_args!(
+        " attaches={} damage_calls={} damage_px={} detaches={} closes={}\n",
+        status.attaches, status.damage_calls, status.damage_pixels, status.detaches, status.closes
+    ));
+}
+
+/// `sysinfo` line: display mode and window state, without the counters `ui` prints.
+pub fn log_sysinfo() {
+    match with_state_mut(|state| state.status()) {
+        Some(status) => serial::write_fmt(format_args!(
+            "sysinfo: gfx ready=true mode={}x{} bpp={} fmt={} double_buffer={} focused={} minimized={} kiosk={} frames={}\n",
+            status.width,
+            status.height,
+            status.bytes_per_pixel,
+            status.pixel_format,
+            status.double_buffer,
+            status.focused_window,
+            status.minimized_windows,
+            if status.kiosk { "on" } else { "off" },
+            status.frames
+        )),
+        None => serial::write_line("sysinfo: gfx ready=false"),
+    }
+}
+
 fn with_state_mut<T>(f: impl FnOnce(&mut GfxState) -> T) -> Option<T> {
     // SAFETY: ArrOSt kernel main loop is single-threaded in current milestones.
     let slot = unsafe { &mut *GFX_STATE.0.get() };
@@ -2219,6 +3856,16 @@ fn pixel_format_name(format: PixelFormat) -> &'static str {
     }
 }
 
+/// Font byte for `ch`: printable ASCII as itself, everything else the placeholder, one cell
+/// per character either way.
+fn glyph_byte(ch: char) -> u8 {
+    if ch == ' ' || ch.is_ascii_graphic() {
+        ch as u8
+    } else {
+        GLYPH_PLACEHOLDER
+    }
+}
+
 fn glyph_rows(byte: u8) -> [u8; 7] {
     let mapped = if byte.is_ascii_lowercase() {
         byte - b'a' + b'A'
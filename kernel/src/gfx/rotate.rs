@@ -0,0 +1,139 @@
+// kernel/src/gfx/rotate.rs: output rotation for portrait panels and odd GOP modes; the compositor
+// draws an upright logical scene into the backbuffer and present maps it onto the framebuffer.
+use super::Rect;
+use bootloader_api::info::FrameBufferInfo;
+
+/// Clockwise turn applied between the logical scene and the physical framebuffer.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    pub const fn degrees(self) -> u32 {
+        match self {
+            Self::Deg0 => 0,
+            Self::Deg90 => 90,
+            Self::Deg180 => 180,
+            Self::Deg270 => 270,
+        }
+    }
+
+    pub const fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees {
+            0 => Some(Self::Deg0),
+            90 => Some(Self::Deg90),
+            180 => Some(Self::Deg180),
+            270 => Some(Self::Deg270),
+            _ => None,
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        Self::from_degrees(text.parse().ok()?)
+    }
+
+    /// Width and height trade places.
+    const fn transposed(self) -> bool {
+        matches!(self, Self::Deg90 | Self::Deg270)
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum RotateError {
+    NoFramebuffer,
+    /// The rotated scene needs a backbuffer and none could be allocated.
+    NoBackbuffer,
+}
+
+impl RotateError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::NoFramebuffer => "no_framebuffer",
+            Self::NoBackbuffer => "no_backbuffer",
+        }
+    }
+}
+
+/// The physical framebuffer and how the logical scene is turned onto it.
+#[derive(Clone, Copy)]
+pub(super) struct Output {
+    physical: FrameBufferInfo,
+    rotation: Rotation,
+}
+
+impl Output {
+    pub(super) const fn new(physical: FrameBufferInfo) -> Self {
+        Self {
+            physical,
+            rotation: Rotation::Deg0,
+        }
+    }
+
+    pub(super) const fn physical(&self) -> &FrameBufferInfo {
+        &self.physical
+    }
+
+    pub(super) const fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    pub(super) fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    /// Layout the compositor draws into: the framebuffer's own at 0 degrees, else a packed
+    /// backbuffer of the turned size.
+    pub(super) fn logical_info(&self) -> FrameBufferInfo {
+        if self.rotation == Rotation::Deg0 {
+            return self.physical;
+        }
+        let (width, height) = self.logical_size();
+        FrameBufferInfo {
+            byte_len: width * height * self.physical.bytes_per_pixel,
+            width,
+            height,
+            stride: width,
+            ..self.physical
+        }
+    }
+
+    /// Width and height of the logical scene.
+    pub(super) const fn logical_size(&self) -> (usize, usize) {
+        if self.rotation.transposed() {
+            (self.physical.height, self.physical.width)
+        } else {
+            (self.physical.width, self.physical.height)
+        }
+    }
+
+    /// Physical pixel showing logical pixel `(x, y)`.
+    pub(super) fn map_point(&self, x: usize, y: usize) -> (usize, usize) {
+        let (width, height) = (self.physical.width, self.physical.height);
+        match self.rotation {
+            Rotation::Deg0 => (x, y),
+            Rotation::Deg90 => (width - 1 - y, x),
+            Rotation::Deg180 => (width - 1 - x, height - 1 - y),
+            Rotation::Deg270 => (y, height - 1 - x),
+        }
+    }
+
+    /// Physical rect covered by a logical `rect` that lies inside the logical scene.
+    pub(super) fn map_rect(&self, rect: Rect) -> Rect {
+        let (width, height) = (self.physical.width, self.physical.height);
+        match self.rotation {
+            Rotation::Deg0 => rect,
+            Rotation::Deg90 => Rect::new(width - rect.y - rect.h, rect.x, rect.h, rect.w),
+            Rotation::Deg180 => Rect::new(
+                width - rect.x - rect.w,
+                height - rect.y - rect.h,
+                rect.w,
+                rect.h,
+            ),
+            Rotation::Deg270 => Rect::new(rect.y, height - rect.x - rect.w, rect.h, rect.w),
+        }
+    }
+}
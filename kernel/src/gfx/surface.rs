@@ -0,0 +1,293 @@
+// kernel/src/gfx/surface.rs: the app window, whose body shows a pixel surface a user task
+// attached through SYS_SURFACE. Pixels are copied out of the task on attach and on each damage
+// call, so the compositor never reads task memory by itself; detach and close drop the mapping.
+use super::lifecycle::AppCloseRequest;
+use super::{
+    APP_WINDOW_INDEX, DragState, GfxState, MIN_WINDOW_HEIGHT, MIN_WINDOW_WIDTH, Rect, ResizeState,
+    SHELL_WINDOW_INDEX, TITLE_BAR_HEIGHT, UiWindow, WINDOW_PADDING, color_from_rgb24,
+};
+use crate::error::KernelError;
+use alloc::vec::Vec;
+use arrostd::syscall::{SURFACE_MAX_HEIGHT, SURFACE_MAX_WIDTH};
+
+/// Where an attached surface lives in its task's memory, as given to SURFACE_ATTACH.
+#[derive(Clone, Copy)]
+pub struct SurfaceMapping {
+    pub ptr: u64,
+    pub width: usize,
+    pub height: usize,
+    /// Pixels from one row to the next.
+    pub stride: usize,
+}
+
+impl SurfaceMapping {
+    /// Pixels from the first one to the end of the last row; what the task must have mapped.
+    pub const fn span(&self) -> usize {
+        self.height
+            .saturating_sub(1)
+            .saturating_mul(self.stride)
+            .saturating_add(self.width)
+    }
+
+    fn valid(&self) -> bool {
+        (1..=SURFACE_MAX_WIDTH as usize).contains(&self.width)
+            && (1..=SURFACE_MAX_HEIGHT as usize).contains(&self.height)
+            && self.stride >= self.width
+    }
+}
+
+pub(super) struct AppSurface {
+    owner: Option<u32>,
+    mapping: Option<SurfaceMapping>,
+    /// Kernel copy of the surface, `width * height` pixels in packed rows.
+    pixels: Vec<u32>,
+    pub(super) close_request: AppCloseRequest,
+    attaches: u64,
+    damage_calls: u64,
+    damage_pixels: u64,
+    detaches: u64,
+    closes: u64,
+}
+
+impl AppSurface {
+    pub(super) const fn new() -> Self {
+        Self {
+            owner: None,
+            mapping: None,
+            pixels: Vec::new(),
+            close_request: AppCloseRequest::None,
+            attaches: 0,
+            damage_calls: 0,
+            damage_pixels: 0,
+            detaches: 0,
+            closes: 0,
+        }
+    }
+
+    pub(super) const fn open(&self) -> bool {
+        self.owner.is_some()
+    }
+
+    pub(super) const fn attached(&self) -> bool {
+        self.mapping.is_some()
+    }
+
+    fn drop_mapping(&mut self) -> bool {
+        self.pixels = Vec::new();
+        self.mapping.take().is_some()
+    }
+}
+
+/// App window state for `ui app`.
+#[derive(Clone, Copy)]
+pub struct AppWindowStatus {
+    pub owner: Option<u32>,
+    pub mapping: Option<SurfaceMapping>,
+    pub attaches: u64,
+    pub damage_calls: u64,
+    pub damage_pixels: u64,
+    pub detaches: u64,
+    pub closes: u64,
+}
+
+impl GfxState {
+    pub(super) fn check_app_owner(&self, pid: u32) -> Result<(), KernelError> {
+        match self.app.owner {
+            Some(owner) if owner == pid => Ok(()),
+            Some(_) => Err(KernelError::PermissionDenied),
+            None => Err(KernelError::NotFound),
+        }
+    }
+
+    /// Opens the app window for `pid`, or refocuses it when `pid` already owns it.
+    pub(super) fn open_app_window(&mut self, pid: u32) -> Result<(), KernelError> {
+        if self.app.owner.is_some_and(|owner| owner != pid) {
+            return Err(KernelError::PermissionDenied);
+        }
+        let was_open = self.app.open();
+        self.app.owner = Some(pid);
+        if self.windows[APP_WINDOW_INDEX].minimized {
+            self.toggle_minimize(APP_WINDOW_INDEX);
+        }
+        let _ = self.set_focus(APP_WINDOW_INDEX);
+        if !was_open {
+            self.windows[APP_WINDOW_INDEX].clear_text();
+            self.windows[APP_WINDOW_INDEX].append_text("No surface attached.\n");
+            self.invalidate_window(APP_WINDOW_INDEX);
+        }
+        Ok(())
+    }
+
+    /// Copies the whole surface and shows it, sizing the window to fit it.
+    pub(super) fn attach_app_surface(
+        &mut self,
+        pid: u32,
+        mapping: SurfaceMapping,
+        pixels: &[u32],
+    ) -> Result<(), KernelError> {
+        self.check_app_owner(pid)?;
+        if !mapping.valid() || pixels.len() < mapping.span() {
+            return Err(KernelError::InvalidArgument);
+        }
+        let mut copy = Vec::new();
+        copy.try_reserve_exact(mapping.width * mapping.height)
+            .map_err(|_| KernelError::OutOfMemory)?;
+        for row in pixels.chunks(mapping.stride).take(mapping.height) {
+            copy.extend_from_slice(&row[..mapping.width]);
+        }
+        self.app.pixels = copy;
+        self.app.mapping = Some(mapping);
+        self.app.attaches = self.app.attaches.saturating_add(1);
+
+        let previous = self.window_rect(APP_WINDOW_INDEX);
+        let window = &mut self.windows[APP_WINDOW_INDEX];
+        window.w = (mapping.width + WINDOW_PADDING * 2).max(MIN_WINDOW_WIDTH);
+        window.h = (mapping.height + TITLE_BAR_HEIGHT + WINDOW_PADDING * 2).max(MIN_WINDOW_HEIGHT);
+        window.tile = None;
+        window.clear_text();
+        self.fit_window(APP_WINDOW_INDEX);
+        self.invalidate_rect(previous);
+        self.invalidate_window(APP_WINDOW_INDEX);
+        Ok(())
+    }
+
+    pub(super) fn app_surface_mapping(&self, pid: u32) -> Result<SurfaceMapping, KernelError> {
+        self.check_app_owner(pid)?;
+        self.app.mapping.ok_or(KernelError::NotFound)
+    }
+
+    /// Recopies `x, y, width, height` (surface pixels, clipped to the surface) and damages just
+    /// that part of the window. Returns the pixels copied.
+    pub(super) fn damage_app_surface(
+        &mut self,
+        pid: u32,
+        area: (usize, usize, usize, usize),
+        pixels: &[u32],
+    ) -> Result<usize, KernelError> {
+        let mapping = self.app_surface_mapping(pid)?;
+        if pixels.len() < mapping.span() {
+            return Err(KernelError::InvalidArgument);
+        }
+        let (x, y, width, height) = area;
+        let x1 = x.saturating_add(width).min(mapping.width);
+        let y1 = y.saturating_add(height).min(mapping.height);
+        if x >= x1 || y >= y1 {
+            return Ok(0);
+        }
+        for row in y..y1 {
+            let source = row * mapping.stride;
+            let target = row * mapping.width;
+            self.app.pixels[target + x..target + x1]
+                .copy_from_slice(&pixels[source + x..source + x1]);
+        }
+        let copied = (x1 - x) * (y1 - y);
+        self.app.damage_calls = self.app.damage_calls.saturating_add(1);
+        self.app.damage_pixels = self.app.damage_pixels.saturating_add(copied as u64);
+
+        let window = self.windows[APP_WINDOW_INDEX];
+        if self.window_visible(APP_WINDOW_INDEX) && !window.minimized {
+            let body = app_body_rect(window);
+            let rect = Rect::new(body.x + x, body.y + y, x1 - x, y1 - y);
+            if let Some(rect) = intersection(rect, body) {
+                self.invalidate_rect(rect);
+            }
+        }
+        Ok(copied)
+    }
+
+    /// Drops the surface; the window stays open and owned.
+    pub(super) fn detach_app_surface(&mut self, pid: u32) -> Result<(), KernelError> {
+        self.check_app_owner(pid)?;
+        if !self.app.drop_mapping() {
+            return Err(KernelError::NotFound);
+        }
+        self.app.detaches = self.app.detaches.saturating_add(1);
+        self.windows[APP_WINDOW_INDEX].clear_text();
+        self.windows[APP_WINDOW_INDEX].append_text("Surface detached.\n");
+        self.invalidate_window(APP_WINDOW_INDEX);
+        Ok(())
+    }
+
+    /// Hides the app window and forgets its owner and surface; returns the previous owner.
+    pub(super) fn close_app_window(&mut self) -> Option<u32> {
+        let owner = self.app.owner?;
+        let previous = self.window_rect(APP_WINDOW_INDEX);
+        let _ = self.app.drop_mapping();
+        self.app.owner = None;
+        self.app.close_request = AppCloseRequest::None;
+        self.app.closes = self.app.closes.saturating_add(1);
+        if self.focused_window == APP_WINDOW_INDEX {
+            self.focused_window = SHELL_WINDOW_INDEX;
+            self.invalidate_window_chrome(SHELL_WINDOW_INDEX);
+        }
+        if self.drag.active && self.drag.window_index == APP_WINDOW_INDEX {
+            self.drag = DragState::inactive();
+        }
+        if self.resize.active && self.resize.window_index == APP_WINDOW_INDEX {
+            self.resize = ResizeState::inactive();
+        }
+        self.invalidate_rect(previous);
+        Some(owner)
+    }
+
+    pub(super) fn app_window_status(&self) -> AppWindowStatus {
+        AppWindowStatus {
+            owner: self.app.owner,
+            mapping: self.app.mapping,
+            attaches: self.app.attaches,
+            damage_calls: self.app.damage_calls,
+            damage_pixels: self.app.damage_pixels,
+            detaches: self.app.detaches,
+            closes: self.app.closes,
+        }
+    }
+
+    /// Blits the surface 1:1 at the top left of the body; what does not fit is cut off.
+    pub(super) fn draw_app_surface(&mut self, window: UiWindow) {
+        let Some(mapping) = self.app.mapping else {
+            return;
+        };
+        let body = app_body_rect(window);
+        let shown = Rect::new(
+            body.x,
+            body.y,
+            mapping.width.min(body.w),
+            mapping.height.min(body.h),
+        );
+        let area = match self.clip {
+            Some(clip) => intersection(shown, clip),
+            None => Some(shown),
+        };
+        let Some(area) = area else {
+            return;
+        };
+        let pixels = core::mem::take(&mut self.app.pixels);
+        for y in area.y..area.y + area.h {
+            let row = (y - body.y) * mapping.width;
+            for x in area.x..area.x + area.w {
+                let source = pixels[row + (x - body.x)] & 0x00FF_FFFF;
+                self.write_pixel(x, y, color_from_rgb24(source));
+            }
+        }
+        self.app.pixels = pixels;
+    }
+}
+
+fn app_body_rect(window: UiWindow) -> Rect {
+    Rect::new(
+        window.x.saturating_add(WINDOW_PADDING),
+        window.y.saturating_add(TITLE_BAR_HEIGHT + WINDOW_PADDING),
+        window.w.saturating_sub(WINDOW_PADDING * 2),
+        window
+            .h
+            .saturating_sub(TITLE_BAR_HEIGHT + WINDOW_PADDING * 2),
+    )
+}
+
+fn intersection(a: Rect, b: Rect) -> Option<Rect> {
+    let x0 = a.x.max(b.x);
+    let y0 = a.y.max(b.y);
+    let x1 = (a.x + a.w).min(b.x + b.w);
+    let y1 = (a.y + a.h).min(b.y + b.h);
+    (x1 > x0 && y1 > y0).then(|| Rect::new(x0, y0, x1 - x0, y1 - y0))
+}
@@ -0,0 +1,646 @@
+// kernel/src/gfx/ui.rs: widget primitives drawn inside window bodies (buttons, selectable list
+// rows, progress bar) and the file manager and settings toolbars built from them.
+use super::{
+    CHAR_H, CHAR_W, Color, FILE_MANAGER_WINDOW_INDEX, GfxState, RESIZE_HANDLE_SIZE, Rect,
+    SETTINGS_WINDOW_INDEX, WINDOW_PADDING,
+};
+use crate::serial;
+
+const BUTTON_HEIGHT: usize = CHAR_H + 6;
+const BUTTON_PAD_X: usize = 3;
+const WIDGET_GAP: usize = 4;
+const MIN_PROGRESS_WIDTH: usize = 16;
+/// Space reserved below a window's text grid for a toolbar row.
+pub(super) const TOOLBAR_HEIGHT: usize = BUTTON_HEIGHT + WIDGET_GAP;
+const MAX_TOOLBAR_BUTTONS: usize = 5;
+const ACTION_CAPACITY: usize = 8;
+
+/// File manager toolbar buttons; each click runs the matching `fm` command in the shell.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FileManagerButton {
+    List,
+    Open,
+    Copy,
+    Verify,
+    Delete,
+}
+
+impl FileManagerButton {
+    const ALL: [Self; 5] = [
+        Self::List,
+        Self::Open,
+        Self::Copy,
+        Self::Verify,
+        Self::Delete,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Open => "open",
+            Self::Copy => "copy",
+            Self::Verify => "verify",
+            Self::Delete => "delete",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::List => "LIST",
+            Self::Open => "OPEN",
+            Self::Copy => "COPY",
+            Self::Verify => "VERIFY",
+            Self::Delete => "DELETE",
+        }
+    }
+
+    /// Open, copy and delete act on the selected row and are disabled without one; verify
+    /// checks every file when nothing is selected.
+    const fn needs_selection(self) -> bool {
+        matches!(self, Self::Open | Self::Copy | Self::Delete)
+    }
+}
+
+/// A toolbar click, queued until the shell's next poll.
+#[derive(Clone, Copy)]
+pub struct FileManagerAction {
+    pub button: FileManagerButton,
+    /// Index of the selected entry among the listed rows.
+    pub selected: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SettingsButton {
+    Decrease,
+    Increase,
+    Sync,
+    Close,
+}
+
+impl SettingsButton {
+    const ALL: [Self; 4] = [Self::Decrease, Self::Increase, Self::Sync, Self::Close];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Decrease => "decrease",
+            Self::Increase => "increase",
+            Self::Sync => "sync",
+            Self::Close => "close",
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Decrease => "-",
+            Self::Increase => "+",
+            Self::Sync => "SYNC",
+            Self::Close => "CLOSE",
+        }
+    }
+
+    const fn needs_selection(self) -> bool {
+        matches!(self, Self::Decrease | Self::Increase)
+    }
+}
+
+/// A settings change from a click or an arrow key, queued until the shell's next poll.
+#[derive(Clone, Copy)]
+pub enum SettingsAction {
+    /// Step the setting on list row `row` down (-1) or up (+1).
+    Adjust {
+        row: usize,
+        delta: i8,
+    },
+    Sync,
+}
+
+#[derive(Clone, Copy)]
+struct Button {
+    rect: Rect,
+    label: &'static str,
+    enabled: bool,
+}
+
+impl Button {
+    const fn width(label: &str) -> usize {
+        label.len() * CHAR_W + BUTTON_PAD_X * 2
+    }
+
+    fn hit(&self, x: usize, y: usize) -> bool {
+        self.enabled && self.rect.contains(x, y)
+    }
+}
+
+/// Selectable rows overlaid on a window's text grid: grid rows `first_row..first_row + len`
+/// are entries 0..len.
+#[derive(Clone, Copy)]
+struct ListView {
+    first_row: usize,
+    len: usize,
+    selected: Option<usize>,
+}
+
+impl ListView {
+    const fn new() -> Self {
+        Self {
+            first_row: 0,
+            len: 0,
+            selected: None,
+        }
+    }
+
+    /// Keeps the selection when it still names a row, so a refresh after `fm copy` does not
+    /// lose it.
+    fn set_rows(&mut self, first_row: usize, len: usize) {
+        self.first_row = first_row;
+        self.len = len;
+        if self.selected.is_some_and(|selected| selected >= len) {
+            self.selected = None;
+        }
+    }
+
+    fn entry_at(&self, grid_row: usize) -> Option<usize> {
+        (self.first_row..self.first_row + self.len)
+            .contains(&grid_row)
+            .then(|| grid_row - self.first_row)
+    }
+
+    fn selected_row(&self) -> Option<usize> {
+        self.selected.map(|selected| self.first_row + selected)
+    }
+
+    /// Moves the selection by `step` rows, stopping at either end; the first step selects
+    /// row 0.
+    fn stepped(&self, step: isize) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+        Some(match self.selected {
+            None => 0,
+            Some(selected) => selected.saturating_add_signed(step).min(self.len - 1),
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ProgressBar {
+    value: usize,
+    max: usize,
+}
+
+impl ProgressBar {
+    const fn new() -> Self {
+        Self { value: 0, max: 0 }
+    }
+
+    fn filled(&self, width: usize) -> usize {
+        if self.max == 0 {
+            return 0;
+        }
+        width.saturating_mul(self.value.min(self.max)) / self.max
+    }
+}
+
+/// Widget events waiting for the shell; when full, new events are counted and dropped.
+struct ActionQueue<T: Copy> {
+    items: [Option<T>; ACTION_CAPACITY],
+    head: usize,
+    len: usize,
+    pushed: u64,
+    dropped: u64,
+}
+
+impl<T: Copy> ActionQueue<T> {
+    const fn new() -> Self {
+        Self {
+            items: [None; ACTION_CAPACITY],
+            head: 0,
+            len: 0,
+            pushed: 0,
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, action: T) {
+        self.pushed = self.pushed.saturating_add(1);
+        if self.len == ACTION_CAPACITY {
+            self.dropped = self.dropped.saturating_add(1);
+            return;
+        }
+        self.items[(self.head + self.len) % ACTION_CAPACITY] = Some(action);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let action = self.items[self.head].take();
+        self.head = (self.head + 1) % ACTION_CAPACITY;
+        self.len -= 1;
+        action
+    }
+}
+
+pub(super) struct FileManagerPanel {
+    list: ListView,
+    usage: ProgressBar,
+    actions: ActionQueue<FileManagerAction>,
+}
+
+impl FileManagerPanel {
+    pub(super) const fn new() -> Self {
+        Self {
+            list: ListView::new(),
+            usage: ProgressBar::new(),
+            actions: ActionQueue::new(),
+        }
+    }
+
+    pub(super) fn pop_action(&mut self) -> Option<FileManagerAction> {
+        self.actions.pop()
+    }
+}
+
+pub(super) struct SettingsPanel {
+    list: ListView,
+    actions: ActionQueue<SettingsAction>,
+}
+
+impl SettingsPanel {
+    pub(super) const fn new() -> Self {
+        Self {
+            list: ListView::new(),
+            actions: ActionQueue::new(),
+        }
+    }
+
+    pub(super) fn pop_action(&mut self) -> Option<SettingsAction> {
+        self.actions.pop()
+    }
+}
+
+/// One window's toolbar state for `ui dump`.
+pub(super) struct ToolbarSnapshot {
+    window: &'static str,
+    buttons: [Option<(&'static str, Button)>; MAX_TOOLBAR_BUTTONS],
+    selected: Option<usize>,
+    usage: Option<ProgressBar>,
+}
+
+impl ToolbarSnapshot {
+    pub(super) fn log(&self) {
+        serial::write_fmt(format_args!("ui: dump {} toolbar buttons=", self.window));
+        for (position, (name, button)) in self.buttons.iter().flatten().enumerate() {
+            let state = if button.rect.w == 0 {
+                "hidden"
+            } else if button.enabled {
+                "on"
+            } else {
+                "off"
+            };
+            let separator = if position == 0 { "" } else { "," };
+            serial::write_fmt(format_args!("{separator}{name}:{state}"));
+        }
+        match self.selected {
+            Some(selected) => serial::write_fmt(format_args!(" selected={selected}")),
+            None => serial::write_str(" selected=none"),
+        }
+        if let Some(usage) = self.usage {
+            serial::write_fmt(format_args!(" usage={}/{}", usage.value, usage.max));
+        }
+        serial::write_str("\n");
+    }
+}
+
+impl GfxState {
+    /// Row under a window's text grid, clear of the resize handle.
+    fn toolbar_rect(&self, index: usize) -> Rect {
+        let window = self.windows[index];
+        Rect::new(
+            window.x.saturating_add(WINDOW_PADDING),
+            window
+                .y
+                .saturating_add(window.h)
+                .saturating_sub(WINDOW_PADDING + BUTTON_HEIGHT),
+            window
+                .w
+                .saturating_sub(WINDOW_PADDING * 2 + RESIZE_HANDLE_SIZE),
+            BUTTON_HEIGHT,
+        )
+    }
+
+    /// Lays `items` out left to right; buttons that no longer fit after a resize get an empty
+    /// rect and are neither drawn nor clickable.
+    fn layout_toolbar<K: Copy, const N: usize>(
+        &self,
+        index: usize,
+        items: [(K, &'static str, bool); N],
+    ) -> [(K, Button); N] {
+        let toolbar = self.toolbar_rect(index);
+        let end = toolbar.x.saturating_add(toolbar.w);
+        let mut x = toolbar.x;
+        items.map(|(kind, label, enabled)| {
+            let width = Button::width(label);
+            let fits = x.saturating_add(width) <= end;
+            let button = Button {
+                rect: Rect::new(x, toolbar.y, if fits { width } else { 0 }, toolbar.h),
+                label,
+                enabled: fits && enabled,
+            };
+            if fits {
+                x = x.saturating_add(width + WIDGET_GAP);
+            }
+            (kind, button)
+        })
+    }
+
+    fn file_manager_buttons(&self) -> [(FileManagerButton, Button); 5] {
+        let has_selection = self.file_manager.list.selected.is_some();
+        self.layout_toolbar(
+            FILE_MANAGER_WINDOW_INDEX,
+            FileManagerButton::ALL
+                .map(|kind| (kind, kind.label(), has_selection || !kind.needs_selection())),
+        )
+    }
+
+    fn settings_buttons(&self) -> [(SettingsButton, Button); 4] {
+        let has_selection = self.settings.list.selected.is_some();
+        self.layout_toolbar(
+            SETTINGS_WINDOW_INDEX,
+            SettingsButton::ALL
+                .map(|kind| (kind, kind.label(), has_selection || !kind.needs_selection())),
+        )
+    }
+
+    /// What is left of the toolbar after the buttons; empty when too narrow to read.
+    fn file_manager_progress_rect(&self) -> Rect {
+        let toolbar = self.toolbar_rect(FILE_MANAGER_WINDOW_INDEX);
+        let end = toolbar.x.saturating_add(toolbar.w);
+        let start = self
+            .file_manager_buttons()
+            .iter()
+            .filter(|(_, button)| button.rect.w > 0)
+            .map(|(_, button)| button.rect.x + button.rect.w + WIDGET_GAP)
+            .max()
+            .unwrap_or(toolbar.x);
+        let width = end.saturating_sub(start);
+        if width < MIN_PROGRESS_WIDTH {
+            return Rect::new(start, toolbar.y, 0, 0);
+        }
+        Rect::new(start, toolbar.y, width, toolbar.h)
+    }
+
+    fn invalidate_toolbar(&mut self, index: usize) {
+        if self.window_visible(index) && !self.windows[index].minimized {
+            self.invalidate_rect(self.toolbar_rect(index));
+        }
+    }
+
+    fn draw_button(&mut self, button: Button, body: Color) {
+        if button.rect.w == 0 {
+            return;
+        }
+        let (edge, face, text) = if button.enabled {
+            (
+                Color::rgb(150, 170, 196),
+                Color::rgb(52, 70, 96),
+                Color::rgb(230, 235, 242),
+            )
+        } else {
+            (Color::rgb(70, 84, 104), body, Color::rgb(96, 110, 130))
+        };
+        let Rect { x, y, w, h } = button.rect;
+        self.fill_rect(x, y, w, h, edge);
+        self.fill_rect(x + 1, y + 1, w.saturating_sub(2), h.saturating_sub(2), face);
+        self.draw_text(x + BUTTON_PAD_X, y + 3, button.label, text, Some(face));
+    }
+
+    fn draw_progress(&mut self, rect: Rect, bar: ProgressBar) {
+        if rect.w == 0 {
+            return;
+        }
+        let Rect { x, y, w, h } = rect;
+        let track = Color::rgb(10, 18, 30);
+        let fill = Color::rgb(96, 168, 112);
+        self.fill_rect(x, y, w, h, Color::rgb(70, 84, 104));
+        let inner_w = w.saturating_sub(2);
+        let filled = bar.filled(inner_w);
+        self.fill_rect(x + 1, y + 1, filled, h.saturating_sub(2), fill);
+        self.fill_rect(
+            x + 1 + filled,
+            y + 1,
+            inner_w - filled,
+            h.saturating_sub(2),
+            track,
+        );
+    }
+
+    /// Toolbar of window `index`, if it has one; drawn after the text grid.
+    pub(super) fn draw_widgets(&mut self, index: usize, body: Color) {
+        match index {
+            FILE_MANAGER_WINDOW_INDEX => {
+                for (_, button) in self.file_manager_buttons() {
+                    self.draw_button(button, body);
+                }
+                let rect = self.file_manager_progress_rect();
+                self.draw_progress(rect, self.file_manager.usage);
+            }
+            SETTINGS_WINDOW_INDEX => {
+                for (_, button) in self.settings_buttons() {
+                    self.draw_button(button, body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn list_view(&self, index: usize) -> Option<ListView> {
+        match index {
+            FILE_MANAGER_WINDOW_INDEX => Some(self.file_manager.list),
+            SETTINGS_WINDOW_INDEX => Some(self.settings.list),
+            _ => None,
+        }
+    }
+
+    fn list_view_mut(&mut self, index: usize) -> Option<&mut ListView> {
+        match index {
+            FILE_MANAGER_WINDOW_INDEX => Some(&mut self.file_manager.list),
+            SETTINGS_WINDOW_INDEX => Some(&mut self.settings.list),
+            _ => None,
+        }
+    }
+
+    /// Grid row drawn highlighted in window `index`.
+    pub(super) fn selected_list_row(&self, index: usize) -> Option<usize> {
+        self.list_view(index)?.selected_row()
+    }
+
+    /// Left click inside a window body: presses a toolbar button or selects a list row.
+    pub(super) fn widget_click(&mut self, index: usize, x: usize, y: usize) {
+        match index {
+            FILE_MANAGER_WINDOW_INDEX => {
+                if let Some((kind, _)) = self
+                    .file_manager_buttons()
+                    .into_iter()
+                    .find(|(_, button)| button.hit(x, y))
+                {
+                    let selected = self.file_manager.list.selected;
+                    self.file_manager.actions.push(FileManagerAction {
+                        button: kind,
+                        selected,
+                    });
+                    return;
+                }
+            }
+            SETTINGS_WINDOW_INDEX => {
+                if let Some((kind, _)) = self
+                    .settings_buttons()
+                    .into_iter()
+                    .find(|(_, button)| button.hit(x, y))
+                {
+                    match kind {
+                        SettingsButton::Decrease => {
+                            let _ = self.settings_adjust(-1);
+                        }
+                        SettingsButton::Increase => {
+                            let _ = self.settings_adjust(1);
+                        }
+                        SettingsButton::Sync => self.settings.actions.push(SettingsAction::Sync),
+                        SettingsButton::Close => self.close_settings_window(),
+                    }
+                    return;
+                }
+            }
+            _ => return,
+        }
+
+        let text = self.window_text_area_rect(index);
+        if !text.contains(x, y) {
+            return;
+        }
+        let grid_row = (y - text.y) / CHAR_H;
+        if let Some(entry) = self
+            .list_view(index)
+            .and_then(|list| list.entry_at(grid_row))
+        {
+            self.select_list_entry(index, Some(entry));
+        }
+    }
+
+    fn select_list_entry(&mut self, index: usize, entry: Option<usize>) {
+        let Some(list) = self.list_view_mut(index) else {
+            return;
+        };
+        let previous = list.selected_row();
+        list.selected = entry;
+        let next = list.selected_row();
+        if previous == next {
+            return;
+        }
+        if self.window_visible(index) {
+            for row in [previous, next].into_iter().flatten() {
+                self.invalidate_rect(self.window_text_row_rect(index, row));
+            }
+        }
+        self.invalidate_toolbar(index);
+    }
+
+    /// Shows a directory listing whose entries are text rows `first_row..first_row + len`.
+    pub(super) fn set_file_manager_list(
+        &mut self,
+        text: &str,
+        first_row: usize,
+        len: usize,
+        used: usize,
+        capacity: usize,
+    ) {
+        self.set_window_text(FILE_MANAGER_WINDOW_INDEX, text);
+        self.file_manager.list.set_rows(first_row, len);
+        self.file_manager.usage = ProgressBar {
+            value: used,
+            max: capacity,
+        };
+        self.invalidate_toolbar(FILE_MANAGER_WINDOW_INDEX);
+    }
+
+    /// Plain text (a file preview) has no rows to select.
+    pub(super) fn clear_file_manager_list(&mut self) {
+        self.file_manager.list.set_rows(0, 0);
+        self.file_manager.list.selected = None;
+        self.invalidate_toolbar(FILE_MANAGER_WINDOW_INDEX);
+    }
+
+    pub(super) fn set_settings_list(&mut self, text: &str, first_row: usize, len: usize) {
+        self.set_window_text(SETTINGS_WINDOW_INDEX, text);
+        self.settings.list.set_rows(first_row, len);
+        self.invalidate_toolbar(SETTINGS_WINDOW_INDEX);
+    }
+
+    /// Arrow keys only reach the settings list while its window is focused.
+    fn settings_focused(&self) -> bool {
+        self.focused_window == SETTINGS_WINDOW_INDEX
+            && self.window_visible(SETTINGS_WINDOW_INDEX)
+            && !self.windows[SETTINGS_WINDOW_INDEX].minimized
+    }
+
+    pub(super) fn settings_select_step(&mut self, step: isize) -> bool {
+        if !self.settings_focused() {
+            return false;
+        }
+        let entry = self.settings.list.stepped(step);
+        self.select_list_entry(SETTINGS_WINDOW_INDEX, entry);
+        true
+    }
+
+    pub(super) fn settings_adjust(&mut self, delta: i8) -> bool {
+        if !self.settings_focused() {
+            return false;
+        }
+        if let Some(row) = self.settings.list.selected {
+            self.settings
+                .actions
+                .push(SettingsAction::Adjust { row, delta });
+        }
+        true
+    }
+
+    pub(super) const fn button_clicks(&self) -> u64 {
+        self.file_manager
+            .actions
+            .pushed
+            .saturating_add(self.settings.actions.pushed)
+    }
+
+    pub(super) const fn button_drops(&self) -> u64 {
+        self.file_manager
+            .actions
+            .dropped
+            .saturating_add(self.settings.actions.dropped)
+    }
+
+    pub(super) fn toolbar_snapshot(&self, index: usize) -> Option<ToolbarSnapshot> {
+        let mut buttons = [None; MAX_TOOLBAR_BUTTONS];
+        let (window, usage) = match index {
+            FILE_MANAGER_WINDOW_INDEX => {
+                for (slot, (kind, button)) in buttons.iter_mut().zip(self.file_manager_buttons()) {
+                    *slot = Some((kind.as_str(), button));
+                }
+                ("fm", Some(self.file_manager.usage))
+            }
+            SETTINGS_WINDOW_INDEX => {
+                for (slot, (kind, button)) in buttons.iter_mut().zip(self.settings_buttons()) {
+                    *slot = Some((kind.as_str(), button));
+                }
+                ("settings", None)
+            }
+            _ => return None,
+        };
+        Some(ToolbarSnapshot {
+            window,
+            buttons,
+            selected: self.list_view(index)?.selected,
+            usage,
+        })
+    }
+}
@@ -0,0 +1,486 @@
+// kernel/src/input_bus.rs: routes keyboard and mouse events to subscribed consumers in priority
+// order. A capturing consumer takes every event of its kinds, and an ESC press ends the capture
+// unless the consumer opted out.
+use crate::error::KernelError;
+use crate::keyboard::{self, KeyByte, KeyCode, KeyEvent};
+use crate::mouse::{self, MouseEvent};
+pub use arrostd::syscall::{INPUT_BYTES, INPUT_KEYS, INPUT_MOUSE};
+use arrostd::syscall::{INPUT_CAPTURE, InputRecord};
+use core::cell::UnsafeCell;
+
+const CONSUMER_COUNT: usize = 4;
+const USER_QUEUE_CAPACITY: usize = 64;
+const ESCAPE: u8 = 0x1b;
+pub const KINDS_ALL: u8 = INPUT_KEYS | INPUT_BYTES | INPUT_MOUSE;
+/// Default priorities: capture-capable consumers sit above the desktop, the shell line editor
+/// takes whatever is left.
+#[cfg(feature = "doom")]
+pub const PRIORITY_DOOM: u8 = 200;
+#[cfg(feature = "desktop")]
+pub const PRIORITY_DESKTOP: u8 = 100;
+pub const PRIORITY_SHELL: u8 = 50;
+
+#[derive(Clone, Copy)]
+pub enum InputEvent {
+    /// Press or release from the scancode decoder.
+    Key(KeyEvent),
+    /// Text byte from the same decoder, queued alongside the key event.
+    Byte(KeyByte),
+    Mouse(MouseEvent),
+}
+
+impl InputEvent {
+    const fn kind(&self) -> u8 {
+        match self {
+            Self::Key(_) => INPUT_KEYS,
+            Self::Byte(_) => INPUT_BYTES,
+            Self::Mouse(_) => INPUT_MOUSE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Consumer {
+    Doom,
+    Desktop,
+    Shell,
+    /// A userland task subscribed through SYS_INPUT_SUBSCRIBE.
+    User,
+}
+
+impl Consumer {
+    const ALL: [Self; CONSUMER_COUNT] = [Self::Doom, Self::Desktop, Self::Shell, Self::User];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Doom => "doom",
+            Self::Desktop => "desktop",
+            Self::Shell => "shell",
+            Self::User => "user",
+        }
+    }
+
+    const fn index(self) -> usize {
+        match self {
+            Self::Doom => 0,
+            Self::Desktop => 1,
+            Self::Shell => 2,
+            Self::User => 3,
+        }
+    }
+}
+
+/// How an event reaches a handler.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// The handler may take the event by returning `true`.
+    Offered,
+    /// Another consumer captured the event; the handler only gets to watch it.
+    Captured,
+}
+
+/// Returns `true` when the handler took an offered event.
+pub type Handler = fn(InputEvent, Delivery) -> bool;
+
+#[derive(Clone, Copy)]
+pub struct Subscription {
+    pub handler: Handler,
+    /// `INPUT_KEYS | INPUT_BYTES | INPUT_MOUSE` mask.
+    pub kinds: u8,
+    /// Higher goes first; ties keep `Consumer` order.
+    pub priority: u8,
+    pub capture: bool,
+    /// An ESC press ends the capture instead of being delivered.
+    pub escape_releases: bool,
+    /// Also sees events of its kinds that another consumer captured.
+    pub observe_captured: bool,
+    /// Called after ESC ended this consumer's capture.
+    pub on_release: Option<fn()>,
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    subscription: Option<Subscription>,
+    delivered: u64,
+    observed: u64,
+    releases: u64,
+}
+
+impl Slot {
+    const EMPTY: Self = Self {
+        subscription: None,
+        delivered: 0,
+        observed: 0,
+        releases: 0,
+    };
+}
+
+struct UserQueue {
+    pid: u32,
+    records: [InputRecord; USER_QUEUE_CAPACITY],
+    head: usize,
+    len: usize,
+    dropped: u64,
+}
+
+struct Bus {
+    slots: [Slot; CONSUMER_COUNT],
+    unclaimed: u64,
+    user: UserQueue,
+}
+
+struct BusCell(UnsafeCell<Bus>);
+
+// SAFETY: the bus is polled and reconfigured only from the main loop thread; handlers run with
+// no reference into it held.
+unsafe impl Sync for BusCell {}
+
+static BUS: BusCell = BusCell(UnsafeCell::new(Bus {
+    slots: [Slot::EMPTY; CONSUMER_COUNT],
+    unclaimed: 0,
+    user: UserQueue {
+        pid: 0,
+        records: [InputRecord::EMPTY; USER_QUEUE_CAPACITY],
+        head: 0,
+        len: 0,
+        dropped: 0,
+    },
+}));
+
+fn with_bus<R>(f: impl FnOnce(&mut Bus) -> R) -> R {
+    // SAFETY: see `BusCell`; the reference does not escape the closure.
+    unsafe { f(&mut *BUS.0.get()) }
+}
+
+/// One consumer's entry for `input bus`.
+#[derive(Clone, Copy)]
+pub struct SubscriptionStatus {
+    pub consumer: Consumer,
+    pub subscribed: bool,
+    pub kinds: u8,
+    pub priority: u8,
+    pub capture: bool,
+    pub escape_releases: bool,
+    pub delivered: u64,
+    pub observed: u64,
+    pub releases: u64,
+}
+
+pub fn subscribe(consumer: Consumer, subscription: Subscription) {
+    with_bus(|bus| bus.slots[consumer.index()].subscription = Some(subscription));
+}
+
+/// Turns `consumer`'s capture on or off; `false` when it is not subscribed.
+#[cfg(feature = "doom")]
+pub fn set_capture(consumer: Consumer, capture: bool) -> bool {
+    with_bus(|bus| match &mut bus.slots[consumer.index()].subscription {
+        Some(subscription) => {
+            subscription.capture = capture;
+            true
+        }
+        None => false,
+    })
+}
+
+#[cfg(feature = "desktop")]
+pub fn set_escape_releases(consumer: Consumer, releases: bool) {
+    with_bus(|bus| {
+        if let Some(subscription) = &mut bus.slots[consumer.index()].subscription {
+            subscription.escape_releases = releases;
+        }
+    });
+}
+
+/// Consumer holding the capture, if any.
+pub fn captor() -> Option<Consumer> {
+    with_bus(|bus| {
+        order(bus, KINDS_ALL)
+            .into_iter()
+            .flatten()
+            .find(|consumer| {
+                bus.slots[consumer.index()]
+                    .subscription
+                    .is_some_and(|subscription| subscription.capture)
+            })
+    })
+}
+
+pub fn status() -> ([SubscriptionStatus; CONSUMER_COUNT], u64) {
+    with_bus(|bus| {
+        let entries = Consumer::ALL.map(|consumer| {
+            let slot = &bus.slots[consumer.index()];
+            let subscription = slot.subscription;
+            SubscriptionStatus {
+                consumer,
+                subscribed: subscription.is_some(),
+                kinds: subscription.map_or(0, |subscription| subscription.kinds),
+                priority: subscription.map_or(0, |subscription| subscription.priority),
+                capture: subscription.is_some_and(|subscription| subscription.capture),
+                escape_releases: subscription
+                    .is_some_and(|subscription| subscription.escape_releases),
+                delivered: slot.delivered,
+                observed: slot.observed,
+                releases: slot.releases,
+            }
+        });
+        (entries, bus.unclaimed)
+    })
+}
+
+/// `kinds` as `keys,bytes,mouse`, or `none`.
+pub fn kinds_name(kinds: u8, out: &mut [u8; 16]) -> &str {
+    let mut len = 0;
+    for (bit, name) in [
+        (INPUT_KEYS, "keys"),
+        (INPUT_BYTES, "bytes"),
+        (INPUT_MOUSE, "mouse"),
+    ] {
+        if kinds & bit == 0 {
+            continue;
+        }
+        if len > 0 {
+            out[len] = b',';
+            len += 1;
+        }
+        out[len..len + name.len()].copy_from_slice(name.as_bytes());
+        len += name.len();
+    }
+    if len == 0 {
+        return "none";
+    }
+    core::str::from_utf8(&out[..len]).unwrap_or("?")
+}
+
+/// Drains the keyboard and mouse queues through the subscribers.
+pub fn poll() {
+    while let Some(event) = keyboard::pop_key_event() {
+        dispatch(InputEvent::Key(event));
+    }
+    while let Some(key) = keyboard::pop_byte() {
+        dispatch(InputEvent::Byte(key));
+    }
+    while let Some(event) = mouse::pop_event() {
+        dispatch(InputEvent::Mouse(event));
+    }
+}
+
+/// Subscribers for `kinds`, highest priority first.
+fn order(bus: &Bus, kinds: u8) -> [Option<Consumer>; CONSUMER_COUNT] {
+    let mut order: [Option<Consumer>; CONSUMER_COUNT] = [None; CONSUMER_COUNT];
+    let mut len = 0;
+    for consumer in Consumer::ALL {
+        let Some(subscription) = bus.slots[consumer.index()].subscription else {
+            continue;
+        };
+        if subscription.kinds & kinds == 0 {
+            continue;
+        }
+        let mut at = len;
+        while at > 0 {
+            let Some(before) = order[at - 1] else { break };
+            let before_priority = bus.slots[before.index()]
+                .subscription
+                .map_or(0, |subscription| subscription.priority);
+            if before_priority >= subscription.priority {
+                break;
+            }
+            order[at] = order[at - 1];
+            at -= 1;
+        }
+        order[at] = Some(consumer);
+        len += 1;
+    }
+    order
+}
+
+fn is_escape(event: InputEvent, captor_kinds: u8) -> bool {
+    match event {
+        InputEvent::Key(key) => key.pressed && key.code == KeyCode::Byte(ESCAPE),
+        // A byte-only captor never sees the key event, so its ESC byte counts instead.
+        InputEvent::Byte(key) => key.byte == ESCAPE && captor_kinds & INPUT_KEYS == 0,
+        InputEvent::Mouse(_) => false,
+    }
+}
+
+fn dispatch(event: InputEvent) {
+    let kind = event.kind();
+    let (order, captor) = with_bus(|bus| {
+        let order = order(bus, kind);
+        let captor = order.into_iter().flatten().find_map(|consumer| {
+            bus.slots[consumer.index()]
+                .subscription
+                .filter(|subscription| subscription.capture)
+                .map(|subscription| (consumer, subscription))
+        });
+        (order, captor)
+    });
+
+    let Some((captor, subscription)) = captor else {
+        for consumer in order.into_iter().flatten() {
+            let Some(subscription) = with_bus(|bus| bus.slots[consumer.index()].subscription)
+            else {
+                continue;
+            };
+            if (subscription.handler)(event, Delivery::Offered) {
+                with_bus(|bus| {
+                    let slot = &mut bus.slots[consumer.index()];
+                    slot.delivered = slot.delivered.saturating_add(1);
+                });
+                return;
+            }
+        }
+        with_bus(|bus| bus.unclaimed = bus.unclaimed.saturating_add(1));
+        return;
+    };
+
+    if subscription.escape_releases && is_escape(event, subscription.kinds) {
+        with_bus(|bus| {
+            let slot = &mut bus.slots[captor.index()];
+            if let Some(subscription) = &mut slot.subscription {
+                subscription.capture = false;
+            }
+            slot.releases = slot.releases.saturating_add(1);
+        });
+        if let Some(on_release) = subscription.on_release {
+            on_release();
+        }
+        return;
+    }
+
+    let _ = (subscription.handler)(event, Delivery::Offered);
+    with_bus(|bus| {
+        let slot = &mut bus.slots[captor.index()];
+        slot.delivered = slot.delivered.saturating_add(1);
+    });
+    for consumer in order.into_iter().flatten() {
+        if consumer == captor {
+            continue;
+        }
+        let Some(observer) = with_bus(|bus| bus.slots[consumer.index()].subscription) else {
+            continue;
+        };
+        if !observer.observe_captured {
+            continue;
+        }
+        let _ = (observer.handler)(event, Delivery::Captured);
+        with_bus(|bus| {
+            let slot = &mut bus.slots[consumer.index()];
+            slot.observed = slot.observed.saturating_add(1);
+        });
+    }
+}
+
+/// SYS_INPUT_SUBSCRIBE: `flags` without a kind drops the task's subscription. `false` when
+/// another task holds the user slot.
+pub fn subscribe_user(pid: u32, flags: u64, priority: u8) -> bool {
+    let kinds = (flags & u64::from(KINDS_ALL)) as u8;
+    let held_by_other = with_bus(|bus| {
+        bus.slots[Consumer::User.index()].subscription.is_some() && bus.user.pid != pid
+    });
+    if held_by_other {
+        return false;
+    }
+    if kinds == 0 {
+        release_user(pid);
+        return true;
+    }
+    with_bus(|bus| {
+        if bus.user.pid != pid {
+            bus.user.head = 0;
+            bus.user.len = 0;
+        }
+        bus.user.pid = pid;
+        bus.slots[Consumer::User.index()].subscription = Some(Subscription {
+            handler: queue_user_event,
+            kinds,
+            priority,
+            capture: flags & INPUT_CAPTURE != 0,
+            escape_releases: true,
+            observe_captured: false,
+            on_release: None,
+        });
+    });
+    true
+}
+
+/// Drops `pid`'s subscription and queued events, for SYS_INPUT_SUBSCRIBE with no kinds and for
+/// task exit.
+pub fn release_user(pid: u32) {
+    with_bus(|bus| {
+        if bus.user.pid != pid {
+            return;
+        }
+        bus.slots[Consumer::User.index()].subscription = None;
+        bus.user.pid = 0;
+        bus.user.head = 0;
+        bus.user.len = 0;
+    });
+}
+
+/// SYS_INPUT_READ: the oldest queued event for `pid`; `EACCES` unless it holds the
+/// subscription.
+pub fn read_user(pid: u32) -> Result<Option<InputRecord>, KernelError> {
+    with_bus(|bus| {
+        if bus.slots[Consumer::User.index()].subscription.is_none() || bus.user.pid != pid {
+            return Err(KernelError::PermissionDenied);
+        }
+        let queue = &mut bus.user;
+        if queue.len == 0 {
+            return Ok(None);
+        }
+        let record = queue.records[queue.head];
+        queue.head = (queue.head + 1) % USER_QUEUE_CAPACITY;
+        queue.len -= 1;
+        Ok(Some(record))
+    })
+}
+
+/// SYS_POLL: whether SYS_INPUT_READ would return an event for `pid` now; `EACCES` like it.
+pub fn user_readable(pid: u32) -> Result<bool, KernelError> {
+    with_bus(|bus| {
+        if bus.slots[Consumer::User.index()].subscription.is_none() || bus.user.pid != pid {
+            return Err(KernelError::PermissionDenied);
+        }
+        Ok(bus.user.len > 0)
+    })
+}
+
+pub fn user_queue_dropped() -> u64 {
+    with_bus(|bus| bus.user.dropped)
+}
+
+fn queue_user_event(event: InputEvent, _delivery: Delivery) -> bool {
+    let record = match event {
+        InputEvent::Key(key) => InputRecord::key(key_code(key.code), key.pressed, key.tick),
+        InputEvent::Byte(key) => InputRecord::byte(key.byte, key.tick),
+        InputEvent::Mouse(event) => InputRecord::mouse(
+            event.dx,
+            event.dy,
+            u8::from(event.left_button)
+                | u8::from(event.right_button) << 1
+                | u8::from(event.middle_button) << 2,
+            crate::time::ticks(),
+        ),
+    };
+    with_bus(|bus| {
+        let queue = &mut bus.user;
+        if queue.len == USER_QUEUE_CAPACITY {
+            queue.dropped = queue.dropped.saturating_add(1);
+            return;
+        }
+        let at = (queue.head + queue.len) % USER_QUEUE_CAPACITY;
+        queue.records[at] = record;
+        queue.len += 1;
+    });
+    true
+}
+
+fn key_code(code: KeyCode) -> u16 {
+    match code {
+        KeyCode::Byte(byte) => u16::from(byte),
+        KeyCode::ArrowUp => arrostd::syscall::INPUT_KEY_UP,
+        KeyCode::ArrowDown => arrostd::syscall::INPUT_KEY_DOWN,
+        KeyCode::ArrowLeft => arrostd::syscall::INPUT_KEY_LEFT,
+        KeyCode::ArrowRight => arrostd::syscall::INPUT_KEY_RIGHT,
+    }
+}
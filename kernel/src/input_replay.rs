@@ -0,0 +1,333 @@
+// kernel/src/input_replay.rs: records raw PS/2 scancodes and mouse bytes with tick deltas to a file
+// and replays them through the keyboard/mouse decoders.
+use crate::fs::{self, MAX_FILE_BYTES, MAX_FILE_NAME_BYTES};
+use crate::proc::executor;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+use crate::{keyboard, mouse, time};
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use x86_64::instructions::interrupts;
+
+const MAGIC: &[u8; 4] = b"AIR1";
+const RECORD_CAPACITY: usize = MAX_FILE_BYTES - MAGIC.len();
+/// Record header: bit 7 marks a mouse byte, bits 0..7 hold the tick delta to the previous record.
+const MOUSE_FLAG: u8 = 0x80;
+const DELTA_MASK: u8 = 0x7f;
+/// Delta field value announcing a little-endian `u16` delta after the header byte.
+const DELTA_ESCAPE: u8 = 0x7f;
+const MAX_RECORD_BYTES: usize = 4;
+const SCANCODE_ENTER: u8 = 0x1c;
+const SCANCODE_RELEASE: u8 = 0x80;
+const SCANCODE_EXTENDED: u8 = 0xe0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum InputSource {
+    Keyboard,
+    Mouse,
+}
+
+#[derive(Clone, Copy)]
+struct Record {
+    source: InputSource,
+    byte: u8,
+    delta: u64,
+    start: usize,
+    end: usize,
+}
+
+impl Record {
+    fn is_key_press(&self) -> bool {
+        self.source == InputSource::Keyboard
+            && self.byte != SCANCODE_EXTENDED
+            && self.byte & SCANCODE_RELEASE == 0
+    }
+}
+
+fn decode(data: &[u8], offset: usize) -> Option<Record> {
+    let header = *data.get(offset)?;
+    let source = if header & MOUSE_FLAG != 0 {
+        InputSource::Mouse
+    } else {
+        InputSource::Keyboard
+    };
+    let (delta, byte_at) = match header & DELTA_MASK {
+        DELTA_ESCAPE => {
+            let low = *data.get(offset + 1)?;
+            let high = *data.get(offset + 2)?;
+            (u64::from(u16::from_le_bytes([low, high])), offset + 3)
+        }
+        delta => (u64::from(delta), offset + 1),
+    };
+    Some(Record {
+        source,
+        byte: *data.get(byte_at)?,
+        delta,
+        start: offset,
+        end: byte_at + 1,
+    })
+}
+
+fn records(data: &[u8]) -> impl Iterator<Item = Record> + '_ {
+    let mut offset = 0;
+    core::iter::from_fn(move || {
+        let record = decode(data, offset)?;
+        offset = record.end;
+        Some(record)
+    })
+}
+
+/// Offset where the line that typed `input record stop` begins: the first key press after
+/// the second-to-last Enter press, or the end of the data when there is none.
+fn stop_line_start(data: &[u8]) -> usize {
+    let mut enters = [0usize; 2];
+    for record in records(data) {
+        if record.source == InputSource::Keyboard && record.byte == SCANCODE_ENTER {
+            enters = [enters[1], record.end];
+        }
+    }
+    records(&data[enters[0]..])
+        .find(Record::is_key_press)
+        .map_or(data.len(), |record| enters[0] + record.start)
+}
+
+struct Recorder {
+    path: [u8; MAX_FILE_NAME_BYTES],
+    path_len: usize,
+    data: [u8; RECORD_CAPACITY],
+    len: usize,
+    last_tick: u64,
+    truncated: bool,
+}
+
+impl Recorder {
+    const fn new() -> Self {
+        Self {
+            path: [0; MAX_FILE_NAME_BYTES],
+            path_len: 0,
+            data: [0; RECORD_CAPACITY],
+            len: 0,
+            last_tick: 0,
+            truncated: false,
+        }
+    }
+
+    fn path(&self) -> &str {
+        core::str::from_utf8(&self.path[..self.path_len]).unwrap_or("?")
+    }
+
+    /// Returns false once the buffer is full; the caller then stops recording.
+    fn push(&mut self, source: InputSource, byte: u8, tick: u64) -> bool {
+        if self.len + MAX_RECORD_BYTES > RECORD_CAPACITY {
+            self.truncated = true;
+            return false;
+        }
+        let flag = match source {
+            InputSource::Keyboard => 0,
+            InputSource::Mouse => MOUSE_FLAG,
+        };
+        let delta = tick.saturating_sub(self.last_tick);
+        self.last_tick = tick;
+        if delta < u64::from(DELTA_ESCAPE) {
+            self.data[self.len] = flag | delta as u8;
+            self.len += 1;
+        } else {
+            let [low, high] = (delta.min(u64::from(u16::MAX)) as u16).to_le_bytes();
+            self.data[self.len..self.len + 3].copy_from_slice(&[flag | DELTA_ESCAPE, low, high]);
+            self.len += 3;
+        }
+        self.data[self.len] = byte;
+        self.len += 1;
+        true
+    }
+}
+
+struct RecorderCell(UnsafeCell<Recorder>);
+
+// SAFETY: IRQ handlers write only while `RECORDING` is set (interrupt gates do not nest); the
+// shell toggles the flag and reads the buffer with interrupts disabled.
+unsafe impl Sync for RecorderCell {}
+
+static RECORDER: RecorderCell = RecorderCell(UnsafeCell::new(Recorder::new()));
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+
+fn with_recorder<R>(f: impl FnOnce(&mut Recorder) -> R) -> R {
+    // SAFETY: see `RecorderCell`; callers are IRQ handlers or run with interrupts disabled.
+    unsafe { f(&mut *RECORDER.0.get()) }
+}
+
+/// Called from the keyboard and mouse IRQ handlers with every raw byte read from port 0x60.
+pub fn capture(source: InputSource, byte: u8) {
+    if !RECORDING.load(Ordering::Acquire) {
+        return;
+    }
+    let tick = time::ticks();
+    if !with_recorder(|recorder| recorder.push(source, byte, tick)) {
+        RECORDING.store(false, Ordering::Release);
+    }
+}
+
+pub fn record_to_serial(path: &str) -> Status {
+    let path = path.trim();
+    if path.is_empty() || path.len() > MAX_FILE_NAME_BYTES {
+        return Status::usage("usage: input record <file>|stop");
+    }
+    if REPLAYING.load(Ordering::Acquire) {
+        serial::write_severity_line(Severity::Warning, "input: replay in progress");
+        return Status::Failed;
+    }
+    interrupts::without_interrupts(|| {
+        with_recorder(|recorder| {
+            *recorder = Recorder::new();
+            recorder.path[..path.len()].copy_from_slice(path.as_bytes());
+            recorder.path_len = path.len();
+            recorder.last_tick = time::ticks();
+        });
+        RECORDING.store(true, Ordering::Release);
+    });
+    serial::write_fmt(format_args!(
+        "input: recording to {} (capacity {} bytes, finish with `input record stop`)\n",
+        path, RECORD_CAPACITY
+    ));
+    Status::Ok
+}
+
+pub fn stop_record_to_serial() -> Status {
+    let was_recording = interrupts::without_interrupts(|| RECORDING.swap(false, Ordering::AcqRel));
+    let mut file = [0u8; MAX_FILE_BYTES];
+    let (len, trimmed, truncated) = interrupts::without_interrupts(|| {
+        with_recorder(|recorder| {
+            // A full buffer stopped recording before the stop line was typed.
+            let keep = if recorder.truncated {
+                recorder.len
+            } else {
+                stop_line_start(&recorder.data[..recorder.len])
+            };
+            file[..MAGIC.len()].copy_from_slice(MAGIC);
+            file[MAGIC.len()..MAGIC.len() + keep].copy_from_slice(&recorder.data[..keep]);
+            (keep, recorder.len - keep, recorder.truncated)
+        })
+    });
+    if !was_recording && !truncated {
+        serial::write_line("input: not recording");
+        return Status::Failed;
+    }
+
+    let mut path = [0u8; MAX_FILE_NAME_BYTES];
+    let path_len = with_recorder(|recorder| {
+        path[..recorder.path_len].copy_from_slice(&recorder.path[..recorder.path_len]);
+        recorder.path_len
+    });
+    let path = core::str::from_utf8(&path[..path_len]).unwrap_or("?");
+    let events = records(&file[MAGIC.len()..MAGIC.len() + len]).count();
+    let status = match fs::write_file(path, &file[..MAGIC.len() + len]) {
+        Ok(written) => {
+            serial::write_fmt(format_args!(
+                "input: saved {} events ({} bytes) to {} trimmed_bytes={} truncated={}\n",
+                events, written, path, trimmed, truncated
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!(
+            "input: record {} failed ({})\n",
+            path,
+            err.as_str()
+        )),
+    };
+    with_recorder(|recorder| recorder.truncated = false);
+    status
+}
+
+/// Reports whether the replay started; it runs on as a service.
+pub fn replay_to_serial(path: &str) -> Status {
+    let path = path.trim();
+    if RECORDING.load(Ordering::Acquire) {
+        serial::write_severity_line(Severity::Warning, "input: stop recording first");
+        return Status::Failed;
+    }
+    if REPLAYING.load(Ordering::Acquire) {
+        serial::write_severity_line(Severity::Warning, "input: replay in progress");
+        return Status::Failed;
+    }
+    let mut file = [0u8; MAX_FILE_BYTES];
+    let len = match fs::read_file(path, &mut file) {
+        Ok(len) => len,
+        Err(err) => {
+            return Status::failed(format_args!(
+                "input: replay {} failed ({})\n",
+                path,
+                err.as_str()
+            ));
+        }
+    };
+    if len < MAGIC.len() || &file[..MAGIC.len()] != MAGIC {
+        return Status::failed(format_args!(
+            "input: replay {} failed (not an input recording)\n",
+            path
+        ));
+    }
+
+    let data = file[MAGIC.len()..len].to_vec();
+    let events = records(&data).count();
+    let ticks: u64 = records(&data).map(|record| record.delta).sum();
+    REPLAYING.store(true, Ordering::Release);
+    if !executor::spawn("input-replay", replay(data)) {
+        REPLAYING.store(false, Ordering::Release);
+        serial::write_severity_line(Severity::Error, "input: replay failed (no service slot)");
+        return Status::Failed;
+    }
+    serial::write_fmt(format_args!(
+        "input: replaying {} events from {} over {} ticks\n",
+        events, path, ticks
+    ));
+    Status::Ok
+}
+
+/// Feeds each byte to its decoder at the recorded tick offset; decoders assume IRQ context,
+/// so injection runs with interrupts disabled.
+async fn replay(data: Vec<u8>) {
+    let start = time::ticks();
+    let mut due = start;
+    let mut events = 0u64;
+    let mut outcome = "finished";
+    for record in records(&data) {
+        due = due.saturating_add(record.delta);
+        if executor::unless_terminated(executor::sleep_until(due))
+            .await
+            .is_none()
+        {
+            outcome = "stopped by signal";
+            break;
+        }
+        interrupts::without_interrupts(|| match record.source {
+            InputSource::Keyboard => keyboard::handle_scancode(record.byte),
+            InputSource::Mouse => mouse::handle_data_byte(record.byte),
+        });
+        events += 1;
+    }
+    REPLAYING.store(false, Ordering::Release);
+    serial::write_fmt(format_args!(
+        "input: replay {} events={} ticks={}\n",
+        outcome,
+        events,
+        time::ticks().saturating_sub(start)
+    ));
+}
+
+pub fn log_status() {
+    let recording = RECORDING.load(Ordering::Acquire);
+    let (path_len, used) = with_recorder(|recorder| (recorder.path_len, recorder.len));
+    serial::write_fmt(format_args!(
+        "input: recording={} replaying={} buffer={}/{}",
+        recording,
+        REPLAYING.load(Ordering::Acquire),
+        used,
+        RECORD_CAPACITY
+    ));
+    if recording && path_len > 0 {
+        with_recorder(|recorder| serial::write_fmt(format_args!(" file={}", recorder.path())));
+    }
+    serial::write_line("");
+}
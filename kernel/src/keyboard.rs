@@ -1,4 +1,5 @@
 // kernel/src/keyboard.rs: PS/2 set-1 scancode decoding with byte queue + press/release events.
+use crate::{serial, time};
 use core::cell::UnsafeCell;
 use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
@@ -10,6 +11,7 @@ const EVENT_CODE_ARROW_LEFT: u16 = 0x0102;
 const EVENT_CODE_ARROW_RIGHT: u16 = 0x0103;
 const EVENT_CODE_MASK: u16 = 0x7fff;
 const EVENT_PRESSED_MASK: u16 = 0x8000;
+const INPUT_SINK_COUNT: usize = 3;
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum KeyCode {
@@ -24,6 +26,42 @@ pub enum KeyCode {
 pub struct KeyEvent {
     pub code: KeyCode,
     pub pressed: bool,
+    /// Timer tick at which the IRQ handler queued the event.
+    pub tick: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct KeyByte {
+    pub byte: u8,
+    pub tick: u64,
+}
+
+/// Consumer a queued key was handed to; latency is tracked separately per sink.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum InputSink {
+    Shell,
+    Gfx,
+    Doom,
+}
+
+impl InputSink {
+    const ALL: [Self; INPUT_SINK_COUNT] = [Self::Shell, Self::Gfx, Self::Doom];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Shell => "shell",
+            Self::Gfx => "gfx",
+            Self::Doom => "doom",
+        }
+    }
+
+    const fn index(self) -> usize {
+        match self {
+            Self::Shell => 0,
+            Self::Gfx => 1,
+            Self::Doom => 2,
+        }
+    }
 }
 
 struct ByteQueueStorage(UnsafeCell<[u8; BYTE_QUEUE_CAPACITY]>);
@@ -36,17 +74,35 @@ struct EventQueueStorage(UnsafeCell<[u16; EVENT_QUEUE_CAPACITY]>);
 // SAFETY: access is synchronized through the single-producer/single-consumer indices.
 unsafe impl Sync for EventQueueStorage {}
 
+struct TickQueueStorage<const N: usize>(UnsafeCell<[u64; N]>);
+
+// SAFETY: slots are written by the producer before publishing `head`, like the queues they shadow.
+unsafe impl<const N: usize> Sync for TickQueueStorage<N> {}
+
 static BYTE_QUEUE_STORAGE: ByteQueueStorage =
     ByteQueueStorage(UnsafeCell::new([0; BYTE_QUEUE_CAPACITY]));
 static BYTE_QUEUE_HEAD: AtomicUsize = AtomicUsize::new(0);
 static BYTE_QUEUE_TAIL: AtomicUsize = AtomicUsize::new(0);
 static BYTE_QUEUE_OVERFLOW_COUNT: AtomicU64 = AtomicU64::new(0);
+static BYTE_TICK_STORAGE: TickQueueStorage<BYTE_QUEUE_CAPACITY> =
+    TickQueueStorage(UnsafeCell::new([0; BYTE_QUEUE_CAPACITY]));
 
 static EVENT_QUEUE_STORAGE: EventQueueStorage =
     EventQueueStorage(UnsafeCell::new([0; EVENT_QUEUE_CAPACITY]));
 static EVENT_QUEUE_HEAD: AtomicUsize = AtomicUsize::new(0);
 static EVENT_QUEUE_TAIL: AtomicUsize = AtomicUsize::new(0);
 static EVENT_QUEUE_OVERFLOW_COUNT: AtomicU64 = AtomicU64::new(0);
+static EVENT_TICK_STORAGE: TickQueueStorage<EVENT_QUEUE_CAPACITY> =
+    TickQueueStorage(UnsafeCell::new([0; EVENT_QUEUE_CAPACITY]));
+
+static LATENCY_SAMPLES: [AtomicU64; INPUT_SINK_COUNT] =
+    [const { AtomicU64::new(0) }; INPUT_SINK_COUNT];
+static LATENCY_TOTAL_TICKS: [AtomicU64; INPUT_SINK_COUNT] =
+    [const { AtomicU64::new(0) }; INPUT_SINK_COUNT];
+static LATENCY_MAX_TICKS: [AtomicU64; INPUT_SINK_COUNT] =
+    [const { AtomicU64::new(0) }; INPUT_SINK_COUNT];
+static LATENCY_LAST_TICKS: [AtomicU64; INPUT_SINK_COUNT] =
+    [const { AtomicU64::new(0) }; INPUT_SINK_COUNT];
 
 static SHIFT_PRESSED: AtomicBool = AtomicBool::new(false);
 static EXTENDED_PREFIX: AtomicBool = AtomicBool::new(false);
@@ -62,6 +118,7 @@ pub fn init() {
 
     SHIFT_PRESSED.store(false, Ordering::Relaxed);
     EXTENDED_PREFIX.store(false, Ordering::Relaxed);
+    reset_latency();
 }
 
 pub fn handle_scancode(scancode: u8) {
@@ -73,6 +130,7 @@ pub fn handle_scancode(scancode: u8) {
     let extended = EXTENDED_PREFIX.swap(false, Ordering::AcqRel);
     let pressed = (scancode & 0x80) == 0;
     let code = scancode & 0x7f;
+    let tick = time::ticks();
 
     match code {
         0x2A | 0x36 => {
@@ -86,6 +144,7 @@ pub fn handle_scancode(scancode: u8) {
         push_key_event(KeyEvent {
             code: event_code,
             pressed,
+            tick,
         });
     }
 
@@ -95,11 +154,11 @@ pub fn handle_scancode(scancode: u8) {
 
     let shift = SHIFT_PRESSED.load(Ordering::Relaxed);
     if let Some(ascii) = map_set1_scancode(code, shift) {
-        push_byte(ascii);
+        push_byte(ascii, tick);
     }
 }
 
-pub fn pop_byte() -> Option<u8> {
+pub fn pop_byte() -> Option<KeyByte> {
     let tail = BYTE_QUEUE_TAIL.load(Ordering::Relaxed);
     let head = BYTE_QUEUE_HEAD.load(Ordering::Acquire);
     if tail == head {
@@ -107,13 +166,14 @@ pub fn pop_byte() -> Option<u8> {
     }
 
     // SAFETY: `tail != head`, so the slot contains initialized queue data.
-    let byte = unsafe {
+    let (byte, tick) = unsafe {
         let ptr = (*BYTE_QUEUE_STORAGE.0.get()).as_ptr().add(tail);
-        ptr.read()
+        let tick_ptr = (*BYTE_TICK_STORAGE.0.get()).as_ptr().add(tail);
+        (ptr.read(), tick_ptr.read())
     };
     let next_tail = (tail + 1) % BYTE_QUEUE_CAPACITY;
     BYTE_QUEUE_TAIL.store(next_tail, Ordering::Release);
-    Some(byte)
+    Some(KeyByte { byte, tick })
 }
 
 pub fn pop_key_event() -> Option<KeyEvent> {
@@ -124,13 +184,64 @@ pub fn pop_key_event() -> Option<KeyEvent> {
     }
 
     // SAFETY: `tail != head`, so the slot contains initialized queue data.
-    let encoded = unsafe {
+    let (encoded, tick) = unsafe {
         let ptr = (*EVENT_QUEUE_STORAGE.0.get()).as_ptr().add(tail);
-        ptr.read()
+        let tick_ptr = (*EVENT_TICK_STORAGE.0.get()).as_ptr().add(tail);
+        (ptr.read(), tick_ptr.read())
     };
     let next_tail = (tail + 1) % EVENT_QUEUE_CAPACITY;
     EVENT_QUEUE_TAIL.store(next_tail, Ordering::Release);
-    decode_key_event(encoded)
+    decode_key_event(encoded, tick)
+}
+
+/// Records IRQ-to-delivery latency for a key timestamped at `tick` that just reached `sink`.
+pub fn record_delivery(sink: InputSink, tick: u64) {
+    let index = sink.index();
+    let latency = time::ticks().saturating_sub(tick);
+    LATENCY_SAMPLES[index].fetch_add(1, Ordering::Relaxed);
+    LATENCY_TOTAL_TICKS[index].fetch_add(latency, Ordering::Relaxed);
+    LATENCY_MAX_TICKS[index].fetch_max(latency, Ordering::Relaxed);
+    LATENCY_LAST_TICKS[index].store(latency, Ordering::Relaxed);
+}
+
+pub fn reset_latency() {
+    for index in 0..INPUT_SINK_COUNT {
+        LATENCY_SAMPLES[index].store(0, Ordering::Relaxed);
+        LATENCY_TOTAL_TICKS[index].store(0, Ordering::Relaxed);
+        LATENCY_MAX_TICKS[index].store(0, Ordering::Relaxed);
+        LATENCY_LAST_TICKS[index].store(0, Ordering::Relaxed);
+    }
+}
+
+pub fn log_latency() {
+    let ms_per_tick = 1000 / u64::from(time::PIT_HZ);
+    serial::write_fmt(format_args!(
+        "input: latency resolution={}ms byte_overflow={} event_overflow={}\n",
+        ms_per_tick,
+        overflow_count(),
+        event_overflow_count()
+    ));
+    for sink in InputSink::ALL {
+        let index = sink.index();
+        let samples = LATENCY_SAMPLES[index].load(Ordering::Relaxed);
+        let total = LATENCY_TOTAL_TICKS[index].load(Ordering::Relaxed);
+        let avg_ms = total
+            .saturating_mul(ms_per_tick)
+            .checked_div(samples)
+            .unwrap_or(0);
+        serial::write_fmt(format_args!(
+            "input: sink={} samples={} avg_ms={} max_ms={} last_ms={}\n",
+            sink.as_str(),
+            samples,
+            avg_ms,
+            LATENCY_MAX_TICKS[index]
+                .load(Ordering::Relaxed)
+                .saturating_mul(ms_per_tick),
+            LATENCY_LAST_TICKS[index]
+                .load(Ordering::Relaxed)
+                .saturating_mul(ms_per_tick)
+        ));
+    }
 }
 
 pub fn overflow_count() -> u64 {
@@ -141,7 +252,7 @@ pub fn event_overflow_count() -> u64 {
     EVENT_QUEUE_OVERFLOW_COUNT.load(Ordering::Relaxed)
 }
 
-fn push_byte(byte: u8) {
+fn push_byte(byte: u8, tick: u64) {
     let head = BYTE_QUEUE_HEAD.load(Ordering::Relaxed);
     let next_head = (head + 1) % BYTE_QUEUE_CAPACITY;
     let tail = BYTE_QUEUE_TAIL.load(Ordering::Acquire);
@@ -154,6 +265,10 @@ fn push_byte(byte: u8) {
     unsafe {
         let ptr = (*BYTE_QUEUE_STORAGE.0.get()).as_mut_ptr().add(head);
         ptr.write(byte);
+        (*BYTE_TICK_STORAGE.0.get())
+            .as_mut_ptr()
+            .add(head)
+            .write(tick);
     }
     BYTE_QUEUE_HEAD.store(next_head, Ordering::Release);
 }
@@ -172,6 +287,10 @@ fn push_key_event(event: KeyEvent) {
     unsafe {
         let ptr = (*EVENT_QUEUE_STORAGE.0.get()).as_mut_ptr().add(head);
         ptr.write(encoded);
+        (*EVENT_TICK_STORAGE.0.get())
+            .as_mut_ptr()
+            .add(head)
+            .write(event.tick);
     }
     EVENT_QUEUE_HEAD.store(next_head, Ordering::Release);
 }
@@ -191,7 +310,7 @@ fn encode_key_event(event: KeyEvent) -> u16 {
     }
 }
 
-fn decode_key_event(encoded: u16) -> Option<KeyEvent> {
+fn decode_key_event(encoded: u16, tick: u64) -> Option<KeyEvent> {
     let pressed = (encoded & EVENT_PRESSED_MASK) != 0;
     let code = match encoded & EVENT_CODE_MASK {
         EVENT_CODE_ARROW_UP => KeyCode::ArrowUp,
@@ -201,7 +320,11 @@ fn decode_key_event(encoded: u16) -> Option<KeyEvent> {
         value if value <= u16::from(u8::MAX) => KeyCode::Byte(value as u8),
         _ => return None,
     };
-    Some(KeyEvent { code, pressed })
+    Some(KeyEvent {
+        code,
+        pressed,
+        tick,
+    })
 }
 
 fn map_set1_scancode_event(scancode: u8, extended: bool) -> Option<KeyCode> {
@@ -0,0 +1,231 @@
+// kernel/src/log.rs: per-subsystem console verbosity (quiet mode) and per-call-site rate limiting.
+use crate::serial;
+#[cfg(feature = "net")]
+use crate::serial::Severity;
+#[cfg(feature = "net")]
+use crate::time;
+#[cfg(feature = "net")]
+use core::fmt;
+#[cfg(feature = "net")]
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// A rate-limited call site prints at most `RATE_BURST` lines per `RATE_WINDOW_TICKS`.
+#[cfg(feature = "net")]
+const RATE_WINDOW_TICKS: u64 = 100;
+#[cfg(feature = "net")]
+const RATE_BURST: u32 = 5;
+/// Build-time quiet list for smoke images, e.g. `ARROST_LOG_QUIET=net`; boot-time logs
+/// happen before the shell (or the fs) could change anything.
+const BUILD_QUIET: Option<&str> = option_env!("ARROST_LOG_QUIET");
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Net,
+    Gfx,
+    Doom,
+    Audio,
+    Fs,
+    Storage,
+    Proc,
+}
+
+impl Subsystem {
+    pub const ALL: [Self; 7] = [
+        Self::Net,
+        Self::Gfx,
+        Self::Doom,
+        Self::Audio,
+        Self::Fs,
+        Self::Storage,
+        Self::Proc,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Net => "net",
+            Self::Gfx => "gfx",
+            Self::Doom => "doom",
+            Self::Audio => "audio",
+            Self::Fs => "fs",
+            Self::Storage => "storage",
+            Self::Proc => "proc",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|subsystem| subsystem.as_str() == text.trim())
+    }
+}
+
+/// Informational lines are dropped in quiet mode; warnings and errors always print.
+#[cfg(any(feature = "net", feature = "desktop"))]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Names for `ui mirror <level>`, the only place a level is typed in.
+#[cfg(feature = "desktop")]
+impl Level {
+    pub const ALL: [Self; 3] = [Self::Info, Self::Warning, Self::Error];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.as_str() == text.trim())
+    }
+}
+
+struct SubsystemLog {
+    quiet: AtomicBool,
+    emitted: AtomicU64,
+    quiet_dropped: AtomicU64,
+    rate_dropped: AtomicU64,
+}
+
+impl SubsystemLog {
+    const fn new() -> Self {
+        Self {
+            quiet: AtomicBool::new(false),
+            emitted: AtomicU64::new(0),
+            quiet_dropped: AtomicU64::new(0),
+            rate_dropped: AtomicU64::new(0),
+        }
+    }
+}
+
+static SUBSYSTEMS: [SubsystemLog; Subsystem::ALL.len()] =
+    [const { SubsystemLog::new() }; Subsystem::ALL.len()];
+
+fn state(subsystem: Subsystem) -> &'static SubsystemLog {
+    &SUBSYSTEMS[subsystem as usize]
+}
+
+/// Like `write`, but each expansion gets its own `RateLimit`, so one hot path (RX errors
+/// under fuzzing, say) cannot flood the console. Dropped lines are summarized when the
+/// site next prints.
+#[cfg(feature = "net")]
+macro_rules! log_ratelimited {
+    ($subsystem:expr, $level:expr, $($arg:tt)+) => {{
+        static LIMIT: $crate::log::RateLimit = $crate::log::RateLimit::new();
+        LIMIT.write($subsystem, $level, format_args!($($arg)+));
+    }};
+}
+
+#[cfg(feature = "net")]
+pub(crate) use log_ratelimited;
+
+/// Applies the `ARROST_LOG_QUIET` build default; runs before the first subsystem logs.
+pub fn init() {
+    let Some(list) = BUILD_QUIET else {
+        return;
+    };
+    for name in list.split(',') {
+        if let Some(subsystem) = Subsystem::parse(name) {
+            set_quiet(subsystem, true);
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+pub fn write(subsystem: Subsystem, level: Level, args: fmt::Arguments<'_>) {
+    let log = state(subsystem);
+    match level {
+        Level::Info if log.quiet.load(Ordering::Relaxed) => {
+            log.quiet_dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        Level::Info => serial::write_fmt(args),
+        Level::Warning => serial::write_severity_fmt(Severity::Warning, args),
+        Level::Error => serial::write_severity_fmt(Severity::Error, args),
+    }
+    log.emitted.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn set_quiet(subsystem: Subsystem, quiet: bool) -> bool {
+    state(subsystem).quiet.swap(quiet, Ordering::Relaxed) != quiet
+}
+
+#[cfg(any(feature = "net", feature = "desktop"))]
+pub fn quiet(subsystem: Subsystem) -> bool {
+    state(subsystem).quiet.load(Ordering::Relaxed)
+}
+
+/// Fixed-window limiter, one static per `log_ratelimited!` expansion.
+#[cfg(feature = "net")]
+pub struct RateLimit {
+    window_start: AtomicU64,
+    printed: AtomicU32,
+    suppressed: AtomicU64,
+}
+
+#[cfg(feature = "net")]
+impl RateLimit {
+    pub const fn new() -> Self {
+        Self {
+            window_start: AtomicU64::new(0),
+            printed: AtomicU32::new(0),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    pub fn write(&self, subsystem: Subsystem, level: Level, args: fmt::Arguments<'_>) {
+        let now = time::ticks();
+        if now.saturating_sub(self.window_start.load(Ordering::Relaxed)) >= RATE_WINDOW_TICKS {
+            self.window_start.store(now, Ordering::Relaxed);
+            self.printed.store(0, Ordering::Relaxed);
+            let suppressed = self.suppressed.swap(0, Ordering::Relaxed);
+            if suppressed > 0 {
+                write(
+                    subsystem,
+                    Level::Warning,
+                    format_args!(
+                        "{}: {} messages suppressed (rate limit)\n",
+                        subsystem.as_str(),
+                        suppressed
+                    ),
+                );
+            }
+        }
+        if self.printed.fetch_add(1, Ordering::Relaxed) >= RATE_BURST {
+            self.printed.store(RATE_BURST, Ordering::Relaxed);
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            state(subsystem)
+                .rate_dropped
+                .fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        write(subsystem, level, args);
+    }
+}
+
+pub fn log_levels() {
+    for subsystem in Subsystem::ALL {
+        let log = state(subsystem);
+        serial::write_fmt(format_args!(
+            "log: {:<8} verbosity={} emitted={} quiet_dropped={} rate_dropped={}\n",
+            subsystem.as_str(),
+            if log.quiet.load(Ordering::Relaxed) {
+                "quiet"
+            } else {
+                "normal"
+            },
+            log.emitted.load(Ordering::Relaxed),
+            log.quiet_dropped.load(Ordering::Relaxed),
+            log.rate_dropped.load(Ordering::Relaxed)
+        ));
+    }
+}
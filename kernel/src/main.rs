@@ -6,21 +6,41 @@
 extern crate alloc;
 
 // kernel/src/main.rs: kernel entry point and early-boot flow.
+#[cfg(feature = "doom")]
+mod app_bridge;
 mod arch;
+mod artifacts;
+#[cfg(feature = "audio")]
 mod audio;
+mod bootchart;
+#[cfg(feature = "doom")]
 mod doom;
+#[cfg(feature = "doom")]
 mod doom_bridge;
+mod error;
+mod evlog;
+mod features;
 mod fs;
+#[cfg(feature = "desktop")]
 mod gfx;
+mod input_bus;
+mod input_replay;
 mod keyboard;
+mod log;
 mod mem;
 mod mouse;
+#[cfg(feature = "net")]
 mod net;
 mod proc;
 mod serial;
 mod shell;
+mod soft_assert;
 mod storage;
+mod sysinfo;
+#[cfg(feature = "net")]
+mod telemetry;
 mod time;
+mod utf8;
 
 const VERSION_MAJOR: &str = match option_env!("ARROST_VERSION_MAJOR") {
     Some(value) => value,
@@ -34,79 +54,12 @@ const VERSION_BUILD: &str = match option_env!("ARROST_BUILD_COUNT") {
     Some(value) => value,
     None => "0",
 };
-const DOOM_APP: &str = match option_env!("ARROST_DOOM_APP") {
-    Some(value) => value,
-    None => "doom",
-};
-const DOOM_ARTIFACT_SIZE: &str = match option_env!("ARROST_DOOM_ARTIFACT_SIZE") {
-    Some(value) => value,
-    None => "0",
-};
-const DOOM_ARTIFACT_HINT: &str = match option_env!("ARROST_DOOM_ARTIFACT_HINT") {
-    Some(value) => value,
-    None => "<none>",
-};
-const DOOM_C_BACKEND_SIZE: &str = match option_env!("ARROST_DOOM_C_BACKEND_SIZE") {
-    Some(value) => value,
-    None => "0",
-};
-const DOOM_C_BACKEND_READY: &str = match option_env!("ARROST_DOOM_C_BACKEND_READY") {
-    Some(value) => value,
-    None => "false",
-};
-const DOOM_C_BACKEND_OBJECT: &str = match option_env!("ARROST_DOOM_C_BACKEND_OBJECT") {
-    Some(value) => value,
-    None => "<none>",
-};
-const DOOM_GENERIC_READY: &str = match option_env!("ARROST_DOOM_GENERIC_READY") {
-    Some(value) => value,
-    None => "false",
-};
-const DOOM_GENERIC_ROOT: &str = match option_env!("ARROST_DOOM_GENERIC_ROOT") {
-    Some(value) => value,
-    None => "<none>",
-};
-const DOOM_GENERIC_CORE_SOURCE: &str = match option_env!("ARROST_DOOM_GENERIC_CORE_SOURCE") {
-    Some(value) => value,
-    None => "<none>",
-};
-const DOOM_GENERIC_CORE_OBJECT: &str = match option_env!("ARROST_DOOM_GENERIC_CORE_OBJECT") {
-    Some(value) => value,
-    None => "<none>",
-};
-const DOOM_GENERIC_CORE_SIZE: &str = match option_env!("ARROST_DOOM_GENERIC_CORE_SIZE") {
-    Some(value) => value,
-    None => "0",
-};
-const DOOM_GENERIC_CORE_READY: &str = match option_env!("ARROST_DOOM_GENERIC_CORE_READY") {
-    Some(value) => value,
-    None => "false",
-};
-const DOOM_GENERIC_PORT_OBJECT: &str = match option_env!("ARROST_DOOM_GENERIC_PORT_OBJECT") {
-    Some(value) => value,
-    None => "<none>",
-};
-const DOOM_GENERIC_PORT_SIZE: &str = match option_env!("ARROST_DOOM_GENERIC_PORT_SIZE") {
-    Some(value) => value,
-    None => "0",
-};
-const DOOM_GENERIC_PORT_READY: &str = match option_env!("ARROST_DOOM_GENERIC_PORT_READY") {
-    Some(value) => value,
-    None => "false",
-};
-const DOOM_WAD_HINT: &str = match option_env!("ARROST_DOOM_WAD_HINT") {
-    Some(value) => value,
-    None => "<none>",
-};
-const DOOM_WAD_PRESENT: &str = match option_env!("ARROST_DOOM_WAD_PRESENT") {
-    Some(value) => value,
-    None => "false",
-};
 
+use arch::x86_64::{cpuid, fpu};
 use bootloader_api::{BootInfo, BootloaderConfig, config::Mapping, entry_point};
 use core::alloc::Layout;
 use core::panic::PanicInfo;
-use x86_64::instructions::hlt;
+use mem::leak::{self, AllocTag};
 
 // kernel/src/main.rs: bootloader setup required by M2 memory management.
 pub static BOOTLOADER_CONFIG: BootloaderConfig = {
@@ -118,8 +71,18 @@ pub static BOOTLOADER_CONFIG: BootloaderConfig = {
 entry_point!(kernel_main, config = &BOOTLOADER_CONFIG);
 
 fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
+    arch::x86_64::stack::paint_boot_stack(
+        boot_info.kernel_stack_bottom,
+        boot_info.kernel_stack_len,
+    );
+    bootchart::mark("entry");
     serial::init();
+    log::init();
+    bootchart::mark("serial");
+    #[cfg(feature = "desktop")]
     let gfx_report = gfx::init(boot_info);
+    #[cfg(feature = "desktop")]
+    bootchart::mark("gfx");
     print_boot_logo();
     serial::write_line("kernel entry reached");
     serial::write_line("ArrOSt booting...");
@@ -127,15 +90,40 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
         "Version: {}.{}.{}\n",
         VERSION_MAJOR, VERSION_MINOR, VERSION_BUILD
     ));
-    match boot_info.ramdisk_addr.into_option() {
+    features::log_boot_line();
+    let cpu = cpuid::init();
+    cpuid::log_boot_line(&cpu);
+    let fpu = fpu::init();
+    serial::write_fmt(format_args!(
+        "FPU: mode={} xcr0={:#x} area_bytes={}\n",
+        fpu.mode.as_str(),
+        fpu.xcr0,
+        fpu.area_bytes
+    ));
+    let ramdisk = match boot_info.ramdisk_addr.into_option() {
         Some(addr) => {
             serial::write_fmt(format_args!(
                 "Ramdisk: present addr={:#018x} len={} bytes\n",
                 addr, boot_info.ramdisk_len
             ));
+            // SAFETY: the bootloader maps the ramdisk at `addr` for `ramdisk_len` bytes and never
+            // reclaims it.
+            Some(unsafe {
+                core::slice::from_raw_parts(addr as *const u8, boot_info.ramdisk_len as usize)
+            })
         }
-        None => serial::write_line("Ramdisk: absent"),
-    }
+        None => {
+            serial::write_line("Ramdisk: absent");
+            None
+        }
+    };
+    let manifest = artifacts::init(ramdisk);
+    serial::write_fmt(format_args!(
+        "Artifacts: manifest={} entries={} skipped={}\n",
+        manifest.state.as_str(),
+        manifest.entries,
+        manifest.skipped
+    ));
 
     match mem::init(boot_info) {
         Ok(report) => {
@@ -147,8 +135,16 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
                 report.stats.total_mib(),
             ));
             serial::write_fmt(format_args!(
-                "Paging: phys_offset={:#018x} l4_frame={:#018x} usable_frames={}\n",
-                report.physical_memory_offset, report.level_4_frame, report.usable_frames,
+                "Paging: phys_offset={:#018x} l4_frame={:#018x} usable_frames={} physmap_pages=(4k:{} 2m:{} 1g:{}) fb_pages=(4k:{} 2m:{}) promoted_2m={}\n",
+                report.physical_memory_offset,
+                report.level_4_frame,
+                report.usable_frames,
+                report.hugepages.physmap.pages_4k,
+                report.hugepages.physmap.pages_2m,
+                report.hugepages.physmap.pages_1g,
+                report.hugepages.framebuffer.pages_4k,
+                report.hugepages.framebuffer.pages_2m,
+                report.hugepages.promoted,
             ));
             serial::write_fmt(format_args!(
                 "Heap: mapped={:#018x}..{:#018x} size={} KiB pages={} guard_low={:#018x} guard_high={:#018x}\n",
@@ -159,6 +155,14 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
                 report.guard_low,
                 report.guard_high,
             ));
+            serial::write_fmt(format_args!(
+                "User heaps: window={:#018x}..{:#018x} slots={} slot={} KiB pages={}\n",
+                report.user_heap_start,
+                report.user_heap_end_exclusive,
+                mem::userheap::SLOTS,
+                mem::userheap::SLOT_BYTES / 1024,
+                report.user_heap_pages,
+            ));
             serial::write_fmt(format_args!(
                 "Alloc smoke: box={:#x} vec_len={} checksum={} sample_heap_phys={:#018x}\n",
                 report.alloc_box_value,
@@ -166,26 +170,36 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
                 report.alloc_checksum,
                 report.sample_heap_phys_addr,
             ));
+            serial::write_fmt(format_args!(
+                "DMA: pool={:#018x} size={} KiB\n",
+                report.dma_pool_phys,
+                report.dma_pool_bytes / 1024,
+            ));
         }
         Err(error) => {
             serial::write_fmt(format_args!("Memory init failed: {error}\n"));
             halt_loop();
         }
     }
+    bootchart::mark("mem");
 
-    let gfx_double_buffer = gfx::try_enable_backbuffer();
-    serial::write_fmt(format_args!(
-        "Gfx: backend={} ready={} {}x{} stride={} bpp={} fmt={} windows={}\n",
-        gfx_report.backend,
-        gfx_report.ready,
-        gfx_report.width,
-        gfx_report.height,
-        gfx_report.stride,
-        gfx_report.bytes_per_pixel,
-        gfx_report.pixel_format,
-        gfx_report.windows
-    ));
-    serial::write_fmt(format_args!("Gfx: double_buffer={}\n", gfx_double_buffer));
+    #[cfg(feature = "desktop")]
+    {
+        let gfx_double_buffer = gfx::try_enable_backbuffer();
+        serial::write_fmt(format_args!(
+            "Gfx: backend={} ready={} {}x{} stride={} bpp={} fmt={} windows={}\n",
+            gfx_report.backend,
+            gfx_report.ready,
+            gfx_report.width,
+            gfx_report.height,
+            gfx_report.stride,
+            gfx_report.bytes_per_pixel,
+            gfx_report.pixel_format,
+            gfx_report.windows
+        ));
+        serial::write_fmt(format_args!("Gfx: double_buffer={}\n", gfx_double_buffer));
+        bootchart::mark("backbuffer");
+    }
 
     keyboard::init();
     let irq = arch::x86_64::interrupts::init();
@@ -206,13 +220,23 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
         "Mouse: backend={} ready={} ack={:#04x}/{:#04x}\n",
         irq.mouse_backend, irq.mouse_ready, irq.mouse_ack_defaults, irq.mouse_ack_enable
     ));
+    bootchart::mark_timer_started("interrupts");
 
     time::set_heartbeat(false);
-    let audio_report = audio::init();
-    serial::write_fmt(format_args!(
-        "Audio: backend={} ready={} detail={}\n",
-        audio_report.backend, audio_report.ready, audio_report.detail
-    ));
+    #[cfg(feature = "audio")]
+    {
+        let audio_report = audio::init();
+        serial::write_fmt(format_args!(
+            "Audio: backend={} ready={} detail={} rate={} channels={} format={}\n",
+            audio_report.backend,
+            audio_report.ready,
+            audio_report.detail,
+            audio_report.rate_hz,
+            audio_report.channels,
+            audio_report.format
+        ));
+        bootchart::mark("audio");
+    }
     serial::write_fmt(format_args!(
         "Keyboard: set1 decoder ready queue_overflow={} event_overflow={}\n",
         keyboard::overflow_count(),
@@ -232,65 +256,74 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
         storage_report.capacity_sectors,
         storage_report.capacity_bytes
     ));
-
-    let net_report = net::init();
+    let kv_report = storage::kvstore::init();
     serial::write_fmt(format_args!(
-        "Net: backend={} cfg={} ready={} io={:#06x} pci={:02x}:{:02x}.{} devid={:#06x} mac={:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} ip={}.{}.{}.{}\n",
-        net_report.backend,
-        net_report.config_source,
-        net_report.ready,
-        net_report.io_base,
-        net_report.pci_bus,
-        net_report.pci_device,
-        net_report.pci_function,
-        net_report.pci_device_id,
-        net_report.mac[0],
-        net_report.mac[1],
-        net_report.mac[2],
-        net_report.mac[3],
-        net_report.mac[4],
-        net_report.mac[5],
-        net_report.ipv4[0],
-        net_report.ipv4[1],
-        net_report.ipv4[2],
-        net_report.ipv4[3]
+        "KV: state={} first_sector={} generation={} entries={} bad_slots={} boot_count={} last_crash={}\n",
+        kv_report.state.as_str(),
+        kv_report.first_sector,
+        kv_report.generation,
+        kv_report.entries,
+        kv_report.bad_slots,
+        kv_report.boot_count,
+        kv_report
+            .last_crash
+            .as_ref()
+            .map_or("none", storage::kvstore::Value::as_str)
     ));
+    bootchart::mark("storage");
+
+    #[cfg(feature = "net")]
+    {
+        let net_report = net::init();
+        serial::write_fmt(format_args!(
+            "Net: backend={} cfg={} ready={} io={:#06x} pci={:02x}:{:02x}.{} devid={:#06x} mac={:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} ip={}.{}.{}.{} mtu={}{}\n",
+            net_report.backend,
+            net_report.config_source,
+            net_report.ready,
+            net_report.io_base,
+            net_report.pci_bus,
+            net_report.pci_device,
+            net_report.pci_function,
+            net_report.pci_device_id,
+            net_report.mac[0],
+            net_report.mac[1],
+            net_report.mac[2],
+            net_report.mac[3],
+            net_report.mac[4],
+            net_report.mac[5],
+            net_report.ipv4[0],
+            net_report.ipv4[1],
+            net_report.ipv4[2],
+            net_report.ipv4[3],
+            net_report.mtu,
+            if net_report.device_mtu.is_some() {
+                " (device)"
+            } else {
+                ""
+            }
+        ));
+        bootchart::mark("net");
+    }
 
     let fs_report = fs::init();
     serial::write_fmt(format_args!(
-        "FS: backend={} storage_backed={} files={} used_bytes={} capacity_files={} capacity_file_bytes={}\n",
+        "FS: backend={} storage_backed={} files={} used_bytes={} capacity_files={} capacity_file_bytes={} crc_ok={} crc_mismatch={} crc_unchecked={}\n",
         fs_report.backend,
         fs_report.storage_backed,
         fs_report.file_count,
         fs_report.used_bytes,
         fs_report.max_files,
-        fs_report.max_file_bytes
-    ));
-    serial::write_fmt(format_args!(
-        "Doom: app={} rust_artifact={} rust_artifact_size={} c_backend_size={} c_backend_ready={} c_backend_object={}\n",
-        DOOM_APP,
-        DOOM_ARTIFACT_HINT,
-        DOOM_ARTIFACT_SIZE,
-        DOOM_C_BACKEND_SIZE,
-        DOOM_C_BACKEND_READY,
-        DOOM_C_BACKEND_OBJECT
-    ));
-    serial::write_fmt(format_args!(
-        "DoomGeneric: ready={} root={} core={} core_obj={} core_size={} core_ready={} port={} port_size={} port_ready={} wad={} wad_present={}\n",
-        DOOM_GENERIC_READY,
-        DOOM_GENERIC_ROOT,
-        DOOM_GENERIC_CORE_SOURCE,
-        DOOM_GENERIC_CORE_OBJECT,
-        DOOM_GENERIC_CORE_SIZE,
-        DOOM_GENERIC_CORE_READY,
-        DOOM_GENERIC_PORT_OBJECT,
-        DOOM_GENERIC_PORT_SIZE,
-        DOOM_GENERIC_PORT_READY,
-        DOOM_WAD_HINT,
-        DOOM_WAD_PRESENT
+        fs_report.max_file_bytes,
+        fs_report.integrity.verified,
+        fs_report.integrity.mismatched,
+        fs_report.integrity.unchecked
     ));
+    bootchart::mark("fs");
+    #[cfg(feature = "doom")]
+    doom::log_build_info();
 
     shell::init();
+    bootchart::mark("shell");
     let proc_report = proc::init();
     serial::write_fmt(format_args!(
         "Scheduler: tasks={} init_pid={} sh_pid={} scripted_input_bytes={}\n",
@@ -299,6 +332,10 @@ fn kernel_main(boot_info: &'static mut BootInfo) -> ! {
         proc_report.shell_pid,
         proc_report.scripted_input_bytes
     ));
+    bootchart::mark("proc");
+    bootchart::log_summary();
+    sysinfo::log_report();
+    shell::autostart();
 
     run_loop()
 }
@@ -328,6 +365,7 @@ fn print_boot_logo() {
 fn panic(info: &PanicInfo<'_>) -> ! {
     serial::write_line("KERNEL PANIC");
     serial::write_fmt(format_args!("{info}\n"));
+    storage::kvstore::record_panic();
     halt_loop()
 }
 
@@ -343,13 +381,27 @@ fn alloc_error(layout: Layout) -> ! {
 
 fn run_loop() -> ! {
     loop {
-        shell::poll();
-        gfx::poll();
-        net::poll();
+        leak::tagged(AllocTag::Shell, input_bus::poll);
+        leak::tagged(AllocTag::Shell, shell::poll);
+        #[cfg(feature = "desktop")]
+        leak::tagged(AllocTag::Ui, gfx::poll);
+        #[cfg(feature = "net")]
+        leak::tagged(AllocTag::Net, net::poll);
         let ticks = time::ticks();
-        doom::poll(ticks);
-        audio::poll(ticks);
-        proc::run_once(ticks);
+        #[cfg(feature = "doom")]
+        {
+            proc::trace_doom_poll(ticks);
+            leak::tagged(AllocTag::Doom, || doom::poll(ticks));
+        }
+        #[cfg(feature = "audio")]
+        leak::tagged(AllocTag::Audio, || audio::poll(ticks));
+        leak::tagged(AllocTag::Proc, || {
+            proc::run_once(ticks);
+            proc::executor::poll(ticks);
+        });
+        #[cfg(feature = "net")]
+        leak::tagged(AllocTag::Telemetry, || telemetry::poll(ticks));
+        mem::poison::poll(ticks);
         if time::heartbeat_enabled()
             && let Some(seconds) = time::poll_elapsed_second()
         {
@@ -359,7 +411,21 @@ fn run_loop() -> ! {
                 time::ticks()
             ));
         }
-        hlt();
+        let deadline = [
+            proc::next_deadline(ticks),
+            proc::executor::next_deadline(ticks),
+            #[cfg(feature = "doom")]
+            doom::next_deadline(ticks),
+            #[cfg(feature = "audio")]
+            audio::next_deadline(ticks),
+            #[cfg(feature = "net")]
+            telemetry::next_deadline(ticks),
+            time::heartbeat_deadline(),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        time::idle_until(deadline);
     }
 }
 
@@ -0,0 +1,123 @@
+// kernel/src/mem/dma.rs: physically contiguous DMA pool carved from usable frames at boot.
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub const DMA_POOL_BYTES: usize = 1024 * 1024;
+
+#[derive(Clone, Copy)]
+pub struct DmaRegion {
+    virt: usize,
+    phys: u64,
+    len: usize,
+}
+
+impl DmaRegion {
+    pub const fn virt(self) -> usize {
+        self.virt
+    }
+
+    pub const fn phys(self) -> u64 {
+        self.phys
+    }
+}
+
+struct DmaPool {
+    virt: usize,
+    phys: u64,
+    len: usize,
+    next: usize,
+}
+
+impl DmaPool {
+    const fn new() -> Self {
+        Self {
+            virt: 0,
+            phys: 0,
+            len: 0,
+            next: 0,
+        }
+    }
+
+    fn allocate(&mut self, len: usize, align: usize) -> Option<DmaRegion> {
+        if self.len == 0 || len == 0 || !align.is_power_of_two() {
+            return None;
+        }
+        // Alignment is applied to the physical address; the pool base is page-aligned in both
+        // spaces, so virtual and physical offsets stay identical.
+        let start = self.next.checked_add(align - 1)? & !(align - 1);
+        let end = start.checked_add(len)?;
+        if end > self.len {
+            return None;
+        }
+        self.next = end;
+        Some(DmaRegion {
+            virt: self.virt + start,
+            phys: self.phys + start as u64,
+            len,
+        })
+    }
+}
+
+struct DmaCell(UnsafeCell<DmaPool>);
+
+// SAFETY: access is serialized through `DMA_LOCK`.
+unsafe impl Sync for DmaCell {}
+
+static DMA_LOCK: SpinLock = SpinLock::new();
+static DMA_POOL: DmaCell = DmaCell(UnsafeCell::new(DmaPool::new()));
+
+/// Installs the pool; `virt..virt+len` must map `phys..phys+len` contiguously and stay reserved.
+pub(super) fn install(virt: usize, phys: u64, len: usize) {
+    with_pool(|pool| {
+        *pool = DmaPool::new();
+        pool.virt = virt;
+        pool.phys = phys;
+        pool.len = len;
+    });
+}
+
+/// Returns zeroed, physically contiguous memory. Regions are never freed; drivers allocate once.
+pub fn alloc_zeroed(len: usize, align: usize) -> Option<DmaRegion> {
+    let region = with_pool(|pool| pool.allocate(len, align))?;
+    // SAFETY: the region was just carved from the reserved pool and is not shared yet.
+    unsafe {
+        core::ptr::write_bytes(region.virt as *mut u8, 0, region.len);
+    }
+    Some(region)
+}
+
+fn with_pool<R>(f: impl FnOnce(&mut DmaPool) -> R) -> R {
+    let _guard = DMA_LOCK.lock();
+    // SAFETY: `DMA_LOCK` serializes mutable access to the pool bookkeeping.
+    unsafe { f(&mut *DMA_POOL.0.get()) }
+}
+
+struct SpinLock {
+    locked: AtomicBool,
+}
+
+impl SpinLock {
+    const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    fn lock(&self) -> SpinLockGuard<'_> {
+        while self.locked.swap(true, Ordering::Acquire) {
+            spin_loop();
+        }
+        SpinLockGuard { lock: self }
+    }
+}
+
+struct SpinLockGuard<'a> {
+    lock: &'a SpinLock,
+}
+
+impl Drop for SpinLockGuard<'_> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
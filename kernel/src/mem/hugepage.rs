@@ -0,0 +1,243 @@
+// kernel/src/mem/hugepage.rs: 2 MiB promotion of contiguous 4 KiB runs and mapping-size census.
+use super::PHYSICAL_MEMORY_OFFSET;
+use core::cell::UnsafeCell;
+use core::sync::atomic::Ordering;
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::page_table::PageTableEntry;
+use x86_64::structures::paging::{PageTable, PageTableFlags};
+use x86_64::{PhysAddr, VirtAddr};
+
+const SMALL_PAGE_SIZE: u64 = 4096;
+const HUGE_PAGE_SIZE: u64 = 2 * 1024 * 1024;
+const GIANT_PAGE_SIZE: u64 = 1024 * 1024 * 1024;
+const ENTRIES_PER_TABLE: usize = 512;
+/// Covers a 256 MiB framebuffer; larger regions keep their tail on 4 KiB pages.
+const MAX_PROMOTED: usize = 128;
+/// Hardware-maintained bits that may differ between otherwise identical leaf entries.
+const VOLATILE_FLAGS: PageTableFlags = PageTableFlags::ACCESSED.union(PageTableFlags::DIRTY);
+
+#[derive(Clone, Copy, Default)]
+pub struct MappingCensus {
+    pub pages_4k: usize,
+    pub pages_2m: usize,
+    pub pages_1g: usize,
+}
+
+#[derive(Clone, Copy)]
+pub struct HugePageReport {
+    pub physmap: MappingCensus,
+    pub framebuffer: MappingCensus,
+    pub promoted: usize,
+}
+
+/// One promoted level-2 slot with both encodings, so `set_enabled` can flip back to the
+/// original (still intact) 4 KiB table for A/B measurements.
+#[derive(Clone, Copy)]
+struct Promotion {
+    virt: u64,
+    table_addr: PhysAddr,
+    table_flags: PageTableFlags,
+    huge_addr: PhysAddr,
+    huge_flags: PageTableFlags,
+}
+
+struct PromotionTable {
+    entries: [Option<Promotion>; MAX_PROMOTED],
+    len: usize,
+    enabled: bool,
+}
+
+struct PromotionCell(UnsafeCell<PromotionTable>);
+
+// SAFETY: promotions are recorded during single-threaded boot and toggled from the shell loop.
+unsafe impl Sync for PromotionCell {}
+
+static PROMOTIONS: PromotionCell = PromotionCell(UnsafeCell::new(PromotionTable {
+    entries: [None; MAX_PROMOTED],
+    len: 0,
+    enabled: true,
+}));
+
+fn with_promotions<R>(f: impl FnOnce(&mut PromotionTable) -> R) -> R {
+    // SAFETY: single-threaded kernel paths are the only callers.
+    unsafe { f(&mut *PROMOTIONS.0.get()) }
+}
+
+enum Level2<'a> {
+    Missing,
+    Giant,
+    Entry(&'a mut PageTableEntry),
+}
+
+fn table_at(phys: PhysAddr) -> &'static mut PageTable {
+    let offset = PHYSICAL_MEMORY_OFFSET.load(Ordering::Acquire);
+    // SAFETY: page-table frames are reachable through the bootloader physical-memory window.
+    unsafe { &mut *((offset + phys.as_u64()) as *mut PageTable) }
+}
+
+fn level_2_entry(virt: VirtAddr) -> Level2<'static> {
+    let (level_4_frame, _) = Cr3::read();
+    let level_4 = table_at(level_4_frame.start_address());
+    let l4_entry = &level_4[virt.p4_index()];
+    if !l4_entry.flags().contains(PageTableFlags::PRESENT) {
+        return Level2::Missing;
+    }
+    let level_3 = table_at(l4_entry.addr());
+    let l3_entry = &level_3[virt.p3_index()];
+    if !l3_entry.flags().contains(PageTableFlags::PRESENT) {
+        return Level2::Missing;
+    }
+    if l3_entry.flags().contains(PageTableFlags::HUGE_PAGE) {
+        return Level2::Giant;
+    }
+    let level_2 = table_at(l3_entry.addr());
+    Level2::Entry(&mut level_2[virt.p2_index()])
+}
+
+/// Returns the 2 MiB-aligned physical base when all 512 entries map one contiguous,
+/// equally-flagged run. Bit 7 on a 4 KiB entry is PAT, which a huge entry cannot express.
+fn contiguous_run(level_1: &PageTable) -> Option<(PhysAddr, PageTableFlags)> {
+    let first = &level_1[0];
+    let base = first.addr();
+    let flags = first.flags() - VOLATILE_FLAGS;
+    if !flags.contains(PageTableFlags::PRESENT)
+        || flags.contains(PageTableFlags::HUGE_PAGE)
+        || !base.is_aligned(HUGE_PAGE_SIZE)
+    {
+        return None;
+    }
+    for (index, entry) in level_1.iter().enumerate().take(ENTRIES_PER_TABLE) {
+        if entry.flags() - VOLATILE_FLAGS != flags
+            || entry.addr().as_u64() != base.as_u64() + index as u64 * SMALL_PAGE_SIZE
+        {
+            return None;
+        }
+    }
+    Some((base, flags))
+}
+
+/// Walks `start..start+len`, promoting fully covered 2 MiB slots, and counts the resulting
+/// leaf mappings by size.
+fn scan_region(start: u64, len: u64) -> (MappingCensus, usize) {
+    let mut census = MappingCensus::default();
+    let mut promoted = 0usize;
+    let end = start.saturating_add(len);
+    let mut chunk = start & !(HUGE_PAGE_SIZE - 1);
+    let mut last_giant = None;
+
+    while chunk < end {
+        let Ok(virt) = VirtAddr::try_new(chunk) else {
+            break;
+        };
+        let covered = chunk >= start && chunk + HUGE_PAGE_SIZE <= end;
+        match level_2_entry(virt) {
+            Level2::Missing => {}
+            Level2::Giant => {
+                let base = chunk & !(GIANT_PAGE_SIZE - 1);
+                if last_giant != Some(base) {
+                    census.pages_1g += 1;
+                    last_giant = Some(base);
+                }
+            }
+            Level2::Entry(entry) if !entry.flags().contains(PageTableFlags::PRESENT) => {}
+            Level2::Entry(entry) if entry.flags().contains(PageTableFlags::HUGE_PAGE) => {
+                census.pages_2m += 1;
+            }
+            Level2::Entry(entry) => {
+                let level_1 = table_at(entry.addr());
+                let run = if covered {
+                    contiguous_run(level_1)
+                } else {
+                    None
+                };
+                if run.is_some_and(|run| record_promotion(chunk, entry, run)) {
+                    census.pages_2m += 1;
+                    promoted += 1;
+                } else {
+                    let first = (start.max(chunk) - chunk) / SMALL_PAGE_SIZE;
+                    let last = (end.min(chunk + HUGE_PAGE_SIZE) - chunk).div_ceil(SMALL_PAGE_SIZE);
+                    census.pages_4k += level_1
+                        .iter()
+                        .take(last as usize)
+                        .skip(first as usize)
+                        .filter(|entry| entry.flags().contains(PageTableFlags::PRESENT))
+                        .count();
+                }
+            }
+        }
+        chunk = chunk.saturating_add(HUGE_PAGE_SIZE);
+    }
+    (census, promoted)
+}
+
+fn record_promotion(
+    virt: u64,
+    entry: &mut PageTableEntry,
+    (huge_addr, leaf_flags): (PhysAddr, PageTableFlags),
+) -> bool {
+    with_promotions(|table| {
+        if table.len >= MAX_PROMOTED {
+            return false;
+        }
+        let promotion = Promotion {
+            virt,
+            table_addr: entry.addr(),
+            table_flags: entry.flags(),
+            huge_addr,
+            huge_flags: leaf_flags | PageTableFlags::HUGE_PAGE,
+        };
+        entry.set_addr(promotion.huge_addr, promotion.huge_flags);
+        table.entries[table.len] = Some(promotion);
+        table.len += 1;
+        true
+    })
+}
+
+/// Promotes the physical-memory window and framebuffer where 2 MiB alignment allows.
+pub fn promote(physmap: (u64, u64), framebuffer: Option<(u64, u64)>) -> HugePageReport {
+    let (physmap, physmap_promoted) = scan_region(physmap.0, physmap.1);
+    let (framebuffer, framebuffer_promoted) = match framebuffer {
+        Some((start, len)) => scan_region(start, len),
+        None => (MappingCensus::default(), 0),
+    };
+    x86_64::instructions::tlb::flush_all();
+    HugePageReport {
+        physmap,
+        framebuffer,
+        promoted: physmap_promoted + framebuffer_promoted,
+    }
+}
+
+/// Switches every promoted slot between its 2 MiB and original 4 KiB encoding.
+/// Returns the number of slots rewritten.
+pub fn set_enabled(enabled: bool) -> usize {
+    let rewritten = with_promotions(|table| {
+        if table.enabled == enabled {
+            return 0;
+        }
+        table.enabled = enabled;
+        let mut rewritten = 0usize;
+        for promotion in table.entries[..table.len].iter().flatten() {
+            let Level2::Entry(entry) = level_2_entry(VirtAddr::new(promotion.virt)) else {
+                continue;
+            };
+            if enabled {
+                entry.set_addr(promotion.huge_addr, promotion.huge_flags);
+            } else {
+                entry.set_addr(promotion.table_addr, promotion.table_flags);
+            }
+            rewritten += 1;
+        }
+        rewritten
+    });
+    x86_64::instructions::tlb::flush_all();
+    rewritten
+}
+
+pub fn enabled() -> bool {
+    with_promotions(|table| table.enabled)
+}
+
+pub fn promoted_count() -> usize {
+    with_promotions(|table| table.len)
+}
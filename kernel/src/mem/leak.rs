@@ -0,0 +1,219 @@
+// kernel/src/mem/leak.rs: optional heap allocation tracker (live count/bytes per call-site tag).
+// Built in only when the kernel is compiled with `ARROST_LEAK_TRACK` set; otherwise every hook
+// is a no-op and the table is zero-sized.
+use super::GLOBAL_ALLOCATOR;
+
+pub const ENABLED: bool = option_env!("ARROST_LEAK_TRACK").is_some();
+/// Live allocations tracked at once; open addressing keeps the load under 3/4.
+const CAPACITY: usize = 4096;
+const MAX_LOAD: usize = CAPACITY / 4 * 3;
+const MASK: usize = CAPACITY - 1;
+/// The table only takes up space in tracking builds.
+const TABLE_LEN: usize = if ENABLED { CAPACITY } else { 0 };
+
+/// Call-site bucket an allocation is charged to; the run loop tags each subsystem's poll.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AllocTag {
+    Boot,
+    Shell,
+    Ui,
+    Net,
+    Doom,
+    Audio,
+    Proc,
+    Telemetry,
+}
+
+impl AllocTag {
+    pub const ALL: [Self; 8] = [
+        Self::Boot,
+        Self::Shell,
+        Self::Ui,
+        Self::Net,
+        Self::Doom,
+        Self::Audio,
+        Self::Proc,
+        Self::Telemetry,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Boot => "boot",
+            Self::Shell => "shell",
+            Self::Ui => "ui",
+            Self::Net => "net",
+            Self::Doom => "doom",
+            Self::Audio => "audio",
+            Self::Proc => "proc",
+            Self::Telemetry => "telemetry",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct TagUsage {
+    pub live_count: u64,
+    pub live_bytes: u64,
+    pub allocs: u64,
+    pub frees: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct LeakSnapshot {
+    pub tags: [TagUsage; AllocTag::ALL.len()],
+    /// Allocations made while the table was full; their frees show up as `unknown_frees`.
+    pub untracked: u64,
+    pub unknown_frees: u64,
+}
+
+impl LeakSnapshot {
+    pub fn usage(&self, tag: AllocTag) -> TagUsage {
+        self.tags[tag as usize]
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    /// 0 marks an empty slot; the heap never hands out address 0.
+    ptr: usize,
+    size: usize,
+    tag: AllocTag,
+}
+
+impl Slot {
+    const EMPTY: Self = Self {
+        ptr: 0,
+        size: 0,
+        tag: AllocTag::Boot,
+    };
+}
+
+pub(super) struct Tracker {
+    slots: [Slot; TABLE_LEN],
+    len: usize,
+    current: AllocTag,
+    snapshot: LeakSnapshot,
+    mark: Option<LeakSnapshot>,
+}
+
+impl Tracker {
+    pub(super) const fn new() -> Self {
+        Self {
+            slots: [Slot::EMPTY; TABLE_LEN],
+            len: 0,
+            current: AllocTag::Boot,
+            snapshot: LeakSnapshot {
+                tags: [TagUsage {
+                    live_count: 0,
+                    live_bytes: 0,
+                    allocs: 0,
+                    frees: 0,
+                }; AllocTag::ALL.len()],
+                untracked: 0,
+                unknown_frees: 0,
+            },
+            mark: None,
+        }
+    }
+
+    /// Tag allocations are charged to right now; also kept when only heap poisoning is on.
+    pub(super) const fn current(&self) -> AllocTag {
+        self.current
+    }
+
+    pub(super) fn record_alloc(&mut self, ptr: usize, size: usize) {
+        if !ENABLED {
+            return;
+        }
+        if self.len >= MAX_LOAD {
+            self.snapshot.untracked = self.snapshot.untracked.saturating_add(1);
+            return;
+        }
+        let mut index = slot_index(ptr);
+        while self.slots[index].ptr != 0 {
+            index = (index + 1) & MASK;
+        }
+        self.slots[index] = Slot {
+            ptr,
+            size,
+            tag: self.current,
+        };
+        self.len += 1;
+        let usage = &mut self.snapshot.tags[self.current as usize];
+        usage.live_count = usage.live_count.saturating_add(1);
+        usage.live_bytes = usage.live_bytes.saturating_add(size as u64);
+        usage.allocs = usage.allocs.saturating_add(1);
+    }
+
+    pub(super) fn record_free(&mut self, ptr: usize) {
+        if !ENABLED {
+            return;
+        }
+        let mut index = slot_index(ptr);
+        loop {
+            let slot = self.slots[index];
+            if slot.ptr == 0 {
+                self.snapshot.unknown_frees = self.snapshot.unknown_frees.saturating_add(1);
+                return;
+            }
+            if slot.ptr == ptr {
+                break;
+            }
+            index = (index + 1) & MASK;
+        }
+        let slot = self.slots[index];
+        let usage = &mut self.snapshot.tags[slot.tag as usize];
+        usage.live_count = usage.live_count.saturating_sub(1);
+        usage.live_bytes = usage.live_bytes.saturating_sub(slot.size as u64);
+        usage.frees = usage.frees.saturating_add(1);
+        self.remove_at(index);
+    }
+
+    /// Backward-shift deletion: pulls later probe-chain members into the hole so lookups never
+    /// need tombstones.
+    fn remove_at(&mut self, index: usize) {
+        let mut hole = index;
+        let mut next = (hole + 1) & MASK;
+        while self.slots[next].ptr != 0 {
+            let home = slot_index(self.slots[next].ptr);
+            if next.wrapping_sub(home) & MASK >= next.wrapping_sub(hole) & MASK {
+                self.slots[hole] = self.slots[next];
+                hole = next;
+            }
+            next = (next + 1) & MASK;
+        }
+        self.slots[hole] = Slot::EMPTY;
+        self.len -= 1;
+    }
+}
+
+fn slot_index(ptr: usize) -> usize {
+    ((ptr >> 3).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32) & MASK
+}
+
+/// Charges every allocation made inside `f` to `tag`; frees are credited to the tag the
+/// allocation was made under, wherever they happen.
+pub fn tagged<R>(tag: AllocTag, f: impl FnOnce() -> R) -> R {
+    if !ENABLED && !super::poison::ENABLED {
+        return f();
+    }
+    let previous = GLOBAL_ALLOCATOR
+        .with_lock(|allocator| core::mem::replace(&mut allocator.tracker.current, tag));
+    let result = f();
+    GLOBAL_ALLOCATOR.with_lock(|allocator| allocator.tracker.current = previous);
+    result
+}
+
+pub fn snapshot() -> LeakSnapshot {
+    GLOBAL_ALLOCATOR.with_lock(|allocator| allocator.tracker.snapshot)
+}
+
+/// Stores the current counters as the baseline `mem leaks` diffs against.
+pub fn mark() {
+    GLOBAL_ALLOCATOR
+        .with_lock(|allocator| allocator.tracker.mark = Some(allocator.tracker.snapshot));
+}
+
+pub fn baseline() -> Option<LeakSnapshot> {
+    GLOBAL_ALLOCATOR.with_lock(|allocator| allocator.tracker.mark)
+}
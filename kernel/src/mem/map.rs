@@ -0,0 +1,343 @@
+// kernel/src/mem/map.rs: `mem map`, the bootloader memory map with region types, the ranges the
+// kernel knows it occupies and the frames each boot-time consumer took.
+use crate::serial;
+use crate::shell::Status;
+use bootloader_api::info::{MemoryRegion, MemoryRegionKind};
+use core::cell::UnsafeCell;
+
+const PAGE_BYTES: u64 = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RegionType {
+    Usable,
+    /// Page tables, boot info, kernel image and ramdisk placed by the bootloader.
+    Bootloader,
+    Reserved,
+    AcpiReclaim,
+    AcpiNvs,
+    Mmio,
+    /// UEFI runtime services code/data and PAL code.
+    Firmware,
+    Unusable,
+    Other,
+}
+
+impl RegionType {
+    /// UEFI memory types (UEFI spec table 7.10) and E820 types as the bootloader passes them.
+    pub const fn from_kind(kind: MemoryRegionKind) -> Self {
+        match kind {
+            MemoryRegionKind::Usable => Self::Usable,
+            MemoryRegionKind::Bootloader => Self::Bootloader,
+            MemoryRegionKind::UnknownUefi(code) => match code {
+                0 => Self::Reserved,
+                5 | 6 | 13 => Self::Firmware,
+                8 => Self::Unusable,
+                9 => Self::AcpiReclaim,
+                10 => Self::AcpiNvs,
+                11 | 12 => Self::Mmio,
+                _ => Self::Other,
+            },
+            MemoryRegionKind::UnknownBios(code) => match code {
+                2 => Self::Reserved,
+                3 => Self::AcpiReclaim,
+                4 => Self::AcpiNvs,
+                5 => Self::Unusable,
+                _ => Self::Other,
+            },
+            _ => Self::Other,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Usable => "usable",
+            Self::Bootloader => "bootloader",
+            Self::Reserved => "reserved",
+            Self::AcpiReclaim => "acpi_reclaim",
+            Self::AcpiNvs => "acpi_nvs",
+            Self::Mmio => "mmio",
+            Self::Firmware => "firmware",
+            Self::Unusable => "unusable",
+            Self::Other => "other",
+        }
+    }
+}
+
+/// Physical ranges the kernel knows about, shown next to the region that holds them.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Annotation {
+    KernelImage,
+    Ramdisk,
+    Framebuffer,
+    DmaPool,
+}
+
+impl Annotation {
+    pub const ALL: [Self; 4] = [
+        Self::KernelImage,
+        Self::Ramdisk,
+        Self::Framebuffer,
+        Self::DmaPool,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::KernelImage => "kernel_image",
+            Self::Ramdisk => "ramdisk",
+            Self::Framebuffer => "framebuffer",
+            Self::DmaPool => "dma_pool",
+        }
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Boot-time users of the frame allocator.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Consumer {
+    KernelHeap,
+    UserHeaps,
+    /// Intermediate tables `map_to` allocated for the heap mappings.
+    PageTables,
+    /// The pool plus frames skipped while looking for a contiguous run.
+    DmaPool,
+}
+
+impl Consumer {
+    pub const ALL: [Self; 4] = [
+        Self::KernelHeap,
+        Self::UserHeaps,
+        Self::PageTables,
+        Self::DmaPool,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::KernelHeap => "kernel_heap",
+            Self::UserHeaps => "user_heaps",
+            Self::PageTables => "page_tables",
+            Self::DmaPool => "dma_pool",
+        }
+    }
+
+    const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+struct MemoryMap {
+    regions: &'static [MemoryRegion],
+    /// Physical `[start, end)` per `Annotation`; empty when absent.
+    annotations: [(u64, u64); Annotation::ALL.len()],
+    consumers: [u64; Consumer::ALL.len()],
+    /// Where the boot frame allocator stopped: regions before `alloc_region` are used up to
+    /// their end, and `alloc_region` itself up to `alloc_next`.
+    alloc_region: usize,
+    alloc_next: u64,
+    min_alloc_addr: u64,
+}
+
+struct MapCell(UnsafeCell<MemoryMap>);
+
+// SAFETY: written only by `mem::init` during single-threaded early boot, read afterwards.
+unsafe impl Sync for MapCell {}
+
+static MAP: MapCell = MapCell(UnsafeCell::new(MemoryMap {
+    regions: &[],
+    annotations: [(0, 0); Annotation::ALL.len()],
+    consumers: [0; Consumer::ALL.len()],
+    alloc_region: 0,
+    alloc_next: 0,
+    min_alloc_addr: 0,
+}));
+
+fn with_map_mut<R>(f: impl FnOnce(&mut MemoryMap) -> R) -> R {
+    // SAFETY: see `MapCell`; only `mem::init` mutates, before any reader runs.
+    unsafe { f(&mut *MAP.0.get()) }
+}
+
+fn with_map<R>(f: impl FnOnce(&MemoryMap) -> R) -> R {
+    // SAFETY: see `MapCell`.
+    unsafe { f(&*MAP.0.get()) }
+}
+
+pub(super) fn record_regions(regions: &'static [MemoryRegion], min_alloc_addr: u64) {
+    with_map_mut(|map| {
+        map.regions = regions;
+        map.min_alloc_addr = min_alloc_addr;
+    });
+}
+
+pub(super) fn annotate(annotation: Annotation, phys_start: u64, bytes: u64) {
+    with_map_mut(|map| {
+        map.annotations[annotation.index()] = (phys_start, phys_start.saturating_add(bytes));
+    });
+}
+
+pub(super) fn record_frames(consumer: Consumer, frames: u64) {
+    with_map_mut(|map| {
+        let slot = &mut map.consumers[consumer.index()];
+        *slot = slot.saturating_add(frames);
+    });
+}
+
+pub(super) fn record_allocator_end(region_cursor: usize, next_addr: u64) {
+    with_map_mut(|map| {
+        map.alloc_region = region_cursor.saturating_sub(1);
+        map.alloc_next = next_addr;
+    });
+}
+
+impl MemoryMap {
+    /// Frames the boot allocator took from usable region `index`.
+    fn used_frames(&self, index: usize) -> u64 {
+        let region = self.regions[index];
+        if region.kind != MemoryRegionKind::Usable || index > self.alloc_region {
+            return 0;
+        }
+        let start = region
+            .start
+            .next_multiple_of(PAGE_BYTES)
+            .max(self.min_alloc_addr);
+        let end = if index == self.alloc_region {
+            self.alloc_next.min(region.end)
+        } else {
+            region.end
+        };
+        end.saturating_sub(start) / PAGE_BYTES
+    }
+
+    fn region_of(&self, phys: u64) -> Option<usize> {
+        self.regions
+            .iter()
+            .position(|region| region.start <= phys && phys < region.end)
+    }
+
+    fn log_annotations_in(&self, start: u64, end: u64) {
+        for annotation in Annotation::ALL {
+            let (from, to) = self.annotations[annotation.index()];
+            if from < to && from < end && start < to {
+                serial::write_fmt(format_args!(" [{}]", annotation.as_str()));
+            }
+        }
+    }
+}
+
+struct Size(u64);
+
+impl core::fmt::Display for Size {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const MIB: u64 = 1024 * 1024;
+        if self.0 >= MIB {
+            write!(f, "{} MiB", self.0 / MIB)
+        } else {
+            write!(f, "{} KiB", self.0.div_ceil(1024))
+        }
+    }
+}
+
+/// `mem map`: one line per region, then the annotated ranges and frames per consumer.
+pub fn log_map() {
+    with_map(|map| {
+        let usable_frames: u64 = map
+            .regions
+            .iter()
+            .filter(|region| region.kind == MemoryRegionKind::Usable)
+            .map(|region| region.end.saturating_sub(region.start) / PAGE_BYTES)
+            .sum();
+        serial::write_fmt(format_args!(
+            "mem: map regions={} usable_frames={usable_frames} alloc_next={:#x}\n",
+            map.regions.len(),
+            map.alloc_next
+        ));
+        for (index, region) in map.regions.iter().enumerate() {
+            let kind = RegionType::from_kind(region.kind);
+            serial::write_fmt(format_args!(
+                "mem: map #{index} {:#014x}-{:#014x} {} {}",
+                region.start,
+                region.end,
+                Size(region.end.saturating_sub(region.start)),
+                kind.as_str()
+            ));
+            match region.kind {
+                MemoryRegionKind::UnknownUefi(code) => {
+                    serial::write_fmt(format_args!(" (uefi {code})"))
+                }
+                MemoryRegionKind::UnknownBios(code) => {
+                    serial::write_fmt(format_args!(" (e820 {code})"))
+                }
+                _ => {}
+            }
+            if kind == RegionType::Usable {
+                serial::write_fmt(format_args!(" used_frames={}", map.used_frames(index)));
+            }
+            map.log_annotations_in(region.start, region.end);
+            serial::write_line("");
+        }
+        for annotation in Annotation::ALL {
+            let (start, end) = map.annotations[annotation.index()];
+            if start >= end {
+                serial::write_fmt(format_args!("mem: map {} absent\n", annotation.as_str()));
+                continue;
+            }
+            serial::write_fmt(format_args!(
+                "mem: map {} phys={start:#x}-{end:#x} size={}",
+                annotation.as_str(),
+                Size(end - start)
+            ));
+            match map.region_of(start) {
+                Some(index) => serial::write_fmt(format_args!(
+                    " region=#{index} {}\n",
+                    RegionType::from_kind(map.regions[index].kind).as_str()
+                )),
+                None => serial::write_line(" region=none"),
+            }
+        }
+        let total: u64 = map.consumers.iter().sum();
+        serial::write_str("mem: map consumers");
+        for consumer in Consumer::ALL {
+            serial::write_fmt(format_args!(
+                " {}={}",
+                consumer.as_str(),
+                map.consumers[consumer.index()]
+            ));
+        }
+        serial::write_fmt(format_args!(
+            " total={total} free_usable~{}\n",
+            usable_frames.saturating_sub(total)
+        ));
+    });
+}
+
+/// `mem map <virt>`: where a virtual address lands, for drivers whose `virt_to_phys` fails.
+pub fn log_lookup(text: &str) -> Status {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    let Ok(virt) = u64::from_str_radix(digits, 16) else {
+        return Status::usage("usage: mem map [<hex virt addr>]");
+    };
+    let Some(phys) = usize::try_from(virt).ok().and_then(super::virt_to_phys) else {
+        serial::write_fmt(format_args!(
+            "mem: map virt={virt:#x} not mapped (or not canonical)\n"
+        ));
+        return Status::Failed;
+    };
+    serial::write_fmt(format_args!("mem: map virt={virt:#x} phys={phys:#x}"));
+    with_map(|map| {
+        match map.region_of(phys) {
+            Some(index) => serial::write_fmt(format_args!(
+                " region=#{index} {}",
+                RegionType::from_kind(map.regions[index].kind).as_str()
+            )),
+            None => serial::write_str(" region=none"),
+        }
+        map.log_annotations_in(phys, phys + 1);
+    });
+    serial::write_line("");
+    Status::Ok
+}
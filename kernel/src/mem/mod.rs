@@ -1,4 +1,12 @@
 // kernel/src/mem/mod.rs: M2 memory management (frame allocator, paging, heap, smoke test).
+#[cfg(feature = "net")]
+pub mod dma;
+pub mod hugepage;
+pub mod leak;
+pub mod map;
+pub mod poison;
+pub mod userheap;
+
 use alloc::{boxed::Box, vec::Vec};
 use bootloader_api::{
     BootInfo,
@@ -13,7 +21,7 @@ use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use x86_64::registers::control::Cr3;
 use x86_64::structures::paging::{
     FrameAllocator, Mapper, Page, PageSize, PageTable, PageTableFlags, PhysFrame, Size4KiB,
-    mapper::{MapToError, OffsetPageTable, Translate},
+    mapper::{MapToError, OffsetPageTable, Translate, TranslateResult},
 };
 use x86_64::{PhysAddr, VirtAddr};
 
@@ -28,6 +36,13 @@ const HEAP_GUARD_HIGH_START: u64 = HEAP_START + HEAP_SIZE_BYTES as u64;
 #[global_allocator]
 static GLOBAL_ALLOCATOR: Locked<BumpAllocator> = Locked::new(BumpAllocator::new());
 static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+/// Memory map totals from `init`; `map` keeps the region list itself.
+static BOOT_STATS: Locked<MemoryStats> = Locked::new(MemoryStats {
+    region_count: 0,
+    usable_bytes: 0,
+    reserved_bytes: 0,
+    total_bytes: 0,
+});
 
 #[derive(Clone, Copy)]
 pub struct MemoryStats {
@@ -51,6 +66,13 @@ impl MemoryStats {
     }
 }
 
+#[derive(Clone, Copy)]
+pub struct HeapUsage {
+    pub size_bytes: usize,
+    pub used_bytes: usize,
+    pub live_allocations: usize,
+}
+
 pub struct MemoryInitReport {
     pub stats: MemoryStats,
     pub physical_memory_offset: u64,
@@ -62,10 +84,16 @@ pub struct MemoryInitReport {
     pub heap_size: usize,
     pub guard_low: u64,
     pub guard_high: u64,
+    pub user_heap_start: u64,
+    pub user_heap_end_exclusive: u64,
+    pub user_heap_pages: usize,
     pub sample_heap_phys_addr: u64,
     pub alloc_box_value: u64,
     pub alloc_vec_len: usize,
     pub alloc_checksum: u64,
+    pub dma_pool_phys: u64,
+    pub dma_pool_bytes: usize,
+    pub hugepages: hugepage::HugePageReport,
 }
 
 #[derive(Debug)]
@@ -101,6 +129,7 @@ pub fn init(boot_info: &'static BootInfo) -> Result<MemoryInitReport, MemoryErro
     validate_heap_layout()?;
 
     let stats = collect_stats(boot_info);
+    BOOT_STATS.with_lock(|boot| *boot = stats);
     let physical_memory_offset = boot_info
         .physical_memory_offset
         .into_option()
@@ -110,11 +139,18 @@ pub fn init(boot_info: &'static BootInfo) -> Result<MemoryInitReport, MemoryErro
     // SAFETY: bootloader-provided memory map lives for the whole kernel lifetime.
     let mut frame_allocator = unsafe { BootInfoFrameAllocator::new(&boot_info.memory_regions) };
     let usable_frames = frame_allocator.usable_frame_count();
+    map::record_regions(&boot_info.memory_regions, MIN_ALLOC_PHYS_ADDR);
 
     // SAFETY: `physical_memory_offset` comes from bootloader config and points to a valid phys map.
     let (mut mapper, level_4_frame) = unsafe { init_mapper(VirtAddr::new(physical_memory_offset)) };
-    let mapped_heap_pages =
-        map_heap(&mut mapper, &mut frame_allocator).map_err(MemoryError::HeapMap)?;
+    let mapped_heap_pages = map_range(
+        &mut mapper,
+        &mut frame_allocator,
+        HEAP_START,
+        HEAP_SIZE_BYTES,
+    )
+    .map_err(MemoryError::HeapMap)?;
+    map::record_frames(map::Consumer::KernelHeap, mapped_heap_pages as u64);
 
     let sample_heap_phys_addr = mapper
         .translate_addr(VirtAddr::new(HEAP_START))
@@ -134,9 +170,41 @@ pub fn init(boot_info: &'static BootInfo) -> Result<MemoryInitReport, MemoryErro
         return Err(MemoryError::GuardPageMapped("high"));
     }
 
+    let user_heap_pages = map_user_heaps(&mut mapper, &mut frame_allocator)?;
+    map::record_frames(map::Consumer::UserHeaps, user_heap_pages as u64);
+    map::record_frames(
+        map::Consumer::PageTables,
+        frame_allocator
+            .allocated
+            .saturating_sub(mapped_heap_pages + user_heap_pages) as u64,
+    );
+
     init_heap_allocator(HEAP_START as usize, HEAP_SIZE_BYTES)?;
     let alloc = allocation_smoke_test()?;
 
+    #[cfg(feature = "net")]
+    let (dma_pool_phys, dma_pool_bytes) =
+        reserve_dma_pool(&mut frame_allocator, physical_memory_offset);
+    // Only the network driver allocates from the pool; without it no frames are set aside.
+    #[cfg(not(feature = "net"))]
+    let (dma_pool_phys, dma_pool_bytes) = (0, 0);
+    map::record_allocator_end(frame_allocator.region_cursor, frame_allocator.next_addr);
+
+    let physmap_len = boot_info
+        .memory_regions
+        .iter()
+        .map(|region| region.end)
+        .max()
+        .unwrap_or(0);
+    let framebuffer = boot_info.framebuffer.as_ref().map(|framebuffer| {
+        (
+            framebuffer.buffer().as_ptr() as u64,
+            framebuffer.info().byte_len as u64,
+        )
+    });
+    let hugepages = hugepage::promote((physical_memory_offset, physmap_len), framebuffer);
+    annotate_boot_ranges(boot_info, &mapper);
+
     Ok(MemoryInitReport {
         stats,
         physical_memory_offset,
@@ -148,14 +216,65 @@ pub fn init(boot_info: &'static BootInfo) -> Result<MemoryInitReport, MemoryErro
         heap_size: HEAP_SIZE_BYTES,
         guard_low: HEAP_GUARD_LOW_START,
         guard_high: HEAP_GUARD_HIGH_START,
+        user_heap_start: userheap::WINDOW_START,
+        user_heap_end_exclusive: userheap::WINDOW_END,
+        user_heap_pages,
         sample_heap_phys_addr,
         alloc_box_value: alloc.box_value,
         alloc_vec_len: alloc.vec_len,
         alloc_checksum: alloc.checksum,
+        dma_pool_phys,
+        dma_pool_bytes,
+        hugepages,
     })
 }
 
+/// Records where the kernel image, ramdisk and framebuffer sit physically for `mem map`. The
+/// ramdisk and framebuffer are given as virtual ranges and assumed physically contiguous.
+fn annotate_boot_ranges(boot_info: &BootInfo, mapper: &OffsetPageTable<'static>) {
+    let translate = |virt: u64| {
+        VirtAddr::try_new(virt)
+            .ok()
+            .and_then(|virt| mapper.translate_addr(virt))
+            .map(PhysAddr::as_u64)
+    };
+    map::annotate(
+        map::Annotation::KernelImage,
+        boot_info.kernel_addr,
+        boot_info.kernel_len,
+    );
+    if let Some(phys) = boot_info.ramdisk_addr.into_option().and_then(translate) {
+        map::annotate(map::Annotation::Ramdisk, phys, boot_info.ramdisk_len);
+    }
+    if let Some(framebuffer) = boot_info.framebuffer.as_ref()
+        && let Some(phys) = translate(framebuffer.buffer().as_ptr() as u64)
+    {
+        map::annotate(
+            map::Annotation::Framebuffer,
+            phys,
+            framebuffer.info().byte_len as u64,
+        );
+    }
+}
+
 pub fn virt_to_phys(virt_addr: usize) -> Option<u64> {
+    let virt = VirtAddr::try_new(virt_addr as u64).ok()?;
+    with_active_mapper(|mapper| mapper.translate_addr(virt).map(PhysAddr::as_u64))?
+}
+
+/// Reports whether the page holding `virt_addr` is present (and writable when `write` is set).
+pub fn is_mapped(virt_addr: usize, write: bool) -> bool {
+    let Ok(virt) = VirtAddr::try_new(virt_addr as u64) else {
+        return false;
+    };
+    with_active_mapper(|mapper| match mapper.translate(virt) {
+        TranslateResult::Mapped { flags, .. } => !write || flags.contains(PageTableFlags::WRITABLE),
+        TranslateResult::NotMapped | TranslateResult::InvalidFrameAddress(_) => false,
+    })
+    .unwrap_or(false)
+}
+
+fn with_active_mapper<R>(f: impl FnOnce(&OffsetPageTable<'_>) -> R) -> Option<R> {
     let physical_memory_offset = PHYSICAL_MEMORY_OFFSET.load(Ordering::Acquire);
     if physical_memory_offset == 0 {
         return None;
@@ -171,11 +290,10 @@ pub fn virt_to_phys(virt_addr: usize) -> Option<u64> {
     let level_4_table = unsafe { &mut *level_4_ptr };
     // SAFETY: `level_4_table` references the active page table and `offset` is valid.
     let mapper = unsafe { OffsetPageTable::new(level_4_table, offset) };
-    mapper
-        .translate_addr(VirtAddr::new(virt_addr as u64))
-        .map(PhysAddr::as_u64)
+    Some(f(&mapper))
 }
 
+#[cfg(feature = "audio")]
 pub fn phys_to_virt(phys_addr: u64) -> Option<usize> {
     let physical_memory_offset = PHYSICAL_MEMORY_OFFSET.load(Ordering::Acquire);
     if physical_memory_offset == 0 {
@@ -185,6 +303,20 @@ pub fn phys_to_virt(phys_addr: u64) -> Option<usize> {
     usize::try_from(virt).ok()
 }
 
+/// Memory map totals recorded by `init`.
+pub fn boot_stats() -> MemoryStats {
+    BOOT_STATS.with_lock(|stats| *stats)
+}
+
+/// Bump-heap usage; `used_bytes` only drops back to zero once every allocation is freed.
+pub fn heap_usage() -> HeapUsage {
+    GLOBAL_ALLOCATOR.with_lock(|allocator| HeapUsage {
+        size_bytes: HEAP_SIZE_BYTES,
+        used_bytes: allocator.next.saturating_sub(allocator.heap_start),
+        live_allocations: allocator.allocations,
+    })
+}
+
 pub fn collect_stats(boot_info: &BootInfo) -> MemoryStats {
     let mut stats = MemoryStats {
         region_count: 0,
@@ -251,14 +383,16 @@ unsafe fn init_mapper(physical_memory_offset: VirtAddr) -> (OffsetPageTable<'sta
     (mapper, level_4_frame)
 }
 
-fn map_heap(
+fn map_range(
     mapper: &mut OffsetPageTable<'static>,
     frame_allocator: &mut BootInfoFrameAllocator,
+    start: u64,
+    bytes: usize,
 ) -> Result<usize, MapToError<Size4KiB>> {
-    let heap_start = VirtAddr::new(HEAP_START);
-    let heap_end = heap_start + (HEAP_SIZE_BYTES as u64) - 1;
-    let start_page = Page::containing_address(heap_start);
-    let end_page = Page::containing_address(heap_end);
+    let range_start = VirtAddr::new(start);
+    let range_end = range_start + (bytes as u64) - 1;
+    let start_page = Page::containing_address(range_start);
+    let end_page = Page::containing_address(range_end);
 
     let mut mapped_pages = 0usize;
     for page in Page::range_inclusive(start_page, end_page) {
@@ -267,7 +401,7 @@ fn map_heap(
             .ok_or(MapToError::FrameAllocationFailed)?;
         let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
 
-        // SAFETY: each `page` in the chosen heap range is currently unused and mapped once.
+        // SAFETY: each `page` in the chosen range is currently unused and mapped once.
         let flush = unsafe { mapper.map_to(page, frame, flags, frame_allocator)? };
         flush.flush();
         mapped_pages = mapped_pages.saturating_add(1);
@@ -276,13 +410,84 @@ fn map_heap(
     Ok(mapped_pages)
 }
 
+/// Maps every task heap slot up front and checks that the guard page below each slot, and
+/// the one closing the window, stayed unmapped.
+fn map_user_heaps(
+    mapper: &mut OffsetPageTable<'static>,
+    frame_allocator: &mut BootInfoFrameAllocator,
+) -> Result<usize, MemoryError> {
+    let mut mapped_pages = 0usize;
+    for index in 0..userheap::SLOTS {
+        let base = userheap::slot_base(index);
+        mapped_pages = mapped_pages.saturating_add(
+            map_range(mapper, frame_allocator, base, userheap::SLOT_BYTES)
+                .map_err(MemoryError::HeapMap)?,
+        );
+        let guard = base - userheap::GUARD_BYTES as u64;
+        if mapper.translate_addr(VirtAddr::new(guard)).is_some() {
+            return Err(MemoryError::GuardPageMapped("user heap"));
+        }
+    }
+    let last_guard = userheap::WINDOW_END - userheap::GUARD_BYTES as u64;
+    if mapper.translate_addr(VirtAddr::new(last_guard)).is_some() {
+        return Err(MemoryError::GuardPageMapped("user heap"));
+    }
+    userheap::mark_mapped();
+    Ok(mapped_pages)
+}
+
+/// Sets the DMA pool aside and hands it to `dma`; `(0, 0)` when no contiguous run was left.
+#[cfg(feature = "net")]
+fn reserve_dma_pool(
+    frame_allocator: &mut BootInfoFrameAllocator,
+    physical_memory_offset: u64,
+) -> (u64, usize) {
+    let before_dma = frame_allocator.allocated;
+    let dma_pool = reserve_contiguous_frames(frame_allocator, dma::DMA_POOL_BYTES / PAGE_SIZE);
+    map::record_frames(
+        map::Consumer::DmaPool,
+        frame_allocator.allocated.saturating_sub(before_dma) as u64,
+    );
+    match dma_pool {
+        Some(phys) => {
+            map::annotate(map::Annotation::DmaPool, phys, dma::DMA_POOL_BYTES as u64);
+            dma::install(
+                (physical_memory_offset + phys) as usize,
+                phys,
+                dma::DMA_POOL_BYTES,
+            );
+            (phys, dma::DMA_POOL_BYTES)
+        }
+        None => (0, 0),
+    }
+}
+
+/// Takes `frames` physically adjacent frames; a region boundary restarts the run.
+#[cfg(feature = "net")]
+fn reserve_contiguous_frames(
+    frame_allocator: &mut BootInfoFrameAllocator,
+    frames: usize,
+) -> Option<u64> {
+    let mut run_start = 0u64;
+    let mut run_len = 0usize;
+    while run_len < frames {
+        let frame = frame_allocator.allocate_frame()?.start_address().as_u64();
+        if run_len == 0 || frame != run_start + (run_len * PAGE_SIZE) as u64 {
+            run_start = frame;
+            run_len = 0;
+        }
+        run_len = run_len.saturating_add(1);
+    }
+    Some(run_start)
+}
+
 fn init_heap_allocator(heap_start: usize, heap_size: usize) -> Result<(), MemoryError> {
     GLOBAL_ALLOCATOR.with_lock(|allocator| {
         if allocator.initialized {
             return Err(MemoryError::HeapAlreadyInitialized);
         }
 
-        // SAFETY: heap pages were mapped by `map_heap`, range is page-aligned and writable.
+        // SAFETY: heap pages were mapped by `map_range`, range is page-aligned and writable.
         unsafe {
             allocator.init(heap_start, heap_size);
         }
@@ -328,6 +533,8 @@ struct BootInfoFrameAllocator {
     region_cursor: usize,
     next_addr: u64,
     current_region_end: u64,
+    /// Frames handed out so far, for the per-consumer counts in `mem map`.
+    allocated: usize,
 }
 
 impl BootInfoFrameAllocator {
@@ -337,6 +544,7 @@ impl BootInfoFrameAllocator {
             region_cursor: 0,
             next_addr: 0,
             current_region_end: 0,
+            allocated: 0,
         }
     }
 
@@ -409,6 +617,7 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
             }
 
             self.next_addr = frame_end;
+            self.allocated = self.allocated.saturating_add(1);
             return Some(PhysFrame::containing_address(PhysAddr::new(frame_start)));
         }
     }
@@ -471,7 +680,11 @@ struct BumpAllocator {
     heap_end: usize,
     next: usize,
     allocations: usize,
+    /// Bumped each time the heap empties and `next` starts over.
+    generation: u64,
     initialized: bool,
+    tracker: leak::Tracker,
+    poison: poison::Scanner,
 }
 
 impl BumpAllocator {
@@ -481,7 +694,10 @@ impl BumpAllocator {
             heap_end: 0,
             next: 0,
             allocations: 0,
+            generation: 0,
             initialized: false,
+            tracker: leak::Tracker::new(),
+            poison: poison::Scanner::new(),
         }
     }
 
@@ -502,30 +718,43 @@ impl BumpAllocator {
             return NonNull::<u8>::dangling().as_ptr();
         }
 
-        let Some(start) = align_up_usize(self.next, layout.align()) else {
-            return null_mut();
+        let start = if poison::ENABLED {
+            self.bump_poisoned(layout.size(), layout.align())
+        } else {
+            self.bump(layout.size(), layout.align())
         };
-        let Some(end) = start.checked_add(layout.size()) else {
+        let Some(start) = start else {
             return null_mut();
         };
 
+        self.allocations = self.allocations.saturating_add(1);
+        self.tracker.record_alloc(start, layout.size());
+        start as *mut u8
+    }
+
+    fn bump(&mut self, size: usize, align: usize) -> Option<usize> {
+        let start = align_up_usize(self.next, align)?;
+        let end = start.checked_add(size)?;
         if end > self.heap_end {
-            return null_mut();
+            return None;
         }
-
         self.next = end;
-        self.allocations = self.allocations.saturating_add(1);
-        start as *mut u8
+        Some(start)
     }
 
-    fn deallocate(&mut self, _ptr: *mut u8, layout: Layout) {
+    fn deallocate(&mut self, ptr: *mut u8, layout: Layout, reports: &mut poison::Reports) {
         if layout.size() == 0 || self.allocations == 0 {
             return;
         }
+        if poison::ENABLED && !self.release_poisoned(ptr as usize, layout.align(), reports) {
+            return;
+        }
 
+        self.tracker.record_free(ptr as usize);
         self.allocations -= 1;
         if self.allocations == 0 {
             self.next = self.heap_start;
+            self.generation = self.generation.wrapping_add(1);
         }
     }
 }
@@ -537,6 +766,8 @@ unsafe impl GlobalAlloc for Locked<BumpAllocator> {
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
-        self.with_lock(|allocator| allocator.deallocate(ptr, layout));
+        let mut reports = poison::Reports::new();
+        self.with_lock(|allocator| allocator.deallocate(ptr, layout, &mut reports));
+        reports.report();
     }
 }
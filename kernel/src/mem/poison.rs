@@ -0,0 +1,423 @@
+// kernel/src/mem/poison.rs: optional heap poisoning. Built in only when the kernel is compiled
+// with `ARROST_HEAP_POISON` set. Every block then carries a header (owner tag, size, state) and
+// canary redzones on both sides; new memory is filled with one pattern and freed memory with
+// another. Frees check the redzones, and the run loop walks the bump heap in slices checking
+// live redzones and that freed blocks still hold the free pattern. The bump heap never reuses
+// a block until everything is freed, so any write to a freed block is a use-after-free.
+use super::leak::AllocTag;
+use super::{BumpAllocator, GLOBAL_ALLOCATOR, align_up_usize};
+use crate::serial;
+use crate::soft_assert::soft_assert;
+use core::mem::size_of;
+use core::ptr;
+use core::slice;
+
+pub const ENABLED: bool = option_env!("ARROST_HEAP_POISON").is_some();
+/// Canary bytes on each side of a block; the front zone also covers alignment padding.
+pub const REDZONE: usize = 16;
+const CANARY: u8 = 0xBB;
+/// Fill for fresh allocations, so reads of memory nobody wrote show a recognisable value.
+const INUSE: u8 = 0x5A;
+const FREED: u8 = 0x6B;
+const MAGIC: u32 = 0xA110_C8ED;
+const HEADER: usize = size_of::<Header>();
+const HEADER_ALIGN: usize = 8;
+/// The run loop checks a slice of the heap this often (1 s) ...
+const CHECK_INTERVAL_TICKS: u64 = 100;
+/// ... and stops after the block that takes it past this many bytes.
+const CHECK_BUDGET_BYTES: usize = 256 * 1024;
+/// Violations a single walk holds for reporting once the allocator lock is dropped.
+const REPORT_CAP: usize = 4;
+
+#[repr(C)]
+struct Header {
+    magic: u32,
+    live: bool,
+    tag: u8,
+    /// Bytes from the header to the block.
+    offset: u32,
+    size: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The front redzone or padding was written: an underflow, or an overflow of the block
+    /// before.
+    Underflow,
+    Overflow,
+    UseAfterFree,
+    DoubleFree,
+    /// Freed pointer with no intact header in front of it.
+    BadHeader,
+}
+
+impl ViolationKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Underflow => "redzone-underflow",
+            Self::Overflow => "redzone-overflow",
+            Self::UseAfterFree => "use-after-free",
+            Self::DoubleFree => "double-free",
+            Self::BadHeader => "bad-header",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    /// The tag the block was allocated under; `None` when the header is gone.
+    pub tag: Option<AllocTag>,
+    pub ptr: usize,
+    pub size: usize,
+    /// First corrupted byte, relative to `ptr` (negative for the front redzone).
+    pub offset: isize,
+}
+
+/// Counters and walk position for `mem poison`.
+#[derive(Clone, Copy)]
+pub struct PoisonStats {
+    pub checks: u64,
+    pub passes: u64,
+    pub blocks: u64,
+    pub bytes: u64,
+    pub violations: u64,
+    pub last: Option<Violation>,
+}
+
+pub(super) struct Scanner {
+    /// Header the next walk resumes at; only meaningful for `generation`.
+    cursor: usize,
+    generation: u64,
+    next_tick: u64,
+    stats: PoisonStats,
+}
+
+impl Scanner {
+    pub(super) const fn new() -> Self {
+        Self {
+            cursor: 0,
+            generation: 0,
+            next_tick: 0,
+            stats: PoisonStats {
+                checks: 0,
+                passes: 0,
+                blocks: 0,
+                bytes: 0,
+                violations: 0,
+                last: None,
+            },
+        }
+    }
+
+    fn record(&mut self, violation: Violation) {
+        self.stats.violations = self.stats.violations.saturating_add(1);
+        self.stats.last = Some(violation);
+    }
+}
+
+/// Fixed-size violation list filled under the allocator lock.
+pub(super) struct Reports {
+    items: [Option<Violation>; REPORT_CAP],
+    len: usize,
+}
+
+impl Reports {
+    pub(super) const fn new() -> Self {
+        Self {
+            items: [None; REPORT_CAP],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, violation: Violation) {
+        if self.len < REPORT_CAP {
+            self.items[self.len] = Some(violation);
+            self.len += 1;
+        }
+    }
+
+    /// Logs every violation through the soft-assert channel. Call without the allocator lock:
+    /// logging may allocate.
+    pub(super) fn report(&self) {
+        for violation in self.items[..self.len].iter().flatten() {
+            soft_assert!(
+                false,
+                "heap {} tag={} ptr={:#x} size={} offset={}",
+                violation.kind.as_str(),
+                violation.tag.map_or("unknown", AllocTag::as_str),
+                violation.ptr,
+                violation.size,
+                violation.offset
+            );
+        }
+    }
+}
+
+fn tag_from(raw: u8) -> Option<AllocTag> {
+    AllocTag::ALL.get(usize::from(raw)).copied()
+}
+
+/// Index of the first byte in `start..start + len` that is not `value`.
+///
+/// # Safety
+/// The range must lie inside the mapped heap.
+unsafe fn first_mismatch(start: usize, len: usize, value: u8) -> Option<usize> {
+    // SAFETY: guaranteed by the caller.
+    let bytes = unsafe { slice::from_raw_parts(start as *const u8, len) };
+    bytes.iter().position(|&byte| byte != value)
+}
+
+/// # Safety
+/// The range must lie inside the mapped heap.
+unsafe fn fill(start: usize, len: usize, value: u8) {
+    // SAFETY: guaranteed by the caller.
+    unsafe { ptr::write_bytes(start as *mut u8, value, len) };
+}
+
+impl BumpAllocator {
+    /// Bumps out a block with a header and redzones around it and returns the block address.
+    pub(super) fn bump_poisoned(&mut self, size: usize, align: usize) -> Option<usize> {
+        let header = align_up_usize(self.next, HEADER_ALIGN)?;
+        let block = align_up_usize(header.checked_add(HEADER + REDZONE)?, align)?;
+        let end = block.checked_add(size)?.checked_add(REDZONE)?;
+        if end > self.heap_end {
+            return None;
+        }
+        let offset = u32::try_from(block - header).ok()?;
+        self.next = end;
+        // SAFETY: `header..end` lies inside the heap past every block handed out so far.
+        unsafe {
+            ptr::write(
+                header as *mut Header,
+                Header {
+                    magic: MAGIC,
+                    live: true,
+                    tag: self.tracker.current() as u8,
+                    offset,
+                    size,
+                },
+            );
+            fill(header + HEADER, block - header - HEADER, CANARY);
+            fill(block, size, INUSE);
+            fill(block + size, REDZONE, CANARY);
+        }
+        Some(block)
+    }
+
+    /// Checks a freed block's redzones and fills it with the free pattern. A double free or an
+    /// unrecognised pointer is reported and leaves the allocation count alone (returns false).
+    pub(super) fn release_poisoned(
+        &mut self,
+        block: usize,
+        align: usize,
+        reports: &mut Reports,
+    ) -> bool {
+        // Alignment padding puts the header anywhere up to `align` bytes further back than the
+        // usual `REDZONE + HEADER`.
+        let nearest = block.wrapping_sub(REDZONE + HEADER) & !(HEADER_ALIGN - 1);
+        let lowest = nearest
+            .saturating_sub(align)
+            .max(align_up_usize(self.heap_start, HEADER_ALIGN).unwrap_or(usize::MAX));
+        let header = (lowest..=nearest).rev().step_by(HEADER_ALIGN).find(|&at| {
+            // SAFETY: `at` lies between `heap_start` and `block`, inside the heap.
+            let header = unsafe { &*(at as *const Header) };
+            header.magic == MAGIC && at + header.offset as usize == block
+        });
+        let mut violation = Violation {
+            kind: ViolationKind::BadHeader,
+            tag: None,
+            ptr: block,
+            size: 0,
+            offset: 0,
+        };
+        let Some(header) = header else {
+            self.poison.record(violation);
+            reports.push(violation);
+            return false;
+        };
+        // SAFETY: `header` was just found intact inside the heap.
+        let (live, size, tag) = unsafe {
+            let header = &mut *(header as *mut Header);
+            let live = header.live;
+            header.live = false;
+            (live, header.size, header.tag)
+        };
+        if !live {
+            violation.kind = ViolationKind::DoubleFree;
+            violation.tag = tag_from(tag);
+            violation.size = size;
+            self.poison.record(violation);
+            reports.push(violation);
+            return false;
+        }
+        self.check_redzones(header, reports);
+        // SAFETY: the block lies inside the heap and its owner just gave it up.
+        unsafe { fill(block, size, FREED) };
+        true
+    }
+
+    /// Checks (and then repairs, so it is reported once) both redzones of the block whose
+    /// header is at `header`.
+    fn check_redzones(&mut self, header: usize, reports: &mut Reports) {
+        // SAFETY: callers pass the address of an intact header inside the heap.
+        let (offset, size, tag) = unsafe {
+            let header = &*(header as *const Header);
+            (header.offset as usize, header.size, header.tag)
+        };
+        let block = header + offset;
+        let front = header + HEADER;
+        let zones = [
+            (front, offset - HEADER, ViolationKind::Underflow),
+            (block + size, REDZONE, ViolationKind::Overflow),
+        ];
+        for (start, len, kind) in zones {
+            // SAFETY: both zones lie between the header and the block's end, inside the heap.
+            if let Some(bad) = unsafe { first_mismatch(start, len, CANARY) } {
+                let violation = Violation {
+                    kind,
+                    tag: tag_from(tag),
+                    ptr: block,
+                    size,
+                    offset: (start + bad) as isize - block as isize,
+                };
+                self.poison.record(violation);
+                reports.push(violation);
+                // SAFETY: as above.
+                unsafe { fill(start, len, CANARY) };
+            }
+        }
+    }
+
+    /// Walks blocks from the saved cursor until `budget` bytes were checked or the end of the
+    /// heap; a full pass restarts from the bottom.
+    pub(super) fn check_heap(&mut self, budget: usize, reports: &mut Reports) {
+        if self.poison.generation != self.generation || self.poison.cursor < self.heap_start {
+            self.poison.generation = self.generation;
+            self.poison.cursor = self.heap_start;
+        }
+        self.poison.stats.checks = self.poison.stats.checks.saturating_add(1);
+        let mut checked = 0usize;
+        while checked < budget {
+            let Some(header) = align_up_usize(self.poison.cursor, HEADER_ALIGN) else {
+                break;
+            };
+            if header + HEADER > self.next {
+                self.poison.cursor = self.heap_start;
+                self.poison.stats.passes = self.poison.stats.passes.saturating_add(1);
+                break;
+            }
+            // SAFETY: `header` is below `next`, where every block starts with a header.
+            let (magic, live, offset, size, tag) = unsafe {
+                let header = &*(header as *const Header);
+                (
+                    header.magic,
+                    header.live,
+                    header.offset as usize,
+                    header.size,
+                    header.tag,
+                )
+            };
+            if magic != MAGIC {
+                // Without the header the walk cannot find the next block; start over next time.
+                let violation = Violation {
+                    kind: ViolationKind::BadHeader,
+                    tag: None,
+                    ptr: header,
+                    size: 0,
+                    offset: 0,
+                };
+                self.poison.record(violation);
+                reports.push(violation);
+                self.poison.cursor = self.heap_start;
+                break;
+            }
+            let block = header + offset;
+            self.check_redzones(header, reports);
+            if !live {
+                // SAFETY: the freed block lies inside the heap below `next`.
+                if let Some(bad) = unsafe { first_mismatch(block, size, FREED) } {
+                    let violation = Violation {
+                        kind: ViolationKind::UseAfterFree,
+                        tag: tag_from(tag),
+                        ptr: block,
+                        size,
+                        offset: bad as isize,
+                    };
+                    self.poison.record(violation);
+                    reports.push(violation);
+                    // SAFETY: as above.
+                    unsafe { fill(block, size, FREED) };
+                }
+            }
+            let end = block + size + REDZONE;
+            checked = checked.saturating_add(end - header);
+            self.poison.stats.blocks = self.poison.stats.blocks.saturating_add(1);
+            self.poison.stats.bytes = self
+                .poison
+                .stats
+                .bytes
+                .saturating_add((end - header) as u64);
+            self.poison.cursor = end;
+        }
+    }
+}
+
+/// Run-loop hook: checks the next slice of the heap once per interval.
+pub fn poll(now: u64) {
+    if !ENABLED {
+        return;
+    }
+    let mut reports = Reports::new();
+    GLOBAL_ALLOCATOR.with_lock(|allocator| {
+        if !allocator.initialized || now < allocator.poison.next_tick {
+            return;
+        }
+        allocator.poison.next_tick = now + CHECK_INTERVAL_TICKS;
+        allocator.check_heap(CHECK_BUDGET_BYTES, &mut reports);
+    });
+    reports.report();
+}
+
+/// Walks the whole heap now (`mem poison check`); returns the violations it found.
+pub fn check_all() -> u64 {
+    if !ENABLED {
+        return 0;
+    }
+    let mut reports = Reports::new();
+    let found = GLOBAL_ALLOCATOR.with_lock(|allocator| {
+        let before = allocator.poison.stats.violations;
+        allocator.poison.cursor = allocator.heap_start;
+        allocator.check_heap(usize::MAX, &mut reports);
+        allocator.poison.stats.violations - before
+    });
+    reports.report();
+    found
+}
+
+pub fn stats() -> PoisonStats {
+    GLOBAL_ALLOCATOR.with_lock(|allocator| allocator.poison.stats)
+}
+
+pub fn log_status() {
+    if !ENABLED {
+        serial::write_line("mem: heap poisoning off (rebuild with ARROST_HEAP_POISON=1)");
+        return;
+    }
+    let stats = stats();
+    serial::write_fmt(format_args!(
+        "mem: poison redzone={REDZONE} checks={} passes={} blocks={} bytes={} violations={}",
+        stats.checks, stats.passes, stats.blocks, stats.bytes, stats.violations
+    ));
+    match stats.last {
+        Some(last) => serial::write_fmt(format_args!(
+            " last={} tag={} ptr={:#x} size={} offset={}\n",
+            last.kind.as_str(),
+            last.tag.map_or("unknown", AllocTag::as_str),
+            last.ptr,
+            last.size,
+            last.offset
+        )),
+        None => serial::write_line(" last=none"),
+    }
+}
@@ -0,0 +1,43 @@
+// kernel/src/mem/userheap.rs: fixed per-task heap windows (grown with SYS_BRK) fenced by guard pages.
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub const SLOTS: usize = 4;
+pub const SLOT_BYTES: usize = 256 * 1024;
+pub(super) const GUARD_BYTES: usize = super::PAGE_SIZE;
+/// Slots sit back to back and share the guard page between them: guard, heap, guard, heap, ..., guard.
+pub(super) const WINDOW_START: u64 = 0x_5555_5555_0000;
+const SLOT_STRIDE: u64 = (GUARD_BYTES + SLOT_BYTES) as u64;
+pub(super) const WINDOW_END: u64 = WINDOW_START + SLOTS as u64 * SLOT_STRIDE + GUARD_BYTES as u64;
+
+static MAPPED: AtomicBool = AtomicBool::new(false);
+
+/// Heap window of one task slot; `guard_low`/`guard_high` are the unmapped pages around it.
+#[derive(Clone, Copy)]
+pub struct HeapSlot {
+    pub base: u64,
+    pub limit: u64,
+    pub guard_low: u64,
+    pub guard_high: u64,
+}
+
+pub(super) const fn slot_base(index: usize) -> u64 {
+    WINDOW_START + index as u64 * SLOT_STRIDE + GUARD_BYTES as u64
+}
+
+pub(super) fn mark_mapped() {
+    MAPPED.store(true, Ordering::Release);
+}
+
+/// `None` until `mem::init` has mapped the window, or for an out-of-range slot.
+pub fn slot(index: usize) -> Option<HeapSlot> {
+    if index >= SLOTS || !MAPPED.load(Ordering::Acquire) {
+        return None;
+    }
+    let base = slot_base(index);
+    Some(HeapSlot {
+        base,
+        limit: base + SLOT_BYTES as u64,
+        guard_low: base - GUARD_BYTES as u64,
+        guard_high: base + SLOT_BYTES as u64,
+    })
+}
@@ -0,0 +1,195 @@
+// kernel/src/net/acd.rs: IPv4 address conflict detection after RFC 5227. Every newly configured
+// address is probed and then announced from `net::poll`; any ARP packet from another MAC that
+// claims it is reported as a duplicate. Conflicts are reported, not resolved: the address stays.
+use super::{IP_ZERO, NetState};
+use crate::arch::x86_64::entropy;
+use crate::log::{Level, Subsystem, log_ratelimited};
+use crate::serial;
+use crate::time;
+
+/// Random delay before the first probe (up to 1 s).
+const PROBE_WAIT_TICKS: u64 = 100;
+const PROBE_NUM: u8 = 3;
+/// Probes are spaced a random 1..2 s apart.
+const PROBE_MIN_TICKS: u64 = 100;
+const PROBE_MAX_TICKS: u64 = 200;
+/// Quiet time after the last probe before the address is announced (2 s).
+const ANNOUNCE_WAIT_TICKS: u64 = 200;
+const ANNOUNCE_NUM: u8 = 2;
+const ANNOUNCE_INTERVAL_TICKS: u64 = 200;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Idle,
+    Probing,
+    Announcing,
+    Done,
+    Conflict,
+}
+
+impl Phase {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Probing => "probing",
+            Self::Announcing => "announcing",
+            Self::Done => "ok",
+            Self::Conflict => "conflict",
+        }
+    }
+}
+
+pub(super) struct Acd {
+    phase: Phase,
+    /// Address being probed or announced; the one `phase` refers to.
+    ip: [u8; 4],
+    /// Probes or announcements sent in the current phase.
+    sent: u8,
+    next_tick: u64,
+    probes: u64,
+    announces: u64,
+    conflicts: u64,
+    last_ip: [u8; 4],
+    last_mac: [u8; 6],
+    last_tick: u64,
+}
+
+impl Acd {
+    pub(super) const fn new() -> Self {
+        Self {
+            phase: Phase::Idle,
+            ip: IP_ZERO,
+            sent: 0,
+            next_tick: 0,
+            probes: 0,
+            announces: 0,
+            conflicts: 0,
+            last_ip: IP_ZERO,
+            last_mac: [0; 6],
+            last_tick: 0,
+        }
+    }
+
+    pub(super) const fn conflicts(&self) -> u64 {
+        self.conflicts
+    }
+}
+
+/// Uniform delay in `min..=max` ticks.
+fn jitter(min: u64, max: u64) -> u64 {
+    min + entropy::next_u64() % (max - min + 1)
+}
+
+impl NetState {
+    /// Starts probing the current address, abandoning any check of a previous one.
+    pub(super) fn start_conflict_check(&mut self) {
+        self.acd.phase = Phase::Probing;
+        self.acd.ip = self.ipv4;
+        self.acd.sent = 0;
+        self.acd.next_tick = time::ticks() + jitter(0, PROBE_WAIT_TICKS);
+    }
+
+    /// Sends the probe or announcement that is due, if any.
+    pub(super) fn poll_conflict_check(&mut self) {
+        if !matches!(self.acd.phase, Phase::Probing | Phase::Announcing) {
+            return;
+        }
+        let now = time::ticks();
+        if now < self.acd.next_tick {
+            return;
+        }
+        let ip = self.acd.ip;
+        if self.acd.phase == Phase::Probing && self.acd.sent == PROBE_NUM {
+            self.acd.phase = Phase::Announcing;
+            self.acd.sent = 0;
+        }
+        // Probe: sender 0.0.0.0 so no cache learns the address yet. Announcement: the address
+        // as both sender and target, which refreshes every neighbor's cache.
+        let (sender_ip, delay) = if self.acd.phase == Phase::Probing {
+            self.acd.probes = self.acd.probes.saturating_add(1);
+            let last = self.acd.sent + 1 == PROBE_NUM;
+            let delay = if last {
+                ANNOUNCE_WAIT_TICKS
+            } else {
+                jitter(PROBE_MIN_TICKS, PROBE_MAX_TICKS)
+            };
+            (IP_ZERO, delay)
+        } else {
+            self.acd.announces = self.acd.announces.saturating_add(1);
+            (ip, ANNOUNCE_INTERVAL_TICKS)
+        };
+        if let Err(err) = self.send_arp_request_from(sender_ip, ip) {
+            log_ratelimited!(
+                Subsystem::Net,
+                Level::Warning,
+                "Net: address check send failed ({})\n",
+                err.as_str()
+            );
+        }
+        self.acd.sent += 1;
+        self.acd.next_tick = now + delay;
+        if self.acd.phase == Phase::Announcing && self.acd.sent == ANNOUNCE_NUM {
+            self.acd.phase = Phase::Done;
+        }
+    }
+
+    /// Checks one received ARP packet against our address; true when it shows a duplicate.
+    /// Besides any packet claiming the address, a rival probe for an address still being
+    /// probed counts too (RFC 5227 2.1.1): two hosts picked it at the same time.
+    pub(super) fn check_arp_conflict(
+        &mut self,
+        sender_mac: [u8; 6],
+        sender_ip: [u8; 4],
+        target_ip: [u8; 4],
+    ) -> bool {
+        if sender_mac == self.mac {
+            return false;
+        }
+        let claimed = sender_ip == self.ipv4 && sender_ip != IP_ZERO;
+        let rival_probe =
+            self.acd.phase == Phase::Probing && sender_ip == IP_ZERO && target_ip == self.acd.ip;
+        if !claimed && !rival_probe {
+            return false;
+        }
+        let ip = if claimed { sender_ip } else { target_ip };
+        self.acd.conflicts = self.acd.conflicts.saturating_add(1);
+        self.acd.last_ip = ip;
+        self.acd.last_mac = sender_mac;
+        self.acd.last_tick = time::ticks();
+        if matches!(self.acd.phase, Phase::Probing | Phase::Announcing) && self.acd.ip == ip {
+            self.acd.phase = Phase::Conflict;
+        }
+        let [a, b, c, d] = ip;
+        let [m0, m1, m2, m3, m4, m5] = sender_mac;
+        log_ratelimited!(
+            Subsystem::Net,
+            Level::Error,
+            "Net: DUPLICATE ADDRESS {a}.{b}.{c}.{d} also in use by {m0:02x}:{m1:02x}:{m2:02x}:{m3:02x}:{m4:02x}:{m5:02x} ({}); traffic to it may reach the other host, pick another with `ifconfig addr`\n",
+            if claimed { "claimed" } else { "probed" }
+        );
+        true
+    }
+}
+
+/// `arp` summary line for the address check; `last=` shows the most recent duplicate.
+pub(super) fn log_status(state: &NetState) {
+    let acd = &state.acd;
+    let [a, b, c, d] = acd.ip;
+    serial::write_fmt(format_args!(
+        "arp: acd ip={a}.{b}.{c}.{d} state={} probes={} announces={} conflicts={}",
+        acd.phase.as_str(),
+        acd.probes,
+        acd.announces,
+        acd.conflicts
+    ));
+    if acd.conflicts == 0 {
+        serial::write_line(" last=none");
+        return;
+    }
+    let [a, b, c, d] = acd.last_ip;
+    let [m0, m1, m2, m3, m4, m5] = acd.last_mac;
+    serial::write_fmt(format_args!(
+        " last={a}.{b}.{c}.{d} by={m0:02x}:{m1:02x}:{m2:02x}:{m3:02x}:{m4:02x}:{m5:02x} age_ms={}\n",
+        time::ticks().saturating_sub(acd.last_tick) * 10
+    ));
+}
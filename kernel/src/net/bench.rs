@@ -0,0 +1,156 @@
+// kernel/src/net/bench.rs: `net bench rx`, the receive side of the net benchmarks. It holds the
+// net lock and polls RX back to back while the host floods a UDP port (`cargo xtask
+// smoke-net-flood`). Each flood datagram starts with a big-endian u64 sequence number, so gaps
+// show datagrams lost before the stack ever saw them (slirp queue, device ring) next to the
+// frames the stack itself dropped.
+use super::{BENCH_MAX_SECONDS, NetError, NetState, with_net_mut};
+use crate::serial;
+use crate::shell::Status;
+use crate::time;
+
+/// Flood port when none is given; next to the echo (7777) and rtt (7778) ports.
+const RX_BENCH_PORT: u16 = 7779;
+
+/// Sequence accounting for the flood datagrams of one `net bench rx` run.
+#[derive(Clone, Copy)]
+pub(super) struct RxBench {
+    port: u16,
+    datagrams: u64,
+    payload_bytes: u64,
+    first_seq: Option<u64>,
+    max_seq: u64,
+    /// Datagrams whose sequence number was not above every one seen before.
+    reordered: u64,
+    /// Datagrams too short to carry a sequence number.
+    short: u64,
+}
+
+impl RxBench {
+    const fn new(port: u16) -> Self {
+        Self {
+            port,
+            datagrams: 0,
+            payload_bytes: 0,
+            first_seq: None,
+            max_seq: 0,
+            reordered: 0,
+            short: 0,
+        }
+    }
+
+    /// Sequence numbers from the first one seen to the highest that never arrived. Duplicates
+    /// count as arrivals, so this is a lower bound.
+    fn lost(&self) -> u64 {
+        let Some(first) = self.first_seq else {
+            return 0;
+        };
+        let sequenced = self.datagrams - self.short;
+        (self.max_seq - first + 1).saturating_sub(sequenced)
+    }
+}
+
+struct RxBenchReport {
+    bench: RxBench,
+    ticks: u64,
+    frames: u64,
+    bytes: u64,
+    dropped: u64,
+}
+
+impl NetState {
+    /// Counts a datagram for a running `net bench rx`; false when it is not a flood datagram.
+    pub(super) fn record_bench_rx(&mut self, dst_port: u16, data: &[u8]) -> bool {
+        let Some(bench) = self
+            .bench_rx
+            .as_mut()
+            .filter(|bench| bench.port == dst_port)
+        else {
+            return false;
+        };
+        bench.datagrams += 1;
+        bench.payload_bytes += data.len() as u64;
+        let Some(seq) = data
+            .first_chunk::<8>()
+            .map(|bytes| u64::from_be_bytes(*bytes))
+        else {
+            bench.short += 1;
+            return true;
+        };
+        match bench.first_seq {
+            None => {
+                bench.first_seq = Some(seq);
+                bench.max_seq = seq;
+            }
+            Some(_) if seq > bench.max_seq => bench.max_seq = seq,
+            Some(first) => {
+                bench.reordered += 1;
+                bench.first_seq = Some(first.min(seq));
+            }
+        }
+        true
+    }
+
+    fn bench_rx(&mut self, port: u16, seconds: u64) -> Result<RxBenchReport, NetError> {
+        if !self.ready {
+            return Err(NetError::NotReady);
+        }
+        let frames_before = self.stats.rx_frames;
+        let bytes_before = self.stats.rx_bytes;
+        let dropped_before = self.stats.dropped;
+        self.bench_rx = Some(RxBench::new(port));
+        let start = time::ticks();
+        let deadline = start + seconds * u64::from(time::PIT_HZ);
+        while time::ticks() < deadline {
+            self.poll();
+        }
+        let bench = self.bench_rx.take().unwrap_or(RxBench::new(port));
+        Ok(RxBenchReport {
+            bench,
+            ticks: (time::ticks() - start).max(1),
+            frames: self.stats.rx_frames - frames_before,
+            bytes: self.stats.rx_bytes - bytes_before,
+            dropped: self.stats.dropped - dropped_before,
+        })
+    }
+}
+
+/// `net bench rx <seconds> [port]`: polls RX for `seconds` and reports what arrived. Holds the
+/// net lock for the whole run, so the shell and every net service wait until it ends.
+pub fn bench_rx_to_serial(args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    let seconds = parts.next().and_then(|value| value.parse::<u64>().ok());
+    let port = match parts.next() {
+        Some(value) => value.parse::<u16>().ok().filter(|port| *port != 0),
+        None => Some(RX_BENCH_PORT),
+    };
+    let (Some(seconds @ 1..=BENCH_MAX_SECONDS), Some(port), None) = (seconds, port, parts.next())
+    else {
+        return Status::usage("usage: net bench rx <1..60> [port]");
+    };
+    // The flood tool waits for this line before it starts sending.
+    serial::write_fmt(format_args!(
+        "net bench: rx listening port={port} seconds={seconds}\n"
+    ));
+    match with_net_mut(|state| state.bench_rx(port, seconds)) {
+        Ok(report) => {
+            let hz = u64::from(time::PIT_HZ);
+            let bench = report.bench;
+            serial::write_fmt(format_args!(
+                "net bench: rx port={port} ticks={} frames={} bytes={} pps={} kbit_s={} datagrams={} payload_bytes={} lost={} reordered={} short={} dropped={}\n",
+                report.ticks,
+                report.frames,
+                report.bytes,
+                report.frames * hz / report.ticks,
+                report.bytes * 8 * hz / report.ticks / 1000,
+                bench.datagrams,
+                bench.payload_bytes,
+                bench.lost(),
+                bench.reordered,
+                bench.short,
+                report.dropped
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("net bench: failed ({})\n", err.as_str())),
+    }
+}
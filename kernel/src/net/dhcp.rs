@@ -0,0 +1,237 @@
+// kernel/src/net/dhcp.rs: DHCP client messages with hostname and client-id options, and the bound
+// lease with its T1/T2 timers and the option codes the server sent, for `dhcp info`.
+use super::{DHCP_MAGIC_COOKIE, DHCP_OPT_END, DHCP_OPT_MSG_TYPE};
+use crate::storage::kvstore;
+use core::fmt;
+
+pub(super) const DHCP_OPT_HOSTNAME: u8 = 12;
+pub(super) const DHCP_OPT_RENEWAL_TIME: u8 = 58;
+pub(super) const DHCP_OPT_REBINDING_TIME: u8 = 59;
+pub(super) const DHCP_OPT_CLIENT_ID: u8 = 61;
+pub(super) const DHCP_MSG_NAK: u8 = 6;
+pub(super) const DHCP_MSG_RELEASE: u8 = 7;
+
+/// kv key with the hostname sent as option 12, set with `kv set net.hostname <name>`.
+pub const HOSTNAME_KEY: &str = "net.hostname";
+const DEFAULT_HOSTNAME: &str = "arrost";
+const MAX_HOSTNAME: usize = 32;
+/// Option 61 hardware type for Ethernet (RFC 2132 9.14).
+const CLIENT_ID_ETHERNET: u8 = 1;
+const MESSAGE_CAP: usize = 320;
+const MAX_LEASE_OPTIONS: usize = 24;
+/// Lease time meaning "never expires" (RFC 2132 9.2).
+const INFINITE_SECS: u32 = u32::MAX;
+
+pub(super) struct Hostname {
+    bytes: [u8; MAX_HOSTNAME],
+    len: usize,
+    pub from_kv: bool,
+}
+
+impl Hostname {
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or(DEFAULT_HOSTNAME)
+    }
+}
+
+/// Letters, digits, `-` and `.`, not starting or ending with `-` or `.`.
+fn valid_hostname(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    (1..=MAX_HOSTNAME).contains(&bytes.len())
+        && bytes
+            .iter()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.'))
+        && !matches!(bytes[0], b'-' | b'.')
+        && !matches!(bytes[bytes.len() - 1], b'-' | b'.')
+}
+
+/// `net.hostname` from the kv store, or `arrost` when it is unset or not a valid name.
+pub(super) fn hostname() -> Hostname {
+    let stored = kvstore::get(HOSTNAME_KEY);
+    let (name, from_kv) = match stored.as_ref().map(kvstore::Value::as_str) {
+        Some(name) if valid_hostname(name) => (name, true),
+        _ => (DEFAULT_HOSTNAME, false),
+    };
+    let mut hostname = Hostname {
+        bytes: [0; MAX_HOSTNAME],
+        len: name.len(),
+        from_kv,
+    };
+    hostname.bytes[..name.len()].copy_from_slice(name.as_bytes());
+    hostname
+}
+
+/// A BOOTREQUEST being built; options are appended in order and `finish` adds the end marker.
+pub(super) struct Message {
+    packet: [u8; MESSAGE_CAP],
+    len: usize,
+}
+
+impl Message {
+    /// Header and message type. `ciaddr` is our address while renewing or releasing; without it
+    /// the broadcast flag asks the server to broadcast its reply.
+    pub fn new(msg_type: u8, xid: u32, mac: [u8; 6], ciaddr: [u8; 4]) -> Self {
+        let mut packet = [0u8; MESSAGE_CAP];
+        packet[0] = 1;
+        packet[1] = 1;
+        packet[2] = 6;
+        packet[4..8].copy_from_slice(&xid.to_be_bytes());
+        if ciaddr == [0; 4] {
+            packet[10..12].copy_from_slice(&0x8000u16.to_be_bytes());
+        }
+        packet[12..16].copy_from_slice(&ciaddr);
+        packet[28..34].copy_from_slice(&mac);
+        packet[236..240].copy_from_slice(&DHCP_MAGIC_COOKIE);
+        let mut message = Self { packet, len: 240 };
+        message.option(DHCP_OPT_MSG_TYPE, &[msg_type]);
+        message
+    }
+
+    /// Appends one option; options that would not leave room for the end marker are dropped.
+    pub fn option(&mut self, code: u8, value: &[u8]) {
+        let Ok(value_len) = u8::try_from(value.len()) else {
+            return;
+        };
+        let end = self.len + 2 + value.len();
+        if end >= MESSAGE_CAP {
+            return;
+        }
+        self.packet[self.len] = code;
+        self.packet[self.len + 1] = value_len;
+        self.packet[self.len + 2..end].copy_from_slice(value);
+        self.len = end;
+    }
+
+    /// Options 12 (hostname) and 61 (client id: hardware type, then the MAC).
+    pub fn identity(&mut self, mac: [u8; 6], hostname: &str) {
+        self.option(DHCP_OPT_HOSTNAME, hostname.as_bytes());
+        let mut client_id = [CLIENT_ID_ETHERNET; 7];
+        client_id[1..].copy_from_slice(&mac);
+        self.option(DHCP_OPT_CLIENT_ID, &client_id);
+    }
+
+    pub fn finish(&mut self) -> &[u8] {
+        self.packet[self.len] = DHCP_OPT_END;
+        &self.packet[..self.len + 1]
+    }
+}
+
+/// What the server granted, kept from the ACK until the lease is released or replaced.
+#[derive(Clone, Copy)]
+pub(super) struct Lease {
+    pub ip: [u8; 4],
+    pub netmask: [u8; 4],
+    pub gateway: [u8; 4],
+    pub dns: [u8; 4],
+    pub server_id: [u8; 4],
+    /// Option 51; `None` when the server sent none.
+    pub lease_secs: Option<u32>,
+    /// Options 58 and 59; see `t1_secs`/`t2_secs` for the defaults.
+    pub renewal_secs: Option<u32>,
+    pub rebinding_secs: Option<u32>,
+    pub bound_tick: u64,
+    options: [u8; MAX_LEASE_OPTIONS],
+    option_count: usize,
+}
+
+impl Lease {
+    pub const fn empty() -> Self {
+        Self {
+            ip: [0; 4],
+            netmask: [0; 4],
+            gateway: [0; 4],
+            dns: [0; 4],
+            server_id: [0; 4],
+            lease_secs: None,
+            renewal_secs: None,
+            rebinding_secs: None,
+            bound_tick: 0,
+            options: [0; MAX_LEASE_OPTIONS],
+            option_count: 0,
+        }
+    }
+
+    /// Remembers that the reply carried option `code`, once per code.
+    pub fn record_option(&mut self, code: u8) {
+        if self.options().contains(&code) || self.option_count == MAX_LEASE_OPTIONS {
+            return;
+        }
+        self.options[self.option_count] = code;
+        self.option_count += 1;
+    }
+
+    pub fn options(&self) -> &[u8] {
+        &self.options[..self.option_count]
+    }
+
+    /// T1 from option 58, else half the lease (RFC 2131 4.4.5).
+    pub fn t1_secs(&self) -> Option<u32> {
+        self.renewal_secs.or(self
+            .lease_secs
+            .filter(|&secs| secs != INFINITE_SECS)
+            .map(|secs| secs / 2))
+    }
+
+    /// T2 from option 59, else 7/8 of the lease.
+    pub fn t2_secs(&self) -> Option<u32> {
+        self.rebinding_secs.or(self
+            .lease_secs
+            .filter(|&secs| secs != INFINITE_SECS)
+            .map(|secs| (u64::from(secs) * 7 / 8) as u32))
+    }
+}
+
+/// A lease timer relative to when the lease was bound: `none`, `infinite`, `<n>s`.
+pub(super) struct Secs(pub Option<u32>);
+
+impl fmt::Display for Secs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            None => f.write_str("none"),
+            Some(INFINITE_SECS) => f.write_str("infinite"),
+            Some(secs) => write!(f, "{secs}s"),
+        }
+    }
+}
+
+/// Seconds left until `at_secs` after binding, given how long the lease has been held.
+pub(super) struct Remaining(pub Option<u32>, pub u64);
+
+impl fmt::Display for Remaining {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            None => f.write_str("none"),
+            Some(INFINITE_SECS) => f.write_str("never"),
+            Some(secs) => write!(f, "{}s", u64::from(secs).saturating_sub(self.1)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RenewOutcome {
+    /// The server extended the lease on the current address.
+    Renewed,
+    /// No lease was held (or the server refused it) and a new DISCOVER got one.
+    Bound,
+    /// The server sent a NAK and no new lease was found; static addressing is back.
+    Lost,
+    /// No reply; the lease is kept until it expires.
+    TimedOut,
+    /// No lease was held and no server answered.
+    Unavailable,
+    /// `restart net`, `ifconfig addr` or `dhcp release` took the interface over first.
+    Superseded,
+}
+
+impl RenewOutcome {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Renewed => "renewed",
+            Self::Bound => "bound",
+            Self::Lost => "lost",
+            Self::TimedOut => "timed_out",
+            Self::Unavailable => "unavailable",
+            Self::Superseded => "superseded",
+        }
+    }
+}
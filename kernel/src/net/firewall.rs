@@ -0,0 +1,253 @@
+// kernel/src/net/firewall.rs: stateless allow/deny table for inbound UDP and TCP, matched on
+// protocol, source prefix and local port so hostfwd-exposed services can be fenced off.
+use super::{parse_ipv4, with_net, with_net_mut};
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+
+pub(super) const MAX_RULES: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Allow,
+    Deny,
+}
+
+impl Action {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Allow => "allow",
+            Self::Deny => "deny",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "allow" => Some(Self::Allow),
+            "deny" => Some(Self::Deny),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Proto {
+    Udp,
+    Tcp,
+    Any,
+}
+
+impl Proto {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Udp => "udp",
+            Self::Tcp => "tcp",
+            Self::Any => "any",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "udp" => Some(Self::Udp),
+            "tcp" => Some(Self::Tcp),
+            "any" => Some(Self::Any),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Rule {
+    action: Action,
+    proto: Proto,
+    src: [u8; 4],
+    prefix_len: u8,
+    /// Local (destination) port; `None` matches every port.
+    port: Option<u16>,
+    hits: u64,
+}
+
+impl Rule {
+    fn matches(&self, proto: Proto, src_ip: [u8; 4], dst_port: u16) -> bool {
+        let mask = prefix_mask(self.prefix_len);
+        (self.proto == Proto::Any || self.proto == proto)
+            && u32::from_be_bytes(src_ip) & mask == u32::from_be_bytes(self.src) & mask
+            && self.port.is_none_or(|port| port == dst_port)
+    }
+}
+
+/// Rules in insertion order; the first match decides and anything unmatched is allowed.
+pub(super) struct Firewall {
+    rules: [Option<Rule>; MAX_RULES],
+    default_allowed: u64,
+    denied: u64,
+}
+
+impl Firewall {
+    pub(super) const fn new() -> Self {
+        Self {
+            rules: [None; MAX_RULES],
+            default_allowed: 0,
+            denied: 0,
+        }
+    }
+
+    /// Whether an inbound segment or datagram may be handled; counts the deciding rule's hit.
+    pub(super) fn admit(&mut self, proto: Proto, src_ip: [u8; 4], dst_port: u16) -> bool {
+        let Some(rule) = self
+            .rules
+            .iter_mut()
+            .flatten()
+            .find(|rule| rule.matches(proto, src_ip, dst_port))
+        else {
+            self.default_allowed = self.default_allowed.saturating_add(1);
+            return true;
+        };
+        rule.hits = rule.hits.saturating_add(1);
+        if rule.action == Action::Deny {
+            self.denied = self.denied.saturating_add(1);
+            return false;
+        }
+        true
+    }
+
+    pub(super) const fn denied(&self) -> u64 {
+        self.denied
+    }
+}
+
+fn prefix_mask(prefix_len: u8) -> u32 {
+    u32::MAX
+        .checked_shl(32 - u32::from(prefix_len))
+        .unwrap_or(0)
+}
+
+/// `<allow|deny> <udp|tcp|any> <a.b.c.d/len> <port|any>`; a bare address is a /32.
+fn parse_rule(args: &str) -> Option<Rule> {
+    let mut parts = args.split_whitespace();
+    let action = Action::parse(parts.next()?)?;
+    let proto = Proto::parse(parts.next()?)?;
+    let source = parts.next()?;
+    let (src_text, prefix_len) = match source.split_once('/') {
+        Some((ip, len)) => (ip, len.parse::<u8>().ok().filter(|len| *len <= 32)?),
+        None => (source, 32),
+    };
+    let src = parse_ipv4(src_text)?;
+    let port = match parts.next()? {
+        "any" => None,
+        text => Some(text.parse::<u16>().ok().filter(|port| *port != 0)?),
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Rule {
+        action,
+        proto,
+        src,
+        prefix_len,
+        port,
+        hits: 0,
+    })
+}
+
+pub fn add_rule_to_serial(args: &str) -> Status {
+    let Some(rule) = parse_rule(args) else {
+        return Status::usage(
+            "usage: fw add <allow|deny> <udp|tcp|any> <a.b.c.d[/len]> <port|any>",
+        );
+    };
+    let added = with_net_mut(|state| {
+        // `fw del` shifts later rules up, so the first free slot is the end of the list.
+        let slot = state.firewall.rules.iter().position(Option::is_none)?;
+        state.firewall.rules[slot] = Some(rule);
+        Some(slot)
+    });
+    match added {
+        Some(slot) => {
+            log_rule("added ", slot, &rule);
+            Status::Ok
+        }
+        None => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("fw: rule table full ({MAX_RULES})\n"),
+            );
+            Status::Failed
+        }
+    }
+}
+
+pub fn delete_rule_to_serial(args: &str) -> Status {
+    let Some(number) = args
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .filter(|number| (1..=MAX_RULES).contains(number))
+    else {
+        return Status::usage("usage: fw del <n>");
+    };
+    let removed = with_net_mut(|state| {
+        let rules = &mut state.firewall.rules;
+        let removed = rules[number - 1].take()?;
+        rules[number - 1..].rotate_left(1);
+        Some(removed)
+    });
+    match removed {
+        Some(rule) => {
+            log_rule("deleted ", number - 1, &rule);
+            Status::Ok
+        }
+        None => {
+            serial::write_severity_fmt(Severity::Warning, format_args!("fw: no rule {number}\n"));
+            Status::Failed
+        }
+    }
+}
+
+pub fn clear_rules_to_serial() {
+    let cleared = with_net_mut(|state| {
+        let count = state.firewall.rules.iter().flatten().count();
+        state.firewall = Firewall::new();
+        count
+    });
+    serial::write_fmt(format_args!("fw: cleared {cleared} rules\n"));
+}
+
+pub fn log_rules() {
+    with_net(|state| {
+        let firewall = &state.firewall;
+        serial::write_fmt(format_args!(
+            "fw: rules={} max={} default=allow default_hits={} denied={}\n",
+            firewall.rules.iter().flatten().count(),
+            MAX_RULES,
+            firewall.default_allowed,
+            firewall.denied
+        ));
+        for (index, rule) in firewall.rules.iter().enumerate() {
+            if let Some(rule) = rule {
+                log_rule("", index, rule);
+            }
+        }
+    });
+}
+
+fn log_rule(label: &str, index: usize, rule: &Rule) {
+    let [a, b, c, d] = rule.src;
+    match rule.port {
+        Some(port) => serial::write_fmt(format_args!(
+            "fw: {label}{} {} {} {a}.{b}.{c}.{d}/{} port={port} hits={}\n",
+            index + 1,
+            rule.action.as_str(),
+            rule.proto.as_str(),
+            rule.prefix_len,
+            rule.hits
+        )),
+        None => serial::write_fmt(format_args!(
+            "fw: {label}{} {} {} {a}.{b}.{c}.{d}/{} port=any hits={}\n",
+            index + 1,
+            rule.action.as_str(),
+            rule.proto.as_str(),
+            rule.prefix_len,
+            rule.hits
+        )),
+    }
+}
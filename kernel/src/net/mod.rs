@@ -1,13 +1,44 @@
 // kernel/src/net/mod.rs: M7 virtio-net legacy driver + minimal IPv4/ARP/ICMP/UDP stack.
-use crate::arch::x86_64::port;
-use crate::mem;
-use crate::serial;
+use crate::arch::x86_64::{entropy, port};
+use crate::evlog::{self, Event};
+use crate::log::{self, Level, Subsystem, log_ratelimited};
+use crate::mem::{self, dma::DmaRegion};
+use crate::proc::executor;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+use crate::soft_assert::soft_assert;
 use crate::time;
+use alloc::string::String;
 use core::cell::UnsafeCell;
+use core::future::Future;
 use core::hint::spin_loop;
-use core::mem::size_of;
-use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
+use core::mem::{align_of, offset_of, size_of};
+use core::pin::Pin;
+use core::ptr::{read_volatile, write_volatile};
 use core::sync::atomic::{AtomicBool, Ordering, fence};
+use core::task::{Context, Poll, Waker};
+
+mod acd;
+mod bench;
+mod dhcp;
+mod firewall;
+mod netconsole;
+mod path;
+mod ports;
+mod service;
+mod tcp;
+mod wire;
+
+pub use bench::bench_rx_to_serial;
+pub use firewall::{add_rule_to_serial, clear_rules_to_serial, delete_rule_to_serial, log_rules};
+pub use netconsole::{
+    REQUEST_CAP as NETCONSOLE_REQUEST_CAP, netconsole_close, netconsole_open, netconsole_recv,
+    netconsole_reply, netconsole_stats,
+};
+pub use ports::{ports_to_serial, udp_allocate_port, udp_release_port};
+pub use service::{log_services, start_service_to_serial, stop_service_to_serial};
+pub use tcp::{TcpInfo, TcpReadiness, tcp_send_to_serial, tcp_to_serial};
+pub use wire::RxDrop;
 
 const VIRTIO_VENDOR_ID: u16 = 0x1AF4;
 const VIRTIO_NET_TRANSITIONAL_ID: u16 = 0x1000;
@@ -36,23 +67,53 @@ const VIRTQ_DESC_F_WRITE: u16 = 2;
 
 const RX_QUEUE_INDEX: u16 = 0;
 const TX_QUEUE_INDEX: u16 = 1;
-const MAX_QUEUE_SIZE: u16 = 256;
-const MAX_QUEUE_SIZE_USIZE: usize = MAX_QUEUE_SIZE as usize;
+const MAX_QUEUE_SIZE: u16 = 1024;
 const VRING_ALIGN: usize = 4096;
 const MAX_POLL_SPINS: usize = 2_000_000;
 
+const VIRTIO_NET_F_MTU: u32 = 1 << 3;
+const VIRTIO_NET_CONFIG_MTU: u16 = 10;
+
 const NET_HDR_SIZE: usize = size_of::<VirtioNetHdr>();
-const MAX_RX_FRAME: usize = 2048;
-const MAX_TX_FRAME: usize = 1536;
+const ETH_HEADER_LEN: usize = 14;
+const DEFAULT_MTU: usize = 1500;
+const MIN_MTU: usize = 576;
+/// Jumbo ceiling; RX/TX buffers are sized for it at init so `set_mtu` never reallocates.
+const MAX_MTU: usize = 9000;
+const MAX_RX_FRAME: usize = ETH_HEADER_LEN + MAX_MTU;
+const MAX_TX_FRAME: usize = ETH_HEADER_LEN + MAX_MTU;
+const BENCH_MAX_SECONDS: u64 = 60;
+/// Filler for `net bench udp`; static so a jumbo payload never lands on the kernel stack.
+static BENCH_PAYLOAD: [u8; MAX_MTU - 28] = [0x5a; MAX_MTU - 28];
+const RTT_DEFAULT_PROBES: u32 = 5;
+const RTT_MAX_PROBES: u32 = 32;
+/// Per-packet TX DMA slots kept in flight, fewer when the queue cannot chain that many.
+const TX_SLOTS: usize = 16;
+/// Descriptors reserved per slot: virtio header, protocol headers, payload.
+const TX_DESCS_PER_SLOT: usize = 3;
+/// RX buffers kept posted, so back-to-back frames do not wait for the stack to repost one.
+const RX_BUFFERS: usize = 16;
+/// Descriptors per RX buffer: virtio header, frame.
+const RX_DESCS_PER_BUFFER: usize = 2;
+const _: () = assert!(TX_SLOTS <= FreeList::CAPACITY && RX_BUFFERS <= FreeList::CAPACITY);
+/// Ethernet + IPv4 + UDP headers of a scatter-gather send.
+const UDP_HEADERS_LEN: usize = ETH_HEADER_LEN + 20 + 8;
 const UDP_MAILBOX_CAP: usize = 512;
 const CURL_HTTP_BUF: usize = 2048;
 const CURL_WAIT_TICKS: u64 = 300;
+const ARP_WAIT_TICKS: u64 = 200;
 const DHCP_WAIT_TICKS: u64 = 400;
 
 const LOCAL_IP: [u8; 4] = [10, 0, 2, 15];
 const LOCAL_NETMASK: [u8; 4] = [255, 255, 255, 0];
 const LOCAL_GATEWAY: [u8; 4] = [10, 0, 2, 2];
 const UDP_ECHO_PORT: u16 = 7777;
+/// `udp rtt` probes leave from here rather than the echo port, so replies are not echoed back.
+const UDP_RTT_PORT: u16 = 7778;
+/// Discard port, the de-facto default for wake-on-LAN listeners.
+const WOL_DEFAULT_PORT: u16 = 9;
+/// Six 0xFF bytes followed by the target MAC repeated 16 times.
+const WOL_MAGIC_LEN: usize = 6 + 16 * 6;
 const PING_IDENTIFIER: u16 = 0xA707;
 const UDP_DHCP_SERVER_PORT: u16 = 67;
 const UDP_DHCP_CLIENT_PORT: u16 = 68;
@@ -60,6 +121,9 @@ const UDP_DNS_PORT: u16 = 53;
 const IP_BROADCAST: [u8; 4] = [255, 255, 255, 255];
 const IP_ZERO: [u8; 4] = [0, 0, 0, 0];
 const MAC_BROADCAST: [u8; 6] = [0xff; 6];
+const IP_ALL_HOSTS: [u8; 4] = [224, 0, 0, 1];
+const IP_ALL_ROUTERS: [u8; 4] = [224, 0, 0, 2];
+const MAX_MCAST_GROUPS: usize = 8;
 const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
 const DNS_WAIT_TICKS: u64 = 300;
 
@@ -77,12 +141,21 @@ const DHCP_MSG_DISCOVER: u8 = 1;
 const DHCP_MSG_OFFER: u8 = 2;
 const DHCP_MSG_REQUEST: u8 = 3;
 const DHCP_MSG_ACK: u8 = 5;
+/// Parameter request list sent with DISCOVER and REQUEST.
+const DHCP_PARAMS: [u8; 3] = [DHCP_OPT_SUBNET_MASK, DHCP_OPT_ROUTER, DHCP_OPT_DNS];
 
 const ETH_TYPE_ARP: u16 = 0x0806;
 const ETH_TYPE_IPV4: u16 = 0x0800;
 const IP_PROTO_TCP: u8 = 6;
 const IP_PROTO_ICMP: u8 = 1;
 const IP_PROTO_UDP: u8 = 17;
+const IP_PROTO_IGMP: u8 = 2;
+const IPV4_MORE_FRAGMENTS: u16 = 0x2000;
+const IPV4_FRAGMENT_OFFSET_MASK: u16 = 0x1fff;
+
+const IGMP_MEMBERSHIP_QUERY: u8 = 0x11;
+const IGMP_V2_MEMBERSHIP_REPORT: u8 = 0x16;
+const IGMP_LEAVE_GROUP: u8 = 0x17;
 
 const TCP_FLAG_FIN: u16 = 0x01;
 const TCP_FLAG_SYN: u16 = 0x02;
@@ -110,30 +183,141 @@ struct VirtqUsedElem {
     len: u32,
 }
 
-#[repr(C)]
-struct VirtqAvail {
-    flags: u16,
-    idx: u16,
-    ring: [u16; MAX_QUEUE_SIZE_USIZE],
-    used_event: u16,
+// Avail and used rings share the layout `flags: u16, idx: u16, ring[size], event: u16`.
+const VIRTQ_IDX_OFFSET: usize = 2;
+const VIRTQ_RING_OFFSET: usize = 4;
+const VIRTQ_EVENT_BYTES: usize = 2;
+
+/// One legacy vring in DMA memory, laid out for the size the device reported.
+#[derive(Clone, Copy)]
+struct Vring {
+    virt: usize,
+    phys: u64,
+    avail_offset: usize,
+    used_offset: usize,
 }
 
-#[repr(C)]
-struct VirtqUsed {
-    flags: u16,
-    idx: u16,
-    ring: [VirtqUsedElem; MAX_QUEUE_SIZE_USIZE],
-    avail_event: u16,
+/// DMA blocks carved on the first bring-up. The pool never frees, so `restart net` reuses them
+/// instead of allocating again.
+#[derive(Clone, Copy)]
+struct NetDma {
+    /// Indexed by virtqueue: RX, TX.
+    rings: [Option<DmaBlock>; 2],
+    rx: Option<DmaBlock>,
+    tx: Option<DmaBlock>,
+}
+
+#[derive(Clone, Copy)]
+struct DmaBlock {
+    region: DmaRegion,
+    len: usize,
+}
+
+impl NetDma {
+    const fn new() -> Self {
+        Self {
+            rings: [None; 2],
+            rx: None,
+            tx: None,
+        }
+    }
+}
+
+/// Buffer indexes the driver holds, one bit each; the others are posted to the device.
+#[derive(Clone, Copy)]
+struct FreeList(u32);
+
+impl FreeList {
+    const CAPACITY: usize = u32::BITS as usize;
+
+    /// Buffers `0..count`, all held by the driver.
+    const fn all(count: usize) -> Self {
+        if count >= Self::CAPACITY {
+            Self(u32::MAX)
+        } else {
+            Self((1 << count) - 1)
+        }
+    }
+
+    /// Lowest free index, taken off the list.
+    const fn pop(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(index)
+    }
+
+    /// Gives `index` back; false when it was not taken, i.e. the device completed it twice.
+    const fn push(&mut self, index: usize) -> bool {
+        let bit = 1 << index;
+        let taken = self.0 & bit == 0;
+        self.0 |= bit;
+        taken
+    }
+
+    const fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+}
+
+/// `slot`'s block, zeroed again, when it holds `len` bytes at `align`; otherwise a new one.
+fn reuse_dma(slot: &mut Option<DmaBlock>, len: usize, align: usize) -> Option<DmaRegion> {
+    if let Some(block) = *slot
+        && block.len >= len
+        && block.region.phys().is_multiple_of(align as u64)
+    {
+        // SAFETY: the block belongs to this driver and the device was reset before a restart,
+        // so nothing else reads or writes it.
+        unsafe { core::ptr::write_bytes(block.region.virt() as *mut u8, 0, len) };
+        return Some(block.region);
+    }
+    let region = mem::dma::alloc_zeroed(len, align)?;
+    *slot = Some(DmaBlock { region, len });
+    Some(region)
 }
 
-const DESC_BYTES: usize = size_of::<VirtqDesc>() * MAX_QUEUE_SIZE_USIZE;
-const AVAIL_BYTES: usize = size_of::<VirtqAvail>();
-const USED_OFFSET: usize = align_up(DESC_BYTES + AVAIL_BYTES, VRING_ALIGN);
-const VRING_BYTES: usize = USED_OFFSET + size_of::<VirtqUsed>();
+impl Vring {
+    const fn empty() -> Self {
+        Self {
+            virt: 0,
+            phys: 0,
+            avail_offset: 0,
+            used_offset: 0,
+        }
+    }
+
+    /// Returns `(avail_offset, used_offset, total_bytes)` for a queue of `size` entries.
+    const fn layout(size: u16) -> (usize, usize, usize) {
+        let size = size as usize;
+        let avail_offset = size_of::<VirtqDesc>() * size;
+        let avail_bytes = VIRTQ_RING_OFFSET + size_of::<u16>() * size + VIRTQ_EVENT_BYTES;
+        let used_offset = align_up(avail_offset + avail_bytes, VRING_ALIGN);
+        let used_bytes = VIRTQ_RING_OFFSET + size_of::<VirtqUsedElem>() * size + VIRTQ_EVENT_BYTES;
+        (avail_offset, used_offset, used_offset + used_bytes)
+    }
+
+    fn desc(&self, index: u16) -> *mut VirtqDesc {
+        (self.virt as *mut VirtqDesc).wrapping_add(index as usize)
+    }
+
+    fn avail_idx(&self) -> *mut u16 {
+        (self.virt + self.avail_offset + VIRTQ_IDX_OFFSET) as *mut u16
+    }
+
+    fn avail_slot(&self, slot: usize) -> *mut u16 {
+        (self.virt + self.avail_offset + VIRTQ_RING_OFFSET + slot * size_of::<u16>()) as *mut u16
+    }
+
+    fn used_idx(&self) -> *const u16 {
+        (self.virt + self.used_offset + VIRTQ_IDX_OFFSET) as *const u16
+    }
 
-#[repr(C, align(4096))]
-struct QueueMemory {
-    bytes: [u8; VRING_BYTES],
+    fn used_elem(&self, slot: usize) -> *const VirtqUsedElem {
+        (self.virt + self.used_offset + VIRTQ_RING_OFFSET + slot * size_of::<VirtqUsedElem>())
+            as *const VirtqUsedElem
+    }
 }
 
 #[repr(C)]
@@ -147,60 +331,14 @@ struct VirtioNetHdr {
     csum_offset: u16,
 }
 
-#[repr(C, align(16))]
-struct RxBuffer {
-    hdr: VirtioNetHdr,
-    frame: [u8; MAX_RX_FRAME],
-}
-
 #[repr(C, align(16))]
 struct TxBuffer {
     hdr: VirtioNetHdr,
+    /// Protocol headers of a scatter-gather send; the payload then goes at the start of `frame`.
+    headers: [u8; UDP_HEADERS_LEN],
     frame: [u8; MAX_TX_FRAME],
 }
 
-struct QueueMemoryCell(UnsafeCell<QueueMemory>);
-struct RxBufferCell(UnsafeCell<RxBuffer>);
-struct TxBufferCell(UnsafeCell<TxBuffer>);
-
-// SAFETY: synchronized via `NET_LOCK`.
-unsafe impl Sync for QueueMemoryCell {}
-// SAFETY: synchronized via `NET_LOCK`.
-unsafe impl Sync for RxBufferCell {}
-// SAFETY: synchronized via `NET_LOCK`.
-unsafe impl Sync for TxBufferCell {}
-
-static RX_QUEUE_MEMORY: QueueMemoryCell = QueueMemoryCell(UnsafeCell::new(QueueMemory {
-    bytes: [0; VRING_BYTES],
-}));
-static TX_QUEUE_MEMORY: QueueMemoryCell = QueueMemoryCell(UnsafeCell::new(QueueMemory {
-    bytes: [0; VRING_BYTES],
-}));
-
-static RX_BUFFER: RxBufferCell = RxBufferCell(UnsafeCell::new(RxBuffer {
-    hdr: VirtioNetHdr {
-        flags: 0,
-        gso_type: 0,
-        hdr_len: 0,
-        gso_size: 0,
-        csum_start: 0,
-        csum_offset: 0,
-    },
-    frame: [0; MAX_RX_FRAME],
-}));
-
-static TX_BUFFER: TxBufferCell = TxBufferCell(UnsafeCell::new(TxBuffer {
-    hdr: VirtioNetHdr {
-        flags: 0,
-        gso_type: 0,
-        hdr_len: 0,
-        gso_size: 0,
-        csum_start: 0,
-        csum_offset: 0,
-    },
-    frame: [0; MAX_TX_FRAME],
-}));
-
 #[derive(Clone, Copy)]
 struct ArpEntry {
     valid: bool,
@@ -218,6 +356,21 @@ impl ArpEntry {
     }
 }
 
+#[derive(Clone, Copy)]
+struct McastMembership {
+    valid: bool,
+    group: [u8; 4],
+}
+
+impl McastMembership {
+    const fn empty() -> Self {
+        Self {
+            valid: false,
+            group: [0; 4],
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 struct PendingPing {
     active: bool,
@@ -244,44 +397,80 @@ impl PendingPing {
 #[derive(Clone, Copy)]
 struct NetStats {
     rx_frames: u64,
+    /// Ethernet frame bytes received, headers included.
+    rx_bytes: u64,
     tx_frames: u64,
+    /// Frames sent as header + payload descriptor chains.
+    tx_sg: u64,
+    /// Sends that found every TX slot in flight and had to wait for the device.
+    tx_ring_waits: u64,
     rx_arp: u64,
     rx_ipv4: u64,
     rx_icmp: u64,
     rx_udp: u64,
     rx_tcp: u64,
+    rx_broadcast: u64,
+    rx_multicast: u64,
+    rx_igmp: u64,
+    igmp_reports: u64,
     dhcp_discover: u64,
     dhcp_offer: u64,
     dhcp_ack: u64,
+    dhcp_nak: u64,
+    dhcp_renew: u64,
+    dhcp_release: u64,
     dns_query: u64,
     dns_answer: u64,
     curl_udp: u64,
     curl_http: u64,
+    tcp_retransmits: u64,
+    /// Keepalive probes sent on idle connections.
+    tcp_keepalive_probes: u64,
+    /// Connections reset because the peer stopped answering.
+    tcp_reaped: u64,
+    wol_sent: u64,
     route_direct: u64,
     route_gateway: u64,
     dropped: u64,
+    /// Per-reason breakdown of the validation failures inside `dropped`.
+    rx_drops: [u64; RxDrop::ALL.len()],
 }
 
 impl NetStats {
     const fn new() -> Self {
         Self {
             rx_frames: 0,
+            rx_bytes: 0,
             tx_frames: 0,
+            tx_sg: 0,
+            tx_ring_waits: 0,
             rx_arp: 0,
             rx_ipv4: 0,
             rx_icmp: 0,
             rx_udp: 0,
             rx_tcp: 0,
+            rx_broadcast: 0,
+            rx_multicast: 0,
+            rx_igmp: 0,
+            igmp_reports: 0,
             dhcp_discover: 0,
             dhcp_offer: 0,
             dhcp_ack: 0,
+            dhcp_nak: 0,
+            dhcp_renew: 0,
+            dhcp_release: 0,
             dns_query: 0,
             dns_answer: 0,
             curl_udp: 0,
             curl_http: 0,
+            tcp_retransmits: 0,
+            tcp_keepalive_probes: 0,
+            tcp_reaped: 0,
+            wol_sent: 0,
             route_direct: 0,
             route_gateway: 0,
             dropped: 0,
+            rx_drops: [0; RxDrop::ALL.len()],
         }
     }
 }
@@ -315,6 +504,8 @@ struct UdpMailbox {
     src_ip: [u8; 4],
     src_port: u16,
     dst_port: u16,
+    broadcast: bool,
+    rx_tick: u64,
     len: usize,
     data: [u8; UDP_MAILBOX_CAP],
 }
@@ -326,6 +517,8 @@ impl UdpMailbox {
             src_ip: [0; 4],
             src_port: 0,
             dst_port: 0,
+            broadcast: false,
+            rx_tick: 0,
             len: 0,
             data: [0; UDP_MAILBOX_CAP],
         }
@@ -347,6 +540,18 @@ impl IpConfigSource {
     }
 }
 
+/// Where a DHCP exchange stands for the service waiting on it, in the order it gets there.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+enum DhcpProgress {
+    Waiting,
+    Offered,
+    Acked,
+    /// The server answered with a NAK.
+    Refused,
+    /// `restart net`, `ifconfig addr` or `dhcp release` took the interface over meanwhile.
+    Superseded,
+}
+
 #[derive(Clone, Copy)]
 struct DhcpOffer {
     valid: bool,
@@ -370,45 +575,22 @@ impl DhcpOffer {
     }
 }
 
+/// Addressing of one TCP connection as seen from this host.
 #[derive(Clone, Copy)]
-struct PendingHttpCurl {
-    active: bool,
-    dst_mac: [u8; 6],
-    remote_ip: [u8; 4],
-    remote_port: u16,
+struct TcpLink {
+    mac: [u8; 6],
+    ip: [u8; 4],
     local_port: u16,
-    seq_next: u32,
-    ack_next: u32,
-    established: bool,
-    sent_request: bool,
-    finished: bool,
-    status_code: u16,
-    response_len: usize,
-    response: [u8; CURL_HTTP_BUF],
+    remote_port: u16,
 }
 
-impl PendingHttpCurl {
-    const fn empty() -> Self {
-        Self {
-            active: false,
-            dst_mac: [0; 6],
-            remote_ip: [0; 4],
-            remote_port: 0,
-            local_port: 0,
-            seq_next: 0,
-            ack_next: 0,
-            established: false,
-            sent_request: false,
-            finished: false,
-            status_code: 0,
-            response_len: 0,
-            response: [0; CURL_HTTP_BUF],
-        }
-    }
-
-    fn clear(&mut self) {
-        *self = Self::empty();
-    }
+/// Cumulative traffic counters exposed to the telemetry stream.
+#[derive(Clone, Copy)]
+pub struct NetCounters {
+    pub ready: bool,
+    pub rx_frames: u64,
+    pub tx_frames: u64,
+    pub dropped: u64,
 }
 
 #[derive(Clone, Copy)]
@@ -423,6 +605,9 @@ pub struct NetInitReport {
     pub mac: [u8; 6],
     pub ipv4: [u8; 4],
     pub config_source: &'static str,
+    pub mtu: usize,
+    /// MTU advertised through VIRTIO_NET_F_MTU; `None` when the feature is not offered.
+    pub device_mtu: Option<u16>,
 }
 
 #[derive(Clone, Copy)]
@@ -430,6 +615,10 @@ pub struct UdpRxMeta {
     pub src_ip: [u8; 4],
     pub src_port: u16,
     pub dst_port: u16,
+    pub broadcast: bool,
+    /// Tick of the `net::poll` that took the datagram off the ring, so round trips measured
+    /// from it leave out how long the reader took to wake up.
+    pub rx_tick: u64,
     pub len: usize,
 }
 
@@ -439,11 +628,23 @@ pub enum NetError {
     NotFound,
     QueueUnavailable,
     QueueTooLarge,
-    AddressTranslationFailed,
     FrameTooLarge,
     IoTimeout,
     ArpTimeout,
     UdpPayloadTooLarge,
+    InvalidMtu,
+    DmaUnavailable,
+    InvalidGroup,
+    GroupTableFull,
+    InvalidAddress,
+    NoLease,
+    WouldBlock,
+    NotConnected,
+    ConnectionReset,
+    ConnectionRefused,
+    InvalidSocket,
+    SocketTableFull,
+    PortInUse,
 }
 
 impl NetError {
@@ -453,11 +654,23 @@ impl NetError {
             Self::NotFound => "not_found",
             Self::QueueUnavailable => "queue_unavailable",
             Self::QueueTooLarge => "queue_too_large",
-            Self::AddressTranslationFailed => "address_translation_failed",
             Self::FrameTooLarge => "frame_too_large",
             Self::IoTimeout => "io_timeout",
             Self::ArpTimeout => "arp_timeout",
             Self::UdpPayloadTooLarge => "udp_payload_too_large",
+            Self::InvalidMtu => "invalid_mtu",
+            Self::DmaUnavailable => "dma_unavailable",
+            Self::InvalidGroup => "invalid_group",
+            Self::GroupTableFull => "group_table_full",
+            Self::InvalidAddress => "invalid_address",
+            Self::NoLease => "no_lease",
+            Self::WouldBlock => "would_block",
+            Self::NotConnected => "not_connected",
+            Self::ConnectionReset => "connection_reset",
+            Self::ConnectionRefused => "connection_refused",
+            Self::InvalidSocket => "invalid_socket",
+            Self::SocketTableFull => "socket_table_full",
+            Self::PortInUse => "port_in_use",
         }
     }
 }
@@ -493,27 +706,61 @@ struct NetState {
     gateway: [u8; 4],
     dns: [u8; 4],
     config_source: IpConfigSource,
+    mtu: usize,
+    /// Largest MTU accepted by `set_mtu`: the device's VIRTIO_NET_F_MTU value when offered.
+    max_mtu: usize,
+    device_mtu: Option<u16>,
     rx_queue_size: u16,
     tx_queue_size: u16,
+    rx_ring: Vring,
+    tx_ring: Vring,
+    /// `rx_count` buffers `rx_stride` bytes apart, each a virtio header followed by a frame
+    /// area of `rx_capacity` bytes sized for `max_mtu`; buffer `n` owns descriptors `2n, 2n+1`.
+    rx_buffers: usize,
+    rx_buffers_phys: u64,
+    rx_stride: usize,
+    rx_count: usize,
+    rx_capacity: usize,
+    /// RX buffers consumed by the stack and not yet posted again.
+    rx_free: FreeList,
+    /// `tx_slots` consecutive `TxBuffer`s; slot `n` owns descriptors `3n..3n+3`.
+    tx_buffers: usize,
+    tx_buffers_phys: u64,
+    tx_slots: usize,
+    /// TX slots the device is done with.
+    tx_free: FreeList,
     rx_last_used: u16,
     rx_avail: u16,
     tx_last_used: u16,
     tx_avail: u16,
-    rx_hdr_phys: u64,
-    rx_frame_phys: u64,
-    tx_hdr_phys: u64,
-    tx_frame_phys: u64,
+    dma: NetDma,
     next_ip_id: u16,
     next_ping_seq: u16,
     arp: [ArpEntry; 8],
+    mcast_groups: [McastMembership; MAX_MCAST_GROUPS],
     pending_ping: PendingPing,
+    paths: path::PathTable,
     stats: NetStats,
     last_udp: LastUdp,
     udp_mailbox: UdpMailbox,
-    pending_http: PendingHttpCurl,
+    /// Async service waiting for the next mailbox datagram; woken from `handle_udp`.
+    udp_waiter: Option<Waker>,
+    tcp: tcp::TcpTable,
+    ports: ports::PortAllocator,
+    /// Set while `net bench rx` runs; flood datagrams for its port stop here.
+    bench_rx: Option<bench::RxBench>,
+    services: service::Services,
+    firewall: firewall::Firewall,
+    acd: acd::Acd,
+    netconsole: netconsole::NetConsole,
     dhcp_xid: u32,
+    /// Xid of the last exchange an ACK completed; `dhcp_xid` is cleared at that point.
+    dhcp_acked_xid: u32,
     dhcp_offer: DhcpOffer,
     dhcp_bound: bool,
+    /// Set when the server answered the pending xid with a NAK.
+    dhcp_nak: bool,
+    dhcp_lease: dhcp::Lease,
 }
 
 impl NetState {
@@ -532,27 +779,51 @@ impl NetState {
             gateway: LOCAL_GATEWAY,
             dns: [0; 4],
             config_source: IpConfigSource::Static,
+            mtu: DEFAULT_MTU,
+            max_mtu: MAX_MTU,
+            device_mtu: None,
             rx_queue_size: 0,
             tx_queue_size: 0,
+            rx_ring: Vring::empty(),
+            tx_ring: Vring::empty(),
+            rx_buffers: 0,
+            rx_buffers_phys: 0,
+            rx_stride: 0,
+            rx_count: 0,
+            rx_capacity: 0,
+            rx_free: FreeList::all(0),
+            tx_buffers: 0,
+            tx_buffers_phys: 0,
+            tx_slots: 0,
+            tx_free: FreeList::all(0),
             rx_last_used: 0,
             rx_avail: 0,
             tx_last_used: 0,
             tx_avail: 0,
-            rx_hdr_phys: 0,
-            rx_frame_phys: 0,
-            tx_hdr_phys: 0,
-            tx_frame_phys: 0,
+            dma: NetDma::new(),
             next_ip_id: 1,
             next_ping_seq: 1,
             arp: [ArpEntry::empty(); 8],
+            mcast_groups: [McastMembership::empty(); MAX_MCAST_GROUPS],
             pending_ping: PendingPing::empty(),
+            paths: path::PathTable::new(),
             stats: NetStats::new(),
             last_udp: LastUdp::empty(),
             udp_mailbox: UdpMailbox::empty(),
-            pending_http: PendingHttpCurl::empty(),
+            udp_waiter: None,
+            tcp: tcp::TcpTable::new(),
+            ports: ports::PortAllocator::new(),
+            bench_rx: None,
+            services: service::Services::new(),
+            firewall: firewall::Firewall::new(),
+            acd: acd::Acd::new(),
+            netconsole: netconsole::NetConsole::new(),
             dhcp_xid: 0,
+            dhcp_acked_xid: 0,
             dhcp_offer: DhcpOffer::empty(),
             dhcp_bound: false,
+            dhcp_nak: false,
+            dhcp_lease: dhcp::Lease::empty(),
         }
     }
 
@@ -572,6 +843,8 @@ impl NetState {
             mac: self.mac,
             ipv4: self.ipv4,
             config_source: self.config_source.as_str(),
+            mtu: self.mtu,
+            device_mtu: self.device_mtu,
         }
     }
 
@@ -586,12 +859,34 @@ impl NetState {
             Err(err) => {
                 self.initialized = true;
                 self.ready = false;
-                serial::write_fmt(format_args!("Net: init failed ({})\n", err.as_str()));
+                log::write(
+                    Subsystem::Net,
+                    Level::Error,
+                    format_args!("Net: init failed ({})\n", err.as_str()),
+                );
             }
         }
         self.report()
     }
 
+    /// `restart net`: resets the device and brings it up again as at boot, so stats, caches,
+    /// groups, TCP sockets and the address (DHCP runs again) start over. Firewall rules,
+    /// services, netconsole, the port range with the UDP ports held and the DMA blocks are kept.
+    fn restart(&mut self) -> NetInitReport {
+        if self.ready {
+            self.virtio_write_status(0);
+        }
+        let previous = core::mem::replace(self, Self::new());
+        self.dma = previous.dma;
+        self.firewall = previous.firewall;
+        self.services = previous.services;
+        self.netconsole = previous.netconsole;
+        self.udp_waiter = previous.udp_waiter;
+        self.tcp = tcp::TcpTable::after_restart(&previous.tcp);
+        self.ports = ports::PortAllocator::after_restart(&previous.ports);
+        self.init()
+    }
+
     fn try_init(&mut self) -> Result<(), NetError> {
         let Some(device) = find_virtio_net_pci() else {
             return Err(NetError::NotFound);
@@ -609,43 +904,31 @@ impl NetState {
         self.virtio_write_status(0);
         self.virtio_write_status(VIRTIO_STATUS_ACK);
         self.virtio_write_status(VIRTIO_STATUS_ACK | VIRTIO_STATUS_DRIVER);
-        let _ = self.virtio_read_u32(VIRTIO_PCI_HOST_FEATURES);
-        self.virtio_write_u32(VIRTIO_PCI_GUEST_FEATURES, 0);
+        let host_features = self.virtio_read_u32(VIRTIO_PCI_HOST_FEATURES);
+        let mut guest_features = 0;
+        if host_features & VIRTIO_NET_F_MTU != 0 {
+            guest_features |= VIRTIO_NET_F_MTU;
+            let device_mtu = self.virtio_read_u16(VIRTIO_PCI_DEVICE_CONFIG + VIRTIO_NET_CONFIG_MTU);
+            self.device_mtu = Some(device_mtu);
+            self.max_mtu = usize::from(device_mtu).clamp(MIN_MTU, MAX_MTU);
+            self.mtu = self.max_mtu;
+        }
+        self.virtio_write_u32(VIRTIO_PCI_GUEST_FEATURES, guest_features);
 
         self.setup_queue(RX_QUEUE_INDEX)?;
         self.setup_queue(TX_QUEUE_INDEX)?;
-        self.setup_buffers_phys()?;
+        self.setup_buffers()?;
         self.setup_rx_descriptors()?;
-        self.post_rx_buffer()?;
+        self.refill_rx()?;
 
         self.virtio_write_status(
             VIRTIO_STATUS_ACK | VIRTIO_STATUS_DRIVER | VIRTIO_STATUS_DRIVER_OK,
         );
         self.ready = true;
 
-        if !self.try_dhcp()? {
-            self.config_source = IpConfigSource::Static;
-            serial::write_line("Net: DHCP unavailable, using static 10.0.2.15/24 gw 10.0.2.2");
-        } else {
-            serial::write_fmt(format_args!(
-                "Net: DHCP lease ip={}.{}.{}.{} mask={}.{}.{}.{} gw={}.{}.{}.{} dns={}.{}.{}.{}\n",
-                self.ipv4[0],
-                self.ipv4[1],
-                self.ipv4[2],
-                self.ipv4[3],
-                self.netmask[0],
-                self.netmask[1],
-                self.netmask[2],
-                self.netmask[3],
-                self.gateway[0],
-                self.gateway[1],
-                self.gateway[2],
-                self.gateway[3],
-                self.dns[0],
-                self.dns[1],
-                self.dns[2],
-                self.dns[3]
-            ));
+        // The lease arrives from the run loop; until then the static address is in use.
+        if !executor::spawn("dhcp", dhcp_boot()) {
+            self.dhcp_unavailable();
         }
         Ok(())
     }
@@ -662,16 +945,19 @@ impl NetState {
             return Err(NetError::QueueTooLarge);
         }
 
-        // SAFETY: `NET_LOCK` serializes exclusive access to queue memory.
-        unsafe {
-            queue_bytes_mut(queue).fill(0);
-        }
-
-        let queue_phys = mem::virt_to_phys(queue_base_ptr(queue) as usize)
-            .ok_or(NetError::AddressTranslationFailed)?;
-        if !queue_phys.is_multiple_of(VRING_ALIGN as u64) {
-            return Err(NetError::AddressTranslationFailed);
-        }
+        let (avail_offset, used_offset, ring_bytes) = Vring::layout(size);
+        let slot = &mut self.dma.rings[usize::from(queue)];
+        let Some(region) = reuse_dma(slot, ring_bytes, VRING_ALIGN) else {
+            self.virtio_write_status(VIRTIO_STATUS_FAILED);
+            return Err(NetError::DmaUnavailable);
+        };
+        let ring = Vring {
+            virt: region.virt(),
+            phys: region.phys(),
+            avail_offset,
+            used_offset,
+        };
+        let queue_phys = ring.phys;
 
         self.virtio_write_u32(VIRTIO_PCI_QUEUE_PFN, (queue_phys >> 12) as u32);
         if self.virtio_read_u32(VIRTIO_PCI_QUEUE_PFN) == 0 {
@@ -681,116 +967,235 @@ impl NetState {
 
         if queue == RX_QUEUE_INDEX {
             self.rx_queue_size = size;
+            self.rx_ring = ring;
             self.rx_last_used = 0;
             self.rx_avail = 0;
         } else {
             self.tx_queue_size = size;
+            self.tx_ring = ring;
             self.tx_last_used = 0;
             self.tx_avail = 0;
         }
         Ok(())
     }
 
-    fn setup_buffers_phys(&mut self) -> Result<(), NetError> {
-        let rx_hdr = rx_hdr_ptr() as usize;
-        let rx_frame = rx_frame_ptr() as usize;
-        let tx_hdr = tx_hdr_ptr() as usize;
-        let tx_frame = tx_frame_ptr() as usize;
-        self.rx_hdr_phys = mem::virt_to_phys(rx_hdr).ok_or(NetError::AddressTranslationFailed)?;
-        self.rx_frame_phys =
-            mem::virt_to_phys(rx_frame).ok_or(NetError::AddressTranslationFailed)?;
-        self.tx_hdr_phys = mem::virt_to_phys(tx_hdr).ok_or(NetError::AddressTranslationFailed)?;
-        self.tx_frame_phys =
-            mem::virt_to_phys(tx_frame).ok_or(NetError::AddressTranslationFailed)?;
+    fn setup_buffers(&mut self) -> Result<(), NetError> {
+        let rx_capacity = ETH_HEADER_LEN + self.max_mtu;
+        let rx_stride = align_up(NET_HDR_SIZE + rx_capacity, align_of::<TxBuffer>());
+        let rx_count = (usize::from(self.rx_queue_size) / RX_DESCS_PER_BUFFER).min(RX_BUFFERS);
+        let tx_slots = (usize::from(self.tx_queue_size) / TX_DESCS_PER_SLOT).min(TX_SLOTS);
+        if rx_count == 0 || tx_slots == 0 {
+            return Err(NetError::QueueUnavailable);
+        }
+        let rx = reuse_dma(
+            &mut self.dma.rx,
+            rx_count * rx_stride,
+            align_of::<TxBuffer>(),
+        )
+        .ok_or(NetError::DmaUnavailable)?;
+        let tx = reuse_dma(
+            &mut self.dma.tx,
+            tx_slots * size_of::<TxBuffer>(),
+            align_of::<TxBuffer>(),
+        )
+        .ok_or(NetError::DmaUnavailable)?;
+        self.rx_buffers = rx.virt();
+        self.rx_buffers_phys = rx.phys();
+        self.rx_stride = rx_stride;
+        self.rx_count = rx_count;
+        self.rx_capacity = rx_capacity;
+        self.rx_free = FreeList::all(rx_count);
+        self.tx_buffers = tx.virt();
+        self.tx_buffers_phys = tx.phys();
+        self.tx_slots = tx_slots;
+        self.tx_free = FreeList::all(tx_slots);
         Ok(())
     }
 
     fn setup_rx_descriptors(&mut self) -> Result<(), NetError> {
-        // SAFETY: descriptor memory belongs to queue0 and access is serialized by `NET_LOCK`.
-        unsafe {
-            let desc = queue_desc_ptr(RX_QUEUE_INDEX);
-            write_volatile(
-                desc.add(0),
-                VirtqDesc {
-                    addr: self.rx_hdr_phys,
-                    len: NET_HDR_SIZE as u32,
-                    flags: VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_WRITE,
-                    next: 1,
-                },
-            );
-            write_volatile(
-                desc.add(1),
-                VirtqDesc {
-                    addr: self.rx_frame_phys,
-                    len: MAX_RX_FRAME as u32,
-                    flags: VIRTQ_DESC_F_WRITE,
-                    next: 0,
-                },
-            );
+        for buffer in 0..self.rx_count {
+            let head = (buffer * RX_DESCS_PER_BUFFER) as u16;
+            let phys = self.rx_buffers_phys + (buffer * self.rx_stride) as u64;
+            // SAFETY: descriptor memory belongs to queue0 and access is serialized by `NET_LOCK`.
+            unsafe {
+                write_volatile(
+                    self.rx_ring.desc(head),
+                    VirtqDesc {
+                        addr: phys,
+                        len: NET_HDR_SIZE as u32,
+                        flags: VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_WRITE,
+                        next: head + 1,
+                    },
+                );
+                write_volatile(
+                    self.rx_ring.desc(head + 1),
+                    VirtqDesc {
+                        addr: phys + NET_HDR_SIZE as u64,
+                        len: (ETH_HEADER_LEN + self.mtu) as u32,
+                        flags: VIRTQ_DESC_F_WRITE,
+                        next: 0,
+                    },
+                );
+            }
         }
         Ok(())
     }
 
-    fn post_rx_buffer(&mut self) -> Result<(), NetError> {
+    /// Posts every RX buffer the stack is done with, with one notify for the batch.
+    fn refill_rx(&mut self) -> Result<(), NetError> {
         if self.rx_queue_size == 0 {
             return Err(NetError::QueueUnavailable);
         }
-        // SAFETY: queue0 avail ring is only modified while holding `NET_LOCK`.
-        unsafe {
-            let avail = queue_avail_ptr(RX_QUEUE_INDEX);
-            let slot = (self.rx_avail % self.rx_queue_size) as usize;
-            write_volatile(addr_of_mut!((*avail).ring[slot]), 0);
-            fence(Ordering::SeqCst);
-            self.rx_avail = self.rx_avail.wrapping_add(1);
-            write_volatile(addr_of_mut!((*avail).idx), self.rx_avail);
+        if self.rx_free.len() == 0 {
+            return Ok(());
+        }
+        while let Some(buffer) = self.rx_free.pop() {
+            // SAFETY: queue0 avail ring is only modified while holding `NET_LOCK`.
+            unsafe {
+                let slot = (self.rx_avail % self.rx_queue_size) as usize;
+                write_volatile(
+                    self.rx_ring.avail_slot(slot),
+                    (buffer * RX_DESCS_PER_BUFFER) as u16,
+                );
+                self.rx_avail = self.rx_avail.wrapping_add(1);
+            }
         }
+        fence(Ordering::SeqCst);
+        // SAFETY: as above.
+        unsafe { write_volatile(self.rx_ring.avail_idx(), self.rx_avail) };
         self.virtio_write_u16(VIRTIO_PCI_QUEUE_NOTIFY, RX_QUEUE_INDEX);
         Ok(())
     }
 
+    fn set_mtu(&mut self, mtu: usize) -> Result<(), NetError> {
+        if !self.ready {
+            return Err(NetError::NotReady);
+        }
+        if !(MIN_MTU..=self.max_mtu).contains(&mtu) {
+            return Err(NetError::InvalidMtu);
+        }
+        self.mtu = mtu;
+        // Posted RX buffers stay posted; only the length the device may fill changes.
+        self.setup_rx_descriptors()
+    }
+
+    /// Static unicast address on the current netmask; replaces a DHCP lease.
+    fn set_ipv4(&mut self, ip: [u8; 4]) -> Result<(), NetError> {
+        if !self.ready {
+            return Err(NetError::NotReady);
+        }
+        if ip == IP_ZERO || ip == IP_BROADCAST || is_multicast_ip(ip) {
+            return Err(NetError::InvalidAddress);
+        }
+        self.ipv4 = ip;
+        self.config_source = IpConfigSource::Static;
+        self.dhcp_xid = 0;
+        self.dhcp_bound = false;
+        self.dhcp_lease = dhcp::Lease::empty();
+        self.start_conflict_check();
+        Ok(())
+    }
+
     fn poll(&mut self) {
         if !self.ready {
             return;
         }
-        while self.poll_rx_once().unwrap_or(false) {}
+        loop {
+            match self.poll_rx_once() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                    log_ratelimited!(
+                        Subsystem::Net,
+                        Level::Warning,
+                        "Net: rx error ({})\n",
+                        err.as_str()
+                    );
+                    break;
+                }
+            }
+        }
+        if let Err(err) = self.refill_rx() {
+            log_ratelimited!(
+                Subsystem::Net,
+                Level::Warning,
+                "Net: rx refill error ({})\n",
+                err.as_str()
+            );
+        }
+        self.reclaim_tx();
+        self.poll_service_keepalive();
+        self.poll_tcp();
+        self.poll_conflict_check();
     }
 
     fn poll_rx_once(&mut self) -> Result<bool, NetError> {
         // SAFETY: queue0 used ring access is synchronized by `NET_LOCK`.
         unsafe {
-            let used = queue_used_ptr(RX_QUEUE_INDEX);
-            let used_idx = read_volatile(addr_of!((*used).idx));
+            let used_idx = read_volatile(self.rx_ring.used_idx());
             if used_idx == self.rx_last_used {
                 return Ok(false);
             }
             let slot = (self.rx_last_used % self.rx_queue_size) as usize;
-            let elem = read_volatile(addr_of!((*used).ring[slot]));
+            let elem = read_volatile(self.rx_ring.used_elem(slot));
             self.rx_last_used = self.rx_last_used.wrapping_add(1);
-
-            let total_len = elem.len as usize;
-            let payload_len = total_len.saturating_sub(NET_HDR_SIZE).min(MAX_RX_FRAME);
-            let mut frame = [0u8; MAX_RX_FRAME];
-            if payload_len > 0 {
-                let rx_frame = &(*RX_BUFFER.0.get()).frame;
-                frame[..payload_len].copy_from_slice(&rx_frame[..payload_len]);
+            let buffer = elem.id as usize / RX_DESCS_PER_BUFFER;
+            if !soft_assert!(
+                (elem.id as usize).is_multiple_of(RX_DESCS_PER_BUFFER) && buffer < self.rx_count,
+                "net rx used id={} outside {} buffers",
+                elem.id,
+                self.rx_count
+            ) {
+                return Ok(true);
             }
 
-            self.post_rx_buffer()?;
+            let total_len = elem.len as usize;
+            soft_assert!(
+                total_len >= NET_HDR_SIZE && total_len - NET_HDR_SIZE <= self.rx_capacity,
+                "net rx used len={total_len} outside hdr={NET_HDR_SIZE} capacity={}",
+                self.rx_capacity
+            );
+            let payload_len = total_len
+                .saturating_sub(NET_HDR_SIZE)
+                .min(self.rx_capacity)
+                .min(MAX_RX_FRAME);
+            // Parsed in place: the buffer stays off the free list, so nothing reposts it to the
+            // device until `process_frame` is done with the slice.
+            let frame = core::slice::from_raw_parts(
+                (self.rx_buffers + buffer * self.rx_stride + NET_HDR_SIZE) as *const u8,
+                payload_len,
+            );
             self.stats.rx_frames = self.stats.rx_frames.saturating_add(1);
-            self.process_frame(&frame[..payload_len])?;
+            self.stats.rx_bytes = self.stats.rx_bytes.saturating_add(payload_len as u64);
+            evlog::record(
+                Event::NetRx,
+                payload_len as u32,
+                wire::be_u16(frame, 12).map_or(0, u32::from),
+            );
+            let processed = self.process_frame(frame);
+
+            soft_assert!(
+                self.rx_free.push(buffer),
+                "net rx buffer {buffer} completed while not posted"
+            );
+            // Batches notifies, but reposts before the device runs dry on a burst.
+            if self.rx_free.len() * 2 >= self.rx_count {
+                self.refill_rx()?;
+            }
+            processed?;
             Ok(true)
         }
     }
 
     fn process_frame(&mut self, frame: &[u8]) -> Result<(), NetError> {
-        if frame.len() < 14 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        if !soft_assert!(frame.len() >= 14, "net rx runt frame len={}", frame.len()) {
+            self.drop_rx(RxDrop::EthRunt);
             return Ok(());
         }
         let dst_mac = [frame[0], frame[1], frame[2], frame[3], frame[4], frame[5]];
         let src_mac = [frame[6], frame[7], frame[8], frame[9], frame[10], frame[11]];
         let ethertype = u16::from_be_bytes([frame[12], frame[13]]);
-        if dst_mac != self.mac && dst_mac != [0xff; 6] {
+        if dst_mac != self.mac && dst_mac != [0xff; 6] && !self.accepts_multicast_mac(dst_mac) {
             return Ok(());
         }
 
@@ -803,37 +1208,45 @@ impl NetState {
                 self.stats.rx_ipv4 = self.stats.rx_ipv4.saturating_add(1);
                 self.handle_ipv4(&src_mac, &frame[14..])?;
             }
-            _ => {
-                self.stats.dropped = self.stats.dropped.saturating_add(1);
-            }
+            _ => self.drop_rx(RxDrop::EthType),
         }
         Ok(())
     }
 
+    /// Counts a malformed or unsupported frame under `reason` as well as the `drop` total.
+    fn drop_rx(&mut self, reason: RxDrop) {
+        self.stats.dropped = self.stats.dropped.saturating_add(1);
+        self.stats.rx_drops[reason.index()] = self.stats.rx_drops[reason.index()].saturating_add(1);
+    }
+
     fn handle_arp(&mut self, src_mac: &[u8; 6], payload: &[u8]) -> Result<(), NetError> {
-        if payload.len() < 28 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        let (
+            Some(htype),
+            Some(ptype),
+            Some(oper),
+            Some(sender_mac),
+            Some(sender_ip),
+            Some(target_ip),
+        ) = (
+            wire::be_u16(payload, 0),
+            wire::be_u16(payload, 2),
+            wire::be_u16(payload, 6),
+            wire::array::<6>(payload, 8),
+            wire::array::<4>(payload, 14),
+            wire::array::<4>(payload, 24),
+        )
+        else {
+            self.drop_rx(RxDrop::ArpShort);
             return Ok(());
-        }
-        let htype = u16::from_be_bytes([payload[0], payload[1]]);
-        let ptype = u16::from_be_bytes([payload[2], payload[3]]);
-        let hlen = payload[4];
-        let plen = payload[5];
+        };
+        let (hlen, plen) = (payload[4], payload[5]);
         if htype != 1 || ptype != ETH_TYPE_IPV4 || hlen != 6 || plen != 4 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+            self.drop_rx(RxDrop::ArpFormat);
+            return Ok(());
+        }
+        if self.check_arp_conflict(sender_mac, sender_ip, target_ip) {
             return Ok(());
         }
-        let oper = u16::from_be_bytes([payload[6], payload[7]]);
-        let sender_mac = [
-            payload[8],
-            payload[9],
-            payload[10],
-            payload[11],
-            payload[12],
-            payload[13],
-        ];
-        let sender_ip = [payload[14], payload[15], payload[16], payload[17]];
-        let target_ip = [payload[24], payload[25], payload[26], payload[27]];
         self.learn_arp(sender_ip, sender_mac);
 
         if oper == 1 && target_ip == self.ipv4 {
@@ -843,37 +1256,57 @@ impl NetState {
     }
 
     fn handle_ipv4(&mut self, src_mac: &[u8; 6], payload: &[u8]) -> Result<(), NetError> {
-        if payload.len() < 20 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        let (Some(total_len), Some(fragment), Some(src_ip), Some(dst_ip)) = (
+            wire::be_u16(payload, 2),
+            wire::be_u16(payload, 6),
+            wire::array::<4>(payload, 12),
+            wire::array::<4>(payload, 16),
+        ) else {
+            self.drop_rx(RxDrop::IpShort);
             return Ok(());
-        }
+        };
         let version_ihl = payload[0];
         if (version_ihl >> 4) != 4 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+            self.drop_rx(RxDrop::IpVersion);
             return Ok(());
         }
         let ihl = ((version_ihl & 0x0f) as usize) * 4;
         if ihl < 20 || payload.len() < ihl {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+            self.drop_rx(RxDrop::IpHeaderLen);
+            return Ok(());
+        }
+        let Some(packet) =
+            wire::range(payload, 0, usize::from(total_len)).filter(|p| p.len() >= ihl)
+        else {
+            self.drop_rx(RxDrop::IpTotalLen);
+            return Ok(());
+        };
+        if checksum(&packet[..ihl]) != 0 {
+            self.drop_rx(RxDrop::IpChecksum);
             return Ok(());
         }
-        let total_len = u16::from_be_bytes([payload[2], payload[3]]) as usize;
-        if total_len < ihl || payload.len() < total_len {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        if !wire::ipv4_options_valid(&packet[20..ihl]) {
+            self.drop_rx(RxDrop::IpOptions);
             return Ok(());
         }
-        if checksum(&payload[..ihl]) != 0 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        if fragment & (IPV4_MORE_FRAGMENTS | IPV4_FRAGMENT_OFFSET_MASK) != 0 {
+            self.drop_rx(RxDrop::IpFragment);
             return Ok(());
         }
-        let proto = payload[9];
-        let src_ip = [payload[12], payload[13], payload[14], payload[15]];
-        let dst_ip = [payload[16], payload[17], payload[18], payload[19]];
+        let proto = packet[9];
         self.learn_arp(src_ip, *src_mac);
-        if dst_ip != self.ipv4 && dst_ip != IP_BROADCAST {
+        let broadcast = self.is_broadcast_ip(dst_ip);
+        let multicast = self.is_joined_group(dst_ip);
+        if dst_ip != self.ipv4 && !broadcast && !multicast {
             return Ok(());
         }
-        let body = &payload[ihl..total_len];
+        if broadcast {
+            self.stats.rx_broadcast = self.stats.rx_broadcast.saturating_add(1);
+        }
+        if multicast {
+            self.stats.rx_multicast = self.stats.rx_multicast.saturating_add(1);
+        }
+        let body = &packet[ihl..];
 
         match proto {
             IP_PROTO_ICMP => {
@@ -882,14 +1315,39 @@ impl NetState {
             }
             IP_PROTO_UDP => {
                 self.stats.rx_udp = self.stats.rx_udp.saturating_add(1);
-                self.handle_udp(*src_mac, src_ip, body)?;
+                self.handle_udp(*src_mac, src_ip, dst_ip, broadcast, body)?;
             }
             IP_PROTO_TCP => {
                 self.stats.rx_tcp = self.stats.rx_tcp.saturating_add(1);
-                self.handle_tcp(*src_mac, src_ip, body)?;
+                self.handle_tcp(*src_mac, src_ip, dst_ip, body)?;
             }
-            _ => {
-                self.stats.dropped = self.stats.dropped.saturating_add(1);
+            IP_PROTO_IGMP => {
+                self.stats.rx_igmp = self.stats.rx_igmp.saturating_add(1);
+                self.handle_igmp(body)?;
+            }
+            _ => self.drop_rx(RxDrop::IpProto),
+        }
+        Ok(())
+    }
+
+    fn handle_igmp(&mut self, payload: &[u8]) -> Result<(), NetError> {
+        let Some(queried) = wire::array::<4>(payload, 4).filter(|_| checksum(payload) == 0) else {
+            self.drop_rx(RxDrop::IgmpMalformed);
+            return Ok(());
+        };
+        if payload[0] != IGMP_MEMBERSHIP_QUERY {
+            // Reports from other members are ignored; IGMPv2 report suppression is not needed
+            // for a single-interface host on the QEMU user network.
+            return Ok(());
+        }
+        for index in 0..self.mcast_groups.len() {
+            let membership = self.mcast_groups[index];
+            if membership.valid && (queried == IP_ZERO || queried == membership.group) {
+                self.send_igmp(
+                    IGMP_V2_MEMBERSHIP_REPORT,
+                    membership.group,
+                    membership.group,
+                )?;
             }
         }
         Ok(())
@@ -901,8 +1359,12 @@ impl NetState {
         src_ip: [u8; 4],
         payload: &[u8],
     ) -> Result<(), NetError> {
-        if payload.len() < 8 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        let (Some(ident), Some(seq)) = (wire::be_u16(payload, 4), wire::be_u16(payload, 6)) else {
+            self.drop_rx(RxDrop::IcmpShort);
+            return Ok(());
+        };
+        if checksum(payload) != 0 {
+            self.drop_rx(RxDrop::IcmpChecksum);
             return Ok(());
         }
         let icmp_type = payload[0];
@@ -910,21 +1372,16 @@ impl NetState {
         if code != 0 {
             return Ok(());
         }
-        let ident = u16::from_be_bytes([payload[4], payload[5]]);
-        let seq = u16::from_be_bytes([payload[6], payload[7]]);
 
         if icmp_type == 8 {
-            let mut reply = [0u8; MAX_TX_FRAME];
-            if payload.len() > reply.len() {
-                return Err(NetError::FrameTooLarge);
-            }
-            reply[..payload.len()].copy_from_slice(payload);
-            reply[0] = 0;
-            reply[2] = 0;
-            reply[3] = 0;
-            let csum = checksum(&reply[..payload.len()]);
-            reply[2..4].copy_from_slice(&csum.to_be_bytes());
-            self.send_ipv4_packet(src_mac, src_ip, IP_PROTO_ICMP, &reply[..payload.len()])?;
+            self.send_ipv4_with(src_mac, src_ip, IP_PROTO_ICMP, 64, payload.len(), |reply| {
+                reply.copy_from_slice(payload);
+                reply[0] = 0;
+                reply[2] = 0;
+                reply[3] = 0;
+                let csum = checksum(reply);
+                reply[2..4].copy_from_slice(&csum.to_be_bytes());
+            })?;
         } else if icmp_type == 0
             && self.pending_ping.active
             && self.pending_ping.ident == ident
@@ -941,24 +1398,40 @@ impl NetState {
         &mut self,
         src_mac: [u8; 6],
         src_ip: [u8; 4],
+        dst_ip: [u8; 4],
+        broadcast: bool,
         payload: &[u8],
     ) -> Result<(), NetError> {
-        if payload.len() < 8 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        let (Some(src_port), Some(dst_port), Some(len), Some(sum)) = (
+            wire::be_u16(payload, 0),
+            wire::be_u16(payload, 2),
+            wire::be_u16(payload, 4),
+            wire::be_u16(payload, 6),
+        ) else {
+            self.drop_rx(RxDrop::UdpShort);
             return Ok(());
-        }
-        let src_port = u16::from_be_bytes([payload[0], payload[1]]);
-        let dst_port = u16::from_be_bytes([payload[2], payload[3]]);
-        let len = u16::from_be_bytes([payload[4], payload[5]]) as usize;
-        if len < 8 || len > payload.len() {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        };
+        let Some(datagram) = wire::range(payload, 0, usize::from(len)).filter(|d| d.len() >= 8)
+        else {
+            self.drop_rx(RxDrop::UdpLength);
+            return Ok(());
+        };
+        // A zero checksum means the sender did not compute one.
+        if sum != 0 && transport_checksum(src_ip, dst_ip, IP_PROTO_UDP, datagram) != 0 {
+            self.drop_rx(RxDrop::UdpChecksum);
             return Ok(());
         }
-        let data = &payload[8..len];
+        let data = &datagram[8..];
         if src_port == UDP_DHCP_SERVER_PORT && dst_port == UDP_DHCP_CLIENT_PORT {
             self.handle_dhcp_message(src_ip, data);
             return Ok(());
         }
+        if !self.firewall.admit(firewall::Proto::Udp, src_ip, dst_port) {
+            return Ok(());
+        }
+        if self.record_bench_rx(dst_port, data) {
+            return Ok(());
+        }
 
         self.last_udp.valid = true;
         self.last_udp.src_ip = src_ip;
@@ -968,16 +1441,28 @@ impl NetState {
         self.last_udp.preview.fill(0);
         let preview_len = data.len().min(self.last_udp.preview.len());
         self.last_udp.preview[..preview_len].copy_from_slice(&data[..preview_len]);
+        if self.deliver_service_udp(src_mac, src_ip, src_port, dst_port, data)
+            || self.deliver_netconsole_udp(src_mac, src_ip, src_port, dst_port, data)
+        {
+            return Ok(());
+        }
         self.udp_mailbox.valid = true;
         self.udp_mailbox.src_ip = src_ip;
         self.udp_mailbox.src_port = src_port;
         self.udp_mailbox.dst_port = dst_port;
+        self.udp_mailbox.broadcast = broadcast;
+        self.udp_mailbox.rx_tick = time::ticks();
         self.udp_mailbox.len = data.len().min(self.udp_mailbox.data.len());
         self.udp_mailbox.data.fill(0);
         self.udp_mailbox.data[..self.udp_mailbox.len]
             .copy_from_slice(&data[..self.udp_mailbox.len]);
+        if let Some(waker) = self.udp_waiter.take() {
+            waker.wake();
+        }
 
-        if dst_port == UDP_ECHO_PORT {
+        // Shell datagrams leave from the echo port, so two arrOSt peers would bounce a
+        // datagram between their responders forever.
+        if dst_port == UDP_ECHO_PORT && src_port != UDP_ECHO_PORT {
             self.send_udp_packet(src_mac, src_ip, src_port, UDP_ECHO_PORT, data)?;
         }
         Ok(())
@@ -985,102 +1470,54 @@ impl NetState {
 
     fn handle_tcp(
         &mut self,
-        _src_mac: [u8; 6],
+        src_mac: [u8; 6],
         src_ip: [u8; 4],
+        dst_ip: [u8; 4],
         payload: &[u8],
     ) -> Result<(), NetError> {
-        if payload.len() < 20 {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+        let Some(header) = wire::array::<20>(payload, 0) else {
+            self.drop_rx(RxDrop::TcpShort);
             return Ok(());
-        }
-        let src_port = u16::from_be_bytes([payload[0], payload[1]]);
-        let dst_port = u16::from_be_bytes([payload[2], payload[3]]);
-        let seq = u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]);
-        let ack = u32::from_be_bytes([payload[8], payload[9], payload[10], payload[11]]);
-        let data_offset = ((payload[12] >> 4) as usize) * 4;
+        };
+        let src_port = u16::from_be_bytes([header[0], header[1]]);
+        let dst_port = u16::from_be_bytes([header[2], header[3]]);
+        let seq = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let ack = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+        let data_offset = ((header[12] >> 4) as usize) * 4;
         if data_offset < 20 || payload.len() < data_offset {
-            self.stats.dropped = self.stats.dropped.saturating_add(1);
+            self.drop_rx(RxDrop::TcpDataOffset);
             return Ok(());
         }
-        let flags = u16::from(payload[13]) & 0x3f;
-        let data = &payload[data_offset..];
-
-        if !self.pending_http.active
-            || self.pending_http.remote_ip != src_ip
-            || self.pending_http.remote_port != src_port
-            || self.pending_http.local_port != dst_port
-        {
-            return Ok(());
-        }
-
-        if (flags & TCP_FLAG_RST) != 0 {
-            self.pending_http.finished = true;
+        if transport_checksum(src_ip, dst_ip, IP_PROTO_TCP, payload) != 0 {
+            self.drop_rx(RxDrop::TcpChecksum);
             return Ok(());
         }
-
-        if (flags & TCP_FLAG_SYN) != 0
-            && (flags & TCP_FLAG_ACK) != 0
-            && !self.pending_http.established
-        {
-            if ack == self.pending_http.seq_next {
-                self.pending_http.ack_next = seq.wrapping_add(1);
-                let _ = self.send_pending_tcp_segment(
-                    self.pending_http.seq_next,
-                    self.pending_http.ack_next,
-                    TCP_FLAG_ACK,
-                    &[],
-                );
-                self.pending_http.established = true;
-            }
+        if !self.firewall.admit(firewall::Proto::Tcp, src_ip, dst_port) {
             return Ok(());
         }
-
-        if !self.pending_http.established {
+        let flags = u16::from(header[13]) & 0x3f;
+        let window = u16::from_be_bytes([header[14], header[15]]);
+        let mss = if flags & TCP_FLAG_SYN != 0 {
+            tcp::parse_mss(&payload[20..data_offset])
+        } else {
+            None
+        };
+        let data = &payload[data_offset..];
+        let segment = service::TcpSegment {
+            src_port,
+            dst_port,
+            seq,
+            ack,
+            flags,
+            window,
+            mss,
+            data,
+        };
+        if self.deliver_service_tcp(src_mac, src_ip, &segment) {
             return Ok(());
         }
 
-        if !data.is_empty() {
-            if seq == self.pending_http.ack_next {
-                let available = self
-                    .pending_http
-                    .response
-                    .len()
-                    .saturating_sub(self.pending_http.response_len);
-                let copy_len = data.len().min(available);
-                if copy_len > 0 {
-                    let start = self.pending_http.response_len;
-                    let end = start + copy_len;
-                    self.pending_http.response[start..end].copy_from_slice(&data[..copy_len]);
-                    self.pending_http.response_len = end;
-                }
-                self.pending_http.ack_next =
-                    self.pending_http.ack_next.wrapping_add(data.len() as u32);
-                if self.pending_http.status_code == 0
-                    && let Some(code) = parse_http_status_code(
-                        &self.pending_http.response[..self.pending_http.response_len],
-                    )
-                {
-                    self.pending_http.status_code = code;
-                }
-            }
-            let _ = self.send_pending_tcp_segment(
-                self.pending_http.seq_next,
-                self.pending_http.ack_next,
-                TCP_FLAG_ACK,
-                &[],
-            );
-        }
-
-        if (flags & TCP_FLAG_FIN) != 0 {
-            self.pending_http.ack_next = self.pending_http.ack_next.wrapping_add(1);
-            let _ = self.send_pending_tcp_segment(
-                self.pending_http.seq_next,
-                self.pending_http.ack_next,
-                TCP_FLAG_ACK,
-                &[],
-            );
-            self.pending_http.finished = true;
-        }
+        self.deliver_tcp(src_mac, src_ip, &segment);
         Ok(())
     }
 
@@ -1123,11 +1560,15 @@ impl NetState {
                 && self.pending_ping.seq == seq
                 && self.pending_ping.reply_tick >= self.pending_ping.start_tick
             {
-                return Ok(self.pending_ping.reply_tick - self.pending_ping.start_tick);
+                let rtt = self.pending_ping.reply_tick - self.pending_ping.start_tick;
+                self.paths
+                    .record_rtt(target, rtt, self.pending_ping.reply_tick);
+                return Ok(rtt);
             }
             spin_loop();
         }
         self.pending_ping.active = false;
+        self.paths.record_loss(target, time::ticks());
         Err(NetError::IoTimeout)
     }
 
@@ -1148,61 +1589,71 @@ impl NetState {
         src_port: u16,
         payload: &[u8],
     ) -> Result<usize, NetError> {
-        if payload.len() > MAX_TX_FRAME.saturating_sub(42) {
+        if payload.len() > self.mtu.saturating_sub(28) {
             return Err(NetError::UdpPayloadTooLarge);
         }
-        let dst_mac = if target_ip == IP_BROADCAST {
-            MAC_BROADCAST
-        } else {
-            let next_hop = self.select_next_hop(target_ip);
-            self.resolve_arp(next_hop)?
+        let dst_mac = match self.link_target(target_ip) {
+            LinkTarget::Mac(mac) => mac,
+            LinkTarget::Resolve(next_hop) => self.resolve_arp(next_hop)?,
         };
-        let src_port = if src_port == 0 {
-            UDP_ECHO_PORT
+        self.send_udp_to_mac(dst_mac, target_ip, target_port, src_port, payload)
+    }
+
+    fn link_target(&mut self, target_ip: [u8; 4]) -> LinkTarget {
+        if self.is_broadcast_ip(target_ip) {
+            LinkTarget::Mac(MAC_BROADCAST)
+        } else if is_multicast_ip(target_ip) {
+            LinkTarget::Mac(multicast_mac(target_ip))
         } else {
-            src_port
-        };
-        self.send_udp_packet(dst_mac, target_ip, target_port, src_port, payload)?;
-        Ok(payload.len())
+            LinkTarget::Resolve(self.select_next_hop(target_ip))
+        }
     }
 
-    fn curl_udp_roundtrip(
+    fn send_udp_to_mac(
         &mut self,
+        dst_mac: [u8; 6],
         target_ip: [u8; 4],
         target_port: u16,
+        src_port: u16,
         payload: &[u8],
-        out: &mut [u8],
-    ) -> Result<Option<UdpRxMeta>, NetError> {
-        self.udp_mailbox.valid = false;
-        self.send_udp(target_ip, target_port, UDP_ECHO_PORT, payload)?;
-        let start = time::ticks();
-        while time::ticks().saturating_sub(start) < CURL_WAIT_TICKS {
-            self.poll();
-            if let Some(meta) = self.pop_udp_mailbox(out) {
-                return Ok(Some(meta));
-            }
-            spin_loop();
+    ) -> Result<usize, NetError> {
+        if payload.len() > self.mtu.saturating_sub(28) {
+            return Err(NetError::UdpPayloadTooLarge);
         }
-        Ok(None)
+        if src_port != 0 {
+            self.send_udp_packet(dst_mac, target_ip, target_port, src_port, payload)?;
+            return Ok(payload.len());
+        }
+        // No source port named: one drawn for this datagram alone.
+        let src_port = self.allocate_port(ports::Transport::Udp)?;
+        let sent = self.send_udp_packet(dst_mac, target_ip, target_port, src_port, payload);
+        self.ports.release(ports::Transport::Udp, src_port);
+        sent.map(|()| payload.len())
     }
 
-    fn dns_resolve_ipv4(&mut self, host: &str) -> Result<[u8; 4], NetError> {
+    /// Where `curl` lookups go: the leased DNS server, else the gateway.
+    fn dns_server(&self) -> Result<[u8; 4], NetError> {
+        if self.dns != [0; 4] {
+            Ok(self.dns)
+        } else if self.gateway != [0; 4] {
+            Ok(self.gateway)
+        } else {
+            Err(NetError::NotFound)
+        }
+    }
+
+    /// Writes an A query for `host` into `query`; returns its id and length.
+    fn dns_query(
+        &mut self,
+        host: &str,
+        query: &mut [u8; UDP_MAILBOX_CAP],
+    ) -> Result<(u16, usize), NetError> {
         let host = host.trim_end_matches('.');
         if host.is_empty() || host.len() > 253 {
             return Err(NetError::NotFound);
         }
-        let dns_server = if self.dns != [0; 4] {
-            self.dns
-        } else if self.gateway != [0; 4] {
-            self.gateway
-        } else {
-            return Err(NetError::NotFound);
-        };
-
         let txid = (self.make_dhcp_xid() as u16).wrapping_add(time::ticks() as u16);
-        let src_port = 53000u16.wrapping_add((time::ticks() as u16) & 0x03ff);
 
-        let mut query = [0u8; UDP_MAILBOX_CAP];
         query[0..2].copy_from_slice(&txid.to_be_bytes());
         query[2..4].copy_from_slice(&0x0100u16.to_be_bytes());
         query[4..6].copy_from_slice(&1u16.to_be_bytes());
@@ -1211,150 +1662,65 @@ impl NetState {
         query[10..12].copy_from_slice(&0u16.to_be_bytes());
 
         let mut idx = 12usize;
-        if !encode_dns_name(host, &mut query, &mut idx) {
+        if !encode_dns_name(host, query, &mut idx) {
             return Err(NetError::NotFound);
         }
-        if !push_bytes(&mut query, &mut idx, &1u16.to_be_bytes())
-            || !push_bytes(&mut query, &mut idx, &1u16.to_be_bytes())
+        if !push_bytes(query, &mut idx, &1u16.to_be_bytes())
+            || !push_bytes(query, &mut idx, &1u16.to_be_bytes())
         {
             return Err(NetError::FrameTooLarge);
         }
-
-        self.udp_mailbox.valid = false;
-        self.send_udp(dns_server, UDP_DNS_PORT, src_port, &query[..idx])?;
-        self.stats.dns_query = self.stats.dns_query.saturating_add(1);
-
-        let start = time::ticks();
-        let mut response = [0u8; UDP_MAILBOX_CAP];
-        while time::ticks().saturating_sub(start) < DNS_WAIT_TICKS {
-            self.poll();
-            if let Some(meta) = self.pop_udp_mailbox(&mut response) {
-                if meta.src_port != UDP_DNS_PORT {
-                    continue;
-                }
-                if let Some(ip) = parse_dns_a_response(&response[..meta.len], txid) {
-                    self.stats.dns_answer = self.stats.dns_answer.saturating_add(1);
-                    return Ok(ip);
-                }
-            }
-            spin_loop();
-        }
-        Err(NetError::IoTimeout)
+        Ok((txid, idx))
     }
 
-    fn curl_http_roundtrip(
+    fn send_tcp_segment(
         &mut self,
-        target_ip: [u8; 4],
-        target_port: u16,
-        path: &str,
-    ) -> Result<(usize, u16), NetError> {
-        let mut request = [0u8; 512];
-        let mut req_len = 0usize;
-        if !push_bytes(&mut request, &mut req_len, b"GET ") {
-            return Err(NetError::FrameTooLarge);
-        }
-        if !push_bytes(&mut request, &mut req_len, path.as_bytes()) {
-            return Err(NetError::FrameTooLarge);
-        }
-        if !push_bytes(
-            &mut request,
-            &mut req_len,
-            b" HTTP/1.0\r\nUser-Agent: arr0st-curl/0.1\r\nAccept: */*\r\nConnection: close\r\n\r\n",
-        ) {
-            return Err(NetError::FrameTooLarge);
-        }
-
-        let next_hop = self.select_next_hop(target_ip);
-        let dst_mac = self.resolve_arp(next_hop)?;
-        let local_port = 49152u16.wrapping_add((time::ticks() as u16) & 0x0fff);
-        let initial_seq = self.make_dhcp_xid().wrapping_add(0x1234_0000);
-
-        self.pending_http.clear();
-        self.pending_http.active = true;
-        self.pending_http.dst_mac = dst_mac;
-        self.pending_http.remote_ip = target_ip;
-        self.pending_http.remote_port = target_port;
-        self.pending_http.local_port = local_port;
-        self.pending_http.seq_next = initial_seq;
-
-        self.send_pending_tcp_segment(self.pending_http.seq_next, 0, TCP_FLAG_SYN, &[])?;
-        self.pending_http.seq_next = self.pending_http.seq_next.wrapping_add(1);
-
-        let start = time::ticks();
-        while time::ticks().saturating_sub(start) < CURL_WAIT_TICKS {
-            self.poll();
-
-            if self.pending_http.established && !self.pending_http.sent_request {
-                self.send_pending_tcp_segment(
-                    self.pending_http.seq_next,
-                    self.pending_http.ack_next,
-                    TCP_FLAG_ACK | TCP_FLAG_PSH,
-                    &request[..req_len],
-                )?;
-                self.pending_http.seq_next =
-                    self.pending_http.seq_next.wrapping_add(req_len as u32);
-                self.pending_http.sent_request = true;
-            }
-
-            if self.pending_http.finished {
-                break;
-            }
-            spin_loop();
-        }
-
-        let got_any = self.pending_http.response_len > 0;
-        let status = self.pending_http.status_code;
-        let response_len = self.pending_http.response_len;
-        if !self.pending_http.finished {
-            let _ = self.send_pending_tcp_segment(
-                self.pending_http.seq_next,
-                self.pending_http.ack_next,
-                TCP_FLAG_RST | TCP_FLAG_ACK,
-                &[],
-            );
-        }
-        self.pending_http.clear();
-
-        if !got_any {
-            return Err(NetError::IoTimeout);
-        }
-        Ok((response_len, status))
+        link: TcpLink,
+        seq: u32,
+        ack: u32,
+        flags: u16,
+        window: u16,
+        payload: &[u8],
+    ) -> Result<(), NetError> {
+        let header = tcp_header(&link, seq, ack, flags, window, 20);
+        self.send_tcp_frame(link, header, &[], payload)
     }
 
-    fn send_pending_tcp_segment(
+    /// A SYN or SYN-ACK announcing `mss`, the only option this stack sends.
+    fn send_tcp_syn_segment(
         &mut self,
+        link: TcpLink,
         seq: u32,
         ack: u32,
         flags: u16,
+        window: u16,
+        mss: u16,
+    ) -> Result<(), NetError> {
+        let [hi, lo] = mss.to_be_bytes();
+        let header = tcp_header(&link, seq, ack, flags, window, 24);
+        self.send_tcp_frame(link, header, &[tcp::OPT_MSS, 4, hi, lo], &[])
+    }
+
+    fn send_tcp_frame(
+        &mut self,
+        link: TcpLink,
+        header: [u8; 20],
+        options: &[u8],
         payload: &[u8],
     ) -> Result<(), NetError> {
-        if payload.len() > MAX_TX_FRAME.saturating_sub(40) {
+        let header_len = 20 + options.len();
+        if header_len + payload.len() > self.mtu.saturating_sub(20) {
             return Err(NetError::FrameTooLarge);
         }
-        if !self.pending_http.active {
-            return Err(NetError::NotReady);
-        }
-        let mut segment = [0u8; MAX_TX_FRAME];
-        segment[0..2].copy_from_slice(&self.pending_http.local_port.to_be_bytes());
-        segment[2..4].copy_from_slice(&self.pending_http.remote_port.to_be_bytes());
-        segment[4..8].copy_from_slice(&seq.to_be_bytes());
-        segment[8..12].copy_from_slice(&ack.to_be_bytes());
-        segment[12] = 5u8 << 4;
-        segment[13] = (flags & 0x3f) as u8;
-        segment[14..16].copy_from_slice(&4096u16.to_be_bytes());
-        segment[16..18].copy_from_slice(&0u16.to_be_bytes());
-        segment[18..20].copy_from_slice(&0u16.to_be_bytes());
-        segment[20..20 + payload.len()].copy_from_slice(payload);
-        let tcp_len = 20 + payload.len();
-        let checksum = tcp_checksum(self.ipv4, self.pending_http.remote_ip, &segment[..tcp_len]);
-        segment[16..18].copy_from_slice(&checksum.to_be_bytes());
-        self.send_ipv4_packet_with_src(
-            self.pending_http.dst_mac,
-            self.pending_http.remote_ip,
-            self.ipv4,
-            IP_PROTO_TCP,
-            &segment[..tcp_len],
-        )
+        let tcp_len = header_len + payload.len();
+        let src_ip = self.ipv4;
+        self.send_ipv4_with(link.mac, link.ip, IP_PROTO_TCP, 64, tcp_len, |segment| {
+            segment[..20].copy_from_slice(&header);
+            segment[20..header_len].copy_from_slice(options);
+            segment[header_len..].copy_from_slice(payload);
+            let checksum = tcp_checksum(src_ip, link.ip, segment);
+            segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+        })
     }
 
     fn send_udp_packet(
@@ -1377,14 +1743,35 @@ impl NetState {
         src_ip: [u8; 4],
         payload: &[u8],
     ) -> Result<(), NetError> {
-        let mut udp = [0u8; MAX_TX_FRAME];
         let udp_len = 8 + payload.len();
+        if 20 + udp_len > self.mtu {
+            return Err(NetError::FrameTooLarge);
+        }
+        let slot = self.tx_slot()?;
+        // SAFETY: `tx_slot` returned a slot the device is done with; `NET_LOCK` is held.
+        let tx = unsafe { &mut *self.tx_slot_ptr(slot) };
+        let (eth, rest) = tx.headers.split_at_mut(ETH_HEADER_LEN);
+        let (ip, udp) = rest.split_at_mut(20);
+        self.write_eth_header(eth, dst_mac);
+        self.write_ipv4_header(ip, dst_ip, src_ip, IP_PROTO_UDP, 64, udp_len);
         udp[0..2].copy_from_slice(&src_port.to_be_bytes());
         udp[2..4].copy_from_slice(&dst_port.to_be_bytes());
         udp[4..6].copy_from_slice(&(udp_len as u16).to_be_bytes());
         udp[6..8].copy_from_slice(&0u16.to_be_bytes());
-        udp[8..8 + payload.len()].copy_from_slice(payload);
-        self.send_ipv4_packet_with_src(dst_mac, dst_ip, src_ip, IP_PROTO_UDP, &udp[..udp_len])
+        // The only copy of the payload: straight from the caller into the slot's DMA memory.
+        tx.frame[..payload.len()].copy_from_slice(payload);
+
+        let phys = self.tx_slot_phys(slot);
+        self.post_tx(
+            slot,
+            &[
+                (phys + offset_of!(TxBuffer, hdr) as u64, NET_HDR_SIZE),
+                (phys + offset_of!(TxBuffer, headers) as u64, UDP_HEADERS_LEN),
+                (phys + offset_of!(TxBuffer, frame) as u64, payload.len()),
+            ],
+        );
+        self.stats.tx_sg = self.stats.tx_sg.saturating_add(1);
+        Ok(())
     }
 
     fn send_ipv4_packet(
@@ -1394,121 +1781,218 @@ impl NetState {
         proto: u8,
         payload: &[u8],
     ) -> Result<(), NetError> {
-        self.send_ipv4_packet_with_src(dst_mac, dst_ip, self.ipv4, proto, payload)
+        self.send_ipv4_packet_ttl(dst_mac, dst_ip, proto, 64, payload)
     }
 
-    fn send_ipv4_packet_with_src(
+    fn send_ipv4_packet_ttl(
         &mut self,
         dst_mac: [u8; 6],
         dst_ip: [u8; 4],
-        src_ip: [u8; 4],
         proto: u8,
+        ttl: u8,
         payload: &[u8],
     ) -> Result<(), NetError> {
-        let total_len = 20 + payload.len();
-        if total_len > MAX_TX_FRAME.saturating_sub(14) {
+        self.send_ipv4_with(dst_mac, dst_ip, proto, ttl, payload.len(), |out| {
+            out.copy_from_slice(payload)
+        })
+    }
+
+    /// Builds an IPv4 frame straight into a TX slot's DMA memory: `fill` writes the
+    /// `payload_len` byte payload behind the headers, so no frame is staged on the stack.
+    fn send_ipv4_with(
+        &mut self,
+        dst_mac: [u8; 6],
+        dst_ip: [u8; 4],
+        proto: u8,
+        ttl: u8,
+        payload_len: usize,
+        fill: impl FnOnce(&mut [u8]),
+    ) -> Result<(), NetError> {
+        let total_len = 20 + payload_len;
+        if total_len > self.mtu {
             return Err(NetError::FrameTooLarge);
         }
+        let slot = self.tx_slot()?;
+        // SAFETY: `tx_slot` returned a slot the device is done with; `NET_LOCK` is held.
+        let tx = unsafe { &mut *self.tx_slot_ptr(slot) };
+        let (eth, rest) = tx.frame.split_at_mut(ETH_HEADER_LEN);
+        let (ip, payload) = rest.split_at_mut(20);
+        self.write_eth_header(eth, dst_mac);
+        self.write_ipv4_header(ip, dst_ip, self.ipv4, proto, ttl, payload_len);
+        fill(&mut payload[..payload_len]);
+
+        let phys = self.tx_slot_phys(slot);
+        self.post_tx(
+            slot,
+            &[
+                (phys + offset_of!(TxBuffer, hdr) as u64, NET_HDR_SIZE),
+                (
+                    phys + offset_of!(TxBuffer, frame) as u64,
+                    ETH_HEADER_LEN + total_len,
+                ),
+            ],
+        );
+        Ok(())
+    }
 
-        let mut frame = [0u8; MAX_TX_FRAME];
-        frame[0..6].copy_from_slice(&dst_mac);
-        frame[6..12].copy_from_slice(&self.mac);
-        frame[12..14].copy_from_slice(&ETH_TYPE_IPV4.to_be_bytes());
+    fn write_eth_header(&self, out: &mut [u8], dst_mac: [u8; 6]) {
+        out[0..6].copy_from_slice(&dst_mac);
+        out[6..12].copy_from_slice(&self.mac);
+        out[12..14].copy_from_slice(&ETH_TYPE_IPV4.to_be_bytes());
+    }
 
-        let ip = &mut frame[14..14 + 20];
+    /// Fills a 20-byte IPv4 header (with checksum) for a `payload_len` byte payload.
+    fn write_ipv4_header(
+        &mut self,
+        ip: &mut [u8],
+        dst_ip: [u8; 4],
+        src_ip: [u8; 4],
+        proto: u8,
+        ttl: u8,
+        payload_len: usize,
+    ) {
+        let total_len = 20 + payload_len;
         ip[0] = 0x45;
         ip[1] = 0;
         ip[2..4].copy_from_slice(&(total_len as u16).to_be_bytes());
         ip[4..6].copy_from_slice(&self.next_ip_id.to_be_bytes());
         self.next_ip_id = self.next_ip_id.wrapping_add(1);
         ip[6..8].copy_from_slice(&0x4000u16.to_be_bytes());
-        ip[8] = 64;
+        ip[8] = ttl;
         ip[9] = proto;
         ip[10..12].copy_from_slice(&0u16.to_be_bytes());
         ip[12..16].copy_from_slice(&src_ip);
         ip[16..20].copy_from_slice(&dst_ip);
         let ip_csum = checksum(ip);
         ip[10..12].copy_from_slice(&ip_csum.to_be_bytes());
-
-        frame[34..34 + payload.len()].copy_from_slice(payload);
-        self.transmit_frame(&frame[..14 + total_len])
     }
 
     fn transmit_frame(&mut self, frame: &[u8]) -> Result<(), NetError> {
+        if frame.len() > ETH_HEADER_LEN + self.mtu {
+            return Err(NetError::FrameTooLarge);
+        }
+        let slot = self.tx_slot()?;
+        // SAFETY: `tx_slot` returned a slot the device is done with; `NET_LOCK` is held.
+        let tx = unsafe { &mut *self.tx_slot_ptr(slot) };
+        tx.frame[..frame.len()].copy_from_slice(frame);
+
+        let phys = self.tx_slot_phys(slot);
+        self.post_tx(
+            slot,
+            &[
+                (phys + offset_of!(TxBuffer, hdr) as u64, NET_HDR_SIZE),
+                (phys + offset_of!(TxBuffer, frame) as u64, frame.len()),
+            ],
+        );
+        Ok(())
+    }
+
+    /// Takes a free TX slot off the free list, waiting for the device only when every slot is
+    /// still in flight. Slots come back in whatever order the device completes them.
+    fn tx_slot(&mut self) -> Result<usize, NetError> {
         if !self.ready {
             return Err(NetError::NotReady);
         }
-        if frame.len() > MAX_TX_FRAME {
-            return Err(NetError::FrameTooLarge);
+        self.reclaim_tx();
+        if let Some(slot) = self.tx_free.pop() {
+            return Ok(slot);
+        }
+        self.stats.tx_ring_waits = self.stats.tx_ring_waits.saturating_add(1);
+        let mut spins = 0usize;
+        loop {
+            if spins >= MAX_POLL_SPINS {
+                let _ = self.virtio_read_u8(VIRTIO_PCI_ISR);
+                return Err(NetError::IoTimeout);
+            }
+            spins = spins.saturating_add(1);
+            spin_loop();
+            self.reclaim_tx();
+            if let Some(slot) = self.tx_free.pop() {
+                return Ok(slot);
+            }
         }
+    }
 
-        // SAFETY: `NET_LOCK` serializes access to shared TX buffer.
-        unsafe {
-            let tx = &mut *TX_BUFFER.0.get();
-            tx.hdr.flags = 0;
-            tx.hdr.gso_type = 0;
-            tx.hdr.hdr_len = 0;
-            tx.hdr.gso_size = 0;
-            tx.hdr.csum_start = 0;
-            tx.hdr.csum_offset = 0;
-            tx.frame[..frame.len()].copy_from_slice(frame);
+    fn tx_slot_ptr(&self, slot: usize) -> *mut TxBuffer {
+        (self.tx_buffers as *mut TxBuffer).wrapping_add(slot)
+    }
+
+    fn tx_slot_phys(&self, slot: usize) -> u64 {
+        self.tx_buffers_phys + (slot * size_of::<TxBuffer>()) as u64
+    }
+
+    /// Puts the slots of frames the device has finished with since the last call back on the
+    /// free list and counts them.
+    fn reclaim_tx(&mut self) {
+        if !self.ready {
+            return;
         }
+        // SAFETY: queue1 used ring is accessed while `NET_LOCK` is held.
+        let used = unsafe { read_volatile(self.tx_ring.used_idx()) };
+        while self.tx_last_used != used {
+            let ring_slot = (self.tx_last_used % self.tx_queue_size) as usize;
+            // SAFETY: as above; the device wrote this element before publishing `used`.
+            let elem = unsafe { read_volatile(self.tx_ring.used_elem(ring_slot)) };
+            self.tx_last_used = self.tx_last_used.wrapping_add(1);
+            let slot = elem.id as usize / TX_DESCS_PER_SLOT;
+            if soft_assert!(
+                (elem.id as usize).is_multiple_of(TX_DESCS_PER_SLOT)
+                    && slot < self.tx_slots
+                    && self.tx_free.push(slot),
+                "net tx used id={} not an in-flight slot of {}",
+                elem.id,
+                self.tx_slots
+            ) {
+                self.stats.tx_frames = self.stats.tx_frames.saturating_add(1);
+            }
+        }
+    }
 
-        // SAFETY: queue1 memory belongs to TX queue and is serialized by `NET_LOCK`.
+    /// Chains `segments` (physical address, length) on `slot`'s descriptors and notifies the
+    /// device without waiting; `reclaim_tx` picks the completion up later.
+    fn post_tx(&mut self, slot: usize, segments: &[(u64, usize)]) {
+        let head = (slot * TX_DESCS_PER_SLOT) as u16;
+        let bytes: usize = segments.iter().map(|&(_, len)| len).sum();
+        evlog::record(Event::NetTx, bytes as u32, slot as u32);
+        // SAFETY: queue1 memory belongs to TX queue and is serialized by `NET_LOCK`; the slot's
+        // descriptors are free because `tx_slot` took it off the free list.
         unsafe {
-            let desc = queue_desc_ptr(TX_QUEUE_INDEX);
-            write_volatile(
-                desc.add(0),
-                VirtqDesc {
-                    addr: self.tx_hdr_phys,
-                    len: NET_HDR_SIZE as u32,
-                    flags: VIRTQ_DESC_F_NEXT,
-                    next: 1,
-                },
-            );
-            write_volatile(
-                desc.add(1),
-                VirtqDesc {
-                    addr: self.tx_frame_phys,
-                    len: frame.len() as u32,
-                    flags: 0,
-                    next: 0,
-                },
-            );
+            for (index, &(addr, len)) in segments.iter().enumerate() {
+                let desc = head + index as u16;
+                let last = index + 1 == segments.len();
+                write_volatile(
+                    self.tx_ring.desc(desc),
+                    VirtqDesc {
+                        addr,
+                        len: len as u32,
+                        flags: if last { 0 } else { VIRTQ_DESC_F_NEXT },
+                        next: if last { 0 } else { desc + 1 },
+                    },
+                );
+            }
 
-            let avail = queue_avail_ptr(TX_QUEUE_INDEX);
-            let slot = (self.tx_avail % self.tx_queue_size) as usize;
-            write_volatile(addr_of_mut!((*avail).ring[slot]), 0);
+            let ring_slot = (self.tx_avail % self.tx_queue_size) as usize;
+            write_volatile(self.tx_ring.avail_slot(ring_slot), head);
             fence(Ordering::SeqCst);
             self.tx_avail = self.tx_avail.wrapping_add(1);
-            write_volatile(addr_of_mut!((*avail).idx), self.tx_avail);
+            write_volatile(self.tx_ring.avail_idx(), self.tx_avail);
             fence(Ordering::SeqCst);
         }
 
         self.virtio_write_u16(VIRTIO_PCI_QUEUE_NOTIFY, TX_QUEUE_INDEX);
-
-        let expected = self.tx_last_used.wrapping_add(1);
-        let mut spins = 0usize;
-        loop {
-            // SAFETY: queue1 used ring is accessed while `NET_LOCK` is held.
-            let observed =
-                unsafe { read_volatile(addr_of!((*queue_used_ptr(TX_QUEUE_INDEX)).idx)) };
-            if observed == expected {
-                self.tx_last_used = expected;
-                self.stats.tx_frames = self.stats.tx_frames.saturating_add(1);
-                break;
-            }
-            if spins >= MAX_POLL_SPINS {
-                let _ = self.virtio_read_u8(VIRTIO_PCI_ISR);
-                return Err(NetError::IoTimeout);
-            }
-            spins = spins.saturating_add(1);
-            spin_loop();
-        }
-        Ok(())
     }
 
     fn send_arp_request(&mut self, target_ip: [u8; 4]) -> Result<(), NetError> {
+        self.send_arp_request_from(self.ipv4, target_ip)
+    }
+
+    /// ARP request with an explicit sender address: 0.0.0.0 for a probe, the target itself for
+    /// an announcement.
+    fn send_arp_request_from(
+        &mut self,
+        sender_ip: [u8; 4],
+        target_ip: [u8; 4],
+    ) -> Result<(), NetError> {
         let mut payload = [0u8; 28];
         payload[0..2].copy_from_slice(&1u16.to_be_bytes());
         payload[2..4].copy_from_slice(&ETH_TYPE_IPV4.to_be_bytes());
@@ -1516,7 +2000,7 @@ impl NetState {
         payload[5] = 4;
         payload[6..8].copy_from_slice(&1u16.to_be_bytes());
         payload[8..14].copy_from_slice(&self.mac);
-        payload[14..18].copy_from_slice(&self.ipv4);
+        payload[14..18].copy_from_slice(&sender_ip);
         payload[18..24].copy_from_slice(&[0; 6]);
         payload[24..28].copy_from_slice(&target_ip);
 
@@ -1548,44 +2032,80 @@ impl NetState {
         self.transmit_frame(&frame[..42])
     }
 
-    fn try_dhcp(&mut self) -> Result<bool, NetError> {
+    /// Sends a DISCOVER under a fresh xid; `dhcp_acquire` waits for the rest of the exchange.
+    fn begin_dhcp(&mut self) -> Result<u32, NetError> {
         self.config_source = IpConfigSource::Static;
         self.dhcp_bound = false;
+        self.dhcp_nak = false;
         self.dhcp_offer = DhcpOffer::empty();
         self.dhcp_xid = self.make_dhcp_xid();
 
         self.send_dhcp_discover(self.dhcp_xid)?;
         self.stats.dhcp_discover = self.stats.dhcp_discover.saturating_add(1);
+        Ok(self.dhcp_xid)
+    }
 
-        let discover_start = time::ticks();
-        while time::ticks().saturating_sub(discover_start) < DHCP_WAIT_TICKS {
-            self.poll();
-            if self.dhcp_offer.valid {
-                break;
-            }
-            spin_loop();
-        }
-
-        if !self.dhcp_offer.valid {
-            self.dhcp_xid = 0;
-            return Ok(false);
+    fn dhcp_progress(&self, xid: u32) -> DhcpProgress {
+        if self.dhcp_acked_xid == xid {
+            DhcpProgress::Acked
+        } else if self.dhcp_xid != xid {
+            DhcpProgress::Superseded
+        } else if self.dhcp_nak {
+            DhcpProgress::Refused
+        } else if self.dhcp_offer.valid {
+            DhcpProgress::Offered
+        } else {
+            DhcpProgress::Waiting
         }
+    }
 
-        let offer = self.dhcp_offer;
-        self.send_dhcp_request(self.dhcp_xid, offer)?;
-
-        let request_start = time::ticks();
-        while time::ticks().saturating_sub(request_start) < DHCP_WAIT_TICKS {
-            self.poll();
-            if self.dhcp_bound {
-                return Ok(true);
-            }
-            spin_loop();
+    /// Stops listening for `xid`; false when another exchange or a static address took over.
+    fn abandon_dhcp(&mut self, xid: u32) -> bool {
+        if self.dhcp_xid != xid {
+            return false;
         }
-
         self.dhcp_xid = 0;
         self.dhcp_offer = DhcpOffer::empty();
-        Ok(false)
+        true
+    }
+
+    /// Boot without a lease: the static address `new` set up stays.
+    fn dhcp_unavailable(&mut self) {
+        self.config_source = IpConfigSource::Static;
+        self.start_conflict_check();
+        log::write(
+            Subsystem::Net,
+            Level::Info,
+            format_args!("Net: DHCP unavailable, using static 10.0.2.15/24 gw 10.0.2.2\n"),
+        );
+    }
+
+    fn log_dhcp_lease(&self) {
+        log::write(
+            Subsystem::Net,
+            Level::Info,
+            format_args!(
+                "Net: DHCP lease ip={}.{}.{}.{} mask={}.{}.{}.{} gw={}.{}.{}.{} dns={}.{}.{}.{} lease={} hostname={}\n",
+                self.ipv4[0],
+                self.ipv4[1],
+                self.ipv4[2],
+                self.ipv4[3],
+                self.netmask[0],
+                self.netmask[1],
+                self.netmask[2],
+                self.netmask[3],
+                self.gateway[0],
+                self.gateway[1],
+                self.gateway[2],
+                self.gateway[3],
+                self.dns[0],
+                self.dns[1],
+                self.dns[2],
+                self.dns[3],
+                dhcp::Secs(self.dhcp_lease.lease_secs),
+                dhcp::hostname().as_str()
+            ),
+        );
     }
 
     fn make_dhcp_xid(&self) -> u32 {
@@ -1593,105 +2113,124 @@ impl NetState {
             | ((self.mac[3] as u32) << 16)
             | ((self.mac[4] as u32) << 8)
             | (self.mac[5] as u32);
-        let tick_part = time::ticks() as u32;
-        tick_part ^ mac_part ^ 0xA770_5D00
+        entropy::next_u64() as u32 ^ mac_part ^ 0xA770_5D00
     }
 
     fn send_dhcp_discover(&mut self, xid: u32) -> Result<(), NetError> {
-        let mut packet = [0u8; 320];
-        packet[0] = 1;
-        packet[1] = 1;
-        packet[2] = 6;
-        packet[3] = 0;
-        packet[4..8].copy_from_slice(&xid.to_be_bytes());
-        packet[10..12].copy_from_slice(&0x8000u16.to_be_bytes());
-        packet[28..34].copy_from_slice(&self.mac);
-        packet[236..240].copy_from_slice(&DHCP_MAGIC_COOKIE);
-        let mut idx = 240usize;
-        packet[idx] = DHCP_OPT_MSG_TYPE;
-        packet[idx + 1] = 1;
-        packet[idx + 2] = DHCP_MSG_DISCOVER;
-        idx += 3;
-        packet[idx] = DHCP_OPT_PARAM_REQ_LIST;
-        packet[idx + 1] = 3;
-        packet[idx + 2] = DHCP_OPT_SUBNET_MASK;
-        packet[idx + 3] = DHCP_OPT_ROUTER;
-        packet[idx + 4] = DHCP_OPT_DNS;
-        idx += 5;
-        packet[idx] = DHCP_OPT_END;
-        idx += 1;
+        let mut message = dhcp::Message::new(DHCP_MSG_DISCOVER, xid, self.mac, IP_ZERO);
+        message.identity(self.mac, dhcp::hostname().as_str());
+        message.option(DHCP_OPT_PARAM_REQ_LIST, &DHCP_PARAMS);
         self.send_udp_packet_with_src(
             MAC_BROADCAST,
             IP_BROADCAST,
             UDP_DHCP_SERVER_PORT,
             UDP_DHCP_CLIENT_PORT,
             IP_ZERO,
-            &packet[..idx],
+            message.finish(),
         )
     }
 
     fn send_dhcp_request(&mut self, xid: u32, offer: DhcpOffer) -> Result<(), NetError> {
-        let mut packet = [0u8; 320];
-        packet[0] = 1;
-        packet[1] = 1;
-        packet[2] = 6;
-        packet[3] = 0;
-        packet[4..8].copy_from_slice(&xid.to_be_bytes());
-        packet[10..12].copy_from_slice(&0x8000u16.to_be_bytes());
-        packet[28..34].copy_from_slice(&self.mac);
-        packet[236..240].copy_from_slice(&DHCP_MAGIC_COOKIE);
-        let mut idx = 240usize;
-        packet[idx] = DHCP_OPT_MSG_TYPE;
-        packet[idx + 1] = 1;
-        packet[idx + 2] = DHCP_MSG_REQUEST;
-        idx += 3;
-        packet[idx] = DHCP_OPT_REQ_IP;
-        packet[idx + 1] = 4;
-        packet[idx + 2..idx + 6].copy_from_slice(&offer.ip);
-        idx += 6;
-        packet[idx] = DHCP_OPT_SERVER_ID;
-        packet[idx + 1] = 4;
-        packet[idx + 2..idx + 6].copy_from_slice(&offer.server_id);
-        idx += 6;
-        packet[idx] = DHCP_OPT_PARAM_REQ_LIST;
-        packet[idx + 1] = 3;
-        packet[idx + 2] = DHCP_OPT_SUBNET_MASK;
-        packet[idx + 3] = DHCP_OPT_ROUTER;
-        packet[idx + 4] = DHCP_OPT_DNS;
-        idx += 5;
-        packet[idx] = DHCP_OPT_END;
-        idx += 1;
+        let mut message = dhcp::Message::new(DHCP_MSG_REQUEST, xid, self.mac, IP_ZERO);
+        message.option(DHCP_OPT_REQ_IP, &offer.ip);
+        message.option(DHCP_OPT_SERVER_ID, &offer.server_id);
+        message.identity(self.mac, dhcp::hostname().as_str());
+        message.option(DHCP_OPT_PARAM_REQ_LIST, &DHCP_PARAMS);
         self.send_udp_packet_with_src(
             MAC_BROADCAST,
             IP_BROADCAST,
             UDP_DHCP_SERVER_PORT,
             UDP_DHCP_CLIENT_PORT,
             IP_ZERO,
-            &packet[..idx],
+            message.finish(),
+        )
+    }
+
+    /// RENEWING (RFC 2131 4.3.2): a REQUEST from the leased address, unicast to the server that
+    /// granted it, without the requested-ip and server-id options.
+    fn send_dhcp_renew(&mut self, server_mac: [u8; 6], xid: u32) -> Result<(), NetError> {
+        let server = self.dhcp_lease.server_id;
+        let mut message = dhcp::Message::new(DHCP_MSG_REQUEST, xid, self.mac, self.ipv4);
+        message.identity(self.mac, dhcp::hostname().as_str());
+        message.option(DHCP_OPT_PARAM_REQ_LIST, &DHCP_PARAMS);
+        self.send_udp_packet(
+            server_mac,
+            server,
+            UDP_DHCP_SERVER_PORT,
+            UDP_DHCP_CLIENT_PORT,
+            message.finish(),
         )
     }
 
+    /// Sends DHCPRELEASE for the current lease and returns to the static configuration.
+    fn release_dhcp(&mut self) -> Result<[u8; 4], NetError> {
+        if !self.ready {
+            return Err(NetError::NotReady);
+        }
+        if !self.dhcp_bound {
+            return Err(NetError::NoLease);
+        }
+        let released = self.ipv4;
+        let server = self.dhcp_lease.server_id;
+        let next_hop = self.select_next_hop(server);
+        let server_mac = self.resolve_arp(next_hop)?;
+        let mut message = dhcp::Message::new(
+            dhcp::DHCP_MSG_RELEASE,
+            self.make_dhcp_xid(),
+            self.mac,
+            released,
+        );
+        message.option(DHCP_OPT_SERVER_ID, &server);
+        message.identity(self.mac, dhcp::hostname().as_str());
+        self.send_udp_packet(
+            server_mac,
+            server,
+            UDP_DHCP_SERVER_PORT,
+            UDP_DHCP_CLIENT_PORT,
+            message.finish(),
+        )?;
+        self.stats.dhcp_release = self.stats.dhcp_release.saturating_add(1);
+        self.use_static_config();
+        Ok(released)
+    }
+
+    /// The addressing used when DHCP is unavailable at boot.
+    fn use_static_config(&mut self) {
+        self.ipv4 = LOCAL_IP;
+        self.netmask = LOCAL_NETMASK;
+        self.gateway = LOCAL_GATEWAY;
+        self.dns = [0; 4];
+        self.config_source = IpConfigSource::Static;
+        self.dhcp_xid = 0;
+        self.dhcp_bound = false;
+        self.dhcp_lease = dhcp::Lease::empty();
+        self.start_conflict_check();
+    }
+
     fn handle_dhcp_message(&mut self, src_ip: [u8; 4], payload: &[u8]) {
-        if payload.len() < 240 {
+        let (Some(xid), Some(yiaddr), Some(cookie)) = (
+            wire::be_u32(payload, 4),
+            wire::array::<4>(payload, 16),
+            wire::array::<4>(payload, 236),
+        ) else {
             return;
-        }
+        };
         if payload[0] != 2 {
             return;
         }
-        let xid = u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]);
         if self.dhcp_xid == 0 || xid != self.dhcp_xid {
             return;
         }
-        if payload[236..240] != DHCP_MAGIC_COOKIE {
+        if cookie != DHCP_MAGIC_COOKIE {
             return;
         }
 
-        let yiaddr = [payload[16], payload[17], payload[18], payload[19]];
         let mut msg_type = 0u8;
         let mut netmask = [0u8; 4];
         let mut gateway = [0u8; 4];
         let mut dns = [0u8; 4];
         let mut server_id = [0u8; 4];
+        let mut lease = dhcp::Lease::empty();
         let mut idx = 240usize;
 
         while idx < payload.len() {
@@ -1712,6 +2251,7 @@ impl NetState {
                 break;
             }
             let value = &payload[idx..idx + opt_len];
+            lease.record_option(code);
             match code {
                 DHCP_OPT_MSG_TYPE if opt_len == 1 => {
                     msg_type = value[0];
@@ -1728,7 +2268,15 @@ impl NetState {
                 DHCP_OPT_SERVER_ID if opt_len >= 4 => {
                     server_id.copy_from_slice(&value[..4]);
                 }
-                DHCP_OPT_LEASE_TIME if opt_len == 4 => {}
+                DHCP_OPT_LEASE_TIME if opt_len == 4 => {
+                    lease.lease_secs = wire::be_u32(value, 0);
+                }
+                dhcp::DHCP_OPT_RENEWAL_TIME if opt_len == 4 => {
+                    lease.renewal_secs = wire::be_u32(value, 0);
+                }
+                dhcp::DHCP_OPT_REBINDING_TIME if opt_len == 4 => {
+                    lease.rebinding_secs = wire::be_u32(value, 0);
+                }
                 _ => {}
             }
             idx = idx.saturating_add(opt_len);
@@ -1758,6 +2306,11 @@ impl NetState {
             return;
         }
 
+        if msg_type == dhcp::DHCP_MSG_NAK {
+            self.stats.dhcp_nak = self.stats.dhcp_nak.saturating_add(1);
+            self.dhcp_nak = true;
+            return;
+        }
         if msg_type != DHCP_MSG_ACK {
             return;
         }
@@ -1791,15 +2344,28 @@ impl NetState {
         } else {
             [0; 4]
         };
+        // A renewal that keeps the address needs no new probe.
+        let new_address = !self.dhcp_bound || lease_ip != self.ipv4;
         self.ipv4 = lease_ip;
         self.netmask = lease_mask;
         self.gateway = lease_gateway;
         self.dns = lease_dns;
+        lease.ip = lease_ip;
+        lease.netmask = lease_mask;
+        lease.gateway = lease_gateway;
+        lease.dns = lease_dns;
+        lease.server_id = server_id;
+        lease.bound_tick = time::ticks();
+        self.dhcp_lease = lease;
         self.config_source = IpConfigSource::Dhcp;
         self.dhcp_bound = true;
+        self.dhcp_acked_xid = self.dhcp_xid;
         self.dhcp_xid = 0;
         self.dhcp_offer = DhcpOffer::empty();
         self.stats.dhcp_ack = self.stats.dhcp_ack.saturating_add(1);
+        if new_address {
+            self.start_conflict_check();
+        }
     }
 
     fn select_next_hop(&mut self, dst_ip: [u8; 4]) -> [u8; 4] {
@@ -1811,6 +2377,95 @@ impl NetState {
         self.gateway
     }
 
+    fn send_igmp(
+        &mut self,
+        igmp_type: u8,
+        group: [u8; 4],
+        dst_ip: [u8; 4],
+    ) -> Result<(), NetError> {
+        let mut message = [0u8; 8];
+        message[0] = igmp_type;
+        message[4..8].copy_from_slice(&group);
+        let csum = checksum(&message);
+        message[2..4].copy_from_slice(&csum.to_be_bytes());
+        // IGMP is link-local: TTL 1 keeps reports from being forwarded past the first router.
+        self.send_ipv4_packet_ttl(multicast_mac(dst_ip), dst_ip, IP_PROTO_IGMP, 1, &message)?;
+        self.stats.igmp_reports = self.stats.igmp_reports.saturating_add(1);
+        Ok(())
+    }
+
+    fn join_group(&mut self, group: [u8; 4]) -> Result<bool, NetError> {
+        if !self.ready {
+            return Err(NetError::NotReady);
+        }
+        if !is_multicast_ip(group) || group == IP_ALL_HOSTS {
+            return Err(NetError::InvalidGroup);
+        }
+        if self
+            .mcast_groups
+            .iter()
+            .any(|entry| entry.valid && entry.group == group)
+        {
+            return Ok(false);
+        }
+        let Some(slot) = self.mcast_groups.iter().position(|entry| !entry.valid) else {
+            return Err(NetError::GroupTableFull);
+        };
+        // Joined only once the report is out, so a failed join can simply be retried.
+        self.send_igmp(IGMP_V2_MEMBERSHIP_REPORT, group, group)?;
+        self.mcast_groups[slot] = McastMembership { valid: true, group };
+        Ok(true)
+    }
+
+    fn leave_group(&mut self, group: [u8; 4]) -> Result<(), NetError> {
+        if !self.ready {
+            return Err(NetError::NotReady);
+        }
+        if !is_multicast_ip(group) {
+            return Err(NetError::InvalidGroup);
+        }
+        let Some(slot) = self
+            .mcast_groups
+            .iter_mut()
+            .find(|entry| entry.valid && entry.group == group)
+        else {
+            return Err(NetError::NotFound);
+        };
+        *slot = McastMembership::empty();
+        self.send_igmp(IGMP_LEAVE_GROUP, group, IP_ALL_ROUTERS)
+    }
+
+    fn is_joined_group(&self, ip: [u8; 4]) -> bool {
+        ip == IP_ALL_HOSTS
+            || self
+                .mcast_groups
+                .iter()
+                .any(|entry| entry.valid && entry.group == ip)
+    }
+
+    fn accepts_multicast_mac(&self, mac: [u8; 6]) -> bool {
+        if mac == multicast_mac(IP_ALL_HOSTS) {
+            return true;
+        }
+        self.mcast_groups
+            .iter()
+            .any(|entry| entry.valid && multicast_mac(entry.group) == mac)
+    }
+
+    fn subnet_broadcast(&self) -> [u8; 4] {
+        [
+            self.ipv4[0] | !self.netmask[0],
+            self.ipv4[1] | !self.netmask[1],
+            self.ipv4[2] | !self.netmask[2],
+            self.ipv4[3] | !self.netmask[3],
+        ]
+    }
+
+    fn is_broadcast_ip(&self, ip: [u8; 4]) -> bool {
+        // A /32 mask has no directed-broadcast address; only the limited one applies.
+        ip == IP_BROADCAST || (self.netmask != IP_BROADCAST && ip == self.subnet_broadcast())
+    }
+
     fn in_same_subnet(&self, other: [u8; 4]) -> bool {
         (self.ipv4[0] & self.netmask[0]) == (other[0] & self.netmask[0])
             && (self.ipv4[1] & self.netmask[1]) == (other[1] & self.netmask[1])
@@ -1850,16 +2505,24 @@ impl NetState {
             .map(|entry| entry.mac)
     }
 
-    fn resolve_arp(&mut self, target_ip: [u8; 4]) -> Result<[u8; 6], NetError> {
+    /// Returns the cached MAC, or sends a request and returns `None` so the caller can wait.
+    fn begin_arp(&mut self, target_ip: [u8; 4]) -> Result<Option<[u8; 6]>, NetError> {
         if target_ip == self.ipv4 {
-            return Ok(self.mac);
+            return Ok(Some(self.mac));
         }
         if let Some(mac) = self.lookup_arp(target_ip) {
-            return Ok(mac);
+            return Ok(Some(mac));
         }
         self.send_arp_request(target_ip)?;
+        Ok(None)
+    }
+
+    fn resolve_arp(&mut self, target_ip: [u8; 4]) -> Result<[u8; 6], NetError> {
+        if let Some(mac) = self.begin_arp(target_ip)? {
+            return Ok(mac);
+        }
         let start = time::ticks();
-        while time::ticks().saturating_sub(start) < 200 {
+        while time::ticks().saturating_sub(start) < ARP_WAIT_TICKS {
             self.poll();
             if let Some(mac) = self.lookup_arp(target_ip) {
                 return Ok(mac);
@@ -1883,6 +2546,8 @@ impl NetState {
             src_ip: self.udp_mailbox.src_ip,
             src_port: self.udp_mailbox.src_port,
             dst_port: self.udp_mailbox.dst_port,
+            broadcast: self.udp_mailbox.broadcast,
+            rx_tick: self.udp_mailbox.rx_tick,
             len: copy_len,
         };
         self.udp_mailbox.valid = false;
@@ -1928,10 +2593,30 @@ pub fn init() -> NetInitReport {
     with_net_mut(|state| state.init())
 }
 
-pub fn poll() {
+pub fn restart() -> NetInitReport {
+    with_net_mut(|state| state.restart())
+}
+
+pub fn poll() {
     with_net_mut(|state| state.poll());
 }
 
+/// Runs `frame` through the RX path as if the NIC had delivered it (`fuzz` builds only).
+#[cfg(feature = "fuzz")]
+pub fn inject_frame(frame: &[u8]) -> Result<(), NetError> {
+    let frame = &frame[..frame.len().min(MAX_RX_FRAME)];
+    with_net_mut(|state| {
+        state.stats.rx_frames = state.stats.rx_frames.saturating_add(1);
+        state.process_frame(frame)
+    })
+}
+
+/// Per-reason RX drop counters, indexed like `RxDrop::ALL`.
+#[cfg(feature = "fuzz")]
+pub fn rx_drop_counts() -> [u64; RxDrop::ALL.len()] {
+    with_net(|state| state.stats.rx_drops)
+}
+
 pub fn log_info() {
     with_net(|state| {
         if !state.ready {
@@ -1939,7 +2624,7 @@ pub fn log_info() {
             return;
         }
         serial::write_fmt(format_args!(
-            "net: backend=virtio-net-legacy cfg={} io={:#06x} pci={:02x}:{:02x}.{} mac={:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} ip={}.{}.{}.{} gw={}.{}.{}.{} mask={}.{}.{}.{} dns={}.{}.{}.{} rx={} tx={} arp={} ipv4={} icmp={} udp={} tcp={} dhcp_discover={} dhcp_offer={} dhcp_ack={} dns_query={} dns_answer={} curl_udp={} curl_http={} route_direct={} route_gw={} drop={}\n",
+            "net: backend=virtio-net-legacy cfg={} io={:#06x} pci={:02x}:{:02x}.{} mac={:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} ip={}.{}.{}.{} gw={}.{}.{}.{} mask={}.{}.{}.{} dns={}.{}.{}.{} rx={} tx={} arp={} ipv4={} icmp={} udp={} tcp={} bcast={} mcast={} igmp={} igmp_tx={} groups={} dhcp_discover={} dhcp_offer={} dhcp_ack={} dns_query={} dns_answer={} curl_udp={} curl_http={} wol={} route_direct={} route_gw={} drop={} fw_denied={} ip_conflicts={} tx_sg={} tx_ring_waits={} rx_posted={}/{} tx_inflight={}/{}\n",
             state.config_source.as_str(),
             state.io_base,
             state.pci_bus,
@@ -1974,6 +2659,15 @@ pub fn log_info() {
             state.stats.rx_icmp,
             state.stats.rx_udp,
             state.stats.rx_tcp,
+            state.stats.rx_broadcast,
+            state.stats.rx_multicast,
+            state.stats.rx_igmp,
+            state.stats.igmp_reports,
+            state
+                .mcast_groups
+                .iter()
+                .filter(|entry| entry.valid)
+                .count(),
             state.stats.dhcp_discover,
             state.stats.dhcp_offer,
             state.stats.dhcp_ack,
@@ -1981,78 +2675,74 @@ pub fn log_info() {
             state.stats.dns_answer,
             state.stats.curl_udp,
             state.stats.curl_http,
+            state.stats.wol_sent,
             state.stats.route_direct,
             state.stats.route_gateway,
-            state.stats.dropped
+            state.stats.dropped,
+            state.firewall.denied(),
+            state.acd.conflicts(),
+            state.stats.tx_sg,
+            state.stats.tx_ring_waits,
+            state.rx_count - state.rx_free.len(),
+            state.rx_count,
+            state.tx_slots - state.tx_free.len(),
+            state.tx_slots
         ));
+        serial::write_str("net: rx_drops");
+        for reason in RxDrop::ALL {
+            serial::write_fmt(format_args!(
+                " {}={}",
+                reason.as_str(),
+                state.stats.rx_drops[reason.index()]
+            ));
+        }
+        serial::write_line("");
     });
 }
 
-pub fn ping_to_serial(ip_text: &str) {
+pub fn counters() -> NetCounters {
+    with_net(|state| NetCounters {
+        ready: state.ready,
+        rx_frames: state.stats.rx_frames,
+        tx_frames: state.stats.tx_frames,
+        dropped: state.stats.dropped,
+    })
+}
+
+pub fn ping_to_serial(ip_text: &str) -> Status {
     let Some(target) = parse_ipv4(ip_text) else {
-        serial::write_line("ping: invalid ip (usage: ping <a.b.c.d>)");
-        return;
+        return Status::usage("ping: invalid ip (usage: ping <a.b.c.d>)");
     };
     match with_net_mut(|state| state.send_ping(target)) {
-        Ok(rtt_ticks) => serial::write_fmt(format_args!(
-            "ping: reply from {}.{}.{}.{} time={} ticks ({} ms)\n",
-            target[0],
-            target[1],
-            target[2],
-            target[3],
-            rtt_ticks,
-            rtt_ticks.saturating_mul(10)
-        )),
-        Err(err) => serial::write_fmt(format_args!("ping: failed ({})\n", err.as_str())),
+        Ok(rtt_ticks) => {
+            serial::write_fmt(format_args!(
+                "ping: reply from {}.{}.{}.{} time={} ticks ({} ms)\n",
+                target[0],
+                target[1],
+                target[2],
+                target[3],
+                rtt_ticks,
+                rtt_ticks.saturating_mul(10)
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("ping: failed ({})\n", err.as_str())),
     }
 }
 
-pub fn curl_to_serial(spec: &str) {
+/// Background UDP requests report only whether they started; their result follows later.
+pub fn curl_to_serial(spec: &str) -> Status {
     if let Some((target, port, payload)) = parse_udp_url(spec) {
         with_net_mut(|state| {
             state.stats.curl_udp = state.stats.curl_udp.saturating_add(1);
         });
-        curl_udp_to_serial_ip(target, port, payload);
-        return;
+        return curl_udp_to_serial_ip(target, port, payload);
     }
     if let Some((host, port, path)) = parse_http_url(spec) {
         with_net_mut(|state| {
             state.stats.curl_http = state.stats.curl_http.saturating_add(1);
         });
-        let target = match parse_ipv4(host) {
-            Some(ip) => ip,
-            None => match with_net_mut(|state| state.dns_resolve_ipv4(host)) {
-                Ok(ip) => {
-                    serial::write_fmt(format_args!(
-                        "curl: dns {} -> {}.{}.{}.{}\n",
-                        host, ip[0], ip[1], ip[2], ip[3]
-                    ));
-                    ip
-                }
-                Err(err) => {
-                    serial::write_fmt(format_args!("curl: dns failed ({})\n", err.as_str()));
-                    return;
-                }
-            },
-        };
-        let path = if path.is_empty() { "/" } else { path };
-        match with_net_mut(|state| state.curl_http_roundtrip(target, port, path)) {
-            Ok((bytes, status)) => {
-                if status != 0 {
-                    serial::write_fmt(format_args!(
-                        "curl: http {}.{}.{}.{}:{}{} status={} bytes={}\n",
-                        target[0], target[1], target[2], target[3], port, path, status, bytes
-                    ));
-                } else {
-                    serial::write_fmt(format_args!(
-                        "curl: http {}.{}.{}.{}:{}{} bytes={}\n",
-                        target[0], target[1], target[2], target[3], port, path, bytes
-                    ));
-                }
-            }
-            Err(err) => serial::write_fmt(format_args!("curl: http failed ({})\n", err.as_str())),
-        }
-        return;
+        return curl_http_to_serial(host, port, path);
     }
 
     let mut parts = spec.trim().splitn(3, ' ');
@@ -2066,36 +2756,337 @@ pub fn curl_to_serial(spec: &str) {
             });
             curl_udp_to_serial_ip(target, port, payload)
         }
-        _ => serial::write_line(
+        _ => Status::usage(
             "usage: curl <ip> <port> <text> | curl udp://<ip>:<port>/<payload> | curl http://<ip>[:port]/<path>",
         ),
     }
 }
 
-pub fn udp_send_to_serial(ip_text: &str, port: u16, payload: &str) {
+pub fn udp_send_to_serial(ip_text: &str, port: u16, payload: &str) -> Status {
     let Some(target) = parse_ipv4(ip_text) else {
         serial::write_line("udp: invalid ip");
-        return;
+        return Status::Usage;
     };
     match with_net_mut(|state| state.send_udp_shell(target, port, payload.as_bytes())) {
-        Ok(()) => serial::write_fmt(format_args!(
-            "udp: sent {} bytes to {}.{}.{}.{}:{}\n",
-            payload.len(),
-            target[0],
-            target[1],
-            target[2],
-            target[3],
-            port
-        )),
-        Err(err) => serial::write_fmt(format_args!("udp: failed ({})\n", err.as_str())),
+        Ok(()) => {
+            serial::write_fmt(format_args!(
+                "udp: sent {} bytes to {}.{}.{}.{}:{}\n",
+                payload.len(),
+                target[0],
+                target[1],
+                target[2],
+                target[3],
+                port
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("udp: failed ({})\n", err.as_str())),
     }
 }
 
-fn curl_udp_to_serial_ip(target: [u8; 4], port: u16, payload: &str) {
+/// Sends MTU-sized UDP datagrams to `ip:port` back to back for `seconds` and reports the rate.
+/// Holds the net lock for the whole run, so RX is not serviced meanwhile.
+pub fn bench_udp_to_serial(args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    let target = parts.next().and_then(parse_ipv4);
+    let port = parts.next().and_then(|value| value.parse::<u16>().ok());
+    let seconds = parts.next().and_then(|value| value.parse::<u64>().ok());
+    let (Some(target), Some(port), Some(seconds @ 1..=BENCH_MAX_SECONDS), None) =
+        (target, port, seconds, parts.next())
+    else {
+        return Status::usage("usage: net bench udp <ip> <port> <1..60>");
+    };
+    let result = with_net_mut(|state| {
+        let dst_mac = match state.link_target(target) {
+            LinkTarget::Mac(mac) => mac,
+            LinkTarget::Resolve(next_hop) => state.resolve_arp(next_hop)?,
+        };
+        let payload = &BENCH_PAYLOAD[..state.mtu - 28];
+        let waits_before = state.stats.tx_ring_waits;
+        let start = time::ticks();
+        let deadline = start + seconds * u64::from(time::PIT_HZ);
+        let mut frames = 0u64;
+        let mut errors = 0u64;
+        while time::ticks() < deadline {
+            match state.send_udp_packet(dst_mac, target, port, UDP_ECHO_PORT, payload) {
+                Ok(()) => frames += 1,
+                Err(_) => errors += 1,
+            }
+        }
+        let ticks = (time::ticks() - start).max(1);
+        Ok::<_, NetError>(UdpBench {
+            frames,
+            errors,
+            payload_len: payload.len(),
+            ticks,
+            ring_waits: state.stats.tx_ring_waits - waits_before,
+        })
+    });
+    match result {
+        Ok(bench) => {
+            let bytes = bench.frames * bench.payload_len as u64;
+            let hz = u64::from(time::PIT_HZ);
+            serial::write_fmt(format_args!(
+                "net bench: udp {}.{}.{}.{}:{} ticks={} frames={} payload={} bytes={} pps={} kbit_s={} ring_waits={} errors={}\n",
+                target[0],
+                target[1],
+                target[2],
+                target[3],
+                port,
+                bench.ticks,
+                bench.frames,
+                bench.payload_len,
+                bytes,
+                bench.frames * hz / bench.ticks,
+                bytes * 8 * hz / bench.ticks / 1000,
+                bench.ring_waits,
+                bench.errors
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("net bench: failed ({})\n", err.as_str())),
+    }
+}
+
+/// `udp rtt <ip> <port> [probes]`: ping-pong against a UDP echo peer. Each round trip runs from
+/// the send tick to the reply's RX timestamp, so executor wake-up latency is reported apart
+/// as `wake_max=`.
+pub fn udp_rtt_to_serial(args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    let target = parts.next().and_then(parse_ipv4);
+    let port = parts.next().and_then(|value| value.parse::<u16>().ok());
+    let probes = match parts.next() {
+        Some(text) => text.parse::<u32>().ok(),
+        None => Some(RTT_DEFAULT_PROBES),
+    };
+    let (Some(target), Some(port), Some(probes @ 1..=RTT_MAX_PROBES), None) =
+        (target, port, probes, parts.next())
+    else {
+        return Status::usage("usage: udp rtt <ip> <port> [1..32]");
+    };
+    let spawned = executor::spawn("udp-rtt", async move {
+        let result = udp_rtt(target, port, probes).await;
+        log_udp_rtt_result(target, port, probes, result);
+    });
+    if !spawned {
+        serial::write_severity_line(Severity::Error, "udp rtt: failed (no free service slot)");
+    }
+    Status::check(spawned)
+}
+
+#[derive(Clone, Copy)]
+struct UdpRtt {
+    replies: u32,
+    min_ticks: u64,
+    max_ticks: u64,
+    total_ticks: u64,
+    /// Longest gap between a reply's RX timestamp and the service reading it.
+    wake_max_ticks: u64,
+}
+
+async fn udp_rtt(target: [u8; 4], port: u16, probes: u32) -> Result<UdpRtt, NetError> {
+    // Resolve first so the first probe does not carry the ARP round trip.
+    let dst_mac = udp_link_mac(target).await?;
+    let mut rtt = UdpRtt {
+        replies: 0,
+        min_ticks: u64::MAX,
+        max_ticks: 0,
+        total_ticks: 0,
+        wake_max_ticks: 0,
+    };
     let mut response = [0u8; UDP_MAILBOX_CAP];
+    for seq in 0..probes {
+        let payload = alloc::format!("arrost rtt {seq}");
+        let sent_tick = time::ticks();
+        let Some(meta) = udp_exchange(
+            dst_mac,
+            target,
+            port,
+            UDP_RTT_PORT,
+            payload.as_bytes(),
+            &mut response,
+        )
+        .await?
+        else {
+            continue;
+        };
+        if meta.src_ip != target || meta.src_port != port || meta.dst_port != UDP_RTT_PORT {
+            continue;
+        }
+        let ticks = meta.rx_tick.saturating_sub(sent_tick);
+        rtt.replies += 1;
+        rtt.min_ticks = rtt.min_ticks.min(ticks);
+        rtt.max_ticks = rtt.max_ticks.max(ticks);
+        rtt.total_ticks = rtt.total_ticks.saturating_add(ticks);
+        rtt.wake_max_ticks = rtt
+            .wake_max_ticks
+            .max(time::ticks().saturating_sub(meta.rx_tick));
+    }
+    Ok(rtt)
+}
+
+fn log_udp_rtt_result(target: [u8; 4], port: u16, probes: u32, result: Result<UdpRtt, NetError>) {
+    let rtt = match result {
+        Ok(rtt) => rtt,
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Error,
+                format_args!("udp rtt: failed ({})\n", err.as_str()),
+            );
+            return;
+        }
+    };
+    let ms_per_tick = 1000 / u64::from(time::PIT_HZ);
+    let (min, avg_ms) = if rtt.replies == 0 {
+        (0, 0)
+    } else {
+        (
+            rtt.min_ticks,
+            rtt.total_ticks * ms_per_tick / u64::from(rtt.replies),
+        )
+    };
+    serial::write_fmt(format_args!(
+        "udp rtt: {}.{}.{}.{}:{} probes={} replies={} lost={} min={} max={} ticks avg_ms={} max_ms={} wake_max={}\n",
+        target[0],
+        target[1],
+        target[2],
+        target[3],
+        port,
+        probes,
+        rtt.replies,
+        probes - rtt.replies,
+        min,
+        rtt.max_ticks,
+        avg_ms,
+        rtt.max_ticks * ms_per_tick,
+        rtt.wake_max_ticks
+    ));
+}
+
+struct UdpBench {
+    frames: u64,
+    errors: u64,
+    payload_len: usize,
+    ticks: u64,
+    ring_waits: u64,
+}
+
+fn wol_magic_packet(mac: [u8; 6]) -> [u8; WOL_MAGIC_LEN] {
+    let mut packet = [0xFF; WOL_MAGIC_LEN];
+    for copy in packet[6..].as_chunks_mut::<6>().0 {
+        *copy = mac;
+    }
+    packet
+}
+
+/// Broadcasts a wake-on-LAN magic packet for `mac` to 255.255.255.255:`port`.
+pub fn wol_to_serial(args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    let mac = parts.next().and_then(parse_mac);
+    let port = match parts.next() {
+        Some(text) => text.parse::<u16>().ok().filter(|&port| port != 0),
+        None => Some(WOL_DEFAULT_PORT),
+    };
+    let (Some(mac), Some(port), None) = (mac, port, parts.next()) else {
+        return Status::usage("usage: wol <aa:bb:cc:dd:ee:ff> [port]");
+    };
+    let packet = wol_magic_packet(mac);
     match with_net_mut(|state| {
-        state.curl_udp_roundtrip(target, port, payload.as_bytes(), &mut response)
+        let sent = state.send_udp(IP_BROADCAST, port, 0, &packet);
+        if sent.is_ok() {
+            state.stats.wol_sent = state.stats.wol_sent.saturating_add(1);
+        }
+        sent
     }) {
+        Ok(bytes) => {
+            serial::write_fmt(format_args!(
+                "wol: magic packet for {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} sent to 255.255.255.255:{} ({} bytes)\n",
+                mac[0], mac[1], mac[2], mac[3], mac[4], mac[5], port, bytes
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("wol: failed ({})\n", err.as_str())),
+    }
+}
+
+/// Runs the round trip as an executor service so the shell and run loop keep going while
+/// ARP and the reply are outstanding; the result is printed when it arrives.
+fn curl_udp_to_serial_ip(target: [u8; 4], port: u16, payload: &str) -> Status {
+    let payload = String::from(payload);
+    let spawned = executor::spawn("curl-udp", async move {
+        let mut response = [0u8; UDP_MAILBOX_CAP];
+        let result = curl_udp_roundtrip(target, port, payload.as_bytes(), &mut response).await;
+        log_curl_udp_result(target, port, result, &response);
+    });
+    if !spawned {
+        serial::write_severity_line(Severity::Error, "curl: failed (no free service slot)");
+    }
+    Status::check(spawned)
+}
+
+/// The lookup, the connection and the transfer run as a service like the UDP round trip;
+/// the result is printed when it is in.
+fn curl_http_to_serial(host: &str, port: u16, path: &str) -> Status {
+    let host = String::from(host);
+    let path = String::from(if path.is_empty() { "/" } else { path });
+    let spawned = executor::spawn("curl-http", async move {
+        let target = match parse_ipv4(&host) {
+            Some(ip) => ip,
+            None => match dns_resolve(&host).await {
+                Ok(ip) => {
+                    serial::write_fmt(format_args!(
+                        "curl: dns {} -> {}.{}.{}.{}\n",
+                        host, ip[0], ip[1], ip[2], ip[3]
+                    ));
+                    ip
+                }
+                Err(err) => {
+                    serial::write_severity_fmt(
+                        Severity::Error,
+                        format_args!("curl: dns failed ({})\n", err.as_str()),
+                    );
+                    return;
+                }
+            },
+        };
+        let result = curl_http_roundtrip(target, port, &path).await;
+        log_curl_http_result(target, port, &path, result);
+    });
+    if !spawned {
+        serial::write_severity_line(Severity::Error, "curl: failed (no free service slot)");
+    }
+    Status::check(spawned)
+}
+
+fn log_curl_http_result(
+    target: [u8; 4],
+    port: u16,
+    path: &str,
+    result: Result<(usize, u16), NetError>,
+) {
+    match result {
+        Ok((bytes, 0)) => serial::write_fmt(format_args!(
+            "curl: http {}.{}.{}.{}:{}{} bytes={}\n",
+            target[0], target[1], target[2], target[3], port, path, bytes
+        )),
+        Ok((bytes, status)) => serial::write_fmt(format_args!(
+            "curl: http {}.{}.{}.{}:{}{} status={} bytes={}\n",
+            target[0], target[1], target[2], target[3], port, path, status, bytes
+        )),
+        Err(err) => serial::write_severity_fmt(
+            Severity::Error,
+            format_args!("curl: http failed ({})\n", err.as_str()),
+        ),
+    }
+}
+
+fn log_curl_udp_result(
+    target: [u8; 4],
+    port: u16,
+    result: Result<Option<UdpRxMeta>, NetError>,
+    response: &[u8],
+) {
+    match result {
         Ok(Some(meta)) => {
             let body = core::str::from_utf8(&response[..meta.len]).unwrap_or("<binary>");
             serial::write_fmt(format_args!(
@@ -2114,8 +3105,215 @@ fn curl_udp_to_serial_ip(target: [u8; 4], port: u16, payload: &str) {
                 body
             ));
         }
-        Ok(None) => serial::write_line("curl: timeout waiting response"),
-        Err(err) => serial::write_fmt(format_args!("curl: failed ({})\n", err.as_str())),
+        Ok(None) => serial::write_severity_line(Severity::Error, "curl: timeout waiting response"),
+        Err(err) => serial::write_severity_fmt(
+            Severity::Error,
+            format_args!("curl: failed ({})\n", err.as_str()),
+        ),
+    }
+}
+
+enum LinkTarget {
+    Mac([u8; 6]),
+    /// Unicast: the next hop still has to go through ARP.
+    Resolve([u8; 4]),
+}
+
+/// ARP resolution for executor services: the request goes out immediately and the reply
+/// is picked up by `net::poll` in the run loop between polls.
+async fn resolve_arp_async(target_ip: [u8; 4]) -> Result<[u8; 6], NetError> {
+    if let Some(mac) = with_net_mut(|state| state.begin_arp(target_ip))? {
+        return Ok(mac);
+    }
+    let deadline = time::ticks().saturating_add(ARP_WAIT_TICKS);
+    while time::ticks() < deadline {
+        executor::sleep_ticks(1).await;
+        if let Some(mac) = with_net(|state| state.lookup_arp(target_ip)) {
+            return Ok(mac);
+        }
+    }
+    Err(NetError::ArpTimeout)
+}
+
+async fn udp_link_mac(target_ip: [u8; 4]) -> Result<[u8; 6], NetError> {
+    if !with_net(|state| state.ready) {
+        return Err(NetError::NotReady);
+    }
+    match with_net_mut(|state| state.link_target(target_ip)) {
+        LinkTarget::Mac(mac) => Ok(mac),
+        LinkTarget::Resolve(next_hop) => resolve_arp_async(next_hop).await,
+    }
+}
+
+async fn curl_udp_roundtrip(
+    target_ip: [u8; 4],
+    target_port: u16,
+    payload: &[u8],
+    out: &mut [u8],
+) -> Result<Option<UdpRxMeta>, NetError> {
+    let dst_mac = udp_link_mac(target_ip).await?;
+    udp_exchange(dst_mac, target_ip, target_port, UDP_ECHO_PORT, payload, out).await
+}
+
+/// Sends `payload` from `src_port` and waits for the next mailbox datagram.
+async fn udp_exchange(
+    dst_mac: [u8; 6],
+    target_ip: [u8; 4],
+    target_port: u16,
+    src_port: u16,
+    payload: &[u8],
+    out: &mut [u8],
+) -> Result<Option<UdpRxMeta>, NetError> {
+    with_net_mut(|state| {
+        state.udp_mailbox.valid = false;
+        state.send_udp_to_mac(dst_mac, target_ip, target_port, src_port, payload)
+    })?;
+    UdpReply {
+        out,
+        timeout: executor::sleep_ticks(CURL_WAIT_TICKS),
+    }
+    .await
+}
+
+/// A lookup from a UDP port of its own, held until the answer is in, so the answer is told
+/// apart from the reply to a query still in flight from another port.
+async fn dns_resolve(host: &str) -> Result<[u8; 4], NetError> {
+    let mut query = [0u8; UDP_MAILBOX_CAP];
+    let (dns_server, txid, len, src_port) = with_net_mut(|state| {
+        let dns_server = state.dns_server()?;
+        let (txid, len) = state.dns_query(host, &mut query)?;
+        let src_port = state.allocate_port(ports::Transport::Udp)?;
+        Ok::<_, NetError>((dns_server, txid, len, src_port))
+    })?;
+    let answer = dns_exchange(dns_server, src_port, txid, &query[..len]).await;
+    with_net_mut(|state| state.ports.release(ports::Transport::Udp, src_port));
+    answer
+}
+
+async fn dns_exchange(
+    dns_server: [u8; 4],
+    src_port: u16,
+    txid: u16,
+    query: &[u8],
+) -> Result<[u8; 4], NetError> {
+    let dst_mac = udp_link_mac(dns_server).await?;
+    with_net_mut(|state| {
+        state.udp_mailbox.valid = false;
+        state.send_udp_to_mac(dst_mac, dns_server, UDP_DNS_PORT, src_port, query)?;
+        state.stats.dns_query = state.stats.dns_query.saturating_add(1);
+        Ok::<_, NetError>(())
+    })?;
+    let deadline = time::ticks().saturating_add(DNS_WAIT_TICKS);
+    let mut response = [0u8; UDP_MAILBOX_CAP];
+    loop {
+        let reply = UdpReply {
+            out: &mut response,
+            timeout: executor::sleep_until(deadline),
+        }
+        .await?;
+        let Some(meta) = reply else {
+            return Err(NetError::IoTimeout);
+        };
+        if meta.src_port != UDP_DNS_PORT || meta.dst_port != src_port {
+            continue;
+        }
+        if let Some(ip) = parse_dns_a_response(&response[..meta.len], txid) {
+            with_net_mut(|state| state.stats.dns_answer = state.stats.dns_answer.saturating_add(1));
+            return Ok(ip);
+        }
+    }
+}
+
+/// HTTP/1.0 GET of `path`: returns the bytes received and the status code (0 when the reply
+/// has no status line). The net lock is only held for each read and write.
+async fn curl_http_roundtrip(
+    target_ip: [u8; 4],
+    target_port: u16,
+    path: &str,
+) -> Result<(usize, u16), NetError> {
+    let mut request = [0u8; 512];
+    let mut req_len = 0usize;
+    if !push_bytes(&mut request, &mut req_len, b"GET ") {
+        return Err(NetError::FrameTooLarge);
+    }
+    if !push_bytes(&mut request, &mut req_len, path.as_bytes()) {
+        return Err(NetError::FrameTooLarge);
+    }
+    if !push_bytes(
+        &mut request,
+        &mut req_len,
+        b" HTTP/1.0\r\nUser-Agent: arr0st-curl/0.1\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+    ) {
+        return Err(NetError::FrameTooLarge);
+    }
+
+    let index = with_net_mut(|state| state.tcp_open(target_ip, target_port))?;
+    let mut head = [0u8; CURL_HTTP_BUF];
+    let mut head_len = 0usize;
+    let mut response_len = 0usize;
+    let mut queued = false;
+    let deadline = time::ticks().saturating_add(CURL_WAIT_TICKS);
+    let result = loop {
+        if time::ticks() >= deadline {
+            break Err(NetError::IoTimeout);
+        }
+        let mut chunk = [0u8; 512];
+        let read = with_net_mut(|state| {
+            if !queued {
+                match state.tcp_write(index, &request[..req_len]) {
+                    Ok(_) => queued = true,
+                    Err(NetError::NotConnected) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            state.tcp_read(index, &mut chunk)
+        });
+        match read {
+            Ok(0) => break Ok(()),
+            Ok(len) => {
+                let copy_len = len.min(head.len() - head_len);
+                head[head_len..head_len + copy_len].copy_from_slice(&chunk[..copy_len]);
+                head_len += copy_len;
+                response_len += len;
+            }
+            Err(NetError::WouldBlock) => executor::sleep_ticks(1).await,
+            Err(err) => break Err(err),
+        }
+    };
+
+    with_net_mut(|state| match result {
+        Ok(()) => state.tcp_close_slot(index),
+        Err(_) => state.tcp_abort_slot(index),
+    });
+    if response_len == 0 {
+        return Err(result.err().unwrap_or(NetError::IoTimeout));
+    }
+    Ok((
+        response_len,
+        parse_http_status_code(&head[..head_len]).unwrap_or(0),
+    ))
+}
+
+/// Next mailbox datagram, or `None` once `timeout` fires.
+struct UdpReply<'a> {
+    out: &'a mut [u8],
+    timeout: executor::Sleep,
+}
+
+impl Future for UdpReply<'_> {
+    type Output = Result<Option<UdpRxMeta>, NetError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if let Some(meta) = with_net_mut(|state| state.pop_udp_mailbox(this.out)) {
+            return Poll::Ready(Ok(Some(meta)));
+        }
+        if Pin::new(&mut this.timeout).poll(cx).is_ready() {
+            with_net_mut(|state| state.udp_waiter = None);
+            return Poll::Ready(Ok(None));
+        }
+        with_net_mut(|state| state.udp_waiter = Some(cx.waker().clone()));
+        Poll::Pending
     }
 }
 
@@ -2128,6 +3326,479 @@ pub fn udp_send(
     with_net_mut(|state| state.send_udp(target_ip, target_port, src_port, payload))
 }
 
+pub fn set_mtu(mtu: usize) -> Result<(), NetError> {
+    with_net_mut(|state| state.set_mtu(mtu))
+}
+
+pub fn log_ifconfig() {
+    with_net(|state| {
+        if !state.ready {
+            serial::write_line("ifconfig: eth0 unavailable");
+            return;
+        }
+        serial::write_fmt(format_args!(
+            "ifconfig: eth0 mac={:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} inet={}.{}.{}.{} mask={}.{}.{}.{} mtu={} max_mtu={} device_mtu={} rx_buf={}\n",
+            state.mac[0],
+            state.mac[1],
+            state.mac[2],
+            state.mac[3],
+            state.mac[4],
+            state.mac[5],
+            state.ipv4[0],
+            state.ipv4[1],
+            state.ipv4[2],
+            state.ipv4[3],
+            state.netmask[0],
+            state.netmask[1],
+            state.netmask[2],
+            state.netmask[3],
+            state.mtu,
+            state.max_mtu,
+            state.device_mtu.map_or(0, usize::from),
+            state.rx_capacity
+        ));
+    });
+}
+
+/// `sysinfo` line: interface config plus frame totals.
+pub fn log_sysinfo() {
+    with_net(|state| {
+        serial::write_fmt(format_args!(
+            "sysinfo: net ready={} mac={:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} inet={}.{}.{}.{} mask={}.{}.{}.{} mtu={} rx_frames={} tx_frames={} dropped={}\n",
+            state.ready,
+            state.mac[0],
+            state.mac[1],
+            state.mac[2],
+            state.mac[3],
+            state.mac[4],
+            state.mac[5],
+            state.ipv4[0],
+            state.ipv4[1],
+            state.ipv4[2],
+            state.ipv4[3],
+            state.netmask[0],
+            state.netmask[1],
+            state.netmask[2],
+            state.netmask[3],
+            state.mtu,
+            state.stats.rx_frames,
+            state.stats.tx_frames,
+            state.stats.dropped
+        ));
+    });
+}
+
+pub fn set_mtu_to_serial(text: &str) -> Status {
+    let Ok(mtu) = text.trim().parse::<usize>() else {
+        return Status::usage("usage: ifconfig mtu <576..9000>");
+    };
+    match set_mtu(mtu) {
+        Ok(()) => {
+            serial::write_fmt(format_args!("ifconfig: eth0 mtu={}\n", mtu));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!(
+                "ifconfig: mtu {} rejected ({})\n",
+                mtu,
+                err.as_str()
+            ));
+            Status::Failed
+        }
+    }
+}
+
+pub fn set_ipv4_to_serial(text: &str) -> Status {
+    let Some(ip) = parse_ipv4(text.trim()) else {
+        return Status::usage("usage: ifconfig addr <a.b.c.d>");
+    };
+    match with_net_mut(|state| state.set_ipv4(ip)) {
+        Ok(()) => {
+            serial::write_fmt(format_args!(
+                "ifconfig: eth0 inet={}.{}.{}.{} cfg=static\n",
+                ip[0], ip[1], ip[2], ip[3]
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!(
+                "ifconfig: addr {}.{}.{}.{} rejected ({})\n",
+                ip[0],
+                ip[1],
+                ip[2],
+                ip[3],
+                err.as_str()
+            ));
+            Status::Failed
+        }
+    }
+}
+
+/// `dhcp info`: the lease as granted, its timers relative to now and the client identity sent.
+pub fn log_dhcp_info() {
+    let hostname = dhcp::hostname();
+    with_net(|state| {
+        let mac = state.mac;
+        serial::write_fmt(format_args!(
+            "dhcp: cfg={} bound={} hostname={} hostname_source={} client_id=01:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x} discover={} offer={} ack={} nak={} renew={} release={}\n",
+            state.config_source.as_str(),
+            state.dhcp_bound,
+            hostname.as_str(),
+            if hostname.from_kv {
+                dhcp::HOSTNAME_KEY
+            } else {
+                "default"
+            },
+            mac[0],
+            mac[1],
+            mac[2],
+            mac[3],
+            mac[4],
+            mac[5],
+            state.stats.dhcp_discover,
+            state.stats.dhcp_offer,
+            state.stats.dhcp_ack,
+            state.stats.dhcp_nak,
+            state.stats.dhcp_renew,
+            state.stats.dhcp_release
+        ));
+        if !state.dhcp_bound {
+            serial::write_line("dhcp: no lease");
+            return;
+        }
+        let lease = &state.dhcp_lease;
+        let held_secs = time::ticks().saturating_sub(lease.bound_tick) / u64::from(time::PIT_HZ);
+        serial::write_fmt(format_args!(
+            "dhcp: lease ip={}.{}.{}.{} mask={}.{}.{}.{} gw={}.{}.{}.{} dns={}.{}.{}.{} server={}.{}.{}.{} lease={} t1={} t2={} held={held_secs}s renew_in={} rebind_in={} expires_in={}\n",
+            lease.ip[0],
+            lease.ip[1],
+            lease.ip[2],
+            lease.ip[3],
+            lease.netmask[0],
+            lease.netmask[1],
+            lease.netmask[2],
+            lease.netmask[3],
+            lease.gateway[0],
+            lease.gateway[1],
+            lease.gateway[2],
+            lease.gateway[3],
+            lease.dns[0],
+            lease.dns[1],
+            lease.dns[2],
+            lease.dns[3],
+            lease.server_id[0],
+            lease.server_id[1],
+            lease.server_id[2],
+            lease.server_id[3],
+            dhcp::Secs(lease.lease_secs),
+            dhcp::Secs(lease.t1_secs()),
+            dhcp::Secs(lease.t2_secs()),
+            dhcp::Remaining(lease.t1_secs(), held_secs),
+            dhcp::Remaining(lease.t2_secs(), held_secs),
+            dhcp::Remaining(lease.lease_secs, held_secs)
+        ));
+        serial::write_str("dhcp: options");
+        for code in lease.options() {
+            serial::write_fmt(format_args!(" {code}"));
+        }
+        serial::write_line("");
+    });
+}
+
+/// Boot-time DHCP, spawned from `init` so boot does not wait on the server. Without a lease
+/// the static address stays in use.
+async fn dhcp_boot() {
+    match dhcp_acquire().await {
+        Ok(DhcpProgress::Acked) => with_net(NetState::log_dhcp_lease),
+        Ok(DhcpProgress::Superseded) => {}
+        Ok(_) => with_net_mut(NetState::dhcp_unavailable),
+        Err(err) => {
+            log::write(
+                Subsystem::Net,
+                Level::Error,
+                format_args!("Net: DHCP failed ({})\n", err.as_str()),
+            );
+            with_net_mut(NetState::dhcp_unavailable);
+        }
+    }
+}
+
+/// DISCOVER, OFFER, REQUEST, ACK. Ends at `Acked`, `Superseded`, or wherever the exchange
+/// was when the server stopped answering.
+async fn dhcp_acquire() -> Result<DhcpProgress, NetError> {
+    let xid = with_net_mut(NetState::begin_dhcp)?;
+    let mut progress = dhcp_wait(xid, DhcpProgress::Waiting).await;
+    if progress == DhcpProgress::Offered {
+        with_net_mut(|state| state.send_dhcp_request(xid, state.dhcp_offer))?;
+        progress = dhcp_wait(xid, DhcpProgress::Offered).await;
+    }
+    if !matches!(progress, DhcpProgress::Acked | DhcpProgress::Superseded)
+        && !with_net_mut(|state| state.abandon_dhcp(xid))
+    {
+        return Ok(DhcpProgress::Superseded);
+    }
+    Ok(progress)
+}
+
+/// Waits up to `DHCP_WAIT_TICKS` for exchange `xid` to get past `from`. The replies are taken
+/// in by `net::poll` in the run loop between polls.
+async fn dhcp_wait(xid: u32, from: DhcpProgress) -> DhcpProgress {
+    let deadline = time::ticks().saturating_add(DHCP_WAIT_TICKS);
+    loop {
+        let progress = with_net(|state| state.dhcp_progress(xid));
+        if progress > from || time::ticks() >= deadline {
+            return progress;
+        }
+        executor::sleep_ticks(1).await;
+    }
+}
+
+/// Extends the lease, or gets a new one when none is held or the server refuses it.
+async fn dhcp_renew() -> Result<dhcp::RenewOutcome, NetError> {
+    let (bound, server) = with_net_mut(|state| {
+        if !state.ready {
+            return Err(NetError::NotReady);
+        }
+        state.stats.dhcp_renew = state.stats.dhcp_renew.saturating_add(1);
+        Ok((state.dhcp_bound, state.dhcp_lease.server_id))
+    })?;
+    if !bound {
+        return Ok(match dhcp_acquire().await? {
+            DhcpProgress::Acked => dhcp::RenewOutcome::Bound,
+            DhcpProgress::Superseded => dhcp::RenewOutcome::Superseded,
+            _ => dhcp::RenewOutcome::Unavailable,
+        });
+    }
+
+    let server_mac = udp_link_mac(server).await?;
+    let xid = with_net_mut(|state| {
+        // `dhcp release` or `ifconfig addr` may have dropped the lease during the ARP wait.
+        if !state.dhcp_bound {
+            return Ok(None);
+        }
+        let xid = state.make_dhcp_xid();
+        state.dhcp_nak = false;
+        state.dhcp_xid = xid;
+        state.send_dhcp_renew(server_mac, xid).map(|()| Some(xid))
+    })?;
+    let Some(xid) = xid else {
+        return Ok(dhcp::RenewOutcome::Superseded);
+    };
+    match dhcp_wait(xid, DhcpProgress::Offered).await {
+        DhcpProgress::Acked => Ok(dhcp::RenewOutcome::Renewed),
+        DhcpProgress::Superseded => Ok(dhcp::RenewOutcome::Superseded),
+        // The address is no longer ours: start over, or fall back like a boot without DHCP.
+        DhcpProgress::Refused => Ok(match dhcp_acquire().await? {
+            DhcpProgress::Acked => dhcp::RenewOutcome::Bound,
+            DhcpProgress::Superseded => dhcp::RenewOutcome::Superseded,
+            _ => {
+                with_net_mut(NetState::use_static_config);
+                dhcp::RenewOutcome::Lost
+            }
+        }),
+        DhcpProgress::Waiting | DhcpProgress::Offered => {
+            Ok(if with_net_mut(|state| state.abandon_dhcp(xid)) {
+                dhcp::RenewOutcome::TimedOut
+            } else {
+                dhcp::RenewOutcome::Superseded
+            })
+        }
+    }
+}
+
+/// `dhcp renew` runs as the `dhcp` service, like the boot exchange; the outcome is printed
+/// when the exchange ends.
+pub fn dhcp_renew_to_serial() -> Status {
+    if executor::is_running("dhcp") {
+        return Status::failed(format_args!("dhcp: renew failed (exchange in progress)\n"));
+    }
+    let spawned = executor::spawn("dhcp", async {
+        match dhcp_renew().await {
+            Ok(outcome) => {
+                let ip = with_net(|state| state.ipv4);
+                serial::write_fmt(format_args!(
+                    "dhcp: renew {} inet={}.{}.{}.{}\n",
+                    outcome.as_str(),
+                    ip[0],
+                    ip[1],
+                    ip[2],
+                    ip[3]
+                ));
+            }
+            Err(err) => serial::write_severity_fmt(
+                Severity::Error,
+                format_args!("dhcp: renew failed ({})\n", err.as_str()),
+            ),
+        }
+    });
+    if !spawned {
+        serial::write_severity_line(Severity::Error, "dhcp: renew failed (no free service slot)");
+    }
+    Status::check(spawned)
+}
+
+pub fn dhcp_release_to_serial() -> Status {
+    match with_net_mut(|state| state.release_dhcp()) {
+        Ok(ip) => {
+            serial::write_fmt(format_args!(
+                "dhcp: released {}.{}.{}.{}, cfg=static inet={}.{}.{}.{}\n",
+                ip[0], ip[1], ip[2], ip[3], LOCAL_IP[0], LOCAL_IP[1], LOCAL_IP[2], LOCAL_IP[3]
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("dhcp: release failed ({})\n", err.as_str())),
+    }
+}
+
+pub fn join_group(group: [u8; 4]) -> Result<bool, NetError> {
+    with_net_mut(|state| state.join_group(group))
+}
+
+pub fn leave_group(group: [u8; 4]) -> Result<(), NetError> {
+    with_net_mut(|state| state.leave_group(group))
+}
+
+pub fn join_group_to_serial(group_text: &str) -> Status {
+    let Some(group) = parse_ipv4(group_text) else {
+        return Status::usage("usage: net join <224.x.y.z>");
+    };
+    match join_group(group) {
+        Ok(joined) => {
+            serial::write_fmt(format_args!(
+                "net: {} {}.{}.{}.{}\n",
+                if joined {
+                    "joined"
+                } else {
+                    "already member of"
+                },
+                group[0],
+                group[1],
+                group[2],
+                group[3]
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("net: join failed ({})\n", err.as_str())),
+    }
+}
+
+pub fn leave_group_to_serial(group_text: &str) -> Status {
+    let Some(group) = parse_ipv4(group_text) else {
+        return Status::usage("usage: net leave <224.x.y.z>");
+    };
+    match leave_group(group) {
+        Ok(()) => {
+            serial::write_fmt(format_args!(
+                "net: left {}.{}.{}.{}\n",
+                group[0], group[1], group[2], group[3]
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("net: leave failed ({})\n", err.as_str())),
+    }
+}
+
+pub fn log_groups() {
+    with_net(|state| {
+        serial::write_line("net: group 224.0.0.1 (all-hosts)");
+        for entry in state.mcast_groups.iter().filter(|entry| entry.valid) {
+            serial::write_fmt(format_args!(
+                "net: group {}.{}.{}.{}\n",
+                entry.group[0], entry.group[1], entry.group[2], entry.group[3]
+            ));
+        }
+    });
+}
+
+/// `arp`: the neighbor cache with the path metrics of each neighbor.
+pub fn log_arp() {
+    with_net(|state| {
+        let entries = state.arp.iter().filter(|entry| entry.valid);
+        serial::write_fmt(format_args!("arp: entries={}\n", entries.clone().count()));
+        for entry in entries {
+            let [a, b, c, d] = entry.ip;
+            let [m0, m1, m2, m3, m4, m5] = entry.mac;
+            serial::write_fmt(format_args!(
+                "arp: {a}.{b}.{c}.{d} mac={m0:02x}:{m1:02x}:{m2:02x}:{m3:02x}:{m4:02x}:{m5:02x}"
+            ));
+            log_path_metrics(state.paths.get(entry.ip));
+        }
+        acd::log_status(state);
+    });
+}
+
+/// `netstat`: path metrics per destination, open TCP connections with their idle time and
+/// keepalive probes, and the TCP totals.
+pub fn log_netstat() {
+    let now = time::ticks();
+    with_net(|state| {
+        serial::write_fmt(format_args!(
+            "netstat: paths={} tcp_conns={} tcp_sockets={}/{} tcp_retransmits={} tcp_keepalive_probes={} tcp_reaped={} initial_rto_ms={} keepalive_idle_ms={} keepalive_interval_ms={} keepalive_probes={}\n",
+            state.paths.entries().count(),
+            state.services.connections(now).count(),
+            state.tcp.open_count(),
+            tcp::MAX_SOCKETS,
+            state.stats.tcp_retransmits,
+            state.stats.tcp_keepalive_probes,
+            state.stats.tcp_reaped,
+            path::INITIAL_RTO_TICKS * 10,
+            service::KEEPALIVE_IDLE_TICKS * 10,
+            service::KEEPALIVE_INTERVAL_TICKS * 10,
+            service::KEEPALIVE_PROBES
+        ));
+        for conn in state.services.connections(now) {
+            let [a, b, c, d] = state.ipv4;
+            let [e, f, g, h] = conn.peer_ip;
+            serial::write_fmt(format_args!(
+                "netstat: tcp {a}.{b}.{c}.{d}:{} {e}.{f}.{g}.{h}:{} state={} idle_ms={} probes={}/{}\n",
+                conn.local_port,
+                conn.peer_port,
+                conn.state,
+                conn.idle_ticks * 10,
+                conn.probes,
+                service::KEEPALIVE_PROBES
+            ));
+        }
+        state.tcp.log_sockets("netstat: tcp", state.ipv4, now);
+        for entry in state.paths.entries() {
+            let [a, b, c, d] = entry.ip;
+            let via = if state.lookup_arp(entry.ip).is_some() || state.in_same_subnet(entry.ip) {
+                "direct"
+            } else {
+                "gateway"
+            };
+            serial::write_fmt(format_args!("netstat: {a}.{b}.{c}.{d} via={via}"));
+            log_path_metrics(Some(entry));
+        }
+    });
+}
+
+/// Finishes an `arp` / `netstat` line with ` rtt_ms=.. rttvar_ms=.. loss=..% ...`.
+fn log_path_metrics(entry: Option<&path::PathEntry>) {
+    match entry {
+        Some(entry) if entry.samples > 0 => serial::write_fmt(format_args!(
+            " rtt_ms={} rttvar_ms={} loss={}% samples={} losses={} rto_ms={}\n",
+            entry.srtt_ms(),
+            entry.rttvar_ms(),
+            entry.loss_percent(),
+            entry.samples,
+            entry.losses,
+            entry.rto_ticks() * 10
+        )),
+        Some(entry) => serial::write_fmt(format_args!(
+            " rtt_ms=- loss={}% samples=0 losses={} rto_ms={}\n",
+            entry.loss_percent(),
+            entry.losses,
+            entry.rto_ticks() * 10
+        )),
+        None => serial::write_line(" rtt_ms=- samples=0"),
+    }
+}
+
+pub fn is_broadcast(ip: [u8; 4]) -> bool {
+    with_net(|state| state.is_broadcast_ip(ip))
+}
+
 pub fn udp_recv(buffer: &mut [u8]) -> Result<Option<UdpRxMeta>, NetError> {
     with_net_mut(|state| {
         if !state.ready {
@@ -2137,6 +3808,86 @@ pub fn udp_recv(buffer: &mut [u8]) -> Result<Option<UdpRxMeta>, NetError> {
     })
 }
 
+/// SYS_POLL state of the user UDP socket.
+#[derive(Clone, Copy)]
+pub struct UdpReadiness {
+    /// A datagram waits in the mailbox, so `udp_recv` returns it without waiting.
+    pub readable: bool,
+    /// The interface has an address to send from.
+    pub writable: bool,
+}
+
+pub fn udp_readiness() -> Result<UdpReadiness, NetError> {
+    with_net(|state| {
+        if !state.ready {
+            return Err(NetError::NotReady);
+        }
+        Ok(UdpReadiness {
+            readable: state.udp_mailbox.valid,
+            writable: state.ipv4 != IP_ZERO,
+        })
+    })
+}
+
+/// Opens a connection to `ip:port` and returns its socket id at once; the handshake runs
+/// from `net::poll`. `tcp_info` shows when it is established, `tcp_send` may queue before.
+pub fn tcp_connect(ip: [u8; 4], port: u16) -> Result<u32, NetError> {
+    with_net_mut(|state| {
+        let index = state.tcp_open(ip, port)?;
+        Ok(state.tcp.id(index))
+    })
+}
+
+/// Listens on `port`; connections wait in the table for `tcp_accept`.
+pub fn tcp_listen(port: u16) -> Result<u32, NetError> {
+    with_net_mut(|state| state.tcp_listen(port))
+}
+
+/// Takes the next established connection of `listener`, if any.
+pub fn tcp_accept(listener: u32) -> Result<Option<u32>, NetError> {
+    with_net_mut(|state| state.tcp_accept(listener))
+}
+
+/// Queues up to the free send buffer of `data`; returns how much was taken.
+pub fn tcp_send(id: u32, data: &[u8]) -> Result<usize, NetError> {
+    with_net_mut(|state| {
+        let index = state.tcp.find(id)?;
+        state.tcp_write(index, data)
+    })
+}
+
+/// Reads received bytes; `Ok(0)` is end of stream, `WouldBlock` means nothing arrived yet.
+pub fn tcp_recv(id: u32, out: &mut [u8]) -> Result<usize, NetError> {
+    with_net_mut(|state| {
+        let index = state.tcp.find(id)?;
+        state.tcp_read(index, out)
+    })
+}
+
+/// Closes the socket: a FIN after the queued data, or the listener and its unaccepted
+/// connections. The id is invalid afterwards.
+pub fn tcp_close(id: u32) -> Result<(), NetError> {
+    with_net_mut(|state| {
+        let index = state.tcp.find(id)?;
+        state.tcp_close_slot(index);
+        Ok(())
+    })
+}
+
+pub fn tcp_info(id: u32) -> Result<TcpInfo, NetError> {
+    with_net(|state| {
+        let index = state.tcp.find(id)?;
+        Ok(state.tcp.info(index))
+    })
+}
+
+pub fn tcp_readiness(id: u32) -> Result<TcpReadiness, NetError> {
+    with_net(|state| {
+        let index = state.tcp.find(id)?;
+        Ok(state.tcp.readiness(index))
+    })
+}
+
 pub fn log_last_udp() {
     with_net(|state| {
         if !state.last_udp.valid {
@@ -2160,6 +3911,21 @@ pub fn log_last_udp() {
     });
 }
 
+/// Accepts `aa:bb:cc:dd:ee:ff` or `aa-bb-cc-dd-ee-ff`.
+pub fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    let separator = if text.contains('-') { '-' } else { ':' };
+    let mut out = [0u8; 6];
+    let mut idx = 0usize;
+    for part in text.split(separator) {
+        if idx >= 6 || part.len() != 2 {
+            return None;
+        }
+        out[idx] = u8::from_str_radix(part, 16).ok()?;
+        idx += 1;
+    }
+    (idx == 6).then_some(out)
+}
+
 pub fn parse_ipv4(text: &str) -> Option<[u8; 4]> {
     let mut out = [0u8; 4];
     let mut idx = 0usize;
@@ -2355,61 +4121,6 @@ fn with_net_mut<R>(f: impl FnOnce(&mut NetState) -> R) -> R {
     unsafe { f(&mut *NET_STATE.0.get()) }
 }
 
-fn queue_base_ptr(queue: u16) -> *mut u8 {
-    // SAFETY: called while holding `NET_LOCK`.
-    unsafe {
-        if queue == RX_QUEUE_INDEX {
-            (*RX_QUEUE_MEMORY.0.get()).bytes.as_mut_ptr()
-        } else {
-            (*TX_QUEUE_MEMORY.0.get()).bytes.as_mut_ptr()
-        }
-    }
-}
-
-unsafe fn queue_bytes_mut(queue: u16) -> &'static mut [u8; VRING_BYTES] {
-    if queue == RX_QUEUE_INDEX {
-        // SAFETY: caller guarantees synchronized access.
-        unsafe { &mut (*RX_QUEUE_MEMORY.0.get()).bytes }
-    } else {
-        // SAFETY: caller guarantees synchronized access.
-        unsafe { &mut (*TX_QUEUE_MEMORY.0.get()).bytes }
-    }
-}
-
-unsafe fn queue_desc_ptr(queue: u16) -> *mut VirtqDesc {
-    queue_base_ptr(queue) as *mut VirtqDesc
-}
-
-unsafe fn queue_avail_ptr(queue: u16) -> *mut VirtqAvail {
-    // SAFETY: pointer arithmetic stays within queue backing allocation.
-    unsafe { queue_base_ptr(queue).add(DESC_BYTES) as *mut VirtqAvail }
-}
-
-unsafe fn queue_used_ptr(queue: u16) -> *mut VirtqUsed {
-    // SAFETY: pointer arithmetic stays within queue backing allocation.
-    unsafe { queue_base_ptr(queue).add(USED_OFFSET) as *mut VirtqUsed }
-}
-
-fn rx_hdr_ptr() -> *mut VirtioNetHdr {
-    // SAFETY: caller ensures synchronized access to RX buffer.
-    unsafe { addr_of_mut!((*RX_BUFFER.0.get()).hdr) }
-}
-
-fn rx_frame_ptr() -> *mut u8 {
-    // SAFETY: caller ensures synchronized access to RX buffer.
-    unsafe { (*RX_BUFFER.0.get()).frame.as_mut_ptr() }
-}
-
-fn tx_hdr_ptr() -> *mut VirtioNetHdr {
-    // SAFETY: caller ensures synchronized access to TX buffer.
-    unsafe { addr_of_mut!((*TX_BUFFER.0.get()).hdr) }
-}
-
-fn tx_frame_ptr() -> *mut u8 {
-    // SAFETY: caller ensures synchronized access to TX buffer.
-    unsafe { (*TX_BUFFER.0.get()).frame.as_mut_ptr() }
-}
-
 fn find_virtio_net_pci() -> Option<PciLocation> {
     for bus in 0u16..=255u16 {
         for device in 0u16..32u16 {
@@ -2492,6 +4203,14 @@ fn pci_write_u16(bus: u8, device: u8, function: u8, offset: u8, value: u16) {
     pci_write_u32(bus, device, function, aligned, dword);
 }
 
+const fn is_multicast_ip(ip: [u8; 4]) -> bool {
+    (ip[0] & 0xf0) == 0xe0
+}
+
+const fn multicast_mac(ip: [u8; 4]) -> [u8; 6] {
+    [0x01, 0x00, 0x5e, ip[1] & 0x7f, ip[2], ip[3]]
+}
+
 fn checksum(data: &[u8]) -> u16 {
     let mut sum = 0u32;
     let mut chunks = data.chunks_exact(2);
@@ -2507,13 +4226,39 @@ fn checksum(data: &[u8]) -> u16 {
     !(sum as u16)
 }
 
+/// Fixed TCP header with the checksum zeroed; `header_len` counts the options after it.
+fn tcp_header(
+    link: &TcpLink,
+    seq: u32,
+    ack: u32,
+    flags: u16,
+    window: u16,
+    header_len: usize,
+) -> [u8; 20] {
+    let mut header = [0u8; 20];
+    header[0..2].copy_from_slice(&link.local_port.to_be_bytes());
+    header[2..4].copy_from_slice(&link.remote_port.to_be_bytes());
+    header[4..8].copy_from_slice(&seq.to_be_bytes());
+    header[8..12].copy_from_slice(&ack.to_be_bytes());
+    header[12] = ((header_len / 4) as u8) << 4;
+    header[13] = (flags & 0x3f) as u8;
+    header[14..16].copy_from_slice(&window.to_be_bytes());
+    header
+}
+
 fn tcp_checksum(src_ip: [u8; 4], dst_ip: [u8; 4], segment: &[u8]) -> u16 {
+    transport_checksum(src_ip, dst_ip, IP_PROTO_TCP, segment)
+}
+
+/// Internet checksum over the IPv4 pseudo-header and `segment`; a received segment whose
+/// checksum field is intact sums to zero.
+fn transport_checksum(src_ip: [u8; 4], dst_ip: [u8; 4], proto: u8, segment: &[u8]) -> u16 {
     let mut sum = 0u32;
     sum = sum.wrapping_add(u16::from_be_bytes([src_ip[0], src_ip[1]]) as u32);
     sum = sum.wrapping_add(u16::from_be_bytes([src_ip[2], src_ip[3]]) as u32);
     sum = sum.wrapping_add(u16::from_be_bytes([dst_ip[0], dst_ip[1]]) as u32);
     sum = sum.wrapping_add(u16::from_be_bytes([dst_ip[2], dst_ip[3]]) as u32);
-    sum = sum.wrapping_add(IP_PROTO_TCP as u32);
+    sum = sum.wrapping_add(proto as u32);
     sum = sum.wrapping_add(segment.len() as u32);
 
     let mut chunks = segment.chunks_exact(2);
@@ -0,0 +1,211 @@
+// kernel/src/net/netconsole.rs: UDP mailbox for the shell's `netconsole` service; one request
+// datagram is held at a time and replies go back to the peer that sent it.
+use super::{NetError, NetState, with_net, with_net_mut};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+/// Token, a space and one shell line (128 bytes) fit with room to spare.
+pub const REQUEST_CAP: usize = 256;
+/// Reply payload per datagram; well under the default MTU, so no reply depends on `ifconfig mtu`.
+const REPLY_CHUNK: usize = 512;
+
+pub(super) struct NetConsole {
+    port: u16,
+    open: bool,
+    pending: [u8; REQUEST_CAP],
+    pending_len: usize,
+    pending_valid: bool,
+    pending_mac: [u8; 6],
+    pending_ip: [u8; 4],
+    pending_port: u16,
+    /// Peer of the request being served; replies go here.
+    peer_mac: [u8; 6],
+    peer_ip: [u8; 4],
+    peer_port: u16,
+    requests: u64,
+    busy_drops: u64,
+    reply_datagrams: u64,
+    reply_errors: u64,
+    waker: Option<Waker>,
+}
+
+impl NetConsole {
+    pub(super) const fn new() -> Self {
+        Self {
+            port: 0,
+            open: false,
+            pending: [0; REQUEST_CAP],
+            pending_len: 0,
+            pending_valid: false,
+            pending_mac: [0; 6],
+            pending_ip: [0; 4],
+            pending_port: 0,
+            peer_mac: [0; 6],
+            peer_ip: [0; 4],
+            peer_port: 0,
+            requests: 0,
+            busy_drops: 0,
+            reply_datagrams: 0,
+            reply_errors: 0,
+            waker: None,
+        }
+    }
+
+    /// Port requests are taken on while the console is open.
+    pub(super) const fn bound_port(&self) -> Option<u16> {
+        if self.open { Some(self.port) } else { None }
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct NetConsoleStats {
+    pub open: bool,
+    pub port: u16,
+    pub requests: u64,
+    /// Requests that arrived while another one was still waiting and were dropped.
+    pub busy_drops: u64,
+    pub reply_datagrams: u64,
+    pub reply_errors: u64,
+}
+
+/// Sender of the request `netconsole_recv` returned.
+#[derive(Clone, Copy)]
+pub struct NetConsolePeer {
+    pub ip: [u8; 4],
+    pub port: u16,
+}
+
+impl NetState {
+    /// Holds a datagram for the netconsole on `dst_port`; false when it is closed or elsewhere.
+    pub(super) fn deliver_netconsole_udp(
+        &mut self,
+        src_mac: [u8; 6],
+        src_ip: [u8; 4],
+        src_port: u16,
+        dst_port: u16,
+        data: &[u8],
+    ) -> bool {
+        let console = &mut self.netconsole;
+        if !console.open || console.port != dst_port {
+            return false;
+        }
+        if console.pending_valid || data.len() > REQUEST_CAP {
+            console.busy_drops = console.busy_drops.saturating_add(1);
+            return true;
+        }
+        console.pending[..data.len()].copy_from_slice(data);
+        console.pending_len = data.len();
+        console.pending_valid = true;
+        console.pending_mac = src_mac;
+        console.pending_ip = src_ip;
+        console.pending_port = src_port;
+        console.requests = console.requests.saturating_add(1);
+        console.wake();
+        true
+    }
+}
+
+/// Starts accepting requests on `port`; a request already waiting is discarded.
+pub fn netconsole_open(port: u16) -> Result<(), NetError> {
+    with_net_mut(|state| {
+        if !state.ready {
+            return Err(NetError::NotReady);
+        }
+        let console = &mut state.netconsole;
+        console.port = port;
+        console.open = true;
+        console.pending_valid = false;
+        Ok(())
+    })
+}
+
+pub fn netconsole_close() {
+    with_net_mut(|state| {
+        let console = &mut state.netconsole;
+        console.open = false;
+        console.pending_valid = false;
+        console.wake();
+    });
+}
+
+pub fn netconsole_stats() -> NetConsoleStats {
+    with_net(|state| {
+        let console = &state.netconsole;
+        NetConsoleStats {
+            open: console.open,
+            port: console.port,
+            requests: console.requests,
+            busy_drops: console.busy_drops,
+            reply_datagrams: console.reply_datagrams,
+            reply_errors: console.reply_errors,
+        }
+    })
+}
+
+/// Waits for the next request and copies it into `out`; `None` once the console is closed.
+pub async fn netconsole_recv(out: &mut [u8]) -> Option<(NetConsolePeer, usize)> {
+    NetConsoleRequest { out }.await
+}
+
+struct NetConsoleRequest<'a> {
+    out: &'a mut [u8],
+}
+
+impl Future for NetConsoleRequest<'_> {
+    type Output = Option<(NetConsolePeer, usize)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        with_net_mut(|state| {
+            let console = &mut state.netconsole;
+            if !console.open {
+                return Poll::Ready(None);
+            }
+            if !console.pending_valid {
+                console.waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            console.pending_valid = false;
+            console.peer_mac = console.pending_mac;
+            console.peer_ip = console.pending_ip;
+            console.peer_port = console.pending_port;
+            let len = console.pending_len.min(this.out.len());
+            this.out[..len].copy_from_slice(&console.pending[..len]);
+            let peer = NetConsolePeer {
+                ip: console.peer_ip,
+                port: console.peer_port,
+            };
+            Poll::Ready(Some((peer, len)))
+        })
+    }
+}
+
+/// Sends `data` to the peer of the last request, split into datagrams of at most 512 bytes.
+pub fn netconsole_reply(data: &[u8]) -> Result<(), NetError> {
+    with_net_mut(|state| {
+        let console = &state.netconsole;
+        let (mac, ip, port, src_port) = (
+            console.peer_mac,
+            console.peer_ip,
+            console.peer_port,
+            console.port,
+        );
+        for chunk in data.chunks(REPLY_CHUNK) {
+            let sent = state.send_udp_packet(mac, ip, port, src_port, chunk);
+            let console = &mut state.netconsole;
+            if let Err(error) = sent {
+                console.reply_errors = console.reply_errors.saturating_add(1);
+                return Err(error);
+            }
+            console.reply_datagrams = console.reply_datagrams.saturating_add(1);
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,147 @@
+// kernel/src/net/path.rs: per-destination path metrics, RFC 6298 smoothed RTT and variance plus a
+// loss average, fed by ping replies and TCP acks and used for retransmit timeouts.
+
+const MAX_PATHS: usize = 16;
+/// RTO before the first sample (RFC 6298 2.1: one second).
+pub(super) const INITIAL_RTO_TICKS: u64 = 100;
+const MIN_RTO_TICKS: u64 = 20;
+pub(super) const MAX_RTO_TICKS: u64 = 600;
+/// Clock granularity `G` in the RTO formula: one PIT tick.
+const GRANULARITY_TICKS: u64 = 1;
+/// `loss_x1000` sample of a lost probe; like SRTT, the average moves 1/8 of the way per probe.
+const LOSS_SCALE: u64 = 1000;
+const MS_PER_TICK: u64 = 10;
+
+#[derive(Clone, Copy)]
+pub(super) struct PathEntry {
+    valid: bool,
+    pub ip: [u8; 4],
+    /// Smoothed RTT in ticks, scaled by 8 so sub-tick changes survive integer math.
+    srtt_x8: u64,
+    /// RTT variance in ticks, scaled by 4.
+    rttvar_x4: u64,
+    pub samples: u64,
+    pub losses: u64,
+    /// Running share of probes lost, in thousandths.
+    loss_x1000: u64,
+    last_tick: u64,
+}
+
+impl PathEntry {
+    const fn empty() -> Self {
+        Self {
+            valid: false,
+            ip: [0; 4],
+            srtt_x8: 0,
+            rttvar_x4: 0,
+            samples: 0,
+            losses: 0,
+            loss_x1000: 0,
+            last_tick: 0,
+        }
+    }
+
+    pub fn srtt_ms(&self) -> u64 {
+        self.srtt_x8 * MS_PER_TICK / 8
+    }
+
+    pub fn rttvar_ms(&self) -> u64 {
+        self.rttvar_x4 * MS_PER_TICK / 4
+    }
+
+    pub fn loss_percent(&self) -> u64 {
+        self.loss_x1000 / 10
+    }
+
+    /// RTO = SRTT + max(G, 4 * RTTVAR), clamped; the initial RTO until a sample arrives.
+    pub fn rto_ticks(&self) -> u64 {
+        if self.samples == 0 {
+            return INITIAL_RTO_TICKS;
+        }
+        let srtt = self.srtt_x8.div_ceil(8);
+        (srtt + GRANULARITY_TICKS.max(self.rttvar_x4)).clamp(MIN_RTO_TICKS, MAX_RTO_TICKS)
+    }
+
+    fn record_loss_sample(&mut self, lost: bool) {
+        let sample = if lost { LOSS_SCALE } else { 0 };
+        self.loss_x1000 = (self.loss_x1000 * 7 + sample) / 8;
+    }
+}
+
+pub(super) struct PathTable {
+    entries: [PathEntry; MAX_PATHS],
+}
+
+impl PathTable {
+    pub const fn new() -> Self {
+        Self {
+            entries: [PathEntry::empty(); MAX_PATHS],
+        }
+    }
+
+    pub fn get(&self, ip: [u8; 4]) -> Option<&PathEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.valid && entry.ip == ip)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &PathEntry> {
+        self.entries.iter().filter(|entry| entry.valid)
+    }
+
+    pub fn rto_ticks(&self, ip: [u8; 4]) -> u64 {
+        self.get(ip).map_or(INITIAL_RTO_TICKS, PathEntry::rto_ticks)
+    }
+
+    /// RFC 6298 2.2/2.3 update. Callers skip retransmitted segments (Karn's algorithm).
+    pub fn record_rtt(&mut self, ip: [u8; 4], rtt_ticks: u64, now: u64) {
+        let entry = self.entry_mut(ip, now);
+        let rtt_x8 = rtt_ticks.saturating_mul(8);
+        if entry.samples == 0 {
+            entry.srtt_x8 = rtt_x8;
+            entry.rttvar_x4 = rtt_ticks.saturating_mul(2);
+        } else {
+            // RTTVAR = 3/4 RTTVAR + 1/4 |SRTT - R|, then SRTT = 7/8 SRTT + 1/8 R.
+            let delta_x4 = entry.srtt_x8.abs_diff(rtt_x8) / 2;
+            entry.rttvar_x4 = (entry.rttvar_x4 * 3 + delta_x4) / 4;
+            entry.srtt_x8 = (entry.srtt_x8 * 7 + rtt_x8) / 8;
+        }
+        entry.samples = entry.samples.saturating_add(1);
+        entry.record_loss_sample(false);
+    }
+
+    /// A probe or segment that timed out.
+    pub fn record_loss(&mut self, ip: [u8; 4], now: u64) {
+        let entry = self.entry_mut(ip, now);
+        entry.losses = entry.losses.saturating_add(1);
+        entry.record_loss_sample(true);
+    }
+
+    /// The entry for `ip`, created in a free slot or over the least recently used one.
+    fn entry_mut(&mut self, ip: [u8; 4], now: u64) -> &mut PathEntry {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.valid && entry.ip == ip)
+            .unwrap_or_else(|| {
+                let index = self
+                    .entries
+                    .iter()
+                    .position(|entry| !entry.valid)
+                    .unwrap_or_else(|| {
+                        (0..MAX_PATHS)
+                            .min_by_key(|&index| self.entries[index].last_tick)
+                            .unwrap_or(0)
+                    });
+                self.entries[index] = PathEntry {
+                    valid: true,
+                    ip,
+                    ..PathEntry::empty()
+                };
+                index
+            });
+        let entry = &mut self.entries[index];
+        entry.last_tick = now;
+        entry
+    }
+}
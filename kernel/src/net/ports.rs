@@ -0,0 +1,199 @@
+// kernel/src/net/ports.rs: source ports for DNS queries, TCP active opens and task UDP sends
+// that name none. Ports are drawn from a configurable range (`net ports`, the IANA dynamic
+// range by default) and marked in one bitmap per protocol: the TCP table keeps its bits in step
+// with its sockets, a DNS query holds its port until it ends and a task keeps the UDP port of
+// its first unbound send until it exits. Ports the stack answers on itself are skipped, so a
+// reply never lands in a service instead of the mailbox.
+use super::{
+    NetError, NetState, UDP_DHCP_CLIENT_PORT, UDP_ECHO_PORT, UDP_RTT_PORT, with_net, with_net_mut,
+};
+use crate::arch::x86_64::entropy;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+
+const DEFAULT_FIRST: u16 = 49152;
+const DEFAULT_LAST: u16 = u16::MAX;
+/// The range stays clear of the well-known and system ports below this.
+const MIN_FIRST: u16 = 1024;
+const WORDS: usize = (u16::MAX as usize + 1) / 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Transport {
+    Udp,
+    Tcp,
+}
+
+pub(super) struct PortAllocator {
+    first: u16,
+    last: u16,
+    /// Where the next search starts; 0 until the first draw picks a random one, so a reboot
+    /// does not reuse the ports of connections a peer may still remember.
+    next: u16,
+    udp: [u64; WORDS],
+    tcp: [u64; WORDS],
+    allocated: u64,
+    /// Draws that found every port of the range held.
+    exhausted: u64,
+}
+
+impl PortAllocator {
+    pub(super) const fn new() -> Self {
+        Self {
+            first: DEFAULT_FIRST,
+            last: DEFAULT_LAST,
+            next: 0,
+            udp: [0; WORDS],
+            tcp: [0; WORDS],
+            allocated: 0,
+            exhausted: 0,
+        }
+    }
+
+    /// After `restart net`: the TCP table starts empty, while UDP ports stay with the tasks
+    /// holding them.
+    pub(super) const fn after_restart(previous: &Self) -> Self {
+        Self {
+            first: previous.first,
+            last: previous.last,
+            next: previous.next,
+            udp: previous.udp,
+            tcp: [0; WORDS],
+            allocated: previous.allocated,
+            exhausted: previous.exhausted,
+        }
+    }
+
+    const fn bits(&self, transport: Transport) -> &[u64; WORDS] {
+        match transport {
+            Transport::Udp => &self.udp,
+            Transport::Tcp => &self.tcp,
+        }
+    }
+
+    const fn bits_mut(&mut self, transport: Transport) -> &mut [u64; WORDS] {
+        match transport {
+            Transport::Udp => &mut self.udp,
+            Transport::Tcp => &mut self.tcp,
+        }
+    }
+
+    pub(super) const fn in_use(&self, transport: Transport, port: u16) -> bool {
+        self.bits(transport)[port as usize / 64] & (1 << (port % 64)) != 0
+    }
+
+    /// Marks `port` held, whether or not it lies in the range.
+    pub(super) const fn reserve(&mut self, transport: Transport, port: u16) {
+        self.bits_mut(transport)[port as usize / 64] |= 1 << (port % 64);
+    }
+
+    pub(super) const fn release(&mut self, transport: Transport, port: u16) {
+        self.bits_mut(transport)[port as usize / 64] &= !(1 << (port % 64));
+    }
+
+    fn held(&self, transport: Transport) -> u32 {
+        self.bits(transport)
+            .iter()
+            .map(|word| word.count_ones())
+            .sum()
+    }
+
+    /// Holds the next port of the range that is neither held nor `skip`ped, searching on from
+    /// the last one drawn so a port just given back is the last to come round again.
+    fn allocate(
+        &mut self,
+        transport: Transport,
+        skip: impl Fn(u16) -> bool,
+    ) -> Result<u16, NetError> {
+        let span = u32::from(self.last - self.first) + 1;
+        if self.next == 0 {
+            self.next = self.first + (entropy::next_u64() % u64::from(span)) as u16;
+        }
+        for _ in 0..span {
+            let port = self.next;
+            self.next = if port >= self.last {
+                self.first
+            } else {
+                port + 1
+            };
+            if !self.in_use(transport, port) && !skip(port) {
+                self.reserve(transport, port);
+                self.allocated = self.allocated.saturating_add(1);
+                return Ok(port);
+            }
+        }
+        self.exhausted = self.exhausted.saturating_add(1);
+        Err(NetError::PortInUse)
+    }
+
+    /// Ports already held keep their bits; only new draws follow the range.
+    fn set_range(&mut self, first: u16, last: u16) -> Result<(), NetError> {
+        if first < MIN_FIRST || first > last {
+            return Err(NetError::InvalidAddress);
+        }
+        self.first = first;
+        self.last = last;
+        self.next = 0;
+        Ok(())
+    }
+}
+
+impl NetState {
+    /// Holds a source port for `transport` until `ports.release` gives it back.
+    pub(super) fn allocate_port(&mut self, transport: Transport) -> Result<u16, NetError> {
+        let services = &self.services;
+        let console = self.netconsole.bound_port();
+        self.ports.allocate(transport, |port| {
+            let tcp = transport == Transport::Tcp;
+            services.find_port(tcp, port).is_some()
+                || (!tcp
+                    && (console == Some(port)
+                        || matches!(port, UDP_ECHO_PORT | UDP_RTT_PORT | UDP_DHCP_CLIENT_PORT)))
+        })
+    }
+}
+
+/// Holds an ephemeral UDP port until `udp_release_port`, for a task that sends without one.
+pub fn udp_allocate_port() -> Result<u16, NetError> {
+    with_net_mut(|state| state.allocate_port(Transport::Udp))
+}
+
+pub fn udp_release_port(port: u16) {
+    with_net_mut(|state| state.ports.release(Transport::Udp, port));
+}
+
+/// `net ports [<first> <last>]`: the range and what is held, or a new range.
+pub fn ports_to_serial(args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (None, _, _) => {}
+        (Some(first), Some(last), None) => {
+            let (Ok(first), Ok(last)) = (first.parse::<u16>(), last.parse::<u16>()) else {
+                return Status::usage("usage: net ports [<first> <last>]");
+            };
+            if let Err(err) = with_net_mut(|state| state.ports.set_range(first, last)) {
+                serial::write_severity_fmt(
+                    Severity::Warning,
+                    format_args!(
+                        "net: ports {first}..{last} rejected ({}; first >= {MIN_FIRST}, first <= last)\n",
+                        err.as_str()
+                    ),
+                );
+                return Status::Failed;
+            }
+        }
+        _ => return Status::usage("usage: net ports [<first> <last>]"),
+    }
+    with_net(|state| {
+        let ports = &state.ports;
+        serial::write_fmt(format_args!(
+            "net: ports range={}..{} udp_held={} tcp_held={} allocated={} exhausted={}\n",
+            ports.first,
+            ports.last,
+            ports.held(Transport::Udp),
+            ports.held(Transport::Tcp),
+            ports.allocated,
+            ports.exhausted
+        ));
+    });
+    Status::Ok
+}
@@ -0,0 +1,717 @@
+// kernel/src/net/service.rs: built-in UDP/TCP echo services run as executor tasks, so host-side
+// `nc` through QEMU hostfwd can exercise both directions of the stack. `midi-udp` (see `midi`)
+// shares the slots and plays what it receives instead of echoing it.
+use super::{
+    NetError, NetState, TCP_FLAG_ACK, TCP_FLAG_FIN, TCP_FLAG_PSH, TCP_FLAG_RST, TCP_FLAG_SYN,
+    TcpLink, with_net, with_net_mut,
+};
+use crate::features::Feature;
+use crate::log::{self, Level, Subsystem};
+use crate::proc::executor;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+use crate::time;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+mod midi;
+
+pub(super) const MAX_SERVICES: usize = 4;
+/// Bytes received but not echoed yet; also the TCP window advertised to the peer.
+const PENDING_CAP: usize = 1024;
+/// Silence on an established connection before the first keepalive probe (30 s).
+pub(super) const KEEPALIVE_IDLE_TICKS: u64 = 3_000;
+/// Gap between unanswered keepalive probes (5 s).
+pub(super) const KEEPALIVE_INTERVAL_TICKS: u64 = 500;
+/// Unanswered probes after which the peer is taken for gone and the connection reset.
+pub(super) const KEEPALIVE_PROBES: u32 = 4;
+/// Silence after which a connection stuck in the handshake or the close is reset (30 s).
+const HALF_OPEN_TIMEOUT_TICKS: u64 = 3_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ServiceKind {
+    EchoUdp,
+    EchoTcp,
+    MidiUdp,
+}
+
+impl ServiceKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::EchoUdp => "echo-udp",
+            Self::EchoTcp => "echo-tcp",
+            Self::MidiUdp => "midi-udp",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "echo-udp" => Some(Self::EchoUdp),
+            "echo-tcp" => Some(Self::EchoTcp),
+            "midi-udp" => Some(Self::MidiUdp),
+            _ => None,
+        }
+    }
+
+    const fn tcp(self) -> bool {
+        matches!(self, Self::EchoTcp)
+    }
+}
+
+/// Passive-open subset of the TCP state machine; one connection per service at a time.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TcpState {
+    Listen,
+    SynReceived,
+    Established,
+    /// Peer sent FIN; the task echoes what is left and then sends its own FIN.
+    CloseWait,
+    LastAck,
+}
+
+impl TcpState {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Listen => "listen",
+            Self::SynReceived => "syn-received",
+            Self::Established => "established",
+            Self::CloseWait => "close-wait",
+            Self::LastAck => "last-ack",
+        }
+    }
+}
+
+/// Parsed header fields of an inbound TCP segment.
+pub(super) struct TcpSegment<'a> {
+    pub(super) src_port: u16,
+    pub(super) dst_port: u16,
+    pub(super) seq: u32,
+    pub(super) ack: u32,
+    pub(super) flags: u16,
+    pub(super) window: u16,
+    /// MSS option of a SYN.
+    pub(super) mss: Option<u16>,
+    pub(super) data: &'a [u8],
+}
+
+struct EchoService {
+    kind: ServiceKind,
+    port: u16,
+    /// Accepting traffic; cleared by `service stop`, after which the task winds down.
+    active: bool,
+    /// The executor task still owns the slot.
+    running: bool,
+    started_tick: u64,
+    peer_mac: [u8; 6],
+    peer_ip: [u8; 4],
+    peer_port: u16,
+    tcp: TcpState,
+    snd_next: u32,
+    rcv_next: u32,
+    /// Tick of the last segment from the peer; idle time counts from here.
+    last_heard_tick: u64,
+    /// Keepalive probes sent since the peer was last heard.
+    probes: u32,
+    pending: [u8; PENDING_CAP],
+    pending_len: usize,
+    rx_packets: u64,
+    rx_bytes: u64,
+    tx_packets: u64,
+    tx_bytes: u64,
+    drops: u64,
+    connections: u64,
+    /// Connections reset because the peer went silent.
+    reaped: u64,
+    midi: midi::MidiCounters,
+    waker: Option<Waker>,
+}
+
+impl EchoService {
+    const fn empty() -> Self {
+        Self {
+            kind: ServiceKind::EchoUdp,
+            port: 0,
+            active: false,
+            running: false,
+            started_tick: 0,
+            peer_mac: [0; 6],
+            peer_ip: [0; 4],
+            peer_port: 0,
+            tcp: TcpState::Listen,
+            snd_next: 0,
+            rcv_next: 0,
+            last_heard_tick: 0,
+            probes: 0,
+            pending: [0; PENDING_CAP],
+            pending_len: 0,
+            rx_packets: 0,
+            rx_bytes: 0,
+            tx_packets: 0,
+            tx_bytes: 0,
+            drops: 0,
+            connections: 0,
+            reaped: 0,
+            midi: midi::MidiCounters::new(),
+            waker: None,
+        }
+    }
+
+    fn has_work(&self) -> bool {
+        !self.active || self.pending_len > 0 || self.tcp == TcpState::CloseWait
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn reset_connection(&mut self) {
+        self.tcp = TcpState::Listen;
+        self.pending_len = 0;
+        self.probes = 0;
+    }
+
+    fn heard(&mut self, now: u64) {
+        self.last_heard_tick = now;
+        self.probes = 0;
+    }
+
+    fn has_connection(&self) -> bool {
+        self.active && self.kind == ServiceKind::EchoTcp && self.tcp != TcpState::Listen
+    }
+}
+
+/// One open TCP connection, as `netstat` lists it.
+pub(super) struct TcpConnection {
+    pub(super) local_port: u16,
+    pub(super) peer_ip: [u8; 4],
+    pub(super) peer_port: u16,
+    pub(super) state: &'static str,
+    pub(super) idle_ticks: u64,
+    pub(super) probes: u32,
+}
+
+pub(super) struct Services {
+    slots: [EchoService; MAX_SERVICES],
+}
+
+impl Services {
+    pub(super) const fn new() -> Self {
+        Self {
+            slots: [const { EchoService::empty() }; MAX_SERVICES],
+        }
+    }
+
+    fn find(&self, kind: ServiceKind, port: u16) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|service| service.active && service.kind == kind && service.port == port)
+    }
+
+    /// The active service on `port` of the UDP or the TCP side, whatever its kind.
+    pub(super) fn find_port(&self, tcp: bool, port: u16) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|service| service.active && service.kind.tcp() == tcp && service.port == port)
+    }
+
+    pub(super) fn connections(&self, now: u64) -> impl Iterator<Item = TcpConnection> + '_ {
+        self.slots
+            .iter()
+            .filter(|service| service.has_connection())
+            .map(move |service| TcpConnection {
+                local_port: service.port,
+                peer_ip: service.peer_ip,
+                peer_port: service.peer_port,
+                state: service.tcp.as_str(),
+                idle_ticks: now.saturating_sub(service.last_heard_tick),
+                probes: service.probes,
+            })
+    }
+}
+
+impl NetState {
+    /// Queues a datagram for the echo-udp or midi-udp service on `dst_port`; false when none
+    /// listens there. One datagram is held at a time so boundaries survive the echo; extras
+    /// are dropped.
+    pub(super) fn deliver_service_udp(
+        &mut self,
+        src_mac: [u8; 6],
+        src_ip: [u8; 4],
+        src_port: u16,
+        dst_port: u16,
+        data: &[u8],
+    ) -> bool {
+        let Some(index) = self.services.find_port(false, dst_port) else {
+            return false;
+        };
+        let service = &mut self.services.slots[index];
+        service.rx_packets = service.rx_packets.saturating_add(1);
+        service.rx_bytes = service.rx_bytes.saturating_add(data.len() as u64);
+        if service.pending_len != 0 || data.len() > PENDING_CAP {
+            service.drops = service.drops.saturating_add(1);
+            return true;
+        }
+        service.peer_mac = src_mac;
+        service.peer_ip = src_ip;
+        service.peer_port = src_port;
+        service.pending[..data.len()].copy_from_slice(data);
+        service.pending_len = data.len();
+        service.wake();
+        true
+    }
+
+    /// Runs an inbound segment through the echo-tcp service on its port; false when none
+    /// listens there.
+    pub(super) fn deliver_service_tcp(
+        &mut self,
+        src_mac: [u8; 6],
+        src_ip: [u8; 4],
+        segment: &TcpSegment<'_>,
+    ) -> bool {
+        let Some(index) = self.services.find(ServiceKind::EchoTcp, segment.dst_port) else {
+            return false;
+        };
+        let syn = segment.flags & TCP_FLAG_SYN != 0;
+        let ack = segment.flags & TCP_FLAG_ACK != 0;
+        let now = time::ticks();
+        let iss = self.make_dhcp_xid() ^ now as u32;
+        let service = &mut self.services.slots[index];
+        let from_peer = service.tcp != TcpState::Listen
+            && service.peer_ip == src_ip
+            && service.peer_port == segment.src_port;
+        if from_peer {
+            service.heard(now);
+        }
+
+        if segment.flags & TCP_FLAG_RST != 0 {
+            if from_peer {
+                service.reset_connection();
+            }
+            return true;
+        }
+        // A peer that never ACKed our FIN must not wedge the service.
+        if service.tcp == TcpState::LastAck && syn && !ack {
+            service.reset_connection();
+        }
+        if service.tcp == TcpState::Listen {
+            if !syn || ack {
+                self.send_tcp_reset(src_mac, src_ip, segment);
+                return true;
+            }
+            service.peer_mac = src_mac;
+            service.peer_ip = src_ip;
+            service.peer_port = segment.src_port;
+            service.rcv_next = segment.seq.wrapping_add(1);
+            service.snd_next = iss;
+            service.tcp = TcpState::SynReceived;
+            service.heard(now);
+            service.connections = service.connections.saturating_add(1);
+            let _ = self.send_service_segment(index, TCP_FLAG_SYN | TCP_FLAG_ACK, &[]);
+            return true;
+        }
+        if !from_peer {
+            self.send_tcp_reset(src_mac, src_ip, segment);
+            return true;
+        }
+        if syn {
+            // Retransmitted SYN: our SYN-ACK got lost, so send it again.
+            if service.tcp == TcpState::SynReceived {
+                service.snd_next = service.snd_next.wrapping_sub(1);
+                let _ = self.send_service_segment(index, TCP_FLAG_SYN | TCP_FLAG_ACK, &[]);
+            }
+            return true;
+        }
+        if !ack {
+            return true;
+        }
+        match service.tcp {
+            TcpState::SynReceived if segment.ack == service.snd_next => {
+                service.tcp = TcpState::Established;
+            }
+            TcpState::SynReceived => return true,
+            TcpState::LastAck => {
+                if segment.ack == service.snd_next {
+                    service.reset_connection();
+                }
+                return true;
+            }
+            _ => {}
+        }
+
+        let mut reply = false;
+        if !segment.data.is_empty() {
+            reply = true;
+            if service.tcp == TcpState::Established && segment.seq == service.rcv_next {
+                // Whatever does not fit is retransmitted by the peer once the window reopens.
+                let take = segment.data.len().min(PENDING_CAP - service.pending_len);
+                let start = service.pending_len;
+                service.pending[start..start + take].copy_from_slice(&segment.data[..take]);
+                service.pending_len += take;
+                service.rcv_next = service.rcv_next.wrapping_add(take as u32);
+                service.rx_packets = service.rx_packets.saturating_add(1);
+                service.rx_bytes = service.rx_bytes.saturating_add(take as u64);
+                if take < segment.data.len() {
+                    service.drops = service.drops.saturating_add(1);
+                }
+                service.wake();
+            }
+        }
+        let fin_seq = segment.seq.wrapping_add(segment.data.len() as u32);
+        if segment.flags & TCP_FLAG_FIN != 0
+            && service.tcp == TcpState::Established
+            && fin_seq == service.rcv_next
+        {
+            service.rcv_next = service.rcv_next.wrapping_add(1);
+            service.tcp = TcpState::CloseWait;
+            service.wake();
+            reply = true;
+        }
+        if reply {
+            let _ = self.send_service_segment(index, TCP_FLAG_ACK, &[]);
+        }
+        true
+    }
+
+    /// Sends on the service's current connection and advances its send sequence.
+    fn send_service_segment(
+        &mut self,
+        index: usize,
+        flags: u16,
+        payload: &[u8],
+    ) -> Result<(), NetError> {
+        let service = &self.services.slots[index];
+        let link = TcpLink {
+            mac: service.peer_mac,
+            ip: service.peer_ip,
+            local_port: service.port,
+            remote_port: service.peer_port,
+        };
+        let (seq, ack) = (service.snd_next, service.rcv_next);
+        let window = (PENDING_CAP - service.pending_len) as u16;
+        self.send_tcp_segment(link, seq, ack, flags, window, payload)?;
+
+        let service = &mut self.services.slots[index];
+        let control = u32::from(flags & (TCP_FLAG_SYN | TCP_FLAG_FIN) != 0);
+        service.snd_next = service
+            .snd_next
+            .wrapping_add(payload.len() as u32)
+            .wrapping_add(control);
+        if !payload.is_empty() {
+            service.tx_packets = service.tx_packets.saturating_add(1);
+            service.tx_bytes = service.tx_bytes.saturating_add(payload.len() as u64);
+        }
+        Ok(())
+    }
+
+    /// Keepalive for the echo-tcp connections, run from every `poll`. An established peer
+    /// silent for `KEEPALIVE_IDLE_TICKS` gets up to `KEEPALIVE_PROBES` probes; one that
+    /// answers none of them, or one that stalls the handshake or close, is reset so it no
+    /// longer holds the service.
+    pub(super) fn poll_service_keepalive(&mut self) {
+        let now = time::ticks();
+        for index in 0..MAX_SERVICES {
+            let service = &self.services.slots[index];
+            if !service.has_connection() {
+                continue;
+            }
+            let idle = now.saturating_sub(service.last_heard_tick);
+            if service.tcp != TcpState::Established {
+                if idle >= HALF_OPEN_TIMEOUT_TICKS {
+                    self.reap_service_connection(index, idle);
+                }
+                continue;
+            }
+            let due = KEEPALIVE_IDLE_TICKS + u64::from(service.probes) * KEEPALIVE_INTERVAL_TICKS;
+            if idle < due {
+                continue;
+            }
+            if service.probes >= KEEPALIVE_PROBES {
+                self.reap_service_connection(index, idle);
+                continue;
+            }
+            // RFC 1122 probe: an empty ACK one below the send sequence, which any live peer
+            // answers with an ACK of its own.
+            let link = TcpLink {
+                mac: service.peer_mac,
+                ip: service.peer_ip,
+                local_port: service.port,
+                remote_port: service.peer_port,
+            };
+            let (seq, ack) = (service.snd_next.wrapping_sub(1), service.rcv_next);
+            let window = (PENDING_CAP - service.pending_len) as u16;
+            let _ = self.send_tcp_segment(link, seq, ack, TCP_FLAG_ACK, window, &[]);
+            self.services.slots[index].probes += 1;
+            self.stats.tcp_keepalive_probes = self.stats.tcp_keepalive_probes.saturating_add(1);
+        }
+    }
+
+    fn reap_service_connection(&mut self, index: usize, idle: u64) {
+        let _ = self.send_service_segment(index, TCP_FLAG_RST | TCP_FLAG_ACK, &[]);
+        let service = &mut self.services.slots[index];
+        let [a, b, c, d] = service.peer_ip;
+        log::write(
+            Subsystem::Net,
+            Level::Info,
+            format_args!(
+                "service: {} port={} reaped peer={a}.{b}.{c}.{d}:{} state={} idle_ms={} probes={}\n",
+                service.kind.as_str(),
+                service.port,
+                service.peer_port,
+                service.tcp.as_str(),
+                idle * 10,
+                service.probes
+            ),
+        );
+        service.reaped = service.reaped.saturating_add(1);
+        service.reset_connection();
+        self.stats.tcp_reaped = self.stats.tcp_reaped.saturating_add(1);
+    }
+
+    /// One round of echo work; false once the service was stopped and its slot released.
+    fn service_step(&mut self, index: usize, buffer: &mut [u8; PENDING_CAP]) -> bool {
+        let service = &mut self.services.slots[index];
+        if !service.active {
+            if service.kind == ServiceKind::EchoTcp && service.tcp != TcpState::Listen {
+                let _ = self.send_service_segment(index, TCP_FLAG_RST | TCP_FLAG_ACK, &[]);
+            }
+            let service = &mut self.services.slots[index];
+            service.reset_connection();
+            service.running = false;
+            return false;
+        }
+
+        let len = service.pending_len;
+        buffer[..len].copy_from_slice(&service.pending[..len]);
+        service.pending_len = 0;
+        match service.kind {
+            ServiceKind::EchoUdp => {
+                let (mac, ip, port, src_port) = (
+                    service.peer_mac,
+                    service.peer_ip,
+                    service.peer_port,
+                    service.port,
+                );
+                if len > 0 {
+                    let sent = self.send_udp_packet(mac, ip, port, src_port, &buffer[..len]);
+                    let service = &mut self.services.slots[index];
+                    if sent.is_ok() {
+                        service.tx_packets = service.tx_packets.saturating_add(1);
+                        service.tx_bytes = service.tx_bytes.saturating_add(len as u64);
+                    } else {
+                        service.drops = service.drops.saturating_add(1);
+                    }
+                }
+            }
+            // Played by `midi::run`, which never steps the slot through here.
+            ServiceKind::MidiUdp => {}
+            ServiceKind::EchoTcp => {
+                let chunk = self.mtu.saturating_sub(40).max(1);
+                for piece in buffer[..len].chunks(chunk) {
+                    if self
+                        .send_service_segment(index, TCP_FLAG_PSH | TCP_FLAG_ACK, piece)
+                        .is_err()
+                    {
+                        let service = &mut self.services.slots[index];
+                        service.drops = service.drops.saturating_add(1);
+                        break;
+                    }
+                }
+                if self.services.slots[index].tcp == TcpState::CloseWait
+                    && self
+                        .send_service_segment(index, TCP_FLAG_FIN | TCP_FLAG_ACK, &[])
+                        .is_ok()
+                {
+                    self.services.slots[index].tcp = TcpState::LastAck;
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Resolves when the service has bytes to echo, a close to finish, or was stopped.
+struct ServiceWork {
+    index: usize,
+}
+
+impl Future for ServiceWork {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        with_net_mut(|state| {
+            let service = &mut state.services.slots[self.index];
+            if service.has_work() {
+                Poll::Ready(())
+            } else {
+                service.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+async fn run_service(index: usize) {
+    let mut buffer = [0u8; PENDING_CAP];
+    loop {
+        if executor::unless_terminated(ServiceWork { index })
+            .await
+            .is_none()
+        {
+            // SIGTERM acts like `service stop`: the next step resets and releases the slot.
+            with_net_mut(|state| state.services.slots[index].active = false);
+        }
+        if !with_net_mut(|state| state.service_step(index, &mut buffer)) {
+            break;
+        }
+    }
+}
+
+fn parse_service_args(args: &str) -> Option<(ServiceKind, u16)> {
+    let mut parts = args.split_whitespace();
+    let kind = ServiceKind::parse(parts.next()?)?;
+    let port = parts
+        .next()?
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port != 0)?;
+    parts.next().is_none().then_some((kind, port))
+}
+
+pub fn start_service_to_serial(args: &str) -> Status {
+    let Some((kind, port)) = parse_service_args(args) else {
+        return Status::usage("usage: service start <echo-udp|echo-tcp|midi-udp> <port>");
+    };
+    let claimed = with_net_mut(|state| {
+        if !state.ready {
+            return Err("net not ready");
+        }
+        if kind == ServiceKind::MidiUdp && !Feature::Audio.enabled() {
+            return Err("built without feature `audio`");
+        }
+        if state.services.find_port(kind.tcp(), port).is_some()
+            || (kind.tcp() && state.tcp.listening(port))
+        {
+            return Err("port in use");
+        }
+        let index = state
+            .services
+            .slots
+            .iter()
+            .position(|service| !service.running)
+            .ok_or("service table full")?;
+        let service = &mut state.services.slots[index];
+        *service = EchoService::empty();
+        service.kind = kind;
+        service.port = port;
+        service.active = true;
+        service.running = true;
+        service.started_tick = time::ticks();
+        Ok(index)
+    });
+    let index = match claimed {
+        Ok(index) => index,
+        Err(reason) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!(
+                    "service: {} {} not started ({})\n",
+                    kind.as_str(),
+                    port,
+                    reason
+                ),
+            );
+            return Status::Failed;
+        }
+    };
+    let spawned = match kind {
+        ServiceKind::MidiUdp => executor::spawn(kind.as_str(), midi::run(index)),
+        ServiceKind::EchoUdp | ServiceKind::EchoTcp => {
+            executor::spawn(kind.as_str(), run_service(index))
+        }
+    };
+    if !spawned {
+        with_net_mut(|state| state.services.slots[index] = EchoService::empty());
+        serial::write_severity_line(Severity::Error, "service: start failed (no executor slot)");
+        return Status::Failed;
+    }
+    serial::write_fmt(format_args!(
+        "service: {} listening on port {}\n",
+        kind.as_str(),
+        port
+    ));
+    Status::Ok
+}
+
+pub fn stop_service_to_serial(args: &str) -> Status {
+    let Some((kind, port)) = parse_service_args(args) else {
+        return Status::usage("usage: service stop <echo-udp|echo-tcp|midi-udp> <port>");
+    };
+    let stopped = with_net_mut(|state| {
+        let index = state.services.find(kind, port)?;
+        let service = &mut state.services.slots[index];
+        service.active = false;
+        service.wake();
+        Some(())
+    });
+    if stopped.is_some() {
+        serial::write_fmt(format_args!(
+            "service: {} on port {} stopped\n",
+            kind.as_str(),
+            port
+        ));
+    } else {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!("service: no {} on port {}\n", kind.as_str(), port),
+        );
+    }
+    Status::check(stopped.is_some())
+}
+
+pub fn log_services() {
+    let now = time::ticks();
+    with_net(|state| {
+        let active = state
+            .services
+            .slots
+            .iter()
+            .filter(|service| service.active)
+            .count();
+        serial::write_fmt(format_args!(
+            "service: slots={} active={}\n",
+            MAX_SERVICES, active
+        ));
+        for service in state.services.slots.iter().filter(|service| service.active) {
+            let status = match service.kind {
+                ServiceKind::EchoUdp | ServiceKind::MidiUdp => "listening",
+                ServiceKind::EchoTcp => service.tcp.as_str(),
+            };
+            serial::write_fmt(format_args!(
+                "service: {} port={} state={} peer={}.{}.{}.{}:{} uptime_ticks={} conns={} reaped={} rx={} rx_bytes={} tx={} tx_bytes={} drops={}",
+                service.kind.as_str(),
+                service.port,
+                status,
+                service.peer_ip[0],
+                service.peer_ip[1],
+                service.peer_ip[2],
+                service.peer_ip[3],
+                service.peer_port,
+                now.saturating_sub(service.started_tick),
+                service.connections,
+                service.reaped,
+                service.rx_packets,
+                service.rx_bytes,
+                service.tx_packets,
+                service.tx_bytes,
+                service.drops
+            ));
+            if service.kind == ServiceKind::MidiUdp {
+                service.midi.log_suffix();
+            }
+            serial::write_str("\n");
+        }
+    });
+}
@@ -0,0 +1,160 @@
+// kernel/src/net/service/midi.rs: the `midi-udp` service. A datagram is a run of 3-byte records
+// played in order: MIDI note-on and note-off drive `audio::note_on`/`note_off`, and a wait record
+// holds the rest of the datagram back on the executor timer, so one datagram can carry a phrase.
+// Waits count from the previous deadline, not from when the task woke, so a phrase keeps time.
+use super::{NetState, PENDING_CAP, ServiceWork, with_net, with_net_mut};
+#[cfg(feature = "audio")]
+use crate::audio;
+use crate::proc::executor;
+use crate::serial;
+use crate::time;
+
+/// `service start` refuses midi-udp in a kernel without `audio`, so nothing ever plays here.
+#[cfg(not(feature = "audio"))]
+mod audio {
+    pub(super) fn note_on(_key: u8, _velocity: u8) {}
+    pub(super) fn note_off(_key: u8) {}
+}
+
+const RECORD_BYTES: usize = 3;
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+/// `F9 lo hi`: wait `lo | hi << 8` ms. MIDI leaves status 0xF9 undefined.
+const WAIT: u8 = 0xF9;
+/// Longest single wait, so `service stop` never waits long for a datagram to finish.
+const MAX_WAIT_MS: u64 = 2_000;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Record {
+    NoteOn { key: u8, velocity: u8 },
+    NoteOff { key: u8 },
+    Wait { ms: u64 },
+    Ignored,
+}
+
+impl Record {
+    /// Any channel is accepted; data bytes are masked to 7 bits as in MIDI.
+    fn parse(bytes: &[u8]) -> Self {
+        let &[status, data1, data2] = bytes else {
+            return Self::Ignored;
+        };
+        let (key, velocity) = (data1 & 0x7F, data2 & 0x7F);
+        match status & 0xF0 {
+            NOTE_ON if velocity > 0 => Self::NoteOn { key, velocity },
+            NOTE_ON | NOTE_OFF => Self::NoteOff { key },
+            _ if status == WAIT => Self::Wait {
+                ms: u64::from(u16::from_le_bytes([data1, data2])).min(MAX_WAIT_MS),
+            },
+            _ => Self::Ignored,
+        }
+    }
+}
+
+/// Records played by one `midi-udp` service, for `service list`.
+#[derive(Clone, Copy)]
+pub(super) struct MidiCounters {
+    notes_on: u64,
+    notes_off: u64,
+    waits: u64,
+    ignored: u64,
+}
+
+impl MidiCounters {
+    pub(super) const fn new() -> Self {
+        Self {
+            notes_on: 0,
+            notes_off: 0,
+            waits: 0,
+            ignored: 0,
+        }
+    }
+
+    fn add(&mut self, other: Self) {
+        self.notes_on = self.notes_on.saturating_add(other.notes_on);
+        self.notes_off = self.notes_off.saturating_add(other.notes_off);
+        self.waits = self.waits.saturating_add(other.waits);
+        self.ignored = self.ignored.saturating_add(other.ignored);
+    }
+
+    pub(super) fn log_suffix(&self) {
+        serial::write_fmt(format_args!(
+            " notes_on={} notes_off={} waits={} ignored={}",
+            self.notes_on, self.notes_off, self.waits, self.ignored
+        ));
+    }
+}
+
+impl NetState {
+    /// Takes the datagram waiting for midi-udp slot `index`; `None` once the service was
+    /// stopped, after releasing the slot.
+    fn take_midi_datagram(
+        &mut self,
+        index: usize,
+        buffer: &mut [u8; PENDING_CAP],
+    ) -> Option<usize> {
+        let service = &mut self.services.slots[index];
+        if !service.active {
+            service.reset_connection();
+            service.running = false;
+            return None;
+        }
+        let len = service.pending_len;
+        buffer[..len].copy_from_slice(&service.pending[..len]);
+        service.pending_len = 0;
+        Some(len)
+    }
+}
+
+pub(super) async fn run(index: usize) {
+    let mut buffer = [0u8; PENDING_CAP];
+    // Keys this service turned on and has not turned off, one bit each.
+    let mut held = 0u128;
+    loop {
+        if executor::unless_terminated(ServiceWork { index })
+            .await
+            .is_none()
+        {
+            with_net_mut(|state| state.services.slots[index].active = false);
+        }
+        let Some(len) = with_net_mut(|state| state.take_midi_datagram(index, &mut buffer)) else {
+            break;
+        };
+        let played = play(index, &buffer[..len], &mut held).await;
+        with_net_mut(|state| state.services.slots[index].midi.add(played));
+    }
+    // A stopped service leaves no note sounding.
+    for key in 0..128u8 {
+        if held & (1 << key) != 0 {
+            audio::note_off(key);
+        }
+    }
+}
+
+async fn play(index: usize, datagram: &[u8], held: &mut u128) -> MidiCounters {
+    let mut played = MidiCounters::new();
+    let mut deadline = time::ticks();
+    for bytes in datagram.chunks(RECORD_BYTES) {
+        match Record::parse(bytes) {
+            Record::NoteOn { key, velocity } => {
+                audio::note_on(key, velocity);
+                *held |= 1 << key;
+                played.notes_on += 1;
+            }
+            Record::NoteOff { key } => {
+                audio::note_off(key);
+                *held &= !(1 << key);
+                played.notes_off += 1;
+            }
+            Record::Wait { ms } => {
+                played.waits += 1;
+                deadline += (ms * u64::from(time::PIT_HZ)).div_ceil(1000);
+                let slept = executor::unless_terminated(executor::sleep_until(deadline)).await;
+                if slept.is_none() || !with_net(|state| state.services.slots[index].active) {
+                    break;
+                }
+            }
+            Record::Ignored => played.ignored += 1,
+        }
+    }
+    played
+}
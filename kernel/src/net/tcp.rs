@@ -0,0 +1,1189 @@
+// kernel/src/net/tcp.rs: the TCP socket table behind `net::tcp_*`. Up to `MAX_SOCKETS` active and
+// passive connections share it, each with its own send and receive buffer, an RFC 6298
+// retransmit timer fed by the path metrics, and the MSS the peer announced. A listener leaves
+// new connections in the table until `tcp_accept` hands them out, as BSD sockets do. The
+// echo-tcp services keep their own one-connection path in `service` and win on their ports.
+use super::{
+    ARP_WAIT_TICKS, NetError, NetState, TCP_FLAG_ACK, TCP_FLAG_FIN, TCP_FLAG_PSH, TCP_FLAG_RST,
+    TCP_FLAG_SYN, TcpLink, parse_ipv4, path, ports::Transport, service::TcpSegment, with_net,
+};
+use crate::arch::x86_64::entropy;
+use crate::serial;
+use crate::shell::Status;
+use crate::time;
+use core::hint::spin_loop;
+
+pub(super) const MAX_SOCKETS: usize = 8;
+/// Received bytes not read yet; the free space is the window advertised to the peer.
+const RX_CAP: usize = 2048;
+/// Bytes queued by `tcp_send`, from the oldest unacked one on.
+const TX_CAP: usize = 2048;
+/// Connections a listener holds for `tcp_accept`; further SYNs are dropped and retried.
+const BACKLOG: usize = 4;
+/// Peer MSS when its SYN carries no option (RFC 9293 3.7.1).
+const DEFAULT_MSS: u16 = 536;
+const OPT_END: u8 = 0;
+const OPT_NOP: u8 = 1;
+pub(super) const OPT_MSS: u8 = 2;
+/// Retransmits of one segment before the connection fails with `io_timeout`.
+const MAX_RETRANSMITS: u32 = 6;
+/// TIME-WAIT hold (2 s); a QEMU link has no old duplicates worth a full 2 MSL.
+const TIME_WAIT_TICKS: u64 = 200;
+/// A closed socket whose peer never sends its FIN is dropped after 60 s, as Linux does.
+const FIN_WAIT_2_TICKS: u64 = 6000;
+/// How long `tcp connect` and `tcp recv` in the shell wait before reporting.
+const SHELL_WAIT_TICKS: u64 = 300;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TcpState {
+    Closed,
+    Listen,
+    SynSent,
+    SynReceived,
+    Established,
+    FinWait1,
+    FinWait2,
+    CloseWait,
+    Closing,
+    LastAck,
+    TimeWait,
+}
+
+impl TcpState {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Listen => "listen",
+            Self::SynSent => "syn-sent",
+            Self::SynReceived => "syn-received",
+            Self::Established => "established",
+            Self::FinWait1 => "fin-wait-1",
+            Self::FinWait2 => "fin-wait-2",
+            Self::CloseWait => "close-wait",
+            Self::Closing => "closing",
+            Self::LastAck => "last-ack",
+            Self::TimeWait => "time-wait",
+        }
+    }
+
+    /// States that still take data from the peer.
+    const fn receives(self) -> bool {
+        matches!(self, Self::Established | Self::FinWait1 | Self::FinWait2)
+    }
+
+    /// States that may still send data or a FIN.
+    const fn sends(self) -> bool {
+        matches!(
+            self,
+            Self::Established | Self::CloseWait | Self::FinWait1 | Self::Closing | Self::LastAck
+        )
+    }
+}
+
+/// One socket as `tcp_info` reports it.
+#[derive(Clone, Copy)]
+pub struct TcpInfo {
+    pub state: TcpState,
+    pub local_port: u16,
+    pub peer_ip: [u8; 4],
+    pub peer_port: u16,
+    /// Received bytes waiting for `tcp_recv`.
+    pub rx_queued: usize,
+    /// Sent bytes not acked yet plus bytes waiting for window.
+    pub tx_queued: usize,
+    pub error: Option<NetError>,
+}
+
+/// SYS_POLL state of one socket.
+#[derive(Clone, Copy)]
+pub struct TcpReadiness {
+    /// `tcp_recv` returns data, end of stream or the error at once; on a listener,
+    /// `tcp_accept` has a connection.
+    pub readable: bool,
+    /// `tcp_send` has buffer space.
+    pub writable: bool,
+    /// The connection failed; the next `tcp_send` or `tcp_recv` reports why.
+    pub failed: bool,
+}
+
+struct Socket {
+    /// 0 while the slot is free.
+    id: u32,
+    state: TcpState,
+    /// Held by a `tcp_*` caller. A socket its owner closed stays until the close completes.
+    owned: bool,
+    /// Listener a passive connection arrived on, until `tcp_accept` hands it out.
+    listener: u32,
+    /// Why the connection ended, reported by the next `tcp_send` or `tcp_recv`.
+    error: Option<NetError>,
+    local_port: u16,
+    peer_mac: [u8; 6],
+    peer_ip: [u8; 4],
+    peer_port: u16,
+    /// The SYN waits for the next hop's MAC until `deadline`.
+    arp_pending: bool,
+    iss: u32,
+    snd_una: u32,
+    snd_next: u32,
+    snd_wnd: u16,
+    /// Largest segment payload: the peer's MSS capped by our MTU.
+    mss: u16,
+    rcv_next: u32,
+    syn_unacked: bool,
+    /// Close requested: a FIN follows the last queued byte.
+    fin_queued: bool,
+    fin_sent: bool,
+    /// `tx[..tx_sent]` is in flight, `tx[tx_sent..tx_len]` waits for window.
+    tx_sent: usize,
+    tx_len: usize,
+    tx: [u8; TX_CAP],
+    rx_len: usize,
+    rx: [u8; RX_CAP],
+    rto: u64,
+    /// Retransmit (or zero-window probe) deadline while anything is outstanding.
+    timer: Option<u64>,
+    /// Retransmits since the last new ack; also marks segments Karn's rule must not time.
+    retransmits: u32,
+    /// Sequence number whose ack gives the next RTT sample, and when it went out.
+    timed: Option<(u32, u64)>,
+    /// End of the ARP wait or of TIME-WAIT.
+    deadline: u64,
+    last_heard_tick: u64,
+    rx_bytes: u64,
+    tx_bytes: u64,
+}
+
+impl Socket {
+    const fn empty() -> Self {
+        Self {
+            id: 0,
+            state: TcpState::Closed,
+            owned: false,
+            listener: 0,
+            error: None,
+            local_port: 0,
+            peer_mac: [0; 6],
+            peer_ip: [0; 4],
+            peer_port: 0,
+            arp_pending: false,
+            iss: 0,
+            snd_una: 0,
+            snd_next: 0,
+            snd_wnd: 0,
+            mss: DEFAULT_MSS,
+            rcv_next: 0,
+            syn_unacked: false,
+            fin_queued: false,
+            fin_sent: false,
+            tx_sent: 0,
+            tx_len: 0,
+            tx: [0; TX_CAP],
+            rx_len: 0,
+            rx: [0; RX_CAP],
+            rto: path::INITIAL_RTO_TICKS,
+            timer: None,
+            retransmits: 0,
+            timed: None,
+            deadline: 0,
+            last_heard_tick: 0,
+            rx_bytes: 0,
+            tx_bytes: 0,
+        }
+    }
+
+    const fn free(&self) -> bool {
+        self.id == 0
+    }
+
+    const fn window(&self) -> u16 {
+        (RX_CAP - self.rx_len) as u16
+    }
+
+    const fn link(&self) -> TcpLink {
+        TcpLink {
+            mac: self.peer_mac,
+            ip: self.peer_ip,
+            local_port: self.local_port,
+            remote_port: self.peer_port,
+        }
+    }
+
+    fn info(&self) -> TcpInfo {
+        TcpInfo {
+            state: self.state,
+            local_port: self.local_port,
+            peer_ip: self.peer_ip,
+            peer_port: self.peer_port,
+            rx_queued: self.rx_len,
+            tx_queued: self.tx_len,
+            error: self.error,
+        }
+    }
+}
+
+pub(super) struct TcpTable {
+    sockets: [Socket; MAX_SOCKETS],
+    next_id: u32,
+}
+
+impl TcpTable {
+    pub(super) const fn new() -> Self {
+        Self {
+            sockets: [const { Socket::empty() }; MAX_SOCKETS],
+            next_id: 1,
+        }
+    }
+
+    /// After `restart net`: every connection is gone, but ids keep counting so a handle from
+    /// before never names a new socket.
+    pub(super) const fn after_restart(previous: &Self) -> Self {
+        let mut table = Self::new();
+        table.next_id = previous.next_id;
+        table
+    }
+
+    /// Slot of the socket `id` held by a caller.
+    pub(super) fn find(&self, id: u32) -> Result<usize, NetError> {
+        self.sockets
+            .iter()
+            .position(|socket| socket.id == id && socket.owned)
+            .ok_or(NetError::InvalidSocket)
+    }
+
+    pub(super) const fn id(&self, index: usize) -> u32 {
+        self.sockets[index].id
+    }
+
+    pub(super) fn info(&self, index: usize) -> TcpInfo {
+        self.sockets[index].info()
+    }
+
+    pub(super) fn readiness(&self, index: usize) -> TcpReadiness {
+        let socket = &self.sockets[index];
+        let readable = match socket.state {
+            TcpState::Listen => self.sockets.iter().any(|child| {
+                !child.free()
+                    && !child.owned
+                    && child.listener == socket.id
+                    && child.state != TcpState::SynReceived
+            }),
+            TcpState::SynSent
+            | TcpState::SynReceived
+            | TcpState::Established
+            | TcpState::FinWait1
+            | TcpState::FinWait2 => socket.rx_len > 0 || socket.error.is_some(),
+            _ => true,
+        };
+        let writable = matches!(socket.state, TcpState::Established | TcpState::CloseWait)
+            && !socket.fin_queued
+            && socket.error.is_none()
+            && socket.tx_len < TX_CAP;
+        TcpReadiness {
+            readable,
+            writable,
+            failed: socket.error.is_some(),
+        }
+    }
+
+    fn claim(&mut self) -> Result<usize, NetError> {
+        let index = self
+            .sockets
+            .iter()
+            .position(Socket::free)
+            .ok_or(NetError::SocketTableFull)?;
+        self.sockets[index] = Socket::empty();
+        self.sockets[index].id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        Ok(index)
+    }
+
+    fn port_in_use(&self, port: u16) -> bool {
+        self.sockets
+            .iter()
+            .any(|socket| !socket.free() && socket.local_port == port)
+    }
+
+    fn connection(&self, local_port: u16, peer_ip: [u8; 4], peer_port: u16) -> Option<usize> {
+        self.sockets.iter().position(|socket| {
+            !socket.free()
+                && socket.state != TcpState::Listen
+                && socket.local_port == local_port
+                && socket.peer_ip == peer_ip
+                && socket.peer_port == peer_port
+        })
+    }
+
+    fn listener(&self, port: u16) -> Option<usize> {
+        self.sockets
+            .iter()
+            .position(|socket| socket.state == TcpState::Listen && socket.local_port == port)
+    }
+
+    /// A socket listens on `port`; `service start echo-tcp` stays off it.
+    pub(super) fn listening(&self, port: u16) -> bool {
+        self.listener(port).is_some()
+    }
+
+    fn backlog(&self, listener: u32) -> usize {
+        self.sockets
+            .iter()
+            .filter(|socket| !socket.free() && !socket.owned && socket.listener == listener)
+            .count()
+    }
+
+    pub(super) fn open_count(&self) -> usize {
+        self.sockets.iter().filter(|socket| !socket.free()).count()
+    }
+
+    /// `netstat` / `tcp list` lines, one per socket.
+    pub(super) fn log_sockets(&self, prefix: &str, local_ip: [u8; 4], now: u64) {
+        let [a, b, c, d] = local_ip;
+        for socket in self.sockets.iter().filter(|socket| !socket.free()) {
+            let [e, f, g, h] = socket.peer_ip;
+            serial::write_fmt(format_args!(
+                "{prefix} {a}.{b}.{c}.{d}:{} {e}.{f}.{g}.{h}:{} state={} id={} owned={} rx_queued={} tx_queued={} mss={} rto_ms={} retransmits={} idle_ms={} rx_bytes={} tx_bytes={}\n",
+                socket.local_port,
+                socket.peer_port,
+                socket.state.as_str(),
+                socket.id,
+                socket.owned,
+                socket.rx_len,
+                socket.tx_len,
+                socket.mss,
+                socket.rto * 10,
+                socket.retransmits,
+                now.saturating_sub(socket.last_heard_tick) * 10,
+                socket.rx_bytes,
+                socket.tx_bytes
+            ));
+        }
+    }
+}
+
+/// MSS from the options of a SYN; `None` when absent or malformed.
+pub(super) fn parse_mss(options: &[u8]) -> Option<u16> {
+    let mut offset = 0;
+    while offset < options.len() {
+        match options[offset] {
+            OPT_END => break,
+            OPT_NOP => offset += 1,
+            kind => {
+                let len = usize::from(*options.get(offset + 1)?);
+                if len < 2 || offset + len > options.len() {
+                    return None;
+                }
+                if kind == OPT_MSS && len == 4 {
+                    return Some(u16::from_be_bytes([
+                        options[offset + 2],
+                        options[offset + 3],
+                    ]));
+                }
+                offset += len;
+            }
+        }
+    }
+    None
+}
+
+/// `a` is `b` or later in sequence space.
+const fn seq_at_or_after(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) >= 0
+}
+
+impl NetState {
+    /// Largest segment payload this side takes: the MTU less IPv4 and TCP headers.
+    fn local_mss(&self) -> u16 {
+        self.mtu.saturating_sub(40).min(usize::from(u16::MAX)) as u16
+    }
+
+    /// Active open. The SYN leaves at once when the next hop's MAC is cached, otherwise from
+    /// `poll_tcp` once ARP resolves.
+    pub(super) fn tcp_open(&mut self, ip: [u8; 4], port: u16) -> Result<usize, NetError> {
+        if !self.ready {
+            return Err(NetError::NotReady);
+        }
+        if port == 0 || ip == [0; 4] || self.is_broadcast_ip(ip) {
+            return Err(NetError::InvalidAddress);
+        }
+        let local_port = self.allocate_port(Transport::Tcp)?;
+        let index = match self.tcp.claim() {
+            Ok(index) => index,
+            Err(err) => {
+                self.ports.release(Transport::Tcp, local_port);
+                return Err(err);
+            }
+        };
+        let next_hop = self.select_next_hop(ip);
+        let mac = self.begin_arp(next_hop);
+        let now = time::ticks();
+        let iss = entropy::next_u64() as u32;
+        let rto = self.paths.rto_ticks(ip);
+        let mss = self.local_mss();
+        let socket = &mut self.tcp.sockets[index];
+        socket.state = TcpState::SynSent;
+        socket.owned = true;
+        socket.local_port = local_port;
+        socket.peer_ip = ip;
+        socket.peer_port = port;
+        socket.iss = iss;
+        socket.snd_una = iss;
+        socket.snd_next = iss;
+        socket.mss = mss;
+        socket.rto = rto;
+        socket.last_heard_tick = now;
+        match mac {
+            Ok(Some(mac)) => {
+                socket.peer_mac = mac;
+                self.tcp_send_syn(index);
+            }
+            Ok(None) => {
+                socket.arp_pending = true;
+                socket.deadline = now + ARP_WAIT_TICKS;
+            }
+            Err(err) => {
+                self.tcp_release(index);
+                return Err(err);
+            }
+        }
+        Ok(index)
+    }
+
+    pub(super) fn tcp_listen(&mut self, port: u16) -> Result<u32, NetError> {
+        if !self.ready {
+            return Err(NetError::NotReady);
+        }
+        if port == 0 {
+            return Err(NetError::InvalidAddress);
+        }
+        if self.tcp.listener(port).is_some() || self.services.find_port(true, port).is_some() {
+            return Err(NetError::PortInUse);
+        }
+        let index = self.tcp.claim()?;
+        self.ports.reserve(Transport::Tcp, port);
+        let socket = &mut self.tcp.sockets[index];
+        socket.state = TcpState::Listen;
+        socket.owned = true;
+        socket.local_port = port;
+        socket.last_heard_tick = time::ticks();
+        Ok(socket.id)
+    }
+
+    pub(super) fn tcp_accept(&mut self, listener: u32) -> Result<Option<u32>, NetError> {
+        let index = self.tcp.find(listener)?;
+        if self.tcp.sockets[index].state != TcpState::Listen {
+            return Err(NetError::NotConnected);
+        }
+        // Only completed handshakes; a peer may have closed again already.
+        let ready = self.tcp.sockets.iter_mut().find(|socket| {
+            !socket.free()
+                && !socket.owned
+                && socket.listener == listener
+                && socket.state != TcpState::SynReceived
+        });
+        Ok(ready.map(|socket| {
+            socket.owned = true;
+            socket.listener = 0;
+            socket.id
+        }))
+    }
+
+    /// Copies out received bytes. `Ok(0)` is end of stream; `WouldBlock` means nothing yet.
+    pub(super) fn tcp_read(&mut self, index: usize, out: &mut [u8]) -> Result<usize, NetError> {
+        let mss = self.tcp.sockets[index].mss;
+        let socket = &mut self.tcp.sockets[index];
+        if socket.rx_len > 0 {
+            let window_before = socket.window();
+            let len = socket.rx_len.min(out.len());
+            out[..len].copy_from_slice(&socket.rx[..len]);
+            socket.rx.copy_within(len..socket.rx_len, 0);
+            socket.rx_len -= len;
+            // Window update once a full segment fits again, so a stalled peer resumes.
+            if window_before < mss && socket.window() >= mss && socket.state.receives() {
+                self.tcp_ack_now(index);
+            }
+            return Ok(len);
+        }
+        if let Some(err) = socket.error {
+            return Err(err);
+        }
+        match socket.state {
+            TcpState::Listen => Err(NetError::NotConnected),
+            TcpState::SynSent
+            | TcpState::SynReceived
+            | TcpState::Established
+            | TcpState::FinWait1
+            | TcpState::FinWait2 => Err(NetError::WouldBlock),
+            TcpState::CloseWait
+            | TcpState::Closing
+            | TcpState::LastAck
+            | TcpState::TimeWait
+            | TcpState::Closed => Ok(0),
+        }
+    }
+
+    /// Queues what fits of `data`; `WouldBlock` while the send buffer is full.
+    pub(super) fn tcp_write(&mut self, index: usize, data: &[u8]) -> Result<usize, NetError> {
+        let socket = &mut self.tcp.sockets[index];
+        if let Some(err) = socket.error {
+            return Err(err);
+        }
+        let open = matches!(
+            socket.state,
+            TcpState::SynSent | TcpState::SynReceived | TcpState::Established | TcpState::CloseWait
+        );
+        if !open || socket.fin_queued {
+            return Err(NetError::NotConnected);
+        }
+        let len = data.len().min(TX_CAP - socket.tx_len);
+        if len == 0 && !data.is_empty() {
+            return Err(NetError::WouldBlock);
+        }
+        socket.tx[socket.tx_len..socket.tx_len + len].copy_from_slice(&data[..len]);
+        socket.tx_len += len;
+        self.tcp_output(index, false);
+        Ok(len)
+    }
+
+    /// Orderly close: queued data goes out, then a FIN. The slot is freed once the peer
+    /// acknowledged it, or right away when nothing was connected.
+    pub(super) fn tcp_close_slot(&mut self, index: usize) {
+        let socket = &mut self.tcp.sockets[index];
+        socket.owned = false;
+        match socket.state {
+            TcpState::Listen => {
+                let listener = socket.id;
+                self.tcp_release(index);
+                for child in 0..MAX_SOCKETS {
+                    let socket = &self.tcp.sockets[child];
+                    if !socket.free() && !socket.owned && socket.listener == listener {
+                        self.tcp_abort_slot(child);
+                    }
+                }
+            }
+            TcpState::Closed | TcpState::SynSent => self.tcp_release(index),
+            TcpState::SynReceived | TcpState::Established | TcpState::CloseWait => {
+                socket.fin_queued = true;
+                self.tcp_output(index, false);
+            }
+            _ => {}
+        }
+    }
+
+    /// Drops the connection with a RST and frees the slot.
+    pub(super) fn tcp_abort_slot(&mut self, index: usize) {
+        let socket = &self.tcp.sockets[index];
+        if !matches!(
+            socket.state,
+            TcpState::Closed | TcpState::Listen | TcpState::SynSent | TcpState::TimeWait
+        ) {
+            let (link, seq, ack) = (socket.link(), socket.snd_next, socket.rcv_next);
+            let _ = self.send_tcp_segment(link, seq, ack, TCP_FLAG_RST | TCP_FLAG_ACK, 0, &[]);
+        }
+        self.tcp_release(index);
+    }
+
+    /// Frees the slot; the port goes back once no connection of a listener still uses it.
+    fn tcp_release(&mut self, index: usize) {
+        let port = self.tcp.sockets[index].local_port;
+        self.tcp.sockets[index] = Socket::empty();
+        if port != 0 && !self.tcp.port_in_use(port) {
+            self.ports.release(Transport::Tcp, port);
+        }
+    }
+
+    /// Ends the connection with `error`: an owned socket keeps it for its owner, anything
+    /// else is freed.
+    fn tcp_fail(&mut self, index: usize, error: NetError) {
+        let socket = &mut self.tcp.sockets[index];
+        if !socket.owned {
+            self.tcp_release(index);
+            return;
+        }
+        socket.state = TcpState::Closed;
+        socket.error = Some(error);
+        socket.timer = None;
+        socket.tx_len = 0;
+        socket.tx_sent = 0;
+    }
+
+    /// SYN (active open) or SYN-ACK (passive), announcing our MSS.
+    fn tcp_send_syn(&mut self, index: usize) {
+        let mss = self.local_mss();
+        let socket = &self.tcp.sockets[index];
+        let (flags, ack) = if socket.state == TcpState::SynReceived {
+            (TCP_FLAG_SYN | TCP_FLAG_ACK, socket.rcv_next)
+        } else {
+            (TCP_FLAG_SYN, 0)
+        };
+        let (link, iss, window) = (socket.link(), socket.iss, socket.window());
+        let _ = self.send_tcp_syn_segment(link, iss, ack, flags, window, mss);
+        let now = time::ticks();
+        let socket = &mut self.tcp.sockets[index];
+        socket.syn_unacked = true;
+        socket.snd_next = iss.wrapping_add(1);
+        socket.timer.get_or_insert(now + socket.rto);
+        if socket.retransmits == 0 && socket.timed.is_none() {
+            socket.timed = Some((socket.snd_next, now));
+        }
+    }
+
+    fn tcp_ack_now(&mut self, index: usize) {
+        let socket = &self.tcp.sockets[index];
+        let (link, seq, ack, window) = (
+            socket.link(),
+            socket.snd_next,
+            socket.rcv_next,
+            socket.window(),
+        );
+        let _ = self.send_tcp_segment(link, seq, ack, TCP_FLAG_ACK, window, &[]);
+    }
+
+    /// Sends queued data as far as the peer's window allows, then the FIN once everything
+    /// is out. `probe` lets one byte into a zero window, for the persist timer.
+    fn tcp_output(&mut self, index: usize, mut probe: bool) {
+        let mut payload = [0u8; TX_CAP];
+        loop {
+            let now = time::ticks();
+            let socket = &mut self.tcp.sockets[index];
+            if !socket.state.sends() || socket.syn_unacked {
+                return;
+            }
+            let in_flight = socket.tx_sent;
+            let mut room = usize::from(socket.snd_wnd).saturating_sub(in_flight);
+            if probe && room == 0 && in_flight == 0 {
+                room = 1;
+            }
+            probe = false;
+            let len = (socket.tx_len - in_flight)
+                .min(room)
+                .min(usize::from(socket.mss));
+            if len == 0 {
+                break;
+            }
+            let seq = socket.snd_una.wrapping_add(in_flight as u32);
+            payload[..len].copy_from_slice(&socket.tx[in_flight..in_flight + len]);
+            let (link, ack, window) = (socket.link(), socket.rcv_next, socket.window());
+            let flags = TCP_FLAG_ACK | TCP_FLAG_PSH;
+            if self
+                .send_tcp_segment(link, seq, ack, flags, window, &payload[..len])
+                .is_err()
+            {
+                break;
+            }
+            let socket = &mut self.tcp.sockets[index];
+            socket.tx_sent += len;
+            socket.snd_next = seq.wrapping_add(len as u32);
+            socket.tx_bytes = socket.tx_bytes.saturating_add(len as u64);
+            socket.timer.get_or_insert(now + socket.rto);
+            if socket.retransmits == 0 && socket.timed.is_none() {
+                socket.timed = Some((socket.snd_next, now));
+            }
+        }
+
+        let now = time::ticks();
+        let socket = &mut self.tcp.sockets[index];
+        if socket.fin_queued && !socket.fin_sent && socket.tx_sent == socket.tx_len {
+            let seq = socket.snd_una.wrapping_add(socket.tx_len as u32);
+            let (link, ack, window) = (socket.link(), socket.rcv_next, socket.window());
+            let flags = TCP_FLAG_FIN | TCP_FLAG_ACK;
+            if self
+                .send_tcp_segment(link, seq, ack, flags, window, &[])
+                .is_ok()
+            {
+                let socket = &mut self.tcp.sockets[index];
+                socket.fin_sent = true;
+                socket.snd_next = seq.wrapping_add(1);
+                socket.timer.get_or_insert(now + socket.rto);
+                socket.state = match socket.state {
+                    TcpState::Established => TcpState::FinWait1,
+                    TcpState::CloseWait => TcpState::LastAck,
+                    state => state,
+                };
+            }
+        }
+        // Data held back by a zero window: the timer doubles as the persist timer.
+        let socket = &mut self.tcp.sockets[index];
+        if socket.tx_sent < socket.tx_len {
+            socket.timer.get_or_insert(now + socket.rto);
+        }
+    }
+
+    /// Applies an acknowledgment: frees acked bytes, takes an RTT sample and finishes a close
+    /// whose FIN it covers.
+    fn tcp_process_ack(&mut self, index: usize, ack: u32, window: u16) {
+        let now = time::ticks();
+        let socket = &mut self.tcp.sockets[index];
+        socket.snd_wnd = window;
+        let acked = ack.wrapping_sub(socket.snd_una);
+        let outstanding = socket.snd_next.wrapping_sub(socket.snd_una);
+        if acked == 0 || acked > outstanding {
+            // A live peer answering zero-window probes is no reason to give up.
+            if window == 0 && socket.retransmits > 0 && socket.tx_len > 0 {
+                socket.retransmits = 1;
+            }
+            return;
+        }
+        let mut left = acked as usize;
+        if socket.syn_unacked {
+            socket.syn_unacked = false;
+            left -= 1;
+        }
+        let data = left.min(socket.tx_sent);
+        socket.tx.copy_within(data..socket.tx_len, 0);
+        socket.tx_len -= data;
+        socket.tx_sent -= data;
+        let fin_acked = socket.fin_sent && ack == socket.snd_next;
+        socket.snd_una = ack;
+        socket.retransmits = 0;
+        let sample = match socket.timed {
+            Some((seq, sent)) if seq_at_or_after(ack, seq) => {
+                socket.timed = None;
+                Some(now.saturating_sub(sent))
+            }
+            _ => None,
+        };
+        let peer_ip = socket.peer_ip;
+        if let Some(rtt) = sample {
+            self.paths.record_rtt(peer_ip, rtt, now);
+        }
+        let rto = self.paths.rto_ticks(peer_ip);
+        let socket = &mut self.tcp.sockets[index];
+        socket.rto = rto;
+        socket.timer = (socket.snd_una != socket.snd_next).then_some(now + rto);
+        if fin_acked {
+            match socket.state {
+                TcpState::FinWait1 => socket.state = TcpState::FinWait2,
+                TcpState::Closing => {
+                    socket.state = TcpState::TimeWait;
+                    socket.deadline = now + TIME_WAIT_TICKS;
+                }
+                TcpState::LastAck => self.tcp_release(index),
+                _ => {}
+            }
+        }
+    }
+
+    /// Runs an inbound segment the services did not take through the socket table.
+    pub(super) fn deliver_tcp(
+        &mut self,
+        src_mac: [u8; 6],
+        src_ip: [u8; 4],
+        segment: &TcpSegment<'_>,
+    ) {
+        let Some(index) = self
+            .tcp
+            .connection(segment.dst_port, src_ip, segment.src_port)
+        else {
+            self.tcp_passive_open(src_mac, src_ip, segment);
+            return;
+        };
+        let rst = segment.flags & TCP_FLAG_RST != 0;
+        let syn = segment.flags & TCP_FLAG_SYN != 0;
+        let fin = segment.flags & TCP_FLAG_FIN != 0;
+        let socket = &mut self.tcp.sockets[index];
+        socket.last_heard_tick = time::ticks();
+        match socket.state {
+            TcpState::SynSent => {
+                self.tcp_syn_sent(index, segment);
+                return;
+            }
+            TcpState::Closed => return,
+            _ => {}
+        }
+        if rst {
+            // Only an in-window reset counts, so a stray one cannot kill the connection.
+            if segment.seq == socket.rcv_next {
+                self.tcp_fail(index, NetError::ConnectionReset);
+            }
+            return;
+        }
+        if syn {
+            if socket.state == TcpState::SynReceived && segment.flags & TCP_FLAG_ACK == 0 {
+                // The peer retransmitted its SYN, so our SYN-ACK was lost.
+                self.tcp_send_syn(index);
+            } else {
+                self.tcp_ack_now(index);
+            }
+            return;
+        }
+        if segment.flags & TCP_FLAG_ACK == 0 {
+            return;
+        }
+        if socket.state == TcpState::SynReceived {
+            if segment.ack != socket.snd_next {
+                self.send_tcp_reset(src_mac, src_ip, segment);
+                return;
+            }
+            socket.state = TcpState::Established;
+        }
+        self.tcp_process_ack(index, segment.ack, segment.window);
+        let socket = &mut self.tcp.sockets[index];
+        if socket.free() {
+            return;
+        }
+
+        let mut ack_now = false;
+        if !segment.data.is_empty() {
+            ack_now = true;
+            if socket.state.receives() && segment.seq == socket.rcv_next {
+                // What does not fit comes again once the window reopens.
+                let take = segment.data.len().min(RX_CAP - socket.rx_len);
+                socket.rx[socket.rx_len..socket.rx_len + take]
+                    .copy_from_slice(&segment.data[..take]);
+                socket.rx_len += take;
+                socket.rcv_next = socket.rcv_next.wrapping_add(take as u32);
+                socket.rx_bytes = socket.rx_bytes.saturating_add(take as u64);
+            }
+        }
+        if fin {
+            ack_now = true;
+            let fin_seq = segment.seq.wrapping_add(segment.data.len() as u32);
+            if socket.state.receives() && fin_seq == socket.rcv_next {
+                socket.rcv_next = socket.rcv_next.wrapping_add(1);
+                socket.state = match socket.state {
+                    TcpState::Established => TcpState::CloseWait,
+                    TcpState::FinWait1 => TcpState::Closing,
+                    _ => TcpState::TimeWait,
+                };
+            }
+            if socket.state == TcpState::TimeWait {
+                socket.deadline = time::ticks() + TIME_WAIT_TICKS;
+            }
+        }
+        if ack_now {
+            self.tcp_ack_now(index);
+        }
+        self.tcp_output(index, false);
+    }
+
+    fn tcp_syn_sent(&mut self, index: usize, segment: &TcpSegment<'_>) {
+        let socket = &mut self.tcp.sockets[index];
+        let has_ack = segment.flags & TCP_FLAG_ACK != 0;
+        let ack_ok = has_ack && segment.ack == socket.snd_next && socket.syn_unacked;
+        if segment.flags & TCP_FLAG_RST != 0 {
+            if ack_ok {
+                self.tcp_fail(index, NetError::ConnectionRefused);
+            }
+            return;
+        }
+        if has_ack && !ack_ok {
+            let (mac, ip) = (socket.peer_mac, socket.peer_ip);
+            self.send_tcp_reset(mac, ip, segment);
+            return;
+        }
+        // Simultaneous open (a bare SYN) is not supported; the peer's SYN times out.
+        if segment.flags & TCP_FLAG_SYN == 0 || !ack_ok {
+            return;
+        }
+        socket.rcv_next = segment.seq.wrapping_add(1);
+        socket.mss = socket.mss.min(segment.mss.unwrap_or(DEFAULT_MSS)).max(1);
+        socket.state = TcpState::Established;
+        self.tcp_process_ack(index, segment.ack, segment.window);
+        self.tcp_ack_now(index);
+        self.tcp_output(index, false);
+    }
+
+    /// A segment for no connection: a SYN to a listener with backlog room opens one,
+    /// anything else not a RST is refused with one.
+    fn tcp_passive_open(&mut self, src_mac: [u8; 6], src_ip: [u8; 4], segment: &TcpSegment<'_>) {
+        if segment.flags & TCP_FLAG_RST != 0 {
+            return;
+        }
+        let listener = self.tcp.listener(segment.dst_port);
+        let syn_only = segment.flags & (TCP_FLAG_SYN | TCP_FLAG_ACK) == TCP_FLAG_SYN;
+        let Some(listener) = listener.filter(|_| syn_only) else {
+            self.send_tcp_reset(src_mac, src_ip, segment);
+            return;
+        };
+        let listener = self.tcp.sockets[listener].id;
+        if self.tcp.backlog(listener) >= BACKLOG {
+            return;
+        }
+        let Ok(index) = self.tcp.claim() else {
+            return;
+        };
+        let iss = entropy::next_u64() as u32;
+        let rto = self.paths.rto_ticks(src_ip);
+        let mss = self.local_mss();
+        let socket = &mut self.tcp.sockets[index];
+        socket.state = TcpState::SynReceived;
+        socket.listener = listener;
+        socket.local_port = segment.dst_port;
+        socket.peer_mac = src_mac;
+        socket.peer_ip = src_ip;
+        socket.peer_port = segment.src_port;
+        socket.iss = iss;
+        socket.snd_una = iss;
+        socket.snd_next = iss;
+        socket.snd_wnd = segment.window;
+        socket.rcv_next = segment.seq.wrapping_add(1);
+        socket.mss = mss.min(segment.mss.unwrap_or(DEFAULT_MSS)).max(1);
+        socket.rto = rto;
+        socket.last_heard_tick = time::ticks();
+        self.tcp_send_syn(index);
+    }
+
+    /// RFC 9293 reset for a segment no connection accepts.
+    pub(super) fn send_tcp_reset(&mut self, mac: [u8; 6], ip: [u8; 4], segment: &TcpSegment<'_>) {
+        let link = TcpLink {
+            mac,
+            ip,
+            local_port: segment.dst_port,
+            remote_port: segment.src_port,
+        };
+        let (seq, ack, flags) = if segment.flags & TCP_FLAG_ACK != 0 {
+            (segment.ack, 0, TCP_FLAG_RST)
+        } else {
+            let syn = u32::from(segment.flags & TCP_FLAG_SYN != 0);
+            let ack = segment
+                .seq
+                .wrapping_add(segment.data.len() as u32)
+                .wrapping_add(syn);
+            (0, ack, TCP_FLAG_RST | TCP_FLAG_ACK)
+        };
+        let _ = self.send_tcp_segment(link, seq, ack, flags, 0, &[]);
+    }
+
+    /// Socket timers, run from every `poll`: pending ARP for a SYN, retransmits with RFC 6298
+    /// backoff (go-back-N from the oldest unacked byte), and the end of TIME-WAIT and of an
+    /// abandoned FIN-WAIT-2.
+    pub(super) fn poll_tcp(&mut self) {
+        let now = time::ticks();
+        for index in 0..MAX_SOCKETS {
+            let socket = &self.tcp.sockets[index];
+            if socket.free() {
+                continue;
+            }
+            if socket.arp_pending {
+                let deadline = socket.deadline;
+                let next_hop = self.select_next_hop(socket.peer_ip);
+                if let Some(mac) = self.lookup_arp(next_hop) {
+                    let socket = &mut self.tcp.sockets[index];
+                    socket.arp_pending = false;
+                    socket.peer_mac = mac;
+                    self.tcp_send_syn(index);
+                } else if now >= deadline {
+                    self.tcp_fail(index, NetError::ArpTimeout);
+                }
+                continue;
+            }
+            if socket.state == TcpState::TimeWait {
+                if now >= socket.deadline {
+                    self.tcp_release(index);
+                }
+                continue;
+            }
+            if socket.state == TcpState::FinWait2 {
+                if now.saturating_sub(socket.last_heard_tick) >= FIN_WAIT_2_TICKS {
+                    self.tcp_release(index);
+                }
+                continue;
+            }
+            if !socket.timer.is_some_and(|deadline| now >= deadline) {
+                continue;
+            }
+            if socket.retransmits >= MAX_RETRANSMITS {
+                // A peer that never answered the SYN has nothing to reset.
+                if socket.state != TcpState::SynSent {
+                    let (link, seq, ack) = (socket.link(), socket.snd_next, socket.rcv_next);
+                    let flags = TCP_FLAG_RST | TCP_FLAG_ACK;
+                    let _ = self.send_tcp_segment(link, seq, ack, flags, 0, &[]);
+                }
+                self.tcp_fail(index, NetError::IoTimeout);
+                continue;
+            }
+            let peer_ip = socket.peer_ip;
+            // A zero-window probe is no loss.
+            if socket.tx_sent > 0 || socket.syn_unacked || socket.fin_sent {
+                self.paths.record_loss(peer_ip, now);
+                self.stats.tcp_retransmits = self.stats.tcp_retransmits.saturating_add(1);
+            }
+            let socket = &mut self.tcp.sockets[index];
+            socket.retransmits += 1;
+            socket.rto = socket.rto.saturating_mul(2).min(path::MAX_RTO_TICKS);
+            socket.timer = Some(now + socket.rto);
+            socket.timed = None;
+            if socket.syn_unacked {
+                self.tcp_send_syn(index);
+            } else {
+                socket.tx_sent = 0;
+                socket.fin_sent = false;
+                socket.snd_next = socket.snd_una;
+                self.tcp_output(index, true);
+            }
+        }
+    }
+}
+
+fn parse_id(text: Option<&str>) -> Option<u32> {
+    text?.parse::<u32>().ok().filter(|id| *id != 0)
+}
+
+fn report_error(op: &str, err: NetError) -> Status {
+    Status::failed(format_args!("tcp: {op} failed ({})\n", err.as_str()))
+}
+
+/// Shell `tcp ...` other than `tcp send`, which the shell parses for quoting.
+pub fn tcp_to_serial(args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (None | Some("list"), None, None, None) => {
+            log_tcp();
+            Status::Ok
+        }
+        (Some("connect"), Some(ip), Some(port), None) => {
+            match (parse_ipv4(ip), port.parse::<u16>().ok()) {
+                (Some(ip), Some(port)) => tcp_connect_to_serial(ip, port),
+                _ => Status::usage("usage: tcp connect <a.b.c.d> <port>"),
+            }
+        }
+        (Some("listen"), port, None, None) => {
+            match port.and_then(|port| port.parse::<u16>().ok()) {
+                Some(port) => match super::tcp_listen(port) {
+                    Ok(id) => {
+                        serial::write_fmt(format_args!("tcp: id={id} listening on port {port}\n"));
+                        Status::Ok
+                    }
+                    Err(err) => report_error("listen", err),
+                },
+                None => Status::usage("usage: tcp listen <port>"),
+            }
+        }
+        (Some("accept"), id, None, None) => match parse_id(id) {
+            Some(listener) => {
+                super::poll();
+                match super::tcp_accept(listener) {
+                    Ok(Some(id)) => {
+                        log_socket_line("accepted", id);
+                        Status::Ok
+                    }
+                    Ok(None) => {
+                        serial::write_fmt(format_args!(
+                            "tcp: id={listener} no connection waiting\n"
+                        ));
+                        Status::Ok
+                    }
+                    Err(err) => report_error("accept", err),
+                }
+            }
+            None => Status::usage("usage: tcp accept <id>"),
+        },
+        (Some("recv"), id, None, None) => match parse_id(id) {
+            Some(id) => tcp_recv_to_serial(id),
+            None => Status::usage("usage: tcp recv <id>"),
+        },
+        (Some("close"), id, None, None) => match parse_id(id) {
+            Some(id) => match super::tcp_close(id) {
+                Ok(()) => {
+                    serial::write_fmt(format_args!("tcp: id={id} closed\n"));
+                    Status::Ok
+                }
+                Err(err) => report_error("close", err),
+            },
+            None => Status::usage("usage: tcp close <id>"),
+        },
+        _ => Status::usage(
+            "usage: tcp [list] | tcp connect <a.b.c.d> <port> | tcp listen <port> | tcp accept <id> | tcp send <id> <text> | tcp recv <id> | tcp close <id>",
+        ),
+    }
+}
+
+fn log_socket_line(what: &str, id: u32) {
+    let Ok(info) = super::tcp_info(id) else {
+        return;
+    };
+    let [a, b, c, d] = info.peer_ip;
+    serial::write_fmt(format_args!(
+        "tcp: id={id} {what} {a}.{b}.{c}.{d}:{} local_port={} state={} rx_queued={} tx_queued={}\n",
+        info.peer_port,
+        info.local_port,
+        info.state.as_str(),
+        info.rx_queued,
+        info.tx_queued
+    ));
+}
+
+/// Polls until the handshake finishes or fails, so the shell reports the outcome.
+fn tcp_connect_to_serial(ip: [u8; 4], port: u16) -> Status {
+    let id = match super::tcp_connect(ip, port) {
+        Ok(id) => id,
+        Err(err) => return report_error("connect", err),
+    };
+    let start = time::ticks();
+    while time::ticks().saturating_sub(start) < SHELL_WAIT_TICKS {
+        super::poll();
+        match super::tcp_info(id) {
+            Ok(info) if info.state == TcpState::SynSent => spin_loop(),
+            Ok(TcpInfo {
+                error: Some(err), ..
+            }) => {
+                let _ = super::tcp_close(id);
+                return report_error("connect", err);
+            }
+            _ => break,
+        }
+    }
+    log_socket_line("connected", id);
+    Status::Ok
+}
+
+pub fn tcp_send_to_serial(id: u32, text: &str) -> Status {
+    match super::tcp_send(id, text.as_bytes()) {
+        Ok(len) => {
+            serial::write_fmt(format_args!(
+                "tcp: id={id} queued {len} of {} bytes\n",
+                text.len()
+            ));
+            Status::Ok
+        }
+        Err(err) => report_error("send", err),
+    }
+}
+
+/// Waits briefly for data, then prints what arrived (printable ASCII, others as `.`).
+fn tcp_recv_to_serial(id: u32) -> Status {
+    let mut buffer = [0u8; RX_CAP];
+    let start = time::ticks();
+    let result = loop {
+        super::poll();
+        match super::tcp_recv(id, &mut buffer) {
+            Err(NetError::WouldBlock) if time::ticks().saturating_sub(start) < SHELL_WAIT_TICKS => {
+                spin_loop();
+            }
+            result => break result,
+        }
+    };
+    match result {
+        Ok(0) => serial::write_fmt(format_args!("tcp: id={id} end of stream\n")),
+        Ok(len) => {
+            serial::write_fmt(format_args!("tcp: id={id} recv bytes={len} data=`"));
+            for &byte in &buffer[..len] {
+                let shown = if byte.is_ascii_graphic() || byte == b' ' {
+                    byte
+                } else {
+                    b'.'
+                };
+                serial::write_byte(shown);
+            }
+            serial::write_str("`\n");
+        }
+        Err(NetError::WouldBlock) => {
+            serial::write_fmt(format_args!("tcp: id={id} nothing received\n"))
+        }
+        Err(err) => return report_error("recv", err),
+    }
+    Status::Ok
+}
+
+fn log_tcp() {
+    let now = time::ticks();
+    with_net(|state| {
+        serial::write_fmt(format_args!(
+            "tcp: sockets={}/{} retransmits={}\n",
+            state.tcp.open_count(),
+            MAX_SOCKETS,
+            state.stats.tcp_retransmits
+        ));
+        state.tcp.log_sockets("tcp:", state.ipv4, now);
+    });
+}
@@ -0,0 +1,121 @@
+// kernel/src/net/wire.rs: bounds-checked header readers and per-validation RX drop reasons.
+
+/// Why a received frame was dropped; each reason has its own counter next to the `drop` total,
+/// so a fuzz run shows which validation fired rather than one opaque count.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RxDrop {
+    EthRunt,
+    EthType,
+    ArpShort,
+    ArpFormat,
+    IpShort,
+    IpVersion,
+    IpHeaderLen,
+    IpTotalLen,
+    IpChecksum,
+    IpOptions,
+    /// No reassembly: fragments would otherwise be parsed as whole datagrams.
+    IpFragment,
+    IpProto,
+    IgmpMalformed,
+    IcmpShort,
+    IcmpChecksum,
+    UdpShort,
+    UdpLength,
+    UdpChecksum,
+    TcpShort,
+    TcpDataOffset,
+    TcpChecksum,
+}
+
+impl RxDrop {
+    pub const ALL: [Self; 21] = [
+        Self::EthRunt,
+        Self::EthType,
+        Self::ArpShort,
+        Self::ArpFormat,
+        Self::IpShort,
+        Self::IpVersion,
+        Self::IpHeaderLen,
+        Self::IpTotalLen,
+        Self::IpChecksum,
+        Self::IpOptions,
+        Self::IpFragment,
+        Self::IpProto,
+        Self::IgmpMalformed,
+        Self::IcmpShort,
+        Self::IcmpChecksum,
+        Self::UdpShort,
+        Self::UdpLength,
+        Self::UdpChecksum,
+        Self::TcpShort,
+        Self::TcpDataOffset,
+        Self::TcpChecksum,
+    ];
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::EthRunt => "eth_runt",
+            Self::EthType => "eth_type",
+            Self::ArpShort => "arp_short",
+            Self::ArpFormat => "arp_format",
+            Self::IpShort => "ip_short",
+            Self::IpVersion => "ip_version",
+            Self::IpHeaderLen => "ip_ihl",
+            Self::IpTotalLen => "ip_total_len",
+            Self::IpChecksum => "ip_checksum",
+            Self::IpOptions => "ip_options",
+            Self::IpFragment => "ip_fragment",
+            Self::IpProto => "ip_proto",
+            Self::IgmpMalformed => "igmp_malformed",
+            Self::IcmpShort => "icmp_short",
+            Self::IcmpChecksum => "icmp_checksum",
+            Self::UdpShort => "udp_short",
+            Self::UdpLength => "udp_len",
+            Self::UdpChecksum => "udp_checksum",
+            Self::TcpShort => "tcp_short",
+            Self::TcpDataOffset => "tcp_data_offset",
+            Self::TcpChecksum => "tcp_checksum",
+        }
+    }
+
+    pub(super) const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+pub(super) fn be_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(array(bytes, offset)?))
+}
+
+pub(super) fn be_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(array(bytes, offset)?))
+}
+
+pub(super) fn array<const N: usize>(bytes: &[u8], offset: usize) -> Option<[u8; N]> {
+    bytes.get(offset..offset.checked_add(N)?)?.try_into().ok()
+}
+
+/// `bytes[start..end]`, or `None` instead of a panic when the range is inverted or too long.
+pub(super) fn range(bytes: &[u8], start: usize, end: usize) -> Option<&[u8]> {
+    bytes.get(start..end)
+}
+
+/// Walks the IPv4 option list: EOL ends it, NOP is one byte, every other option carries a
+/// length byte of at least 2 that must stay inside the header.
+pub(super) fn ipv4_options_valid(options: &[u8]) -> bool {
+    let mut index = 0;
+    while let Some(&kind) = options.get(index) {
+        match kind {
+            0 => return true,
+            1 => index += 1,
+            _ => match options.get(index + 1) {
+                Some(&len) if len >= 2 && index + usize::from(len) <= options.len() => {
+                    index += usize::from(len);
+                }
+                _ => return false,
+            },
+        }
+    }
+    true
+}
@@ -0,0 +1,329 @@
+// kernel/src/proc/executor.rs: static-slot async executor for kernel services polled from run_loop.
+use super::signal::{Signal, TERMINATE_MASK};
+use crate::arch::x86_64::stack;
+use crate::{serial, time};
+use alloc::boxed::Box;
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+const MAX_SERVICES: usize = 8;
+const NO_SERVICE: usize = usize::MAX;
+
+type ServiceFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+/// One bit per slot; set by wakers (which may fire from anywhere) and drained by `poll`.
+static READY: AtomicU32 = AtomicU32::new(0);
+/// Slot whose future is on the stack right now, so timers know whom to wake.
+static CURRENT: AtomicUsize = AtomicUsize::new(NO_SERVICE);
+
+struct Slot {
+    /// Drawn from the same counter as task pids, so `kill` addresses both.
+    pid: u32,
+    name: &'static str,
+    future: Option<ServiceFuture>,
+    wake_tick: Option<u64>,
+    polls: u64,
+    /// Pending signal bits; SIGTERM/SIGINT are observed through `terminate_requested`.
+    signals: u64,
+}
+
+impl Slot {
+    const fn empty() -> Self {
+        Self {
+            pid: 0,
+            name: "",
+            future: None,
+            wake_tick: None,
+            polls: 0,
+            signals: 0,
+        }
+    }
+}
+
+struct Executor {
+    slots: [Slot; MAX_SERVICES],
+    spawned: u64,
+    completed: u64,
+    rejected: u64,
+    killed: u64,
+}
+
+struct ExecutorCell(UnsafeCell<Executor>);
+
+// SAFETY: services are spawned and polled only from the single-threaded run loop and shell.
+unsafe impl Sync for ExecutorCell {}
+
+static EXECUTOR: ExecutorCell = ExecutorCell(UnsafeCell::new(Executor {
+    slots: [const { Slot::empty() }; MAX_SERVICES],
+    spawned: 0,
+    completed: 0,
+    rejected: 0,
+    killed: 0,
+}));
+
+/// Borrows must not span a future poll: the future itself may spawn or arm timers.
+fn with_executor<R>(f: impl FnOnce(&mut Executor) -> R) -> R {
+    // SAFETY: run-loop only; `poll` takes each future out of its slot before polling it.
+    unsafe { f(&mut *EXECUTOR.0.get()) }
+}
+
+const WAKER_VTABLE: RawWakerVTable =
+    RawWakerVTable::new(clone_waker, wake_slot, wake_slot, drop_waker);
+
+fn clone_waker(data: *const ()) -> RawWaker {
+    RawWaker::new(data, &WAKER_VTABLE)
+}
+
+fn wake_slot(data: *const ()) {
+    READY.fetch_or(1 << (data as usize), Ordering::AcqRel);
+}
+
+fn drop_waker(_: *const ()) {}
+
+fn slot_waker(index: usize) -> Waker {
+    // SAFETY: the vtable only treats `data` as a slot index and never dereferences it.
+    unsafe { Waker::from_raw(RawWaker::new(index as *const (), &WAKER_VTABLE)) }
+}
+
+/// Queues `future` in a free slot; it is first polled on the next run-loop pass.
+pub fn spawn(name: &'static str, future: impl Future<Output = ()> + 'static) -> bool {
+    let Some(index) = with_executor(|executor| {
+        // The slot being polled has its future on the stack, so it only looks vacant.
+        let current = CURRENT.load(Ordering::Relaxed);
+        let index = (0..MAX_SERVICES)
+            .find(|&index| index != current && executor.slots[index].future.is_none());
+        if index.is_none() {
+            executor.rejected = executor.rejected.saturating_add(1);
+        }
+        index
+    }) else {
+        return false;
+    };
+    let future: ServiceFuture = Box::pin(future);
+    let pid = super::alloc_pid();
+    with_executor(|executor| {
+        executor.slots[index] = Slot {
+            pid,
+            name,
+            future: Some(future),
+            wake_tick: None,
+            polls: 0,
+            signals: 0,
+        };
+        executor.spawned = executor.spawned.saturating_add(1);
+    });
+    READY.fetch_or(1 << index, Ordering::AcqRel);
+    true
+}
+
+/// Polls every woken service once: explicitly woken ones and those whose timer expired.
+pub fn poll(now_ticks: u64) {
+    let ready = READY.swap(0, Ordering::AcqRel);
+    for index in 0..MAX_SERVICES {
+        let Some((name, mut future)) = with_executor(|executor| {
+            let slot = &mut executor.slots[index];
+            let timer_due = slot.wake_tick.is_some_and(|tick| now_ticks >= tick);
+            if slot.future.is_none() || (ready & (1 << index) == 0 && !timer_due) {
+                return None;
+            }
+            slot.wake_tick = None;
+            slot.polls = slot.polls.saturating_add(1);
+            Some((slot.name, slot.future.take()?))
+        }) else {
+            continue;
+        };
+
+        CURRENT.store(index, Ordering::Relaxed);
+        let waker = slot_waker(index);
+        let owner = stack::enter(name);
+        let done = future
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_ready();
+        drop(owner);
+        CURRENT.store(NO_SERVICE, Ordering::Relaxed);
+
+        with_executor(|executor| {
+            let slot = &mut executor.slots[index];
+            if done {
+                slot.wake_tick = None;
+                executor.completed = executor.completed.saturating_add(1);
+            } else if slot.signals & Signal::Kill.bit() != 0 {
+                // SIGKILL sent while this future was on the stack; drop it now.
+                slot.wake_tick = None;
+                executor.killed = executor.killed.saturating_add(1);
+            } else {
+                slot.future = Some(future);
+            }
+        });
+    }
+}
+
+/// Whether the running service has a pending SIGTERM or SIGINT. Services check it at their
+/// wait points, usually through `unless_terminated`, and wind down instead of being killed.
+pub fn terminate_requested() -> bool {
+    let index = CURRENT.load(Ordering::Relaxed);
+    index != NO_SERVICE
+        && with_executor(|executor| executor.slots[index].signals & TERMINATE_MASK != 0)
+}
+
+/// Runs `future` unless the service is asked to terminate first; then resolves to `None`.
+pub fn unless_terminated<F: Future>(future: F) -> UnlessTerminated<F> {
+    UnlessTerminated { future }
+}
+
+pub struct UnlessTerminated<F> {
+    future: F,
+}
+
+impl<F: Future> Future for UnlessTerminated<F> {
+    type Output = Option<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if terminate_requested() {
+            return Poll::Ready(None);
+        }
+        // SAFETY: `future` is structurally pinned: it is never moved out of `self`.
+        let future = unsafe { self.map_unchecked_mut(|this| &mut this.future) };
+        future.poll(cx).map(Some)
+    }
+}
+
+/// Whether a service spawned as `name` has not finished.
+#[cfg(feature = "net")]
+pub fn is_running(name: &str) -> bool {
+    let current = CURRENT.load(Ordering::Relaxed);
+    with_executor(|executor| {
+        (0..MAX_SERVICES).any(|index| {
+            let slot = &executor.slots[index];
+            slot.name == name && (slot.future.is_some() || index == current)
+        })
+    })
+}
+
+/// Whether a live service has `pid`.
+pub(super) fn is_live(pid: u32) -> bool {
+    find_live(pid).is_some()
+}
+
+fn find_live(pid: u32) -> Option<usize> {
+    let current = CURRENT.load(Ordering::Relaxed);
+    with_executor(|executor| {
+        (0..MAX_SERVICES).find(|&index| {
+            let slot = &executor.slots[index];
+            slot.pid == pid && (slot.future.is_some() || index == current)
+        })
+    })
+}
+
+/// Queues `signal` for the service with `pid` and wakes it; SIGKILL drops its future at once
+/// (or when its current poll returns). `None` only checks that the service exists.
+pub(super) fn send_signal(pid: u32, signal: Option<Signal>) -> bool {
+    let Some(index) = find_live(pid) else {
+        return false;
+    };
+    let Some(signal) = signal else {
+        return true;
+    };
+    let current = CURRENT.load(Ordering::Relaxed);
+    let dropped = with_executor(|executor| {
+        let slot = &mut executor.slots[index];
+        slot.signals |= signal.bit();
+        if signal != Signal::Kill || index == current {
+            return None;
+        }
+        slot.wake_tick = None;
+        executor.killed = executor.killed.saturating_add(1);
+        slot.future.take()
+    });
+    // Dropped outside the borrow: the future's destructors may touch the executor.
+    drop(dropped);
+    READY.fetch_or(1 << index, Ordering::AcqRel);
+    true
+}
+
+/// Now when a service was woken, otherwise the earliest armed timer.
+pub fn next_deadline(now_ticks: u64) -> Option<u64> {
+    if READY.load(Ordering::Acquire) != 0 {
+        return Some(now_ticks);
+    }
+    with_executor(|executor| {
+        executor
+            .slots
+            .iter()
+            .filter(|slot| slot.future.is_some())
+            .filter_map(|slot| slot.wake_tick)
+            .min()
+            .map(|tick| tick.max(now_ticks))
+    })
+}
+
+fn arm_timer(tick: u64) {
+    let index = CURRENT.load(Ordering::Relaxed);
+    if index == NO_SERVICE {
+        return;
+    }
+    with_executor(|executor| {
+        let slot = &mut executor.slots[index];
+        slot.wake_tick = Some(slot.wake_tick.map_or(tick, |armed| armed.min(tick)));
+    });
+}
+
+/// Resolves once the PIT tick counter reaches `tick`.
+pub struct Sleep {
+    until_tick: u64,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        if time::ticks() >= self.until_tick {
+            return Poll::Ready(());
+        }
+        arm_timer(self.until_tick);
+        Poll::Pending
+    }
+}
+
+pub fn sleep_until(tick: u64) -> Sleep {
+    Sleep { until_tick: tick }
+}
+
+pub fn sleep_ticks(ticks: u64) -> Sleep {
+    sleep_until(time::ticks().saturating_add(ticks))
+}
+
+pub fn log_services() {
+    let ready = READY.load(Ordering::Acquire);
+    with_executor(|executor| {
+        serial::write_fmt(format_args!(
+            "svc: slots={} spawned={} completed={} rejected={} killed={}\n",
+            MAX_SERVICES, executor.spawned, executor.completed, executor.rejected, executor.killed
+        ));
+        for (index, slot) in executor.slots.iter().enumerate() {
+            if slot.future.is_none() {
+                continue;
+            }
+            if ready & (1 << index) != 0 {
+                serial::write_fmt(format_args!(
+                    "svc: slot={} pid={} name={} state=ready polls={} pending={:#x}\n",
+                    index, slot.pid, slot.name, slot.polls, slot.signals
+                ));
+            } else if let Some(tick) = slot.wake_tick {
+                serial::write_fmt(format_args!(
+                    "svc: slot={} pid={} name={} state=sleep until_tick={} polls={} pending={:#x}\n",
+                    index, slot.pid, slot.name, tick, slot.polls, slot.signals
+                ));
+            } else {
+                serial::write_fmt(format_args!(
+                    "svc: slot={} pid={} name={} state=waiting polls={} pending={:#x}\n",
+                    index, slot.pid, slot.name, slot.polls, slot.signals
+                ));
+            }
+        }
+    });
+}
@@ -1,25 +1,66 @@
 // kernel/src/proc/mod.rs: M4 cooperative scheduler and syscall dispatch (same address space).
-use crate::{net, serial, time};
+pub mod executor;
+pub mod signal;
+#[cfg(feature = "net")]
+mod socket;
+#[cfg(feature = "desktop")]
+mod surface;
+mod trace;
+mod usercopy;
+
+use crate::arch::x86_64::entropy;
+use crate::arch::x86_64::fpu::{self, FpuArea};
+use crate::arch::x86_64::stack;
+use crate::error::KernelError;
+use crate::evlog::{self, Event};
+#[cfg(feature = "desktop")]
+use crate::gfx;
+use crate::mem::userheap::{self, HeapSlot};
+use crate::serial::{self, Severity};
+use crate::{input_bus, time};
 use arrostd::abi::{USERLAND_ABI_REVISION, USERLAND_INIT_APP};
+use arrostd::args;
+use arrostd::heap::BrkHeap;
+use arrostd::rng::{self, TlsRng};
+#[cfg(feature = "net")]
+use arrostd::syscall::TCP_FDS;
 use arrostd::syscall::{
-    AF_INET, IPPROTO_UDP, SOCK_DGRAM, SYS_EXIT, SYS_READ, SYS_RECVFROM, SYS_SENDTO, SYS_SLEEP,
-    SYS_SOCKET, SYS_WRITE, SYS_YIELD, UDP_SOCKET_FD, UdpRecvReq, UdpSendReq,
+    AF_INET, BATCH_MAX_RECORDS, BATCH_STOP_ON_ERROR, Batch, INPUT_FD, INPUT_KEYS,
+    IP_ADD_MEMBERSHIP, IP_DROP_MEMBERSHIP, IPPROTO_TCP, IPPROTO_UDP, InputRecord, POLL_FOREVER,
+    POLL_MAX_FDS, POLLERR, POLLIN, POLLNVAL, POLLOUT, PollFd, SO_BROADCAST, SO_TIMESTAMP,
+    SOCK_DGRAM, SOCK_STREAM, SOL_SOCKET, SURFACE_ATTACH, SURFACE_CLOSE, SURFACE_DAMAGE,
+    SURFACE_DETACH, SURFACE_EVENT_CLOSE, SURFACE_EVENTS, SURFACE_OPEN, SURFACE_WINDOW_APP,
+    SYS_ACCEPT, SYS_BATCH, SYS_BIND, SYS_BRK, SYS_CLOSE, SYS_CONNECT, SYS_EXIT, SYS_INPUT_READ,
+    SYS_INPUT_SUBSCRIBE, SYS_KILL, SYS_LISTEN, SYS_POLL, SYS_READ, SYS_RECVFROM, SYS_SENDTO,
+    SYS_SETSOCKOPT, SYS_SIGPENDING, SYS_SLEEP, SYS_SOCKET, SYS_SURFACE, SYS_TIMER_SET, SYS_TLS_GET,
+    SYS_TLS_SET, SYS_WRITE, SYS_YIELD, SockAddrIn, SockOptReq, SurfaceDesc, SurfaceRect,
+    SyscallRecord, TIMER_FD, TLS_SLOT_ERRNO, TLS_SLOT_RNG, TLS_SLOTS, UDP_SOCKET_FD, UdpRecvReq,
+    UdpSendReq,
 };
 use core::cell::UnsafeCell;
 use core::hint::spin_loop;
 use core::mem::size_of;
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use signal::{Signal, TERMINATE_MASK};
+#[cfg(feature = "net")]
+use socket::{TcpFd, UdpSocketOptions};
+use trace::{TraceEvent, TraceRing};
+use usercopy::{AddressSpace, FaultInjection};
 
 const MAX_TASKS: usize = 4;
 const MAX_LINE_LEN: usize = 96;
 const MAX_WRITE_BYTES: usize = 256;
 const USER_SHELL_SCRIPT: &[u8] = b"";
+/// Entries in the sh `poll` demo's array: UDP socket, timer, input.
+const POLL_DEMO_FDS: usize = 3;
 
 struct SchedulerCell(UnsafeCell<Scheduler>);
 
 // SAFETY: access is serialized through `SCHED_LOCK`.
 unsafe impl Sync for SchedulerCell {}
 
+/// Shared by scheduler tasks and executor services, so a pid names exactly one of them.
+static NEXT_PID: AtomicU32 = AtomicU32::new(1);
 static SCHED_LOCK: SpinLock = SpinLock::new();
 static SCHEDULER: SchedulerCell = SchedulerCell(UnsafeCell::new(Scheduler::new()));
 
@@ -41,6 +82,30 @@ pub struct SyscallStats {
     pub socket: u64,
     pub sendto: u64,
     pub recvfrom: u64,
+    pub setsockopt: u64,
+    pub brk: u64,
+    pub kill: u64,
+    pub sigpending: u64,
+    pub tls_get: u64,
+    pub tls_set: u64,
+    pub input_subscribe: u64,
+    pub input_read: u64,
+    pub surface: u64,
+    pub poll: u64,
+    /// Polls that parked the task, and how many of those ended by timeout.
+    pub poll_waits: u64,
+    pub poll_timeouts: u64,
+    pub timer_set: u64,
+    pub connect: u64,
+    pub bind: u64,
+    pub listen: u64,
+    pub accept: u64,
+    pub close: u64,
+    pub batch: u64,
+    /// Records executed across all SYS_BATCH calls; the records also count in their own fields.
+    pub batch_records: u64,
+    pub batch_max: u64,
+    pub efault: u64,
     pub errors: u64,
 }
 
@@ -55,6 +120,28 @@ impl SyscallStats {
             socket: 0,
             sendto: 0,
             recvfrom: 0,
+            setsockopt: 0,
+            brk: 0,
+            kill: 0,
+            sigpending: 0,
+            tls_get: 0,
+            tls_set: 0,
+            input_subscribe: 0,
+            input_read: 0,
+            surface: 0,
+            poll: 0,
+            poll_waits: 0,
+            poll_timeouts: 0,
+            timer_set: 0,
+            connect: 0,
+            bind: 0,
+            listen: 0,
+            accept: 0,
+            close: 0,
+            batch: 0,
+            batch_records: 0,
+            batch_max: 0,
+            efault: 0,
             errors: 0,
         }
     }
@@ -69,8 +156,44 @@ enum TaskKind {
 #[derive(Clone, Copy)]
 enum TaskState {
     Ready,
-    Sleeping { until_tick: u64 },
-    Exited { code: i32 },
+    Sleeping {
+        until_tick: u64,
+    },
+    /// Parked in SYS_POLL on the `PollFd` array at `fds` until one is ready or `until_tick`.
+    Polling {
+        fds: u64,
+        nfds: usize,
+        until_tick: Option<u64>,
+    },
+    Exited {
+        code: i32,
+    },
+}
+
+/// Program break bookkeeping for the task's `userheap` slot; tasks without a slot get
+/// `ENOMEM` from SYS_BRK.
+#[derive(Clone, Copy)]
+struct TaskHeap {
+    slot: Option<HeapSlot>,
+    brk: u64,
+    peak: u64,
+}
+
+impl TaskHeap {
+    const EMPTY: Self = Self {
+        slot: None,
+        brk: 0,
+        peak: 0,
+    };
+
+    fn new(slot: Option<HeapSlot>) -> Self {
+        let brk = slot.map_or(0, |slot| slot.base);
+        Self {
+            slot,
+            brk,
+            peak: brk,
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -78,11 +201,75 @@ struct Task {
     pid: u32,
     name: &'static str,
     kind: TaskKind,
+    address_space: AddressSpace,
     state: TaskState,
     started: bool,
     step: u8,
     line: [u8; MAX_LINE_LEN],
     line_len: usize,
+    heap: TaskHeap,
+    arena: BrkHeap,
+    /// Pending signal bits, set by SYS_KILL and drained by SYS_SIGPENDING.
+    signals: u64,
+    /// SYS_TLS_GET/SYS_TLS_SET slots; `TLS_SLOT_RNG` is seeded at spawn.
+    tls: [u64; TLS_SLOTS as usize],
+    /// Pixel buffer of the sh `surface` demo, carved from the brk arena on first use.
+    surface: Option<DemoSurface>,
+    /// SYS_TIMER_SET timer behind `TIMER_FD`.
+    timer: TaskTimer,
+    /// `PollFd` array of the sh `poll` demo, in the brk arena so it outlives the step that
+    /// parked the task.
+    poll_fds: Option<u64>,
+    poll_waiting: bool,
+    /// SYS_SOCKET stream fds, `TCP_FD_FIRST + index`.
+    #[cfg(feature = "net")]
+    tcp_fds: [TcpFd; TCP_FDS as usize],
+    /// Source port of UDP sends that name none; drawn at the first one, 0 before.
+    #[cfg(feature = "net")]
+    udp_port: u16,
+}
+
+#[derive(Clone, Copy)]
+struct TaskTimer {
+    deadline: Option<u64>,
+    /// Re-arm period in ticks; 0 for a one-shot timer.
+    interval: u64,
+    expirations: u64,
+}
+
+impl TaskTimer {
+    const DISARMED: Self = Self {
+        deadline: None,
+        interval: 0,
+        expirations: 0,
+    };
+
+    /// Whether the timer has expired; reporting it consumes the expiration and re-arms a
+    /// periodic timer past `now_ticks`.
+    fn take_expired(&mut self, now_ticks: u64) -> bool {
+        let Some(deadline) = self.deadline else {
+            return false;
+        };
+        if now_ticks < deadline {
+            return false;
+        }
+        self.expirations = self.expirations.saturating_add(1);
+        self.deadline = (self.interval > 0).then(|| {
+            let missed = (now_ticks - deadline) / self.interval;
+            deadline + (missed + 1) * self.interval
+        });
+        true
+    }
+}
+
+#[derive(Clone, Copy)]
+struct DemoSurface {
+    pixels: u64,
+    width: u32,
+    height: u32,
+    /// Pixels the buffer can hold; a larger `surface <w> <h>` allocates a new one.
+    capacity: u64,
+    frame: u32,
 }
 
 impl Task {
@@ -91,11 +278,24 @@ impl Task {
             pid,
             name,
             kind,
+            address_space: AddressSpace::Shared,
             state: TaskState::Ready,
             started: false,
             step: 0,
             line: [0; MAX_LINE_LEN],
             line_len: 0,
+            heap: TaskHeap::EMPTY,
+            arena: BrkHeap::new(),
+            signals: 0,
+            tls: [0; TLS_SLOTS as usize],
+            surface: None,
+            timer: TaskTimer::DISARMED,
+            poll_fds: None,
+            poll_waiting: false,
+            #[cfg(feature = "net")]
+            tcp_fds: [TcpFd::Free; TCP_FDS as usize],
+            #[cfg(feature = "net")]
+            udp_port: 0,
         }
     }
 }
@@ -122,22 +322,34 @@ impl InputScript {
 
 struct Scheduler {
     initialized: bool,
-    next_pid: u32,
     cursor: usize,
     tasks: [Option<Task>; MAX_TASKS],
     stats: SyscallStats,
+    #[cfg(feature = "net")]
+    udp_options: UdpSocketOptions,
+    fault_injection: FaultInjection,
     input_script: InputScript,
+    /// x87/SSE state of the main loop (and the doom C code it runs) while a task is on the CPU.
+    kernel_fpu: FpuArea,
+    /// Per-slot task FPU state, reset to the clean state on spawn.
+    task_fpu: [FpuArea; MAX_TASKS],
+    trace: TraceRing,
 }
 
 impl Scheduler {
     const fn new() -> Self {
         Self {
             initialized: false,
-            next_pid: 1,
             cursor: 0,
             tasks: [None; MAX_TASKS],
             stats: SyscallStats::new(),
+            #[cfg(feature = "net")]
+            udp_options: UdpSocketOptions::new(),
+            fault_injection: FaultInjection::new(),
             input_script: InputScript::new(USER_SHELL_SCRIPT),
+            kernel_fpu: FpuArea::ZERO,
+            task_fpu: [FpuArea::ZERO; MAX_TASKS],
+            trace: TraceRing::new(),
         }
     }
 
@@ -175,8 +387,15 @@ impl Scheduler {
             if !matches!(task.state, TaskState::Ready) {
                 continue;
             }
+            self.trace.record(now_ticks, task.pid, TraceEvent::Run);
 
+            // Tasks are cooperative today, so this brackets each step; the same pair moves to
+            // the switch path once tasks can be preempted.
+            fpu::switch(&mut self.kernel_fpu, &self.task_fpu[index]);
+            let owner = stack::enter(task.name);
             self.run_task(&mut task, now_ticks);
+            drop(owner);
+            fpu::switch(&mut self.task_fpu[index], &self.kernel_fpu);
             self.tasks[index] = Some(task);
             return;
         }
@@ -196,6 +415,9 @@ impl Scheduler {
             self.sys_sleep(task, 25, now_ticks);
             return;
         }
+        if self.deliver_signals(task, now_ticks) {
+            return;
+        }
 
         match task.step {
             0 => {
@@ -228,6 +450,13 @@ impl Scheduler {
             self.sys_yield(task, now_ticks);
             return;
         }
+        if self.deliver_signals(task, now_ticks) {
+            return;
+        }
+        if task.poll_waiting {
+            task.poll_waiting = false;
+            self.report_poll(task, now_ticks);
+        }
 
         let mut byte = 0u8;
         let read = self.dispatch_syscall(
@@ -289,7 +518,8 @@ impl Scheduler {
             }
         };
 
-        if let Some((dst_ip, dst_port, payload)) = parse_send_command(command) {
+        let mut args_buf = [0u8; MAX_LINE_LEN];
+        if let Some((dst_ip, dst_port, payload)) = parse_send_command(command, &mut args_buf) {
             let request = UdpSendReq::new(
                 dst_ip,
                 dst_port,
@@ -311,19 +541,157 @@ impl Scheduler {
                     sent, dst_ip[0], dst_ip[1], dst_ip[2], dst_ip[3], dst_port
                 ));
             } else {
-                serial::write_fmt(format_args!("sh(send): failed rc={sent}\n"));
+                serial::write_fmt(format_args!(
+                    "sh(send): failed rc={sent} ({})\n",
+                    errno_name(sent)
+                ));
             }
             return;
         }
 
+        if let Some(tcp) = parse_tcp_command(command, &mut args_buf) {
+            self.run_tcp_command(task, tcp, now_ticks);
+            return;
+        }
+
+        if let Some((option, group)) = parse_membership_command(command) {
+            let request = SockOptReq::membership(option, group);
+            let rc = self.dispatch_syscall(
+                task,
+                now_ticks,
+                SYS_SETSOCKOPT,
+                UDP_SOCKET_FD,
+                core::ptr::addr_of!(request) as u64,
+                size_of::<SockOptReq>() as u64,
+            );
+            let verb = if option == IP_ADD_MEMBERSHIP {
+                "join"
+            } else {
+                "leave"
+            };
+            if rc == 0 {
+                serial::write_fmt(format_args!(
+                    "sh({verb}): {}.{}.{}.{}\n",
+                    group[0], group[1], group[2], group[3]
+                ));
+            } else {
+                serial::write_fmt(format_args!(
+                    "sh({verb}): failed rc={rc} ({})\n",
+                    errno_name(rc)
+                ));
+            }
+            return;
+        }
+
+        if let Some(args) = command.strip_prefix("kill ") {
+            let Some((pid, number)) = parse_kill_args(args) else {
+                self.sys_write(
+                    task,
+                    "sh(kill): usage kill <pid> [INT|TERM|KILL|0]\n",
+                    now_ticks,
+                );
+                return;
+            };
+            self.run_kill(task, pid, number, now_ticks);
+            return;
+        }
+
+        if let Some(args) = command.strip_prefix("tls set ") {
+            let mut parts = args.split_whitespace();
+            let (Some(Ok(slot)), Some(Ok(value)), None) = (
+                parts.next().map(str::parse::<u64>),
+                parts.next().map(str::parse::<u64>),
+                parts.next(),
+            ) else {
+                self.sys_write(task, "sh(tls): usage tls set <slot> <value>\n", now_ticks);
+                return;
+            };
+            let rc = self.dispatch_syscall(task, now_ticks, SYS_TLS_SET, slot, value, 0);
+            if rc == 0 {
+                serial::write_fmt(format_args!("sh(tls): slot {slot} = {value:#x}\n"));
+            } else {
+                serial::write_fmt(format_args!(
+                    "sh(tls): set failed rc={rc} ({})\n",
+                    errno_name(rc)
+                ));
+            }
+            return;
+        }
+
+        if let Some(size) = command.strip_prefix("heap ") {
+            let Ok(size) = size.trim().parse::<u64>() else {
+                self.sys_write(task, "sh(heap): usage heap [bytes]\n", now_ticks);
+                return;
+            };
+            self.run_heap_alloc(task, size, now_ticks);
+            return;
+        }
+
+        if let Some(args) = command
+            .strip_prefix("poll")
+            .filter(|args| args.is_empty() || args.starts_with(' '))
+        {
+            let mut parts = args.split_whitespace().peekable();
+            let input = parts.next_if_eq(&"input").is_some();
+            let timeout = match (parts.next(), parts.next()) {
+                (None, _) => Some(500),
+                (Some(ticks), None) => ticks.parse::<u64>().ok(),
+                _ => None,
+            };
+            match timeout {
+                Some(timeout) => self.run_poll(task, input, timeout, now_ticks),
+                None => self.sys_write(task, "sh(poll): usage poll [input] [ticks]\n", now_ticks),
+            }
+            return;
+        }
+
+        if let Some(args) = command.strip_prefix("timer ") {
+            let mut parts = args.split_whitespace();
+            let (Some(Ok(delay)), interval, None) = (
+                parts.next().map(str::parse::<u64>),
+                parts.next().map(str::parse::<u64>),
+                parts.next(),
+            ) else {
+                self.sys_write(
+                    task,
+                    "sh(timer): usage timer <ticks> [interval]\n",
+                    now_ticks,
+                );
+                return;
+            };
+            let Ok(interval) = interval.unwrap_or(Ok(0)) else {
+                self.sys_write(
+                    task,
+                    "sh(timer): usage timer <ticks> [interval]\n",
+                    now_ticks,
+                );
+                return;
+            };
+            let _ = self.dispatch_syscall(task, now_ticks, SYS_TIMER_SET, delay, interval, 0);
+            if delay == 0 {
+                self.sys_write(task, "sh(timer): disarmed\n", now_ticks);
+            } else {
+                serial::write_fmt(format_args!(
+                    "sh(timer): armed delay={delay} interval={interval} ticks\n"
+                ));
+            }
+            return;
+        }
+
+        if let Some(surface) = parse_surface_command(command) {
+            self.run_surface_command(task, surface, now_ticks);
+            return;
+        }
+
         match command {
             "help" => {
                 self.sys_write(
                     task,
-                    "sh(help): help | uptime | user | socket | send <ip> <port> <text> | recv\n",
+                    "sh(help): help | uptime | user | socket | broadcast on|off | timestamp on|off | join <group> | leave <group> | send <ip> <port> <text> | recv | tcp connect <ip> <port> | tcp listen <port> | tcp accept|recv|close <fd> | tcp send <fd> <text> | heap [bytes] | batch | timer <ticks> [interval] | poll [input] [ticks] | surface [w h] | surface step|events|detach|close | kill <pid> [signal] | tls | tls set <slot> <value>\n",
                     now_ticks,
                 );
             }
+            "tls" => self.run_tls(task, now_ticks),
             "uptime" => {
                 serial::write_fmt(format_args!(
                     "sh(uptime): {} ms ({} ticks)\n",
@@ -349,9 +717,55 @@ impl Scheduler {
                 if fd >= 0 {
                     serial::write_fmt(format_args!("sh(socket): fd={fd}\n"));
                 } else {
-                    serial::write_fmt(format_args!("sh(socket): failed rc={fd}\n"));
+                    serial::write_fmt(format_args!(
+                        "sh(socket): failed rc={fd} ({})\n",
+                        errno_name(fd)
+                    ));
+                }
+            }
+            "broadcast on" | "broadcast off" | "timestamp on" | "timestamp off" => {
+                let option = if command.starts_with("broadcast") {
+                    SO_BROADCAST
+                } else {
+                    SO_TIMESTAMP
+                };
+                let request =
+                    SockOptReq::new(SOL_SOCKET, option, u64::from(command.ends_with("on")));
+                let rc = self.dispatch_syscall(
+                    task,
+                    now_ticks,
+                    SYS_SETSOCKOPT,
+                    UDP_SOCKET_FD,
+                    core::ptr::addr_of!(request) as u64,
+                    size_of::<SockOptReq>() as u64,
+                );
+                let name = if option == SO_BROADCAST {
+                    "broadcast"
+                } else {
+                    "timestamp"
+                };
+                if rc == 0 {
+                    #[cfg(feature = "net")]
+                    serial::write_fmt(format_args!(
+                        "sh({name}): so_broadcast={} so_timestamp={}\n",
+                        self.udp_options.broadcast, self.udp_options.timestamp
+                    ));
+                } else {
+                    serial::write_fmt(format_args!(
+                        "sh({name}): failed rc={rc} ({})\n",
+                        errno_name(rc)
+                    ));
                 }
             }
+            "batch" => self.run_batch_demo(task, now_ticks),
+            "heap" => {
+                let brk = self.dispatch_syscall(task, now_ticks, SYS_BRK, 0, 0, 0);
+                serial::write_fmt(format_args!(
+                    "sh(heap): brk={:#x} arena_used={}\n",
+                    brk,
+                    task.arena.used()
+                ));
+            }
             "recv" => {
                 let mut payload = [0u8; 128];
                 let mut request =
@@ -368,19 +782,23 @@ impl Scheduler {
                     let used = (received as usize).min(payload.len());
                     let text = core::str::from_utf8(&payload[..used]).unwrap_or("<binary>");
                     serial::write_fmt(format_args!(
-                        "sh(recv): {} bytes from {}.{}.{}.{}:{} -> `{}`\n",
+                        "sh(recv): {} bytes from {}.{}.{}.{}:{} rx_tick={} -> `{}`\n",
                         received,
                         request.src_ip[0],
                         request.src_ip[1],
                         request.src_ip[2],
                         request.src_ip[3],
                         request.src_port,
+                        request.rx_tick,
                         text
                     ));
                 } else if received == 0 {
                     self.sys_write(task, "sh(recv): no udp data\n", now_ticks);
                 } else {
-                    serial::write_fmt(format_args!("sh(recv): failed rc={received}\n"));
+                    serial::write_fmt(format_args!(
+                        "sh(recv): failed rc={received} ({})\n",
+                        errno_name(received)
+                    ));
                 }
             }
             "" => {}
@@ -390,6 +808,544 @@ impl Scheduler {
         }
     }
 
+    /// Issues write + brk query + yield as one SYS_BATCH, the shape of a per-frame update.
+    fn run_batch_demo(&mut self, task: &mut Task, now_ticks: u64) {
+        let text = "sh(batch): write from inside a batch\n";
+        let mut batch = Batch::new();
+        let _ = batch.push(SYS_WRITE, text.as_ptr() as u64, text.len() as u64, 0);
+        let _ = batch.push(SYS_BRK, 0, 0, 0);
+        let _ = batch.push(SYS_YIELD, 0, 0, 0);
+        let ran = batch.submit(BATCH_STOP_ON_ERROR, |number, arg0, arg1, arg2| {
+            self.dispatch_syscall(task, now_ticks, number, arg0, arg1, arg2)
+        });
+        if ran < 0 {
+            serial::write_fmt(format_args!(
+                "sh(batch): failed rc={ran} ({})\n",
+                errno_name(ran)
+            ));
+            return;
+        }
+        serial::write_fmt(format_args!(
+            "sh(batch): ran={}/{} write={} brk={:#x} yield={}\n",
+            ran,
+            batch.len(),
+            batch.result(0).unwrap_or(0),
+            batch.result(1).unwrap_or(0),
+            batch.result(2).unwrap_or(0)
+        ));
+    }
+
+    /// `poll [input] [ticks]`: waits on the UDP socket and the task's timer (and key input
+    /// with `input`) through SYS_POLL. The array lives in the brk arena, since the task is
+    /// parked after this step returns and the kernel fills it in when it wakes.
+    fn run_poll(&mut self, task: &mut Task, input: bool, timeout: u64, now_ticks: u64) {
+        let fds = match task.poll_fds {
+            Some(fds) => Some(fds),
+            None => {
+                let mut arena = task.arena;
+                let fds = arena.alloc((POLL_DEMO_FDS * size_of::<PollFd>()) as u64, 8, |addr| {
+                    self.dispatch_syscall(task, now_ticks, SYS_BRK, addr, 0, 0)
+                });
+                task.arena = arena;
+                fds
+            }
+        };
+        let Some(fds) = fds else {
+            self.sys_write(task, "sh(poll): no memory for the fd array\n", now_ticks);
+            return;
+        };
+        task.poll_fds = Some(fds);
+        if input {
+            let rc = self.dispatch_syscall(
+                task,
+                now_ticks,
+                SYS_INPUT_SUBSCRIBE,
+                u64::from(INPUT_KEYS),
+                10,
+                0,
+            );
+            if rc < 0 {
+                serial::write_fmt(format_args!(
+                    "sh(poll): input subscribe failed rc={rc} ({})\n",
+                    errno_name(rc)
+                ));
+                return;
+            }
+        }
+        let entries = [
+            PollFd::new(UDP_SOCKET_FD, POLLIN),
+            PollFd::new(TIMER_FD, POLLIN),
+            if input {
+                PollFd::new(INPUT_FD, POLLIN)
+            } else {
+                PollFd::new(0, 0)
+            },
+        ];
+        let nfds = if input { 3 } else { 2 };
+        // SAFETY: the arena range holds `POLL_DEMO_FDS` entries below the granted break.
+        unsafe {
+            core::ptr::copy_nonoverlapping(entries.as_ptr(), fds as *mut PollFd, POLL_DEMO_FDS);
+        }
+        let rc = self.dispatch_syscall(task, now_ticks, SYS_POLL, fds, nfds as u64, timeout);
+        if rc < 0 {
+            serial::write_fmt(format_args!(
+                "sh(poll): failed rc={rc} ({})\n",
+                errno_name(rc)
+            ));
+        } else if matches!(task.state, TaskState::Polling { .. }) {
+            task.poll_waiting = true;
+            serial::write_fmt(format_args!(
+                "sh(poll): waiting nfds={nfds} timeout={timeout} ticks\n"
+            ));
+        } else {
+            self.report_poll(task, now_ticks);
+        }
+    }
+
+    /// Prints the `revents` of the last `poll` and drops its input subscription, if any.
+    fn report_poll(&mut self, task: &mut Task, now_ticks: u64) {
+        let Some(fds) = task.poll_fds else {
+            return;
+        };
+        serial::write_str("sh(poll):");
+        let mut ready = 0;
+        let mut input = false;
+        for index in 0..POLL_DEMO_FDS {
+            // SAFETY: `fds` holds `POLL_DEMO_FDS` entries in the task's arena.
+            let entry = unsafe { (fds as *const PollFd).add(index).read_unaligned() };
+            let name = match u64::from(entry.fd) {
+                UDP_SOCKET_FD => "udp",
+                TIMER_FD => "timer",
+                INPUT_FD => "input",
+                _ => continue,
+            };
+            if name == "input" {
+                input = true;
+            }
+            if entry.revents != 0 {
+                ready += 1;
+            }
+            serial::write_fmt(format_args!(" {name}={}", poll_events_name(entry.revents)));
+        }
+        serial::write_fmt(format_args!(" ready={ready}\n"));
+        if input {
+            let _ = self.dispatch_syscall(task, now_ticks, SYS_INPUT_SUBSCRIBE, 0, 0, 0);
+        }
+    }
+
+    /// Drives a stream fd through the TCP syscalls, one step per command: `tcp connect`,
+    /// `tcp send` and `tcp recv` against an echo server make the echo client.
+    fn run_tcp_command(&mut self, task: &mut Task, command: TcpCommand<'_>, now_ticks: u64) {
+        let (verb, rc) = match command {
+            TcpCommand::Connect { ip, port } => {
+                let addr = SockAddrIn::new(ip, port);
+                let rc = self.open_tcp_fd(task, SYS_CONNECT, &addr, now_ticks);
+                if rc >= 0 {
+                    serial::write_fmt(format_args!(
+                        "sh(tcp): fd={rc} connecting to {}.{}.{}.{}:{port}\n",
+                        ip[0], ip[1], ip[2], ip[3]
+                    ));
+                    return;
+                }
+                ("connect", rc)
+            }
+            TcpCommand::Listen { port } => {
+                let addr = SockAddrIn::new([0; 4], port);
+                let rc = self.open_tcp_fd(task, SYS_LISTEN, &addr, now_ticks);
+                if rc >= 0 {
+                    serial::write_fmt(format_args!("sh(tcp): fd={rc} listening on {port}\n"));
+                    return;
+                }
+                ("listen", rc)
+            }
+            TcpCommand::Accept { fd } => {
+                let mut addr = SockAddrIn::new([0; 4], 0);
+                let rc = self.dispatch_syscall(
+                    task,
+                    now_ticks,
+                    SYS_ACCEPT,
+                    fd,
+                    core::ptr::addr_of_mut!(addr) as u64,
+                    size_of::<SockAddrIn>() as u64,
+                );
+                if rc >= 0 {
+                    serial::write_fmt(format_args!(
+                        "sh(tcp): fd={rc} accepted from {}.{}.{}.{}:{}\n",
+                        addr.ip[0], addr.ip[1], addr.ip[2], addr.ip[3], addr.port
+                    ));
+                    return;
+                }
+                if rc == KernelError::WouldBlock.syscall_return() {
+                    return self.sys_write(task, "sh(tcp): no connection waiting\n", now_ticks);
+                }
+                ("accept", rc)
+            }
+            TcpCommand::Send { fd, text } => {
+                let mut line = [0u8; MAX_LINE_LEN + 1];
+                let len = text.len().min(MAX_LINE_LEN);
+                line[..len].copy_from_slice(&text.as_bytes()[..len]);
+                line[len] = b'\n';
+                let rc = self.dispatch_syscall(
+                    task,
+                    now_ticks,
+                    SYS_SENDTO,
+                    fd,
+                    line.as_ptr() as u64,
+                    len as u64 + 1,
+                );
+                if rc >= 0 {
+                    serial::write_fmt(format_args!("sh(tcp): sent={rc} on fd={fd}\n"));
+                    return;
+                }
+                ("send", rc)
+            }
+            TcpCommand::Recv { fd } => {
+                let mut payload = [0u8; 128];
+                let rc = self.dispatch_syscall(
+                    task,
+                    now_ticks,
+                    SYS_RECVFROM,
+                    fd,
+                    payload.as_mut_ptr() as u64,
+                    payload.len() as u64,
+                );
+                if rc > 0 {
+                    let used = (rc as usize).min(payload.len());
+                    let text = core::str::from_utf8(&payload[..used]).unwrap_or("<binary>");
+                    serial::write_fmt(format_args!(
+                        "sh(tcp): {rc} bytes on fd={fd} -> `{}`\n",
+                        text.trim_end()
+                    ));
+                    return;
+                }
+                if rc == 0 {
+                    serial::write_fmt(format_args!("sh(tcp): fd={fd} closed by peer\n"));
+                    return;
+                }
+                if rc == KernelError::WouldBlock.syscall_return() {
+                    return self.sys_write(task, "sh(tcp): no tcp data\n", now_ticks);
+                }
+                ("recv", rc)
+            }
+            TcpCommand::Close { fd } => {
+                let rc = self.dispatch_syscall(task, now_ticks, SYS_CLOSE, fd, 0, 0);
+                if rc == 0 {
+                    serial::write_fmt(format_args!("sh(tcp): fd={fd} closed\n"));
+                    return;
+                }
+                ("close", rc)
+            }
+            TcpCommand::Usage => {
+                return self.sys_write(
+                    task,
+                    "sh(tcp): usage tcp connect <ip> <port> | tcp listen <port> | tcp accept|recv|close <fd> | tcp send <fd> <text>\n",
+                    now_ticks,
+                );
+            }
+        };
+        serial::write_fmt(format_args!(
+            "sh(tcp): {verb} failed rc={rc} ({})\n",
+            errno_name(rc)
+        ));
+    }
+
+    /// socket + connect to `addr`, or socket + bind + listen on its port for `SYS_LISTEN`.
+    /// Returns the fd; on failure the fd is closed again and the failing return comes back.
+    fn open_tcp_fd(
+        &mut self,
+        task: &mut Task,
+        op: u64,
+        addr: &SockAddrIn,
+        now_ticks: u64,
+    ) -> isize {
+        let fd = self.dispatch_syscall(
+            task,
+            now_ticks,
+            SYS_SOCKET,
+            AF_INET,
+            SOCK_STREAM,
+            IPPROTO_TCP,
+        );
+        if fd < 0 {
+            return fd;
+        }
+        let addr_ptr = core::ptr::addr_of!(*addr) as u64;
+        let addr_len = size_of::<SockAddrIn>() as u64;
+        let rc = if op == SYS_CONNECT {
+            self.dispatch_syscall(task, now_ticks, SYS_CONNECT, fd as u64, addr_ptr, addr_len)
+        } else {
+            match self.dispatch_syscall(task, now_ticks, SYS_BIND, fd as u64, addr_ptr, addr_len) {
+                0 => self.dispatch_syscall(task, now_ticks, SYS_LISTEN, fd as u64, 0, 0),
+                rc => rc,
+            }
+        };
+        if rc < 0 {
+            let _ = self.dispatch_syscall(task, now_ticks, SYS_CLOSE, fd as u64, 0, 0);
+            return rc;
+        }
+        fd
+    }
+
+    /// `surface [w h]` opens the app window and attaches a gradient drawn in the brk arena;
+    /// `surface step` moves a block across it and damages only the two rects that changed;
+    /// `surface events` takes a close request.
+    fn run_surface_command(&mut self, task: &mut Task, command: SurfaceCommand, now_ticks: u64) {
+        let (op, verb) = match command {
+            SurfaceCommand::Attach { width, height } => {
+                return self.run_surface_attach(task, width, height, now_ticks);
+            }
+            SurfaceCommand::Step => return self.run_surface_step(task, now_ticks),
+            SurfaceCommand::Events => return self.run_surface_events(task, now_ticks),
+            SurfaceCommand::Detach => (SURFACE_DETACH, "detach"),
+            SurfaceCommand::Close => (SURFACE_CLOSE, "close"),
+            SurfaceCommand::Usage => {
+                return self.sys_write(
+                    task,
+                    "sh(surface): usage surface [w h] | surface step|events|detach|close\n",
+                    now_ticks,
+                );
+            }
+        };
+        let rc = self.dispatch_syscall(
+            task,
+            now_ticks,
+            SYS_SURFACE,
+            op,
+            u64::from(SURFACE_WINDOW_APP),
+            0,
+        );
+        if rc == 0 {
+            serial::write_fmt(format_args!("sh(surface): {verb} ok\n"));
+        } else {
+            serial::write_fmt(format_args!(
+                "sh(surface): {verb} failed rc={rc} ({})\n",
+                errno_name(rc)
+            ));
+        }
+    }
+
+    /// Takes a close request from the app window's close button. The sh task does not decide
+    /// by itself: `surface close` accepts it, anything else keeps the window.
+    fn run_surface_events(&mut self, task: &mut Task, now_ticks: u64) {
+        let rc = self.dispatch_syscall(
+            task,
+            now_ticks,
+            SYS_SURFACE,
+            SURFACE_EVENTS,
+            u64::from(SURFACE_WINDOW_APP),
+            0,
+        );
+        if rc < 0 {
+            serial::write_fmt(format_args!(
+                "sh(surface): events failed rc={rc} ({})\n",
+                errno_name(rc)
+            ));
+        } else if rc as u64 & SURFACE_EVENT_CLOSE != 0 {
+            serial::write_line(
+                "sh(surface): close requested (surface close accepts, keeping the window vetoes)",
+            );
+        } else {
+            serial::write_line("sh(surface): no events");
+        }
+    }
+
+    fn run_surface_attach(&mut self, task: &mut Task, width: u32, height: u32, now_ticks: u64) {
+        let pixels = u64::from(width) * u64::from(height);
+        let buffer = match task.surface {
+            Some(surface) if surface.capacity >= pixels => Some(surface.pixels),
+            _ => {
+                let mut arena = task.arena;
+                let addr = arena.alloc(pixels * 4, 16, |addr| {
+                    self.dispatch_syscall(task, now_ticks, SYS_BRK, addr, 0, 0)
+                });
+                task.arena = arena;
+                addr
+            }
+        };
+        let Some(buffer) = buffer else {
+            serial::write_fmt(format_args!(
+                "sh(surface): no memory for {width}x{height} (brk={:#x})\n",
+                task.heap.brk
+            ));
+            return;
+        };
+        let capacity = match task.surface {
+            Some(surface) if surface.pixels == buffer => surface.capacity,
+            _ => pixels,
+        };
+        let surface = DemoSurface {
+            pixels: buffer,
+            width,
+            height,
+            capacity,
+            frame: 0,
+        };
+        task.surface = Some(surface);
+        for y in 0..height {
+            for x in 0..width {
+                surface.write(x, y, gradient(x, y, width, height));
+            }
+        }
+
+        let window = self.dispatch_syscall(task, now_ticks, SYS_SURFACE, SURFACE_OPEN, 0, 0);
+        if window < 0 {
+            serial::write_fmt(format_args!(
+                "sh(surface): open failed rc={window} ({})\n",
+                errno_name(window)
+            ));
+            return;
+        }
+        let desc = SurfaceDesc::new(window as u32, width, height, buffer);
+        let rc = self.dispatch_syscall(
+            task,
+            now_ticks,
+            SYS_SURFACE,
+            SURFACE_ATTACH,
+            core::ptr::addr_of!(desc) as u64,
+            size_of::<SurfaceDesc>() as u64,
+        );
+        if rc == 0 {
+            serial::write_fmt(format_args!(
+                "sh(surface): window={window} attached {width}x{height} at {buffer:#x}\n"
+            ));
+        } else {
+            serial::write_fmt(format_args!(
+                "sh(surface): attach failed rc={rc} ({})\n",
+                errno_name(rc)
+            ));
+        }
+    }
+
+    fn run_surface_step(&mut self, task: &mut Task, now_ticks: u64) {
+        let Some(mut surface) = task.surface else {
+            self.sys_write(
+                task,
+                "sh(surface): no surface, run `surface` first\n",
+                now_ticks,
+            );
+            return;
+        };
+        let block = 16.min(surface.width).min(surface.height);
+        let columns = (surface.width / block).max(1);
+        let old = surface.frame % columns;
+        surface.frame = surface.frame.wrapping_add(1);
+        let new = surface.frame % columns;
+        task.surface = Some(surface);
+        let y0 = (surface.height - block) / 2;
+        let mut copied = 0;
+        for (column, colour) in [(old, None), (new, Some(0x00ff_ffff))] {
+            let x0 = column * block;
+            for y in y0..y0 + block {
+                for x in x0..x0 + block {
+                    let pixel =
+                        colour.unwrap_or_else(|| gradient(x, y, surface.width, surface.height));
+                    surface.write(x, y, pixel);
+                }
+            }
+            let rect = SurfaceRect::new(SURFACE_WINDOW_APP, x0, y0, block, block);
+            let rc = self.dispatch_syscall(
+                task,
+                now_ticks,
+                SYS_SURFACE,
+                SURFACE_DAMAGE,
+                core::ptr::addr_of!(rect) as u64,
+                size_of::<SurfaceRect>() as u64,
+            );
+            if rc < 0 {
+                serial::write_fmt(format_args!(
+                    "sh(surface): damage failed rc={rc} ({})\n",
+                    errno_name(rc)
+                ));
+                return;
+            }
+            copied += rc;
+        }
+        serial::write_fmt(format_args!(
+            "sh(surface): step frame={} block={block} damaged_px={copied}\n",
+            surface.frame
+        ));
+    }
+
+    fn run_kill(&mut self, task: &mut Task, pid: u64, number: u64, now_ticks: u64) {
+        let rc = self.dispatch_syscall(task, now_ticks, SYS_KILL, pid, number, 0);
+        if rc == 0 {
+            serial::write_fmt(format_args!("sh(kill): pid={pid} signal={number}\n"));
+        } else {
+            serial::write_fmt(format_args!(
+                "sh(kill): failed rc={rc} ({})\n",
+                errno_name(rc)
+            ));
+        }
+    }
+
+    /// Allocates `size` bytes from the task's brk arena and fills them, touching every page.
+    /// `tls`: the task's slots as read back through SYS_TLS_GET, then three draws from the
+    /// seeded RNG slot via `arrostd::rng::TlsRng`.
+    fn run_tls(&mut self, task: &mut Task, now_ticks: u64) {
+        serial::write_str("sh(tls):");
+        for slot in 0..TLS_SLOTS {
+            let mut value = 0u64;
+            let rc = self.dispatch_syscall(
+                task,
+                now_ticks,
+                SYS_TLS_GET,
+                slot,
+                core::ptr::addr_of_mut!(value) as u64,
+                0,
+            );
+            let label = match slot {
+                TLS_SLOT_RNG => "rng",
+                TLS_SLOT_ERRNO => "errno",
+                _ => "",
+            };
+            if rc < 0 {
+                serial::write_fmt(format_args!(" {slot}{label}=err({})", errno_name(rc)));
+            } else if label.is_empty() {
+                serial::write_fmt(format_args!(" {slot}={value:#x}"));
+            } else {
+                serial::write_fmt(format_args!(" {slot}:{label}={value:#x}"));
+            }
+        }
+        serial::write_line("");
+        serial::write_str("sh(tls): rng");
+        for _ in 0..3 {
+            match TlsRng::next_u64(|number, arg0, arg1, arg2| {
+                self.dispatch_syscall(task, now_ticks, number, arg0, arg1, arg2)
+            }) {
+                Ok(value) => serial::write_fmt(format_args!(" {value:#018x}")),
+                Err(rc) => {
+                    serial::write_fmt(format_args!(" failed rc={rc} ({})", errno_name(rc)));
+                    break;
+                }
+            }
+        }
+        serial::write_line("");
+    }
+
+    fn run_heap_alloc(&mut self, task: &mut Task, size: u64, now_ticks: u64) {
+        let mut arena = task.arena;
+        let addr = arena.alloc(size, 16, |addr| {
+            self.dispatch_syscall(task, now_ticks, SYS_BRK, addr, 0, 0)
+        });
+        task.arena = arena;
+        let Some(addr) = addr else {
+            serial::write_fmt(format_args!(
+                "sh(heap): alloc {size} bytes failed (brk={:#x})\n",
+                task.heap.brk
+            ));
+            return;
+        };
+        // SAFETY: the arena only hands out ranges below the break SYS_BRK granted.
+        unsafe {
+            core::ptr::write_bytes(addr as *mut u8, 0xa5, size as usize);
+        }
+        serial::write_fmt(format_args!(
+            "sh(heap): {size} bytes at {addr:#x} brk={:#x} arena_used={}\n",
+            task.heap.brk,
+            task.arena.used()
+        ));
+    }
+
     fn dispatch_syscall(
         &mut self,
         task: &mut Task,
@@ -399,18 +1355,37 @@ impl Scheduler {
         arg1: u64,
         arg2: u64,
     ) -> isize {
-        match number {
+        let rc = match number {
             SYS_WRITE => {
                 self.stats.write = self.stats.write.saturating_add(1);
                 self.syscall_write(task, arg0, arg1)
             }
             SYS_READ => {
                 self.stats.read = self.stats.read.saturating_add(1);
-                self.syscall_read(arg0, arg1)
+                self.syscall_read(task, arg0, arg1)
             }
             SYS_EXIT => {
                 self.stats.exit = self.stats.exit.saturating_add(1);
                 task.state = TaskState::Exited { code: arg0 as i32 };
+                input_bus::release_user(task.pid);
+                #[cfg(feature = "desktop")]
+                gfx::release_app_window(task.pid);
+                #[cfg(feature = "net")]
+                task.release_sockets();
+                self.trace
+                    .record(now_ticks, task.pid, TraceEvent::Exit { code: arg0 as i32 });
+                if arg0 as i32 != 0 {
+                    // Nothing waits for a task, so a failure is announced where the shell sees it.
+                    serial::write_severity_fmt(
+                        Severity::Warning,
+                        format_args!(
+                            "proc: pid={} name={} exited code={}\n",
+                            task.pid, task.name, arg0 as i32
+                        ),
+                    );
+                    #[cfg(feature = "audio")]
+                    let _ = crate::audio::alert();
+                }
                 0
             }
             SYS_YIELD => {
@@ -425,40 +1400,155 @@ impl Scheduler {
                 };
                 0
             }
+            #[cfg(feature = "net")]
             SYS_SOCKET => {
                 self.stats.socket = self.stats.socket.saturating_add(1);
-                self.syscall_socket(arg0, arg1, arg2)
+                self.syscall_socket(task, arg0, arg1, arg2)
             }
+            #[cfg(feature = "net")]
             SYS_SENDTO => {
                 self.stats.sendto = self.stats.sendto.saturating_add(1);
-                self.syscall_sendto(arg0, arg1, arg2)
+                self.syscall_sendto(task, arg0, arg1, arg2)
             }
+            #[cfg(feature = "net")]
             SYS_RECVFROM => {
                 self.stats.recvfrom = self.stats.recvfrom.saturating_add(1);
-                self.syscall_recvfrom(arg0, arg1, arg2)
+                self.syscall_recvfrom(task, arg0, arg1, arg2)
+            }
+            #[cfg(feature = "net")]
+            SYS_SETSOCKOPT => {
+                self.stats.setsockopt = self.stats.setsockopt.saturating_add(1);
+                self.syscall_setsockopt(task, arg0, arg1, arg2)
+            }
+            SYS_BRK => {
+                self.stats.brk = self.stats.brk.saturating_add(1);
+                self.syscall_brk(task, arg0)
+            }
+            SYS_BATCH => {
+                self.stats.batch = self.stats.batch.saturating_add(1);
+                self.syscall_batch(task, now_ticks, arg0, arg1, arg2)
+            }
+            SYS_KILL => {
+                self.stats.kill = self.stats.kill.saturating_add(1);
+                self.syscall_kill(task, now_ticks, arg0, arg1)
+            }
+            SYS_SIGPENDING => {
+                self.stats.sigpending = self.stats.sigpending.saturating_add(1);
+                let pending = task.signals;
+                task.signals = 0;
+                pending as isize
+            }
+            SYS_TLS_GET => {
+                self.stats.tls_get = self.stats.tls_get.saturating_add(1);
+                self.syscall_tls_get(task, arg0, arg1)
+            }
+            SYS_TLS_SET => {
+                self.stats.tls_set = self.stats.tls_set.saturating_add(1);
+                match usize::try_from(arg0)
+                    .ok()
+                    .and_then(|slot| task.tls.get_mut(slot))
+                {
+                    Some(slot) => {
+                        *slot = arg1;
+                        0
+                    }
+                    None => self.fail(KernelError::InvalidArgument),
+                }
+            }
+            SYS_INPUT_SUBSCRIBE => {
+                self.stats.input_subscribe = self.stats.input_subscribe.saturating_add(1);
+                let priority = arg1.min(u64::from(u8::MAX)) as u8;
+                if input_bus::subscribe_user(task.pid, arg0, priority) {
+                    0
+                } else {
+                    self.fail(KernelError::PermissionDenied)
+                }
+            }
+            SYS_INPUT_READ => {
+                self.stats.input_read = self.stats.input_read.saturating_add(1);
+                self.syscall_input_read(task, arg0, arg1)
+            }
+            #[cfg(feature = "desktop")]
+            SYS_SURFACE => {
+                self.stats.surface = self.stats.surface.saturating_add(1);
+                self.syscall_surface(task, arg0, arg1, arg2)
+            }
+            SYS_POLL => {
+                self.stats.poll = self.stats.poll.saturating_add(1);
+                self.syscall_poll(task, now_ticks, arg0, arg1, arg2)
+            }
+            SYS_TIMER_SET => {
+                self.stats.timer_set = self.stats.timer_set.saturating_add(1);
+                task.timer = TaskTimer {
+                    deadline: (arg0 != 0).then(|| now_ticks.saturating_add(arg0)),
+                    interval: if arg0 != 0 { arg1 } else { 0 },
+                    expirations: task.timer.expirations,
+                };
+                0
+            }
+            #[cfg(feature = "net")]
+            SYS_CONNECT => {
+                self.stats.connect = self.stats.connect.saturating_add(1);
+                self.syscall_connect(task, arg0, arg1, arg2)
+            }
+            #[cfg(feature = "net")]
+            SYS_BIND => {
+                self.stats.bind = self.stats.bind.saturating_add(1);
+                self.syscall_bind(task, arg0, arg1, arg2)
+            }
+            #[cfg(feature = "net")]
+            SYS_LISTEN => {
+                self.stats.listen = self.stats.listen.saturating_add(1);
+                self.syscall_listen(task, arg0)
+            }
+            #[cfg(feature = "net")]
+            SYS_ACCEPT => {
+                self.stats.accept = self.stats.accept.saturating_add(1);
+                self.syscall_accept(task, arg0, arg1, arg2)
+            }
+            #[cfg(feature = "net")]
+            SYS_CLOSE => {
+                self.stats.close = self.stats.close.saturating_add(1);
+                self.syscall_close(task, arg0)
             }
             _ => {
-                self.stats.errors = self.stats.errors.saturating_add(1);
                 serial::write_fmt(format_args!(
-                    "syscall: pid={} name={} number={} ({}) -> ENOSYS\n",
+                    "syscall: pid={} name={} number={} ({}) -> {}\n",
                     task.pid,
                     task.name,
                     number,
-                    arrostd::syscall::name(number)
+                    arrostd::syscall::name(number),
+                    KernelError::NoSys.name()
                 ));
-                -38
+                self.fail(KernelError::NoSys)
             }
-        }
+        };
+        evlog::record(
+            Event::Syscall,
+            task.pid,
+            (number as u32) << 16 | u32::from(rc as u16),
+        );
+        self.trace.record(
+            now_ticks,
+            task.pid,
+            TraceEvent::Syscall {
+                number: number as u16,
+                rc: rc as i32,
+            },
+        );
+        rc
     }
 
-    fn syscall_write(&mut self, _task: &Task, ptr: u64, len: u64) -> isize {
+    fn syscall_write(&mut self, task: &Task, ptr: u64, len: u64) -> isize {
         let len = len as usize;
         if ptr == 0 || len > MAX_WRITE_BYTES {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -22;
+            return self.fail(KernelError::InvalidArgument);
+        }
+        if let Err(rc) = self.check_user_range(task, SYS_WRITE, ptr, len, false) {
+            return rc;
         }
 
-        // SAFETY: M4 tasks run in the same address space and pass in-kernel pointers.
+        // SAFETY: the range was validated as mapped in the task's address space.
         let bytes = unsafe { core::slice::from_raw_parts(ptr as *const u8, len) };
         for byte in bytes {
             if *byte == b'\n' {
@@ -469,109 +1559,335 @@ impl Scheduler {
         len as isize
     }
 
-    fn syscall_read(&mut self, ptr: u64, len: u64) -> isize {
+    fn syscall_read(&mut self, task: &Task, ptr: u64, len: u64) -> isize {
         if ptr == 0 || len == 0 {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -22;
+            return self.fail(KernelError::InvalidArgument);
+        }
+        if let Err(rc) = self.check_user_range(task, SYS_READ, ptr, 1, true) {
+            return rc;
         }
 
         let Some(byte) = self.input_script.next_byte() else {
             return 0;
         };
 
-        // SAFETY: `ptr` is provided by in-kernel task and points to writable memory.
+        // SAFETY: `ptr` was validated as a writable byte in the task's address space.
         unsafe {
             (ptr as *mut u8).write(byte);
         }
         1
     }
 
-    fn syscall_socket(&mut self, domain: u64, socket_type: u64, protocol: u64) -> isize {
-        if domain != AF_INET || socket_type != SOCK_DGRAM {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -97;
+    fn syscall_input_read(&mut self, task: &Task, ptr: u64, len: u64) -> isize {
+        if ptr == 0 || len != size_of::<InputRecord>() as u64 {
+            return self.fail(KernelError::InvalidArgument);
         }
-        if protocol != 0 && protocol != IPPROTO_UDP {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -93;
+        if let Err(rc) =
+            self.check_user_range(task, SYS_INPUT_READ, ptr, size_of::<InputRecord>(), true)
+        {
+            return rc;
+        }
+        match input_bus::read_user(task.pid) {
+            Ok(Some(record)) => {
+                // SAFETY: the record range was validated as writable in the task's address space.
+                unsafe {
+                    (ptr as *mut InputRecord).write_unaligned(record);
+                }
+                1
+            }
+            Ok(None) => 0,
+            Err(err) => self.fail(err),
         }
-        UDP_SOCKET_FD as isize
     }
 
-    fn syscall_sendto(&mut self, fd: u64, req_ptr: u64, req_len: u64) -> isize {
-        if fd != UDP_SOCKET_FD {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -9;
+    /// `poll(fds, nfds, timeout)`: fills each `PollFd::revents` and returns how many are
+    /// non-zero. With none ready and a non-zero timeout (in ticks) the task is parked instead,
+    /// like SYS_SLEEP: the call returns 0 and the task runs again once an fd is ready or the
+    /// timeout passes, with `revents` filled in by then.
+    fn syscall_poll(
+        &mut self,
+        task: &mut Task,
+        now_ticks: u64,
+        fds: u64,
+        nfds: u64,
+        timeout: u64,
+    ) -> isize {
+        let Ok(nfds) = usize::try_from(nfds) else {
+            return self.fail(KernelError::InvalidArgument);
+        };
+        if nfds > POLL_MAX_FDS || (nfds > 0 && fds == 0) {
+            return self.fail(KernelError::InvalidArgument);
         }
-        if req_ptr == 0 || req_len != size_of::<UdpSendReq>() as u64 {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -22;
+        if nfds > 0
+            && let Err(rc) =
+                self.check_user_range(task, SYS_POLL, fds, nfds * size_of::<PollFd>(), true)
+        {
+            return rc;
         }
+        let ready = poll_scan(task, fds, nfds, now_ticks);
+        if ready > 0 || timeout == 0 {
+            return ready as isize;
+        }
+        self.stats.poll_waits = self.stats.poll_waits.saturating_add(1);
+        task.state = TaskState::Polling {
+            fds,
+            nfds,
+            until_tick: (timeout != POLL_FOREVER).then(|| now_ticks.saturating_add(timeout)),
+        };
+        0
+    }
 
-        // SAFETY: M4/M7 cooperative tasks share the kernel address space.
-        let request = unsafe { (req_ptr as *const UdpSendReq).read() };
-        let Some(payload_len) = usize::try_from(request.payload_len).ok() else {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -22;
+    /// `tls_get(slot, out_ptr)`: writes the slot's value to `out_ptr`, since a full `u64` does
+    /// not fit a non-negative return.
+    fn syscall_tls_get(&mut self, task: &Task, slot: u64, out_ptr: u64) -> isize {
+        let Some(value) = usize::try_from(slot)
+            .ok()
+            .and_then(|slot| task.tls.get(slot))
+        else {
+            return self.fail(KernelError::InvalidArgument);
         };
-        if request.payload_ptr == 0 || payload_len == 0 {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -22;
+        if out_ptr == 0 {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        if let Err(rc) = self.check_user_range(task, SYS_TLS_GET, out_ptr, size_of::<u64>(), true) {
+            return rc;
+        }
+        // SAFETY: the range was validated as writable in the task's address space.
+        unsafe {
+            (out_ptr as *mut u64).write_unaligned(*value);
         }
+        0
+    }
 
-        // SAFETY: request payload pointer is validated by shared-address-space model.
-        let payload =
-            unsafe { core::slice::from_raw_parts(request.payload_ptr as *const u8, payload_len) };
-        match net::udp_send(request.dst_ip, request.dst_port, request.src_port, payload) {
-            Ok(sent) => sent as isize,
-            Err(err) => {
-                self.stats.errors = self.stats.errors.saturating_add(1);
-                map_net_error(err)
+    /// Moves the program break to `addr` inside the task's heap slot and returns the new break;
+    /// `addr == 0` only queries it. Pages past the break stay mapped, so growth re-zeroes them.
+    fn syscall_brk(&mut self, task: &mut Task, addr: u64) -> isize {
+        let Some(slot) = task.heap.slot else {
+            return self.fail(KernelError::OutOfMemory);
+        };
+        if addr == 0 {
+            return task.heap.brk as isize;
+        }
+        if addr < slot.base {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        if addr > slot.limit {
+            return self.fail(KernelError::OutOfMemory);
+        }
+        if addr > task.heap.brk {
+            // SAFETY: `brk..addr` lies inside the slot, which `mem::init` mapped writable.
+            unsafe {
+                core::ptr::write_bytes(
+                    task.heap.brk as *mut u8,
+                    0,
+                    (addr - task.heap.brk) as usize,
+                );
             }
         }
+        task.heap.brk = addr;
+        task.heap.peak = task.heap.peak.max(addr);
+        addr as isize
     }
 
-    fn syscall_recvfrom(&mut self, fd: u64, req_ptr: u64, req_len: u64) -> isize {
-        if fd != UDP_SOCKET_FD {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -9;
+    /// Runs `count` `SyscallRecord`s at `ptr` in order through `dispatch_syscall`, writing each
+    /// result back into its record, and returns how many ran. A run stops early once a record
+    /// exits or sleeps the task, or on the first error with `BATCH_STOP_ON_ERROR`. Nested
+    /// batches fail with `EINVAL`.
+    fn syscall_batch(
+        &mut self,
+        task: &mut Task,
+        now_ticks: u64,
+        ptr: u64,
+        count: u64,
+        flags: u64,
+    ) -> isize {
+        let Ok(count) = usize::try_from(count) else {
+            return self.fail(KernelError::InvalidArgument);
+        };
+        if ptr == 0 || count == 0 || count > BATCH_MAX_RECORDS || flags & !BATCH_STOP_ON_ERROR != 0
+        {
+            return self.fail(KernelError::InvalidArgument);
         }
-        if req_ptr == 0 || req_len != size_of::<UdpRecvReq>() as u64 {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -22;
+        if let Err(rc) = self.check_user_range(
+            task,
+            SYS_BATCH,
+            ptr,
+            count * size_of::<SyscallRecord>(),
+            true,
+        ) {
+            return rc;
         }
 
-        // SAFETY: M4/M7 cooperative tasks share the kernel address space.
-        let mut request = unsafe { (req_ptr as *const UdpRecvReq).read() };
-        let Some(payload_cap) = usize::try_from(request.payload_cap).ok() else {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -22;
-        };
-        if request.payload_ptr == 0 || payload_cap == 0 {
-            self.stats.errors = self.stats.errors.saturating_add(1);
-            return -22;
-        }
-
-        // SAFETY: request payload pointer is writable in shared address space.
-        let output =
-            unsafe { core::slice::from_raw_parts_mut(request.payload_ptr as *mut u8, payload_cap) };
-        match net::udp_recv(output) {
-            Ok(Some(meta)) => {
-                request.src_ip = meta.src_ip;
-                request.src_port = meta.src_port;
-                request.dst_port = meta.dst_port;
-                // SAFETY: request pointer is valid and writable in shared address space.
-                unsafe {
-                    (req_ptr as *mut UdpRecvReq).write(request);
-                }
-                meta.len as isize
-            }
-            Ok(None) => 0,
-            Err(err) => {
-                self.stats.errors = self.stats.errors.saturating_add(1);
-                map_net_error(err)
+        let records = ptr as *mut SyscallRecord;
+        let mut executed = 0usize;
+        while executed < count {
+            // SAFETY: the whole record array was validated as writable above.
+            let mut record = unsafe { records.add(executed).read_unaligned() };
+            let result = if record.number == SYS_BATCH {
+                self.fail(KernelError::InvalidArgument)
+            } else {
+                let [arg0, arg1, arg2] = record.args;
+                self.dispatch_syscall(task, now_ticks, record.number, arg0, arg1, arg2)
+            };
+            record.result = result as i64;
+            // SAFETY: as above; records are plain data, so rewriting one in place is sound.
+            unsafe { records.add(executed).write_unaligned(record) };
+            executed += 1;
+            if !matches!(task.state, TaskState::Ready)
+                || (flags & BATCH_STOP_ON_ERROR != 0 && result < 0)
+            {
+                break;
             }
         }
+        self.stats.batch_records = self.stats.batch_records.saturating_add(executed as u64);
+        self.stats.batch_max = self.stats.batch_max.max(executed as u64);
+        executed as isize
+    }
+
+    /// Central pointer check for syscalls; failures are counted and mapped to `EFAULT`.
+    fn check_user_range(
+        &mut self,
+        task: &Task,
+        number: u64,
+        ptr: u64,
+        len: usize,
+        write: bool,
+    ) -> Result<(), isize> {
+        let result = usercopy::validate_user_range(
+            task.address_space,
+            &mut self.fault_injection,
+            ptr,
+            len,
+            write,
+        );
+        let Err(error) = result else {
+            return Ok(());
+        };
+        self.stats.efault = self.stats.efault.saturating_add(1);
+        serial::write_fmt(format_args!(
+            "syscall: pid={} name={} number={} ({}) ptr={:#x} len={} -> {} ({})\n",
+            task.pid,
+            task.name,
+            number,
+            arrostd::syscall::name(number),
+            ptr,
+            len,
+            KernelError::BadAddress.name(),
+            error.as_str()
+        ));
+        Err(self.fail(KernelError::BadAddress))
+    }
+
+    /// `kill(pid, signal)`: signal 0 only checks that `pid` exists. SIGKILL ends a task at once;
+    /// other signals stay pending until the task's next delivery point.
+    fn syscall_kill(&mut self, task: &mut Task, now_ticks: u64, pid: u64, number: u64) -> isize {
+        let signal = match number {
+            0 => None,
+            number => match Signal::from_number(number) {
+                Some(signal) => Some(signal),
+                None => return self.fail(KernelError::InvalidArgument),
+            },
+        };
+        let Ok(pid) = u32::try_from(pid) else {
+            return self.fail(KernelError::NoSuchProcess);
+        };
+        // The running task was copied out of `tasks`, so its own slot there is stale.
+        let sent = if pid == task.pid {
+            self.signal_task(task, signal, now_ticks)
+        } else if let Some(index) = self.live_task_index(pid)
+            && let Some(mut target) = self.tasks[index]
+        {
+            let sent = self.signal_task(&mut target, signal, now_ticks);
+            self.tasks[index] = Some(target);
+            sent
+        } else {
+            executor::send_signal(pid, signal)
+        };
+        if sent {
+            0
+        } else {
+            self.fail(KernelError::NoSuchProcess)
+        }
+    }
+
+    fn live_task_index(&self, pid: u32) -> Option<usize> {
+        self.tasks.iter().position(|slot| {
+            slot.is_some_and(|task| {
+                task.pid == pid && !matches!(task.state, TaskState::Exited { .. })
+            })
+        })
+    }
+
+    /// Marks `signal` pending on `task` and wakes it; SIGKILL exits it instead.
+    fn signal_task(&mut self, task: &mut Task, signal: Option<Signal>, now_ticks: u64) -> bool {
+        if matches!(task.state, TaskState::Exited { .. }) {
+            return false;
+        }
+        let Some(signal) = signal else {
+            return true;
+        };
+        if signal == Signal::Kill {
+            let code = signal.exit_code();
+            task.state = TaskState::Exited { code };
+            input_bus::release_user(task.pid);
+            #[cfg(feature = "desktop")]
+            gfx::release_app_window(task.pid);
+            #[cfg(feature = "net")]
+            task.release_sockets();
+            self.trace
+                .record(now_ticks, task.pid, TraceEvent::Exit { code });
+            serial::write_fmt(format_args!(
+                "proc: pid={} name={} killed by {} code={}\n",
+                task.pid,
+                task.name,
+                signal.as_str(),
+                code
+            ));
+            return true;
+        }
+        task.signals |= signal.bit();
+        if matches!(
+            task.state,
+            TaskState::Sleeping { .. } | TaskState::Polling { .. }
+        ) {
+            task.state = TaskState::Ready;
+            self.trace.record(now_ticks, task.pid, TraceEvent::Wake);
+        }
+        true
+    }
+
+    /// Delivery point at the top of a task step: a pending SIGTERM or SIGINT ends the task
+    /// with `128 + signal`. Returns whether the task exited.
+    fn deliver_signals(&mut self, task: &mut Task, now_ticks: u64) -> bool {
+        if task.signals == 0 {
+            return false;
+        }
+        let pending = self.dispatch_syscall(task, now_ticks, SYS_SIGPENDING, 0, 0, 0);
+        let Some(signal) = signal::first(pending as u64 & TERMINATE_MASK) else {
+            return false;
+        };
+        serial::write_fmt(format_args!(
+            "[{}] {}, exit({})\n",
+            task.name,
+            signal.as_str(),
+            signal.exit_code()
+        ));
+        self.sys_exit(task, signal.exit_code(), now_ticks);
+        true
+    }
+
+    /// Counts a failed syscall and encodes `error` as its negative errno return.
+    fn fail(&mut self, error: KernelError) -> isize {
+        self.stats.errors = self.stats.errors.saturating_add(1);
+        error.syscall_return()
+    }
+
+    fn log_fault_injection(&self) {
+        serial::write_fmt(format_args!(
+            "syscalls: fault injection armed={} fired={}\n",
+            self.fault_injection.armed(),
+            self.fault_injection.fired()
+        ));
     }
 
     fn sys_write(&mut self, task: &mut Task, text: &str, now_ticks: u64) {
@@ -602,21 +1918,68 @@ impl Scheduler {
             let Some(task) = slot.as_mut() else {
                 continue;
             };
-            if let TaskState::Sleeping { until_tick } = task.state
-                && now_ticks >= until_tick
-            {
-                task.state = TaskState::Ready;
+            match task.state {
+                TaskState::Sleeping { until_tick } if now_ticks >= until_tick => {}
+                TaskState::Polling {
+                    fds,
+                    nfds,
+                    until_tick,
+                } => {
+                    // The array was checked when the task parked; it may have been unmapped
+                    // since (a shrunk break), so check it again before writing `revents`.
+                    if nfds > 0
+                        && usercopy::validate_user_range(
+                            task.address_space,
+                            &mut self.fault_injection,
+                            fds,
+                            nfds * size_of::<PollFd>(),
+                            true,
+                        )
+                        .is_err()
+                    {
+                        self.stats.efault = self.stats.efault.saturating_add(1);
+                    } else if poll_scan(task, fds, nfds, now_ticks) == 0 {
+                        if !until_tick.is_some_and(|until| now_ticks >= until) {
+                            continue;
+                        }
+                        self.stats.poll_timeouts = self.stats.poll_timeouts.saturating_add(1);
+                    }
+                }
+                _ => continue,
             }
+            task.state = TaskState::Ready;
+            self.trace.record(now_ticks, task.pid, TraceEvent::Wake);
         }
     }
 
+    fn next_deadline(&self, now_ticks: u64) -> Option<u64> {
+        self.tasks
+            .iter()
+            .flatten()
+            .filter_map(|task| match task.state {
+                TaskState::Ready => Some(now_ticks),
+                TaskState::Sleeping { until_tick } => Some(until_tick),
+                // Sockets and input wake the loop through their interrupts; only the timeout
+                // and the task's timer need a deadline.
+                TaskState::Polling { until_tick, .. } => match (until_tick, task.timer.deadline) {
+                    (Some(until), Some(timer)) => Some(until.min(timer)),
+                    (until, timer) => until.or(timer),
+                },
+                TaskState::Exited { .. } => None,
+            })
+            .min()
+    }
+
     fn spawn_task(&mut self, name: &'static str, kind: TaskKind) -> Option<u32> {
-        let pid = self.next_pid;
-        self.next_pid = self.next_pid.saturating_add(1);
+        let pid = alloc_pid();
 
-        for slot in &mut self.tasks {
+        for (index, slot) in self.tasks.iter_mut().enumerate() {
             if slot.is_none() {
-                *slot = Some(Task::new(pid, name, kind));
+                let mut task = Task::new(pid, name, kind);
+                task.heap = TaskHeap::new(userheap::slot(index));
+                task.tls[TLS_SLOT_RNG as usize] = rng::mix(entropy::next_u64() ^ u64::from(pid));
+                *slot = Some(task);
+                self.task_fpu[index] = fpu::clean();
                 return Some(pid);
             }
         }
@@ -642,16 +2005,28 @@ impl Scheduler {
             match task.state {
                 TaskState::Ready => {
                     serial::write_fmt(format_args!(
-                        "proc: pid={} name={} state=ready\n",
-                        task.pid, task.name
+                        "proc: pid={} name={} state=ready pending={:#x}\n",
+                        task.pid, task.name, task.signals
                     ));
                 }
                 TaskState::Sleeping { until_tick } => {
                     serial::write_fmt(format_args!(
-                        "proc: pid={} name={} state=sleep until_tick={}\n",
-                        task.pid, task.name, until_tick
+                        "proc: pid={} name={} state=sleep until_tick={} pending={:#x}\n",
+                        task.pid, task.name, until_tick, task.signals
                     ));
                 }
+                TaskState::Polling {
+                    nfds, until_tick, ..
+                } => match until_tick {
+                    Some(until_tick) => serial::write_fmt(format_args!(
+                        "proc: pid={} name={} state=poll nfds={} until_tick={} pending={:#x}\n",
+                        task.pid, task.name, nfds, until_tick, task.signals
+                    )),
+                    None => serial::write_fmt(format_args!(
+                        "proc: pid={} name={} state=poll nfds={} until_tick=never pending={:#x}\n",
+                        task.pid, task.name, nfds, task.signals
+                    )),
+                },
                 TaskState::Exited { code } => {
                     serial::write_fmt(format_args!(
                         "proc: pid={} name={} state=exited code={}\n",
@@ -662,9 +2037,60 @@ impl Scheduler {
         }
     }
 
+    fn log_sysinfo(&self) {
+        serial::write_fmt(format_args!(
+            "sysinfo: tasks count={}\n",
+            self.count_tasks()
+        ));
+        for task in self.tasks.iter().flatten() {
+            let prefix = format_args!("sysinfo: task pid={} name={}", task.pid, task.name);
+            match task.state {
+                TaskState::Ready => serial::write_fmt(format_args!("{prefix} state=ready\n")),
+                TaskState::Sleeping { .. } => {
+                    serial::write_fmt(format_args!("{prefix} state=sleep\n"))
+                }
+                TaskState::Polling { .. } => {
+                    serial::write_fmt(format_args!("{prefix} state=poll\n"))
+                }
+                TaskState::Exited { code } => {
+                    serial::write_fmt(format_args!("{prefix} state=exited code={code}\n"))
+                }
+            }
+        }
+    }
+
+    fn log_heaps(&self) {
+        serial::write_fmt(format_args!(
+            "mem: tasks={} heap_slot={} KiB\n",
+            self.count_tasks(),
+            userheap::SLOT_BYTES / 1024
+        ));
+        for task in self.tasks.iter().flatten() {
+            let Some(slot) = task.heap.slot else {
+                serial::write_fmt(format_args!(
+                    "mem: pid={} name={} heap=none\n",
+                    task.pid, task.name
+                ));
+                continue;
+            };
+            serial::write_fmt(format_args!(
+                "mem: pid={} name={} heap={:#x}..{:#x} brk={:#x} used={} peak={} guard_low={:#x} guard_high={:#x}\n",
+                task.pid,
+                task.name,
+                slot.base,
+                slot.limit,
+                task.heap.brk,
+                task.heap.brk - slot.base,
+                task.heap.peak - slot.base,
+                slot.guard_low,
+                slot.guard_high
+            ));
+        }
+    }
+
     fn log_syscall_stats(&self) {
         serial::write_fmt(format_args!(
-            "syscalls: write={} read={} yield={} sleep={} exit={} socket={} sendto={} recvfrom={} errors={}\n",
+            "syscalls: write={} read={} yield={} sleep={} exit={} socket={} sendto={} recvfrom={} setsockopt={} brk={} kill={} sigpending={} tls_get={} tls_set={} input_subscribe={} input_read={} surface={} poll={} poll_waits={} poll_timeouts={} timer_set={} connect={} bind={} listen={} accept={} close={} batch={} batch_records={} batch_avg={} batch_max={} efault={} errors={}\n",
             self.stats.write,
             self.stats.read,
             self.stats.yield_now,
@@ -673,37 +2099,208 @@ impl Scheduler {
             self.stats.socket,
             self.stats.sendto,
             self.stats.recvfrom,
+            self.stats.setsockopt,
+            self.stats.brk,
+            self.stats.kill,
+            self.stats.sigpending,
+            self.stats.tls_get,
+            self.stats.tls_set,
+            self.stats.input_subscribe,
+            self.stats.input_read,
+            self.stats.surface,
+            self.stats.poll,
+            self.stats.poll_waits,
+            self.stats.poll_timeouts,
+            self.stats.timer_set,
+            self.stats.connect,
+            self.stats.bind,
+            self.stats.listen,
+            self.stats.accept,
+            self.stats.close,
+            self.stats.batch,
+            self.stats.batch_records,
+            self.stats.batch_records / self.stats.batch.max(1),
+            self.stats.batch_max,
+            self.stats.efault,
             self.stats.errors
         ));
     }
 }
 
-fn map_net_error(error: net::NetError) -> isize {
-    match error {
-        net::NetError::NotReady => -107,
-        net::NetError::NotFound => -19,
-        net::NetError::QueueUnavailable => -19,
-        net::NetError::QueueTooLarge => -90,
-        net::NetError::AddressTranslationFailed => -14,
-        net::NetError::FrameTooLarge => -90,
-        net::NetError::IoTimeout => -110,
-        net::NetError::ArpTimeout => -113,
-        net::NetError::UdpPayloadTooLarge => -90,
+impl DemoSurface {
+    fn write(&self, x: u32, y: u32, pixel: u32) {
+        let index = u64::from(y) * u64::from(self.width) + u64::from(x);
+        // SAFETY: callers stay inside `width x height`, which fits the arena range it was given.
+        unsafe { (self.pixels as *mut u32).add(index as usize).write(pixel) };
+    }
+}
+
+/// Fills `revents` for the `nfds` entries at `fds` (already validated as writable) and
+/// returns how many are non-zero. An expired timer is consumed when it is reported.
+fn poll_scan(task: &mut Task, fds: u64, nfds: usize, now_ticks: u64) -> usize {
+    let entries = fds as *mut PollFd;
+    let mut ready = 0;
+    for index in 0..nfds {
+        // SAFETY: the caller validated all `nfds` entries as writable in the task's memory.
+        let mut entry = unsafe { entries.add(index).read_unaligned() };
+        let wanted = entry.events | POLLERR | POLLNVAL;
+        entry.revents = fd_readiness(task, u64::from(entry.fd), entry.events, now_ticks) & wanted;
+        if entry.revents != 0 {
+            ready += 1;
+        }
+        // SAFETY: as above.
+        unsafe { entries.add(index).write_unaligned(entry) };
+    }
+    ready
+}
+
+/// Current `POLL*` bits for one fd; the timer is only consumed when `POLLIN` was asked for.
+fn fd_readiness(task: &mut Task, fd: u64, events: u16, now_ticks: u64) -> u16 {
+    match fd {
+        INPUT_FD => match input_bus::user_readable(task.pid) {
+            Ok(true) => POLLIN,
+            Ok(false) => 0,
+            Err(_) => POLLERR,
+        },
+        TIMER_FD if events & POLLIN != 0 && task.timer.take_expired(now_ticks) => POLLIN,
+        TIMER_FD => 0,
+        #[cfg(feature = "net")]
+        _ => task.socket_readiness(fd),
+        #[cfg(not(feature = "net"))]
+        _ => POLLNVAL,
+    }
+}
+
+/// `revents` for `sh(poll)`: `in`, `out`, `err`, `nval` joined by `|`, or `-`.
+fn poll_events_name(revents: u16) -> &'static str {
+    match revents & (POLLIN | POLLOUT | POLLERR | POLLNVAL) {
+        0 => "-",
+        POLLIN => "in",
+        POLLOUT => "out",
+        bits if bits == POLLIN | POLLOUT => "in|out",
+        bits if bits & POLLNVAL != 0 => "nval",
+        _ => "err",
+    }
+}
+
+/// `surface` demo background: red across, blue down.
+fn gradient(x: u32, y: u32, width: u32, height: u32) -> u32 {
+    let red = x * 255 / width.max(1);
+    let blue = y * 255 / height.max(1);
+    red << 16 | 0x40 << 8 | blue
+}
+
+fn errno_name(rc: isize) -> &'static str {
+    KernelError::from_syscall_return(rc)
+        .map(KernelError::name)
+        .unwrap_or("?")
+}
+
+#[derive(Clone, Copy)]
+enum SurfaceCommand {
+    Attach { width: u32, height: u32 },
+    Step,
+    Events,
+    Detach,
+    Close,
+    Usage,
+}
+
+/// `surface [w h]`, `surface step|events|detach|close`; `None` for any other command.
+fn parse_surface_command(command: &str) -> Option<SurfaceCommand> {
+    let mut parts = command.split_whitespace();
+    if parts.next()? != "surface" {
+        return None;
     }
+    Some(match (parts.next(), parts.next(), parts.next()) {
+        (None, _, _) => SurfaceCommand::Attach {
+            width: 160,
+            height: 100,
+        },
+        (Some("step"), None, _) => SurfaceCommand::Step,
+        (Some("events"), None, _) => SurfaceCommand::Events,
+        (Some("detach"), None, _) => SurfaceCommand::Detach,
+        (Some("close"), None, _) => SurfaceCommand::Close,
+        (Some(width), Some(height), None) => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) => SurfaceCommand::Attach { width, height },
+            _ => SurfaceCommand::Usage,
+        },
+        _ => SurfaceCommand::Usage,
+    })
 }
 
-fn parse_send_command(command: &str) -> Option<([u8; 4], u16, &str)> {
-    let rest = command.strip_prefix("send ")?;
-    let mut parts = rest.splitn(3, ' ');
-    let ip = parse_ipv4(parts.next()?)?;
-    let port = parts.next()?.parse::<u16>().ok()?;
-    let payload = parts.next()?;
+enum TcpCommand<'a> {
+    Connect { ip: [u8; 4], port: u16 },
+    Listen { port: u16 },
+    Accept { fd: u64 },
+    Send { fd: u64, text: &'a str },
+    Recv { fd: u64 },
+    Close { fd: u64 },
+    Usage,
+}
+
+/// `tcp connect <ip> <port>`, `tcp listen <port>`, `tcp accept|recv|close <fd>` and
+/// `tcp send <fd> <text>`; `None` for any other command.
+fn parse_tcp_command<'a>(command: &str, buf: &'a mut [u8]) -> Option<TcpCommand<'a>> {
+    let args = args::split(command, buf).ok()?;
+    if args.get(0)? != "tcp" {
+        return None;
+    }
+    let number = |index: usize| args.get(index).and_then(|text| text.parse::<u64>().ok());
+    let port = |index: usize| args.get(index).and_then(|text| text.parse::<u16>().ok());
+    let command = match (args.get(1), args.len()) {
+        (Some("connect"), 4) => args
+            .get(2)
+            .and_then(parse_ipv4)
+            .zip(port(3))
+            .map(|(ip, port)| TcpCommand::Connect { ip, port }),
+        (Some("listen"), 3) => port(2).map(|port| TcpCommand::Listen { port }),
+        (Some("accept"), 3) => number(2).map(|fd| TcpCommand::Accept { fd }),
+        (Some("recv"), 3) => number(2).map(|fd| TcpCommand::Recv { fd }),
+        (Some("close"), 3) => number(2).map(|fd| TcpCommand::Close { fd }),
+        (Some("send"), len) if len >= 4 => number(2)
+            .zip(args.rest(3))
+            .map(|(fd, text)| TcpCommand::Send { fd, text }),
+        _ => None,
+    };
+    Some(command.unwrap_or(TcpCommand::Usage))
+}
+
+/// `send <ip> <port> <text>`, tokenized like the kernel shell so the text can be quoted.
+fn parse_send_command<'a>(command: &str, buf: &'a mut [u8]) -> Option<([u8; 4], u16, &'a str)> {
+    let args = args::split(command, buf).ok()?;
+    if args.get(0)? != "send" {
+        return None;
+    }
+    let ip = parse_ipv4(args.get(1)?)?;
+    let port = args.get(2)?.parse::<u16>().ok()?;
+    let payload = args.rest(3)?;
     if payload.is_empty() {
         return None;
     }
     Some((ip, port, payload))
 }
 
+/// `kill <pid> [signal]`: the signal defaults to SIGTERM, `0` only probes the pid.
+fn parse_kill_args(args: &str) -> Option<(u64, u64)> {
+    let mut parts = args.split_whitespace();
+    let pid = parts.next()?.parse::<u64>().ok()?;
+    let number = match parts.next() {
+        None => Signal::Term.number(),
+        Some("0") => 0,
+        Some(text) => Signal::parse(text)?.number(),
+    };
+    parts.next().is_none().then_some((pid, number))
+}
+
+fn parse_membership_command(command: &str) -> Option<(u32, [u8; 4])> {
+    if let Some(group) = command.strip_prefix("join ") {
+        return Some((IP_ADD_MEMBERSHIP, parse_ipv4(group.trim())?));
+    }
+    let group = command.strip_prefix("leave ")?;
+    Some((IP_DROP_MEMBERSHIP, parse_ipv4(group.trim())?))
+}
+
 fn parse_ipv4(text: &str) -> Option<[u8; 4]> {
     let mut ip = [0u8; 4];
     let mut count = 0usize;
@@ -720,7 +2317,35 @@ fn parse_ipv4(text: &str) -> Option<[u8; 4]> {
     Some(ip)
 }
 
+fn alloc_pid() -> u32 {
+    NEXT_PID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Sends `signal` to the task or service with `pid` from kernel context (the shell `kill`);
+/// `None` only checks that it exists.
+pub fn send_signal(pid: u32, signal: Option<Signal>) -> Result<(), KernelError> {
+    let sent = with_scheduler(|scheduler| {
+        let index = scheduler.live_task_index(pid)?;
+        let mut task = scheduler.tasks[index]?;
+        let sent = scheduler.signal_task(&mut task, signal, time::ticks());
+        scheduler.tasks[index] = Some(task);
+        Some(sent)
+    })
+    .unwrap_or_else(|| executor::send_signal(pid, signal));
+    if sent {
+        Ok(())
+    } else {
+        Err(KernelError::NoSuchProcess)
+    }
+}
+
+/// Whether `pid` names a task that has not exited or a running service.
+pub fn is_alive(pid: u32) -> bool {
+    with_scheduler(|scheduler| scheduler.live_task_index(pid).is_some()) || executor::is_live(pid)
+}
+
 pub fn init() -> ProcInitReport {
+    trace::init();
     with_scheduler(|scheduler| scheduler.init())
 }
 
@@ -728,14 +2353,62 @@ pub fn run_once(now_ticks: u64) {
     with_scheduler(|scheduler| scheduler.run_once(now_ticks));
 }
 
+/// Earliest tick a task needs the CPU: now for ready tasks, the wake tick for sleepers.
+pub fn next_deadline(now_ticks: u64) -> Option<u64> {
+    with_scheduler(|scheduler| scheduler.next_deadline(now_ticks))
+}
+
 pub fn log_process_table() {
     with_scheduler(|scheduler| scheduler.log_tasks());
+    executor::log_services();
+}
+
+pub fn log_sysinfo() {
+    with_scheduler(|scheduler| scheduler.log_sysinfo());
+}
+
+/// Per-task heap slot, break, and high-water mark for `mem tasks`.
+pub fn log_task_heaps() {
+    with_scheduler(|scheduler| scheduler.log_heaps());
 }
 
 pub fn log_syscall_stats() {
     with_scheduler(|scheduler| scheduler.log_syscall_stats());
 }
 
+/// Makes the `nth` upcoming user-pointer validation fail with EFAULT; `0` disarms the hook.
+pub fn inject_user_fault(nth: u64) {
+    with_scheduler(|scheduler| {
+        scheduler.fault_injection.arm(nth);
+        scheduler.log_fault_injection();
+    });
+}
+
+/// Records a main-loop doom poll in the scheduler trace so it interleaves with task steps.
+#[cfg(feature = "doom")]
+pub fn trace_doom_poll(now_ticks: u64) {
+    if trace::enabled() {
+        with_scheduler(|scheduler| {
+            scheduler
+                .trace
+                .record(now_ticks, 0, TraceEvent::DoomPoll { polls: 1 })
+        });
+    }
+}
+
+pub fn log_trace() {
+    with_scheduler(|scheduler| scheduler.trace.log());
+}
+
+pub fn clear_trace() {
+    with_scheduler(|scheduler| scheduler.trace.clear());
+}
+
+/// Returns whether the setting changed; recording starts or stops at the next event.
+pub fn set_trace(enabled: bool) -> bool {
+    trace::set_enabled(enabled)
+}
+
 fn with_scheduler<R>(f: impl FnOnce(&mut Scheduler) -> R) -> R {
     let _guard = SCHED_LOCK.lock();
     // SAFETY: `SCHED_LOCK` serializes mutable access to scheduler state.
@@ -0,0 +1,149 @@
+// kernel/src/proc/signal.rs: signal numbers, pending-signal bitmasks and the shell `kill`
+// command shared by scheduler tasks and executor services.
+use super::executor;
+use crate::error::KernelError;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+use arrostd::syscall::{SIGINT, SIGKILL, SIGTERM};
+
+/// Ticks `kill <pid>` waits after SIGTERM before it sends SIGKILL.
+const KILL_GRACE_TICKS: u64 = 100;
+/// How often the `kill` service checks whether the target has exited.
+const KILL_POLL_TICKS: u64 = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Int,
+    Kill,
+    Term,
+}
+
+impl Signal {
+    /// In signal-number order.
+    pub const ALL: [Self; 3] = [Self::Int, Self::Kill, Self::Term];
+
+    pub const fn number(self) -> u64 {
+        match self {
+            Self::Int => SIGINT,
+            Self::Kill => SIGKILL,
+            Self::Term => SIGTERM,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Int => "SIGINT",
+            Self::Kill => "SIGKILL",
+            Self::Term => "SIGTERM",
+        }
+    }
+
+    pub fn from_number(number: u64) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|signal| signal.number() == number)
+    }
+
+    /// Accepts `15`, `TERM` or `SIGTERM`, in any case.
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Ok(number) = text.parse::<u64>() {
+            return Self::from_number(number);
+        }
+        Self::ALL.iter().copied().find(|signal| {
+            let name = signal.as_str();
+            name.eq_ignore_ascii_case(text) || name[3..].eq_ignore_ascii_case(text)
+        })
+    }
+
+    pub const fn bit(self) -> u64 {
+        1 << self.number()
+    }
+
+    /// Exit code of a task the signal ended, as a POSIX shell reports it.
+    pub const fn exit_code(self) -> i32 {
+        128 + self.number() as i32
+    }
+}
+
+/// Pending bits that ask a task or service to shut down gracefully.
+pub const TERMINATE_MASK: u64 = Signal::Int.bit() | Signal::Term.bit();
+
+/// Lowest-numbered signal in `mask`.
+pub fn first(mask: u64) -> Option<Signal> {
+    Signal::ALL
+        .iter()
+        .copied()
+        .find(|signal| mask & signal.bit() != 0)
+}
+
+/// `kill [-<signal>] <pid>`. Without a signal it sends SIGTERM and, if the target is still
+/// alive after the grace period, SIGKILL.
+pub fn kill_to_serial(args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    let (signal, pid) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(pid), None, None) => (None, pid),
+        (Some(signal), Some(pid), None) if signal.starts_with('-') => {
+            let Some(signal) = Signal::parse(&signal[1..]) else {
+                serial::write_severity_fmt(
+                    Severity::Warning,
+                    format_args!("kill: unknown signal {signal}\n"),
+                );
+                return Status::Usage;
+            };
+            (Some(signal), pid)
+        }
+        _ => return Status::usage("usage: kill [-INT|-TERM|-KILL] <pid>"),
+    };
+    let Ok(pid) = pid.parse::<u32>() else {
+        serial::write_severity_fmt(Severity::Warning, format_args!("kill: bad pid {pid}\n"));
+        return Status::Usage;
+    };
+
+    let sent = signal.unwrap_or(Signal::Term);
+    if let Err(err) = super::send_signal(pid, Some(sent)) {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!("kill: pid={pid} failed ({})\n", err.name()),
+        );
+        return Status::Failed;
+    }
+    serial::write_fmt(format_args!("kill: pid={pid} sent {}\n", sent.as_str()));
+    if signal.is_some() || !super::is_alive(pid) {
+        return Status::Ok;
+    }
+    if !executor::spawn("kill", escalate(pid)) {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!("kill: no executor slot to escalate, use kill -KILL {pid}\n"),
+        );
+    }
+    Status::Ok
+}
+
+/// Gives a SIGTERM'd `pid` `KILL_GRACE_TICKS` to exit, then force-terminates it.
+async fn escalate(pid: u32) {
+    let mut waited = 0u64;
+    while waited < KILL_GRACE_TICKS {
+        executor::sleep_ticks(KILL_POLL_TICKS).await;
+        waited = waited.saturating_add(KILL_POLL_TICKS);
+        if !super::is_alive(pid) {
+            serial::write_fmt(format_args!(
+                "kill: pid={pid} exited within {waited} ticks\n"
+            ));
+            return;
+        }
+    }
+    match super::send_signal(pid, Some(Signal::Kill)) {
+        Ok(()) => serial::write_fmt(format_args!(
+            "kill: pid={pid} still alive after {KILL_GRACE_TICKS} ticks, sent SIGKILL\n"
+        )),
+        Err(KernelError::NoSuchProcess) => {
+            serial::write_fmt(format_args!("kill: pid={pid} exited\n"))
+        }
+        Err(err) => serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!("kill: pid={pid} SIGKILL failed ({})\n", err.name()),
+        ),
+    }
+}
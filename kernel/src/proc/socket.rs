@@ -0,0 +1,501 @@
+// kernel/src/proc/socket.rs: SYS_SOCKET family on top of the `net` UDP mailbox and TCP table.
+use super::{Scheduler, Task};
+use crate::error::KernelError;
+use crate::net;
+use arrostd::syscall::{
+    AF_INET, IP_ADD_MEMBERSHIP, IP_DROP_MEMBERSHIP, IPPROTO_IP, IPPROTO_TCP, IPPROTO_UDP, POLLERR,
+    POLLIN, POLLNVAL, POLLOUT, SO_BROADCAST, SO_TIMESTAMP, SOCK_DGRAM, SOCK_STREAM, SOL_SOCKET,
+    SYS_ACCEPT, SYS_BIND, SYS_CONNECT, SYS_RECVFROM, SYS_SENDTO, SYS_SETSOCKOPT, SockAddrIn,
+    SockOptReq, TCP_FD_FIRST, TCP_FDS, UDP_RECV_REQ_BASE_LEN, UDP_SOCKET_FD, UdpRecvReq,
+    UdpSendReq,
+};
+use core::mem::size_of;
+
+#[derive(Clone, Copy)]
+pub(super) enum TcpFd {
+    Free,
+    /// Not connected or listening yet; SYS_BIND records the port SYS_LISTEN will use.
+    Open {
+        port: u16,
+    },
+    /// A `net::tcp_*` socket id: connecting, connected, listening or accepted.
+    Socket(u32),
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct UdpSocketOptions {
+    pub(super) broadcast: bool,
+    pub(super) timestamp: bool,
+}
+
+impl UdpSocketOptions {
+    pub(super) const fn new() -> Self {
+        Self {
+            broadcast: false,
+            timestamp: false,
+        }
+    }
+}
+
+impl Task {
+    /// Index into `tcp_fds` for a stream fd, open or not.
+    fn tcp_slot(fd: u64) -> Option<usize> {
+        fd.checked_sub(TCP_FD_FIRST)
+            .filter(|index| *index < TCP_FDS)
+            .map(|index| index as usize)
+    }
+
+    /// Closes every stream socket and gives back the UDP port, as an exiting task leaves
+    /// them behind.
+    pub(super) fn release_sockets(&mut self) {
+        for fd in &mut self.tcp_fds {
+            if let TcpFd::Socket(id) = *fd {
+                let _ = net::tcp_close(id);
+            }
+            *fd = TcpFd::Free;
+        }
+        if self.udp_port != 0 {
+            net::udp_release_port(self.udp_port);
+            self.udp_port = 0;
+        }
+    }
+
+    /// `POLL*` bits of the UDP fd or a stream fd; any other fd is `POLLNVAL`.
+    pub(super) fn socket_readiness(&self, fd: u64) -> u16 {
+        if fd == UDP_SOCKET_FD {
+            return match net::udp_readiness() {
+                Ok(ready) => {
+                    (if ready.readable { POLLIN } else { 0 })
+                        | (if ready.writable { POLLOUT } else { 0 })
+                }
+                Err(_) => POLLERR,
+            };
+        }
+        match Task::tcp_slot(fd).map(|index| self.tcp_fds[index]) {
+            Some(TcpFd::Socket(id)) => match net::tcp_readiness(id) {
+                Ok(ready) => {
+                    (if ready.readable { POLLIN } else { 0 })
+                        | (if ready.writable { POLLOUT } else { 0 })
+                        | (if ready.failed { POLLERR } else { 0 })
+                }
+                Err(_) => POLLERR,
+            },
+            Some(TcpFd::Open { .. }) => 0,
+            Some(TcpFd::Free) | None => POLLNVAL,
+        }
+    }
+}
+
+impl Scheduler {
+    /// `socket(domain, type, protocol)`: the fixed UDP fd for `SOCK_DGRAM`, a fresh stream fd
+    /// for `SOCK_STREAM`.
+    pub(super) fn syscall_socket(
+        &mut self,
+        task: &mut Task,
+        domain: u64,
+        socket_type: u64,
+        protocol: u64,
+    ) -> isize {
+        if domain != AF_INET || (socket_type != SOCK_DGRAM && socket_type != SOCK_STREAM) {
+            return self.fail(KernelError::AddressFamilyNotSupported);
+        }
+        if socket_type == SOCK_STREAM {
+            if protocol != 0 && protocol != IPPROTO_TCP {
+                return self.fail(KernelError::ProtocolNotSupported);
+            }
+            let Some(index) = task.tcp_fds.iter().position(|fd| matches!(fd, TcpFd::Free)) else {
+                return self.fail(KernelError::TooManyFiles);
+            };
+            task.tcp_fds[index] = TcpFd::Open { port: 0 };
+            return (TCP_FD_FIRST + index as u64) as isize;
+        }
+        if protocol != 0 && protocol != IPPROTO_UDP {
+            return self.fail(KernelError::ProtocolNotSupported);
+        }
+        UDP_SOCKET_FD as isize
+    }
+
+    /// Slot of an open stream fd, or the `EBADF` return.
+    fn tcp_fd(&mut self, task: &Task, fd: u64) -> Result<usize, isize> {
+        match Task::tcp_slot(fd) {
+            Some(index) if !matches!(task.tcp_fds[index], TcpFd::Free) => Ok(index),
+            _ => Err(self.fail(KernelError::BadFd)),
+        }
+    }
+
+    /// Reads the `SockAddrIn` at `ptr`; `len` must be its size.
+    fn read_sockaddr(
+        &mut self,
+        task: &Task,
+        number: u64,
+        ptr: u64,
+        len: u64,
+    ) -> Result<SockAddrIn, isize> {
+        if ptr == 0 || len != size_of::<SockAddrIn>() as u64 {
+            return Err(self.fail(KernelError::InvalidArgument));
+        }
+        self.check_user_range(task, number, ptr, size_of::<SockAddrIn>(), false)?;
+        // SAFETY: the address range was validated in the task's address space.
+        Ok(unsafe { (ptr as *const SockAddrIn).read_unaligned() })
+    }
+
+    /// `connect(fd, addr_ptr, addr_len)`: starts the handshake and returns 0 at once. Sends
+    /// queue until it completes; `POLLOUT` reports when it has.
+    pub(super) fn syscall_connect(
+        &mut self,
+        task: &mut Task,
+        fd: u64,
+        addr_ptr: u64,
+        addr_len: u64,
+    ) -> isize {
+        let index = match self.tcp_fd(task, fd) {
+            Ok(index) => index,
+            Err(rc) => return rc,
+        };
+        if matches!(task.tcp_fds[index], TcpFd::Socket(_)) {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        let addr = match self.read_sockaddr(task, SYS_CONNECT, addr_ptr, addr_len) {
+            Ok(addr) => addr,
+            Err(rc) => return rc,
+        };
+        match net::tcp_connect(addr.ip, addr.port) {
+            Ok(id) => {
+                task.tcp_fds[index] = TcpFd::Socket(id);
+                0
+            }
+            Err(err) => self.fail(err.into()),
+        }
+    }
+
+    /// `bind(fd, addr_ptr, addr_len)`: names the port a later `listen` takes. Only the port
+    /// counts; `connect` always picks an ephemeral one.
+    pub(super) fn syscall_bind(
+        &mut self,
+        task: &mut Task,
+        fd: u64,
+        addr_ptr: u64,
+        addr_len: u64,
+    ) -> isize {
+        let index = match self.tcp_fd(task, fd) {
+            Ok(index) => index,
+            Err(rc) => return rc,
+        };
+        if !matches!(task.tcp_fds[index], TcpFd::Open { .. }) {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        let addr = match self.read_sockaddr(task, SYS_BIND, addr_ptr, addr_len) {
+            Ok(addr) => addr,
+            Err(rc) => return rc,
+        };
+        if addr.port == 0 {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        task.tcp_fds[index] = TcpFd::Open { port: addr.port };
+        0
+    }
+
+    /// `listen(fd)`: listens on the bound port. The backlog is the stack's fixed one.
+    pub(super) fn syscall_listen(&mut self, task: &mut Task, fd: u64) -> isize {
+        let index = match self.tcp_fd(task, fd) {
+            Ok(index) => index,
+            Err(rc) => return rc,
+        };
+        let TcpFd::Open { port } = task.tcp_fds[index] else {
+            return self.fail(KernelError::InvalidArgument);
+        };
+        if port == 0 {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        match net::tcp_listen(port) {
+            Ok(id) => {
+                task.tcp_fds[index] = TcpFd::Socket(id);
+                0
+            }
+            Err(err) => self.fail(err.into()),
+        }
+    }
+
+    /// `accept(fd, addr_ptr, addr_len)`: a new stream fd for the next established connection,
+    /// or `EAGAIN` while there is none. A non-null `addr_ptr` gets the peer's address.
+    pub(super) fn syscall_accept(
+        &mut self,
+        task: &mut Task,
+        fd: u64,
+        addr_ptr: u64,
+        addr_len: u64,
+    ) -> isize {
+        let index = match self.tcp_fd(task, fd) {
+            Ok(index) => index,
+            Err(rc) => return rc,
+        };
+        let TcpFd::Socket(listener) = task.tcp_fds[index] else {
+            return self.fail(KernelError::InvalidArgument);
+        };
+        if addr_ptr != 0 {
+            if addr_len != size_of::<SockAddrIn>() as u64 {
+                return self.fail(KernelError::InvalidArgument);
+            }
+            if let Err(rc) =
+                self.check_user_range(task, SYS_ACCEPT, addr_ptr, size_of::<SockAddrIn>(), true)
+            {
+                return rc;
+            }
+        }
+        // Checked before taking the connection, which would be lost without an fd for it.
+        let Some(slot) = task.tcp_fds.iter().position(|fd| matches!(fd, TcpFd::Free)) else {
+            return self.fail(KernelError::TooManyFiles);
+        };
+        let id = match net::tcp_accept(listener) {
+            Ok(Some(id)) => id,
+            Ok(None) => return self.fail(KernelError::WouldBlock),
+            Err(err) => return self.fail(err.into()),
+        };
+        task.tcp_fds[slot] = TcpFd::Socket(id);
+        if addr_ptr != 0
+            && let Ok(info) = net::tcp_info(id)
+        {
+            // SAFETY: the address range was validated as writable above.
+            unsafe {
+                (addr_ptr as *mut SockAddrIn)
+                    .write_unaligned(SockAddrIn::new(info.peer_ip, info.peer_port));
+            }
+        }
+        (TCP_FD_FIRST + slot as u64) as isize
+    }
+
+    /// `close(fd)`: an orderly close of a stream fd; queued data still goes out.
+    pub(super) fn syscall_close(&mut self, task: &mut Task, fd: u64) -> isize {
+        let index = match self.tcp_fd(task, fd) {
+            Ok(index) => index,
+            Err(rc) => return rc,
+        };
+        let previous = core::mem::replace(&mut task.tcp_fds[index], TcpFd::Free);
+        if let TcpFd::Socket(id) = previous
+            && let Err(err) = net::tcp_close(id)
+        {
+            return self.fail(err.into());
+        }
+        0
+    }
+
+    /// On a stream fd `sendto` and `recvfrom` take the buffer and its length directly: the
+    /// connection already names the peer.
+    fn tcp_transfer(&mut self, task: &Task, number: u64, fd: u64, ptr: u64, len: u64) -> isize {
+        let index = match self.tcp_fd(task, fd) {
+            Ok(index) => index,
+            Err(rc) => return rc,
+        };
+        let TcpFd::Socket(id) = task.tcp_fds[index] else {
+            return self.fail(KernelError::NotConnected);
+        };
+        let Some(len) = usize::try_from(len).ok().filter(|len| *len > 0) else {
+            return self.fail(KernelError::InvalidArgument);
+        };
+        let write = number == SYS_RECVFROM;
+        if let Err(rc) = self.check_user_range(task, number, ptr, len, write) {
+            return rc;
+        }
+        let result = if write {
+            // SAFETY: the buffer was validated as writable in the task's address space.
+            let output = unsafe { core::slice::from_raw_parts_mut(ptr as *mut u8, len) };
+            net::tcp_recv(id, output)
+        } else {
+            // SAFETY: the buffer was validated in the task's address space.
+            let data = unsafe { core::slice::from_raw_parts(ptr as *const u8, len) };
+            net::tcp_send(id, data)
+        };
+        match result {
+            Ok(len) => len as isize,
+            Err(err) => self.fail(err.into()),
+        }
+    }
+
+    pub(super) fn syscall_sendto(
+        &mut self,
+        task: &mut Task,
+        fd: u64,
+        req_ptr: u64,
+        req_len: u64,
+    ) -> isize {
+        if Task::tcp_slot(fd).is_some() {
+            return self.tcp_transfer(task, SYS_SENDTO, fd, req_ptr, req_len);
+        }
+        if fd != UDP_SOCKET_FD {
+            return self.fail(KernelError::BadFd);
+        }
+        if req_ptr == 0 || req_len != size_of::<UdpSendReq>() as u64 {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        if let Err(rc) =
+            self.check_user_range(task, SYS_SENDTO, req_ptr, size_of::<UdpSendReq>(), false)
+        {
+            return rc;
+        }
+
+        // SAFETY: the request range was validated in the task's address space.
+        let request = unsafe { (req_ptr as *const UdpSendReq).read_unaligned() };
+        let Some(payload_len) = usize::try_from(request.payload_len).ok() else {
+            return self.fail(KernelError::InvalidArgument);
+        };
+        if request.payload_ptr == 0 || payload_len == 0 {
+            return self.fail(KernelError::InvalidArgument);
+        }
+
+        if !self.udp_options.broadcast && net::is_broadcast(request.dst_ip) {
+            return self.fail(KernelError::PermissionDenied);
+        }
+        if let Err(rc) =
+            self.check_user_range(task, SYS_SENDTO, request.payload_ptr, payload_len, false)
+        {
+            return rc;
+        }
+
+        // SAFETY: the payload range was validated in the task's address space.
+        let payload =
+            unsafe { core::slice::from_raw_parts(request.payload_ptr as *const u8, payload_len) };
+        let src_port = match request.src_port {
+            0 if task.udp_port != 0 => task.udp_port,
+            0 => match net::udp_allocate_port() {
+                Ok(port) => {
+                    task.udp_port = port;
+                    port
+                }
+                Err(err) => return self.fail(err.into()),
+            },
+            port => port,
+        };
+        match net::udp_send(request.dst_ip, request.dst_port, src_port, payload) {
+            Ok(sent) => sent as isize,
+            Err(err) => self.fail(err.into()),
+        }
+    }
+
+    pub(super) fn syscall_recvfrom(
+        &mut self,
+        task: &Task,
+        fd: u64,
+        req_ptr: u64,
+        req_len: u64,
+    ) -> isize {
+        if Task::tcp_slot(fd).is_some() {
+            return self.tcp_transfer(task, SYS_RECVFROM, fd, req_ptr, req_len);
+        }
+        if fd != UDP_SOCKET_FD {
+            return self.fail(KernelError::BadFd);
+        }
+        // The pre-timestamp layout stays accepted; only the full one gets `rx_tick`.
+        let req_len = match usize::try_from(req_len) {
+            Ok(len) if len == size_of::<UdpRecvReq>() || len == UDP_RECV_REQ_BASE_LEN => len,
+            _ => return self.fail(KernelError::InvalidArgument),
+        };
+        if req_ptr == 0 {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        if let Err(rc) = self.check_user_range(task, SYS_RECVFROM, req_ptr, req_len, true) {
+            return rc;
+        }
+
+        let mut request = UdpRecvReq::new(0, 0);
+        // SAFETY: the request range was validated as writable in the task's address space, and
+        // `req_len` is at most the size of `request`.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                req_ptr as *const u8,
+                (&raw mut request).cast::<u8>(),
+                req_len,
+            );
+        }
+        let Some(payload_cap) = usize::try_from(request.payload_cap).ok() else {
+            return self.fail(KernelError::InvalidArgument);
+        };
+        if request.payload_ptr == 0 || payload_cap == 0 {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        if let Err(rc) =
+            self.check_user_range(task, SYS_RECVFROM, request.payload_ptr, payload_cap, true)
+        {
+            return rc;
+        }
+
+        // SAFETY: the payload range was validated as writable in the task's address space.
+        let output =
+            unsafe { core::slice::from_raw_parts_mut(request.payload_ptr as *mut u8, payload_cap) };
+        match net::udp_recv(output) {
+            Ok(Some(meta)) if meta.broadcast && !self.udp_options.broadcast => 0,
+            Ok(Some(meta)) => {
+                request.src_ip = meta.src_ip;
+                request.src_port = meta.src_port;
+                request.dst_port = meta.dst_port;
+                request.rx_tick = if self.udp_options.timestamp {
+                    meta.rx_tick
+                } else {
+                    0
+                };
+                // SAFETY: the request range was validated as writable above.
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        (&raw const request).cast::<u8>(),
+                        req_ptr as *mut u8,
+                        req_len,
+                    );
+                }
+                meta.len as isize
+            }
+            Ok(None) => 0,
+            Err(err) => self.fail(err.into()),
+        }
+    }
+
+    pub(super) fn syscall_setsockopt(
+        &mut self,
+        task: &Task,
+        fd: u64,
+        req_ptr: u64,
+        req_len: u64,
+    ) -> isize {
+        if fd != UDP_SOCKET_FD {
+            return self.fail(KernelError::BadFd);
+        }
+        if req_ptr == 0 || req_len != size_of::<SockOptReq>() as u64 {
+            return self.fail(KernelError::InvalidArgument);
+        }
+        if let Err(rc) = self.check_user_range(
+            task,
+            SYS_SETSOCKOPT,
+            req_ptr,
+            size_of::<SockOptReq>(),
+            false,
+        ) {
+            return rc;
+        }
+
+        // SAFETY: the request range was validated in the task's address space.
+        let request = unsafe { (req_ptr as *const SockOptReq).read_unaligned() };
+        match (request.level, request.option) {
+            (SOL_SOCKET, SO_BROADCAST) => {
+                self.udp_options.broadcast = request.value != 0;
+                0
+            }
+            (SOL_SOCKET, SO_TIMESTAMP) => {
+                self.udp_options.timestamp = request.value != 0;
+                0
+            }
+            (IPPROTO_IP, IP_ADD_MEMBERSHIP | IP_DROP_MEMBERSHIP) => {
+                let Ok(group) = u32::try_from(request.value).map(u32::to_be_bytes) else {
+                    return self.fail(KernelError::InvalidArgument);
+                };
+                let result = if request.option == IP_ADD_MEMBERSHIP {
+                    net::join_group(group).map(|_| ())
+                } else {
+                    net::leave_group(group)
+                };
+                match result {
+                    Ok(()) => 0,
+                    Err(err) => self.fail(err.into()),
+                }
+            }
+            _ => self.fail(KernelError::NoProtocolOption),
+        }
+    }
+}
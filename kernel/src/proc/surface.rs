@@ -0,0 +1,124 @@
+// kernel/src/proc/surface.rs: SYS_SURFACE, a task's pixels shown in the desktop app window.
+use super::{Scheduler, Task};
+use crate::error::KernelError;
+use crate::gfx;
+use arrostd::syscall::{
+    SURFACE_ATTACH, SURFACE_CLOSE, SURFACE_DAMAGE, SURFACE_DETACH, SURFACE_EVENT_CLOSE,
+    SURFACE_EVENTS, SURFACE_OPEN, SURFACE_WINDOW_APP, SYS_SURFACE, SurfaceDesc, SurfaceRect,
+};
+use core::mem::size_of;
+
+impl Scheduler {
+    /// `surface(op, arg, len)`: SURFACE_OPEN returns the app window id; ATTACH and DAMAGE take
+    /// a `SurfaceDesc`/`SurfaceRect` at `arg` of `len` bytes; DETACH, CLOSE and EVENTS take the
+    /// id.
+    /// Every ATTACH and DAMAGE re-validates the whole pixel span before gfx copies from it.
+    pub(super) fn syscall_surface(&mut self, task: &Task, op: u64, arg: u64, len: u64) -> isize {
+        let result = match op {
+            SURFACE_OPEN => gfx::open_app_window(task.pid).map(|()| SURFACE_WINDOW_APP as isize),
+            SURFACE_ATTACH => {
+                let desc = match self.read_surface_request::<SurfaceDesc>(task, arg, len) {
+                    Ok(desc) => desc,
+                    Err(rc) => return rc,
+                };
+                if desc.window != SURFACE_WINDOW_APP || desc.pixels == 0 {
+                    return self.fail(KernelError::InvalidArgument);
+                }
+                let mapping = gfx::SurfaceMapping {
+                    ptr: desc.pixels,
+                    width: desc.width as usize,
+                    height: desc.height as usize,
+                    stride: desc.stride as usize,
+                };
+                match self.surface_pixels(task, mapping) {
+                    Ok(pixels) => gfx::attach_app_surface(task.pid, mapping, pixels).map(|()| 0),
+                    Err(rc) => return rc,
+                }
+            }
+            SURFACE_DAMAGE => {
+                let rect = match self.read_surface_request::<SurfaceRect>(task, arg, len) {
+                    Ok(rect) => rect,
+                    Err(rc) => return rc,
+                };
+                if rect.window != SURFACE_WINDOW_APP {
+                    return self.fail(KernelError::InvalidArgument);
+                }
+                let mapping = match gfx::app_surface_mapping(task.pid) {
+                    Ok(mapping) => mapping,
+                    Err(err) => return self.fail(err),
+                };
+                let area = (
+                    rect.x as usize,
+                    rect.y as usize,
+                    rect.width as usize,
+                    rect.height as usize,
+                );
+                match self.surface_pixels(task, mapping) {
+                    Ok(pixels) => gfx::damage_app_surface(task.pid, area, pixels)
+                        .map(|copied| copied as isize),
+                    Err(rc) => return rc,
+                }
+            }
+            SURFACE_DETACH | SURFACE_CLOSE | SURFACE_EVENTS
+                if arg != u64::from(SURFACE_WINDOW_APP) =>
+            {
+                Err(KernelError::InvalidArgument)
+            }
+            SURFACE_DETACH => gfx::detach_app_surface(task.pid).map(|()| 0),
+            SURFACE_EVENTS => gfx::take_app_close_request(task.pid).map(|close| {
+                if close {
+                    SURFACE_EVENT_CLOSE as isize
+                } else {
+                    0
+                }
+            }),
+            SURFACE_CLOSE => match gfx::app_window_status() {
+                Some(status) if status.owner == Some(task.pid) => {
+                    gfx::release_app_window(task.pid);
+                    Ok(0)
+                }
+                Some(status) if status.owner.is_some() => Err(KernelError::PermissionDenied),
+                Some(_) => Err(KernelError::NotFound),
+                None => Err(KernelError::NoDevice),
+            },
+            _ => Err(KernelError::InvalidArgument),
+        };
+        match result {
+            Ok(rc) => rc,
+            Err(err) => self.fail(err),
+        }
+    }
+
+    /// Reads the SYS_SURFACE request struct at `ptr`; `len` must be its size.
+    fn read_surface_request<T: Copy>(
+        &mut self,
+        task: &Task,
+        ptr: u64,
+        len: u64,
+    ) -> Result<T, isize> {
+        if ptr == 0 || len != size_of::<T>() as u64 {
+            return Err(self.fail(KernelError::InvalidArgument));
+        }
+        self.check_user_range(task, SYS_SURFACE, ptr, size_of::<T>(), false)?;
+        // SAFETY: the request range was validated in the task's address space.
+        Ok(unsafe { (ptr as *const T).read_unaligned() })
+    }
+
+    /// The task's pixels behind `mapping`, after checking the whole span is mapped.
+    fn surface_pixels(
+        &mut self,
+        task: &Task,
+        mapping: gfx::SurfaceMapping,
+    ) -> Result<&'static [u32], isize> {
+        let span = mapping.span();
+        let Some(bytes) = span.checked_mul(size_of::<u32>()) else {
+            return Err(self.fail(KernelError::InvalidArgument));
+        };
+        if span == 0 || !mapping.ptr.is_multiple_of(size_of::<u32>() as u64) {
+            return Err(self.fail(KernelError::InvalidArgument));
+        }
+        self.check_user_range(task, SYS_SURFACE, mapping.ptr, bytes, false)?;
+        // SAFETY: the span was validated as mapped in the task's address space and is aligned.
+        Ok(unsafe { core::slice::from_raw_parts(mapping.ptr as *const u32, span) })
+    }
+}
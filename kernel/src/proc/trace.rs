@@ -0,0 +1,135 @@
+// kernel/src/proc/trace.rs: opt-in ring of scheduling decisions and syscall outcomes (`sched trace`).
+use crate::serial;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Entries kept; older ones are overwritten and counted as `dropped`.
+pub const TRACE_CAPACITY: usize = 256;
+/// Build-time default so boot-time scheduling can be captured, e.g. `ARROST_SCHED_TRACE=1`.
+const BUILD_ENABLED: Option<&str> = option_env!("ARROST_SCHED_TRACE");
+
+/// Checked outside the scheduler lock so a disabled trace costs the main loop nothing.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy)]
+pub enum TraceEvent {
+    /// The scheduler picked the task for this step.
+    Run,
+    /// A sleeping task's deadline passed.
+    Wake,
+    Syscall {
+        number: u16,
+        rc: i32,
+    },
+    Exit {
+        code: i32,
+    },
+    /// The main loop polled the doom runtime (pid 0) `polls` times in a row between task
+    /// events; back-to-back polls share one entry so they cannot flush the ring.
+    #[cfg(feature = "doom")]
+    DoomPoll {
+        polls: u32,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct TraceEntry {
+    tick: u64,
+    pid: u32,
+    event: TraceEvent,
+}
+
+impl TraceEntry {
+    const EMPTY: Self = Self {
+        tick: 0,
+        pid: 0,
+        event: TraceEvent::Run,
+    };
+}
+
+pub struct TraceRing {
+    entries: [TraceEntry; TRACE_CAPACITY],
+    /// Events recorded since the last clear; the next slot is `recorded % TRACE_CAPACITY`.
+    recorded: u64,
+}
+
+impl TraceRing {
+    pub const fn new() -> Self {
+        Self {
+            entries: [TraceEntry::EMPTY; TRACE_CAPACITY],
+            recorded: 0,
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, pid: u32, event: TraceEvent) {
+        if !enabled() {
+            return;
+        }
+        #[cfg(feature = "doom")]
+        if let TraceEvent::DoomPoll { .. } = event
+            && self.recorded > 0
+        {
+            let last = (self.recorded - 1) % TRACE_CAPACITY as u64;
+            if let TraceEvent::DoomPoll { polls } = &mut self.entries[last as usize].event {
+                *polls = polls.saturating_add(1);
+                return;
+            }
+        }
+        self.entries[(self.recorded % TRACE_CAPACITY as u64) as usize] =
+            TraceEntry { tick, pid, event };
+        self.recorded = self.recorded.saturating_add(1);
+    }
+
+    pub fn clear(&mut self) {
+        self.recorded = 0;
+    }
+
+    /// Oldest first; `seq` numbers keep counting across wraps so two dumps can be lined up.
+    pub fn log(&self) {
+        let kept = self.recorded.min(TRACE_CAPACITY as u64);
+        let first = self.recorded - kept;
+        serial::write_fmt(format_args!(
+            "sched: trace enabled={} events={} kept={} dropped={} capacity={}\n",
+            if enabled() { "on" } else { "off" },
+            self.recorded,
+            kept,
+            first,
+            TRACE_CAPACITY
+        ));
+        for seq in first..self.recorded {
+            let entry = self.entries[(seq % TRACE_CAPACITY as u64) as usize];
+            let prefix = format_args!(
+                "sched: trace seq={seq} tick={} pid={}",
+                entry.tick, entry.pid
+            );
+            match entry.event {
+                TraceEvent::Run => serial::write_fmt(format_args!("{prefix} run\n")),
+                TraceEvent::Wake => serial::write_fmt(format_args!("{prefix} wake\n")),
+                TraceEvent::Syscall { number, rc } => serial::write_fmt(format_args!(
+                    "{prefix} syscall={} rc={rc}\n",
+                    arrostd::syscall::name(u64::from(number))
+                )),
+                TraceEvent::Exit { code } => {
+                    serial::write_fmt(format_args!("{prefix} exit code={code}\n"))
+                }
+                #[cfg(feature = "doom")]
+                TraceEvent::DoomPoll { polls } => {
+                    serial::write_fmt(format_args!("{prefix} doom_poll polls={polls}\n"))
+                }
+            }
+        }
+    }
+}
+
+pub fn init() {
+    if matches!(BUILD_ENABLED, Some("1" | "true" | "on")) {
+        set_enabled(true);
+    }
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) -> bool {
+    ENABLED.swap(enabled, Ordering::Relaxed) != enabled
+}
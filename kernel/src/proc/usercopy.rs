@@ -0,0 +1,110 @@
+// kernel/src/proc/usercopy.rs: user pointer range validation for pointer-taking syscalls.
+use crate::mem;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// Address space a task's pointers are resolved in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AddressSpace {
+    /// M4 tasks run on the kernel page tables; any present mapping is reachable.
+    Shared,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UserAccessError {
+    Null,
+    Overflow,
+    Unmapped,
+    ReadOnly,
+    Injected,
+}
+
+impl UserAccessError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Null => "null pointer",
+            Self::Overflow => "range overflow",
+            Self::Unmapped => "unmapped page",
+            Self::ReadOnly => "read-only page",
+            Self::Injected => "injected fault",
+        }
+    }
+}
+
+/// Fails the `n`th validation after arming so EFAULT paths can be exercised without bad pointers.
+pub struct FaultInjection {
+    countdown: u64,
+    fired: u64,
+}
+
+impl FaultInjection {
+    pub const fn new() -> Self {
+        Self {
+            countdown: 0,
+            fired: 0,
+        }
+    }
+
+    pub fn arm(&mut self, nth: u64) {
+        self.countdown = nth;
+    }
+
+    pub const fn armed(&self) -> u64 {
+        self.countdown
+    }
+
+    pub const fn fired(&self) -> u64 {
+        self.fired
+    }
+
+    fn trip(&mut self) -> bool {
+        if self.countdown == 0 {
+            return false;
+        }
+        self.countdown -= 1;
+        if self.countdown != 0 {
+            return false;
+        }
+        self.fired = self.fired.saturating_add(1);
+        true
+    }
+}
+
+/// Checks that `ptr..ptr+len` is a non-null range whose pages are all present in `space`,
+/// and writable when `write` is set. Zero-length ranges still require a non-null pointer.
+pub fn validate_user_range(
+    space: AddressSpace,
+    injection: &mut FaultInjection,
+    ptr: u64,
+    len: usize,
+    write: bool,
+) -> Result<(), UserAccessError> {
+    if injection.trip() {
+        return Err(UserAccessError::Injected);
+    }
+    if ptr == 0 {
+        return Err(UserAccessError::Null);
+    }
+    let end = ptr
+        .checked_add(len as u64)
+        .ok_or(UserAccessError::Overflow)?;
+
+    match space {
+        AddressSpace::Shared => {
+            let mut page = ptr & !(PAGE_SIZE - 1);
+            while page < end.max(ptr.saturating_add(1)) {
+                let Ok(virt) = usize::try_from(page) else {
+                    return Err(UserAccessError::Overflow);
+                };
+                if !mem::is_mapped(virt, false) {
+                    return Err(UserAccessError::Unmapped);
+                }
+                if write && !mem::is_mapped(virt, true) {
+                    return Err(UserAccessError::ReadOnly);
+                }
+                page = page.saturating_add(PAGE_SIZE);
+            }
+        }
+    }
+    Ok(())
+}
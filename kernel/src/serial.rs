@@ -1,12 +1,53 @@
 // kernel/src/serial.rs: early-boot COM1 serial output (0x3F8).
+mod frame;
+#[cfg(feature = "desktop")]
+mod ring;
+
+#[cfg(feature = "desktop")]
+use crate::log::Level;
 use core::arch::asm;
 use core::cell::UnsafeCell;
 use core::fmt::{self, Write};
 use core::hint::spin_loop;
 use core::sync::atomic::{AtomicBool, Ordering};
+use frame::FrameBuffer;
+#[cfg(feature = "desktop")]
+use ring::LogRing;
+
+pub use frame::CODEC as COMPRESS_CODEC;
+#[cfg(feature = "desktop")]
+pub use ring::CAPACITY as LOG_RING_BYTES;
+#[cfg(feature = "desktop")]
+pub use ring::LogRead;
 
 const COM1_BASE: u16 = 0x3F8;
-const MIRROR_CAPACITY: usize = 16384;
+const CAPTURE_CAPACITY: usize = 4096;
+const SGR_RESET: &str = "\x1b[0m";
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Output severity; warnings and errors are wrapped in ANSI SGR colors.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Severity {
+    const fn sgr(self) -> &'static str {
+        match self {
+            Self::Warning => "\x1b[33m",
+            Self::Error => "\x1b[31m",
+        }
+    }
+
+    #[cfg(feature = "desktop")]
+    const fn level(self) -> Level {
+        match self {
+            Self::Warning => Level::Warning,
+            Self::Error => Level::Error,
+        }
+    }
+}
 
 struct SpinLock {
     locked: AtomicBool,
@@ -42,14 +83,37 @@ struct SerialCell(UnsafeCell<SerialPort>);
 // SAFETY: access is serialized through `SERIAL_LOCK`, so interior mutation is synchronized.
 unsafe impl Sync for SerialCell {}
 
-struct MirrorCell(UnsafeCell<MirrorQueue>);
+#[cfg(feature = "desktop")]
+struct RingCell(UnsafeCell<LogRing>);
 
 // SAFETY: access is serialized through `SERIAL_LOCK`, so interior mutation is synchronized.
-unsafe impl Sync for MirrorCell {}
+#[cfg(feature = "desktop")]
+unsafe impl Sync for RingCell {}
 
 static SERIAL_LOCK: SpinLock = SpinLock::new();
 static SERIAL1: SerialCell = SerialCell(UnsafeCell::new(SerialPort::new(COM1_BASE)));
-static MIRROR_QUEUE: MirrorCell = MirrorCell(UnsafeCell::new(MirrorQueue::new()));
+#[cfg(feature = "desktop")]
+static LOG_RING: RingCell = RingCell(UnsafeCell::new(LogRing::new()));
+static CAPTURE_BUFFER: CaptureCell = CaptureCell(UnsafeCell::new(CaptureBuffer::new()));
+static FRAME_BUFFER: FrameCell = FrameCell(UnsafeCell::new(FrameBuffer::new()));
+
+struct CaptureCell(UnsafeCell<CaptureBuffer>);
+
+// SAFETY: access is serialized through `SERIAL_LOCK`, so interior mutation is synchronized.
+unsafe impl Sync for CaptureCell {}
+
+struct FrameCell(UnsafeCell<FrameBuffer>);
+
+// SAFETY: access is serialized through `SERIAL_LOCK`, so interior mutation is synchronized.
+unsafe impl Sync for FrameCell {}
+
+#[derive(Clone, Copy)]
+pub struct CompressStats {
+    pub enabled: bool,
+    pub frames: u64,
+    pub raw_bytes: u64,
+    pub wire_bytes: u64,
+}
 
 pub fn init() {
     with_serial(|serial| serial.init());
@@ -71,20 +135,142 @@ pub fn write_fmt(args: fmt::Arguments<'_>) {
     let _ = with_serial(|serial| serial.write_fmt(args));
 }
 
+/// Writes one line in the color of `severity`; the reset precedes the newline so the
+/// next line starts uncolored.
+pub fn write_severity_line(severity: Severity, message: &str) {
+    write_severity_fmt(severity, format_args!("{message}\n"));
+}
+
+pub fn write_severity_fmt(severity: Severity, args: fmt::Arguments<'_>) {
+    let _ = with_serial(|serial| {
+        #[cfg(feature = "desktop")]
+        {
+            serial.level = severity.level();
+        }
+        let mut line = SgrLine {
+            serial,
+            sgr: severity.sgr(),
+            open: false,
+        };
+        let result = line.write_fmt(args);
+        line.close();
+        #[cfg(feature = "desktop")]
+        {
+            serial.level = Level::Info;
+        }
+        result
+    });
+}
+
+/// Writes to COM1 only, skipping the log ring (and so the gfx shell mirror); for bulky
+/// diagnostics that only make sense on a host terminal.
+#[cfg(feature = "desktop")]
+pub fn write_str_unmirrored(message: &str) {
+    let _ = with_serial(|serial| {
+        serial.mirror = false;
+        let result = serial.write_str(message);
+        serial.mirror = true;
+        result
+    });
+}
+
+/// `write_str_unmirrored` for formatted output, e.g. diagnostics emitted while the gfx
+/// compositor itself is running.
+#[cfg(feature = "desktop")]
+pub fn write_fmt_unmirrored(args: fmt::Arguments<'_>) {
+    let _ = with_serial(|serial| {
+        serial.mirror = false;
+        let result = serial.write_fmt(args);
+        serial.mirror = true;
+        result
+    });
+}
+
+/// Runs `f` with serial output diverted into a buffer instead of COM1 and the log ring,
+/// then copies what fits into `out` and returns its length. `\r` is dropped and output past
+/// 4 KiB is lost. Not reentrant: `watch` uses it to diff one command's output per run.
+pub fn capture(out: &mut [u8], f: impl FnOnce()) -> usize {
+    with_serial(|serial| {
+        serial.capture = true;
+        // SAFETY: `SERIAL_LOCK` serializes mutable access to the capture buffer.
+        unsafe { (&mut *CAPTURE_BUFFER.0.get()).len = 0 };
+    });
+    f();
+    with_serial(|serial| {
+        serial.capture = false;
+        // SAFETY: `SERIAL_LOCK` serializes access to the capture buffer.
+        let buffer = unsafe { &*CAPTURE_BUFFER.0.get() };
+        let len = buffer.len.min(out.len());
+        out[..len].copy_from_slice(&buffer.bytes[..len]);
+        len
+    })
+}
+
+/// Runs `f` with its COM1 output sent as compressed `~z1` frame lines when `serial compress`
+/// is on; the gfx mirror still gets plain text. For bulk dumps (`fs export`, `sched trace`,
+/// `ui dump`) that take long at 38400 baud. Without compression `f` runs unchanged.
+pub fn compressed<R>(f: impl FnOnce() -> R) -> R {
+    let outer = with_serial(|serial| core::mem::replace(&mut serial.bulk, true));
+    let result = f();
+    with_serial(|serial| {
+        serial.flush_frame();
+        serial.bulk = outer;
+    });
+    result
+}
+
+/// Returns true when the mode changed. xtask turns it on after checking `COMPRESS_CODEC`.
+pub fn set_compress(enabled: bool) -> bool {
+    with_serial(|serial| core::mem::replace(&mut serial.compress, enabled) != enabled)
+}
+
+pub fn compress_stats() -> CompressStats {
+    with_serial(|serial| {
+        // SAFETY: `SERIAL_LOCK` serializes access to the frame buffer.
+        let frames = unsafe { &*FRAME_BUFFER.0.get() };
+        CompressStats {
+            enabled: serial.compress,
+            frames: frames.frames,
+            raw_bytes: frames.raw_bytes,
+            wire_bytes: frames.wire_bytes,
+        }
+    })
+}
+
+/// Clears the terminal on the serial side; the gfx shell mirror applies the same sequence.
+pub fn clear_screen() {
+    write_str(CLEAR_SCREEN);
+}
+
 pub fn try_read_byte() -> Option<u8> {
     with_serial(|serial| serial.read_byte())
 }
 
-pub fn pop_mirror_byte() -> Option<u8> {
-    let _guard = SERIAL_LOCK.lock();
-    // SAFETY: `SERIAL_LOCK` serializes mutable access to the mirror queue.
-    unsafe { (&mut *MIRROR_QUEUE.0.get()).pop() }
+/// Copies console output at `min` or above from the log ring, starting at `*cursor` (a count
+/// of bytes written since boot) and advancing it. A cursor that fell a whole ring behind jumps
+/// to the oldest byte still held; `LogRead::skipped` says how many it missed.
+#[cfg(feature = "desktop")]
+pub fn read_log(cursor: &mut u64, min: Level, out: &mut [u8]) -> LogRead {
+    with_ring(|ring| ring.read(cursor, min, out))
 }
 
-pub fn mirror_dropped() -> u64 {
+/// Position of the oldest byte the log ring still holds; a new reader starts here.
+#[cfg(feature = "desktop")]
+pub fn log_oldest() -> u64 {
+    with_ring(|ring| ring.oldest())
+}
+
+/// Bytes written to the log ring since boot.
+#[cfg(feature = "desktop")]
+pub fn log_written() -> u64 {
+    with_ring(|ring| ring.written())
+}
+
+#[cfg(feature = "desktop")]
+fn with_ring<R>(f: impl FnOnce(&LogRing) -> R) -> R {
     let _guard = SERIAL_LOCK.lock();
-    // SAFETY: `SERIAL_LOCK` serializes mutable access to the mirror queue.
-    unsafe { (&*MIRROR_QUEUE.0.get()).dropped }
+    // SAFETY: `SERIAL_LOCK` serializes access to the log ring.
+    unsafe { f(&*LOG_RING.0.get()) }
 }
 
 fn with_serial<R>(f: impl FnOnce(&mut SerialPort) -> R) -> R {
@@ -93,50 +279,54 @@ fn with_serial<R>(f: impl FnOnce(&mut SerialPort) -> R) -> R {
     unsafe { f(&mut *SERIAL1.0.get()) }
 }
 
-struct MirrorQueue {
-    bytes: [u8; MIRROR_CAPACITY],
-    head: usize,
-    tail: usize,
-    dropped: u64,
+struct CaptureBuffer {
+    bytes: [u8; CAPTURE_CAPACITY],
+    len: usize,
 }
 
-impl MirrorQueue {
+impl CaptureBuffer {
     const fn new() -> Self {
         Self {
-            bytes: [0; MIRROR_CAPACITY],
-            head: 0,
-            tail: 0,
-            dropped: 0,
+            bytes: [0; CAPTURE_CAPACITY],
+            len: 0,
         }
     }
 
     fn push(&mut self, byte: u8) {
-        let next_head = (self.head + 1) % MIRROR_CAPACITY;
-        if next_head == self.tail {
-            self.dropped = self.dropped.saturating_add(1);
-            return;
+        if byte != b'\r' && self.len < CAPTURE_CAPACITY {
+            self.bytes[self.len] = byte;
+            self.len += 1;
         }
-        self.bytes[self.head] = byte;
-        self.head = next_head;
-    }
-
-    fn pop(&mut self) -> Option<u8> {
-        if self.tail == self.head {
-            return None;
-        }
-        let byte = self.bytes[self.tail];
-        self.tail = (self.tail + 1) % MIRROR_CAPACITY;
-        Some(byte)
     }
 }
 
 struct SerialPort {
     base: u16,
+    /// Whether written bytes go into the log ring.
+    #[cfg(feature = "desktop")]
+    mirror: bool,
+    /// Level the log ring records for the bytes being written.
+    #[cfg(feature = "desktop")]
+    level: Level,
+    capture: bool,
+    /// `serial compress on`: bulk output goes out as frames.
+    compress: bool,
+    /// Inside `compressed`.
+    bulk: bool,
 }
 
 impl SerialPort {
     const fn new(base: u16) -> Self {
-        Self { base }
+        Self {
+            base,
+            #[cfg(feature = "desktop")]
+            mirror: true,
+            #[cfg(feature = "desktop")]
+            level: Level::Info,
+            capture: false,
+            compress: false,
+            bulk: false,
+        }
     }
 
     fn init(&mut self) {
@@ -163,6 +353,31 @@ impl SerialPort {
     }
 
     fn write_byte(&mut self, byte: u8) {
+        if self.capture {
+            // SAFETY: caller executes under `SERIAL_LOCK`, so buffer mutation is serialized.
+            unsafe { (&mut *CAPTURE_BUFFER.0.get()).push(byte) };
+            return;
+        }
+        if self.bulk && self.compress {
+            // The host sees `\n` line ends inside frames; the decoder restores them.
+            // SAFETY: caller executes under `SERIAL_LOCK`, so buffer mutation is serialized.
+            if byte != b'\r' && unsafe { (&mut *FRAME_BUFFER.0.get()).push(byte) } {
+                self.flush_frame();
+            }
+        } else {
+            self.transmit(byte);
+        }
+        // Only the desktop's shell mirror reads the log ring, so only it keeps one.
+        #[cfg(feature = "desktop")]
+        if self.mirror {
+            // SAFETY: caller executes under `SERIAL_LOCK`, so ring mutation is serialized.
+            unsafe {
+                (&mut *LOG_RING.0.get()).push(byte, self.level);
+            }
+        }
+    }
+
+    fn transmit(&mut self, byte: u8) {
         while !self.can_transmit() {
             spin_loop();
         }
@@ -171,10 +386,16 @@ impl SerialPort {
         unsafe {
             outb(self.base, byte);
         }
-        // SAFETY: caller executes under `SERIAL_LOCK`, so queue mutation is serialized.
-        unsafe {
-            (&mut *MIRROR_QUEUE.0.get()).push(byte);
-        }
+    }
+
+    fn flush_frame(&mut self) {
+        // SAFETY: caller executes under `SERIAL_LOCK`, so buffer mutation is serialized.
+        let frames = unsafe { &mut *FRAME_BUFFER.0.get() };
+        frames.flush(|line| {
+            for &byte in line {
+                self.transmit(byte);
+            }
+        });
     }
 
     fn read_byte(&mut self) -> Option<u8> {
@@ -200,6 +421,41 @@ impl Write for SerialPort {
     }
 }
 
+/// Wraps each line segment in `sgr` .. reset, leaving newlines outside the colored span.
+struct SgrLine<'a> {
+    serial: &'a mut SerialPort,
+    sgr: &'static str,
+    open: bool,
+}
+
+impl SgrLine<'_> {
+    fn close(&mut self) {
+        if self.open {
+            let _ = self.serial.write_str(SGR_RESET);
+            self.open = false;
+        }
+    }
+}
+
+impl Write for SgrLine<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (index, segment) in s.split('\n').enumerate() {
+            if index > 0 {
+                self.close();
+                self.serial.write_str("\n")?;
+            }
+            if !segment.is_empty() {
+                if !self.open {
+                    self.serial.write_str(self.sgr)?;
+                    self.open = true;
+                }
+                self.serial.write_str(segment)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 unsafe fn outb(port: u16, value: u8) {
     // SAFETY: caller guarantees that `port` and `value` are valid for the platform I/O operation.
     unsafe {
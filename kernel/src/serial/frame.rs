@@ -0,0 +1,160 @@
+// kernel/src/serial/frame.rs: `serial compress` framing, an RLE/varint codec whose blocks go out
+// as base64 `~z1` lines that `cargo xtask` expands back into text.
+use crate::fs::{base64, crc32::crc32};
+
+/// Codec name `serial compress` reports; xtask only enables compression when it matches.
+pub const CODEC: &str = "rle1";
+/// Prefix of every frame line: `~z1 <raw_len> <crc32> <base64>`.
+const LINE_PREFIX: &str = "~z1 ";
+/// Text bytes buffered per frame.
+const RAW_BYTES: usize = 384;
+/// Runs shorter than this stay in a literal; a run token costs a header and one byte.
+const MIN_RUN: usize = 4;
+/// Worst case: all literals split by runs of `MIN_RUN`, each token with a two-byte header.
+const ENCODED_BYTES: usize = RAW_BYTES * 2;
+const LINE_BYTES: usize = LINE_PREFIX.len() + 4 + 9 + 1 + base64::encoded_len(ENCODED_BYTES) + 2;
+
+/// Text collected while compression is active, and the running totals `serial compress` shows.
+pub struct FrameBuffer {
+    raw: [u8; RAW_BYTES],
+    len: usize,
+    pub frames: u64,
+    pub raw_bytes: u64,
+    pub wire_bytes: u64,
+}
+
+impl FrameBuffer {
+    pub const fn new() -> Self {
+        Self {
+            raw: [0; RAW_BYTES],
+            len: 0,
+            frames: 0,
+            raw_bytes: 0,
+            wire_bytes: 0,
+        }
+    }
+
+    /// Returns true when the buffer is full and must be flushed before the next push.
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.len < RAW_BYTES {
+            self.raw[self.len] = byte;
+            self.len += 1;
+        }
+        self.len == RAW_BYTES
+    }
+
+    /// Encodes the buffered text as one frame line and hands it to `emit`; nothing when empty.
+    pub fn flush(&mut self, mut emit: impl FnMut(&[u8])) {
+        if self.len == 0 {
+            return;
+        }
+        let raw = &self.raw[..self.len];
+        let mut encoded = [0u8; ENCODED_BYTES];
+        let encoded_len = encode(raw, &mut encoded);
+
+        let mut line = [0u8; LINE_BYTES];
+        let mut cursor = Cursor {
+            bytes: &mut line,
+            len: 0,
+        };
+        cursor.put(LINE_PREFIX.as_bytes());
+        cursor.put_decimal(raw.len());
+        cursor.put(b" ");
+        cursor.put_hex32(crc32(raw));
+        cursor.put(b" ");
+        let Cursor { bytes, len } = cursor;
+        let len = len + base64::encode(&encoded[..encoded_len], &mut bytes[len..]);
+        bytes[len..len + 2].copy_from_slice(b"\r\n");
+        let line = &bytes[..len + 2];
+        emit(line);
+
+        self.frames = self.frames.saturating_add(1);
+        self.raw_bytes = self.raw_bytes.saturating_add(raw.len() as u64);
+        self.wire_bytes = self.wire_bytes.saturating_add(line.len() as u64);
+        self.len = 0;
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a mut [u8],
+    len: usize,
+}
+
+impl Cursor<'_> {
+    fn put(&mut self, text: &[u8]) {
+        self.bytes[self.len..self.len + text.len()].copy_from_slice(text);
+        self.len += text.len();
+    }
+
+    fn put_decimal(&mut self, value: usize) {
+        let mut digits = [0u8; 20];
+        let mut start = digits.len();
+        let mut value = value;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (value % 10) as u8;
+            value /= 10;
+            if value == 0 {
+                break;
+            }
+        }
+        self.put(&digits[start..]);
+    }
+
+    fn put_hex32(&mut self, value: u32) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        for shift in (0..8).rev() {
+            self.put(&[HEX[(value >> (shift * 4) & 0xf) as usize]]);
+        }
+    }
+}
+
+/// Token stream of varint headers: an even header `2n` is followed by `n` literal bytes, an odd
+/// header `2n + 1` by one byte repeated `n + MIN_RUN` times.
+fn encode(input: &[u8], out: &mut [u8]) -> usize {
+    let mut written = 0;
+    let mut literal_start = 0;
+    let mut index = 0;
+    while index < input.len() {
+        let byte = input[index];
+        let run = input[index..]
+            .iter()
+            .take_while(|&&next| next == byte)
+            .count();
+        if run < MIN_RUN {
+            index += run;
+            continue;
+        }
+        written += encode_literal(&input[literal_start..index], &mut out[written..]);
+        written += encode_varint((((run - MIN_RUN) << 1) | 1) as u64, &mut out[written..]);
+        out[written] = byte;
+        written += 1;
+        index += run;
+        literal_start = index;
+    }
+    written + encode_literal(&input[literal_start..], &mut out[written..])
+}
+
+fn encode_literal(literal: &[u8], out: &mut [u8]) -> usize {
+    if literal.is_empty() {
+        return 0;
+    }
+    let header = encode_varint((literal.len() << 1) as u64, out);
+    out[header..header + literal.len()].copy_from_slice(literal);
+    header + literal.len()
+}
+
+/// LEB128: seven bits per byte, low group first, high bit set on all but the last.
+fn encode_varint(mut value: u64, out: &mut [u8]) -> usize {
+    let mut written = 0;
+    loop {
+        let low = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out[written] = low;
+            return written + 1;
+        }
+        out[written] = low | 0x80;
+        written += 1;
+    }
+}
@@ -0,0 +1,68 @@
+// kernel/src/serial/ring.rs: the console log ring. Every byte written to the console, apart from
+// `*_unmirrored` output and `capture`, is kept here with the level of the write it came from.
+// Readers keep their own position and copy out at their own pace. One that falls a whole ring
+// behind skips to the oldest byte still held and is told how much it missed: a slow reader
+// never stalls writers and never loses the newest output.
+use crate::log::Level;
+
+pub const CAPACITY: usize = 16384;
+
+pub(super) struct LogRing {
+    bytes: [u8; CAPACITY],
+    levels: [Level; CAPACITY],
+    /// Bytes written since boot; byte `n` sits at `n % CAPACITY` until `n + CAPACITY`.
+    written: u64,
+}
+
+/// Result of one `serial::read_log` call.
+#[derive(Clone, Copy)]
+pub struct LogRead {
+    /// Bytes copied into the caller's buffer.
+    pub len: usize,
+    /// Bytes overwritten before the reader got to them.
+    pub skipped: u64,
+}
+
+impl LogRing {
+    pub(super) const fn new() -> Self {
+        Self {
+            bytes: [0; CAPACITY],
+            levels: [Level::Info; CAPACITY],
+            written: 0,
+        }
+    }
+
+    pub(super) fn push(&mut self, byte: u8, level: Level) {
+        let slot = (self.written % CAPACITY as u64) as usize;
+        self.bytes[slot] = byte;
+        self.levels[slot] = level;
+        self.written += 1;
+    }
+
+    pub(super) const fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Oldest position still held.
+    pub(super) const fn oldest(&self) -> u64 {
+        self.written.saturating_sub(CAPACITY as u64)
+    }
+
+    /// Copies bytes at `min` or above from `*cursor` on into `out`, advancing `*cursor` past
+    /// everything it looked at, filtered or not.
+    pub(super) fn read(&self, cursor: &mut u64, min: Level, out: &mut [u8]) -> LogRead {
+        let oldest = self.oldest();
+        let skipped = oldest.saturating_sub(*cursor);
+        *cursor = (*cursor).clamp(oldest, self.written);
+        let mut len = 0;
+        while *cursor < self.written && len < out.len() {
+            let slot = (*cursor % CAPACITY as u64) as usize;
+            if self.levels[slot] >= min {
+                out[len] = self.bytes[slot];
+                len += 1;
+            }
+            *cursor += 1;
+        }
+        LogRead { len, skipped }
+    }
+}
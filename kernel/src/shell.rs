@@ -1,28 +1,80 @@
 // kernel/src/shell.rs: line-based in-kernel shell driven by keyboard events.
+use crate::arch::x86_64::{cpuid, fpu, power, stack};
+use crate::artifacts;
+#[cfg(feature = "audio")]
 use crate::audio;
-use crate::doom;
+use crate::bootchart;
+use crate::error;
+use crate::evlog;
+use crate::features;
 use crate::fs;
+#[cfg(feature = "desktop")]
 use crate::gfx;
+use crate::input_bus::{self, Consumer, Delivery, InputEvent, Subscription};
+use crate::input_replay;
 use crate::keyboard;
+use crate::log::{self, Subsystem};
+use crate::mem;
 use crate::mouse;
+#[cfg(feature = "net")]
 use crate::net;
 use crate::proc;
-use crate::serial;
+use crate::proc::executor;
+use crate::serial::{self, Severity};
+use crate::soft_assert;
 use crate::storage;
+use crate::sysinfo;
 use crate::time;
+use crate::utf8::Utf8Decoder;
+use alias::Aliases;
 use alloc::string::String;
+use alloc::vec;
 use arrostd::abi::{USERLAND_ABI_REVISION, USERLAND_INIT_APP, shell_prompt};
+use arrostd::args::{self, ArgsError};
+#[cfg(feature = "doom")]
+use autostart::AutostartMode;
 use core::cell::UnsafeCell;
-use core::fmt::Write;
 use core::str;
+#[cfg(feature = "doom")]
+use doom_commands::{HeldCaptureKey, SERIAL_CAPTURE_HELD_KEYS};
+use history::{Expansion, History};
+#[cfg(feature = "net")]
+use netconsole::NetConsole;
+#[cfg(feature = "desktop")]
+use ui_commands::{refresh_file_manager_list_view, refresh_file_manager_preview_view};
+use watch::Watch;
+
+mod alias;
+#[cfg(feature = "audio")]
+mod audio_commands;
+#[cfg(feature = "doom")]
+mod autostart;
+mod boot_config;
+#[cfg(feature = "doom")]
+mod doom_commands;
+#[cfg(feature = "fuzz")]
+mod fuzz;
+mod history;
+#[cfg(feature = "net")]
+mod net_commands;
+#[cfg(feature = "net")]
+mod netconsole;
+mod rc;
+mod script;
+#[cfg(feature = "desktop")]
+mod settings;
+#[cfg(feature = "desktop")]
+mod ui_commands;
+mod watch;
+
+pub use script::Status;
 
 const MAX_LINE_LEN: usize = 128;
-const SERIAL_CAPTURE_HELD_KEYS: usize = 8;
-const SERIAL_CAPTURE_HOLD_TICKS_DEFAULT: u64 = 8;
-const SERIAL_CAPTURE_HOLD_TICKS_MOVE: u64 = 12;
-const SERIAL_CAPTURE_HOLD_TICKS_ACTION: u64 = 14;
-const FILE_MANAGER_LIST_LINES: usize = 5;
-const FILE_MANAGER_PREVIEW_BYTES: usize = 180;
+/// Longest command assembled from `\`- or quote-continued lines.
+const MAX_CONTINUED_LEN: usize = 1024;
+const CONTINUATION_PROMPT: &str = "> ";
+/// Boot config key picking the tick source, `pit` or `virtual` (see `tick boot`).
+const TICK_SOURCE_KEY: &str = "time.source";
 const VERSION_MAJOR: &str = match option_env!("ARROST_VERSION_MAJOR") {
     Some(value) => value,
     None => "0",
@@ -43,28 +95,26 @@ unsafe impl Sync for ShellCell {}
 
 static SHELL_STATE: ShellCell = ShellCell(UnsafeCell::new(ShellState::new()));
 
-#[derive(Clone, Copy)]
-struct HeldCaptureKey {
-    byte: u8,
-    release_tick: u64,
-    active: bool,
-}
-
-impl HeldCaptureKey {
-    const fn inactive() -> Self {
-        Self {
-            byte: 0,
-            release_tick: 0,
-            active: false,
-        }
-    }
-}
-
 struct ShellState {
     line: [u8; MAX_LINE_LEN],
     len: usize,
+    /// Keyboard and mouse go to doom; stays false in a kernel without doom.
     doom_capture: bool,
+    #[cfg(feature = "doom")]
     held_serial_capture_keys: [HeldCaptureKey; SERIAL_CAPTURE_HELD_KEYS],
+    history: History,
+    aliases: Aliases,
+    watch: Watch,
+    /// Set by `fs import`; every line goes to it until the file is complete or aborted.
+    import: Option<fs::Import>,
+    #[cfg(feature = "net")]
+    netconsole: NetConsole,
+    /// Lines so far of a command that ended in `\` or inside a quote.
+    continuation: Option<String>,
+    /// Exit status of the last command, `$?`.
+    status: i32,
+    /// Pasted or netconsole text may be UTF-8; the line keeps whole characters only.
+    utf8: Utf8Decoder,
 }
 
 impl ShellState {
@@ -73,7 +123,17 @@ impl ShellState {
             line: [0; MAX_LINE_LEN],
             len: 0,
             doom_capture: false,
+            #[cfg(feature = "doom")]
             held_serial_capture_keys: [HeldCaptureKey::inactive(); SERIAL_CAPTURE_HELD_KEYS],
+            history: History::new(),
+            aliases: Aliases::new(),
+            watch: Watch::new(),
+            import: None,
+            #[cfg(feature = "net")]
+            netconsole: NetConsole::new(),
+            continuation: None,
+            status: 0,
+            utf8: Utf8Decoder::new(),
         }
     }
 
@@ -81,452 +141,577 @@ impl ShellState {
         self.len = 0;
     }
 
-    fn release_all_serial_capture_keys(&mut self) {
-        for slot in &mut self.held_serial_capture_keys {
-            if slot.active {
-                let _ = doom::inject_key_release(slot.byte);
-                *slot = HeldCaptureKey::inactive();
-            }
-        }
-    }
-
-    fn release_expired_serial_capture_keys(&mut self, now_ticks: u64) {
-        for slot in &mut self.held_serial_capture_keys {
-            if slot.active && now_ticks >= slot.release_tick {
-                let _ = doom::inject_key_release(slot.byte);
-                *slot = HeldCaptureKey::inactive();
-            }
-        }
-    }
-
-    fn refresh_serial_capture_key(&mut self, byte: u8, now_ticks: u64) {
-        let release_tick = now_ticks.saturating_add(serial_capture_hold_ticks(byte));
-        for slot in &mut self.held_serial_capture_keys {
-            if slot.active && slot.byte == byte {
-                slot.release_tick = release_tick;
-                return;
-            }
-        }
-
-        if !doom::inject_key(byte) {
-            return;
-        }
-
-        for slot in &mut self.held_serial_capture_keys {
-            if !slot.active {
-                *slot = HeldCaptureKey {
-                    byte,
-                    release_tick,
-                    active: true,
-                };
-                return;
-            }
-        }
-
-        let mut oldest_index = 0usize;
-        let mut oldest_tick = self.held_serial_capture_keys[0].release_tick;
-        for index in 1..SERIAL_CAPTURE_HELD_KEYS {
-            let tick = self.held_serial_capture_keys[index].release_tick;
-            if tick < oldest_tick {
-                oldest_tick = tick;
-                oldest_index = index;
-            }
-        }
-
-        let oldest = self.held_serial_capture_keys[oldest_index];
-        if oldest.active {
-            let _ = doom::inject_key_release(oldest.byte);
+    fn push_char(&mut self, ch: char) {
+        let mut encoded = [0u8; 4];
+        if let Some(echo) = push_line_char(&mut self.line, &mut self.len, ch, &mut encoded) {
+            serial::write_str(echo);
         }
-        self.held_serial_capture_keys[oldest_index] = HeldCaptureKey {
-            byte,
-            release_tick,
-            active: true,
-        };
-    }
-}
-
-fn serial_capture_hold_ticks(byte: u8) -> u64 {
-    match byte {
-        b'w' | b'a' | b's' | b'd' | b'W' | b'A' | b'S' | b'D' => SERIAL_CAPTURE_HOLD_TICKS_MOVE,
-        b' ' | b'e' | b'E' | b'f' | b'F' | b'\n' | b'\r' => SERIAL_CAPTURE_HOLD_TICKS_ACTION,
-        _ => SERIAL_CAPTURE_HOLD_TICKS_DEFAULT,
     }
 }
 
 pub fn init() {
+    // SAFETY: shell state is accessed on the main loop thread.
+    let shell = unsafe { &mut *SHELL_STATE.0.get() };
+    if let Ok(loaded) = shell.history.load() {
+        serial::write_fmt(format_args!(
+            "Shell: history loaded {} commands from {}\n",
+            loaded,
+            history::HISTORY_FILE
+        ));
+    }
     serial::write_line(
-        "Shell: line mode ready (commands: help, version, ticks, uptime, user, ps, syscalls, ls, cat, echo >, disk, ui, fm, doom, mouse, net, ping, udp send, udp last, curl, sync, reload, watch on|off; ui subcmd: redraw|next|minimize; doom subcmd: status|play|run|stop|ui|key|keyup|capture|view|mouse|audio|reset|source|doctor)",
+        "Shell: line mode ready (commands: help, version, sysinfo, ticks, uptime, bootchart, artifacts [verify], asserts, cpu features, stack usage, user, ps, kill, sched trace, evlog [on|off|clear|dump|tail], serial compress, syscalls [fault], errno, ls, cat, echo [>|>>], fs import|export|case|punch, log rotate|levels|quiet, disk [stats], kv [get|set|del], ui, fm, doom, mouse, audio route, audio devices, audio duck focus, audio tap [start|stop], input latency, input bus, input record|replay, tickless, tick [advance|source|boot], ifconfig [mtu|addr], dhcp [info|renew|release], arp, netstat, net, net join|leave|groups, net ports, net quiet, net bench udp|rx, ping, udp send, udp rtt, udp last, tcp [connect|listen|accept|send|recv|close], wol, service, fw [add|del|clear], netconsole [start|stop], settings [ui|close|set], telemetry start|stop, curl, history, !!, !n, alias, unalias, rc, run, iferr, $?, sync, reload, reboot, poweroff, restart net|audio|gfx, watch <ticks> <cmd>|stop|on|off; ui subcmd: redraw|next|minimize|tile|bench|checksum|cursor|rotate|night|brightness|contrast|debug|mirror|kiosk|dump|click close; mem map [addr]|hugepages|tasks|leaks [mark]|poison [check]; doom subcmd: status|play|run|stop|pause|resume|autopause|autostart|ui|key|keyup|capture|view|mouse|audio|reset|source|doctor)",
+    );
+    #[cfg(feature = "doom")]
+    doom_commands::subscribe_capture();
+    input_bus::subscribe(
+        Consumer::Shell,
+        Subscription {
+            handler: shell_input,
+            kinds: input_bus::INPUT_BYTES,
+            priority: input_bus::PRIORITY_SHELL,
+            capture: false,
+            escape_releases: false,
+            observe_captured: false,
+            on_release: None,
+        },
     );
     refresh_file_manager_list_view();
     print_prompt();
 }
 
 pub fn poll() {
-    while let Some(event) = keyboard::pop_key_event() {
-        process_keyboard_event(event);
-    }
-
-    while let Some(byte) = keyboard::pop_byte() {
-        if doom_capture_enabled() {
-            continue;
-        }
-        process_byte(byte);
-    }
     while let Some(byte) = serial::try_read_byte() {
         process_byte(byte);
     }
 
     // SAFETY: shell state is accessed on the main loop thread.
+    #[cfg(feature = "desktop")]
     let shell = unsafe { &mut *SHELL_STATE.0.get() };
+    // An import owns every line until it finishes; clicks wait for it.
+    #[cfg(feature = "desktop")]
+    if shell.import.is_none() {
+        ui_commands::run_window_actions(shell);
+    }
+    #[cfg(feature = "doom")]
     if shell.doom_capture {
         shell.release_expired_serial_capture_keys(time::ticks());
     }
 }
 
-fn process_keyboard_event(event: keyboard::KeyEvent) {
-    // SAFETY: shell is single-threaded and only mutated from main loop.
-    let shell = unsafe { &mut *SHELL_STATE.0.get() };
-    if !shell.doom_capture {
-        return;
-    }
-
-    let Some(byte) = map_doom_capture_key(event.code) else {
-        return;
+/// Input bus handler for the line editor: keyboard text nobody above it took.
+fn shell_input(event: InputEvent, _delivery: Delivery) -> bool {
+    let InputEvent::Byte(key) = event else {
+        return false;
     };
-    if byte == 0x1b && event.pressed {
-        shell.release_all_serial_capture_keys();
-        shell.doom_capture = false;
-        let _ = doom::set_capture(false);
-        serial::write_line("\ndoom: capture disabled");
-        print_prompt();
-        return;
-    }
-
-    if event.pressed {
-        let _ = doom::inject_key(byte);
+    let sink = if key.byte == b'\t' {
+        keyboard::InputSink::Gfx
     } else {
-        let _ = doom::inject_key_release(byte);
-    }
-}
-
-fn map_doom_capture_key(code: keyboard::KeyCode) -> Option<u8> {
-    match code {
-        keyboard::KeyCode::ArrowUp => Some(b'w'),
-        keyboard::KeyCode::ArrowDown => Some(b's'),
-        keyboard::KeyCode::ArrowLeft => Some(b'a'),
-        keyboard::KeyCode::ArrowRight => Some(b'd'),
-        keyboard::KeyCode::Byte(byte) => Some(byte),
-    }
-}
-
-fn doom_capture_enabled() -> bool {
-    // SAFETY: shell state is read on the main loop thread.
-    let shell = unsafe { &*SHELL_STATE.0.get() };
-    shell.doom_capture
+        keyboard::InputSink::Shell
+    };
+    keyboard::record_delivery(sink, key.tick);
+    process_byte(key.byte);
+    true
 }
 
 fn process_byte(byte: u8) {
     // SAFETY: shell is single-threaded and only mutated from main loop.
     let shell = unsafe { &mut *SHELL_STATE.0.get() };
+    #[cfg(feature = "doom")]
     if shell.doom_capture {
         if byte == 0x1b {
-            shell.release_all_serial_capture_keys();
-            shell.doom_capture = false;
-            let _ = doom::set_capture(false);
-            serial::write_line("\ndoom: capture disabled");
-            print_prompt();
+            doom_commands::end_doom_capture(shell);
             return;
         }
         shell.refresh_serial_capture_key(byte, time::ticks());
         return;
     }
-    if byte == b'\t' {
-        gfx::on_input_byte(byte);
+    let mut utf8 = shell.utf8;
+    utf8.push(byte, |ch| process_char(shell, ch));
+    shell.utf8 = utf8;
+}
+
+fn process_char(shell: &mut ShellState, ch: char) {
+    #[cfg(feature = "desktop")]
+    if ch == '\t' {
+        gfx::on_input_byte(b'\t');
     }
 
-    match byte {
-        b'\n' | b'\r' => {
+    match ch {
+        '\n' | '\r' => {
             serial::write_str("\n");
             run_command(shell);
             shell.clear();
-            if !shell.doom_capture {
+            if shell.continuation.is_some() {
+                serial::write_str(CONTINUATION_PROMPT);
+            } else if !shell.doom_capture {
                 print_prompt();
             }
         }
-        0x08 | 0x7f => {
+        #[cfg(feature = "audio")]
+        '\x07' => {
+            let _ = audio::bell();
+        }
+        '\x08' | '\x7f' => {
             if shell.len > 0 {
-                shell.len -= 1;
+                shell.len = pop_line_char(&shell.line, shell.len);
                 serial::write_str("\x08 \x08");
             }
         }
-        0x20..=0x7e => {
-            if shell.len < MAX_LINE_LEN.saturating_sub(1) {
-                shell.line[shell.len] = byte;
-                shell.len += 1;
-                serial::write_byte(byte);
-            }
-        }
+        ch if !ch.is_control() => shell.push_char(ch),
         _ => {}
     }
 }
 
+/// Appends `ch` to the line as UTF-8 if all of it fits below the length cap, and returns it
+/// for the echo.
+fn push_line_char<'a>(
+    line: &mut [u8; MAX_LINE_LEN],
+    len: &mut usize,
+    ch: char,
+    encoded: &'a mut [u8; 4],
+) -> Option<&'a str> {
+    let text = ch.encode_utf8(encoded);
+    let end = *len + text.len();
+    if end >= MAX_LINE_LEN {
+        return None;
+    }
+    line[*len..end].copy_from_slice(text.as_bytes());
+    *len = end;
+    Some(text)
+}
+
+/// Line length without its last character, so a backspace never leaves half of one.
+fn pop_line_char(line: &[u8], len: usize) -> usize {
+    str::from_utf8(&line[..len])
+        .ok()
+        .and_then(|text| text.char_indices().next_back())
+        .map_or(len.saturating_sub(1), |(start, _)| start)
+}
+
 fn run_command(shell: &mut ShellState) {
-    if shell.len == 0 {
+    if shell.len == 0 && shell.continuation.is_none() {
         return;
     }
 
     let input_owned = match str::from_utf8(&shell.line[..shell.len]) {
+        // Leading spaces of a continuation line may be inside a quote.
+        Ok(text) if shell.continuation.is_some() => String::from(text.trim_end()),
         Ok(text) => String::from(text.trim()),
         Err(_) => {
             serial::write_line("shell: invalid utf-8 input");
+            shell.continuation = None;
             return;
         }
     };
-    let input = input_owned.as_str();
-
-    if input == "ls" {
-        fs::list_to_serial();
+    if shell.import.is_some() {
+        import_line_to_serial(shell, &input_owned);
         return;
     }
-
-    if input == "cat" {
-        serial::write_line("usage: cat <file>");
+    let Some(input_owned) = continue_command(shell, input_owned) else {
         return;
-    }
-    if let Some(path) = input.strip_prefix("cat ") {
-        let path = path.trim();
-        if path.is_empty() {
-            serial::write_line("usage: cat <file>");
+    };
+    let input_owned = match shell.history.expand(&input_owned) {
+        Expansion::Unchanged => input_owned,
+        Expansion::Expanded(command) => {
+            serial::write_line(command);
+            String::from(command)
+        }
+        Expansion::NotFound => {
+            serial::write_severity_fmt(
+                Severity::Error,
+                format_args!("history: {input_owned}: event not found\n"),
+            );
             return;
         }
-        fs::cat_to_serial(path);
-        return;
+    };
+    let input = input_owned.as_str();
+    // History is saved one command per line.
+    if !input.contains('\n') {
+        shell.history.push(input);
     }
+    execute(shell, input);
+}
 
-    if let Some((text, path)) = parse_echo_redirect(input) {
-        fs::write_from_echo(path, text);
-        refresh_file_manager_list_view();
+/// Joins `line` onto a pending continuation. Returns the whole command once its quotes are
+/// closed and it no longer ends in `\`; otherwise keeps it pending for the next line.
+fn continue_command(shell: &mut ShellState, line: String) -> Option<String> {
+    let mut input = match shell.continuation.take() {
+        Some(mut pending) => {
+            pending.push_str(&line);
+            pending
+        }
+        None => line,
+    };
+    let mut buf = vec![0u8; input.len()];
+    let Err(error) = args::split(&input, &mut buf) else {
+        return Some(input);
+    };
+    let Some(joiner) = error.continuation_joiner() else {
+        // Commands that do not tokenize report their own errors.
+        return Some(input);
+    };
+    if error == ArgsError::TrailingBackslash {
+        input.pop();
+    }
+    input.push_str(joiner);
+    if input.len() > MAX_CONTINUED_LEN {
+        serial::write_severity_fmt(
+            Severity::Error,
+            format_args!("shell: continued command over {MAX_CONTINUED_LEN} bytes dropped\n"),
+        );
+        return None;
+    }
+    shell.continuation = Some(input);
+    None
+}
+
+/// Runs one command and records its exit status as `$?`.
+fn execute(shell: &mut ShellState, input: &str) {
+    let with_status = script::expand(input, shell.status);
+    let input = with_status.as_deref().unwrap_or(input);
+    let expanded = shell.aliases.expand(input);
+    let input = expanded.as_deref().unwrap_or(input);
+    if let Some(command) = input.strip_prefix("iferr ") {
+        // A skipped command leaves `$?` as it was.
+        if shell.status != 0 {
+            execute(shell, command.trim_start());
+        }
         return;
     }
-    if input.starts_with("echo ") || input == "echo" {
-        serial::write_line("usage: echo <text> > <file>");
+    if input == "run" || input.starts_with("run ") {
+        script::run_to_serial(shell, input[3..].trim());
         return;
     }
+    shell.status = dispatch(shell, input).code();
+}
 
-    if let Some(ip) = input.strip_prefix("ping ") {
-        let ip = ip.trim();
-        if ip.is_empty() {
-            serial::write_line("usage: ping <a.b.c.d>");
-            return;
+fn dispatch(shell: &mut ShellState, input: &str) -> Status {
+    if input == "alias" {
+        for (name, command) in shell.aliases.iter() {
+            serial::write_fmt(format_args!("alias {name}='{command}'\n"));
         }
-        net::ping_to_serial(ip);
-        return;
+        return Status::Ok;
     }
-
-    if input == "udp last" {
-        net::log_last_udp();
-        return;
+    if let Some(args) = input.strip_prefix("alias ") {
+        return alias_to_serial(&mut shell.aliases, args.trim());
     }
-    if let Some(rest) = input.strip_prefix("udp send ") {
-        match parse_udp_send(rest) {
-            Some((ip, port, payload)) => net::udp_send_to_serial(ip, port, payload),
-            None => serial::write_line("usage: udp send <a.b.c.d> <port> <text>"),
+    if let Some(name) = input.strip_prefix("unalias ") {
+        if !shell.aliases.remove(name.trim()) {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("alias: {}: not found\n", name.trim()),
+            );
+            return Status::Failed;
         }
-        return;
+        serial::write_fmt(format_args!("alias: removed {}\n", name.trim()));
+        return Status::Ok;
+    }
+    if input == "rc" {
+        let Some(report) = rc::run(shell) else {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("rc: {} not found\n", rc::RC_FILE),
+            );
+            return Status::Failed;
+        };
+        log_rc(&report);
+        return Status::Ok;
     }
-    if let Some(rest) = input.strip_prefix("curl ") {
-        net::curl_to_serial(rest.trim());
-        return;
+    if input == "history" {
+        for number in shell.history.first_number()..=shell.history.last_number() {
+            if let Some(command) = shell.history.get(number) {
+                serial::write_fmt(format_args!("{number:5}  {command}\n"));
+            }
+        }
+        return Status::Ok;
     }
-    if input == "doom key" {
-        serial::write_line(
-            "usage: doom key <w|a|s|d|x|up|down|left|right|stop|fire|use|enter|esc|tab|space>",
-        );
-        return;
+    if input == "history save" {
+        return save_history_to_serial(&shell.history);
     }
-    if input == "doom keyup" {
-        serial::write_line(
-            "usage: doom keyup <w|a|s|d|x|up|down|left|right|stop|fire|use|enter|esc|tab|space>",
+    if input == "history clear" {
+        shell.history.clear();
+        serial::write_line("history: cleared");
+        return Status::Ok;
+    }
+
+    if let Some(feature) = features::disabled_for(input) {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!(
+                "{}: not in this kernel (built without feature `{}`)\n",
+                input.split_whitespace().next().unwrap_or(input),
+                feature.as_str()
+            ),
         );
-        return;
+        return Status::Failed;
     }
-    if input == "doom capture" {
-        serial::write_fmt(format_args!(
-            "doom: capture={}\n",
-            if doom::capture_enabled() { "on" } else { "off" }
-        ));
-        return;
+
+    #[cfg(feature = "fuzz")]
+    if let Some(args) = input
+        .strip_prefix("fuzz")
+        .filter(|args| args.is_empty() || args.starts_with(' '))
+    {
+        return fuzz::execute(shell, args.trim());
     }
-    if input == "doom view" {
-        serial::write_fmt(format_args!(
-            "doom: viewport filter={} (usage: doom view <bilinear|nearest>)\n",
-            gfx::file_manager_doom_filter().as_str()
-        ));
-        return;
+
+    #[cfg(feature = "net")]
+    if let Some(status) = net_commands::dispatch(shell, input) {
+        return status;
     }
-    if input == "doom mouse" {
-        doom::log_status();
-        return;
+    #[cfg(feature = "audio")]
+    if let Some(status) = audio_commands::dispatch(input) {
+        return status;
     }
-    if input == "doom audio" {
-        serial::write_line("usage: doom audio <on|off|virtio|pcspk|status|test>");
-        return;
+    #[cfg(feature = "doom")]
+    if let Some(status) = doom_commands::dispatch(shell, input) {
+        return status;
     }
-    if input == "doom audio status" {
-        log_doom_audio_status();
-        return;
+    #[cfg(feature = "desktop")]
+    if let Some(status) = ui_commands::dispatch(input) {
+        return status;
     }
-    if input == "doom mouse y on" {
-        doom::set_mouse_y_enabled(true);
-        serial::write_line("doom: mouse y mapping enabled");
-        doom::render_ui_status();
-        return;
+
+    if input == "ls" {
+        fs::list_to_serial();
+        return Status::Ok;
     }
-    if input == "doom mouse y off" {
-        doom::set_mouse_y_enabled(false);
-        serial::write_line("doom: mouse y mapping disabled");
-        doom::render_ui_status();
-        return;
+
+    if input == "watch" {
+        log_watch(&shell.watch);
+        return Status::Ok;
+    }
+    if input == "watch stop" {
+        if !shell.watch.stop() {
+            serial::write_line("watch: not running");
+            return Status::Failed;
+        }
+        serial::write_line("watch: stopped");
+        return Status::Ok;
     }
-    if let Some(rest) = input.strip_prefix("doom mouse turn ") {
-        let value = rest.trim().parse::<i16>().ok();
-        match value {
-            Some(threshold) if doom::set_mouse_turn_threshold(threshold) => {
-                serial::write_fmt(format_args!(
-                    "doom: mouse turn threshold set to {}\n",
-                    threshold
-                ));
-                doom::render_ui_status();
-            }
-            _ => serial::write_line("usage: doom mouse turn <1..64>"),
+    if let Some(rest) = input.strip_prefix("watch ")
+        && let Some((interval, command)) = rest.split_once(' ')
+        && let Ok(interval) = interval.parse::<u64>()
+    {
+        return start_watch_to_serial(shell, interval, command.trim());
+    }
+
+    if input == "cat" {
+        return Status::usage("usage: cat <file>");
+    }
+    if let Some(path) = input.strip_prefix("cat ") {
+        let path = path.trim();
+        if path.is_empty() {
+            return Status::usage("usage: cat <file>");
         }
-        return;
+        return fs::cat_to_serial(path);
     }
-    if let Some(rest) = input.strip_prefix("doom mouse move ") {
-        let value = rest.trim().parse::<i16>().ok();
-        match value {
-            Some(threshold) if doom::set_mouse_move_threshold(threshold) => {
-                serial::write_fmt(format_args!(
-                    "doom: mouse move threshold set to {}\n",
-                    threshold
-                ));
-                doom::render_ui_status();
-            }
-            _ => serial::write_line("usage: doom mouse move <1..64>"),
+
+    if input.starts_with("echo ") {
+        let mut buf = vec![0u8; input.len()];
+        if let Some((text, path, append)) = parse_echo(input, &mut buf) {
+            let status = if append {
+                fs::append_from_echo(path, text)
+            } else {
+                fs::write_from_echo(path, text)
+            };
+            refresh_file_manager_list_view();
+            return status;
         }
-        return;
     }
-    if input == "doom capture on" {
-        if !doom::set_capture(true) {
-            serial::write_line("doom: capture requires `doom play` running");
-            return;
+    if input.starts_with("echo ") || input == "echo" {
+        let mut buf = vec![0u8; input.len()];
+        let Some(text) = parse_echo_print(input, &mut buf) else {
+            return Status::usage(
+                "usage: echo [text] | echo <text> > <file> | echo <text> >> <file>",
+            );
+        };
+        serial::write_line(text);
+        return Status::Ok;
+    }
+
+    if input == "log rotate" {
+        fs::rotation_to_serial();
+        return Status::Ok;
+    }
+    if input == "log levels" {
+        log::log_levels();
+        return Status::Ok;
+    }
+    if let Some(args) = input.strip_prefix("log quiet ") {
+        let mut parts = args.split_whitespace();
+        match (
+            parts.next().and_then(Subsystem::parse),
+            parts.next().and_then(parse_on_off),
+            parts.next(),
+        ) {
+            (Some(subsystem), Some(quiet), None) => set_log_quiet_to_serial(subsystem, quiet),
+            _ => {
+                return Status::usage(
+                    "usage: log quiet <net|gfx|doom|audio|fs|storage|proc> <on|off>",
+                );
+            }
         }
-        shell.doom_capture = true;
-        serial::write_line("doom: capture enabled (press ESC to exit)");
-        return;
+        return Status::Ok;
+    }
+    if let Some(args) = input.strip_prefix("log rotate ") {
+        let mut parts = args.split_whitespace();
+        return match (
+            parts.next(),
+            parts.next().and_then(|value| value.parse::<usize>().ok()),
+            parts.next(),
+        ) {
+            (Some(path), Some(max_bytes), None) => {
+                let status = fs::set_rotation_to_serial(path, max_bytes);
+                refresh_file_manager_list_view();
+                status
+            }
+            _ => Status::usage("usage: log rotate <file> <max-bytes>"),
+        };
     }
-    if input == "doom capture off" {
-        if shell.doom_capture {
-            shell.release_all_serial_capture_keys();
-            shell.doom_capture = false;
-            let _ = doom::set_capture(false);
-            serial::write_line("doom: capture disabled");
-        } else {
-            serial::write_line("doom: capture already off");
+
+    if input == "restart" || input.starts_with("restart ") {
+        return restart_to_serial(input[7..].trim());
+    }
+    if input == "kill" || input.starts_with("kill ") {
+        return proc::signal::kill_to_serial(&input[4..]);
+    }
+    if let Some(key) = input.strip_prefix("kv get ") {
+        return storage::kvstore::get_to_serial(key);
+    }
+    if let Some(args) = input.strip_prefix("kv set ") {
+        return storage::kvstore::set_to_serial(args);
+    }
+    if let Some(key) = input.strip_prefix("kv del ") {
+        return storage::kvstore::remove_to_serial(key);
+    }
+    if let Some(addr) = input.strip_prefix("mem map ") {
+        return mem::map::log_lookup(addr);
+    }
+    if let Some(rest) = input.strip_prefix("errno ") {
+        return match rest.trim().parse::<i32>() {
+            Ok(errno) => error::log_errno_to_serial(errno),
+            Err(_) => Status::usage("usage: errno [n]"),
+        };
+    }
+
+    if let Some(rest) = input.strip_prefix("syscalls fault ") {
+        match rest.trim() {
+            "off" => proc::inject_user_fault(0),
+            value => match value.parse::<u64>() {
+                Ok(nth) if nth > 0 => proc::inject_user_fault(nth),
+                _ => return Status::usage("usage: syscalls fault <n>|off"),
+            },
         }
-        return;
+        return Status::Ok;
     }
-    if let Some(rest) = input.strip_prefix("doom keyup ") {
-        match parse_doom_key(rest) {
-            Some(key) => {
-                if doom::inject_key_release(key) {
-                    serial::write_fmt(format_args!("doom: injected keyup {:#04x}\n", key));
-                    doom::render_ui_status();
-                } else {
-                    serial::write_line("doom: runtime not running in play mode");
+
+    if let Some(path) = input.strip_prefix("input record ")
+        && path.trim() != "stop"
+    {
+        return input_replay::record_to_serial(path);
+    }
+    if let Some(path) = input.strip_prefix("input replay ") {
+        return input_replay::replay_to_serial(path);
+    }
+    if let Some(rest) = input.strip_prefix("fs import ") {
+        return start_import_to_serial(shell, rest);
+    }
+    if let Some(rest) = input.strip_prefix("fs case") {
+        match rest.trim() {
+            "" => serial::write_fmt(format_args!(
+                "fs: case_insensitive={}\n",
+                if fs::case_insensitive() { "on" } else { "off" }
+            )),
+            value => match parse_on_off(value) {
+                Some(enabled) => {
+                    let changed = fs::set_case_insensitive(enabled);
+                    serial::write_fmt(format_args!(
+                        "fs: case_insensitive={}{}\n",
+                        if enabled { "on" } else { "off" },
+                        if changed { "" } else { " (unchanged)" }
+                    ));
+                    refresh_file_manager_list_view();
                 }
-            }
-            None => serial::write_line(
-                "usage: doom keyup <w|a|s|d|x|up|down|left|right|stop|fire|use|enter|esc|tab|space>",
-            ),
+                None => return Status::usage("usage: fs case [on|off]"),
+            },
         }
-        return;
+        return Status::Ok;
+    }
+    if let Some(rest) = input.strip_prefix("fs punch ") {
+        let mut parts = rest.split_whitespace();
+        return match (
+            parts.next(),
+            parts.next().and_then(|value| value.parse().ok()),
+            parts.next().and_then(|value| value.parse().ok()),
+            parts.next(),
+        ) {
+            (Some(path), Some(offset), Some(len), None) => {
+                let status = fs::punch_to_serial(path, offset, len);
+                refresh_file_manager_list_view();
+                status
+            }
+            _ => Status::usage("usage: fs punch <file> <offset> <len>"),
+        };
     }
-    if let Some(rest) = input.strip_prefix("doom key ") {
-        match parse_doom_key(rest) {
-            Some(key) => {
-                if doom::inject_key(key) {
-                    serial::write_fmt(format_args!("doom: injected key {:#04x}\n", key));
-                    doom::render_ui_status();
-                } else {
-                    serial::write_line("doom: runtime not running");
+    if let Some(path) = input.strip_prefix("fs export ") {
+        return serial::compressed(|| fs::export_to_serial(path));
+    }
+    if let Some(rest) = input.strip_prefix("serial compress") {
+        match rest.trim() {
+            "" => log_serial_compress(),
+            state => match parse_on_off(state) {
+                Some(enabled) => {
+                    serial::set_compress(enabled);
+                    log_serial_compress();
                 }
-            }
-            None => serial::write_line(
-                "usage: doom key <w|a|s|d|x|up|down|left|right|stop|fire|use|enter|esc|tab|space>",
-            ),
+                None => return Status::usage("usage: serial compress [on|off]"),
+            },
         }
-        return;
+        return Status::Ok;
+    }
+    if let Some(rest) = input.strip_prefix("evlog")
+        && (rest.is_empty() || rest.starts_with(' '))
+    {
+        return evlog_to_serial(rest.trim());
     }
-    if let Some(rest) = input.strip_prefix("doom audio ") {
+    if let Some(rest) = input.strip_prefix("sched trace") {
         match rest.trim() {
-            "off" => {
-                let _ = audio::set_mode(audio::AudioMode::Off);
-                serial::write_line("doom: audio mode set to off");
-            }
-            "on" => {
-                let mode = audio::set_mode(audio::AudioMode::Virtio);
-                serial::write_fmt(format_args!("doom: audio mode set to {}\n", mode.as_str()));
+            "" => serial::compressed(proc::log_trace),
+            "clear" => {
+                proc::clear_trace();
+                serial::write_line("sched: trace cleared");
             }
-            "virtio" | "pcm" => {
-                let mode = audio::set_mode(audio::AudioMode::Virtio);
-                serial::write_fmt(format_args!("doom: audio mode set to {}\n", mode.as_str()));
-            }
-            "pcspk" | "pcspeaker" => {
-                let _ = audio::set_mode(audio::AudioMode::PcSpeaker);
-                serial::write_line("doom: audio mode set to pcspk");
-            }
-            "status" => log_doom_audio_status(),
-            "test" => {
-                if audio::play_test_tone() {
-                    serial::write_line("doom: audio test tone queued");
-                } else {
-                    serial::write_line("doom: audio test unavailable (mode=off)");
+            state => match parse_on_off(state) {
+                Some(enabled) => {
+                    let changed = proc::set_trace(enabled);
+                    serial::write_fmt(format_args!(
+                        "sched: trace={}{}\n",
+                        if enabled { "on" } else { "off" },
+                        if changed { "" } else { " (unchanged)" }
+                    ));
                 }
-            }
-            _ => serial::write_line("usage: doom audio <on|off|virtio|pcspk|status|test>"),
+                None => return Status::usage("usage: sched trace [on|off|clear]"),
+            },
         }
-        return;
+        return Status::Ok;
     }
-    if let Some(rest) = input.strip_prefix("doom view ") {
-        let mode = rest.trim();
-        let changed = match mode {
-            "bilinear" | "smooth" => {
-                gfx::set_file_manager_doom_filter(gfx::DoomViewFilter::Bilinear)
-            }
-            "nearest" | "fast" => gfx::set_file_manager_doom_filter(gfx::DoomViewFilter::Nearest),
-            _ => {
-                serial::write_line("usage: doom view <bilinear|nearest>");
-                return;
-            }
-        };
-        serial::write_fmt(format_args!(
-            "doom: viewport filter={}{}\n",
-            gfx::file_manager_doom_filter().as_str(),
-            if changed { "" } else { " (unchanged)" }
-        ));
-        doom::render_ui_status();
-        return;
+    if let Some(rest) = input.strip_prefix("tick")
+        && (rest.is_empty() || rest.starts_with(' '))
+    {
+        return tick_to_serial(rest.trim());
     }
-    if handle_file_manager_command(input) {
-        return;
+    if let Some(status) = handle_file_manager_command(input) {
+        return status;
     }
 
     match input {
         "help" => {
             serial::write_line(
-                "help: help | version | ticks | uptime | user | ps | syscalls | ls | cat <file> | echo <text> > <file> | disk | ui | ui redraw | ui next | ui minimize | fm | fm list | fm open <file> | fm copy <src> <dst> | fm delete <file> | doom | doom status | doom source | doom doctor | doom play | doom run | doom stop | doom ui | doom key <dir> | doom keyup <dir> | doom capture [on|off] | doom view <bilinear|nearest> | doom mouse | doom mouse y <on|off> | doom mouse turn <1..64> | doom mouse move <1..64> | doom audio <on|off|virtio|pcspk|status|test> | doom reset | mouse | net | ping <ip> | udp send <ip> <port> <text> | udp last | curl <ip> <port> <text> | curl udp://<ip>:<port>/<payload> | curl http://<host|ip>[:port]/<path> | sync | reload | watch on | watch off",
+                "help: help | version | sysinfo | clear | ticks | uptime | bootchart | artifacts | artifacts verify | asserts | cpu features | stack usage | user | ps | kill [-INT|-TERM|-KILL] <pid> | sched trace [on|off|clear] | evlog | evlog on|off | evlog clear | evlog dump | evlog tail [n] | serial compress [on|off] | syscalls | syscalls fault <n>|off | errno [n] | ls | cat <file> | echo [text] | echo <text> > <file> | echo <text> >> <file> | fs import <name> <size> [crc32] | fs export <name> | fs case [on|off] | fs punch <file> <offset> <len> | log rotate | log rotate <file> <max-bytes> | log levels | log quiet <subsystem> <on|off> | disk | disk stats | kv | kv get <key> | kv set <key> <value> | kv del <key> | ui | ui redraw | ui next | ui minimize | ui tile <left|right|max|restore> | ui bench [frames] | ui checksum | ui cursor <light|dark> | ui rotate [0|90|180|270] | ui night [on|off] | ui brightness [-100..100] | ui contrast [50..200] | ui debug damage <on|off> | ui mirror [info|warning|error] | ui kiosk [on|off] | ui dump <shell|fm|doom|settings|app> | ui app | ui close app | ui click close <doom|settings|app> | settings | settings ui | settings close | settings set <key> <value> | mem map | mem map <virt> | mem hugepages [on|off] | mem tasks | mem leaks [mark] | mem poison [check] | fm | fm list | fm open <file> | fm copy <src> <dst> | fm delete <file> | fm stat <file> | fm verify [file] | doom | doom status | doom source | doom doctor | doom play | doom run | doom stop | doom pause | doom resume | doom autopause <off|minimized|unfocused> | doom autostart [off|play|kiosk] | doom ui | doom key <dir> | doom keyup <dir> | doom capture [on|off] | doom view <bilinear|nearest> | doom view ascii <on|off> | doom mouse | doom mouse y <on|off> | doom mouse turn <1..64> | doom mouse move <1..64> | doom audio <on|off|virtio|pcspk|status|test [s8|u8]> | doom audio play <file.wav> | doom reset | audio route [<doom|play|tone|beep|midi> <off|duck|on>] | audio beep [<hz> <ms> [flat|pluck|swell]] | audio devices | audio duck focus [on|off] | audio tap | audio tap start <file> [seconds] | audio tap stop | mouse | input latency [reset] | input bus | input record [<file>|stop] | input replay <file> | tickless [on|off|reset] | tick | tick advance <n> | tick source <pit|virtual> | tick boot <pit|virtual> | ifconfig | ifconfig mtu <576..9000> | ifconfig addr <a.b.c.d> | dhcp info | dhcp renew | dhcp release | arp | netstat | net | net quiet [on|off] | net join <group> | net leave <group> | net groups | net ports [<first> <last>] | net bench udp <ip> <port> <seconds> | net bench rx <seconds> [port] | ping <ip> | udp send <ip> <port> <text> | udp rtt <ip> <port> [probes] | udp last | tcp | tcp list | tcp connect <ip> <port> | tcp listen <port> | tcp accept <id> | tcp send <id> <text> | tcp recv <id> | tcp close <id> | wol <mac> [port] | service list | service start <echo-udp|echo-tcp|midi-udp> <port> | service stop <echo-udp|echo-tcp|midi-udp> <port> | fw | fw list | fw add <allow|deny> <udp|tcp|any> <a.b.c.d[/len]> <port|any> | fw del <n> | fw clear | netconsole | netconsole start [port] | netconsole stop | telemetry | telemetry start <ip> <port> <interval_ms> | telemetry stop | curl <ip> <port> <text> | curl udp://<ip>:<port>/<payload> | curl http://<host|ip>[:port]/<path> | history | history save | history clear | !! | !<n> | alias | alias <name>[=<command>] | unalias <name> | rc | run <file> | iferr <command> | sync | reload | reboot | poweroff | restart <net|audio|gfx> | watch | watch <ticks> <command> | watch stop | watch on | watch off",
             );
+            #[cfg(feature = "fuzz")]
+            fuzz::log_help();
         }
         "version" => {
             serial::write_fmt(format_args!(
@@ -534,9 +719,22 @@ fn run_command(shell: &mut ShellState) {
                 VERSION_MAJOR, VERSION_MINOR, VERSION_BUILD
             ));
         }
+        "sysinfo" => sysinfo::log_report(),
+        "clear" => {
+            serial::clear_screen();
+        }
         "ticks" => {
             serial::write_fmt(format_args!("ticks: {}\n", time::ticks()));
         }
+        "bootchart" => bootchart::log_chart(),
+        "artifacts" => artifacts::log_to_serial(),
+        "artifacts verify" => return artifacts::verify_to_serial(),
+        "asserts" => soft_assert::log_status(),
+        "stack usage" => stack::log_usage(),
+        "cpu features" => {
+            cpuid::log_features();
+            fpu::log_status();
+        }
         "uptime" => {
             let millis = time::uptime_millis();
             serial::write_fmt(format_args!(
@@ -557,92 +755,101 @@ fn run_command(shell: &mut ShellState) {
         "syscalls" => {
             proc::log_syscall_stats();
         }
+        "errno" => {
+            error::log_errno_table();
+        }
         "disk" => {
             storage::log_info();
         }
-        "doom" | "doom status" => doom::log_status(),
-        "doom source" => doom::log_doomgeneric_info(),
-        "doom doctor" => doom::log_doomgeneric_doctor(),
-        "doom play" => {
-            let start = doom::play(time::ticks());
-            match start {
-                doom::PlayStart::DoomGeneric => {
-                    serial::write_line("doom: play mode started (doomgeneric)");
-                }
-                doom::PlayStart::Fallback => {
-                    serial::write_line(
-                        "doom: doomgeneric not ready; starting fallback runtime (run scripts/vendor_doomgeneric.sh and provide user/doom/wad/doom1.wad)",
-                    );
-                }
-                doom::PlayStart::AlreadyRunning => {
-                    serial::write_line("doom: runtime already running");
-                }
-            }
-            if !matches!(start, doom::PlayStart::AlreadyRunning) {
-                if doom::set_capture(true) {
-                    shell.doom_capture = true;
-                    serial::write_line("doom: capture enabled (press ESC to exit)");
+        "disk stats" => fs::disk_stats_to_serial(),
+        "kv" => storage::kvstore::log_list(),
+        "mem hugepages" => {
+            serial::write_fmt(format_args!(
+                "mem: hugepages={} promoted_2m={}\n",
+                if mem::hugepage::enabled() {
+                    "on"
                 } else {
-                    shell.doom_capture = false;
-                    serial::write_line("doom: capture unavailable (fallback mode)");
-                }
-            }
-            doom::render_ui_status();
+                    "off"
+                },
+                mem::hugepage::promoted_count()
+            ));
         }
-        "doom run" => {
-            if doom::start(time::ticks()) {
-                serial::write_line("doom: runtime started");
+        "mem hugepages on" | "mem hugepages off" => {
+            let enabled = input.ends_with("on");
+            let rewritten = mem::hugepage::set_enabled(enabled);
+            serial::write_fmt(format_args!(
+                "mem: hugepages={} rewritten={}\n",
+                if enabled { "on" } else { "off" },
+                rewritten
+            ));
+        }
+        "mem map" => serial::compressed(mem::map::log_map),
+        "mem tasks" => proc::log_task_heaps(),
+        "mem leaks" => log_leaks(),
+        "mem poison" => mem::poison::log_status(),
+        "mem poison check" => {
+            if mem::poison::ENABLED {
+                let found = mem::poison::check_all();
+                serial::write_fmt(format_args!("mem: poison check violations={found}\n"));
+                return Status::check(found == 0);
             } else {
-                serial::write_line("doom: runtime already running");
+                serial::write_line("mem: heap poisoning off (rebuild with ARROST_HEAP_POISON=1)");
+                return Status::Failed;
             }
-            doom::render_ui_status();
-        }
-        "doom stop" => {
-            if doom::stop(time::ticks()) {
-                shell.release_all_serial_capture_keys();
-                shell.doom_capture = false;
-                let _ = doom::set_capture(false);
-                serial::write_line("doom: runtime stopped");
+        }
+        "mem leaks mark" => {
+            if mem::leak::ENABLED {
+                mem::leak::mark();
+                serial::write_line("mem: leaks baseline marked");
             } else {
-                serial::write_line("doom: runtime already stopped");
+                serial::write_line("mem: leak tracking off (rebuild with ARROST_LEAK_TRACK=1)");
+                return Status::Failed;
             }
-            doom::render_ui_status();
         }
-        "doom ui" => {
-            doom::render_ui_status();
-            serial::write_line("doom: ui status pushed to doom window");
-        }
-        "doom reset" => {
-            doom::reset(time::ticks());
-            doom::render_ui_status();
-            serial::write_line("doom: simulation reset");
+        "mouse" => {
+            mouse::log_info();
         }
-        "ui" => {
-            gfx::log_info();
+        "tickless" => {
+            time::log_idle_stats();
         }
-        "ui redraw" => {
-            gfx::redraw();
-            serial::write_line("ui: redraw requested");
+        "tickless on" => {
+            time::set_tickless(true);
+            serial::write_line("tickless: one-shot idle enabled");
         }
-        "ui next" => {
-            gfx::focus_next();
-            serial::write_line("ui: focus advanced");
+        "tickless off" => {
+            time::set_tickless(false);
+            serial::write_line("tickless: periodic idle restored");
         }
-        "ui minimize" => {
-            gfx::toggle_focused_minimize();
-            serial::write_line("ui: focused window minimize toggled");
+        "tickless reset" => {
+            time::reset_idle_stats();
+            serial::write_line("tickless: idle counters reset");
         }
-        "mouse" => {
-            mouse::log_info();
+        "input latency" => {
+            keyboard::log_latency();
         }
-        "net" => {
-            net::log_info();
+        "input latency reset" => {
+            keyboard::reset_latency();
+            serial::write_line("input: latency counters reset");
         }
+        "input bus" => log_input_bus(),
+        "input record" => input_replay::log_status(),
+        "input record stop" => return input_replay::stop_record_to_serial(),
         "sync" => {
-            fs::sync_to_disk_to_serial();
+            let history = save_history_to_serial(&shell.history);
+            let disk = fs::sync_to_disk_to_serial();
+            return if history == Status::Ok { disk } else { history };
         }
-        "reload" => {
-            fs::reload_from_disk_to_serial();
+        "reload" => return fs::reload_from_disk_to_serial(),
+        "reboot" | "poweroff" => {
+            // A failed save is reported but does not keep the machine up.
+            let _ = save_history_to_serial(&shell.history);
+            let _ = fs::sync_to_disk_to_serial();
+            serial::write_fmt(format_args!("{input}: going down\n"));
+            if input == "reboot" {
+                power::reboot()
+            } else {
+                power::power_off()
+            }
         }
         "watch on" => {
             time::set_heartbeat(true);
@@ -652,103 +859,185 @@ fn run_command(shell: &mut ShellState) {
             time::set_heartbeat(false);
             serial::write_line("watch: tick heartbeat disabled");
         }
+        _ => return Status::failed(format_args!("unknown command: {input}\n")),
+    }
+    Status::Ok
+}
+
+/// `restart net|audio|gfx`: tears one subsystem down and brings it up again as at boot, for
+/// trying driver changes without a reboot.
+fn restart_to_serial(target: &str) -> Status {
+    let feature = match target {
+        "net" => features::Feature::Net,
+        "audio" => features::Feature::Audio,
+        "gfx" => features::Feature::Desktop,
+        _ => return Status::usage("usage: restart <net|audio|gfx>"),
+    };
+    match target {
+        #[cfg(feature = "net")]
+        "net" => {
+            let started = time::ticks();
+            let report = net::restart();
+            let [a, b, c, d] = report.ipv4;
+            serial::write_fmt(format_args!(
+                "restart: net ready={} cfg={} ip={a}.{b}.{c}.{d} took_ms={}\n",
+                report.ready,
+                report.config_source,
+                time::ticks().saturating_sub(started) * 10
+            ));
+            Status::check(report.ready)
+        }
+        #[cfg(feature = "audio")]
+        "audio" => {
+            let started = time::ticks();
+            let report = audio::restart();
+            serial::write_fmt(format_args!(
+                "restart: audio backend={} detail={} rate={} channels={} took_ms={}\n",
+                report.backend,
+                report.detail,
+                report.rate_hz,
+                report.channels,
+                time::ticks().saturating_sub(started) * 10
+            ));
+            Status::Ok
+        }
+        #[cfg(feature = "desktop")]
+        "gfx" => {
+            let started = time::ticks();
+            match gfx::restart() {
+                Some(report) => {
+                    serial::write_fmt(format_args!(
+                        "restart: gfx {}x{} double_buffer={} rotate={}{} took_ms={}\n",
+                        report.width,
+                        report.height,
+                        report.double_buffer,
+                        report.rotation.degrees(),
+                        if report.rotation_reset {
+                            " (reset, no backbuffer)"
+                        } else {
+                            ""
+                        },
+                        time::ticks().saturating_sub(started) * 10
+                    ));
+                    Status::Ok
+                }
+                None => {
+                    serial::write_severity_line(Severity::Warning, "restart: gfx no framebuffer");
+                    Status::Failed
+                }
+            }
+        }
         _ => {
-            serial::write_fmt(format_args!("unknown command: {input}\n"));
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!(
+                    "restart: {target} not in this kernel (built without feature `{}`)\n",
+                    feature.as_str()
+                ),
+            );
+            Status::Failed
         }
     }
 }
 
-fn log_doom_audio_status() {
-    let status = audio::status();
+/// Per-tag live heap usage, with the change since the last `mem leaks mark` so a steady-state
+/// leak shows up as a delta that keeps growing.
+fn log_leaks() {
+    if !mem::leak::ENABLED {
+        serial::write_line("mem: leak tracking off (rebuild with ARROST_LEAK_TRACK=1)");
+        return;
+    }
+    let now = mem::leak::snapshot();
+    let baseline = mem::leak::baseline();
     serial::write_fmt(format_args!(
-        "doom: audio mode={} backend={} active={} hz={} pcm_evt={} pcm_samples={} pcm_sw={} pcm_min={} pcm_max={} pcm_q={} pcm_buf={} pcm_tx={} pcm_done={} pcm_drop={} pcm_frames={} pcm_drop_frames={} pcm_rate={} pcm_ch={} pcm_stream={} pcm_ctrl={:#x}\n",
-        status.mode.as_str(),
-        status.pcm_backend,
-        status.active,
-        status.tone_hz,
-        status.pcm_mix_events,
-        status.pcm_samples,
-        status.pcm_tone_switches,
-        status.pcm_hz_min,
-        status.pcm_hz_max,
-        status.pcm_queue_pending,
-        status.pcm_buffered_frames,
-        status.pcm_packets_submitted,
-        status.pcm_packets_completed,
-        status.pcm_packets_dropped,
-        status.pcm_frames_completed,
-        status.pcm_frames_dropped,
-        status.pcm_rate_hz,
-        status.pcm_channels,
-        status.pcm_stream_id,
-        status.pcm_last_ctrl_status
+        "mem: leaks baseline={} untracked={} unknown_frees={}\n",
+        if baseline.is_some() { "mark" } else { "none" },
+        now.untracked,
+        now.unknown_frees
     ));
+    for tag in mem::leak::AllocTag::ALL {
+        let usage = now.usage(tag);
+        let base = baseline
+            .map(|snapshot| snapshot.usage(tag))
+            .unwrap_or_default();
+        serial::write_fmt(format_args!(
+            "  {:<9} live={} bytes={} allocs={} frees={} delta_live={:+} delta_bytes={:+}\n",
+            tag.as_str(),
+            usage.live_count,
+            usage.live_bytes,
+            usage.allocs,
+            usage.frees,
+            usage.live_count as i64 - base.live_count as i64,
+            usage.live_bytes as i64 - base.live_bytes as i64
+        ));
+    }
 }
 
-fn parse_echo_redirect(input: &str) -> Option<(&str, &str)> {
-    if !input.starts_with("echo ") {
-        return None;
-    }
-    let (left, right) = input.split_once('>')?;
-    let text = left.strip_prefix("echo ")?.trim_end();
-    let path = right.trim();
-    if path.is_empty() {
-        return None;
+fn log_input_bus() {
+    let (entries, unclaimed) = input_bus::status();
+    let captor = input_bus::captor();
+    serial::write_fmt(format_args!(
+        "input: bus captor={} unclaimed={} user_dropped={}\n",
+        captor.map_or("none", Consumer::as_str),
+        unclaimed,
+        input_bus::user_queue_dropped()
+    ));
+    for entry in entries {
+        if !entry.subscribed {
+            serial::write_fmt(format_args!(
+                "input: {} subscribed=no\n",
+                entry.consumer.as_str()
+            ));
+            continue;
+        }
+        let mut kinds = [0u8; 16];
+        serial::write_fmt(format_args!(
+            "input: {} prio={} kinds={} capture={} esc={} delivered={} observed={} releases={}\n",
+            entry.consumer.as_str(),
+            entry.priority,
+            input_bus::kinds_name(entry.kinds, &mut kinds),
+            if entry.capture { "on" } else { "off" },
+            if entry.escape_releases {
+                "release"
+            } else {
+                "pass"
+            },
+            entry.delivered,
+            entry.observed,
+            entry.releases
+        ));
     }
-    Some((text, path))
 }
 
-fn parse_udp_send(input: &str) -> Option<(&str, u16, &str)> {
-    let mut parts = input.trim().splitn(3, ' ');
-    let ip = parts.next()?;
-    let port = parts.next()?.parse::<u16>().ok()?;
-    let payload = parts.next()?;
-    if payload.is_empty() {
+/// `echo <text> > <file>` or `echo <text> >> <file>`, with quoting; the operator must be a word
+/// of its own. Returns the text, the file and whether to append.
+fn parse_echo<'a>(input: &str, buf: &'a mut [u8]) -> Option<(&'a str, &'a str, bool)> {
+    let args = args::split(input, buf).ok()?;
+    let count = args.len();
+    if count < 3 || args.get(0)? != "echo" || args.is_quoted(count - 2) {
         return None;
     }
-    Some((ip, port, payload))
-}
-
-fn parse_doom_key(input: &str) -> Option<u8> {
-    let key = input.trim();
-    if key.is_empty() {
+    let append = match args.get(count - 2)? {
+        ">" => false,
+        ">>" => true,
+        _ => return None,
+    };
+    let path = args.get(count - 1)?;
+    if path.is_empty() {
         return None;
     }
+    Some((args.join(1..count - 2)?, path, append))
+}
 
-    if key.eq_ignore_ascii_case("up") {
-        return Some(b'w');
-    }
-    if key.eq_ignore_ascii_case("down") {
-        return Some(b's');
-    }
-    if key.eq_ignore_ascii_case("left") {
-        return Some(b'a');
-    }
-    if key.eq_ignore_ascii_case("right") {
-        return Some(b'd');
-    }
-    if key.eq_ignore_ascii_case("stop") {
-        return Some(b'x');
-    }
-    if key.eq_ignore_ascii_case("fire") || key.eq_ignore_ascii_case("space") {
-        return Some(b' ');
-    }
-    if key.eq_ignore_ascii_case("use") {
-        return Some(b'e');
-    }
-    if key.eq_ignore_ascii_case("enter") {
-        return Some(b'\n');
-    }
-    if key.eq_ignore_ascii_case("esc") || key.eq_ignore_ascii_case("escape") {
-        return Some(0x1b);
-    }
-    if key.eq_ignore_ascii_case("tab") {
-        return Some(b'\t');
-    }
-    if key.len() == 1 {
-        return key.as_bytes().first().copied();
+/// `echo [text]` without a redirect; `None` when a misplaced `>` or `>>` is left in.
+fn parse_echo_print<'a>(input: &str, buf: &'a mut [u8]) -> Option<&'a str> {
+    let args = args::split(input, buf).ok()?;
+    let redirect = (1..args.len())
+        .any(|index| !args.is_quoted(index) && matches!(args.get(index), Some(">" | ">>")));
+    if redirect {
+        return None;
     }
-    None
+    args.join(1..args.len())
 }
 
 fn parse_file_manager_copy(input: &str) -> Option<(&str, &str)> {
@@ -761,114 +1050,466 @@ fn parse_file_manager_copy(input: &str) -> Option<(&str, &str)> {
     Some((source, destination))
 }
 
-fn handle_file_manager_command(input: &str) -> bool {
-    match input {
+/// Status of an `fm` command; `None` when `input` is not one.
+fn handle_file_manager_command(input: &str) -> Option<Status> {
+    let status = match input {
         "fm" | "fm list" => {
             fs::list_to_serial();
             refresh_file_manager_list_view();
-            true
-        }
-        "fm open" => {
-            serial::write_line("usage: fm open <file>");
-            true
-        }
-        "fm copy" => {
-            serial::write_line("usage: fm copy <src> <dst>");
-            true
-        }
-        "fm delete" => {
-            serial::write_line("usage: fm delete <file>");
-            true
+            Status::Ok
         }
+        "fm open" => Status::usage("usage: fm open <file>"),
+        "fm copy" => Status::usage("usage: fm copy <src> <dst>"),
+        "fm delete" => Status::usage("usage: fm delete <file>"),
+        "fm stat" => Status::usage("usage: fm stat <file>"),
+        "fm verify" => fs::verify_to_serial(None),
         _ => {
             if let Some(path) = input.strip_prefix("fm open ") {
                 let path = path.trim();
                 if path.is_empty() {
-                    serial::write_line("usage: fm open <file>");
-                } else {
-                    let mut buffer = [0u8; fs::MAX_FILE_BYTES];
-                    match fs::read_file(path, &mut buffer) {
-                        Ok(len) => {
-                            fs::cat_to_serial(path);
-                            refresh_file_manager_preview_view(path, &buffer[..len]);
-                        }
-                        Err(err) => serial::write_fmt(format_args!(
-                            "fm: open {} ({})\n",
-                            path,
-                            err.as_str()
-                        )),
-                    }
+                    return Some(Status::usage("usage: fm open <file>"));
                 }
-                return true;
+                let mut buffer = [0u8; fs::MAX_FILE_BYTES];
+                return Some(match fs::read_file(path, &mut buffer) {
+                    Ok(len) => {
+                        let status = fs::cat_to_serial(path);
+                        refresh_file_manager_preview_view(path, &buffer[..len]);
+                        status
+                    }
+                    Err(err) => {
+                        serial::write_fmt(format_args!("fm: open {} ({})\n", path, err.as_str()));
+                        Status::Failed
+                    }
+                });
             }
 
             if let Some(rest) = input.strip_prefix("fm copy ") {
-                match parse_file_manager_copy(rest) {
+                return Some(match parse_file_manager_copy(rest) {
                     Some((source, destination)) => {
-                        fs::copy_file_to_serial(source, destination);
+                        let status = fs::copy_file_to_serial(source, destination);
                         refresh_file_manager_list_view();
+                        status
                     }
-                    None => serial::write_line("usage: fm copy <src> <dst>"),
-                }
-                return true;
+                    None => Status::usage("usage: fm copy <src> <dst>"),
+                });
+            }
+
+            if let Some(path) = input.strip_prefix("fm stat ") {
+                let path = path.trim();
+                return Some(if path.is_empty() {
+                    Status::usage("usage: fm stat <file>")
+                } else {
+                    fs::stat_to_serial(path)
+                });
+            }
+
+            if let Some(path) = input.strip_prefix("fm verify ") {
+                let path = path.trim();
+                return Some(fs::verify_to_serial((!path.is_empty()).then_some(path)));
             }
 
             if let Some(path) = input.strip_prefix("fm delete ") {
                 let path = path.trim();
                 if path.is_empty() {
-                    serial::write_line("usage: fm delete <file>");
-                } else {
-                    fs::delete_file_to_serial(path);
-                    refresh_file_manager_list_view();
+                    return Some(Status::usage("usage: fm delete <file>"));
                 }
-                return true;
+                let status = fs::delete_file_to_serial(path);
+                refresh_file_manager_list_view();
+                return Some(status);
             }
 
-            false
+            return None;
         }
+    };
+    Some(status)
+}
+
+/// Without the desktop there is no file manager window to keep in step.
+#[cfg(not(feature = "desktop"))]
+fn refresh_file_manager_list_view() {}
+
+#[cfg(not(feature = "desktop"))]
+fn refresh_file_manager_preview_view(_path: &str, _bytes: &[u8]) {}
+
+fn start_watch_to_serial(shell: &mut ShellState, interval_ticks: u64, command: &str) -> Status {
+    if !(1..=watch::MAX_INTERVAL_TICKS).contains(&interval_ticks)
+        || command.is_empty()
+        || command.starts_with("watch")
+        || command.starts_with('!')
+    {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!(
+                "usage: watch <1..{}> <command> (not watch or !history)\n",
+                watch::MAX_INTERVAL_TICKS
+            ),
+        );
+        return Status::Usage;
+    }
+    let generation = shell.watch.start(command, interval_ticks);
+    if !executor::spawn("watch", watch_task(generation, interval_ticks)) {
+        shell.watch.stop();
+        return Status::failed(format_args!("watch: start failed (no service slot)\n"));
     }
+    serial::write_fmt(format_args!(
+        "watch: `{}` every {} ticks, printing changed lines (watch stop to end)\n",
+        command, interval_ticks
+    ));
+    Status::Ok
 }
 
-fn refresh_file_manager_list_view() {
-    let mut entries = [fs::DirEntry::empty(); fs::MAX_FILES];
-    let count = fs::list_entries(&mut entries);
+/// Re-runs the watched command with its output captured and prints only what changed.
+async fn watch_task(generation: u64, interval_ticks: u64) {
+    let mut output = vec![0u8; watch::OUTPUT_CAPACITY];
+    let mut due = time::ticks();
+    loop {
+        // SAFETY: services are polled from the main loop between shell polls, never while a
+        // shell command is running.
+        let shell = unsafe { &mut *SHELL_STATE.0.get() };
+        if !shell.watch.is_current(generation) {
+            break;
+        }
+        let command = String::from(shell.watch.command());
+        // A background re-run leaves `$?` to the commands typed at the prompt.
+        let status = shell.status;
+        let len = serial::capture(&mut output, || execute(shell, &command));
+        shell.status = status;
+        shell.watch.report(&output[..len], time::ticks());
+        due = due.saturating_add(interval_ticks);
+        if executor::unless_terminated(executor::sleep_until(due))
+            .await
+            .is_none()
+        {
+            // SAFETY: as above.
+            let shell = unsafe { &mut *SHELL_STATE.0.get() };
+            if shell.watch.is_current(generation) {
+                shell.watch.stop();
+                serial::write_line("watch: stopped by signal");
+            }
+            break;
+        }
+    }
+}
 
-    let mut view = String::new();
-    let _ = writeln!(view, "FILES ({count})");
-    let _ = writeln!(view, "name               size");
-    for entry in entries.iter().take(count).take(FILE_MANAGER_LIST_LINES) {
-        let _ = writeln!(view, "{} {}b", entry.name(), entry.size());
+/// `alias <name>=<command>` defines, `alias <name>` shows one.
+fn alias_to_serial(aliases: &mut Aliases, args: &str) -> Status {
+    let Some((name, command)) = alias::parse_definition(args) else {
+        return match aliases.get(args) {
+            Some(command) => {
+                serial::write_fmt(format_args!("alias {args}='{command}'\n"));
+                Status::Ok
+            }
+            None => {
+                serial::write_severity_fmt(
+                    Severity::Warning,
+                    format_args!("alias: {args}: not found\n"),
+                );
+                Status::Failed
+            }
+        };
+    };
+    match aliases.set(name, command) {
+        Ok(replaced) => {
+            serial::write_fmt(format_args!(
+                "alias: {} {name}='{command}'\n",
+                if replaced { "replaced" } else { "added" }
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!(
+                    "alias: {name} not set ({}); max {} aliases, names of up to {} [A-Za-z0-9._-]\n",
+                    err.as_str(),
+                    alias::MAX_ALIASES,
+                    alias::MAX_ALIAS_NAME
+                ),
+            );
+            Status::Failed
+        }
     }
-    if count == 0 {
-        let _ = writeln!(view, "<empty>");
+}
+
+fn log_rc(report: &rc::RcReport) {
+    serial::write_fmt(format_args!(
+        "rc: {} ran={} skipped={}\n",
+        rc::RC_FILE,
+        report.ran,
+        report.skipped
+    ));
+}
+
+fn log_watch(watch: &Watch) {
+    if watch.active() {
+        serial::write_fmt(format_args!(
+            "watch: command=`{}` interval_ticks={} runs={} changed_runs={} heartbeat={}\n",
+            watch.command(),
+            watch.interval_ticks(),
+            watch.runs(),
+            watch.changed_runs(),
+            time::heartbeat_enabled()
+        ));
+    } else {
+        serial::write_fmt(format_args!(
+            "watch: idle heartbeat={} (usage: watch <ticks> <command> | watch stop | watch on|off)\n",
+            time::heartbeat_enabled()
+        ));
+    }
+}
+
+/// Runs the `doom.autostart` boot option once boot is complete: `play` starts doom with
+/// capture in a maximized window, `kiosk` also hides the other desktop windows. The `/RC` file
+/// runs last, so its commands see the boot config already applied.
+pub fn autostart() {
+    // SAFETY: shell state is accessed on the main loop thread.
+    let shell = unsafe { &mut *SHELL_STATE.0.get() };
+    if let Some(source) = boot_config::lookup(TICK_SOURCE_KEY, time::TickSource::parse) {
+        time::set_tick_source(source);
+        serial::write_fmt(format_args!(
+            "Shell: tick source={} from {}\n",
+            source.as_str(),
+            boot_config::BOOT_CONFIG_FILE
+        ));
+    }
+    #[cfg(feature = "desktop")]
+    settings::apply_from_config();
+    #[cfg(feature = "net")]
+    if shell.netconsole.load() {
+        let _ = netconsole::start_to_serial(&mut shell.netconsole, None);
+    }
+    #[cfg(feature = "doom")]
+    {
+        let (mode, source) = autostart::load();
+        serial::write_fmt(format_args!(
+            "Shell: doom autostart={} source={}\n",
+            mode.as_str(),
+            source.as_str()
+        ));
+        if mode != AutostartMode::Off {
+            if mode == AutostartMode::Kiosk {
+                let _ = gfx::set_kiosk(true);
+            }
+            doom_commands::start_doom_play(shell);
+            let _ = gfx::maximize_doom_window();
+        }
+    }
+    match rc::run(shell) {
+        Some(report) => log_rc(&report),
+        None => serial::write_fmt(format_args!("Shell: rc={} absent\n", rc::RC_FILE)),
+    }
+}
+
+/// One line xtask parses to negotiate framing: it only turns compression on for a codec it
+/// knows.
+fn log_serial_compress() {
+    let stats = serial::compress_stats();
+    serial::write_fmt(format_args!(
+        "serial: compress={} codec={} frames={} raw_bytes={} wire_bytes={}\n",
+        if stats.enabled { "on" } else { "off" },
+        serial::COMPRESS_CODEC,
+        stats.frames,
+        stats.raw_bytes,
+        stats.wire_bytes
+    ));
+}
+
+fn parse_on_off(text: &str) -> Option<bool> {
+    match text.trim() {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
     }
-    let _ = writeln!(view, "fm open <file>");
-    let _ = writeln!(view, "fm copy <src> <dst>");
-    let _ = writeln!(view, "fm delete <file>");
+}
 
-    gfx::set_file_manager_text(&view);
+fn set_log_quiet_to_serial(subsystem: Subsystem, quiet: bool) {
+    let changed = log::set_quiet(subsystem, quiet);
+    serial::write_fmt(format_args!(
+        "log: {} verbosity={}{}\n",
+        subsystem.as_str(),
+        if quiet { "quiet" } else { "normal" },
+        if changed { "" } else { " (unchanged)" }
+    ));
+    #[cfg(feature = "desktop")]
+    settings::refresh_view();
 }
 
-fn refresh_file_manager_preview_view(path: &str, bytes: &[u8]) {
-    let mut view = String::new();
-    let _ = writeln!(view, "OPEN {}", path.trim());
-    let _ = writeln!(view, "{} bytes", bytes.len());
-    let _ = writeln!(view, "----------------");
+/// `fs import <name> <size> [crc32]`: later lines are base64 until `size` bytes arrived.
+fn start_import_to_serial(shell: &mut ShellState, args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    let (Some(name), Some(size), crc) = (parts.next(), parts.next(), parts.next()) else {
+        return Status::usage("usage: fs import <name> <size> [crc32]");
+    };
+    let Ok(size) = size.parse::<usize>() else {
+        return Status::usage("usage: fs import <name> <size> [crc32]");
+    };
+    let crc = match crc.map(|crc| u32::from_str_radix(crc, 16)) {
+        None => None,
+        Some(Ok(crc)) => Some(crc),
+        Some(Err(_)) => return Status::usage("fs: import crc32 must be hex"),
+    };
+    match fs::Import::begin(name, size, crc) {
+        Ok(import) => {
+            serial::write_fmt(format_args!(
+                "fs: import ready name={} size={} (send base64 lines, `abort` cancels)\n",
+                import.name(),
+                import.size()
+            ));
+            shell.import = Some(import);
+            if size == 0 {
+                import_line_to_serial(shell, "");
+            }
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("fs: import {} ({})\n", name, err.as_str())),
+    }
+}
 
-    for &byte in bytes.iter().take(FILE_MANAGER_PREVIEW_BYTES) {
-        match byte {
-            b'\r' => {}
-            b'\n' => view.push('\n'),
-            0x20..=0x7e => view.push(byte as char),
-            _ => view.push('.'),
+fn import_line_to_serial(shell: &mut ShellState, line: &str) {
+    let Some(import) = shell.import.as_mut() else {
+        return;
+    };
+    if line == "abort" {
+        serial::write_fmt(format_args!("fs: import aborted name={}\n", import.name()));
+        shell.import = None;
+        return;
+    }
+    match import.push_line(line) {
+        Ok(fs::ImportStep::NeedMore { received }) => serial::write_fmt(format_args!(
+            "fs: import got={received}/{}\n",
+            import.size()
+        )),
+        Ok(fs::ImportStep::Done { bytes, crc }) => {
+            serial::write_fmt(format_args!(
+                "fs: import done name={} bytes={bytes} crc32={crc:08x}\n",
+                import.name()
+            ));
+            shell.import = None;
+        }
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Error,
+                format_args!(
+                    "fs: import failed name={} ({})\n",
+                    import.name(),
+                    err.as_str()
+                ),
+            );
+            shell.import = None;
         }
     }
-    if bytes.len() > FILE_MANAGER_PREVIEW_BYTES {
-        let _ = writeln!(view, "\n...truncated...");
+}
+
+fn save_history_to_serial(history: &History) -> Status {
+    match history.save() {
+        Ok(saved) => {
+            serial::write_fmt(format_args!(
+                "history: saved {} commands to {}\n",
+                saved,
+                history::HISTORY_FILE
+            ));
+            Status::Ok
+        }
+        Err(err) => Status::failed(format_args!("history: save failed ({})\n", err.as_str())),
     }
-    let _ = writeln!(view, "\nfm list");
+}
 
-    gfx::set_file_manager_text(&view);
+/// `tick [advance <n>|source <pit|virtual>|boot <pit|virtual>]`.
+fn tick_to_serial(args: &str) -> Status {
+    let (command, value) = args.split_once(' ').unwrap_or((args, ""));
+    let value = value.trim();
+    match command {
+        "" => {
+            time::log_tick_source();
+            let boot = boot_config::lookup(TICK_SOURCE_KEY, time::TickSource::parse)
+                .unwrap_or(time::TickSource::Pit);
+            serial::write_fmt(format_args!(
+                "tick: boot={} config={}\n",
+                boot.as_str(),
+                boot_config::BOOT_CONFIG_FILE
+            ));
+            Status::Ok
+        }
+        "advance" => {
+            let Some(count) = value
+                .parse::<u64>()
+                .ok()
+                .filter(|count| (1..=time::MAX_ADVANCE_TICKS).contains(count))
+            else {
+                serial::write_severity_fmt(
+                    Severity::Warning,
+                    format_args!("usage: tick advance <1..{}>\n", time::MAX_ADVANCE_TICKS),
+                );
+                return Status::Usage;
+            };
+            match time::advance(count) {
+                Some(now) => {
+                    serial::write_fmt(format_args!("tick: advanced={count} ticks={now}\n"));
+                    Status::Ok
+                }
+                None => {
+                    serial::write_severity_line(
+                        Severity::Warning,
+                        "tick: advance needs the virtual source (tick source virtual)",
+                    );
+                    Status::Failed
+                }
+            }
+        }
+        "source" => match time::TickSource::parse(value) {
+            Some(source) => {
+                let changed = time::set_tick_source(source);
+                serial::write_fmt(format_args!(
+                    "tick: source={}{}\n",
+                    source.as_str(),
+                    if changed { "" } else { " (unchanged)" }
+                ));
+                Status::Ok
+            }
+            None => Status::usage("usage: tick source <pit|virtual>"),
+        },
+        "boot" => match time::TickSource::parse(value) {
+            Some(source) => match boot_config::set(TICK_SOURCE_KEY, source.as_str()) {
+                Ok(_) => {
+                    serial::write_fmt(format_args!(
+                        "tick: boot={} saved to {} (sync to keep it across reboots)\n",
+                        source.as_str(),
+                        boot_config::BOOT_CONFIG_FILE
+                    ));
+                    Status::Ok
+                }
+                Err(err) => {
+                    Status::failed(format_args!("tick: boot save failed ({})\n", err.as_str()))
+                }
+            },
+            None => Status::usage("usage: tick boot <pit|virtual>"),
+        },
+        _ => Status::usage("usage: tick [advance <n>|source <pit|virtual>|boot <pit|virtual>]"),
+    }
+}
+
+fn evlog_to_serial(args: &str) -> Status {
+    const DEFAULT_TAIL: usize = 20;
+    match args {
+        "" => evlog::log_status(),
+        "dump" => evlog::dump_to_serial(),
+        "clear" => {
+            evlog::clear();
+            serial::write_line("evlog: cleared");
+        }
+        "tail" => evlog::log_tail(DEFAULT_TAIL),
+        _ => {
+            if let Some(enabled) = parse_on_off(args) {
+                evlog::set_enabled(enabled);
+                serial::write_fmt(format_args!("evlog: enabled={enabled}\n"));
+            } else if let Some(Ok(count)) = args.strip_prefix("tail ").map(str::parse::<usize>) {
+                evlog::log_tail(count);
+            } else {
+                return Status::usage("usage: evlog [on|off|clear|dump|tail [n]]");
+            }
+        }
+    }
+    Status::Ok
 }
 
 fn print_prompt() {
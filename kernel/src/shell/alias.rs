@@ -0,0 +1,157 @@
+// kernel/src/shell/alias.rs: `alias name=command` table. The first word of a command that
+// names an alias is replaced by its command once; the result is not expanded again.
+use super::MAX_LINE_LEN;
+use alloc::string::String;
+
+pub const MAX_ALIASES: usize = 16;
+pub const MAX_ALIAS_NAME: usize = 16;
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum AliasError {
+    /// Empty, too long, not `[A-Za-z0-9._-]`, or `alias`/`unalias` themselves.
+    BadName,
+    EmptyCommand,
+    CommandTooLong,
+    TableFull,
+}
+
+impl AliasError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::BadName => "bad_name",
+            Self::EmptyCommand => "empty_command",
+            Self::CommandTooLong => "command_too_long",
+            Self::TableFull => "table_full",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct AliasEntry {
+    name: [u8; MAX_ALIAS_NAME],
+    name_len: usize,
+    command: [u8; MAX_LINE_LEN],
+    command_len: usize,
+}
+
+impl AliasEntry {
+    const fn empty() -> Self {
+        Self {
+            name: [0; MAX_ALIAS_NAME],
+            name_len: 0,
+            command: [0; MAX_LINE_LEN],
+            command_len: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len]).unwrap_or("")
+    }
+
+    fn command(&self) -> &str {
+        core::str::from_utf8(&self.command[..self.command_len]).unwrap_or("")
+    }
+}
+
+pub struct Aliases {
+    entries: [AliasEntry; MAX_ALIASES],
+}
+
+impl Aliases {
+    pub const fn new() -> Self {
+        Self {
+            entries: [AliasEntry::empty(); MAX_ALIASES],
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.name_len > 0 && entry.name() == name)
+    }
+
+    /// Defines or replaces `name`; returns true when it replaced an existing alias.
+    pub fn set(&mut self, name: &str, command: &str) -> Result<bool, AliasError> {
+        if !valid_name(name) {
+            return Err(AliasError::BadName);
+        }
+        let command = command.trim();
+        if command.is_empty() {
+            return Err(AliasError::EmptyCommand);
+        }
+        if command.len() > MAX_LINE_LEN {
+            return Err(AliasError::CommandTooLong);
+        }
+        let existing = self.find(name);
+        let index = existing
+            .or_else(|| self.entries.iter().position(|entry| entry.name_len == 0))
+            .ok_or(AliasError::TableFull)?;
+        let entry = &mut self.entries[index];
+        entry.name[..name.len()].copy_from_slice(name.as_bytes());
+        entry.name_len = name.len();
+        entry.command[..command.len()].copy_from_slice(command.as_bytes());
+        entry.command_len = command.len();
+        Ok(existing.is_some())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        let Some(index) = self.find(name) else {
+            return false;
+        };
+        self.entries[index] = AliasEntry::empty();
+        true
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.find(name).map(|index| self.entries[index].command())
+    }
+
+    /// `(name, command)` pairs in definition slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.name_len > 0)
+            .map(|entry| (entry.name(), entry.command()))
+    }
+
+    /// `input` with its first word replaced by the alias command, or `None` when it names no
+    /// alias.
+    pub fn expand(&self, input: &str) -> Option<String> {
+        let (word, rest) = match input.split_once(char::is_whitespace) {
+            Some((word, rest)) => (word, rest),
+            None => (input, ""),
+        };
+        let command = self.get(word)?;
+        let mut expanded = String::from(command);
+        if !rest.is_empty() {
+            expanded.push(' ');
+            expanded.push_str(rest);
+        }
+        Some(expanded)
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_ALIAS_NAME
+        && name != "alias"
+        && name != "unalias"
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'_' | b'-'))
+}
+
+/// `name=command`, with one pair of matching quotes around the command removed.
+pub fn parse_definition(text: &str) -> Option<(&str, &str)> {
+    let (name, command) = text.split_once('=')?;
+    let command = command.trim();
+    let command = ['\'', '"']
+        .into_iter()
+        .find_map(|quote| {
+            command
+                .strip_prefix(quote)
+                .and_then(|inner| inner.strip_suffix(quote))
+        })
+        .unwrap_or(command);
+    Some((name.trim(), command))
+}
@@ -0,0 +1,264 @@
+// kernel/src/shell/audio_commands.rs: shell commands of the `audio` feature: routes, beeps,
+// devices, the tap and focus ducking.
+use super::Status;
+use crate::audio;
+use crate::serial::{self, Severity};
+
+/// Runs `input` if it is an audio command; `None` leaves it to the rest of `dispatch`.
+pub(super) fn dispatch(input: &str) -> Option<Status> {
+    if let Some(rest) = input.strip_prefix("audio route") {
+        let mut parts = rest.split_whitespace();
+        match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => log_audio_routes(),
+            (Some(owner), Some(route), None) => {
+                match (audio::AudioOwner::parse(owner), audio::Route::parse(route)) {
+                    (Some(owner), Some(route)) => {
+                        audio::set_route(owner, route);
+                        log_audio_routes();
+                    }
+                    _ => {
+                        return Some(Status::usage(
+                            "usage: audio route [<doom|play|tone|beep|midi> <off|duck|on>]",
+                        ));
+                    }
+                }
+            }
+            _ => {
+                return Some(Status::usage(
+                    "usage: audio route [<doom|play|tone|beep|midi> <off|duck|on>]",
+                ));
+            }
+        }
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("audio beep") {
+        let mut parts = rest.split_whitespace();
+        return Some(
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (None, ..) => {
+                    log_beep_status();
+                    Status::Ok
+                }
+                (Some(hz), Some(ms), envelope, None) => {
+                    let envelope =
+                        envelope.map_or(Some(audio::Envelope::Flat), audio::Envelope::parse);
+                    match (hz.parse::<u32>(), ms.parse::<u32>(), envelope) {
+                        (Ok(hz), Ok(ms), Some(envelope)) => {
+                            let queued = audio::beep(hz, ms, envelope);
+                            if queued {
+                                serial::write_fmt(format_args!(
+                                    "audio: beep hz={hz} ms={ms} envelope={}\n",
+                                    envelope.as_str()
+                                ));
+                            } else {
+                                serial::write_severity_line(
+                                    Severity::Warning,
+                                    "audio: beep queue full",
+                                );
+                            }
+                            log_beep_status();
+                            Status::check(queued)
+                        }
+                        _ => Status::usage("usage: audio beep [<hz> <ms> [flat|pluck|swell]]"),
+                    }
+                }
+                _ => Status::usage("usage: audio beep [<hz> <ms> [flat|pluck|swell]]"),
+            },
+        );
+    }
+    if input == "audio devices" {
+        log_audio_devices();
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("audio tap") {
+        return Some(audio_tap_to_serial(rest.trim()));
+    }
+    if let Some(rest) = input.strip_prefix("audio duck focus") {
+        match rest.trim() {
+            "on" => audio::set_focus_duck(true),
+            "off" => audio::set_focus_duck(false),
+            "" => {}
+            _ => return Some(Status::usage("usage: audio duck focus [on|off]")),
+        }
+        log_audio_routes();
+        return Some(Status::Ok);
+    }
+    None
+}
+
+fn log_audio_routes() {
+    serial::write_fmt(format_args!(
+        "audio: focus_duck={}",
+        if audio::focus_duck() { "on" } else { "off" }
+    ));
+    for owner in audio::AudioOwner::ALL {
+        let route = audio::route_status(owner);
+        serial::write_fmt(format_args!(
+            " {}={}/{} samples={} dropped={} ducked={}",
+            route.owner.as_str(),
+            route.route.as_str(),
+            route.effective.as_str(),
+            route.samples,
+            route.dropped,
+            route.ducked
+        ));
+    }
+    serial::write_line("");
+}
+
+fn audio_tap_to_serial(args: &str) -> Status {
+    const USAGE: &str = "usage: audio tap [start <file> [seconds] | stop]";
+    let mut parts = args.split_whitespace();
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (None, ..) => {
+            log_audio_tap(&audio::tap_status());
+            Status::Ok
+        }
+        (Some("start"), Some(path), seconds, None) => {
+            let seconds = match seconds.map(str::parse::<u32>) {
+                None => audio::DEFAULT_TAP_SECONDS,
+                Some(Ok(seconds)) if (1..=audio::MAX_TAP_SECONDS).contains(&seconds) => seconds,
+                Some(_) => {
+                    serial::write_severity_fmt(
+                        Severity::Warning,
+                        format_args!(
+                            "audio: tap seconds must be 1..={}\n",
+                            audio::MAX_TAP_SECONDS
+                        ),
+                    );
+                    return Status::Usage;
+                }
+            };
+            match audio::tap_start(path, seconds) {
+                Ok(()) => {
+                    serial::write_fmt(format_args!(
+                        "audio: tap started file={path} window_s={seconds} (finish with `audio tap stop`)\n"
+                    ));
+                    Status::Ok
+                }
+                Err(err) => {
+                    serial::write_severity_fmt(
+                        Severity::Warning,
+                        format_args!("audio: tap start failed ({})\n", err.as_str()),
+                    );
+                    Status::Failed
+                }
+            }
+        }
+        (Some("stop"), None, ..) => match audio::tap_stop() {
+            Ok(saved) => {
+                log_audio_tap(&saved.status);
+                serial::write_fmt(format_args!(
+                    "audio: tap saved {} frames ({} bytes, {} ms) to {} truncated={}\n",
+                    saved.saved_frames,
+                    saved.bytes,
+                    saved.status.frames_to_ms(saved.saved_frames),
+                    saved.path(),
+                    saved.truncated
+                ));
+                Status::Ok
+            }
+            Err(err) => {
+                serial::write_severity_fmt(
+                    Severity::Warning,
+                    format_args!("audio: tap stop failed ({})\n", err.as_str()),
+                );
+                Status::Failed
+            }
+        },
+        _ => Status::usage(USAGE),
+    }
+}
+
+fn log_audio_tap(status: &audio::TapStatus) {
+    serial::write_fmt(format_args!(
+        "audio: tap active={} stream={}/{} packets={} frames={} capacity_ms={} window_ms={} silent_ms={} peak={} gaps={} gap_ms={}{}\n",
+        if status.active { "yes" } else { "no" },
+        status.rate_hz,
+        status.channels,
+        status.packets,
+        status.frames,
+        status.capacity_ms,
+        status.frames_to_ms(status.window_frames),
+        status.frames_to_ms(status.silent_frames),
+        status.peak,
+        status.gaps,
+        status.gap_ms,
+        if status.alloc_failed {
+            " alloc_failed"
+        } else {
+            ""
+        }
+    ));
+}
+
+fn log_audio_devices() {
+    let devices = audio::devices();
+    if !devices.present {
+        serial::write_fmt(format_args!(
+            "audio: devices virtio-snd=absent mode={}\n",
+            audio::status().mode.as_str()
+        ));
+        return;
+    }
+    serial::write_fmt(format_args!(
+        "audio: devices virtio-snd jacks={} streams={} chmaps={} jack_query={} period_frames={} buffer_periods={}\n",
+        devices.jacks,
+        devices.streams,
+        devices.chmaps,
+        devices.jack_query,
+        devices.period_frames,
+        devices.buffer_periods
+    ));
+    for (index, jack) in devices.jack_infos.iter().enumerate() {
+        let Some(jack) = jack else { continue };
+        serial::write_fmt(format_args!(
+            "audio: jack #{index} nid={} connected={} defconf={:#010x} caps={:#010x}\n",
+            jack.hda_fn_nid,
+            if jack.connected { "yes" } else { "no" },
+            jack.defconf,
+            jack.caps
+        ));
+    }
+    for (index, stream) in devices.stream_infos.iter().enumerate() {
+        let Some(stream) = stream else { continue };
+        serial::write_fmt(format_args!(
+            "audio: stream #{index} {} nid={} channels={}-{} formats=",
+            if stream.output { "output" } else { "input" },
+            stream.hda_fn_nid,
+            stream.channels_min,
+            stream.channels_max
+        ));
+        let mut first = true;
+        for bit in (0..64).filter(|bit| stream.formats & (1u64 << bit) != 0) {
+            let separator = if first { "" } else { "," };
+            first = false;
+            match audio::format_name(bit) {
+                Some(name) => serial::write_fmt(format_args!("{separator}{name}")),
+                None => serial::write_fmt(format_args!("{separator}fmt{bit}")),
+            }
+        }
+        serial::write_str(" rates=");
+        first = true;
+        for bit in (0..64).filter(|bit| stream.rates & (1u64 << bit) != 0) {
+            let separator = if first { "" } else { "," };
+            first = false;
+            match audio::rate_hz(bit) {
+                Some(hz) => serial::write_fmt(format_args!("{separator}{hz}")),
+                None => serial::write_fmt(format_args!("{separator}rate{bit}")),
+            }
+        }
+        if devices.selected_stream == Some(index as u32) {
+            serial::write_str(" selected");
+        }
+        serial::write_line("");
+    }
+}
+
+fn log_beep_status() {
+    let beeps = audio::beep_status();
+    serial::write_fmt(format_args!(
+        "audio: beep queued={} played={} overlapped={} dropped={} voices={}\n",
+        beeps.queued, beeps.played, beeps.overlapped, beeps.dropped, beeps.voices
+    ));
+}
@@ -0,0 +1,68 @@
+// kernel/src/shell/autostart.rs: `doom.autostart` boot option (off, play, kiosk) and its config file.
+use super::boot_config;
+use crate::fs;
+
+const AUTOSTART_KEY: &str = "doom.autostart";
+/// Build-time default for demo images, e.g. `ARROST_DOOM_AUTOSTART=kiosk cargo xtask run`.
+const BUILD_DEFAULT: Option<&str> = option_env!("ARROST_DOOM_AUTOSTART");
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AutostartMode {
+    Off,
+    /// `doom play` with capture, doom window maximized.
+    Play,
+    /// `Play`, plus only the doom window is shown.
+    Kiosk,
+}
+
+impl AutostartMode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Play => "play",
+            Self::Kiosk => "kiosk",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "off" => Some(Self::Off),
+            "play" | "on" => Some(Self::Play),
+            "kiosk" => Some(Self::Kiosk),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AutostartSource {
+    Default,
+    Build,
+    File,
+}
+
+impl AutostartSource {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Build => "build",
+            Self::File => "file",
+        }
+    }
+}
+
+/// Effective mode: a `doom.autostart=` line in the boot config wins over the build default.
+pub fn load() -> (AutostartMode, AutostartSource) {
+    if let Some(mode) = boot_config::lookup(AUTOSTART_KEY, AutostartMode::parse) {
+        return (mode, AutostartSource::File);
+    }
+    match BUILD_DEFAULT.and_then(AutostartMode::parse) {
+        Some(mode) => (mode, AutostartSource::Build),
+        None => (AutostartMode::Off, AutostartSource::Default),
+    }
+}
+
+/// Rewrites the `doom.autostart=` line of the boot config; `sync` makes it persistent.
+pub fn save(mode: AutostartMode) -> Result<usize, fs::FsError> {
+    boot_config::set(AUTOSTART_KEY, mode.as_str())
+}
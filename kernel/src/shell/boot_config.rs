@@ -0,0 +1,49 @@
+// kernel/src/shell/boot_config.rs: `/BOOT.CFG` key=value lines shared by the boot options
+// (doom autostart, netconsole, settings).
+use crate::fs;
+
+pub const BOOT_CONFIG_FILE: &str = "/BOOT.CFG";
+
+/// Last `key=` line whose value `parse` accepts; later lines override earlier ones.
+pub fn lookup<T>(key: &str, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    let mut data = [0u8; fs::MAX_FILE_BYTES];
+    let len = fs::read_file(BOOT_CONFIG_FILE, &mut data).ok()?;
+    let text = core::str::from_utf8(&data[..len]).unwrap_or("");
+    text.lines()
+        .rev()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter(|(line_key, _)| line_key.trim() == key)
+        .find_map(|(_, value)| parse(value.trim()))
+}
+
+/// Rewrites the boot config with `key=value`, keeping its other lines; `sync` makes it
+/// persistent.
+pub fn set(key: &str, value: &str) -> Result<usize, fs::FsError> {
+    let mut existing = [0u8; fs::MAX_FILE_BYTES];
+    let existing_len = fs::read_file(BOOT_CONFIG_FILE, &mut existing).unwrap_or(0);
+    let text = core::str::from_utf8(&existing[..existing_len]).unwrap_or("");
+
+    let mut data = [0u8; fs::MAX_FILE_BYTES];
+    let mut len = 0usize;
+    let mut push_line = |parts: &[&str]| -> Result<(), fs::FsError> {
+        let line_len: usize = parts.iter().map(|part| part.len()).sum();
+        if len + line_len + 1 > data.len() {
+            return Err(fs::FsError::FileTooLarge);
+        }
+        for part in parts {
+            data[len..len + part.len()].copy_from_slice(part.as_bytes());
+            len += part.len();
+        }
+        data[len] = b'\n';
+        len += 1;
+        Ok(())
+    };
+    for line in text.lines().filter(|line| {
+        line.split_once('=')
+            .is_none_or(|(line_key, _)| line_key.trim() != key)
+    }) {
+        push_line(&[line])?;
+    }
+    push_line(&[key, "=", value])?;
+    fs::write_file(BOOT_CONFIG_FILE, &data[..len])
+}
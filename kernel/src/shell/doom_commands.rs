@@ -0,0 +1,664 @@
+// kernel/src/shell/doom_commands.rs: shell commands of the `doom` feature: the runtime, its
+// input, views and audio.
+use super::autostart::{self, AutostartMode};
+use super::{ShellState, Status, boot_config, print_prompt};
+use crate::audio;
+use crate::doom;
+use crate::fs;
+use crate::gfx;
+use crate::input_bus::{self, Consumer, Delivery, InputEvent, Subscription};
+use crate::keyboard;
+use crate::serial;
+use crate::time;
+
+pub(super) const SERIAL_CAPTURE_HELD_KEYS: usize = 8;
+const SERIAL_CAPTURE_HOLD_TICKS_DEFAULT: u64 = 8;
+const SERIAL_CAPTURE_HOLD_TICKS_MOVE: u64 = 12;
+const SERIAL_CAPTURE_HOLD_TICKS_ACTION: u64 = 14;
+
+#[derive(Clone, Copy)]
+pub(super) struct HeldCaptureKey {
+    byte: u8,
+    release_tick: u64,
+    active: bool,
+}
+
+impl HeldCaptureKey {
+    pub(super) const fn inactive() -> Self {
+        Self {
+            byte: 0,
+            release_tick: 0,
+            active: false,
+        }
+    }
+}
+
+impl ShellState {
+    /// Keeps the input bus capture in step, so keyboard and mouse go to doom while it is on.
+    pub(super) fn set_doom_capture(&mut self, enabled: bool) {
+        self.doom_capture = enabled;
+        let _ = input_bus::set_capture(Consumer::Doom, enabled);
+    }
+
+    pub(super) fn release_all_serial_capture_keys(&mut self) {
+        for slot in &mut self.held_serial_capture_keys {
+            if slot.active {
+                let _ = doom::inject_key_release(slot.byte);
+                *slot = HeldCaptureKey::inactive();
+            }
+        }
+    }
+
+    pub(super) fn release_expired_serial_capture_keys(&mut self, now_ticks: u64) {
+        for slot in &mut self.held_serial_capture_keys {
+            if slot.active && now_ticks >= slot.release_tick {
+                let _ = doom::inject_key_release(slot.byte);
+                *slot = HeldCaptureKey::inactive();
+            }
+        }
+    }
+
+    pub(super) fn refresh_serial_capture_key(&mut self, byte: u8, now_ticks: u64) {
+        let release_tick = now_ticks.saturating_add(serial_capture_hold_ticks(byte));
+        for slot in &mut self.held_serial_capture_keys {
+            if slot.active && slot.byte == byte {
+                slot.release_tick = release_tick;
+                return;
+            }
+        }
+
+        if !doom::inject_key(byte) {
+            return;
+        }
+
+        for slot in &mut self.held_serial_capture_keys {
+            if !slot.active {
+                *slot = HeldCaptureKey {
+                    byte,
+                    release_tick,
+                    active: true,
+                };
+                return;
+            }
+        }
+
+        let mut oldest_index = 0usize;
+        let mut oldest_tick = self.held_serial_capture_keys[0].release_tick;
+        for index in 1..SERIAL_CAPTURE_HELD_KEYS {
+            let tick = self.held_serial_capture_keys[index].release_tick;
+            if tick < oldest_tick {
+                oldest_tick = tick;
+                oldest_index = index;
+            }
+        }
+
+        let oldest = self.held_serial_capture_keys[oldest_index];
+        if oldest.active {
+            let _ = doom::inject_key_release(oldest.byte);
+        }
+        self.held_serial_capture_keys[oldest_index] = HeldCaptureKey {
+            byte,
+            release_tick,
+            active: true,
+        };
+    }
+}
+
+fn serial_capture_hold_ticks(byte: u8) -> u64 {
+    match byte {
+        b'w' | b'a' | b's' | b'd' | b'W' | b'A' | b'S' | b'D' => SERIAL_CAPTURE_HOLD_TICKS_MOVE,
+        b' ' | b'e' | b'E' | b'f' | b'F' | b'\n' | b'\r' => SERIAL_CAPTURE_HOLD_TICKS_ACTION,
+        _ => SERIAL_CAPTURE_HOLD_TICKS_DEFAULT,
+    }
+}
+
+/// Subscribes doom capture to the input bus; it stays idle until `doom capture on`.
+pub(super) fn subscribe_capture() {
+    input_bus::subscribe(
+        Consumer::Doom,
+        Subscription {
+            handler: doom_capture_input,
+            kinds: input_bus::KINDS_ALL,
+            priority: input_bus::PRIORITY_DOOM,
+            capture: false,
+            // Kiosk keeps the keyboard in the game; ESC opens doom's menu.
+            escape_releases: !gfx::kiosk(),
+            observe_captured: false,
+            on_release: Some(end_doom_capture_on_escape),
+        },
+    );
+}
+
+/// Input bus handler for doom capture. It only takes events while capture is on, and then
+/// takes all of them; text bytes are dropped because their key events already went in.
+fn doom_capture_input(event: InputEvent, _delivery: Delivery) -> bool {
+    if !doom_capture_enabled() {
+        return false;
+    }
+    match event {
+        InputEvent::Key(event) => {
+            let Some(byte) = map_doom_capture_key(event.code) else {
+                return true;
+            };
+            let delivered = if event.pressed {
+                doom::inject_key(byte)
+            } else {
+                doom::inject_key_release(byte)
+            };
+            if delivered {
+                keyboard::record_delivery(keyboard::InputSink::Doom, event.tick);
+            }
+        }
+        InputEvent::Byte(_) => {}
+        InputEvent::Mouse(event) => {
+            let _ = doom::inject_mouse(
+                event.dx,
+                event.dy,
+                event.left_button,
+                event.right_button,
+                event.middle_button,
+            );
+        }
+    }
+    true
+}
+
+/// The input bus already dropped the capture when ESC ended it.
+fn end_doom_capture_on_escape() {
+    // SAFETY: shell is single-threaded and only mutated from main loop.
+    let shell = unsafe { &mut *super::SHELL_STATE.0.get() };
+    end_doom_capture(shell);
+}
+
+pub(super) fn end_doom_capture(shell: &mut ShellState) {
+    shell.release_all_serial_capture_keys();
+    shell.set_doom_capture(false);
+    let _ = doom::set_capture(false);
+    serial::write_line("\ndoom: capture disabled");
+    print_prompt();
+}
+
+fn map_doom_capture_key(code: keyboard::KeyCode) -> Option<u8> {
+    match code {
+        keyboard::KeyCode::ArrowUp => Some(b'w'),
+        keyboard::KeyCode::ArrowDown => Some(b's'),
+        keyboard::KeyCode::ArrowLeft => Some(b'a'),
+        keyboard::KeyCode::ArrowRight => Some(b'd'),
+        keyboard::KeyCode::Byte(byte) => Some(byte),
+    }
+}
+
+fn doom_capture_enabled() -> bool {
+    // SAFETY: shell state is read on the main loop thread.
+    let shell = unsafe { &*super::SHELL_STATE.0.get() };
+    shell.doom_capture
+}
+
+/// Runs `input` if it is a doom command; `None` leaves it to the rest of `dispatch`.
+pub(super) fn dispatch(shell: &mut ShellState, input: &str) -> Option<Status> {
+    if input == "doom key" {
+        return Some(Status::usage(
+            "usage: doom key <w|a|s|d|x|up|down|left|right|stop|fire|use|enter|esc|tab|space>",
+        ));
+    }
+    if input == "doom keyup" {
+        return Some(Status::usage(
+            "usage: doom keyup <w|a|s|d|x|up|down|left|right|stop|fire|use|enter|esc|tab|space>",
+        ));
+    }
+    if input == "doom capture" {
+        serial::write_fmt(format_args!(
+            "doom: capture={}\n",
+            if doom::capture_enabled() { "on" } else { "off" }
+        ));
+        return Some(Status::Ok);
+    }
+    if input == "doom view" {
+        serial::write_fmt(format_args!(
+            "doom: viewport filter={} (usage: doom view <bilinear|nearest> | doom view ascii <on|off> | doom view map <on|off> | doom view map <on|off>)\n",
+            gfx::file_manager_doom_filter().as_str()
+        ));
+        return Some(Status::Ok);
+    }
+    if input == "doom mouse" {
+        doom::log_status();
+        return Some(Status::Ok);
+    }
+    if input == "doom audio" {
+        return Some(Status::usage(
+            "usage: doom audio <on|off|virtio|pcspk|status|test>",
+        ));
+    }
+    if input == "doom audio status" {
+        log_doom_audio_status();
+        return Some(Status::Ok);
+    }
+    if input == "doom mouse y on" {
+        doom::set_mouse_y_enabled(true);
+        serial::write_line("doom: mouse y mapping enabled");
+        doom::render_ui_status();
+        return Some(Status::Ok);
+    }
+    if input == "doom mouse y off" {
+        doom::set_mouse_y_enabled(false);
+        serial::write_line("doom: mouse y mapping disabled");
+        doom::render_ui_status();
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("doom mouse turn ") {
+        let value = rest.trim().parse::<i16>().ok();
+        return Some(match value {
+            Some(threshold) if doom::set_mouse_turn_threshold(threshold) => {
+                serial::write_fmt(format_args!(
+                    "doom: mouse turn threshold set to {}\n",
+                    threshold
+                ));
+                doom::render_ui_status();
+                Status::Ok
+            }
+            _ => Status::usage("usage: doom mouse turn <1..64>"),
+        });
+    }
+    if let Some(rest) = input.strip_prefix("doom mouse move ") {
+        let value = rest.trim().parse::<i16>().ok();
+        return Some(match value {
+            Some(threshold) if doom::set_mouse_move_threshold(threshold) => {
+                serial::write_fmt(format_args!(
+                    "doom: mouse move threshold set to {}\n",
+                    threshold
+                ));
+                doom::render_ui_status();
+                Status::Ok
+            }
+            _ => Status::usage("usage: doom mouse move <1..64>"),
+        });
+    }
+    if input == "doom capture on" {
+        if !doom::set_capture(true) {
+            serial::write_line("doom: capture requires `doom play` running");
+            return Some(Status::Failed);
+        }
+        shell.set_doom_capture(true);
+        serial::write_line("doom: capture enabled (press ESC to exit)");
+        return Some(Status::Ok);
+    }
+    if input == "doom capture off" {
+        if shell.doom_capture {
+            shell.release_all_serial_capture_keys();
+            shell.set_doom_capture(false);
+            let _ = doom::set_capture(false);
+            serial::write_line("doom: capture disabled");
+        } else {
+            serial::write_line("doom: capture already off");
+        }
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("doom keyup ") {
+        let Some(key) = parse_doom_key(rest) else {
+            return Some(Status::usage(
+                "usage: doom keyup <w|a|s|d|x|up|down|left|right|stop|fire|use|enter|esc|tab|space>",
+            ));
+        };
+        if !doom::inject_key_release(key) {
+            serial::write_line("doom: runtime not running in play mode");
+            return Some(Status::Failed);
+        }
+        serial::write_fmt(format_args!("doom: injected keyup {:#04x}\n", key));
+        doom::render_ui_status();
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("doom key ") {
+        let Some(key) = parse_doom_key(rest) else {
+            return Some(Status::usage(
+                "usage: doom key <w|a|s|d|x|up|down|left|right|stop|fire|use|enter|esc|tab|space>",
+            ));
+        };
+        if !doom::inject_key(key) {
+            serial::write_line("doom: runtime not running");
+            return Some(Status::Failed);
+        }
+        serial::write_fmt(format_args!("doom: injected key {:#04x}\n", key));
+        doom::render_ui_status();
+        return Some(Status::Ok);
+    }
+    if let Some(path) = input.strip_prefix("doom audio play ") {
+        return Some(play_wav_to_serial(path.trim()));
+    }
+    if let Some(rest) = input.strip_prefix("doom audio ") {
+        match rest.trim() {
+            "off" => {
+                let _ = audio::set_mode(audio::AudioMode::Off);
+                serial::write_line("doom: audio mode set to off");
+            }
+            "on" => {
+                let mode = audio::set_mode(audio::AudioMode::Virtio);
+                serial::write_fmt(format_args!("doom: audio mode set to {}\n", mode.as_str()));
+            }
+            "virtio" | "pcm" => {
+                let mode = audio::set_mode(audio::AudioMode::Virtio);
+                serial::write_fmt(format_args!("doom: audio mode set to {}\n", mode.as_str()));
+            }
+            "pcspk" | "pcspeaker" => {
+                let _ = audio::set_mode(audio::AudioMode::PcSpeaker);
+                serial::write_line("doom: audio mode set to pcspk");
+            }
+            "status" => log_doom_audio_status(),
+            "test" => return Some(play_test_tone_to_serial(audio::PcmFormat::S16, 44_100)),
+            "test s8" => return Some(play_test_tone_to_serial(audio::PcmFormat::S8, 22_050)),
+            "test u8" => return Some(play_test_tone_to_serial(audio::PcmFormat::U8, 11_025)),
+            _ => {
+                return Some(Status::usage(
+                    "usage: doom audio <on|off|virtio|pcspk|status|test [s8|u8]|play <file.wav>>",
+                ));
+            }
+        }
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("doom autostart") {
+        let rest = rest.trim();
+        if rest.is_empty() {
+            let (mode, source) = autostart::load();
+            serial::write_fmt(format_args!(
+                "doom: autostart={} source={} config={}\n",
+                mode.as_str(),
+                source.as_str(),
+                boot_config::BOOT_CONFIG_FILE
+            ));
+            return Some(Status::Ok);
+        }
+        let Some(mode) = AutostartMode::parse(rest) else {
+            return Some(Status::usage("usage: doom autostart [off|play|kiosk]"));
+        };
+        if let Err(err) = autostart::save(mode) {
+            serial::write_fmt(format_args!(
+                "doom: autostart save failed ({})\n",
+                err.as_str()
+            ));
+            return Some(Status::Failed);
+        }
+        serial::write_fmt(format_args!(
+            "doom: autostart={} saved to {} (sync to keep it across reboots)\n",
+            mode.as_str(),
+            boot_config::BOOT_CONFIG_FILE
+        ));
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("doom view map") {
+        let enabled = match rest.trim() {
+            "on" => true,
+            "off" => false,
+            _ => return Some(Status::usage("usage: doom view map <on|off>")),
+        };
+        let changed = doom::set_map_view(enabled);
+        serial::write_fmt(format_args!(
+            "doom: map view={}{}\n",
+            if enabled { "on" } else { "off" },
+            if changed { "" } else { " (unchanged)" }
+        ));
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("doom view ascii") {
+        let enabled = match rest.trim() {
+            "on" => true,
+            "off" => false,
+            _ => return Some(Status::usage("usage: doom view ascii <on|off>")),
+        };
+        let changed = doom::set_ascii_view(enabled);
+        serial::write_fmt(format_args!(
+            "doom: ascii view={}{}\n",
+            if enabled { "on" } else { "off" },
+            if changed { "" } else { " (unchanged)" }
+        ));
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("doom view ") {
+        let mode = rest.trim();
+        let changed = match mode {
+            "bilinear" | "smooth" => {
+                gfx::set_file_manager_doom_filter(gfx::DoomViewFilter::Bilinear)
+            }
+            "nearest" | "fast" => gfx::set_file_manager_doom_filter(gfx::DoomViewFilter::Nearest),
+            _ => return Some(Status::usage("usage: doom view <bilinear|nearest>")),
+        };
+        serial::write_fmt(format_args!(
+            "doom: viewport filter={}{}\n",
+            gfx::file_manager_doom_filter().as_str(),
+            if changed { "" } else { " (unchanged)" }
+        ));
+        doom::render_ui_status();
+        return Some(Status::Ok);
+    }
+    match input {
+        "doom" | "doom status" => doom::log_status(),
+        "doom source" => doom::log_doomgeneric_info(),
+        "doom doctor" => doom::log_doomgeneric_doctor(),
+        "doom play" => start_doom_play(shell),
+        "doom run" => {
+            if doom::start(time::ticks()) {
+                serial::write_line("doom: runtime started");
+            } else {
+                serial::write_line("doom: runtime already running");
+            }
+            doom::render_ui_status();
+        }
+        "doom stop" => {
+            let status = stop_doom_to_serial(shell);
+            doom::render_ui_status();
+            return Some(status);
+        }
+        "doom ui" => {
+            doom::render_ui_status();
+            serial::write_line("doom: ui status pushed to doom window");
+        }
+        "doom pause" => {
+            if doom::pause() {
+                serial::write_line("doom: paused (tics and audio halted)");
+                doom::render_ui_status();
+            } else {
+                serial::write_line("doom: runtime not running or already paused");
+                return Some(Status::Failed);
+            }
+        }
+        "doom resume" => {
+            if !doom::resume() {
+                serial::write_line("doom: runtime not running or not manually paused");
+                return Some(Status::Failed);
+            } else {
+                match doom::status().pause_reason {
+                    doom::PauseReason::Running => serial::write_line("doom: resumed"),
+                    reason => serial::write_fmt(format_args!(
+                        "doom: manual pause cleared; still paused ({})\n",
+                        reason.as_str()
+                    )),
+                }
+            }
+        }
+        "doom autopause" => {
+            serial::write_fmt(format_args!(
+                "doom: autopause={} (usage: doom autopause <off|minimized|unfocused>)\n",
+                doom::status().auto_pause.as_str()
+            ));
+        }
+        "doom autopause off" => set_doom_auto_pause_to_serial(doom::AutoPause::Off),
+        "doom autopause minimized" => set_doom_auto_pause_to_serial(doom::AutoPause::Minimized),
+        "doom autopause unfocused" => set_doom_auto_pause_to_serial(doom::AutoPause::Unfocused),
+        "doom reset" => {
+            doom::reset(time::ticks());
+            doom::render_ui_status();
+            serial::write_line("doom: simulation reset");
+        }
+        _ => return None,
+    }
+    Some(Status::Ok)
+}
+
+fn play_test_tone_to_serial(format: audio::PcmFormat, rate_hz: u32) -> Status {
+    if !audio::play_test_tone(format, rate_hz) {
+        serial::write_line("doom: audio test unavailable (mode=off)");
+        return Status::Failed;
+    }
+    serial::write_fmt(format_args!(
+        "doom: audio test tone queued fmt={} rate={} -> device rate={}\n",
+        format.as_str(),
+        rate_hz,
+        audio::status().pcm_rate_hz
+    ));
+    Status::Ok
+}
+
+fn play_wav_to_serial(path: &str) -> Status {
+    let data = match fs::read_all(path) {
+        Ok(data) => data,
+        Err(err) => {
+            serial::write_fmt(format_args!(
+                "doom: audio play {} ({})\n",
+                path,
+                err.as_str()
+            ));
+            return Status::Failed;
+        }
+    };
+    match audio::play_wav(&data) {
+        Ok(clip) => {
+            serial::write_fmt(format_args!(
+                "doom: audio play {} fmt={} rate={} ch={} bytes={} -> device rate={}\n",
+                path,
+                clip.format.as_str(),
+                clip.sample_rate,
+                clip.channels,
+                clip.data.len(),
+                audio::status().pcm_rate_hz
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_fmt(format_args!(
+                "doom: audio play {} ({})\n",
+                path,
+                err.as_str()
+            ));
+            Status::Failed
+        }
+    }
+}
+
+fn log_doom_audio_status() {
+    let status = audio::status();
+    let pcm = audio::pcm_status();
+    serial::write_fmt(format_args!(
+        "doom: audio mode={} backend={} active={} muted={} volume={} hz={} pcm_evt={} pcm_samples={} pcm_sw={} pcm_min={} pcm_max={} pcm_q={} pcm_buf={} pcm_tx={} pcm_done={} pcm_drop={} pcm_frames={} pcm_drop_frames={} pcm_rate={} pcm_ch={} pcm_stream={} pcm_ctrl={:#x} pcm_src={}/{}/{}\n",
+        status.mode.as_str(),
+        status.pcm_backend,
+        status.active,
+        status.muted,
+        audio::volume(),
+        pcm.tone_hz,
+        pcm.mix_events,
+        pcm.samples,
+        pcm.tone_switches,
+        pcm.hz_min,
+        pcm.hz_max,
+        pcm.queue_pending,
+        pcm.buffered_frames,
+        pcm.packets_submitted,
+        pcm.packets_completed,
+        pcm.packets_dropped,
+        pcm.frames_completed,
+        pcm.frames_dropped,
+        status.pcm_rate_hz,
+        status.pcm_channels,
+        pcm.stream_id,
+        pcm.last_ctrl_status,
+        pcm.src_rate_hz,
+        pcm.src_channels,
+        pcm.src_format.as_str()
+    ));
+}
+
+pub(super) fn parse_doom_key(input: &str) -> Option<u8> {
+    let key = input.trim();
+    if key.is_empty() {
+        return None;
+    }
+
+    if key.eq_ignore_ascii_case("up") {
+        return Some(b'w');
+    }
+    if key.eq_ignore_ascii_case("down") {
+        return Some(b's');
+    }
+    if key.eq_ignore_ascii_case("left") {
+        return Some(b'a');
+    }
+    if key.eq_ignore_ascii_case("right") {
+        return Some(b'd');
+    }
+    if key.eq_ignore_ascii_case("stop") {
+        return Some(b'x');
+    }
+    if key.eq_ignore_ascii_case("fire") || key.eq_ignore_ascii_case("space") {
+        return Some(b' ');
+    }
+    if key.eq_ignore_ascii_case("use") {
+        return Some(b'e');
+    }
+    if key.eq_ignore_ascii_case("enter") {
+        return Some(b'\n');
+    }
+    if key.eq_ignore_ascii_case("esc") || key.eq_ignore_ascii_case("escape") {
+        return Some(0x1b);
+    }
+    if key.eq_ignore_ascii_case("tab") {
+        return Some(b'\t');
+    }
+    if key.len() == 1 {
+        return key.as_bytes().first().copied();
+    }
+    None
+}
+
+/// `doom stop`, also run by the doom window's close button.
+pub(super) fn stop_doom_to_serial(shell: &mut ShellState) -> Status {
+    if !doom::stop(time::ticks()) {
+        serial::write_line("doom: runtime already stopped");
+        return Status::Failed;
+    }
+    shell.release_all_serial_capture_keys();
+    shell.set_doom_capture(false);
+    let _ = doom::set_capture(false);
+    serial::write_line("doom: runtime stopped");
+    Status::Ok
+}
+
+fn set_doom_auto_pause_to_serial(policy: doom::AutoPause) {
+    doom::set_auto_pause(policy);
+    serial::write_fmt(format_args!("doom: autopause={}\n", policy.as_str()));
+}
+
+/// `doom play`: start the runtime (doomgeneric or fallback) and grab keyboard capture.
+pub(super) fn start_doom_play(shell: &mut ShellState) {
+    let start = doom::play(time::ticks());
+    match start {
+        doom::PlayStart::DoomGeneric => {
+            serial::write_line("doom: play mode started (doomgeneric)");
+        }
+        doom::PlayStart::Fallback => {
+            serial::write_line(
+                "doom: doomgeneric not ready; starting fallback runtime (run cargo xtask doom-setup)",
+            );
+        }
+        doom::PlayStart::AlreadyRunning => {
+            serial::write_line("doom: runtime already running");
+        }
+    }
+    if !matches!(start, doom::PlayStart::AlreadyRunning) {
+        if doom::set_capture(true) {
+            shell.set_doom_capture(true);
+            serial::write_line("doom: capture enabled (press ESC to exit)");
+        } else {
+            shell.set_doom_capture(false);
+            serial::write_line("doom: capture unavailable (fallback mode)");
+        }
+    }
+    doom::render_ui_status();
+}
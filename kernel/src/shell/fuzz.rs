@@ -0,0 +1,306 @@
+// kernel/src/shell/fuzz.rs: `fuzz` debug commands feeding raw bytes to the packet and shell parsers.
+// Compiled in only with the kernel `fuzz` feature; `cargo xtask fuzz` replays corpora through it.
+#[cfg(feature = "doom")]
+use super::autostart::AutostartMode;
+#[cfg(feature = "doom")]
+use super::doom_commands::parse_doom_key;
+#[cfg(feature = "net")]
+use super::net_commands::parse_udp_send;
+use super::{
+    MAX_LINE_LEN, ShellState, Status, parse_echo, parse_file_manager_copy, parse_on_off,
+    pop_line_char, push_line_char,
+};
+use crate::fs;
+use crate::log::Subsystem;
+#[cfg(feature = "net")]
+use crate::net;
+use crate::serial;
+use crate::soft_assert;
+use crate::utf8::Utf8Decoder;
+use core::cell::UnsafeCell;
+use core::str;
+
+/// Largest case; longer frames are clamped by the RX path anyway. Shell lines are capped at
+/// 128 bytes, so longer cases are staged with `fuzz append` first.
+const MAX_CASE_BYTES: usize = 2048;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Target {
+    #[cfg(feature = "net")]
+    Net,
+    Shell,
+}
+
+impl Target {
+    const fn as_str(self) -> &'static str {
+        match self {
+            #[cfg(feature = "net")]
+            Self::Net => "net",
+            Self::Shell => "shell",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FuzzError {
+    InvalidHex,
+    InvalidEscape,
+    CaseTooLarge,
+}
+
+impl FuzzError {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidHex => "invalid_hex",
+            Self::InvalidEscape => "invalid_escape",
+            Self::CaseTooLarge => "case_too_large",
+        }
+    }
+}
+
+struct FuzzState {
+    staged: [u8; MAX_CASE_BYTES],
+    staged_len: usize,
+    cases: u64,
+    asserting_cases: u64,
+    errors: u64,
+}
+
+impl FuzzState {
+    fn push(&mut self, byte: u8) -> Result<(), FuzzError> {
+        if self.staged_len >= MAX_CASE_BYTES {
+            return Err(FuzzError::CaseTooLarge);
+        }
+        self.staged[self.staged_len] = byte;
+        self.staged_len += 1;
+        Ok(())
+    }
+
+    fn push_hex(&mut self, text: &str) -> Result<(), FuzzError> {
+        let digits = text.as_bytes();
+        if !digits.len().is_multiple_of(2) {
+            return Err(FuzzError::InvalidHex);
+        }
+        for &[high, low] in digits.as_chunks::<2>().0 {
+            let high = hex_value(high).ok_or(FuzzError::InvalidHex)?;
+            let low = hex_value(low).ok_or(FuzzError::InvalidHex)?;
+            self.push(high << 4 | low)?;
+        }
+        Ok(())
+    }
+
+    /// Literal bytes with `\xNN` and `\\` escapes, so control bytes survive the serial line.
+    fn push_escaped(&mut self, text: &str) -> Result<(), FuzzError> {
+        let bytes = text.as_bytes();
+        let mut index = 0;
+        while index < bytes.len() {
+            let byte = bytes[index];
+            if byte != b'\\' {
+                self.push(byte)?;
+                index += 1;
+                continue;
+            }
+            match bytes.get(index + 1) {
+                Some(b'\\') => {
+                    self.push(b'\\')?;
+                    index += 2;
+                }
+                Some(b'x') => {
+                    let high = bytes.get(index + 2).copied().and_then(hex_value);
+                    let low = bytes.get(index + 3).copied().and_then(hex_value);
+                    let (Some(high), Some(low)) = (high, low) else {
+                        return Err(FuzzError::InvalidEscape);
+                    };
+                    self.push(high << 4 | low)?;
+                    index += 4;
+                }
+                _ => return Err(FuzzError::InvalidEscape),
+            }
+        }
+        Ok(())
+    }
+}
+
+struct FuzzCell(UnsafeCell<FuzzState>);
+
+// SAFETY: fuzz state is only touched by shell commands on the main loop thread.
+unsafe impl Sync for FuzzCell {}
+
+static FUZZ: FuzzCell = FuzzCell(UnsafeCell::new(FuzzState {
+    staged: [0; MAX_CASE_BYTES],
+    staged_len: 0,
+    cases: 0,
+    asserting_cases: 0,
+    errors: 0,
+}));
+
+pub fn log_help() {
+    serial::write_line(
+        "help: fuzz | fuzz append <hex> | fuzz clear | fuzz inject net [hex] | fuzz inject shell [bytes with \\xNN]",
+    );
+}
+
+pub fn execute(shell: &mut ShellState, args: &str) -> Status {
+    // SAFETY: shell commands run on the main loop thread only.
+    let fuzz = unsafe { &mut *FUZZ.0.get() };
+    if args.is_empty() {
+        log_status(fuzz);
+        return Status::Ok;
+    }
+    if args == "clear" {
+        fuzz.staged_len = 0;
+        log_status(fuzz);
+        return Status::Ok;
+    }
+    if let Some(hex) = args.strip_prefix("append ") {
+        return match fuzz.push_hex(hex.trim()) {
+            Ok(()) => {
+                log_status(fuzz);
+                Status::Ok
+            }
+            Err(error) => reject(fuzz, error),
+        };
+    }
+    if let Some(rest) = args.strip_prefix("inject ") {
+        let (target, payload) = rest.split_once(' ').unwrap_or((rest, ""));
+        let result = match target {
+            #[cfg(feature = "net")]
+            "net" => fuzz.push_hex(payload.trim()).map(|()| Target::Net),
+            "shell" => fuzz.push_escaped(payload).map(|()| Target::Shell),
+            _ => {
+                serial::write_line("usage: fuzz inject <net|shell> [payload]");
+                return Status::Usage;
+            }
+        };
+        return match result {
+            Ok(target) => {
+                inject(shell, fuzz, target);
+                Status::Ok
+            }
+            Err(error) => reject(fuzz, error),
+        };
+    }
+    serial::write_line("usage: fuzz [append <hex>|clear|inject <net|shell> [payload]]");
+    Status::Usage
+}
+
+fn inject(shell: &ShellState, fuzz: &mut FuzzState, target: Target) {
+    fuzz.cases = fuzz.cases.saturating_add(1);
+    let case = fuzz.cases;
+    let len = fuzz.staged_len;
+    fuzz.staged_len = 0;
+    // Printed first so a panic or hang right after it names the case that caused it.
+    serial::write_fmt(format_args!(
+        "fuzz: case={case} target={} len={len}\n",
+        target.as_str()
+    ));
+    let asserts_before = soft_assert::count();
+    #[cfg(feature = "net")]
+    let drops_before = net::rx_drop_counts();
+    let bytes = &fuzz.staged[..len];
+    let (result, accepted) = match target {
+        #[cfg(feature = "net")]
+        Target::Net => (net::inject_frame(bytes).map_err(|error| error.as_str()), 0),
+        Target::Shell => (Ok(()), run_shell_parsers(shell, bytes)),
+    };
+    let asserts = soft_assert::count().saturating_sub(asserts_before);
+    if asserts > 0 {
+        fuzz.asserting_cases = fuzz.asserting_cases.saturating_add(1);
+    }
+    serial::write_fmt(format_args!(
+        "fuzz: done case={case} target={} result={} accepted={accepted} soft_asserts={asserts} drops=",
+        target.as_str(),
+        result.err().unwrap_or("ok")
+    ));
+    #[cfg(feature = "net")]
+    log_rx_drops(&drops_before);
+    #[cfg(not(feature = "net"))]
+    serial::write_line("none");
+}
+
+/// Names every RX validation that rejected the case, e.g. `drops=ip_checksum`.
+#[cfg(feature = "net")]
+fn log_rx_drops(before: &[u64; net::RxDrop::ALL.len()]) {
+    let after = net::rx_drop_counts();
+    let mut fired = 0usize;
+    for (index, reason) in net::RxDrop::ALL.into_iter().enumerate() {
+        if after[index] != before[index] {
+            let separator = if fired == 0 { "" } else { "," };
+            serial::write_fmt(format_args!("{separator}{}", reason.as_str()));
+            fired += 1;
+        }
+    }
+    serial::write_line(if fired == 0 { "none" } else { "" });
+}
+
+/// Applies the same line editing as `process_byte` (UTF-8 decoding, printable characters,
+/// backspace, length cap), then runs every side-effect-free parser on the line and on its
+/// argument tail. The command dispatch itself is left out: it would execute whatever the
+/// fuzzer typed.
+fn run_shell_parsers(shell: &ShellState, bytes: &[u8]) -> usize {
+    let mut line = [0u8; MAX_LINE_LEN];
+    let mut len = 0usize;
+    let mut utf8 = Utf8Decoder::new();
+    for &byte in bytes {
+        utf8.push(byte, |ch| match ch {
+            '\x08' | '\x7f' => len = pop_line_char(&line, len),
+            ch if !ch.is_control() => {
+                let _ = push_line_char(&mut line, &mut len, ch, &mut [0; 4]);
+            }
+            _ => {}
+        });
+    }
+    let input = str::from_utf8(&line[..len]).unwrap_or("").trim();
+    let tail = input.split_once(' ').map_or("", |(_, tail)| tail);
+    let _ = shell.history.expand(input);
+
+    let mut accepted = 0usize;
+    let mut buf = [0u8; MAX_LINE_LEN];
+    for text in [input, tail] {
+        let hits = [
+            parse_echo(text, &mut buf).is_some(),
+            #[cfg(feature = "net")]
+            parse_udp_send(text, &mut buf).is_some(),
+            #[cfg(feature = "doom")]
+            parse_doom_key(text).is_some(),
+            parse_file_manager_copy(text).is_some(),
+            fs::validate_name(text).is_ok(),
+            parse_on_off(text).is_some(),
+            #[cfg(feature = "net")]
+            net::parse_ipv4(text).is_some(),
+            #[cfg(feature = "net")]
+            net::parse_mac(text).is_some(),
+            #[cfg(feature = "doom")]
+            AutostartMode::parse(text).is_some(),
+            Subsystem::parse(text).is_some(),
+        ];
+        accepted += hits.into_iter().filter(|hit| *hit).count();
+    }
+    accepted
+}
+
+fn reject(fuzz: &mut FuzzState, error: FuzzError) -> Status {
+    fuzz.errors = fuzz.errors.saturating_add(1);
+    fuzz.staged_len = 0;
+    Status::failed(format_args!("fuzz: rejected ({})\n", error.as_str()))
+}
+
+fn log_status(fuzz: &FuzzState) {
+    serial::write_fmt(format_args!(
+        "fuzz: cases={} staged={} asserting_cases={} errors={} soft_asserts={}\n",
+        fuzz.cases,
+        fuzz.staged_len,
+        fuzz.asserting_cases,
+        fuzz.errors,
+        soft_assert::count()
+    ));
+}
+
+const fn hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
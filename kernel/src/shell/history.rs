@@ -0,0 +1,136 @@
+// kernel/src/shell/history.rs: command history ring, `!!`/`!n` expansion, fs persistence.
+use super::MAX_LINE_LEN;
+use crate::fs;
+use alloc::vec::Vec;
+
+pub const HISTORY_CAPACITY: usize = 100;
+pub const HISTORY_FILE: &str = "/HISTORY.TXT";
+
+#[derive(Clone, Copy)]
+struct HistoryEntry {
+    line: [u8; MAX_LINE_LEN],
+    len: usize,
+}
+
+impl HistoryEntry {
+    const fn empty() -> Self {
+        Self {
+            line: [0; MAX_LINE_LEN],
+            len: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum Expansion<'a> {
+    /// The line carries no history designator and runs as typed.
+    Unchanged,
+    Expanded(&'a str),
+    NotFound,
+}
+
+pub struct History {
+    entries: [HistoryEntry; HISTORY_CAPACITY],
+    /// Total commands recorded since boot; entry `n` (1-based) lives at `(n - 1) % CAPACITY`.
+    recorded: usize,
+}
+
+impl History {
+    pub const fn new() -> Self {
+        Self {
+            entries: [HistoryEntry::empty(); HISTORY_CAPACITY],
+            recorded: 0,
+        }
+    }
+
+    pub fn push(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        let bytes = command.as_bytes();
+        let len = bytes.len().min(MAX_LINE_LEN);
+        let entry = &mut self.entries[self.recorded % HISTORY_CAPACITY];
+        entry.line[..len].copy_from_slice(&bytes[..len]);
+        entry.len = len;
+        self.recorded = self.recorded.saturating_add(1);
+    }
+
+    pub fn clear(&mut self) {
+        self.recorded = 0;
+    }
+
+    pub const fn first_number(&self) -> usize {
+        self.recorded.saturating_sub(HISTORY_CAPACITY) + 1
+    }
+
+    pub const fn last_number(&self) -> usize {
+        self.recorded
+    }
+
+    pub fn get(&self, number: usize) -> Option<&str> {
+        if number == 0 || number < self.first_number() || number > self.last_number() {
+            return None;
+        }
+        let entry = &self.entries[(number - 1) % HISTORY_CAPACITY];
+        core::str::from_utf8(&entry.line[..entry.len]).ok()
+    }
+
+    pub fn expand<'a>(&'a self, input: &str) -> Expansion<'a> {
+        let Some(designator) = input.strip_prefix('!') else {
+            return Expansion::Unchanged;
+        };
+        let number = if designator == "!" {
+            Some(self.last_number())
+        } else {
+            designator.parse::<usize>().ok()
+        };
+        match number.and_then(|number| self.get(number)) {
+            Some(command) => Expansion::Expanded(command),
+            None => Expansion::NotFound,
+        }
+    }
+
+    /// Writes the newest commands that fit in one file, oldest first, one per line. A full
+    /// ring is at most 100 lines of 128 bytes, which diskfs always holds; the ramfs fallback
+    /// keeps what fits in its 512-byte slots.
+    pub fn save(&self) -> Result<usize, fs::FsError> {
+        let max_bytes = fs::max_file_bytes();
+        let mut first = self.last_number() + 1;
+        let mut bytes = 0usize;
+        while first > self.first_number() {
+            let Some(command) = self.get(first - 1) else {
+                break;
+            };
+            let needed = command.len() + 1;
+            if bytes + needed > max_bytes {
+                break;
+            }
+            bytes += needed;
+            first -= 1;
+        }
+
+        let mut data = Vec::new();
+        data.try_reserve_exact(bytes)
+            .map_err(|_| fs::FsError::FileTooLarge)?;
+        for number in first..=self.last_number() {
+            let Some(command) = self.get(number) else {
+                continue;
+            };
+            data.extend_from_slice(command.as_bytes());
+            data.push(b'\n');
+        }
+        fs::write_file(HISTORY_FILE, &data)?;
+        Ok(self.last_number() + 1 - first)
+    }
+
+    pub fn load(&mut self) -> Result<usize, fs::FsError> {
+        let data = fs::read_all(HISTORY_FILE)?;
+        let text = core::str::from_utf8(&data).unwrap_or("");
+        let before = self.recorded;
+        for line in text.lines() {
+            self.push(line);
+        }
+        Ok(self.recorded - before)
+    }
+}
@@ -0,0 +1,185 @@
+// kernel/src/shell/net_commands.rs: shell commands of the `net` feature: interface, DHCP,
+// UDP/TCP, firewall, services, netconsole and telemetry.
+use super::{ShellState, Status, netconsole, parse_on_off, set_log_quiet_to_serial};
+use crate::log::{self, Subsystem};
+use crate::net;
+use crate::serial;
+use crate::telemetry;
+use alloc::format;
+use alloc::vec;
+use arrostd::args;
+
+/// Runs `input` if it is a net command; `None` leaves it to the rest of `dispatch`.
+pub(super) fn dispatch(shell: &mut ShellState, input: &str) -> Option<Status> {
+    if let Some(rest) = input.strip_prefix("net quiet") {
+        match rest.trim() {
+            "" => serial::write_fmt(format_args!(
+                "log: net verbosity={}\n",
+                if log::quiet(Subsystem::Net) {
+                    "quiet"
+                } else {
+                    "normal"
+                }
+            )),
+            value => match parse_on_off(value) {
+                Some(quiet) => set_log_quiet_to_serial(Subsystem::Net, quiet),
+                None => return Some(Status::usage("usage: net quiet [on|off]")),
+            },
+        }
+        return Some(Status::Ok);
+    }
+    if let Some(ip) = input.strip_prefix("ping ") {
+        let ip = ip.trim();
+        if ip.is_empty() {
+            return Some(Status::usage("usage: ping <a.b.c.d>"));
+        }
+        return Some(net::ping_to_serial(ip));
+    }
+
+    if input == "dhcp" || input == "dhcp info" {
+        net::log_dhcp_info();
+        return Some(Status::Ok);
+    }
+    if input == "dhcp renew" {
+        return Some(net::dhcp_renew_to_serial());
+    }
+    if input == "dhcp release" {
+        return Some(net::dhcp_release_to_serial());
+    }
+
+    if input == "udp last" {
+        net::log_last_udp();
+        return Some(Status::Ok);
+    }
+    if input == "wol" || input.starts_with("wol ") {
+        return Some(net::wol_to_serial(&input[3..]));
+    }
+    if let Some(rest) = input.strip_prefix("udp rtt") {
+        return Some(net::udp_rtt_to_serial(rest));
+    }
+    if let Some(rest) = input.strip_prefix("udp send ") {
+        let mut buf = vec![0u8; rest.len()];
+        return Some(match parse_udp_send(rest, &mut buf) {
+            Some((ip, port, payload)) => net::udp_send_to_serial(ip, port, payload),
+            None => Status::usage("usage: udp send <a.b.c.d> <port> <text>"),
+        });
+    }
+    if let Some(args) = input.strip_prefix("telemetry start") {
+        return Some(telemetry::start_to_serial(args));
+    }
+    if let Some(mtu) = input.strip_prefix("ifconfig mtu ") {
+        return Some(net::set_mtu_to_serial(mtu));
+    }
+    if let Some(ip) = input.strip_prefix("ifconfig addr ") {
+        return Some(net::set_ipv4_to_serial(ip));
+    }
+    if let Some(rest) = input.strip_prefix("net bench rx") {
+        return Some(net::bench_rx_to_serial(rest));
+    }
+    if let Some(rest) = input.strip_prefix("net bench udp") {
+        return Some(net::bench_udp_to_serial(rest));
+    }
+    if let Some(rest) = input.strip_prefix("net ports") {
+        return Some(net::ports_to_serial(rest));
+    }
+    if let Some(group) = input.strip_prefix("net join ") {
+        return Some(net::join_group_to_serial(group.trim()));
+    }
+    if let Some(group) = input.strip_prefix("net leave ") {
+        return Some(net::leave_group_to_serial(group.trim()));
+    }
+    if let Some(rest) = input.strip_prefix("curl ") {
+        return Some(net::curl_to_serial(rest.trim()));
+    }
+    if input == "netconsole" {
+        netconsole::log_status(&shell.netconsole);
+        return Some(Status::Ok);
+    }
+    if input == "netconsole stop" {
+        netconsole::stop_to_serial();
+        return Some(Status::Ok);
+    }
+    if let Some(args) = input
+        .strip_prefix("netconsole start")
+        .filter(|args| args.is_empty() || args.starts_with(' '))
+    {
+        let args = args.trim();
+        let port = if args.is_empty() {
+            None
+        } else {
+            match args.parse::<u16>() {
+                Ok(port) if port != 0 => Some(port),
+                _ => return Some(Status::usage("usage: netconsole start [port]")),
+            }
+        };
+        return Some(netconsole::start_to_serial(&mut shell.netconsole, port));
+    }
+    if let Some(args) = input.strip_prefix("fw add ") {
+        return Some(net::add_rule_to_serial(args));
+    }
+    if let Some(args) = input.strip_prefix("fw del ") {
+        return Some(net::delete_rule_to_serial(args));
+    }
+    if let Some(rest) = input.strip_prefix("tcp send ") {
+        let mut buf = vec![0u8; rest.len()];
+        return Some(match parse_tcp_send(rest, &mut buf) {
+            Some((id, text)) => net::tcp_send_to_serial(id, &format!("{text}\n")),
+            None => Status::usage("usage: tcp send <id> <text>"),
+        });
+    }
+    if input == "tcp" || input.starts_with("tcp ") {
+        return Some(net::tcp_to_serial(&input[3..]));
+    }
+    if let Some(args) = input.strip_prefix("service start ") {
+        return Some(net::start_service_to_serial(args));
+    }
+    if let Some(args) = input.strip_prefix("service stop ") {
+        return Some(net::stop_service_to_serial(args));
+    }
+    match input {
+        "service" | "service list" => net::log_services(),
+        "fw" | "fw list" => net::log_rules(),
+        "fw clear" => net::clear_rules_to_serial(),
+        "net" => {
+            net::log_info();
+        }
+        "ifconfig" => {
+            net::log_ifconfig();
+        }
+        "net groups" => {
+            net::log_groups();
+        }
+        "arp" => net::log_arp(),
+        "netstat" => net::log_netstat(),
+        "telemetry" | "telemetry status" => telemetry::log_status(),
+        "telemetry stop" => return Some(telemetry::stop_to_serial()),
+        _ => return None,
+    }
+    Some(Status::Ok)
+}
+
+/// `<ip> <port> <text>`; unquoted text words are joined by single spaces.
+pub(super) fn parse_udp_send<'a>(
+    input: &str,
+    buf: &'a mut [u8],
+) -> Option<(&'a str, u16, &'a str)> {
+    let args = args::split(input, buf).ok()?;
+    let ip = args.get(0)?;
+    let port = args.get(1)?.parse::<u16>().ok()?;
+    let payload = args.rest(2)?;
+    if payload.is_empty() {
+        return None;
+    }
+    Some((ip, port, payload))
+}
+
+/// `<id> <text>`, joined like `udp send`.
+fn parse_tcp_send<'a>(input: &str, buf: &'a mut [u8]) -> Option<(u32, &'a str)> {
+    let args = args::split(input, buf).ok()?;
+    let id = args.get(0)?.parse::<u32>().ok()?;
+    let text = args.rest(1)?;
+    if text.is_empty() {
+        return None;
+    }
+    Some((id, text))
+}
@@ -0,0 +1,252 @@
+// kernel/src/shell/netconsole.rs: `netconsole` service, shell commands over UDP for CI and remote
+// debugging. A request is `<token> <command>`; the reply is the command's captured output in
+// one or more datagrams, then a `netconsole: end` line.
+use super::alias::Aliases;
+use super::boot_config::BOOT_CONFIG_FILE;
+use super::watch::OUTPUT_CAPACITY;
+use super::{SHELL_STATE, Status, execute};
+use crate::fs;
+use crate::log::{self, Level, Subsystem};
+use crate::net;
+use crate::proc::executor;
+use crate::serial::{self, Severity};
+use alloc::{format, vec};
+
+const TOKEN_KEY: &str = "netconsole.token";
+const PORT_KEY: &str = "netconsole.port";
+const DEFAULT_PORT: u16 = 6666;
+/// Short tokens are guessable by brute force over a LAN.
+const MIN_TOKEN_LEN: usize = 8;
+const MAX_TOKEN_LEN: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    Denied,
+    /// Well-formed but not allowed remotely, e.g. `fs import`, which takes over later lines.
+    Rejected,
+    Invalid,
+}
+
+impl Outcome {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Denied => "denied",
+            Self::Rejected => "rejected",
+            Self::Invalid => "invalid",
+        }
+    }
+}
+
+pub struct NetConsole {
+    token: [u8; MAX_TOKEN_LEN],
+    token_len: usize,
+    port: u16,
+    commands: u64,
+    denied: u64,
+    rejected: u64,
+}
+
+impl NetConsole {
+    pub const fn new() -> Self {
+        Self {
+            token: [0; MAX_TOKEN_LEN],
+            token_len: 0,
+            port: DEFAULT_PORT,
+            commands: 0,
+            denied: 0,
+            rejected: 0,
+        }
+    }
+
+    /// Reads `netconsole.token=` and `netconsole.port=` from the boot config; true when a
+    /// usable token was found.
+    pub(super) fn load(&mut self) -> bool {
+        self.token_len = 0;
+        self.port = DEFAULT_PORT;
+        let mut data = [0u8; fs::MAX_FILE_BYTES];
+        let Ok(len) = fs::read_file(BOOT_CONFIG_FILE, &mut data) else {
+            return false;
+        };
+        let text = core::str::from_utf8(&data[..len]).unwrap_or("");
+        for (key, value) in text.lines().filter_map(|line| line.trim().split_once('=')) {
+            let value = value.trim();
+            match key.trim() {
+                TOKEN_KEY
+                    if (MIN_TOKEN_LEN..=MAX_TOKEN_LEN).contains(&value.len())
+                        && !value.contains(char::is_whitespace) =>
+                {
+                    self.token[..value.len()].copy_from_slice(value.as_bytes());
+                    self.token_len = value.len();
+                }
+                PORT_KEY => {
+                    if let Some(port) = value.parse::<u16>().ok().filter(|port| *port != 0) {
+                        self.port = port;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.token_len > 0
+    }
+
+    /// Splits `<token> <command>` and checks the token without an early exit on the first
+    /// differing byte.
+    /// The command of a request with the right token. Aliases are looked through, so one cannot
+    /// smuggle in a rejected command.
+    fn command<'a>(&self, request: &'a [u8], aliases: &Aliases) -> Result<&'a str, Outcome> {
+        let text = core::str::from_utf8(request).map_err(|_| Outcome::Invalid)?;
+        let (token, command) = text.trim().split_once(' ').ok_or(Outcome::Invalid)?;
+        let expected = &self.token[..self.token_len];
+        let difference = token
+            .bytes()
+            .zip(expected)
+            .fold(token.len() ^ expected.len(), |acc, (a, b)| {
+                acc | usize::from(a ^ b)
+            });
+        if self.token_len == 0 || difference != 0 {
+            return Err(Outcome::Denied);
+        }
+        let command = command.trim();
+        if command.is_empty() || command.len() >= super::MAX_LINE_LEN {
+            return Err(Outcome::Invalid);
+        }
+        let expanded = aliases.expand(command);
+        let effective = expanded.as_deref().unwrap_or(command);
+        if effective.starts_with("fs import") || effective.starts_with("netconsole") {
+            return Err(Outcome::Rejected);
+        }
+        Ok(command)
+    }
+}
+
+/// `netconsole start [port]`, and boot with `netconsole.token=` set.
+pub(super) fn start_to_serial(console: &mut NetConsole, port_override: Option<u16>) -> Status {
+    if !console.load() {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!(
+                "netconsole: no {TOKEN_KEY}= ({MIN_TOKEN_LEN}..{MAX_TOKEN_LEN} chars) in {BOOT_CONFIG_FILE}\n"
+            ),
+        );
+        return Status::Failed;
+    }
+    if let Some(port) = port_override {
+        console.port = port;
+    }
+    if let Err(error) = net::netconsole_open(console.port) {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!("netconsole: not started ({})\n", error.as_str()),
+        );
+        return Status::Failed;
+    }
+    // A `start` after `stop` reopens the mailbox and reuses a service that has not exited yet;
+    // asking the executor also covers one that `kill -KILL` dropped.
+    if !executor::is_running("netconsole") && !executor::spawn("netconsole", console_task()) {
+        net::netconsole_close();
+        serial::write_severity_line(
+            Severity::Error,
+            "netconsole: start failed (no executor slot)",
+        );
+        return Status::Failed;
+    }
+    serial::write_fmt(format_args!(
+        "netconsole: listening on udp port {}\n",
+        console.port
+    ));
+    Status::Ok
+}
+
+pub(super) fn stop_to_serial() {
+    net::netconsole_close();
+    serial::write_line("netconsole: stopped");
+}
+
+pub(super) fn log_status(console: &NetConsole) {
+    let stats = net::netconsole_stats();
+    serial::write_fmt(format_args!(
+        "netconsole: open={} port={} token_set={} requests={} commands={} denied={} rejected={} busy_drops={} reply_datagrams={} reply_errors={}\n",
+        stats.open,
+        stats.port,
+        console.token_len > 0,
+        stats.requests,
+        console.commands,
+        console.denied,
+        console.rejected,
+        stats.busy_drops,
+        stats.reply_datagrams,
+        stats.reply_errors
+    ));
+}
+
+/// Serves requests until `netconsole stop` or a SIGTERM. Output is captured like `watch`, so it never
+/// reaches COM1; one log line per request records who ran what.
+async fn console_task() {
+    let mut request = [0u8; net::NETCONSOLE_REQUEST_CAP];
+    let mut output = vec![0u8; OUTPUT_CAPACITY];
+    let mut seq = 0u64;
+    while let Some((peer, len)) = executor::unless_terminated(net::netconsole_recv(&mut request))
+        .await
+        .flatten()
+    {
+        seq = seq.saturating_add(1);
+        // SAFETY: services are polled from the main loop between shell polls, never while a
+        // shell command is running.
+        let shell = unsafe { &mut *SHELL_STATE.0.get() };
+        let (outcome, output_len) = match shell.netconsole.command(&request[..len], &shell.aliases)
+        {
+            Ok(command) => {
+                shell.netconsole.commands = shell.netconsole.commands.saturating_add(1);
+                log::write(
+                    Subsystem::Net,
+                    Level::Info,
+                    format_args!(
+                        "netconsole: {}.{}.{}.{}:{} `{}`\n",
+                        peer.ip[0], peer.ip[1], peer.ip[2], peer.ip[3], peer.port, command
+                    ),
+                );
+                let len = serial::capture(&mut output, || execute(shell, command));
+                (Outcome::Ok, len)
+            }
+            Err(outcome) => {
+                match outcome {
+                    Outcome::Denied => {
+                        shell.netconsole.denied = shell.netconsole.denied.saturating_add(1);
+                    }
+                    Outcome::Rejected => {
+                        shell.netconsole.rejected = shell.netconsole.rejected.saturating_add(1);
+                    }
+                    Outcome::Ok | Outcome::Invalid => {}
+                }
+                log::write(
+                    Subsystem::Net,
+                    Level::Warning,
+                    format_args!(
+                        "netconsole: {}.{}.{}.{}:{} request {}\n",
+                        peer.ip[0],
+                        peer.ip[1],
+                        peer.ip[2],
+                        peer.ip[3],
+                        peer.port,
+                        outcome.as_str()
+                    ),
+                );
+                (outcome, 0)
+            }
+        };
+        let _ = net::netconsole_reply(&output[..output_len]);
+        let end = format!(
+            "netconsole: end seq={} status={} bytes={}\n",
+            seq,
+            outcome.as_str(),
+            output_len
+        );
+        let _ = net::netconsole_reply(end.as_bytes());
+    }
+    if executor::terminate_requested() {
+        net::netconsole_close();
+        serial::write_line("netconsole: stopped by signal");
+    }
+}
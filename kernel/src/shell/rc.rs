@@ -0,0 +1,47 @@
+// kernel/src/shell/rc.rs: `/RC` startup file, one shell command per line, run by
+// `shell::autostart` after the `/BOOT.CFG` options and again on `rc`.
+use super::alias::Aliases;
+use super::{ShellState, execute};
+use crate::fs;
+use crate::serial::{self, Severity};
+
+pub const RC_FILE: &str = "/RC";
+
+/// What one pass over the rc file did.
+pub struct RcReport {
+    pub ran: usize,
+    pub skipped: usize,
+}
+
+/// Runs every line of `/RC`; blank lines and `#` comments are ignored. `None` when the file
+/// does not exist.
+pub fn run(shell: &mut ShellState) -> Option<RcReport> {
+    let mut data = [0u8; fs::MAX_FILE_BYTES];
+    let len = fs::read_file(RC_FILE, &mut data).ok()?;
+    let text = core::str::from_utf8(&data[..len]).unwrap_or("");
+    let mut report = RcReport { ran: 0, skipped: 0 };
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        serial::write_fmt(format_args!("rc: {line}\n"));
+        if !allowed(&shell.aliases, line) {
+            serial::write_severity_line(
+                Severity::Warning,
+                "rc: skipped (fs import and rc cannot run from the rc file)",
+            );
+            report.skipped += 1;
+            continue;
+        }
+        execute(shell, line);
+        report.ran += 1;
+    }
+    Some(report)
+}
+
+/// `fs import` would take over the lines typed after boot, and `rc` would run the file again.
+fn allowed(aliases: &Aliases, line: &str) -> bool {
+    let expanded = aliases.expand(line);
+    let command = expanded.as_deref().unwrap_or(line);
+    !command.starts_with("fs import") && command != "rc" && !command.starts_with("rc ")
+}
@@ -0,0 +1,116 @@
+// kernel/src/shell/script.rs: exit status of shell commands (`$?`), the `iferr` directive and
+// `run <file>` scripts. Every command handler returns a `Status`; `$?` is its code.
+use super::alias::Aliases;
+use super::{ShellState, execute};
+use crate::fs;
+use crate::serial::{self, Severity};
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+
+/// How a shell command ended; `$?` is its `code`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub enum Status {
+    Ok,
+    /// The command ran and failed.
+    Failed,
+    /// The command line was not understood: usage messages and the like.
+    Usage,
+}
+
+impl Status {
+    pub const fn code(self) -> i32 {
+        match self {
+            Self::Ok => 0,
+            Self::Failed => 1,
+            Self::Usage => 2,
+        }
+    }
+
+    /// `Ok` when `ok` holds, `Failed` otherwise.
+    pub const fn check(ok: bool) -> Self {
+        if ok { Self::Ok } else { Self::Failed }
+    }
+
+    /// Prints `message` as a warning and returns `Usage`.
+    pub fn usage(message: &str) -> Self {
+        serial::write_severity_line(Severity::Warning, message);
+        Self::Usage
+    }
+
+    /// Prints `args` as an error and returns `Failed`.
+    pub fn failed(args: fmt::Arguments<'_>) -> Self {
+        serial::write_severity_fmt(Severity::Error, args);
+        Self::Failed
+    }
+}
+
+/// `input` with every `$?` replaced by `status`; `None` when it has none.
+pub fn expand(input: &str, status: i32) -> Option<String> {
+    if !input.contains("$?") {
+        return None;
+    }
+    Some(input.replace("$?", &format!("{status}")))
+}
+
+/// Runs `run <path>` and leaves `$?` at the status of the last line that ran.
+pub fn run_to_serial(shell: &mut ShellState, path: &str) {
+    if path.is_empty() {
+        shell.status = Status::usage("usage: run <file>").code();
+        return;
+    }
+    let mut data = [0u8; fs::MAX_FILE_BYTES];
+    let len = match fs::read_file(path, &mut data) {
+        Ok(len) => len,
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Error,
+                format_args!("run: {path}: {}\n", err.as_str()),
+            );
+            shell.status = Status::Failed.code();
+            return;
+        }
+    };
+    let text = core::str::from_utf8(&data[..len]).unwrap_or("");
+    let (mut ran, mut skipped, mut failed) = (0usize, 0usize, 0usize);
+    shell.status = 0;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        serial::write_fmt(format_args!("run: {line}\n"));
+        if !allowed(&shell.aliases, line) {
+            serial::write_severity_line(
+                Severity::Warning,
+                "run: skipped (fs import, rc and run cannot run from a script)",
+            );
+            skipped += 1;
+            continue;
+        }
+        execute(shell, line);
+        ran += 1;
+        if shell.status != 0 {
+            failed += 1;
+        }
+    }
+    let status = shell.status;
+    serial::write_fmt(format_args!(
+        "run: {path} ran={ran} skipped={skipped} failed={failed} status={status}\n"
+    ));
+}
+
+/// Scripts run one level deep: `run` and `rc` inside one would let a file run itself, and
+/// `fs import` would take over the lines typed afterwards.
+fn allowed(aliases: &Aliases, line: &str) -> bool {
+    let expanded = aliases.expand(line);
+    let mut command = expanded.as_deref().unwrap_or(line);
+    while let Some(rest) = command.strip_prefix("iferr ") {
+        command = rest.trim_start();
+    }
+    !command.starts_with("fs import")
+        && command != "rc"
+        && !command.starts_with("rc ")
+        && command != "run"
+        && !command.starts_with("run ")
+}
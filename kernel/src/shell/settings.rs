@@ -0,0 +1,324 @@
+// kernel/src/shell/settings.rs: runtime settings (mouse speed, audio volume, doom view fps, log
+// levels, display rotation) behind the `settings` command and window, persisted as boot config lines.
+use super::Status;
+use super::boot_config::{self, BOOT_CONFIG_FILE};
+#[cfg(feature = "audio")]
+use crate::audio;
+#[cfg(feature = "doom")]
+use crate::doom;
+use crate::gfx;
+use crate::log::{self, Subsystem};
+use crate::serial::{self, Severity};
+use crate::storage::kvstore;
+use alloc::string::String;
+use core::fmt::Write;
+
+const MOUSE_SPEED_STEP: u32 = 25;
+#[cfg(feature = "audio")]
+const VOLUME_STEP: u32 = 10;
+/// Rates the 100 Hz PIT divides evenly enough to pace the doom view.
+#[cfg(feature = "doom")]
+const DOOM_FPS_STEPS: [u32; 6] = [5, 10, 20, 25, 33, 50];
+/// Rows above the log levels; a setting of a subsystem that is built out has no row.
+const FIXED: &[Setting] = &[
+    Setting::MouseSpeed,
+    #[cfg(feature = "audio")]
+    Setting::Volume,
+    #[cfg(feature = "doom")]
+    Setting::DoomFps,
+    Setting::Rotation,
+];
+const ROWS: usize = FIXED.len() + Subsystem::ALL.len();
+/// Settings window grid row of the first setting; row 0 is the heading.
+const FIRST_ROW: usize = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Setting {
+    MouseSpeed,
+    #[cfg(feature = "audio")]
+    Volume,
+    #[cfg(feature = "doom")]
+    DoomFps,
+    /// Degrees clockwise: 0, 90, 180 or 270.
+    Rotation,
+    /// 0 is normal, 1 is quiet.
+    Log(Subsystem),
+}
+
+impl Setting {
+    fn at(row: usize) -> Option<Self> {
+        match FIXED.get(row) {
+            Some(setting) => Some(*setting),
+            None => Subsystem::ALL
+                .get(row - FIXED.len())
+                .copied()
+                .map(Self::Log),
+        }
+    }
+
+    fn all() -> impl Iterator<Item = Self> {
+        (0..ROWS).filter_map(Self::at)
+    }
+
+    fn parse_key(key: &str) -> Option<Self> {
+        Self::all().find(|setting| setting.key() == key)
+    }
+
+    const fn key(self) -> &'static str {
+        match self {
+            Self::MouseSpeed => "mouse.speed",
+            #[cfg(feature = "audio")]
+            Self::Volume => "audio.volume",
+            #[cfg(feature = "doom")]
+            Self::DoomFps => "doom.fps",
+            Self::Rotation => "gfx.rotate",
+            Self::Log(Subsystem::Net) => "log.net",
+            Self::Log(Subsystem::Gfx) => "log.gfx",
+            Self::Log(Subsystem::Doom) => "log.doom",
+            Self::Log(Subsystem::Audio) => "log.audio",
+            Self::Log(Subsystem::Fs) => "log.fs",
+            Self::Log(Subsystem::Storage) => "log.storage",
+            Self::Log(Subsystem::Proc) => "log.proc",
+        }
+    }
+
+    fn current(self) -> u32 {
+        match self {
+            Self::MouseSpeed => u32::from(gfx::mouse_speed()),
+            #[cfg(feature = "audio")]
+            Self::Volume => u32::from(audio::volume()),
+            #[cfg(feature = "doom")]
+            Self::DoomFps => doom::status().view_fps,
+            Self::Rotation => gfx::rotation().degrees(),
+            Self::Log(subsystem) => u32::from(log::quiet(subsystem)),
+        }
+    }
+
+    fn parse_value(self, text: &str) -> Option<u32> {
+        let text = text.trim();
+        match self {
+            Self::Log(_) => match text {
+                "normal" | "off" => Some(0),
+                "quiet" | "on" => Some(1),
+                _ => None,
+            },
+            _ => {
+                let value = text.strip_suffix('%').unwrap_or(text).parse::<u32>().ok()?;
+                self.in_range(value).then_some(value)
+            }
+        }
+    }
+
+    fn in_range(self, value: u32) -> bool {
+        match self {
+            Self::MouseSpeed => {
+                (u32::from(gfx::MIN_MOUSE_SPEED)..=u32::from(gfx::MAX_MOUSE_SPEED)).contains(&value)
+            }
+            #[cfg(feature = "audio")]
+            Self::Volume => value <= u32::from(audio::MAX_VOLUME),
+            #[cfg(feature = "doom")]
+            Self::DoomFps => (doom::MIN_VIEW_FPS..=doom::MAX_VIEW_FPS).contains(&value),
+            Self::Rotation => gfx::Rotation::from_degrees(value).is_some(),
+            Self::Log(_) => value <= 1,
+        }
+    }
+
+    fn write_value(self, out: &mut String, value: u32) {
+        let _ = match self {
+            Self::MouseSpeed => write!(out, "{value}%"),
+            #[cfg(feature = "audio")]
+            Self::Volume => write!(out, "{value}%"),
+            #[cfg(feature = "doom")]
+            Self::DoomFps => write!(out, "{value}"),
+            Self::Rotation => write!(out, "{value}"),
+            Self::Log(_) => out.write_str(if value == 0 { "normal" } else { "quiet" }),
+        };
+    }
+
+    /// Boot config form: like the display value, without the percent sign.
+    fn config_value(self, value: u32) -> String {
+        let mut out = String::new();
+        self.write_value(&mut out, value);
+        if let Some(len) = out.strip_suffix('%').map(str::len) {
+            out.truncate(len);
+        }
+        out
+    }
+
+    /// Value one step down (`delta < 0`) or up from `value`, clamped to the range; log rows
+    /// toggle.
+    fn stepped(self, value: u32, delta: i8) -> u32 {
+        let up = delta > 0;
+        match self {
+            Self::MouseSpeed => {
+                let value = if up {
+                    value.saturating_add(MOUSE_SPEED_STEP)
+                } else {
+                    value.saturating_sub(MOUSE_SPEED_STEP)
+                };
+                value.clamp(
+                    u32::from(gfx::MIN_MOUSE_SPEED),
+                    u32::from(gfx::MAX_MOUSE_SPEED),
+                )
+            }
+            #[cfg(feature = "audio")]
+            Self::Volume => {
+                let value = if up {
+                    value.saturating_add(VOLUME_STEP)
+                } else {
+                    value.saturating_sub(VOLUME_STEP)
+                };
+                value.min(u32::from(audio::MAX_VOLUME))
+            }
+            #[cfg(feature = "doom")]
+            Self::DoomFps => {
+                let next = if up {
+                    DOOM_FPS_STEPS.iter().find(|fps| **fps > value)
+                } else {
+                    DOOM_FPS_STEPS.iter().rev().find(|fps| **fps < value)
+                };
+                next.copied().unwrap_or(value)
+            }
+            Self::Rotation => (value + if up { 90 } else { 270 }) % 360,
+            Self::Log(_) => u32::from(value == 0),
+        }
+    }
+
+    fn apply(self, value: u32) -> bool {
+        match self {
+            Self::MouseSpeed => gfx::set_mouse_speed(value as u16),
+            #[cfg(feature = "audio")]
+            Self::Volume => audio::set_volume(value as u8),
+            #[cfg(feature = "doom")]
+            Self::DoomFps => doom::set_view_fps(value),
+            Self::Rotation => match gfx::Rotation::from_degrees(value).map(gfx::set_rotation) {
+                Some(Ok(changed)) => changed,
+                Some(Err(err)) => {
+                    serial::write_severity_fmt(
+                        Severity::Warning,
+                        format_args!("settings: gfx.rotate={value} failed ({})\n", err.as_str()),
+                    );
+                    false
+                }
+                None => false,
+            },
+            Self::Log(subsystem) => log::set_quiet(subsystem, value != 0),
+        }
+    }
+}
+
+/// Applies every setting found in the boot config, falling back to the copy in the kv store
+/// when the config lacks it (e.g. after a reformat); runs once boot is complete.
+pub(super) fn apply_from_config() {
+    let mut applied = 0usize;
+    let mut from_kv = 0usize;
+    for setting in Setting::all() {
+        if let Some(value) = boot_config::lookup(setting.key(), |text| setting.parse_value(text)) {
+            let _ = setting.apply(value);
+            applied += 1;
+        } else if let Some(value) =
+            kvstore::get(setting.key()).and_then(|stored| setting.parse_value(stored.as_str()))
+        {
+            let _ = setting.apply(value);
+            from_kv += 1;
+        }
+    }
+    serial::write_fmt(format_args!(
+        "Shell: settings applied={applied} from_kv={from_kv} config={BOOT_CONFIG_FILE}\n"
+    ));
+    refresh_view();
+}
+
+pub(super) fn list_to_serial() {
+    let mut line = String::new();
+    for setting in Setting::all() {
+        line.clear();
+        setting.write_value(&mut line, setting.current());
+        serial::write_fmt(format_args!("settings: {}={}\n", setting.key(), line));
+    }
+}
+
+/// `settings set <key> <value>`.
+pub(super) fn set_to_serial(key: &str, value: &str) -> Status {
+    let Some(setting) = Setting::parse_key(key) else {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!("settings: unknown key {key}\n"),
+        );
+        return Status::Usage;
+    };
+    let Some(value) = setting.parse_value(value) else {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!("settings: bad value for {}\n", setting.key()),
+        );
+        return Status::Usage;
+    };
+    store_to_serial(setting, value)
+}
+
+/// A click or arrow key in the settings window stepped list row `row`.
+pub(super) fn adjust_to_serial(row: usize, delta: i8) {
+    let Some(setting) = Setting::at(row) else {
+        return;
+    };
+    let _ = store_to_serial(setting, setting.stepped(setting.current(), delta));
+}
+
+/// Applies `value` and saves it; fails when the save does, though the value stays applied.
+fn store_to_serial(setting: Setting, value: u32) -> Status {
+    let changed = setting.apply(value);
+    let stored = setting.config_value(value);
+    let mut shown = String::new();
+    setting.write_value(&mut shown, value);
+    if kvstore::is_ready()
+        && let Err(err) = kvstore::set(setting.key(), &stored)
+    {
+        serial::write_severity_fmt(
+            Severity::Warning,
+            format_args!(
+                "settings: kv copy of {} failed ({})\n",
+                setting.key(),
+                err.as_str()
+            ),
+        );
+    }
+    let status = match boot_config::set(setting.key(), &stored) {
+        Ok(_) => {
+            serial::write_fmt(format_args!(
+                "settings: {}={}{} saved to {} (sync to keep it across reboots)\n",
+                setting.key(),
+                shown,
+                if changed { "" } else { " (unchanged)" },
+                BOOT_CONFIG_FILE
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!(
+                    "settings: {}={} applied, save failed ({})\n",
+                    setting.key(),
+                    shown,
+                    err.as_str()
+                ),
+            );
+            Status::Failed
+        }
+    };
+    refresh_view();
+    status
+}
+
+/// Redraws the settings window list from the live values.
+pub(super) fn refresh_view() {
+    let mut view = String::new();
+    let _ = writeln!(view, "SETTINGS  (arrows or - / + to change)");
+    for setting in Setting::all() {
+        let _ = write!(view, "{:<14}", setting.key());
+        setting.write_value(&mut view, setting.current());
+        view.push('\n');
+    }
+    gfx::set_settings_list(&view, FIRST_ROW, ROWS);
+}
@@ -0,0 +1,479 @@
+// kernel/src/shell/ui_commands.rs: shell commands of the `desktop` feature (`ui`, `settings`)
+// and the window actions `shell::poll` takes from gfx.
+#[cfg(feature = "doom")]
+use super::doom_commands;
+use super::{
+    ShellState, Status, handle_file_manager_command, parse_on_off, print_prompt,
+    save_history_to_serial, settings,
+};
+use crate::fs;
+use crate::gfx;
+use crate::log;
+use crate::mem;
+use crate::serial::{self, Severity};
+use crate::utf8;
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Write;
+use core::str;
+
+const FILE_MANAGER_LIST_LINES: usize = 5;
+const UI_BENCH_DEFAULT_FRAMES: u32 = 16;
+const FILE_MANAGER_PREVIEW_BYTES: usize = 180;
+
+/// Runs `input` if it is a desktop command; `None` leaves it to the rest of `dispatch`.
+pub(super) fn dispatch(input: &str) -> Option<Status> {
+    if let Some(rest) = input.strip_prefix("ui bench") {
+        let rest = rest.trim();
+        let frames = if rest.is_empty() {
+            Some(UI_BENCH_DEFAULT_FRAMES)
+        } else {
+            rest.parse::<u32>()
+                .ok()
+                .filter(|frames| (1..=256).contains(frames))
+        };
+        return Some(match frames {
+            Some(frames) => ui_bench_to_serial(frames),
+            None => Status::usage("usage: ui bench [1..256]"),
+        });
+    }
+    if let Some(rest) = input.strip_prefix("ui debug damage") {
+        let enabled = match rest.trim() {
+            "on" => true,
+            "off" => false,
+            _ => return Some(Status::usage("usage: ui debug damage <on|off>")),
+        };
+        let changed = gfx::set_damage_debug(enabled);
+        serial::write_fmt(format_args!(
+            "ui: damage debug={}{}\n",
+            if enabled { "on" } else { "off" },
+            if changed { "" } else { " (unchanged)" }
+        ));
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("ui click close") {
+        return Some(match gfx::WindowId::parse(rest) {
+            Some(window) if gfx::click_close(window) => {
+                serial::write_fmt(format_args!(
+                    "ui: close requested window={}\n",
+                    window.as_str()
+                ));
+                Status::Ok
+            }
+            Some(window) => {
+                serial::write_severity_fmt(
+                    Severity::Warning,
+                    format_args!(
+                        "ui: {} window has no close button or is not open\n",
+                        window.as_str()
+                    ),
+                );
+                Status::Failed
+            }
+            None => Status::usage("usage: ui click close <doom|settings|app>"),
+        });
+    }
+    if let Some(rest) = input.strip_prefix("ui dump") {
+        return Some(match gfx::WindowId::parse(rest) {
+            Some(window) => serial::compressed(|| gfx::dump_window_to_serial(window)),
+            None => Status::usage("usage: ui dump <shell|fm|doom|settings|app>"),
+        });
+    }
+    if let Some(rest) = input.strip_prefix("settings") {
+        let rest = rest.trim();
+        match rest {
+            "" => settings::list_to_serial(),
+            "ui" => {
+                settings::refresh_view();
+                let opened = gfx::open_settings_window();
+                serial::write_fmt(format_args!(
+                    "settings: window open{}\n",
+                    if opened { "" } else { " (unchanged)" }
+                ));
+            }
+            "close" => {
+                gfx::close_settings_window();
+                serial::write_line("settings: window closed");
+            }
+            _ => {
+                let mut parts = rest.split_whitespace();
+                return Some(
+                    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                        (Some("set"), Some(key), Some(value), None) => {
+                            settings::set_to_serial(key, value)
+                        }
+                        _ => Status::usage("usage: settings [ui|close|set <key> <value>]"),
+                    },
+                );
+            }
+        }
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("ui mirror") {
+        let rest = rest.trim();
+        if !rest.is_empty() {
+            let Some(level) = log::Level::parse(rest) else {
+                return Some(Status::usage("usage: ui mirror [info|warning|error]"));
+            };
+            if !gfx::set_mirror_level(level) {
+                serial::write_line("ui: backend=none ready=false");
+                return Some(Status::Failed);
+            }
+        }
+        let Some((level, cursor, skipped)) = gfx::mirror_status() else {
+            serial::write_line("ui: backend=none ready=false");
+            return Some(Status::Failed);
+        };
+        serial::write_fmt(format_args!(
+            "ui: mirror level={} cursor={cursor} written={} ring={} skipped={skipped}\n",
+            level.as_str(),
+            serial::log_written(),
+            serial::LOG_RING_BYTES
+        ));
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("ui kiosk") {
+        let enabled = match rest.trim() {
+            "" => {
+                serial::write_fmt(format_args!(
+                    "ui: kiosk={}\n",
+                    if gfx::kiosk() { "on" } else { "off" }
+                ));
+                return Some(Status::Ok);
+            }
+            "on" => true,
+            "off" => false,
+            _ => return Some(Status::usage("usage: ui kiosk [on|off]")),
+        };
+        let changed = gfx::set_kiosk(enabled);
+        serial::write_fmt(format_args!(
+            "ui: kiosk={}{}\n",
+            if enabled { "on" } else { "off" },
+            if changed { "" } else { " (unchanged)" }
+        ));
+        return Some(Status::Ok);
+    }
+    if let Some(rest) = input.strip_prefix("ui rotate")
+        && (rest.is_empty() || rest.starts_with(' '))
+    {
+        return Some(rotate_to_serial(rest.trim()));
+    }
+    for command in ["ui night", "ui brightness", "ui contrast"] {
+        if let Some(rest) = input.strip_prefix(command)
+            && (rest.is_empty() || rest.starts_with(' '))
+        {
+            return Some(color_to_serial(&command[3..], rest.trim()));
+        }
+    }
+    if let Some(rest) = input.strip_prefix("ui cursor ") {
+        match gfx::CursorTheme::parse(rest.trim()) {
+            Some(theme) if gfx::set_cursor_theme(theme) => {
+                serial::write_fmt(format_args!("ui: cursor theme={}\n", theme.as_str()));
+            }
+            Some(theme) => serial::write_fmt(format_args!(
+                "ui: cursor theme already {}\n",
+                theme.as_str()
+            )),
+            None => return Some(Status::usage("usage: ui cursor <light|dark>")),
+        }
+        return Some(Status::Ok);
+    }
+    match input {
+        "ui" => {
+            gfx::log_info();
+        }
+        "ui redraw" => {
+            gfx::redraw();
+            serial::write_line("ui: redraw requested");
+        }
+        "ui checksum" => return Some(log_ui_checksum()),
+        "ui app" => gfx::log_app(),
+        "ui close app" => match gfx::close_app_window() {
+            Some(owner) => {
+                serial::write_fmt(format_args!("ui: app window closed (owner pid={owner})\n"))
+            }
+            None => {
+                serial::write_line("ui: app window not open");
+                return Some(Status::Failed);
+            }
+        },
+        "ui next" => {
+            gfx::focus_next();
+            serial::write_line("ui: focus advanced");
+        }
+        "ui minimize" => {
+            gfx::toggle_focused_minimize();
+            serial::write_line("ui: focused window minimize toggled");
+        }
+        "ui tile left" => tile_focused_to_serial(gfx::TileMode::Left),
+        "ui tile right" => tile_focused_to_serial(gfx::TileMode::Right),
+        "ui tile max" => tile_focused_to_serial(gfx::TileMode::Max),
+        "ui tile restore" => tile_focused_to_serial(gfx::TileMode::Restore),
+        _ => return None,
+    }
+    Some(Status::Ok)
+}
+
+fn log_ui_checksum() -> Status {
+    let Some(report) = gfx::checksum() else {
+        serial::write_line("ui checksum: framebuffer unavailable");
+        return Status::Failed;
+    };
+    let presents = report.presents;
+    let (x, y, w, h) = presents.last_region;
+    serial::write_fmt(format_args!(
+        "ui: checksum frame={:016x} source={}",
+        report.frame, report.source
+    ));
+    match report.doom {
+        Some(doom) => serial::write_fmt(format_args!(" doom={doom:016x}")),
+        None => serial::write_str(" doom=none"),
+    }
+    serial::write_fmt(format_args!(
+        " presents={} rolling={:016x} last_region={x},{y},{w}x{h} last_region_checksum={:016x} full_presents={}",
+        presents.presents, presents.rolling, presents.last_region_checksum, presents.full_presents
+    ));
+    match presents.last_full {
+        Some(full) => serial::write_fmt(format_args!(" last_full={full:016x}\n")),
+        None => serial::write_line(" last_full=none"),
+    }
+    Status::Ok
+}
+
+/// Runs the compositor benchmark; with promoted 2 MiB slots it runs once per page size so the
+/// TLB effect shows up side by side, then restores the previous setting.
+fn ui_bench_to_serial(frames: u32) -> Status {
+    let was_enabled = mem::hugepage::enabled();
+    let passes: &[bool] = if mem::hugepage::promoted_count() > 0 {
+        &[true, false]
+    } else {
+        &[was_enabled]
+    };
+    for &hugepages in passes {
+        let _ = mem::hugepage::set_enabled(hugepages);
+        let Some(bench) = gfx::bench(frames) else {
+            let _ = mem::hugepage::set_enabled(was_enabled);
+            serial::write_line("ui bench: framebuffer unavailable");
+            return Status::Failed;
+        };
+        serial::write_fmt(format_args!(
+            "ui bench: frames={} pages={} redraw_cycles={} present_cycles={} bytes_per_frame={} text_cycles={} text_uncached_cycles={}\n",
+            bench.frames,
+            if hugepages { "2m" } else { "4k" },
+            bench.redraw_cycles,
+            bench.present_cycles,
+            bench.bytes_per_frame,
+            bench.text_cycles,
+            bench.text_uncached_cycles
+        ));
+    }
+    let _ = mem::hugepage::set_enabled(was_enabled);
+    Status::Ok
+}
+
+fn tile_focused_to_serial(mode: gfx::TileMode) {
+    if gfx::tile_focused(mode) {
+        serial::write_fmt(format_args!("ui: focused window tiled {}\n", mode.as_str()));
+    } else {
+        serial::write_fmt(format_args!(
+            "ui: focused window already {}\n",
+            mode.as_str()
+        ));
+    }
+}
+
+fn rotate_to_serial(args: &str) -> Status {
+    if args.is_empty() {
+        serial::write_fmt(format_args!("ui: rotate={}\n", gfx::rotation().degrees()));
+        return Status::Ok;
+    }
+    let Some(rotation) = gfx::Rotation::parse(args) else {
+        return Status::usage("usage: ui rotate [0|90|180|270]");
+    };
+    match gfx::set_rotation(rotation) {
+        Ok(changed) => {
+            serial::write_fmt(format_args!(
+                "ui: rotate={}{}\n",
+                rotation.degrees(),
+                if changed { "" } else { " (unchanged)" }
+            ));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("ui: rotate failed ({})\n", err.as_str()),
+            );
+            Status::Failed
+        }
+    }
+}
+
+/// `ui night|brightness|contrast [value]`; the bare form prints the whole adjust.
+fn color_to_serial(command: &str, args: &str) -> Status {
+    let mut adjust = gfx::color_adjust();
+    if !args.is_empty() {
+        let parsed = match command {
+            "night" => parse_on_off(args).map(|night| adjust.night = night),
+            "brightness" => gfx::ColorAdjust::parse_brightness(args)
+                .map(|brightness| adjust.brightness = brightness),
+            _ => gfx::ColorAdjust::parse_contrast(args).map(|contrast| adjust.contrast = contrast),
+        };
+        if parsed.is_none() {
+            let usage = match command {
+                "night" => "usage: ui night [on|off]",
+                "brightness" => "usage: ui brightness [-100..100]",
+                _ => "usage: ui contrast [50..200]",
+            };
+            return Status::usage(usage);
+        }
+    }
+    let changed = args.is_empty() || gfx::set_color_adjust(adjust);
+    serial::write_fmt(format_args!(
+        "ui: night={} brightness={} contrast={}{}\n",
+        if adjust.night { "on" } else { "off" },
+        adjust.brightness,
+        adjust.contrast,
+        if changed { "" } else { " (unchanged)" }
+    ));
+    Status::Ok
+}
+
+/// Runs the file manager clicks, settings clicks and close requests gfx queued since the
+/// last poll.
+pub(super) fn run_window_actions(shell: &mut ShellState) {
+    while let Some(action) = gfx::pop_file_manager_action() {
+        run_file_manager_action(shell, action);
+    }
+    while let Some(action) = gfx::pop_settings_action() {
+        run_settings_action(shell, action);
+    }
+    while let Some(window) = gfx::pop_close_request() {
+        run_close_request(shell, window);
+    }
+}
+
+/// Runs the `fm` command behind a file manager toolbar click as if it had been typed, then
+/// redraws the prompt and any half-typed line.
+fn run_file_manager_action(shell: &mut ShellState, action: gfx::FileManagerAction) {
+    let mut entries = [fs::DirEntry::empty(); fs::MAX_FILES];
+    let count = fs::list_entries(&mut entries).min(FILE_MANAGER_LIST_LINES);
+    let name = action
+        .selected
+        .filter(|selected| *selected < count)
+        .map(|selected| entries[selected].name());
+    let command = match (action.button, name) {
+        (gfx::FileManagerButton::List, _) => String::from("fm list"),
+        (gfx::FileManagerButton::Verify, None) => String::from("fm verify"),
+        (gfx::FileManagerButton::Verify, Some(name)) => format!("fm verify {name}"),
+        (gfx::FileManagerButton::Open, Some(name)) => format!("fm open {name}"),
+        (gfx::FileManagerButton::Copy, Some(name)) => format!("fm copy {name} {name}.COPY"),
+        (gfx::FileManagerButton::Delete, Some(name)) => format!("fm delete {name}"),
+        (button, None) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("\nfm: {} needs a selected file\n", button.as_str()),
+            );
+            print_prompt();
+            serial::write_str(str::from_utf8(&shell.line[..shell.len]).unwrap_or(""));
+            return;
+        }
+    };
+    serial::write_fmt(format_args!(
+        "\nfm: button {} -> {}\n",
+        action.button.as_str(),
+        command
+    ));
+    let _ = handle_file_manager_command(&command);
+    print_prompt();
+    serial::write_str(str::from_utf8(&shell.line[..shell.len]).unwrap_or(""));
+}
+
+/// Close button of a window the kernel owns. Doom stops as `doom stop` does before its window
+/// goes, except in kiosk mode, where the doom window is all there is and the close is vetoed.
+fn run_close_request(shell: &mut ShellState, window: gfx::WindowId) {
+    serial::write_str("\n");
+    let reply = match window {
+        gfx::WindowId::Doom if gfx::kiosk() => gfx::CloseReply::Veto,
+        #[cfg(feature = "doom")]
+        gfx::WindowId::Doom => {
+            let _ = doom_commands::stop_doom_to_serial(shell);
+            gfx::CloseReply::Close
+        }
+        _ => gfx::CloseReply::Close,
+    };
+    gfx::answer_close(window, reply);
+    match reply {
+        gfx::CloseReply::Close => {
+            serial::write_fmt(format_args!("ui: {} window closed\n", window.as_str()))
+        }
+        gfx::CloseReply::Veto => serial::write_fmt(format_args!(
+            "ui: close of {} window vetoed (kiosk on)\n",
+            window.as_str()
+        )),
+    }
+    print_prompt();
+    serial::write_str(str::from_utf8(&shell.line[..shell.len]).unwrap_or(""));
+}
+
+/// Applies a settings window click or arrow key, or syncs as the `sync` command would.
+fn run_settings_action(shell: &mut ShellState, action: gfx::SettingsAction) {
+    serial::write_str("\n");
+    match action {
+        gfx::SettingsAction::Adjust { row, delta } => settings::adjust_to_serial(row, delta),
+        gfx::SettingsAction::Sync => {
+            serial::write_line("settings: button sync -> sync");
+            let _ = save_history_to_serial(&shell.history);
+            let _ = fs::sync_to_disk_to_serial();
+        }
+    }
+    print_prompt();
+    serial::write_str(str::from_utf8(&shell.line[..shell.len]).unwrap_or(""));
+}
+
+pub(super) fn refresh_file_manager_list_view() {
+    let mut entries = [fs::DirEntry::empty(); fs::MAX_FILES];
+    let count = fs::list_entries(&mut entries);
+    let listed = count.min(FILE_MANAGER_LIST_LINES);
+
+    let mut view = String::new();
+    let _ = writeln!(view, "FILES ({count})");
+    let _ = writeln!(view, "name               size");
+    for entry in entries.iter().take(listed) {
+        let _ = writeln!(view, "{} {}b", entry.name(), entry.size());
+    }
+    if count == 0 {
+        let _ = writeln!(view, "<empty>");
+    }
+    let _ = writeln!(view, "click a file, then a button below");
+
+    gfx::set_file_manager_list(&view, 2, listed, count, fs::MAX_FILES);
+}
+
+pub(super) fn refresh_file_manager_preview_view(path: &str, bytes: &[u8]) {
+    let mut view = String::new();
+    let _ = writeln!(view, "OPEN {}", path.trim());
+    let _ = writeln!(view, "{} bytes", bytes.len());
+    let _ = writeln!(view, "----------------");
+
+    let preview = &bytes[..bytes.len().min(FILE_MANAGER_PREVIEW_BYTES)];
+    for chunk in preview.utf8_chunks() {
+        for ch in chunk.valid().chars() {
+            match ch {
+                '\r' => {}
+                '\n' => view.push('\n'),
+                ch if ch.is_control() => view.push('.'),
+                ch => view.push(ch),
+            }
+        }
+        if !chunk.invalid().is_empty() {
+            view.push(utf8::REPLACEMENT);
+        }
+    }
+    if bytes.len() > FILE_MANAGER_PREVIEW_BYTES {
+        let _ = writeln!(view, "\n...truncated...");
+    }
+    let _ = writeln!(view, "\nfm list");
+
+    gfx::set_file_manager_text(&view);
+}
@@ -0,0 +1,117 @@
+// kernel/src/shell/watch.rs: `watch <ticks> <command>` state; each re-run is diffed line by line
+// against the previous one so only changed lines reach the console.
+use super::MAX_LINE_LEN;
+use crate::serial;
+
+pub const MAX_INTERVAL_TICKS: u64 = 360_000;
+pub const OUTPUT_CAPACITY: usize = 4096;
+
+pub struct Watch {
+    /// Bumped on every start and stop so a superseded background task notices and exits.
+    generation: u64,
+    active: bool,
+    command: [u8; MAX_LINE_LEN],
+    command_len: usize,
+    interval_ticks: u64,
+    runs: u64,
+    changed_runs: u64,
+    last: [u8; OUTPUT_CAPACITY],
+    last_len: usize,
+}
+
+impl Watch {
+    pub const fn new() -> Self {
+        Self {
+            generation: 0,
+            active: false,
+            command: [0; MAX_LINE_LEN],
+            command_len: 0,
+            interval_ticks: 0,
+            runs: 0,
+            changed_runs: 0,
+            last: [0; OUTPUT_CAPACITY],
+            last_len: 0,
+        }
+    }
+
+    /// Replaces any running watch; returns the generation the new task must carry.
+    pub fn start(&mut self, command: &str, interval_ticks: u64) -> u64 {
+        let len = command.len().min(MAX_LINE_LEN);
+        self.command[..len].copy_from_slice(&command.as_bytes()[..len]);
+        self.command_len = len;
+        self.interval_ticks = interval_ticks;
+        self.runs = 0;
+        self.changed_runs = 0;
+        self.last_len = 0;
+        self.active = true;
+        self.generation = self.generation.wrapping_add(1);
+        self.generation
+    }
+
+    pub fn stop(&mut self) -> bool {
+        self.generation = self.generation.wrapping_add(1);
+        core::mem::replace(&mut self.active, false)
+    }
+
+    pub fn is_current(&self, generation: u64) -> bool {
+        self.active && self.generation == generation
+    }
+
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    pub fn command(&self) -> &str {
+        core::str::from_utf8(&self.command[..self.command_len]).unwrap_or("")
+    }
+
+    pub fn interval_ticks(&self) -> u64 {
+        self.interval_ticks
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    pub fn changed_runs(&self) -> u64 {
+        self.changed_runs
+    }
+
+    /// Prints the lines of `output` that differ from the same line of the previous run (all
+    /// of them on the first run), then keeps `output` for the next comparison.
+    pub fn report(&mut self, output: &[u8], tick: u64) {
+        self.runs = self.runs.saturating_add(1);
+        let mut previous = self.last[..self.last_len].split(|&byte| byte == b'\n');
+        let mut changed = false;
+        for line in output.split(|&byte| byte == b'\n') {
+            let old = previous.next();
+            if old == Some(line) || (old.is_none() && line.is_empty()) {
+                continue;
+            }
+            if !changed {
+                serial::write_fmt(format_args!(
+                    "watch: run={} tick={} `{}`\n",
+                    self.runs,
+                    tick,
+                    self.command()
+                ));
+                changed = true;
+            }
+            serial::write_fmt(format_args!(
+                "  {}\n",
+                core::str::from_utf8(line).unwrap_or("<binary>")
+            ));
+        }
+        let gone = previous.filter(|line| !line.is_empty()).count();
+        if gone > 0 {
+            serial::write_fmt(format_args!("watch: {} trailing lines gone\n", gone));
+            changed = true;
+        }
+        if changed {
+            self.changed_runs = self.changed_runs.saturating_add(1);
+        }
+        let len = output.len().min(OUTPUT_CAPACITY);
+        self.last[..len].copy_from_slice(&output[..len]);
+        self.last_len = len;
+    }
+}
@@ -0,0 +1,83 @@
+// kernel/src/soft_assert.rs: non-fatal invariant checks that log, count and let the kernel continue.
+use crate::serial::{self, Severity};
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+
+static TOTAL: AtomicU64 = AtomicU64::new(0);
+static SITES: AtomicUsize = AtomicUsize::new(0);
+static LAST: AtomicPtr<Site> = AtomicPtr::new(ptr::null_mut());
+
+/// Evaluates `$cond`; when false, logs the call site and message, bumps the `soft_asserts`
+/// metric and returns `false` so the caller can take its recovery path. Each site logs its
+/// 1st, 2nd, 4th, 8th, ... failure so a hot path cannot flood the console.
+macro_rules! soft_assert {
+    ($cond:expr, $($arg:tt)+) => {{
+        let ok: bool = $cond;
+        if !ok {
+            static SITE: $crate::soft_assert::Site =
+                $crate::soft_assert::Site::new(file!(), line!());
+            SITE.fail(format_args!($($arg)+));
+        }
+        ok
+    }};
+}
+
+pub(crate) use soft_assert;
+
+/// Per-call-site hit counter, one static per `soft_assert!` expansion.
+pub struct Site {
+    file: &'static str,
+    line: u32,
+    hits: AtomicU64,
+}
+
+impl Site {
+    pub const fn new(file: &'static str, line: u32) -> Self {
+        Self {
+            file,
+            line,
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    #[cold]
+    pub fn fail(&'static self, args: fmt::Arguments<'_>) {
+        let hits = self.hits.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+        if hits == 1 {
+            SITES.fetch_add(1, Ordering::Relaxed);
+        }
+        TOTAL.fetch_add(1, Ordering::Relaxed);
+        LAST.store(ptr::from_ref(self).cast_mut(), Ordering::Relaxed);
+        if hits.is_power_of_two() {
+            // No unwinder yet; the call site stands in for a backtrace.
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!(
+                    "soft-assert: {}:{} {args} (hits={hits})\n",
+                    self.file, self.line
+                ),
+            );
+        }
+    }
+}
+
+/// Total failed soft asserts since boot (the `soft_asserts` metric).
+pub fn count() -> u64 {
+    TOTAL.load(Ordering::Relaxed)
+}
+
+pub fn log_status() {
+    let last = LAST.load(Ordering::Relaxed);
+    // SAFETY: `LAST` only ever holds null or a pointer to a `soft_assert!` site static.
+    match unsafe { last.as_ref() } {
+        Some(site) => serial::write_fmt(format_args!(
+            "asserts: soft_asserts={} sites={} last={}:{}\n",
+            count(),
+            SITES.load(Ordering::Relaxed),
+            site.file,
+            site.line
+        )),
+        None => serial::write_line("asserts: soft_asserts=0 sites=0 last=none"),
+    }
+}
@@ -0,0 +1,502 @@
+// kernel/src/storage/kvstore.rs: tiny key-value store in the last sectors of the data disk, outside
+// diskfs, for the boot counter, the last-crash flag and settings that must survive a reformat.
+use crate::fs::crc32::crc32;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+const MAGIC: &[u8; 8] = b"AROSTKV1";
+const VERSION: u16 = 1;
+/// Two copies; a write goes to the older one, so a torn write leaves the previous state intact.
+const SLOTS: usize = 2;
+const SLOT_SECTORS: usize = 4;
+/// Sectors at the end of the disk that diskfs never touches.
+pub const RESERVED_SECTORS: u64 = (SLOTS * SLOT_SECTORS) as u64;
+const SLOT_BYTES: usize = SLOT_SECTORS * super::SECTOR_SIZE;
+/// magic[8] version[2] count[2] crc32[4] generation[8] reserved[8]; crc covers the entries.
+const HEADER_BYTES: usize = 32;
+pub const MAX_KEY_BYTES: usize = 22;
+pub const MAX_VALUE_BYTES: usize = 40;
+/// key_len[1] value_len[1] key[22] value[40].
+const ENTRY_BYTES: usize = 2 + MAX_KEY_BYTES + MAX_VALUE_BYTES;
+pub const MAX_ENTRIES: usize = (SLOT_BYTES - HEADER_BYTES) / ENTRY_BYTES;
+
+const BOOT_COUNT_KEY: &str = "boot.count";
+/// Set by the panic handler, reported and cleared by the next boot.
+const CRASH_KEY: &str = "boot.crash";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KvState {
+    Unavailable,
+    /// No valid slot was found; the store starts empty.
+    Formatted,
+    Loaded,
+}
+
+impl KvState {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Unavailable => "unavailable",
+            Self::Formatted => "formatted",
+            Self::Loaded => "loaded",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KvError {
+    Unavailable,
+    InvalidKey,
+    ValueTooLong,
+    Full,
+    Io,
+}
+
+impl KvError {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Unavailable => "unavailable",
+            Self::InvalidKey => "invalid_key",
+            Self::ValueTooLong => "value_too_long",
+            Self::Full => "full",
+            Self::Io => "io",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct KvReport {
+    pub state: KvState,
+    pub first_sector: u64,
+    pub generation: u64,
+    pub entries: usize,
+    /// Slots that had the magic but failed the version or checksum check.
+    pub bad_slots: usize,
+    pub boot_count: u64,
+    /// What the previous boot left in `boot.crash`, e.g. `panic`.
+    pub last_crash: Option<Value>,
+}
+
+#[derive(Clone, Copy)]
+pub struct Value {
+    bytes: [u8; MAX_VALUE_BYTES],
+    len: u8,
+}
+
+impl Value {
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..usize::from(self.len)]).unwrap_or("<invalid-utf8>")
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    key: [u8; MAX_KEY_BYTES],
+    key_len: u8,
+    value: Value,
+}
+
+impl Entry {
+    const fn empty() -> Self {
+        Self {
+            key: [0; MAX_KEY_BYTES],
+            key_len: 0,
+            value: Value {
+                bytes: [0; MAX_VALUE_BYTES],
+                len: 0,
+            },
+        }
+    }
+
+    fn key(&self) -> &str {
+        core::str::from_utf8(&self.key[..usize::from(self.key_len)]).unwrap_or("<invalid-key>")
+    }
+}
+
+struct KvStore {
+    state: KvState,
+    first_sector: u64,
+    generation: u64,
+    /// Slot holding `generation`; the next write goes to the other one.
+    active_slot: usize,
+    entries: [Entry; MAX_ENTRIES],
+    count: usize,
+    writes: u64,
+    boot_count: u64,
+}
+
+struct KvCell(UnsafeCell<KvStore>);
+
+// SAFETY: the kernel is single-threaded; the store is only touched from the main loop and,
+// once, from the panic handler (see `record_panic`).
+unsafe impl Sync for KvCell {}
+
+static KV: KvCell = KvCell(UnsafeCell::new(KvStore {
+    state: KvState::Unavailable,
+    first_sector: 0,
+    generation: 0,
+    active_slot: 0,
+    entries: [Entry::empty(); MAX_ENTRIES],
+    count: 0,
+    writes: 0,
+    boot_count: 0,
+}));
+static PANIC_RECORDED: AtomicBool = AtomicBool::new(false);
+
+fn with_kv<R>(f: impl FnOnce(&mut KvStore) -> R) -> R {
+    // SAFETY: see `KvCell`.
+    unsafe { f(&mut *KV.0.get()) }
+}
+
+impl KvStore {
+    fn position(&self, key: &str) -> Option<usize> {
+        self.entries[..self.count]
+            .iter()
+            .position(|entry| entry.key() == key)
+    }
+
+    fn get(&self, key: &str) -> Option<Value> {
+        self.position(key).map(|index| self.entries[index].value)
+    }
+
+    fn put(&mut self, key: &str, value: &str) -> Result<(), KvError> {
+        if !valid_key(key) {
+            return Err(KvError::InvalidKey);
+        }
+        if value.len() > MAX_VALUE_BYTES {
+            return Err(KvError::ValueTooLong);
+        }
+        let index = match self.position(key) {
+            Some(index) => index,
+            None if self.count < MAX_ENTRIES => {
+                let index = self.count;
+                let entry = &mut self.entries[index];
+                *entry = Entry::empty();
+                entry.key[..key.len()].copy_from_slice(key.as_bytes());
+                entry.key_len = key.len() as u8;
+                self.count += 1;
+                index
+            }
+            None => return Err(KvError::Full),
+        };
+        let stored = &mut self.entries[index].value;
+        stored.bytes = [0; MAX_VALUE_BYTES];
+        stored.bytes[..value.len()].copy_from_slice(value.as_bytes());
+        stored.len = value.len() as u8;
+        Ok(())
+    }
+
+    fn take(&mut self, key: &str) -> Option<Value> {
+        let index = self.position(key)?;
+        let value = self.entries[index].value;
+        self.entries.copy_within(index + 1..self.count, index);
+        self.count -= 1;
+        Some(value)
+    }
+
+    fn slot_sector(&self, slot: usize) -> u64 {
+        self.first_sector + (slot * SLOT_SECTORS) as u64
+    }
+
+    /// Reads both slots and keeps the valid one with the highest generation.
+    fn load(&mut self) -> usize {
+        let mut bad_slots = 0;
+        let mut best: Option<(usize, u64)> = None;
+        let mut bytes = [0u8; SLOT_BYTES];
+        for slot in 0..SLOTS {
+            match read_slot(self.slot_sector(slot), &mut bytes) {
+                SlotRead::Valid(generation) => {
+                    if best.is_none_or(|(_, best_generation)| generation > best_generation) {
+                        best = Some((slot, generation));
+                    }
+                }
+                SlotRead::Corrupt => bad_slots += 1,
+                SlotRead::Empty => {}
+            }
+        }
+        let Some((slot, generation)) = best else {
+            self.state = KvState::Formatted;
+            return bad_slots;
+        };
+        let _ = read_slot(self.slot_sector(slot), &mut bytes);
+        let count = usize::from(u16::from_le_bytes([bytes[10], bytes[11]])).min(MAX_ENTRIES);
+        self.count = 0;
+        for index in 0..count {
+            let raw = &bytes[HEADER_BYTES + index * ENTRY_BYTES..][..ENTRY_BYTES];
+            let key_len = usize::from(raw[0]).min(MAX_KEY_BYTES);
+            let value_len = usize::from(raw[1]).min(MAX_VALUE_BYTES);
+            let entry = &mut self.entries[self.count];
+            *entry = Entry::empty();
+            entry.key[..key_len].copy_from_slice(&raw[2..2 + key_len]);
+            entry.key_len = key_len as u8;
+            entry.value.bytes[..value_len]
+                .copy_from_slice(&raw[2 + MAX_KEY_BYTES..2 + MAX_KEY_BYTES + value_len]);
+            entry.value.len = value_len as u8;
+            if key_len > 0 {
+                self.count += 1;
+            }
+        }
+        self.active_slot = slot;
+        self.generation = generation;
+        self.state = KvState::Loaded;
+        bad_slots
+    }
+
+    /// Writes the entries to the older slot under the next generation.
+    fn persist(&mut self) -> Result<(), KvError> {
+        if self.state == KvState::Unavailable {
+            return Err(KvError::Unavailable);
+        }
+        let mut bytes = [0u8; SLOT_BYTES];
+        for (index, entry) in self.entries[..self.count].iter().enumerate() {
+            let raw = &mut bytes[HEADER_BYTES + index * ENTRY_BYTES..][..ENTRY_BYTES];
+            let key_len = usize::from(entry.key_len);
+            let value_len = usize::from(entry.value.len);
+            raw[0] = entry.key_len;
+            raw[1] = entry.value.len;
+            raw[2..2 + key_len].copy_from_slice(&entry.key[..key_len]);
+            raw[2 + MAX_KEY_BYTES..2 + MAX_KEY_BYTES + value_len]
+                .copy_from_slice(&entry.value.bytes[..value_len]);
+        }
+        let generation = self.generation.wrapping_add(1);
+        let checksum = crc32(&bytes[HEADER_BYTES..]);
+        bytes[..8].copy_from_slice(MAGIC);
+        bytes[8..10].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[10..12].copy_from_slice(&(self.count as u16).to_le_bytes());
+        bytes[12..16].copy_from_slice(&checksum.to_le_bytes());
+        bytes[16..24].copy_from_slice(&generation.to_le_bytes());
+
+        let slot = (self.active_slot + 1) % SLOTS;
+        let first = self.slot_sector(slot);
+        for (index, sector) in bytes
+            .as_chunks::<{ super::SECTOR_SIZE }>()
+            .0
+            .iter()
+            .enumerate()
+        {
+            super::write_sector(first + index as u64, sector).map_err(|_| KvError::Io)?;
+        }
+        self.active_slot = slot;
+        self.generation = generation;
+        self.writes = self.writes.saturating_add(1);
+        Ok(())
+    }
+}
+
+enum SlotRead {
+    Empty,
+    Corrupt,
+    Valid(u64),
+}
+
+fn read_slot(first_sector: u64, bytes: &mut [u8; SLOT_BYTES]) -> SlotRead {
+    for (index, sector) in bytes
+        .as_chunks_mut::<{ super::SECTOR_SIZE }>()
+        .0
+        .iter_mut()
+        .enumerate()
+    {
+        if super::read_sector(first_sector + index as u64, sector).is_err() {
+            return SlotRead::Corrupt;
+        }
+    }
+    if &bytes[..8] != MAGIC {
+        return SlotRead::Empty;
+    }
+    let version = u16::from_le_bytes([bytes[8], bytes[9]]);
+    let checksum = u32::from_le_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]);
+    if version != VERSION || crc32(&bytes[HEADER_BYTES..]) != checksum {
+        return SlotRead::Corrupt;
+    }
+    let mut generation = [0u8; 8];
+    generation.copy_from_slice(&bytes[16..24]);
+    SlotRead::Valid(u64::from_le_bytes(generation))
+}
+
+/// Printable ASCII without spaces, so `kv set <key> <value>` splits unambiguously.
+fn valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_BYTES && key.bytes().all(|byte| byte.is_ascii_graphic())
+}
+
+/// Loads the store after storage init, clears the previous boot's crash flag and counts this boot.
+pub fn init() -> KvReport {
+    let capacity = super::capacity_sectors();
+    with_kv(|kv| {
+        let mut report = KvReport {
+            state: KvState::Unavailable,
+            first_sector: 0,
+            generation: 0,
+            entries: 0,
+            bad_slots: 0,
+            boot_count: 0,
+            last_crash: None,
+        };
+        if !super::is_ready() || capacity <= RESERVED_SECTORS * 2 {
+            return report;
+        }
+        kv.first_sector = capacity - RESERVED_SECTORS;
+        report.bad_slots = kv.load();
+        report.last_crash = kv.take(CRASH_KEY);
+        let boot_count = kv
+            .get(BOOT_COUNT_KEY)
+            .and_then(|value| value.as_str().parse::<u64>().ok())
+            .unwrap_or(0)
+            .saturating_add(1);
+        let mut text = [0u8; 20];
+        let _ = kv.put(BOOT_COUNT_KEY, format_u64(boot_count, &mut text));
+        if kv.persist().is_err() {
+            kv.state = KvState::Unavailable;
+        }
+        kv.boot_count = boot_count;
+        report.state = kv.state;
+        report.first_sector = kv.first_sector;
+        report.generation = kv.generation;
+        report.entries = kv.count;
+        report.boot_count = boot_count;
+        report
+    })
+}
+
+fn format_u64(mut value: u64, out: &mut [u8; 20]) -> &str {
+    let mut start = out.len();
+    loop {
+        start -= 1;
+        out[start] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    core::str::from_utf8(&out[start..]).unwrap_or("0")
+}
+
+#[cfg(feature = "desktop")]
+pub fn is_ready() -> bool {
+    with_kv(|kv| kv.state != KvState::Unavailable)
+}
+
+pub fn get(key: &str) -> Option<Value> {
+    with_kv(|kv| kv.get(key))
+}
+
+/// Stores `value` under `key` and writes the store to disk before returning.
+pub fn set(key: &str, value: &str) -> Result<(), KvError> {
+    with_kv(|kv| {
+        if kv.state == KvState::Unavailable {
+            return Err(KvError::Unavailable);
+        }
+        if kv.get(key).is_some_and(|stored| stored.as_str() == value) {
+            return Ok(());
+        }
+        kv.put(key, value)?;
+        kv.persist()
+    })
+}
+
+/// Returns whether the key existed.
+pub fn remove(key: &str) -> Result<bool, KvError> {
+    with_kv(|kv| {
+        if kv.state == KvState::Unavailable {
+            return Err(KvError::Unavailable);
+        }
+        if kv.take(key).is_none() {
+            return Ok(false);
+        }
+        kv.persist().map(|()| true)
+    })
+}
+
+/// Best effort from the panic handler: skipped when the panic hit inside storage or the store.
+pub fn record_panic() {
+    if PANIC_RECORDED.swap(true, Ordering::Relaxed) || super::is_busy() {
+        return;
+    }
+    let _ = set(CRASH_KEY, "panic");
+}
+
+/// `kv`: every entry plus the store's location and generation.
+pub fn log_list() {
+    with_kv(|kv| {
+        if kv.state == KvState::Unavailable {
+            serial::write_line("kv: unavailable (no storage)");
+            return;
+        }
+        serial::write_fmt(format_args!(
+            "kv: state={} sectors={}+{} slot={} generation={} entries={}/{} writes={} boot_count={}\n",
+            kv.state.as_str(),
+            kv.first_sector,
+            RESERVED_SECTORS,
+            kv.active_slot,
+            kv.generation,
+            kv.count,
+            MAX_ENTRIES,
+            kv.writes,
+            kv.boot_count
+        ));
+        for entry in &kv.entries[..kv.count] {
+            serial::write_fmt(format_args!(
+                "kv: {}={}\n",
+                entry.key(),
+                entry.value.as_str()
+            ));
+        }
+    });
+}
+
+/// A key that is not set fails, for `get` and `del` alike.
+pub fn get_to_serial(key: &str) -> Status {
+    let key = key.trim();
+    match get(key) {
+        Some(value) => {
+            serial::write_fmt(format_args!("kv: {key}={}\n", value.as_str()));
+            Status::Ok
+        }
+        None => {
+            serial::write_fmt(format_args!("kv: {key} not set\n"));
+            Status::Failed
+        }
+    }
+}
+
+/// `kv set <key> <value>`; the value is the rest of the line.
+pub fn set_to_serial(args: &str) -> Status {
+    let Some((key, value)) = args.trim().split_once(' ') else {
+        return Status::usage("usage: kv set <key> <value>");
+    };
+    let value = value.trim();
+    match set(key, value) {
+        Ok(()) => {
+            serial::write_fmt(format_args!("kv: {key}={value} stored\n"));
+            Status::Ok
+        }
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("kv: set {key} failed ({})\n", err.as_str()),
+            );
+            Status::Failed
+        }
+    }
+}
+
+pub fn remove_to_serial(key: &str) -> Status {
+    let key = key.trim();
+    match remove(key) {
+        Ok(true) => {
+            serial::write_fmt(format_args!("kv: {key} removed\n"));
+            Status::Ok
+        }
+        Ok(false) => {
+            serial::write_fmt(format_args!("kv: {key} not set\n"));
+            Status::Failed
+        }
+        Err(err) => {
+            serial::write_severity_fmt(
+                Severity::Warning,
+                format_args!("kv: del {key} failed ({})\n", err.as_str()),
+            );
+            Status::Failed
+        }
+    }
+}
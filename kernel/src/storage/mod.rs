@@ -1,5 +1,6 @@
 // kernel/src/storage/mod.rs: M6 virtio-blk (legacy PCI) storage backend for QEMU.
 use crate::arch::x86_64::port;
+use crate::evlog::{self, Event};
 use crate::mem;
 use crate::serial;
 use core::cell::UnsafeCell;
@@ -8,6 +9,12 @@ use core::mem::size_of;
 use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
 use core::sync::atomic::{AtomicBool, Ordering, fence};
 
+pub mod kvstore;
+mod readahead;
+
+pub use readahead::ReadAheadStats;
+use readahead::{ReadAhead, WINDOW_BYTES, WINDOW_SECTORS};
+
 pub const SECTOR_SIZE: usize = 512;
 const MAX_QUEUE_SIZE: u16 = 256;
 const MAX_QUEUE_SIZE_USIZE: usize = MAX_QUEUE_SIZE as usize;
@@ -40,6 +47,14 @@ const VIRTQ_DESC_F_WRITE: u16 = 2;
 
 const VIRTIO_BLK_T_IN: u32 = 0;
 const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_DISCARD: u32 = 11;
+const VIRTIO_BLK_T_WRITE_ZEROES: u32 = 13;
+const VIRTIO_BLK_F_DISCARD: u32 = 1 << 13;
+const VIRTIO_BLK_F_WRITE_ZEROES: u32 = 1 << 14;
+const VIRTIO_BLK_WRITE_ZEROES_FLAG_UNMAP: u32 = 1 << 0;
+/// Legacy device-config offsets of `max_discard_sectors` and `max_write_zeroes_sectors`.
+const VIRTIO_BLK_CONFIG_MAX_DISCARD: u16 = 36;
+const VIRTIO_BLK_CONFIG_MAX_WRITE_ZEROES: u16 = 48;
 
 const fn align_up(value: usize, align: usize) -> usize {
     (value + (align - 1)) & !(align - 1)
@@ -96,13 +111,24 @@ struct VirtioBlkReqHeader {
     sector: u64,
 }
 
-#[repr(C, align(16))]
+/// Data payload of DISCARD and WRITE_ZEROES requests (one segment per request).
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct VirtioBlkDiscardSegment {
+    sector: u64,
+    num_sectors: u32,
+    flags: u32,
+}
+
+/// `data` comes first and is one page, so a read-ahead run never crosses a page boundary.
+#[repr(C, align(4096))]
 struct RequestMemory {
+    data: [u8; WINDOW_BYTES],
     header: VirtioBlkReqHeader,
-    data: [u8; SECTOR_SIZE],
     status: u8,
     _pad: [u8; 15],
 }
+const _: () = assert!(WINDOW_BYTES <= 4096);
 
 struct QueueMemoryCell(UnsafeCell<QueueMemory>);
 struct RequestMemoryCell(UnsafeCell<RequestMemory>);
@@ -117,12 +143,12 @@ static QUEUE_MEMORY: QueueMemoryCell = QueueMemoryCell(UnsafeCell::new(QueueMemo
 }));
 
 static REQUEST_MEMORY: RequestMemoryCell = RequestMemoryCell(UnsafeCell::new(RequestMemory {
+    data: [0; WINDOW_BYTES],
     header: VirtioBlkReqHeader {
         req_type: 0,
         reserved: 0,
         sector: 0,
     },
-    data: [0; SECTOR_SIZE],
     status: 0,
     _pad: [0; 15],
 }));
@@ -152,6 +178,50 @@ pub enum StorageError {
     DeviceFailure,
 }
 
+/// How freed sectors are handed back: DISCARD unmaps them on the host, WRITE_ZEROES (with
+/// the unmap flag) lets the host punch holes, and zero-fill only scrubs the old contents.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum DiscardMode {
+    Discard,
+    WriteZeroes,
+    ZeroFill,
+}
+
+impl DiscardMode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Discard => "discard",
+            Self::WriteZeroes => "write-zeroes",
+            Self::ZeroFill => "zero-fill",
+        }
+    }
+}
+
+/// One sector of a `read_sectors` batch.
+pub struct SectorRead<'a> {
+    pub sector: u64,
+    pub out: &'a mut [u8; SECTOR_SIZE],
+}
+
+/// Request ordering counters for `disk stats`.
+#[derive(Clone, Copy)]
+pub struct QueueStats {
+    pub batches: u64,
+    /// Batches that were not already in sweep order.
+    pub reordered: u64,
+    /// Sum of the distances, in sectors, between the end of one request and the next.
+    pub seek_sectors: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct DiscardStats {
+    pub mode: DiscardMode,
+    pub max_sectors: u32,
+    pub requests: u64,
+    pub sectors: u64,
+    pub failures: u64,
+}
+
 impl StorageError {
     pub const fn as_str(self) -> &'static str {
         match self {
@@ -195,6 +265,17 @@ struct StorageState {
     queue_size: u16,
     last_used_idx: u16,
     ready: bool,
+    discard_mode: DiscardMode,
+    max_discard_sectors: u32,
+    discard_requests: u64,
+    discarded_sectors: u64,
+    discard_failures: u64,
+    readahead: ReadAhead,
+    /// Sector just past the last request, where the emulated head rests.
+    head: u64,
+    seek_sectors: u64,
+    batches: u64,
+    reordered_batches: u64,
 }
 
 impl StorageState {
@@ -210,6 +291,16 @@ impl StorageState {
             queue_size: 0,
             last_used_idx: 0,
             ready: false,
+            discard_mode: DiscardMode::ZeroFill,
+            max_discard_sectors: 0,
+            discard_requests: 0,
+            discarded_sectors: 0,
+            discard_failures: 0,
+            readahead: ReadAhead::new(),
+            head: 0,
+            seek_sectors: 0,
+            batches: 0,
+            reordered_batches: 0,
         }
     }
 
@@ -303,6 +394,22 @@ impl StorageState {
         let cap_high = self.virtio_read_u32(VIRTIO_PCI_DEVICE_CONFIG + 4);
         self.capacity_sectors = ((cap_high as u64) << 32) | (cap_low as u64);
 
+        // Every offered feature is accepted above, so offered == negotiated here.
+        (self.discard_mode, self.max_discard_sectors) = if host_features & VIRTIO_BLK_F_DISCARD != 0
+        {
+            (
+                DiscardMode::Discard,
+                self.virtio_read_u32(VIRTIO_PCI_DEVICE_CONFIG + VIRTIO_BLK_CONFIG_MAX_DISCARD),
+            )
+        } else if host_features & VIRTIO_BLK_F_WRITE_ZEROES != 0 {
+            (
+                DiscardMode::WriteZeroes,
+                self.virtio_read_u32(VIRTIO_PCI_DEVICE_CONFIG + VIRTIO_BLK_CONFIG_MAX_WRITE_ZEROES),
+            )
+        } else {
+            (DiscardMode::ZeroFill, 1)
+        };
+
         self.last_used_idx = 0;
         self.ready = true;
         self.virtio_write_status(
@@ -322,7 +429,57 @@ impl StorageState {
         if sector >= self.capacity_sectors {
             return Err(StorageError::OutOfRange);
         }
-        self.submit_io(VIRTIO_BLK_T_IN, sector, Some(out))
+        if self.readahead.read(sector, out) {
+            return Ok(());
+        }
+        let run = if self.readahead.sequential(sector) {
+            (self.capacity_sectors - sector).min(WINDOW_SECTORS as u64) as usize
+        } else {
+            1
+        };
+        if run == 1 {
+            self.submit_io(VIRTIO_BLK_T_IN, sector, Some(out))?;
+            self.readahead.note_direct(sector);
+            return Ok(());
+        }
+
+        evlog::record(Event::DiskIo, sector as u32, VIRTIO_BLK_T_IN);
+        // SAFETY: serialized by `STORAGE_LOCK`; request memory is single-owner here.
+        unsafe {
+            let req = &mut *REQUEST_MEMORY.0.get();
+            req.header.req_type = VIRTIO_BLK_T_IN;
+            req.header.reserved = 0;
+            req.header.sector = sector;
+            req.status = 0xFF;
+        }
+        self.note_seek(sector, run as u64);
+        self.submit_chain(run * SECTOR_SIZE, true)?;
+        // SAFETY: serialized by `STORAGE_LOCK`; request data just filled by device.
+        let data = unsafe { &(*REQUEST_MEMORY.0.get()).data };
+        self.readahead.fill(sector, &data[..run * SECTOR_SIZE], out);
+        Ok(())
+    }
+
+    /// Reads a batch in one sweep of the head: ascending from where it rests, then the sectors
+    /// below it, also ascending. The sorted batch is usually sequential, so read-ahead serves
+    /// most of it.
+    fn read_sectors(&mut self, reads: &mut [SectorRead<'_>]) -> Result<(), StorageError> {
+        let head = self.head;
+        let sweep = |read: &SectorRead<'_>| (read.sector < head, read.sector);
+        self.batches = self.batches.saturating_add(1);
+        if !reads.is_sorted_by_key(sweep) {
+            self.reordered_batches = self.reordered_batches.saturating_add(1);
+            reads.sort_unstable_by_key(sweep);
+        }
+        for read in reads.iter_mut() {
+            self.read_sector(read.sector, read.out)?;
+        }
+        Ok(())
+    }
+
+    fn note_seek(&mut self, sector: u64, count: u64) {
+        self.seek_sectors = self.seek_sectors.saturating_add(sector.abs_diff(self.head));
+        self.head = sector.saturating_add(count);
     }
 
     fn write_sector(&mut self, sector: u64, data: &[u8; SECTOR_SIZE]) -> Result<(), StorageError> {
@@ -334,7 +491,79 @@ impl StorageState {
         }
         let mut scratch = [0u8; SECTOR_SIZE];
         scratch.copy_from_slice(data);
-        self.submit_io(VIRTIO_BLK_T_OUT, sector, Some(&mut scratch))
+        let result = self.submit_io(VIRTIO_BLK_T_OUT, sector, Some(&mut scratch));
+        match result {
+            Ok(()) => self.readahead.write(sector, data),
+            Err(_) => self.readahead.invalidate(sector, 1),
+        }
+        result
+    }
+
+    /// Releases `count` sectors starting at `sector`, split into requests the device accepts.
+    fn discard(&mut self, sector: u64, count: u32) -> Result<(), StorageError> {
+        if !self.ready {
+            return Err(StorageError::NotReady);
+        }
+        let end = sector.saturating_add(u64::from(count));
+        if end > self.capacity_sectors {
+            return Err(StorageError::OutOfRange);
+        }
+        self.readahead.invalidate(sector, u64::from(count));
+        let chunk = self.max_discard_sectors.max(1);
+        let mut next = sector;
+        while next < end {
+            let sectors = (end - next).min(u64::from(chunk)) as u32;
+            let result = match self.discard_mode {
+                DiscardMode::Discard => self.submit_discard(VIRTIO_BLK_T_DISCARD, next, sectors, 0),
+                DiscardMode::WriteZeroes => self.submit_discard(
+                    VIRTIO_BLK_T_WRITE_ZEROES,
+                    next,
+                    sectors,
+                    VIRTIO_BLK_WRITE_ZEROES_FLAG_UNMAP,
+                ),
+                DiscardMode::ZeroFill => {
+                    let mut zeroes = [0u8; SECTOR_SIZE];
+                    self.submit_io(VIRTIO_BLK_T_OUT, next, Some(&mut zeroes))
+                }
+            };
+            self.discard_requests = self.discard_requests.saturating_add(1);
+            if let Err(error) = result {
+                self.discard_failures = self.discard_failures.saturating_add(1);
+                return Err(error);
+            }
+            self.discarded_sectors = self.discarded_sectors.saturating_add(u64::from(sectors));
+            next += u64::from(sectors);
+        }
+        Ok(())
+    }
+
+    fn submit_discard(
+        &mut self,
+        request_type: u32,
+        sector: u64,
+        sectors: u32,
+        flags: u32,
+    ) -> Result<(), StorageError> {
+        let segment = VirtioBlkDiscardSegment {
+            sector,
+            num_sectors: sectors,
+            flags,
+        };
+        // SAFETY: serialized by `STORAGE_LOCK`; request memory is single-owner here and the
+        // data buffer is larger than one segment.
+        unsafe {
+            let req = &mut *REQUEST_MEMORY.0.get();
+            req.header.req_type = request_type;
+            req.header.reserved = 0;
+            req.header.sector = 0;
+            req.status = 0xFF;
+            req.data
+                .as_mut_ptr()
+                .cast::<VirtioBlkDiscardSegment>()
+                .write_unaligned(segment);
+        }
+        self.note_seek(sector, u64::from(sectors));
+        self.submit_chain(size_of::<VirtioBlkDiscardSegment>(), false)
     }
 
     fn submit_io(
@@ -346,6 +575,7 @@ impl StorageState {
         let Some(data_buf) = data else {
             return Err(StorageError::DeviceFailure);
         };
+        evlog::record(Event::DiskIo, sector as u32, request_type);
 
         // SAFETY: serialized by `STORAGE_LOCK`; request memory is single-owner here.
         unsafe {
@@ -355,10 +585,27 @@ impl StorageState {
             req.header.sector = sector;
             req.status = 0xFF;
             if request_type == VIRTIO_BLK_T_OUT {
-                req.data.copy_from_slice(data_buf);
+                req.data[..SECTOR_SIZE].copy_from_slice(data_buf);
             }
         }
+        self.note_seek(sector, 1);
+
+        self.submit_chain(SECTOR_SIZE, request_type == VIRTIO_BLK_T_IN)?;
+
+        if request_type == VIRTIO_BLK_T_IN {
+            // SAFETY: serialized by `STORAGE_LOCK`; request data just filled by device.
+            unsafe {
+                let req = &*REQUEST_MEMORY.0.get();
+                data_buf.copy_from_slice(&req.data[..SECTOR_SIZE]);
+            }
+        }
+
+        Ok(())
+    }
 
+    /// Publishes the header/data/status chain from `REQUEST_MEMORY` and polls for completion;
+    /// `device_writes` marks the first `data_len` bytes of the data buffer as device-writable.
+    fn submit_chain(&mut self, data_len: usize, device_writes: bool) -> Result<(), StorageError> {
         let header_phys = mem::virt_to_phys(request_header_ptr() as usize)
             .ok_or(StorageError::AddressTranslationFailed)?;
         let data_phys = mem::virt_to_phys(request_data_ptr() as usize)
@@ -381,7 +628,7 @@ impl StorageState {
                     next: 1,
                 },
             );
-            let data_flags = if request_type == VIRTIO_BLK_T_IN {
+            let data_flags = if device_writes {
                 VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_WRITE
             } else {
                 VIRTQ_DESC_F_NEXT
@@ -390,7 +637,7 @@ impl StorageState {
                 desc.add(1),
                 VirtqDesc {
                     addr: data_phys,
-                    len: SECTOR_SIZE as u32,
+                    len: data_len as u32,
                     flags: data_flags,
                     next: 2,
                 },
@@ -439,14 +686,6 @@ impl StorageState {
             return Err(StorageError::DeviceFailure);
         }
 
-        if request_type == VIRTIO_BLK_T_IN {
-            // SAFETY: serialized by `STORAGE_LOCK`; request data just filled by device.
-            unsafe {
-                let req = &*REQUEST_MEMORY.0.get();
-                data_buf.copy_from_slice(&req.data);
-            }
-        }
-
         Ok(())
     }
 
@@ -497,14 +736,55 @@ pub fn capacity_sectors() -> u64 {
     with_storage(|state| state.capacity_sectors)
 }
 
+/// Sectors diskfs may use; the tail of the disk belongs to `kvstore`.
+pub fn fs_capacity_sectors() -> u64 {
+    capacity_sectors().saturating_sub(kvstore::RESERVED_SECTORS)
+}
+
+/// True while a storage call is in progress, i.e. when a panic interrupted one.
+pub fn is_busy() -> bool {
+    STORAGE_LOCK.is_locked()
+}
+
 pub fn read_sector(sector: u64, out: &mut [u8; SECTOR_SIZE]) -> Result<(), StorageError> {
     with_storage_mut(|state| state.read_sector(sector, out))
 }
 
+/// Reads every sector of `reads`, in an order that keeps the head moving one way.
+pub fn read_sectors(reads: &mut [SectorRead<'_>]) -> Result<(), StorageError> {
+    with_storage_mut(|state| state.read_sectors(reads))
+}
+
 pub fn write_sector(sector: u64, data: &[u8; SECTOR_SIZE]) -> Result<(), StorageError> {
     with_storage_mut(|state| state.write_sector(sector, data))
 }
 
+pub fn discard(sector: u64, count: u32) -> Result<(), StorageError> {
+    with_storage_mut(|state| state.discard(sector, count))
+}
+
+pub fn discard_stats() -> DiscardStats {
+    with_storage(|state| DiscardStats {
+        mode: state.discard_mode,
+        max_sectors: state.max_discard_sectors,
+        requests: state.discard_requests,
+        sectors: state.discarded_sectors,
+        failures: state.discard_failures,
+    })
+}
+
+pub fn readahead_stats() -> ReadAheadStats {
+    with_storage(|state| state.readahead.stats())
+}
+
+pub fn queue_stats() -> QueueStats {
+    with_storage(|state| QueueStats {
+        batches: state.batches,
+        reordered: state.reordered_batches,
+        seek_sectors: state.seek_sectors,
+    })
+}
+
 pub fn log_info() {
     let report = with_storage(|state| state.report());
     if report.ready {
@@ -524,6 +804,14 @@ pub fn log_info() {
     }
 }
 
+pub fn log_sysinfo() {
+    let report = with_storage(|state| state.report());
+    serial::write_fmt(format_args!(
+        "sysinfo: storage backend={} ready={} sectors={} bytes={}\n",
+        report.backend, report.ready, report.capacity_sectors, report.capacity_bytes
+    ));
+}
+
 fn with_storage<R>(f: impl FnOnce(&StorageState) -> R) -> R {
     let _guard = STORAGE_LOCK.lock();
     // SAFETY: `STORAGE_LOCK` serializes access to global storage state.
@@ -670,6 +958,10 @@ impl SpinLock {
         }
         SpinLockGuard { lock: self }
     }
+
+    fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
 }
 
 struct SpinLockGuard<'a> {
@@ -0,0 +1,147 @@
+// kernel/src/storage/readahead.rs: sequential read-ahead for virtio-blk. A read right after the
+// previous one fetches the next `WINDOW_SECTORS` in a single request; reads that land in that
+// window are copied out of it instead of going to the device. Writes update the window and a
+// discard that touches it drops it, so it never serves stale data.
+use super::SECTOR_SIZE;
+
+/// Sectors fetched by one read-ahead request; also the size of the request data buffer.
+pub(super) const WINDOW_SECTORS: usize = 8;
+pub(super) const WINDOW_BYTES: usize = WINDOW_SECTORS * SECTOR_SIZE;
+const _: () = assert!(WINDOW_SECTORS <= 8);
+
+pub(super) struct ReadAhead {
+    start: u64,
+    len: usize,
+    /// Window sectors already read, one bit each, so the ones fetched for nothing can be
+    /// counted.
+    used: u8,
+    data: [u8; WINDOW_BYTES],
+    /// The sector that would continue the last read; a miss there starts read-ahead.
+    next: Option<u64>,
+    hits: u64,
+    misses: u64,
+    fills: u64,
+    fill_sectors: u64,
+    unused_sectors: u64,
+}
+
+/// Read-ahead counters for `disk stats`.
+#[derive(Clone, Copy)]
+pub struct ReadAheadStats {
+    pub window: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub fills: u64,
+    pub fill_sectors: u64,
+    /// Sectors fetched ahead and dropped before anything read them.
+    pub unused_sectors: u64,
+}
+
+impl ReadAheadStats {
+    /// Share of sector reads served from the window, in percent.
+    pub const fn hit_percent(&self) -> u64 {
+        match (self.hits * 100).checked_div(self.hits + self.misses) {
+            Some(percent) => percent,
+            None => 0,
+        }
+    }
+}
+
+impl ReadAhead {
+    pub(super) const fn new() -> Self {
+        Self {
+            start: 0,
+            len: 0,
+            used: 0,
+            data: [0; WINDOW_BYTES],
+            next: None,
+            hits: 0,
+            misses: 0,
+            fills: 0,
+            fill_sectors: 0,
+            unused_sectors: 0,
+        }
+    }
+
+    fn slot(&self, sector: u64) -> Option<usize> {
+        let offset = sector.checked_sub(self.start)?;
+        (offset < self.len as u64).then_some(offset as usize)
+    }
+
+    /// Copies `sector` out of the window; false on a miss, which the caller must serve.
+    pub(super) fn read(&mut self, sector: u64, out: &mut [u8; SECTOR_SIZE]) -> bool {
+        let Some(slot) = self.slot(sector) else {
+            self.misses = self.misses.saturating_add(1);
+            return false;
+        };
+        let offset = slot * SECTOR_SIZE;
+        out.copy_from_slice(&self.data[offset..offset + SECTOR_SIZE]);
+        self.used |= 1 << slot;
+        self.hits = self.hits.saturating_add(1);
+        self.next = Some(sector + 1);
+        true
+    }
+
+    /// Whether a miss at `sector` continues a sequential run and should fill the window.
+    pub(super) fn sequential(&self, sector: u64) -> bool {
+        self.next == Some(sector)
+    }
+
+    /// Records a miss served by a plain one-sector read.
+    pub(super) fn note_direct(&mut self, sector: u64) {
+        self.next = Some(sector + 1);
+    }
+
+    /// Replaces the window with `sectors` just read from `start` (the first of which is the
+    /// sector the caller asked for) and hands that first sector out.
+    pub(super) fn fill(&mut self, start: u64, sectors: &[u8], out: &mut [u8; SECTOR_SIZE]) {
+        self.drop_window();
+        let len = sectors.len() / SECTOR_SIZE;
+        self.data[..len * SECTOR_SIZE].copy_from_slice(&sectors[..len * SECTOR_SIZE]);
+        self.start = start;
+        self.len = len;
+        self.used = 1;
+        out.copy_from_slice(&sectors[..SECTOR_SIZE]);
+        self.fills = self.fills.saturating_add(1);
+        self.fill_sectors = self.fill_sectors.saturating_add(len as u64);
+        self.next = Some(start + 1);
+    }
+
+    /// Keeps a cached copy of `sector` in step with a write.
+    pub(super) fn write(&mut self, sector: u64, data: &[u8; SECTOR_SIZE]) {
+        if let Some(slot) = self.slot(sector) {
+            let offset = slot * SECTOR_SIZE;
+            self.data[offset..offset + SECTOR_SIZE].copy_from_slice(data);
+        }
+    }
+
+    /// Drops the window when it overlaps `count` sectors from `sector`.
+    pub(super) fn invalidate(&mut self, sector: u64, count: u64) {
+        let end = sector.saturating_add(count);
+        if self.len > 0 && sector < self.start + self.len as u64 && end > self.start {
+            self.drop_window();
+        }
+    }
+
+    fn drop_window(&mut self) {
+        let unread = self.len as u32 - (self.used & window_mask(self.len)).count_ones();
+        self.unused_sectors = self.unused_sectors.saturating_add(u64::from(unread));
+        self.len = 0;
+        self.used = 0;
+    }
+
+    pub(super) const fn stats(&self) -> ReadAheadStats {
+        ReadAheadStats {
+            window: WINDOW_SECTORS,
+            hits: self.hits,
+            misses: self.misses,
+            fills: self.fills,
+            fill_sectors: self.fill_sectors,
+            unused_sectors: self.unused_sectors,
+        }
+    }
+}
+
+const fn window_mask(len: usize) -> u8 {
+    if len >= 8 { u8::MAX } else { (1u8 << len) - 1 }
+}
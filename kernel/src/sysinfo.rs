@@ -0,0 +1,105 @@
+// kernel/src/sysinfo.rs: `sysinfo` one-block system report, also printed once at boot.
+// Every line starts with `sysinfo: <section>` and carries key=value pairs, so a bug report or
+// an xtask check can capture the whole system with a single parse between begin and end.
+#[cfg(feature = "audio")]
+use crate::audio;
+#[cfg(feature = "doom")]
+use crate::doom;
+#[cfg(feature = "desktop")]
+use crate::gfx;
+#[cfg(feature = "net")]
+use crate::net;
+use crate::{features, fs, mem, proc, serial, storage, time};
+
+/// Section lines between `begin` and `end`; `tasks` is followed by one `task` line per task.
+const SECTIONS: usize = 8;
+
+pub fn log_report() {
+    serial::write_fmt(format_args!(
+        "sysinfo: begin version={}.{}.{} uptime_ms={} ticks={} features={}\n",
+        crate::VERSION_MAJOR,
+        crate::VERSION_MINOR,
+        crate::VERSION_BUILD,
+        time::uptime_millis(),
+        time::ticks(),
+        features::EnabledList
+    ));
+    log_mem();
+    #[cfg(feature = "net")]
+    net::log_sysinfo();
+    #[cfg(not(feature = "net"))]
+    log_built_out("net");
+    storage::log_sysinfo();
+    fs::log_sysinfo();
+    #[cfg(feature = "desktop")]
+    gfx::log_sysinfo();
+    #[cfg(not(feature = "desktop"))]
+    log_built_out("gfx");
+    #[cfg(feature = "audio")]
+    log_audio();
+    #[cfg(not(feature = "audio"))]
+    log_built_out("audio");
+    proc::log_sysinfo();
+    #[cfg(feature = "doom")]
+    log_doom();
+    #[cfg(not(feature = "doom"))]
+    log_built_out("doom");
+    serial::write_fmt(format_args!("sysinfo: end sections={SECTIONS}\n"));
+}
+
+fn log_mem() {
+    let stats = mem::boot_stats();
+    let heap = mem::heap_usage();
+    serial::write_fmt(format_args!(
+        "sysinfo: mem regions={} usable_mib={} reserved_mib={} total_mib={} heap_kib={} heap_used={} heap_live_allocs={}\n",
+        stats.region_count,
+        stats.usable_mib(),
+        stats.reserved_mib(),
+        stats.total_mib(),
+        heap.size_bytes / 1024,
+        heap.used_bytes,
+        heap.live_allocations
+    ));
+}
+
+#[cfg(feature = "audio")]
+fn log_audio() {
+    let status = audio::status();
+    serial::write_fmt(format_args!(
+        "sysinfo: audio mode={} backend={} active={} muted={} rate={} channels={}\n",
+        status.mode.as_str(),
+        status.pcm_backend,
+        status.active,
+        status.muted,
+        status.pcm_rate_hz,
+        status.pcm_channels
+    ));
+}
+
+#[cfg(feature = "doom")]
+fn log_doom() {
+    let status = doom::status();
+    serial::write_fmt(format_args!(
+        "sysinfo: doom app={} engine={} wad_present={} running={} play_mode={} paused={} runtime_ticks={} frames={}\n",
+        status.app,
+        status.engine,
+        status.wad_present,
+        status.running,
+        status.play_mode,
+        status.pause_reason.as_str(),
+        status.runtime_ticks,
+        status.frames
+    ));
+}
+
+/// Keeps the section count fixed when a subsystem is compiled out, so one parser reads every
+/// build.
+#[cfg(not(all(
+    feature = "net",
+    feature = "audio",
+    feature = "doom",
+    feature = "desktop"
+)))]
+fn log_built_out(section: &str) {
+    serial::write_fmt(format_args!("sysinfo: {section} built=off\n"));
+}
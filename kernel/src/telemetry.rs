@@ -0,0 +1,308 @@
+// kernel/src/telemetry.rs: periodic metric snapshots streamed as UDP datagrams to a host collector.
+#[cfg(feature = "doom")]
+use crate::audio;
+#[cfg(feature = "doom")]
+use crate::doom;
+use crate::serial::{self, Severity};
+use crate::shell::Status;
+use crate::{net, soft_assert, time};
+use alloc::string::String;
+use core::cell::UnsafeCell;
+use core::fmt::Write;
+
+const TELEMETRY_SRC_PORT: u16 = 40_124;
+const MIN_INTERVAL_MS: u64 = 100;
+const MAX_INTERVAL_MS: u64 = 60_000;
+const DATAGRAM_CAPACITY: usize = 512;
+
+/// Values captured once per datagram so every metric in it describes the same instant.
+struct Sample {
+    ticks: u64,
+    doom: DoomSample,
+    audio: AudioSample,
+    net: net::NetCounters,
+    fps_x10: u64,
+    soft_asserts: u64,
+}
+
+/// The doom metrics; all zero in a kernel built without doom, so the schema never changes.
+#[derive(Default)]
+struct DoomSample {
+    running: bool,
+    paused: bool,
+    frames: u64,
+    dg_audio_dropped_samples: u64,
+}
+
+impl DoomSample {
+    #[cfg(feature = "doom")]
+    fn now() -> Self {
+        let status = doom::status();
+        Self {
+            running: status.running,
+            paused: status.pause_reason != doom::PauseReason::Running,
+            frames: status.frames,
+            dg_audio_dropped_samples: status.dg_audio_dropped_samples,
+        }
+    }
+
+    #[cfg(not(feature = "doom"))]
+    fn now() -> Self {
+        Self::default()
+    }
+}
+
+/// The PCM metrics; all zero in a kernel built without doom, the only PCM client.
+#[derive(Default)]
+struct AudioSample {
+    pcm_samples: u64,
+    pcm_buffered_frames: u32,
+    pcm_packets_dropped: u64,
+    pcm_frames_dropped: u64,
+}
+
+impl AudioSample {
+    #[cfg(feature = "doom")]
+    fn now() -> Self {
+        let status = audio::pcm_status();
+        Self {
+            pcm_samples: status.samples,
+            pcm_buffered_frames: status.buffered_frames,
+            pcm_packets_dropped: status.packets_dropped,
+            pcm_frames_dropped: status.frames_dropped,
+        }
+    }
+
+    #[cfg(not(feature = "doom"))]
+    fn now() -> Self {
+        Self::default()
+    }
+}
+
+type MetricReader = fn(&Sample) -> u64;
+
+/// Wire schema, in datagram order. Counters are cumulative; the collector derives rates.
+const METRICS: [(&str, MetricReader); 14] = [
+    ("ticks", |sample| sample.ticks),
+    ("doom_running", |sample| u64::from(sample.doom.running)),
+    ("doom_paused", |sample| u64::from(sample.doom.paused)),
+    ("frames", |sample| sample.doom.frames),
+    ("fps_x10", |sample| sample.fps_x10),
+    ("pcm_samples", |sample| sample.audio.pcm_samples),
+    ("pcm_buffered", |sample| {
+        u64::from(sample.audio.pcm_buffered_frames)
+    }),
+    ("pcm_pkt_drop", |sample| sample.audio.pcm_packets_dropped),
+    ("pcm_frame_drop", |sample| sample.audio.pcm_frames_dropped),
+    ("dg_audio_drop", |sample| {
+        sample.doom.dg_audio_dropped_samples
+    }),
+    ("net_rx", |sample| sample.net.rx_frames),
+    ("net_tx", |sample| sample.net.tx_frames),
+    ("net_drop", |sample| sample.net.dropped),
+    ("soft_asserts", |sample| sample.soft_asserts),
+];
+
+struct TelemetryState {
+    active: bool,
+    target_ip: [u8; 4],
+    target_port: u16,
+    interval_ticks: u64,
+    next_tick: u64,
+    seq: u64,
+    sent: u64,
+    errors: u64,
+    last_error: Option<net::NetError>,
+    last_frames: u64,
+    last_tick: u64,
+}
+
+impl TelemetryState {
+    const fn new() -> Self {
+        Self {
+            active: false,
+            target_ip: [0; 4],
+            target_port: 0,
+            interval_ticks: 0,
+            next_tick: 0,
+            seq: 0,
+            sent: 0,
+            errors: 0,
+            last_error: None,
+            last_frames: 0,
+            last_tick: 0,
+        }
+    }
+
+    fn sample(&mut self, now_ticks: u64) -> Sample {
+        let doom = DoomSample::now();
+        let elapsed = now_ticks.saturating_sub(self.last_tick);
+        let fps_x10 = doom
+            .frames
+            .saturating_sub(self.last_frames)
+            .saturating_mul(u64::from(time::PIT_HZ) * 10)
+            .checked_div(elapsed)
+            .unwrap_or(0);
+        self.last_frames = doom.frames;
+        self.last_tick = now_ticks;
+        Sample {
+            ticks: now_ticks,
+            doom,
+            audio: AudioSample::now(),
+            net: net::counters(),
+            fps_x10,
+            soft_asserts: soft_assert::count(),
+        }
+    }
+
+    fn send(&mut self, now_ticks: u64) {
+        let sample = self.sample(now_ticks);
+        let mut datagram = String::new();
+        if datagram.try_reserve(DATAGRAM_CAPACITY).is_err() {
+            self.errors = self.errors.saturating_add(1);
+            return;
+        }
+        let _ = write!(datagram, "arrost-telemetry v1 seq={}", self.seq);
+        for (name, read) in METRICS {
+            let _ = write!(datagram, " {name}={}", read(&sample));
+        }
+        datagram.push('\n');
+        self.seq = self.seq.saturating_add(1);
+
+        match net::udp_send(
+            self.target_ip,
+            self.target_port,
+            TELEMETRY_SRC_PORT,
+            datagram.as_bytes(),
+        ) {
+            Ok(_) => self.sent = self.sent.saturating_add(1),
+            Err(err) => {
+                self.errors = self.errors.saturating_add(1);
+                self.last_error = Some(err);
+            }
+        }
+    }
+}
+
+struct TelemetryCell(UnsafeCell<TelemetryState>);
+
+// SAFETY: telemetry is only touched from the single-threaded run loop and shell.
+unsafe impl Sync for TelemetryCell {}
+
+static TELEMETRY: TelemetryCell = TelemetryCell(UnsafeCell::new(TelemetryState::new()));
+
+fn with_state_mut<R>(f: impl FnOnce(&mut TelemetryState) -> R) -> R {
+    // SAFETY: callers run on the kernel main loop; no interrupt handler reaches this state.
+    unsafe { f(&mut *TELEMETRY.0.get()) }
+}
+
+pub fn poll(now_ticks: u64) {
+    with_state_mut(|state| {
+        if !state.active || now_ticks < state.next_tick {
+            return;
+        }
+        state.send(now_ticks);
+        state.next_tick = now_ticks.saturating_add(state.interval_ticks);
+    });
+}
+
+/// Tick at which the next datagram is due while streaming.
+pub fn next_deadline(now_ticks: u64) -> Option<u64> {
+    with_state_mut(|state| state.active.then(|| state.next_tick.max(now_ticks)))
+}
+
+/// Starts streaming to `ip:port` every `interval_ms`; the first datagram goes out on the
+/// next poll.
+pub fn start(ip: [u8; 4], port: u16, interval_ms: u64) -> bool {
+    if port == 0 || !(MIN_INTERVAL_MS..=MAX_INTERVAL_MS).contains(&interval_ms) {
+        return false;
+    }
+    let now = time::ticks();
+    let interval_ticks = (interval_ms * u64::from(time::PIT_HZ) / 1000).max(1);
+    with_state_mut(|state| {
+        *state = TelemetryState {
+            active: true,
+            target_ip: ip,
+            target_port: port,
+            interval_ticks,
+            next_tick: now,
+            last_frames: DoomSample::now().frames,
+            last_tick: now,
+            ..TelemetryState::new()
+        };
+    });
+    true
+}
+
+pub fn stop() -> bool {
+    with_state_mut(|state| core::mem::replace(&mut state.active, false))
+}
+
+pub fn start_to_serial(args: &str) -> Status {
+    let mut parts = args.split_whitespace();
+    let target = parts.next().and_then(net::parse_ipv4);
+    let port = parts.next().and_then(|text| text.parse::<u16>().ok());
+    let interval = parts.next().and_then(|text| text.parse::<u64>().ok());
+    let (Some(ip), Some(port), Some(interval_ms), None) = (target, port, interval, parts.next())
+    else {
+        return Status::usage("usage: telemetry start <a.b.c.d> <port> <interval_ms 100..60000>");
+    };
+    if !net::counters().ready {
+        serial::write_severity_line(Severity::Error, "telemetry: net unavailable");
+        return Status::Failed;
+    }
+    if !start(ip, port, interval_ms) {
+        return Status::usage("usage: telemetry start <a.b.c.d> <port> <interval_ms 100..60000>");
+    }
+    serial::write_fmt(format_args!(
+        "telemetry: streaming {} metrics to {}.{}.{}.{}:{} every {} ms\n",
+        METRICS.len(),
+        ip[0],
+        ip[1],
+        ip[2],
+        ip[3],
+        port,
+        interval_ms
+    ));
+    Status::Ok
+}
+
+pub fn stop_to_serial() -> Status {
+    let was_active = stop();
+    with_state_mut(|state| {
+        serial::write_fmt(format_args!(
+            "telemetry: {} (sent={} errors={})\n",
+            if was_active { "stopped" } else { "not running" },
+            state.sent,
+            state.errors
+        ));
+    });
+    Status::check(was_active)
+}
+
+pub fn log_status() {
+    with_state_mut(|state| {
+        serial::write_fmt(format_args!(
+            "telemetry: active={} target={}.{}.{}.{}:{} interval_ticks={} seq={} sent={} errors={} last_error={}\n",
+            state.active,
+            state.target_ip[0],
+            state.target_ip[1],
+            state.target_ip[2],
+            state.target_ip[3],
+            state.target_port,
+            state.interval_ticks,
+            state.seq,
+            state.sent,
+            state.errors,
+            state.last_error.map_or("none", net::NetError::as_str)
+        ));
+    });
+    let mut names = String::new();
+    for (index, (name, _)) in METRICS.iter().enumerate() {
+        if index > 0 {
+            names.push(',');
+        }
+        names.push_str(name);
+    }
+    serial::write_fmt(format_args!("telemetry: metrics={names}\n"));
+}
@@ -1,14 +1,99 @@
-// kernel/src/time.rs: timer tick accounting for IRQ0.
+// kernel/src/time.rs: timer tick accounting for IRQ0, tickless idle and the virtual tick source
+// used for deterministic runs.
+use crate::arch::x86_64::{pic, pit};
+use crate::serial;
 use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use x86_64::instructions::interrupts;
 
 pub const PIT_HZ: u32 = 100;
+/// Longest one-shot the 16-bit PIT counter can hold at PIT_HZ (5 ticks = 50 ms).
+const MAX_ONESHOT_TICKS: u64 = u16::MAX as u64 / (pit::divisor_for(PIT_HZ) as u64);
 
 static TIMER_TICKS: AtomicU64 = AtomicU64::new(0);
 static LAST_REPORTED_SECOND: AtomicU64 = AtomicU64::new(0);
 static HEARTBEAT_ENABLED: AtomicBool = AtomicBool::new(false);
+static TICKLESS_ENABLED: AtomicBool = AtomicBool::new(false);
+/// Ticks the armed one-shot stands for; IRQ0 credits these instead of one.
+static ONESHOT_TICKS: AtomicU64 = AtomicU64::new(0);
+/// PIT input clocks elapsed outside whole ticks (partial periods and early wakes).
+static SUBTICK_COUNTS: AtomicU64 = AtomicU64::new(0);
+static IDLE_SLEEPS: AtomicU64 = AtomicU64::new(0);
+static IDLE_SLEPT_TICKS: AtomicU64 = AtomicU64::new(0);
+static IDLE_EARLY_WAKES: AtomicU64 = AtomicU64::new(0);
+static IDLE_PERIODIC_HALTS: AtomicU64 = AtomicU64::new(0);
+static TIMER_IRQS: AtomicU64 = AtomicU64::new(0);
+/// IRQ0 no longer moves `ticks`; only `advance` does.
+static VIRTUAL_TICKS: AtomicBool = AtomicBool::new(false);
+static VIRTUAL_ADVANCED: AtomicU64 = AtomicU64::new(0);
+/// Largest single `tick advance` (one hour).
+pub const MAX_ADVANCE_TICKS: u64 = 3_600 * PIT_HZ as u64;
+
+/// Where `ticks` comes from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TickSource {
+    Pit,
+    /// Frozen between `tick advance` calls, so timeouts and pacing replay the same way.
+    Virtual,
+}
+
+impl TickSource {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pit => "pit",
+            Self::Virtual => "virtual",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "pit" => Some(Self::Pit),
+            "virtual" => Some(Self::Virtual),
+            _ => None,
+        }
+    }
+}
 
 pub fn on_timer_tick() -> u64 {
-    TIMER_TICKS.fetch_add(1, Ordering::Relaxed) + 1
+    TIMER_IRQS.fetch_add(1, Ordering::Relaxed);
+    let credited = ONESHOT_TICKS.swap(0, Ordering::AcqRel).max(1);
+    if VIRTUAL_TICKS.load(Ordering::Relaxed) {
+        return ticks();
+    }
+    TIMER_TICKS.fetch_add(credited, Ordering::Relaxed) + credited
+}
+
+pub fn tick_source() -> TickSource {
+    if VIRTUAL_TICKS.load(Ordering::Relaxed) {
+        TickSource::Virtual
+    } else {
+        TickSource::Pit
+    }
+}
+
+/// Switches the tick source; `ticks` carries on from its current value either way.
+pub fn set_tick_source(source: TickSource) -> bool {
+    let virtual_ticks = source == TickSource::Virtual;
+    VIRTUAL_TICKS.swap(virtual_ticks, Ordering::Relaxed) != virtual_ticks
+}
+
+/// `tick advance <n>`: moves virtual time forward; `None` while the PIT drives it.
+pub fn advance(ticks: u64) -> Option<u64> {
+    if tick_source() != TickSource::Virtual {
+        return None;
+    }
+    let ticks = ticks.min(MAX_ADVANCE_TICKS);
+    VIRTUAL_ADVANCED.fetch_add(ticks, Ordering::Relaxed);
+    Some(TIMER_TICKS.fetch_add(ticks, Ordering::Relaxed) + ticks)
+}
+
+pub fn log_tick_source() {
+    serial::write_fmt(format_args!(
+        "tick: source={} ticks={} advanced={} timer_irqs={}\n",
+        tick_source().as_str(),
+        ticks(),
+        VIRTUAL_ADVANCED.load(Ordering::Relaxed),
+        TIMER_IRQS.load(Ordering::Relaxed)
+    ));
 }
 
 pub fn ticks() -> u64 {
@@ -27,6 +112,15 @@ pub fn heartbeat_enabled() -> bool {
     HEARTBEAT_ENABLED.load(Ordering::Relaxed)
 }
 
+/// Tick at which the heartbeat line for the next second is due, if heartbeats are on.
+pub fn heartbeat_deadline() -> Option<u64> {
+    if !heartbeat_enabled() {
+        return None;
+    }
+    let hz = PIT_HZ as u64;
+    Some((ticks() / hz + 1) * hz)
+}
+
 pub fn poll_elapsed_second() -> Option<u64> {
     let elapsed_seconds = ticks() / PIT_HZ as u64;
     let last = LAST_REPORTED_SECOND.load(Ordering::Relaxed);
@@ -43,3 +137,100 @@ pub fn poll_elapsed_second() -> Option<u64> {
         None
     }
 }
+
+pub fn set_tickless(enabled: bool) {
+    TICKLESS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn tickless_enabled() -> bool {
+    TICKLESS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Halts until the next interrupt. With tickless idle on and `deadline` at least two ticks
+/// away, the periodic PIT is swapped for a one-shot covering the gap so idle stretches take
+/// one IRQ0 instead of one per tick; `None` means no subsystem has a pending deadline.
+pub fn idle_until(deadline: Option<u64>) {
+    let now = ticks();
+    let sleep_ticks = deadline
+        .map_or(MAX_ONESHOT_TICKS, |deadline| deadline.saturating_sub(now))
+        .min(MAX_ONESHOT_TICKS);
+    // Virtual time never reaches the deadline on its own, so keep the periodic wakeups.
+    if !tickless_enabled() || sleep_ticks < 2 || tick_source() == TickSource::Virtual {
+        IDLE_PERIODIC_HALTS.fetch_add(1, Ordering::Relaxed);
+        x86_64::instructions::hlt();
+        return;
+    }
+
+    interrupts::disable();
+    // A latched periodic tick would be credited as the whole one-shot; take it normally.
+    if pic::irq_pending(0) {
+        interrupts::enable();
+        IDLE_PERIODIC_HALTS.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+
+    let divisor = u64::from(pit::divisor_for(PIT_HZ));
+    let remaining = u64::from(pit::read_counter()).min(divisor);
+    credit_counts(divisor - remaining);
+
+    let programmed = sleep_ticks * divisor;
+    ONESHOT_TICKS.store(sleep_ticks, Ordering::Release);
+    pit::arm_oneshot(programmed as u16);
+    IDLE_SLEEPS.fetch_add(1, Ordering::Relaxed);
+    interrupts::enable_and_hlt();
+
+    interrupts::disable();
+    if ONESHOT_TICKS.swap(0, Ordering::AcqRel) != 0 {
+        // Woken by another IRQ: credit only the clocks that actually elapsed. After terminal
+        // count mode 0 wraps to 0xFFFF, so anything above `programmed` means fully elapsed.
+        let left = u64::from(pit::read_counter());
+        let elapsed = if left > programmed {
+            programmed
+        } else {
+            programmed - left
+        };
+        credit_counts(elapsed);
+        IDLE_EARLY_WAKES.fetch_add(1, Ordering::Relaxed);
+        IDLE_SLEPT_TICKS.fetch_add(elapsed / divisor, Ordering::Relaxed);
+    } else {
+        IDLE_SLEPT_TICKS.fetch_add(sleep_ticks, Ordering::Relaxed);
+    }
+    let _ = pit::init(PIT_HZ);
+    interrupts::enable();
+}
+
+fn credit_counts(counts: u64) {
+    let divisor = u64::from(pit::divisor_for(PIT_HZ));
+    let total = SUBTICK_COUNTS
+        .load(Ordering::Relaxed)
+        .saturating_add(counts);
+    SUBTICK_COUNTS.store(total % divisor, Ordering::Relaxed);
+    if total >= divisor {
+        TIMER_TICKS.fetch_add(total / divisor, Ordering::Relaxed);
+    }
+}
+
+pub fn reset_idle_stats() {
+    IDLE_SLEEPS.store(0, Ordering::Relaxed);
+    IDLE_SLEPT_TICKS.store(0, Ordering::Relaxed);
+    IDLE_EARLY_WAKES.store(0, Ordering::Relaxed);
+    IDLE_PERIODIC_HALTS.store(0, Ordering::Relaxed);
+    TIMER_IRQS.store(0, Ordering::Relaxed);
+}
+
+pub fn log_idle_stats() {
+    let sleeps = IDLE_SLEEPS.load(Ordering::Relaxed);
+    let slept = IDLE_SLEPT_TICKS.load(Ordering::Relaxed);
+    serial::write_fmt(format_args!(
+        "tickless: enabled={} max_oneshot_ticks={} timer_irqs={} sleeps={} slept_ticks={} avg_sleep_ticks={} early_wakes={} periodic_halts={} ticks={}\n",
+        tickless_enabled(),
+        MAX_ONESHOT_TICKS,
+        TIMER_IRQS.load(Ordering::Relaxed),
+        sleeps,
+        slept,
+        slept.checked_div(sleeps).unwrap_or(0),
+        IDLE_EARLY_WAKES.load(Ordering::Relaxed),
+        IDLE_PERIODIC_HALTS.load(Ordering::Relaxed),
+        ticks()
+    ));
+}
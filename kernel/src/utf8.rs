@@ -0,0 +1,60 @@
+// kernel/src/utf8.rs: incremental UTF-8 decoding for byte streams that can split a character
+// across reads (serial input, the console log). A broken sequence comes out as one U+FFFD, so a
+// consumer sees one character per cell instead of one per byte.
+
+pub const REPLACEMENT: char = char::REPLACEMENT_CHARACTER;
+
+#[derive(Clone, Copy)]
+pub struct Utf8Decoder {
+    /// Code point bits collected so far.
+    value: u32,
+    /// Continuation bytes still expected.
+    remaining: u8,
+    /// Smallest code point the lead byte may encode; anything below is an overlong form.
+    min: u32,
+}
+
+impl Utf8Decoder {
+    pub const fn new() -> Self {
+        Self {
+            value: 0,
+            remaining: 0,
+            min: 0,
+        }
+    }
+
+    /// Feeds one byte and hands every finished character to `emit`. A byte that cannot
+    /// continue the pending sequence ends it with U+FFFD and is then decoded on its own.
+    pub fn push(&mut self, byte: u8, mut emit: impl FnMut(char)) {
+        if self.remaining > 0 {
+            if byte & 0xc0 == 0x80 {
+                self.value = (self.value << 6) | u32::from(byte & 0x3f);
+                self.remaining -= 1;
+                if self.remaining == 0 {
+                    let ch = char::from_u32(self.value)
+                        .filter(|_| self.value >= self.min)
+                        .unwrap_or(REPLACEMENT);
+                    emit(ch);
+                }
+                return;
+            }
+            self.remaining = 0;
+            emit(REPLACEMENT);
+        }
+        match byte {
+            0x00..=0x7f => emit(char::from(byte)),
+            0xc2..=0xdf => self.start(byte & 0x1f, 1, 0x80),
+            0xe0..=0xef => self.start(byte & 0x0f, 2, 0x800),
+            0xf0..=0xf4 => self.start(byte & 0x07, 3, 0x1_0000),
+            // Continuation bytes without a lead, and leads that only encode overlong or
+            // out-of-range code points.
+            _ => emit(REPLACEMENT),
+        }
+    }
+
+    const fn start(&mut self, bits: u8, remaining: u8, min: u32) {
+        self.value = bits as u32;
+        self.remaining = remaining;
+        self.min = min;
+    }
+}
@@ -59,7 +59,30 @@ if [[ ! -f "$DATA_IMG" ]]; then
   exit 1
 fi
 
-if lsof "$IMG" >/dev/null 2>&1; then
+# QEMU_QCOW2_DIR runs from qcow2 copies of the boot, data and vars drives kept in
+# that directory (made on first use). Unlike the raw images they can hold
+# `savevm` snapshots; QEMU_LOADVM restores one at startup and QEMU_MONITOR puts
+# the human monitor on a unix socket (see `cargo xtask smoke-doom --snapshot`).
+QEMU_DRIVE_FORMAT="raw"
+if [[ -n "${QEMU_QCOW2_DIR:-}" ]]; then
+  mkdir -p "$QEMU_QCOW2_DIR"
+  for drive in "boot:$IMG" "data:$DATA_IMG" "vars:$OVMF_VARS_PATH"; do
+    copy="$QEMU_QCOW2_DIR/${drive%%:*}.qcow2"
+    if [[ ! -f "$copy" ]]; then
+      qemu-img convert -f raw -O qcow2 "${drive#*:}" "$copy"
+    fi
+  done
+  IMG="$QEMU_QCOW2_DIR/boot.qcow2"
+  DATA_IMG="$QEMU_QCOW2_DIR/data.qcow2"
+  OVMF_VARS_PATH="$QEMU_QCOW2_DIR/vars.qcow2"
+  QEMU_DRIVE_FORMAT="qcow2"
+fi
+
+# QEMU_SNAPSHOT=1 keeps every drive write in a temporary overlay, so several
+# instances can boot the same images at once (see `cargo xtask smoke-net-duo`).
+QEMU_SNAPSHOT_MODE="${QEMU_SNAPSHOT:-0}"
+
+if [[ "$QEMU_SNAPSHOT_MODE" != "1" ]] && lsof "$IMG" >/dev/null 2>&1; then
   echo "Image is already in use: $IMG"
   lsof "$IMG" || true
   echo "Close the running QEMU instance and retry."
@@ -348,7 +371,18 @@ UDP_FWD_PORT="${ARR_UDP_FWD_PORT:-}"
 UDP_FWD_GUEST_PORT="${ARR_UDP_FWD_GUEST_PORT:-7777}"
 TCP_FWD_PORT="${ARR_TCP_FWD_PORT:-}"
 TCP_FWD_GUEST_PORT="${ARR_TCP_FWD_GUEST_PORT:-80}"
-NETDEV_SPEC="user,id=arr_net"
+# QEMU_NETDEV replaces the slirp backend, e.g. `socket,mcast=230.0.0.1:12341`
+# to put several guests on one virtual LAN (host forwarding is slirp-only).
+# QEMU_MAC overrides the NIC address so those guests stay distinguishable.
+QEMU_NETDEV_BACKEND="${QEMU_NETDEV:-}"
+QEMU_NIC_MAC="${QEMU_MAC:-}"
+if [[ -n "$QEMU_NETDEV_BACKEND" ]]; then
+  NETDEV_SPEC="${QEMU_NETDEV_BACKEND},id=arr_net"
+  UDP_FWD_PORT=""
+  TCP_FWD_PORT=""
+else
+  NETDEV_SPEC="user,id=arr_net"
+fi
 
 if [[ -n "$UDP_FWD_PORT" ]]; then
   NETDEV_SPEC+=",hostfwd=udp::${UDP_FWD_PORT}-:${UDP_FWD_GUEST_PORT}"
@@ -358,6 +392,11 @@ if [[ -n "$TCP_FWD_PORT" ]]; then
   NETDEV_SPEC+=",hostfwd=tcp::${TCP_FWD_PORT}-:${TCP_FWD_GUEST_PORT}"
 fi
 
+NIC_SPEC="virtio-net-pci,netdev=arr_net,disable-modern=on,disable-legacy=off"
+if [[ -n "$QEMU_NIC_MAC" ]]; then
+  NIC_SPEC+=",mac=${QEMU_NIC_MAC}"
+fi
+
 NETDEV_ARGS=(-netdev "$NETDEV_SPEC")
 
 echo "Using QEMU display backend: $DISPLAY_BACKEND"
@@ -396,6 +435,19 @@ fi
 if [[ -n "$TCP_FWD_PORT" ]]; then
   echo "Forwarding TCP host:${TCP_FWD_PORT} -> guest:${TCP_FWD_GUEST_PORT}"
 fi
+echo "Using QEMU netdev: ${NETDEV_SPEC}"
+if [[ -n "$QEMU_NIC_MAC" ]]; then
+  echo "Using QEMU NIC mac: $QEMU_NIC_MAC"
+fi
+if [[ "$QEMU_SNAPSHOT_MODE" == "1" ]]; then
+  echo "Using QEMU snapshot drives: on"
+fi
+if [[ "$QEMU_DRIVE_FORMAT" == "qcow2" ]]; then
+  echo "Using QEMU qcow2 drives: $QEMU_QCOW2_DIR"
+fi
+if [[ -n "${QEMU_LOADVM:-}" ]]; then
+  echo "Restoring QEMU snapshot: $QEMU_LOADVM"
+fi
 
 QEMU_BASE_ARGS=(
   -machine "$MACHINE_SPEC"
@@ -408,16 +460,25 @@ QEMU_BASE_ARGS+=(
   -m 512M
   -serial stdio
   -drive if=pflash,format=raw,readonly=on,file="$OVMF_CODE_PATH"
-  -drive if=pflash,format=raw,file="$OVMF_VARS_PATH"
-  -drive format=raw,file="$IMG"
-  -drive if=none,id=arr_data,format=raw,file="$DATA_IMG"
+  -drive if=pflash,format="$QEMU_DRIVE_FORMAT",file="$OVMF_VARS_PATH"
+  -drive format="$QEMU_DRIVE_FORMAT",file="$IMG"
+  -drive if=none,id=arr_data,format="$QEMU_DRIVE_FORMAT",file="$DATA_IMG",discard=unmap,detect-zeroes=unmap
   -device virtio-blk-pci,drive=arr_data,disable-modern=on,disable-legacy=off
   "${NETDEV_ARGS[@]}"
-  -device virtio-net-pci,netdev=arr_net,disable-modern=on,disable-legacy=off
+  -device "$NIC_SPEC"
 )
 if [[ -n "$CPU_SPEC" ]]; then
   QEMU_BASE_ARGS+=(-cpu "$CPU_SPEC")
 fi
+if [[ "$QEMU_SNAPSHOT_MODE" == "1" ]]; then
+  QEMU_BASE_ARGS+=(-snapshot)
+fi
+if [[ -n "${QEMU_MONITOR:-}" ]]; then
+  QEMU_BASE_ARGS+=(-monitor "unix:${QEMU_MONITOR},server,nowait")
+fi
+if [[ -n "${QEMU_LOADVM:-}" ]]; then
+  QEMU_BASE_ARGS+=(-loadvm "$QEMU_LOADVM")
+fi
 
 if [[ "$AUDIO_BACKEND" != "none" ]]; then
   exec qemu-system-x86_64 \
@@ -1,6 +1,8 @@
 /* user/doom/c/doomgeneric_arrost.c: ArrOSt DoomGeneric platform glue (M10.6). */
 #include <stdint.h>
 
+#include "doomstat.h"
+
 #ifndef DOOMGENERIC_RESX
 #define DOOMGENERIC_RESX 320
 #endif
@@ -14,6 +16,7 @@ extern pixel_t *DG_ScreenBuffer;
 /* Rust callbacks implemented in kernel/src/doom_bridge.rs */
 extern void arr_dg_init(void);
 extern void arr_dg_draw_frame(const uint32_t *frame, uint32_t width, uint32_t height);
+extern uint32_t *arr_dg_back_surface(void);
 extern uint32_t arr_dg_get_ticks_ms(void);
 extern int arr_dg_pop_key(uint8_t *pressed, uint8_t *key);
 extern void arr_dg_sleep_ms(uint32_t ms);
@@ -24,19 +27,29 @@ const char *arr_doomgeneric_port_name(void) {
 }
 
 uint32_t arr_doomgeneric_port_abi_revision(void) {
-    return 2u;
+    return 3u;
 }
 
 uint32_t arr_doomgeneric_port_caps(void) {
     return 0x0Fu; /* video|input|timer|audio */
 }
 
+/* At the kernel viewport size the engine renders straight into the kernel's back surface and
+ * each DrawFrame is a flip; other sizes keep doomgeneric's buffer and get copied and scaled. */
+#define ARR_DG_ZERO_COPY (DOOMGENERIC_RESX == 320 && DOOMGENERIC_RESY == 200)
+
 void DG_Init(void) {
     arr_dg_init();
+#if ARR_DG_ZERO_COPY
+    DG_ScreenBuffer = (pixel_t *)arr_dg_back_surface();
+#endif
 }
 
 void DG_DrawFrame(void) {
     arr_dg_draw_frame((const uint32_t *)DG_ScreenBuffer, DOOMGENERIC_RESX, DOOMGENERIC_RESY);
+#if ARR_DG_ZERO_COPY
+    DG_ScreenBuffer = (pixel_t *)arr_dg_back_surface();
+#endif
 }
 
 void DG_SleepMs(uint32_t ms) {
@@ -72,3 +85,12 @@ int DG_GetKey(int *pressed, unsigned char *key) {
 void DG_SetWindowTitle(const char *title) {
     arr_dg_set_title(title);
 }
+
+/* bit 0: automap drawn; bit 1: a level is running with no menu open, so Tab toggles the map. */
+uint32_t arr_doomgeneric_automap_state(void) {
+    uint32_t state = automapactive ? 1u : 0u;
+    if (gamestate == GS_LEVEL && !menuactive) {
+        state |= 2u;
+    }
+    return state;
+}
@@ -12,3 +12,7 @@ void doomgeneric_Tick(void) {}
 uint32_t arr_doomgeneric_frame_counter(void) {
     return 0u;
 }
+
+uint32_t arr_doomgeneric_automap_state(void) {
+    return 0u;
+}
@@ -3,7 +3,10 @@
 // user/init/src/lib.rs: M3 userland init stub (no_std) built together with the workspace.
 use arrostd::abi::{USERLAND_ABI_REVISION, USERLAND_INIT_APP};
 use arrostd::syscall::{
-    SYS_EXIT, SYS_READ, SYS_RECVFROM, SYS_SENDTO, SYS_SLEEP, SYS_SOCKET, SYS_WRITE, SYS_YIELD,
+    SYS_ACCEPT, SYS_BATCH, SYS_BIND, SYS_BRK, SYS_CLOSE, SYS_CONNECT, SYS_EXIT, SYS_INPUT_READ,
+    SYS_INPUT_SUBSCRIBE, SYS_KILL, SYS_LISTEN, SYS_POLL, SYS_READ, SYS_RECVFROM, SYS_SENDTO,
+    SYS_SETSOCKOPT, SYS_SIGPENDING, SYS_SLEEP, SYS_SOCKET, SYS_SURFACE, SYS_TIMER_SET, SYS_TLS_GET,
+    SYS_TLS_SET, SYS_WRITE, SYS_YIELD,
 };
 
 pub const fn app_name() -> &'static str {
@@ -28,7 +31,7 @@ pub fn handle_command(command: &str) -> &'static str {
     }
 }
 
-pub const fn supported_syscalls() -> [u64; 8] {
+pub const fn supported_syscalls() -> [u64; 25] {
     [
         SYS_WRITE,
         SYS_READ,
@@ -38,6 +41,23 @@ pub const fn supported_syscalls() -> [u64; 8] {
         SYS_SOCKET,
         SYS_SENDTO,
         SYS_RECVFROM,
+        SYS_SETSOCKOPT,
+        SYS_BRK,
+        SYS_BATCH,
+        SYS_KILL,
+        SYS_SIGPENDING,
+        SYS_TLS_GET,
+        SYS_TLS_SET,
+        SYS_INPUT_SUBSCRIBE,
+        SYS_INPUT_READ,
+        SYS_SURFACE,
+        SYS_POLL,
+        SYS_TIMER_SET,
+        SYS_CONNECT,
+        SYS_BIND,
+        SYS_LISTEN,
+        SYS_ACCEPT,
+        SYS_CLOSE,
     ]
 }
 
@@ -75,7 +95,91 @@ mod tests {
                 SYS_SOCKET,
                 SYS_SENDTO,
                 SYS_RECVFROM,
+                SYS_SETSOCKOPT,
+                SYS_BRK,
+                SYS_BATCH,
+                SYS_KILL,
+                SYS_SIGPENDING,
+                SYS_TLS_GET,
+                SYS_TLS_SET,
+                SYS_INPUT_SUBSCRIBE,
+                SYS_INPUT_READ,
+                SYS_SURFACE,
+                SYS_POLL,
+                SYS_TIMER_SET,
+                SYS_CONNECT,
+                SYS_BIND,
+                SYS_LISTEN,
+                SYS_ACCEPT,
+                SYS_CLOSE,
             ]
         );
     }
+
+    #[test]
+    fn batch_collects_records_and_reports_results() {
+        use arrostd::syscall::{BATCH_MAX_RECORDS, BATCH_STOP_ON_ERROR, Batch, SyscallRecord};
+
+        let mut batch = Batch::new();
+        assert!(batch.is_empty());
+        assert_eq!(batch.push(SYS_YIELD, 0, 0, 0), Some(0));
+        assert_eq!(batch.push(SYS_BRK, 0, 0, 0), Some(1));
+        for _ in 2..BATCH_MAX_RECORDS {
+            assert!(batch.push(SYS_YIELD, 0, 0, 0).is_some());
+        }
+        assert_eq!(batch.push(SYS_YIELD, 0, 0, 0), None);
+
+        let rc = batch.submit(BATCH_STOP_ON_ERROR, |number, ptr, len, flags| {
+            assert_eq!(number, SYS_BATCH);
+            assert_eq!(len, BATCH_MAX_RECORDS as u64);
+            assert_eq!(flags, BATCH_STOP_ON_ERROR);
+            // Stand-in kernel: answer the first two records only.
+            let records = ptr as *mut SyscallRecord;
+            // SAFETY: `ptr` points at the batch's record array for the duration of the call.
+            unsafe {
+                (*records).result = 0;
+                (*records.add(1)).result = 0x10_0000;
+            }
+            2
+        });
+        assert_eq!(rc, 2);
+        assert_eq!(batch.result(0), Some(0));
+        assert_eq!(batch.result(1), Some(0x10_0000));
+        assert_eq!(batch.result(BATCH_MAX_RECORDS), None);
+
+        batch.clear();
+        assert_eq!(batch.len(), 0);
+        assert_eq!(batch.result(0), None);
+    }
+
+    #[test]
+    fn brk_heap_grows_break_in_steps() {
+        use arrostd::heap::{BRK_STEP, BrkHeap};
+
+        let base = 0x10_0000u64;
+        let limit = base + 2 * BRK_STEP;
+        let mut brk = base;
+        let mut calls = 0;
+        let mut fake_brk = |addr: u64| {
+            calls += 1;
+            if addr == 0 {
+                return brk as isize;
+            }
+            if addr > limit {
+                return -12;
+            }
+            brk = addr;
+            addr as isize
+        };
+
+        let mut heap = BrkHeap::new();
+        assert_eq!(heap.alloc(10, 1, &mut fake_brk), Some(base));
+        assert_eq!(heap.alloc(8, 16, &mut fake_brk), Some(base + 16));
+        assert_eq!(heap.brk(), base + BRK_STEP);
+        assert_eq!(heap.used(), 24);
+        assert_eq!(heap.alloc(BRK_STEP, 1, &mut fake_brk), Some(base + 24));
+        assert_eq!(heap.brk(), limit);
+        assert_eq!(heap.alloc(BRK_STEP, 1, &mut fake_brk), None);
+        assert_eq!(calls, 4);
+    }
 }
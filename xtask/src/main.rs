@@ -1,8 +1,9 @@
 use anyhow::{Context, Result, bail};
 use bootloader::DiskImageBuilder;
 use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::process::{ChildStdin, Command, Stdio};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -25,6 +26,98 @@ const DOOM_GENERIC_INCLUDE_DIR: &str = "user/doom/third_party/doomgeneric/doomge
 const DOOM_GENERIC_PORT_SOURCE: &str = "user/doom/c/doomgeneric_arrost.c";
 const DOOM_WAD_HINT: &str = "user/doom/wad/doom1.wad";
 const DOOM_FORCE_FALLBACK_ENV: &str = "ARROST_DOOM_FORCE_FALLBACK";
+const DOOM_GENERIC_URL: &str = "https://github.com/ozkl/doomgeneric.git";
+/// Commit `doom-setup` vendors. Written by the first setup that finds no pin; commit it so
+/// every checkout and CI run builds the same DoomGeneric sources.
+const DOOM_GENERIC_PIN_FILE: &str = "user/doom/third_party/doomgeneric.commit";
+const DOOM_WAD_URL_ENV: &str = "ARROST_DOOM_WAD_URL";
+const DOOM_WAD_DEFAULT_URL: &str = "https://distro.ibiblio.org/slitaz/sources/packages/d/doom1.wad";
+/// Shareware DOOM v1.9 `doom1.wad`.
+const DOOM_WAD_SIZE: u64 = 4_196_020;
+const DOOM_WAD_SHA256: &str = "1d7d43be501e67d927e415e0b8f3e29c3bf33075e859721816f652a526cac771";
+/// Firmware `scripts/qemu.sh` looks for when `OVMF_CODE`/`OVMF_VARS` are unset; keep in step.
+const OVMF_CODE_CANDIDATES: [&str; 4] = [
+    "/usr/share/OVMF/OVMF_CODE.fd",
+    "/usr/share/OVMF/OVMF_CODE_4M.fd",
+    "/opt/homebrew/share/qemu/edk2-x86_64-code.fd",
+    "/usr/local/share/qemu/edk2-x86_64-code.fd",
+];
+const OVMF_VARS_CANDIDATES: [&str; 4] = [
+    "/usr/share/OVMF/OVMF_VARS.fd",
+    "/usr/share/OVMF/OVMF_VARS_4M.fd",
+    "/opt/homebrew/share/qemu/edk2-i386-vars.fd",
+    "/usr/local/share/qemu/edk2-i386-vars.fd",
+];
+/// Scratch space for the `doctor` compiler probes.
+const DOCTOR_DIR: &str = "target/doctor";
+/// Bumped when the manifest JSON layout changes; the kernel skips other versions.
+const ARTIFACT_MANIFEST_VERSION: u32 = 1;
+const REPRODUCIBLE_ENV: &str = "ARROST_REPRODUCIBLE";
+/// Optional kernel subsystems (Cargo features of `arrost-kernel`, all on by default).
+const KERNEL_SUBSYSTEMS: [&str; 4] = ["net", "audio", "doom", "desktop"];
+const GPT_HEADER_OFFSET: usize = 512;
+const GPT_SECTOR_SIZE: u64 = 512;
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+const SIZE_BUDGET_ENV: &str = "ARROST_KERNEL_SIZE_BUDGET_KIB";
+const DEFAULT_KERNEL_SIZE_BUDGET_KIB: u64 = 4096;
+const SIZE_REPORT_MODULES: usize = 24;
+const SIZE_REPORT_SYMBOLS: usize = 12;
+/// Set to fail smoke runs on any soft assert instead of only reporting them.
+const SMOKE_STRICT_ASSERTS_ENV: &str = "ARROST_SMOKE_STRICT_ASSERTS";
+/// `savevm` tag recorded at the first `arrost> ` prompt for `--snapshot` smoke runs.
+const SMOKE_SNAPSHOT_TAG: &str = "arrost-prompt";
+/// Environment that shapes the virtual machine; a change invalidates smoke snapshots.
+const SMOKE_SNAPSHOT_ENV: [&str; 9] = [
+    "QEMU_ACCEL",
+    "QEMU_CPU",
+    "QEMU_SMP",
+    "QEMU_AUDIO",
+    "QEMU_VIRTIO_SND_STREAMS",
+    "QEMU_NETDEV",
+    "QEMU_MAC",
+    "OVMF_CODE",
+    "OVMF_VARS",
+];
+/// Host-side multicast group that carries the duo guests' LAN; override for parallel CI jobs.
+const NET_DUO_MCAST_ENV: &str = "ARROST_NET_DUO_MCAST";
+const NET_DUO_DEFAULT_MCAST: &str = "230.0.0.1:12341";
+/// UDP port the duo datagram targets; anything but the 7777 echo responder.
+const NET_DUO_UDP_PORT: u16 = 9000;
+const NET_DUO_PAYLOAD: &str = "duo-hello";
+/// Host port slirp forwards to the guest's `net bench rx` port; override for parallel CI jobs.
+const NET_FLOOD_PORT_ENV: &str = "ARROST_NET_FLOOD_PORT";
+const NET_FLOOD_DEFAULT_PORT: u16 = 5560;
+const NET_FLOOD_GUEST_PORT: u16 = 7779;
+const NET_FLOOD_SECONDS: u64 = 5;
+/// Flood datagram size: a big-endian u64 sequence number and padding.
+const NET_FLOOD_PAYLOAD_BYTES: usize = 1024;
+/// `cargo xtask fuzz` corpus layout: raw cases under `net/` (Ethernet frames) and `shell/`.
+const FUZZ_DEFAULT_CORPUS: &str = "target/fuzz/corpus";
+const FUZZ_CRASH_DIR: &str = "target/fuzz/crashes";
+const FUZZ_DEFAULT_SEED: u64 = 0x6172_724f_5374_2121;
+/// Fixed NIC address so generated frames are not filtered out by destination MAC.
+const FUZZ_GUEST_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0xf2, 0x00, 0x01];
+const FUZZ_GUEST_IP: [u8; 4] = [10, 0, 2, 15];
+/// Bytes per `fuzz append` line; the shell caps lines at 127 characters.
+const FUZZ_APPEND_CHUNK: usize = 48;
+/// Guest diskfs limit (`kernel::fs::diskfs::MAX_DISK_FILE_BYTES`); larger files cannot be imported.
+const FS_MAX_FILE_BYTES: usize = 4096 * 512;
+/// Bytes per base64 line for `fs-import`; 76 characters fit the 127-byte shell line.
+const FS_TRANSFER_LINE_BYTES: usize = 57;
+/// How long `cargo xtask netconsole` waits for the guest's `netconsole: end` line.
+/// Where `console` writes its transcript unless `--log` names another file.
+const CONSOLE_DEFAULT_LOG: &str = "target/console.log";
+const NETCONSOLE_TIMEOUT: Duration = Duration::from_secs(10);
+const SOAK_DEFAULT_MINUTES: u64 = 10;
+const SOAK_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+const SOAK_MAX_DROPS_ENV: &str = "ARROST_SOAK_MAX_DROPS";
+const SOAK_DEFAULT_MAX_DROPS: u64 = 256;
+const SOAK_CSV_HEADER: &str = "elapsed_s,dg_frames,dg_drop,dg_audio_drop,pcm_samples,pcm_drop,pcm_drop_frames,ui_frames,ui_dropped,stdout_skipped,net_rx,net_tx,net_drop";
+const PRIMITIVE_TYPES: [&str; 17] = [
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize", "f32",
+    "f64", "bool", "char", "str",
+];
 
 struct UserArtifact {
     hint: PathBuf,
@@ -37,6 +130,126 @@ struct DoomCBackendArtifact {
     ready: bool,
 }
 
+/// One file the build placed in (or linked into) the boot image, as listed in the initramfs
+/// artifact manifest.
+struct ManifestEntry {
+    name: &'static str,
+    path: PathBuf,
+    size: u64,
+    sha256: String,
+}
+
+#[derive(Clone, Copy, Default)]
+struct SectionSizes {
+    code: u64,
+    rodata: u64,
+    data: u64,
+    bss: u64,
+}
+
+impl SectionSizes {
+    fn add(&mut self, kind: char, size: u64) {
+        match kind.to_ascii_lowercase() {
+            't' | 'w' => self.code += size,
+            'r' => self.rodata += size,
+            'd' => self.data += size,
+            'b' => self.bss += size,
+            _ => {}
+        }
+    }
+
+    fn total(self) -> u64 {
+        self.code + self.rodata + self.data + self.bss
+    }
+}
+
+struct SymbolSize {
+    name: String,
+    kind: char,
+    size: u64,
+}
+
+/// One row of the soak CSV; every field is a cumulative kernel counter.
+struct SoakSample {
+    elapsed_s: u64,
+    dg_frames: u64,
+    dg_drop: u64,
+    dg_audio_drop: u64,
+    pcm_samples: u64,
+    pcm_drop: u64,
+    pcm_drop_frames: u64,
+    ui_frames: u64,
+    ui_dropped: u64,
+    stdout_skipped: u64,
+    net_rx: u64,
+    net_tx: u64,
+    net_drop: u64,
+}
+
+impl SoakSample {
+    fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.elapsed_s,
+            self.dg_frames,
+            self.dg_drop,
+            self.dg_audio_drop,
+            self.pcm_samples,
+            self.pcm_drop,
+            self.pcm_drop_frames,
+            self.ui_frames,
+            self.ui_dropped,
+            self.stdout_skipped,
+            self.net_rx,
+            self.net_tx,
+            self.net_drop
+        )
+    }
+
+    fn drops(&self) -> u64 {
+        self.dg_drop + self.dg_audio_drop + self.pcm_drop_frames
+    }
+}
+
+/// One `smoke-net-duo` guest: its serial log and the static address it is given.
+/// Headless guest driven over its serial console (`fuzz`, `fs-import`, `fs-export`).
+struct HeadlessGuest {
+    child: Child,
+    log: Arc<Mutex<Vec<u8>>>,
+    readers: Vec<thread::JoinHandle<()>>,
+}
+
+/// Timestamped transcript of a `console` session. Guest output is buffered until its newline
+/// so every line gets one stamp, however the serial reads split it.
+struct ConsoleTranscript {
+    file: std::fs::File,
+    start: Instant,
+    guest_line: Vec<u8>,
+}
+
+/// Per-smoke qcow2 drive copies holding a `savevm` taken at the shell prompt, plus the boot
+/// log of that run and the key of the inputs it was recorded from.
+struct SmokeSnapshot {
+    dir: PathBuf,
+}
+
+struct DuoGuest {
+    name: &'static str,
+    ip: &'static str,
+    child: Child,
+    log: Arc<Mutex<Vec<u8>>>,
+    readers: Vec<thread::JoinHandle<()>>,
+}
+
+/// `net` counters compared before and after the duo exchange.
+struct DuoNetCounters {
+    rx: u64,
+    arp: u64,
+    icmp: u64,
+    udp: u64,
+    drop: u64,
+}
+
 struct DoomGenericArtifact {
     root: PathBuf,
     core_source: PathBuf,
@@ -54,37 +267,122 @@ struct DoomGenericArtifact {
 fn main() -> Result<()> {
     let mut args = std::env::args().skip(1);
     match args.next().as_deref() {
-        Some("build") => build(),
+        Some("build") => build(args),
         Some("run") => run_qemu(),
-        Some("smoke-doom") => smoke_doom(),
-        Some("smoke-doom-long") => smoke_doom_long(),
-        Some("smoke-doom-virtio") => smoke_doom_virtio(),
-        Some("smoke-doom-fallback") => smoke_doom_fallback(),
+        Some("smoke-doom") => smoke_doom(smoke_snapshot_flag(args)?),
+        Some("smoke-doom-long") => smoke_doom_long(smoke_snapshot_flag(args)?),
+        Some("smoke-doom-virtio") => smoke_doom_virtio(smoke_snapshot_flag(args)?),
+        Some("smoke-doom-fallback") => smoke_doom_fallback(smoke_snapshot_flag(args)?),
+        Some("size") => size_report(),
+        Some("soak") => soak(args),
+        Some("smoke-net-duo") => smoke_net_duo(),
+        Some("smoke-net-flood") => smoke_net_flood(),
+        Some("smoke-fs") => smoke_fs(),
+        Some("smoke-history") => smoke_history(),
+        Some("fuzz") => fuzz(args),
+        Some("fs-import") => fs_import(args),
+        Some("fs-export") => fs_export(args),
+        Some("doom-setup") => doom_setup(args),
+        Some("doctor") => doctor(),
+        Some("netconsole") => netconsole(args),
+        Some("console") => console(args),
+        Some("serial-decode") => serial_decode(args),
+        Some("evlog-decode") => evlog_decode(args),
         _ => {
             eprintln!(
-                "Usage: cargo xtask <build|run|smoke-doom|smoke-doom-long|smoke-doom-virtio|smoke-doom-fallback>"
+                "Usage: cargo xtask <build [--reproducible] [--features LIST|--minimal]|run|size|soak [--minutes N]|smoke-doom [--snapshot]|smoke-doom-long [--snapshot]|smoke-doom-virtio [--snapshot]|smoke-doom-fallback [--snapshot]|smoke-net-duo|smoke-net-flood|smoke-fs|smoke-history|fuzz [--corpus DIR] [--generate N] [--seed S]|fs-import <host-file> [name]|fs-export <name> [host-file]|doom-setup [--no-wad]|doctor|netconsole <host:port> <token> <command>|console [--log FILE]|serial-decode <log> [out]|evlog-decode <log> [out]>"
             );
             Ok(())
         }
     }
 }
 
-fn build() -> Result<()> {
-    build_impl(env_truthy(DOOM_FORCE_FALLBACK_ENV))
+/// `--features net,desktop` builds the kernel with only those subsystems, `--minimal` with none
+/// of them; without either every subsystem is built in.
+fn build(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut reproducible = env_truthy(REPRODUCIBLE_ENV);
+    let mut subsystems = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--reproducible" => reproducible = true,
+            "--minimal" => subsystems = Some(Vec::new()),
+            "--features" => {
+                let list = args
+                    .next()
+                    .context("--features needs a comma-separated list")?;
+                subsystems = Some(parse_kernel_subsystems(&list)?);
+            }
+            other => bail!("unknown build argument `{other}`"),
+        }
+    }
+    build_impl(
+        env_truthy(DOOM_FORCE_FALLBACK_ENV),
+        reproducible,
+        false,
+        subsystems.as_deref(),
+    )
+}
+
+fn parse_kernel_subsystems(list: &str) -> Result<Vec<String>> {
+    let mut subsystems = Vec::new();
+    for name in list
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        if !KERNEL_SUBSYSTEMS.contains(&name) {
+            bail!(
+                "unknown kernel feature `{name}` (expected {})",
+                KERNEL_SUBSYSTEMS.join(", ")
+            );
+        }
+        if !subsystems.iter().any(|known| known == name) {
+            subsystems.push(name.to_string());
+        }
+    }
+    Ok(subsystems)
 }
 
-fn build_impl(force_fallback: bool) -> Result<()> {
-    let build_count = next_build_count()?;
-    let version = format!("{VERSION_MAJOR}.{VERSION_MINOR}.{build_count}");
-    let build_count_env = build_count.to_string();
+/// `subsystems` replaces the kernel's default features when set (see `KERNEL_SUBSYSTEMS`).
+fn build_impl(
+    force_fallback: bool,
+    reproducible: bool,
+    fuzz: bool,
+    subsystems: Option<&[String]>,
+) -> Result<()> {
+    // Reproducible builds leave `.arrost_build_count` untouched and take the build field
+    // from git instead, so the same commit always embeds the same version string.
+    let (build_count_env, cargo_config) = if reproducible {
+        (reproducible_build_id()?, reproducible_cargo_config()?)
+    } else {
+        (next_build_count()?.to_string(), Vec::new())
+    };
+    let version = format!("{VERSION_MAJOR}.{VERSION_MINOR}.{build_count_env}");
     let major_env = VERSION_MAJOR.to_string();
     let minor_env = VERSION_MINOR.to_string();
-    println!("ArrOSt build version: {version}");
+    let subsystems_label = match subsystems {
+        None => "default".to_string(),
+        Some([]) => "none".to_string(),
+        Some(list) => list.join(","),
+    };
+    println!(
+        "ArrOSt build version: {version} (reproducible={reproducible} fuzz={fuzz} features={subsystems_label})"
+    );
 
-    let user_init =
-        build_userland_package(USER_INIT_PACKAGE, &build_count_env, &major_env, &minor_env)?;
-    let user_doom =
-        build_userland_package(USER_DOOM_PACKAGE, &build_count_env, &major_env, &minor_env)?;
+    let user_init = build_userland_package(
+        USER_INIT_PACKAGE,
+        &build_count_env,
+        &major_env,
+        &minor_env,
+        &cargo_config,
+    )?;
+    let user_doom = build_userland_package(
+        USER_DOOM_PACKAGE,
+        &build_count_env,
+        &major_env,
+        &minor_env,
+        &cargo_config,
+    )?;
     let doom_c_backend = build_doom_c_backend_artifact()?;
     let doom_generic = build_doom_generic_artifact()?;
     println!(
@@ -107,6 +405,14 @@ fn build_impl(force_fallback: bool) -> Result<()> {
         doom_generic.wad_hint.display(),
         doom_generic.wad_present
     );
+    let manifest =
+        collect_artifact_manifest(&user_init, &user_doom, &doom_c_backend, &doom_generic)?;
+    let manifest_digest = |name: &str| {
+        manifest
+            .iter()
+            .find(|entry| entry.name == name)
+            .map_or_else(String::new, |entry| entry.sha256.clone())
+    };
     let doom_generic_ready_for_kernel = doom_generic.ready && !force_fallback;
     if force_fallback {
         println!(
@@ -193,6 +499,16 @@ fn build_impl(force_fallback: bool) -> Result<()> {
             "ARROST_DOOM_WAD_HINT",
             doom_generic.wad_hint.display().to_string(),
         )
+        // Digests the kernel checks the initramfs manifest against at boot.
+        .env(
+            "ARROST_DOOM_GENERIC_CORE_SHA256",
+            manifest_digest("doom_core"),
+        )
+        .env(
+            "ARROST_DOOM_GENERIC_PORT_SHA256",
+            manifest_digest("doom_port"),
+        )
+        .env("ARROST_DOOM_WAD_SHA256", manifest_digest("doom_wad"))
         .env(
             "ARROST_DOOM_WAD_PRESENT",
             if doom_generic.wad_present {
@@ -210,6 +526,8 @@ fn build_impl(force_fallback: bool) -> Result<()> {
             BUILD_STD,
             BUILD_STD_FEATURES,
         ])
+        .args(kernel_feature_args(fuzz, subsystems))
+        .args(&cargo_config)
         .status()
         .context("cargo build failed")?;
     if !status.success() {
@@ -221,8 +539,13 @@ fn build_impl(force_fallback: bool) -> Result<()> {
     if !kernel_binary.exists() {
         bail!("missing kernel binary at {}", kernel_binary.display());
     }
-    let ramdisk_path =
-        create_ramdisk_image(&user_init, &user_doom, &doom_c_backend, &doom_generic)?;
+    let ramdisk_path = create_ramdisk_image(
+        &user_init,
+        &user_doom,
+        &doom_c_backend,
+        &doom_generic,
+        &manifest,
+    )?;
     let _storage_disk_path = ensure_storage_disk_image()?;
 
     let disk_image = PathBuf::from(format!(
@@ -233,15 +556,36 @@ fn build_impl(force_fallback: bool) -> Result<()> {
     builder
         .create_uefi_image(&disk_image)
         .context("failed to create UEFI disk image")?;
+    if reproducible {
+        normalize_gpt_guids(&disk_image, &build_count_env)?;
+    }
 
     Ok(())
 }
 
+/// Cargo flags selecting the kernel features: the default set unless `subsystems` is given.
+fn kernel_feature_args(fuzz: bool, subsystems: Option<&[String]>) -> Vec<String> {
+    let mut features: Vec<String> = subsystems.map(<[String]>::to_vec).unwrap_or_default();
+    if fuzz {
+        features.push("fuzz".to_string());
+    }
+    let mut args = Vec::new();
+    if subsystems.is_some() {
+        args.push("--no-default-features".to_string());
+    }
+    if !features.is_empty() {
+        args.push("--features".to_string());
+        args.push(features.join(","));
+    }
+    args
+}
+
 fn build_userland_package(
     package: &str,
     build_count_env: &str,
     major_env: &str,
     minor_env: &str,
+    cargo_config: &[String],
 ) -> Result<UserArtifact> {
     let status = Command::new("cargo")
         .env("ARROST_BUILD_COUNT", build_count_env)
@@ -256,6 +600,7 @@ fn build_userland_package(
             BUILD_STD,
             BUILD_STD_FEATURES,
         ])
+        .args(cargo_config)
         .status()
         .with_context(|| format!("cargo build for {package} failed"))?;
     if !status.success() {
@@ -467,7 +812,7 @@ fn build_doom_generic_artifact() -> Result<DoomGenericArtifact> {
 
     if !core_source.exists() {
         eprintln!(
-            "warning: missing DoomGeneric sources at {}; run scripts/vendor_doomgeneric.sh",
+            "warning: missing DoomGeneric sources at {}; run cargo xtask doom-setup",
             root.display()
         );
     }
@@ -494,685 +839,3259 @@ fn build_doom_generic_artifact() -> Result<DoomGenericArtifact> {
     })
 }
 
-fn next_build_count() -> Result<u64> {
-    let path = PathBuf::from(BUILD_COUNTER_FILE);
-    let current = std::fs::read_to_string(&path)
+/// Vendors DoomGeneric at the pinned commit, fetches the shareware WAD and checks the files
+/// `build_doom_generic_artifact` compiles and embeds, so a fresh checkout builds real Doom.
+fn doom_setup(args: impl Iterator<Item = String>) -> Result<()> {
+    let mut fetch_wad = true;
+    for arg in args {
+        match arg.as_str() {
+            "--no-wad" => fetch_wad = false,
+            other => bail!("unknown doom-setup argument `{other}`"),
+        }
+    }
+    let commit = vendor_doom_generic()?;
+    if fetch_wad {
+        fetch_doom_wad()?;
+    }
+    check_doom_layout(fetch_wad)?;
+    println!("doom-setup: ready doomgeneric={commit} wad={DOOM_WAD_HINT}");
+    Ok(())
+}
+
+fn vendor_doom_generic() -> Result<String> {
+    let root = PathBuf::from(DOOM_GENERIC_ROOT);
+    let root_str = root.to_string_lossy().into_owned();
+    let pin = std::fs::read_to_string(DOOM_GENERIC_PIN_FILE)
         .ok()
-        .and_then(|content| content.trim().parse::<u64>().ok())
-        .unwrap_or(0);
-    let next = current.saturating_add(1);
-    std::fs::write(&path, format!("{next}\n"))
-        .with_context(|| format!("failed to write {}", path.display()))?;
-    Ok(next)
+        .map(|content| content.trim().to_string())
+        .filter(|commit| !commit.is_empty());
+
+    let current = if root.join(".git").exists() {
+        git_output(&["-C", &root_str, "rev-parse", "HEAD"])
+            .ok()
+            .map(|head| head.trim().to_string())
+    } else {
+        None
+    };
+    if let (Some(pin), Some(current)) = (&pin, &current)
+        && pin == current
+    {
+        println!("doom-setup: doomgeneric already at {pin}");
+        return Ok(pin.clone());
+    }
+
+    // Same recovery as scripts/vendor_doomgeneric.sh: a partial or foreign checkout is replaced.
+    if root.exists() {
+        println!("doom-setup: replacing {}", root.display());
+        std::fs::remove_dir_all(&root)
+            .with_context(|| format!("failed to remove {}", root.display()))?;
+    }
+    std::fs::create_dir_all(&root)
+        .with_context(|| format!("failed to create {}", root.display()))?;
+    git_output(&["-C", &root_str, "init", "--quiet"])?;
+    git_output(&["-C", &root_str, "remote", "add", "origin", DOOM_GENERIC_URL])?;
+    let wanted = pin.as_deref().unwrap_or("HEAD");
+    git_output(&[
+        "-C", &root_str, "fetch", "--quiet", "--depth", "1", "origin", wanted,
+    ])?;
+    git_output(&["-C", &root_str, "checkout", "--quiet", "FETCH_HEAD"])?;
+    let commit = git_output(&["-C", &root_str, "rev-parse", "HEAD"])?
+        .trim()
+        .to_string();
+
+    if pin.is_none() {
+        std::fs::write(DOOM_GENERIC_PIN_FILE, format!("{commit}\n"))
+            .with_context(|| format!("failed to write {DOOM_GENERIC_PIN_FILE}"))?;
+        println!("doom-setup: pinned doomgeneric {commit} in {DOOM_GENERIC_PIN_FILE}; commit it");
+    }
+    println!(
+        "doom-setup: vendored doomgeneric {commit} at {}",
+        root.display()
+    );
+    Ok(commit)
 }
 
-fn run_qemu() -> Result<()> {
-    // Si appoggia a scripts/qemu.sh per semplicità
-    let status = Command::new("bash")
-        .args(["scripts/qemu.sh"])
+fn fetch_doom_wad() -> Result<()> {
+    let wad = PathBuf::from(DOOM_WAD_HINT);
+    if wad.exists() {
+        let bytes =
+            std::fs::read(&wad).with_context(|| format!("failed to read {}", wad.display()))?;
+        if sha256_hex(&bytes) == DOOM_WAD_SHA256 {
+            println!("doom-setup: {} verified", wad.display());
+        } else {
+            // Registered or modded WADs are fine to run; only the download is pinned.
+            println!(
+                "doom-setup: {} is not the shareware v1.9 WAD; keeping it",
+                wad.display()
+            );
+        }
+        return Ok(());
+    }
+
+    let url = std::env::var(DOOM_WAD_URL_ENV).unwrap_or_else(|_| DOOM_WAD_DEFAULT_URL.to_string());
+    let partial = wad.with_extension("wad.part");
+    println!("doom-setup: downloading {url}");
+    let status = Command::new("curl")
+        .args([
+            "--fail",
+            "--location",
+            "--silent",
+            "--show-error",
+            "--output",
+        ])
+        .arg(&partial)
+        .arg(&url)
         .status()
-        .context("qemu run failed")?;
+        .context("failed to run curl")?;
     if !status.success() {
-        bail!("qemu exited with error");
+        let _ = std::fs::remove_file(&partial);
+        bail!("download of {url} failed; set {DOOM_WAD_URL_ENV} to another mirror");
+    }
+    let bytes =
+        std::fs::read(&partial).with_context(|| format!("failed to read {}", partial.display()))?;
+    let digest = sha256_hex(&bytes);
+    if bytes.len() as u64 != DOOM_WAD_SIZE || digest != DOOM_WAD_SHA256 {
+        let _ = std::fs::remove_file(&partial);
+        bail!(
+            "{url} is not the shareware doom1.wad (size={} sha256={digest}, expected size={DOOM_WAD_SIZE} sha256={DOOM_WAD_SHA256})",
+            bytes.len()
+        );
+    }
+    std::fs::rename(&partial, &wad)
+        .with_context(|| format!("failed to move WAD to {}", wad.display()))?;
+    println!("doom-setup: {} verified", wad.display());
+    Ok(())
+}
+
+/// Fails with every missing piece at once rather than stopping at the first.
+fn check_doom_layout(require_wad: bool) -> Result<()> {
+    let mut missing = Vec::new();
+    for path in [
+        DOOM_GENERIC_CORE_SOURCE,
+        &format!("{DOOM_GENERIC_INCLUDE_DIR}/doomgeneric.h"),
+        DOOM_GENERIC_PORT_SOURCE,
+        DOOM_C_SOURCE,
+    ] {
+        if !Path::new(path).is_file() {
+            missing.push(path.to_string());
+        }
+    }
+    if require_wad {
+        let mut magic = [0u8; 4];
+        let iwad = std::fs::File::open(DOOM_WAD_HINT)
+            .and_then(|mut file| file.read_exact(&mut magic))
+            .is_ok()
+            && &magic == b"IWAD";
+        if !iwad {
+            missing.push(format!("{DOOM_WAD_HINT} (IWAD header)"));
+        }
+    }
+    if !missing.is_empty() {
+        bail!("doom layout incomplete: {}", missing.join(", "));
     }
     Ok(())
 }
 
-fn smoke_doom() -> Result<()> {
-    smoke_doom_impl(false, false, false)
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DoctorStatus {
+    Ok,
+    /// Builds and boots, with a feature missing (fallback Doom, no virtio-sound).
+    Warn,
+    /// `cargo xtask build` or `run` fails, or quietly produces something broken.
+    Fail,
 }
 
-fn smoke_doom_long() -> Result<()> {
-    smoke_doom_impl(true, false, false)
+impl DoctorStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+        }
+    }
 }
 
-fn smoke_doom_virtio() -> Result<()> {
-    smoke_doom_impl(true, false, true)
+struct DoctorCheck {
+    name: &'static str,
+    status: DoctorStatus,
+    detail: String,
+    fix: Option<String>,
 }
 
-fn smoke_doom_fallback() -> Result<()> {
-    build_impl(true)?;
-    let smoke_result = smoke_doom_impl(false, true, false);
-    let restore_result = build_impl(false);
-    match smoke_result {
-        Ok(()) => {
-            restore_result?;
-            Ok(())
+impl DoctorCheck {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: DoctorStatus::Ok,
+            detail: detail.into(),
+            fix: None,
         }
-        Err(smoke_err) => {
-            if let Err(restore_err) = restore_result {
-                return Err(smoke_err.context(format!(
-                    "fallback smoke failed and restoring normal DoomGeneric build failed: {restore_err:#}"
-                )));
-            }
-            Err(smoke_err)
+    }
+
+    fn problem(
+        name: &'static str,
+        status: DoctorStatus,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            fix: Some(fix.into()),
         }
     }
 }
 
-fn smoke_doom_impl(long_run: bool, force_fallback: bool, strict_virtio: bool) -> Result<()> {
-    let smoke_name = if strict_virtio {
-        "smoke-doom-virtio"
-    } else if force_fallback {
-        "smoke-doom-fallback"
-    } else if long_run {
-        "smoke-doom-long"
-    } else {
-        "smoke-doom"
-    };
+/// Checks the host prerequisites `build` and `run` rely on and prints a fix for each one
+/// missing. Without this they show up late: a QEMU error after a full build, or placeholder
+/// Doom objects that only a `warning:` line mentions.
+fn doctor() -> Result<()> {
+    let mut checks = Vec::new();
+    doctor_toolchain(&mut checks);
+    doctor_c_compilers(&mut checks)?;
+    doctor_qemu(&mut checks);
+    doctor_firmware(&mut checks);
+    checks.push(match check_doom_layout(true) {
+        Ok(()) => DoctorCheck::ok("doom", format!("doomgeneric and {DOOM_WAD_HINT} present")),
+        Err(error) => DoctorCheck::problem(
+            "doom",
+            DoctorStatus::Warn,
+            error.to_string(),
+            "run `cargo xtask doom-setup`; until then the doom app runs the built-in fallback",
+        ),
+    });
 
-    let kernel_image = PathBuf::from(format!(
-        "target/{KERNEL_TARGET}/debug/bootimage-{KERNEL_PACKAGE}.bin"
-    ));
-    if !kernel_image.exists() {
-        bail!(
-            "missing kernel image at {}; run `cargo xtask build` first",
-            kernel_image.display()
+    for check in &checks {
+        println!(
+            "doctor: {:<4} {}: {}",
+            check.status.as_str(),
+            check.name,
+            check.detail
         );
+        if let Some(fix) = &check.fix {
+            println!("doctor:      fix: {fix}");
+        }
     }
-
-    let data_image = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/m6-disk.img"));
-    if !data_image.exists() {
-        bail!(
-            "missing storage image at {}; run `cargo xtask build` first",
-            data_image.display()
-        );
+    let count = |status| checks.iter().filter(|check| check.status == status).count();
+    let failed = count(DoctorStatus::Fail);
+    println!(
+        "doctor: ok={} warn={} fail={failed}",
+        count(DoctorStatus::Ok),
+        count(DoctorStatus::Warn)
+    );
+    if failed > 0 {
+        bail!("{failed} prerequisite check(s) failed");
     }
+    Ok(())
+}
 
-    let mut qemu_cmd = Command::new("bash");
-    qemu_cmd
-        .args(["scripts/qemu.sh"])
-        .env("QEMU_DISPLAY", "none");
-    if strict_virtio {
-        qemu_cmd.env("QEMU_VIRTIO_SND", "on");
-        qemu_cmd.env("QEMU_PCSPK", "off");
-    }
-    if std::env::var_os("QEMU_AUDIO").is_none() {
-        qemu_cmd.env("QEMU_AUDIO", "wav");
-    }
-    if std::env::var_os("QEMU_AUDIO_WAV_PATH").is_none() {
-        qemu_cmd.env(
-            "QEMU_AUDIO_WAV_PATH",
-            format!("target/{KERNEL_TARGET}/debug/{smoke_name}.wav"),
-        );
+/// Stdout of a command that ran and exited 0.
+fn command_stdout(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn doctor_toolchain(checks: &mut Vec<DoctorCheck>) {
+    // Run from the workspace, so rustup already applies rust-toolchain.toml.
+    let Some(version) = command_stdout("rustc", &["--version"]) else {
+        checks.push(DoctorCheck::problem(
+            "toolchain",
+            DoctorStatus::Fail,
+            "rustc not found",
+            "install rustup (https://rustup.rs); rust-toolchain.toml then selects nightly",
+        ));
+        return;
+    };
+    let version = version.trim().to_string();
+    if !version.contains("nightly") {
+        checks.push(DoctorCheck::problem(
+            "toolchain",
+            DoctorStatus::Fail,
+            format!("{version} is not a nightly (-Zbuild-std needs one)"),
+            "rustup toolchain install nightly, and drop any RUSTUP_TOOLCHAIN or override set here",
+        ));
+        return;
     }
-    let mut child = qemu_cmd
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .with_context(|| format!("failed to start qemu run for {smoke_name}"))?;
+    checks.push(DoctorCheck::ok("toolchain", version));
 
-    let stdout = child
-        .stdout
-        .take()
-        .context("failed to capture qemu stdout")?;
-    let stderr = child
-        .stderr
-        .take()
-        .context("failed to capture qemu stderr")?;
+    let sysroot = command_stdout("rustc", &["--print", "sysroot"]).unwrap_or_default();
+    let library = Path::new(sysroot.trim()).join("lib/rustlib/src/rust/library/core/Cargo.toml");
+    checks.push(if library.is_file() {
+        DoctorCheck::ok("rust-src", format!("{}", library.display()))
+    } else {
+        DoctorCheck::problem(
+            "rust-src",
+            DoctorStatus::Fail,
+            "core sources missing from the sysroot (-Zbuild-std builds core from them)",
+            "rustup component add rust-src --toolchain nightly",
+        )
+    });
+}
 
-    let log = Arc::new(Mutex::new(Vec::<u8>::new()));
-    let stdout_reader = spawn_log_reader(stdout, Arc::clone(&log));
-    let stderr_reader = spawn_log_reader(stderr, Arc::clone(&log));
+/// Compiles a one-function probe with the flags `build` uses: host `cc` for the Doom artifact
+/// objects, and `clang --target=x86_64-unknown-none-elf` for the kernel's C bridge.
+fn doctor_c_compilers(checks: &mut Vec<DoctorCheck>) -> Result<()> {
+    let dir = PathBuf::from(DOCTOR_DIR);
+    std::fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let source = dir.join("probe.c");
+    std::fs::write(
+        &source,
+        "int arrost_probe(int value) { return value * 3; }\n",
+    )
+    .with_context(|| format!("failed to write {}", source.display()))?;
 
-    let smoke_result = (|| -> Result<()> {
-        wait_for_log(&log, "arrost> ", Duration::from_secs(40), "shell prompt")?;
-        let startup_snapshot = snapshot_log(&log);
-        let software_accel_mode = startup_snapshot.contains("Using QEMU acceleration: tcg")
-            || startup_snapshot.contains("Using QEMU acceleration: none");
-        let stdin = child
-            .stdin
-            .as_mut()
-            .context("failed to capture qemu stdin")?;
+    let probes: [(&'static str, &str, &[&str], &str); 2] = [
+        (
+            "cc",
+            "cc",
+            &["-std=c11", "-ffreestanding", "-fno-builtin", "-O2"],
+            "install a C compiler as `cc` (build-essential, or the Xcode command line tools); without one the Doom objects are placeholders and the doom app falls back",
+        ),
+        (
+            "clang",
+            "clang",
+            &[
+                "--target=x86_64-unknown-none-elf",
+                "-std=c11",
+                "-ffreestanding",
+                "-mno-red-zone",
+            ],
+            "install clang with the x86 backend (apt install clang, or brew install llvm and put its bin/ on PATH); kernel/build.rs compiles the Doom bridge with it",
+        ),
+    ];
+    for (name, program, flags, fix) in probes {
+        let object = dir.join(format!("probe-{name}.o"));
+        let _ = std::fs::remove_file(&object);
+        let output = Command::new(program)
+            .args(flags)
+            .arg("-c")
+            .arg(&source)
+            .arg("-o")
+            .arg(&object)
+            .output();
+        let check = match output {
+            Err(error) => {
+                DoctorCheck::problem(name, DoctorStatus::Fail, format!("{program}: {error}"), fix)
+            }
+            Ok(output) if !output.status.success() => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let reason = stderr.lines().next().unwrap_or("no diagnostics").trim();
+                DoctorCheck::problem(
+                    name,
+                    DoctorStatus::Fail,
+                    format!("freestanding compile failed: {reason}"),
+                    fix,
+                )
+            }
+            Ok(_) if elf_x86_64(&object) => DoctorCheck::ok(name, "freestanding x86_64 ELF object"),
+            Ok(_) => DoctorCheck::problem(
+                name,
+                DoctorStatus::Warn,
+                "compiles, but not to an x86_64 ELF object",
+                fix,
+            ),
+        };
+        checks.push(check);
+    }
+    Ok(())
+}
 
-        send_serial_command(stdin, "versionx\u{7f}\n")?;
-        wait_for_log(
-            &log,
-            "version: ",
-            Duration::from_secs(8),
-            "serial backspace",
-        )?;
+fn elf_x86_64(path: &Path) -> bool {
+    const EM_X86_64: u16 = 0x3e;
+    let mut header = [0u8; 20];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && header[..4] == *b"\x7fELF"
+        && header[4] == 2
+        && u16::from_le_bytes([header[18], header[19]]) == EM_X86_64
+}
 
-        let ready = snapshot_log(&log).contains("DoomGeneric: ready=true");
-        if force_fallback && ready {
-            bail!("expected DoomGeneric ready=false for fallback smoke");
-        }
-        if !force_fallback && !ready {
-            bail!(
-                "doomgeneric ready=false in smoke-doom; run `cargo xtask build` (or wait for fallback restore) and retry"
-            );
-        }
+fn doctor_qemu(checks: &mut Vec<DoctorCheck>) {
+    let Some(version) = command_stdout("qemu-system-x86_64", &["--version"]) else {
+        checks.push(DoctorCheck::problem(
+            "qemu",
+            DoctorStatus::Fail,
+            "qemu-system-x86_64 not found",
+            "install QEMU (apt install qemu-system-x86, or brew install qemu)",
+        ));
+        return;
+    };
+    let version = version
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    checks.push(DoctorCheck::ok("qemu", version));
 
-        send_serial_command(stdin, "doom play\n")?;
-        let play_marker = if ready {
-            "doom: play mode started (doomgeneric)"
+    let devices = Command::new("qemu-system-x86_64")
+        .args(["-device", "help"])
+        .output()
+        .map(|output| {
+            let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&output.stderr));
+            text
+        })
+        .unwrap_or_default();
+    let has_device = |device: &str| devices.contains(&format!("\"{device}\""));
+    for device in ["virtio-net-pci", "virtio-blk-pci"] {
+        checks.push(if has_device(device) {
+            DoctorCheck::ok("qemu-device", device)
         } else {
-            "doom: doomgeneric not ready; starting fallback runtime"
+            DoctorCheck::problem(
+                "qemu-device",
+                DoctorStatus::Fail,
+                format!("{device} missing"),
+                "install a QEMU build with virtio support (distribution packages have it)",
+            )
+        });
+    }
+    checks.push(if has_device("virtio-sound-pci") {
+        DoctorCheck::ok("qemu-device", "virtio-sound-pci")
+    } else {
+        DoctorCheck::problem(
+            "qemu-device",
+            DoctorStatus::Warn,
+            "virtio-sound-pci missing",
+            "upgrade to QEMU 8.2 or newer, or run with QEMU_VIRTIO_SND=off (PC speaker only)",
+        )
+    });
+}
+
+/// Same lookup as `scripts/qemu.sh`: the env override, else the first candidate that exists.
+fn doctor_firmware(checks: &mut Vec<DoctorCheck>) {
+    for (env, candidates) in [
+        ("OVMF_CODE", OVMF_CODE_CANDIDATES),
+        ("OVMF_VARS", OVMF_VARS_CANDIDATES),
+    ] {
+        let found = match std::env::var(env) {
+            Ok(path) => Some(PathBuf::from(path)).filter(|path| path.is_file()),
+            Err(_) => candidates
+                .iter()
+                .map(PathBuf::from)
+                .find(|path| path.is_file()),
         };
-        wait_for_log(
-            &log,
-            play_marker,
-            Duration::from_secs(12),
-            "doom play confirmation",
-        )?;
+        checks.push(match found {
+            Some(path) => DoctorCheck::ok("firmware", format!("{env}={}", path.display())),
+            None => DoctorCheck::problem(
+                "firmware",
+                DoctorStatus::Fail,
+                match std::env::var(env) {
+                    Ok(path) => format!("{env}={path} does not exist"),
+                    Err(_) => format!("no {env} firmware in {}", candidates.join(", ")),
+                },
+                format!("install OVMF (apt install ovmf, or brew install qemu) or set {env}"),
+            ),
+        });
+    }
+}
 
-        if force_fallback {
-            wait_for_log(
-                &log,
-                "doom: capture unavailable (fallback mode)",
-                Duration::from_secs(8),
-                "doom fallback capture notice",
-            )?;
+fn next_build_count() -> Result<u64> {
+    let path = PathBuf::from(BUILD_COUNTER_FILE);
+    let current = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| content.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    let next = current.saturating_add(1);
+    std::fs::write(&path, format!("{next}\n"))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(next)
+}
 
-            send_serial_command(stdin, "doom status\n")?;
-            wait_for_log(
-                &log,
-                "doom: app=doom engine=fallback-sim",
-                Duration::from_secs(8),
-                "doom fallback status line",
-            )?;
-            let fallback_snapshot = snapshot_log(&log);
-            let Some(fallback_line) =
-                last_matching_line(&fallback_snapshot, "doom: app=doom engine=fallback-sim")
-            else {
-                bail!("missing fallback status line");
-            };
-            if !(fallback_line.contains("doomgeneric=false")
-                || fallback_line.contains("bridge=stub"))
-            {
-                bail!("fallback status mismatch: expected bridge=stub or doomgeneric=false");
-            }
+/// `git describe` of HEAD; a dirty tree adds a hash of the diff so uncommitted edits
+/// never masquerade as the clean commit.
+fn reproducible_build_id() -> Result<String> {
+    let describe = git_output(&["describe", "--always", "--dirty", "--abbrev=12"])?;
+    let describe = describe.trim();
+    if !describe.ends_with("-dirty") {
+        return Ok(format!("g{describe}"));
+    }
+    let diff = git_output(&["diff", "HEAD", "--binary"])?;
+    let untracked = git_output(&["ls-files", "--others", "--exclude-standard"])?;
+    let mut hash = fnv1a64(FNV_OFFSET_BASIS, diff.as_bytes());
+    for path in untracked.lines() {
+        hash = fnv1a64(hash, path.as_bytes());
+        if let Ok(bytes) = std::fs::read(path) {
+            hash = fnv1a64(hash, &bytes);
+        }
+    }
+    Ok(format!("g{describe}.{hash:016x}"))
+}
 
-            send_serial_command(stdin, "doom key left\n")?;
-            wait_for_log(
-                &log,
-                "doom: injected key 0x61",
-                Duration::from_secs(8),
-                "fallback key injection",
-            )?;
+fn git_output(args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .with_context(|| format!("failed to run git {}", args.join(" ")))?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed (reproducible builds need a git checkout): {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
 
-            send_serial_command(stdin, "doom status\n")?;
-            wait_for_log(
-                &log,
-                "doom: app=doom engine=fallback-sim",
-                Duration::from_secs(8),
-                "fallback status post-input",
-            )?;
+/// Extra `--config` for cargo that strips machine-specific absolute paths (workspace,
+/// cargo registry, sysroot sources) from panic locations and debug info. Cargo appends
+/// these rustflags to the ones in `.cargo/config.toml`.
+fn reproducible_cargo_config() -> Result<Vec<String>> {
+    let mut remaps = Vec::new();
+    let workspace = std::env::current_dir().context("failed to resolve workspace dir")?;
+    remaps.push(format!("{}=/arrost", workspace.display()));
+    if let Some(cargo_home) = std::env::var_os("CARGO_HOME") {
+        remaps.push(format!("{}=/cargo", PathBuf::from(cargo_home).display()));
+    } else if let Some(home) = std::env::var_os("HOME") {
+        remaps.push(format!(
+            "{}=/cargo",
+            PathBuf::from(home).join(".cargo").display()
+        ));
+    }
+    let sysroot = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .context("failed to query rustc sysroot")?;
+    let sysroot = String::from_utf8_lossy(&sysroot.stdout).trim().to_string();
+    if !sysroot.is_empty() {
+        remaps.push(format!("{sysroot}=/rustc-sysroot"));
+    }
 
-            send_serial_command(stdin, "ui\n")?;
-            wait_for_log(
-                &log,
-                "ui: backend=uefi-gop ready=true",
-                Duration::from_secs(8),
-                "ui diagnostics line",
-            )?;
-            let ui_snapshot = snapshot_log(&log);
-            if let Some(ui_line) =
-                last_matching_line(&ui_snapshot, "ui: backend=uefi-gop ready=true")
-                && let Some(stdout_dropped) = parse_metric_value(ui_line, "stdout_dropped=")
-                && stdout_dropped > 0
-            {
-                bail!(
-                    "stdout mirror dropped bytes during fallback smoke (stdout_dropped={stdout_dropped})"
-                );
-            }
+    let flags = remaps
+        .iter()
+        .map(|remap| format!("\"--remap-path-prefix={remap}\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(vec![
+        String::from("--config"),
+        format!("target.{KERNEL_TARGET}.rustflags=[{flags}]"),
+    ])
+}
 
-            send_serial_command(stdin, "doom stop\n")?;
-            wait_for_log(
-                &log,
-                "doom: runtime stopped",
-                Duration::from_secs(8),
-                "doom stop confirmation",
-            )?;
-            return Ok(());
+/// The bootloader's GPT writer picks random disk/partition GUIDs; rewrite them from
+/// `seed` in both headers and refresh the CRCs so identical inputs give identical images.
+fn normalize_gpt_guids(image: &PathBuf, seed: &str) -> Result<()> {
+    let mut bytes =
+        std::fs::read(image).with_context(|| format!("failed to read {}", image.display()))?;
+    let primary = GPT_HEADER_OFFSET;
+    if bytes.get(primary..primary + 8) != Some(b"EFI PART".as_slice()) {
+        bail!("{} has no GPT header", image.display());
+    }
+    let backup_lba = read_u64_le(&bytes, primary + 32);
+    let backup = usize::try_from(backup_lba * GPT_SECTOR_SIZE).unwrap_or(usize::MAX);
+    let disk_guid = derived_guid(seed, "disk");
+
+    for header in [primary, backup] {
+        if bytes.get(header..header + 8) != Some(b"EFI PART".as_slice()) {
+            bail!("{} has a damaged GPT header at {header}", image.display());
+        }
+        let header_size = read_u32_le(&bytes, header + 12) as usize;
+        let entries = usize::try_from(read_u64_le(&bytes, header + 72) * GPT_SECTOR_SIZE)
+            .unwrap_or(usize::MAX);
+        let entry_count = read_u32_le(&bytes, header + 80) as usize;
+        let entry_size = read_u32_le(&bytes, header + 84) as usize;
+        let entries_end = entries.saturating_add(entry_count * entry_size);
+        if entries_end > bytes.len() || header + header_size > bytes.len() {
+            bail!("{} has GPT entries outside the image", image.display());
         }
 
-        if ready {
-            wait_for_log(
-                &log,
-                "doom: capture enabled (press ESC to exit)",
-                Duration::from_secs(8),
-                "doom auto-capture enabled",
-            )?;
-            send_serial_command(stdin, "\u{1b}")?;
-            wait_for_log(
-                &log,
-                "doom: capture disabled",
-                Duration::from_secs(8),
-                "doom auto-capture escape",
-            )?;
+        for index in 0..entry_count {
+            let entry = entries + index * entry_size;
+            if bytes[entry..entry + 16].iter().all(|byte| *byte == 0) {
+                continue;
+            }
+            let guid = derived_guid(seed, &format!("partition{index}"));
+            bytes[entry + 16..entry + 32].copy_from_slice(&guid);
         }
+        bytes[header + 56..header + 72].copy_from_slice(&disk_guid);
+        let entries_crc = crc32(&bytes[entries..entries_end]);
+        bytes[header + 88..header + 92].copy_from_slice(&entries_crc.to_le_bytes());
+        bytes[header + 16..header + 20].fill(0);
+        let header_crc = crc32(&bytes[header..header + header_size]);
+        bytes[header + 16..header + 20].copy_from_slice(&header_crc.to_le_bytes());
+    }
 
-        wait_for_music_pcm_activity(&log, stdin, Duration::from_secs(10))?;
+    std::fs::write(image, &bytes).with_context(|| format!("failed to write {}", image.display()))
+}
 
-        send_serial_command(stdin, "doom audio off\n")?;
-        wait_for_log(
-            &log,
-            "doom: audio mode set to off",
-            Duration::from_secs(8),
-            "doom audio off",
-        )?;
+fn derived_guid(seed: &str, label: &str) -> [u8; 16] {
+    let high = fnv1a64(fnv1a64(FNV_OFFSET_BASIS, seed.as_bytes()), label.as_bytes());
+    let low = fnv1a64(high, b"arrost-gpt");
+    let mut guid = [0u8; 16];
+    guid[..8].copy_from_slice(&high.to_le_bytes());
+    guid[8..].copy_from_slice(&low.to_le_bytes());
+    // RFC 4122 version 4 / variant 1 bits, in the GPT mixed-endian layout.
+    guid[7] = (guid[7] & 0x0f) | 0x40;
+    guid[8] = (guid[8] & 0x3f) | 0x80;
+    guid
+}
 
-        send_serial_command(stdin, "doom audio on\n")?;
-        wait_for_log(
-            &log,
-            "doom: audio mode set to ",
-            Duration::from_secs(8),
-            "doom audio on",
-        )?;
+fn read_u32_le(bytes: &[u8], offset: usize) -> u32 {
+    let mut raw = [0u8; 4];
+    raw.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(raw)
+}
 
-        if !long_run {
-            send_serial_command(stdin, "doom audio test\n")?;
-            wait_for_log(
-                &log,
-                "doom: audio test tone queued",
-                Duration::from_secs(8),
-                "doom audio test",
-            )?;
-        }
+fn read_u64_le(bytes: &[u8], offset: usize) -> u64 {
+    let mut raw = [0u8; 8];
+    raw.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(raw)
+}
 
-        send_serial_command(stdin, "doom capture on\n")?;
-        wait_for_log(
-            &log,
-            "doom: capture enabled (press ESC to exit)",
-            Duration::from_secs(8),
-            "doom capture on",
-        )?;
+fn fnv1a64(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
 
-        send_serial_command(stdin, "ww  ww")?;
-        thread::sleep(Duration::from_millis(220));
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
 
-        send_serial_command(stdin, "\u{1b}")?;
-        wait_for_log(
-            &log,
-            "doom: capture disabled",
-            Duration::from_secs(8),
-            "doom capture escape",
-        )?;
+/// FIPS 180-4 SHA-256, used only to verify downloads.
+fn sha256_hex(bytes: &[u8]) -> String {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((bytes.len() as u64) * 8).to_be_bytes());
 
-        send_serial_command(stdin, "doom status\n")?;
-        wait_for_log(
-            &log,
-            "doom: app=doom engine=",
-            Duration::from_secs(8),
-            "doom status post-capture",
-        )?;
-        let capture_snapshot = snapshot_log(&log);
-        let Some(capture_status_line) =
-            last_matching_line(&capture_snapshot, "doom: app=doom engine=")
-        else {
-            bail!("missing doom status line after serial capture input");
-        };
-        if !capture_status_line.contains("capture=false") {
-            bail!("doom capture did not return to false after ESC");
+    let (blocks, _) = message.as_chunks::<64>();
+    for block in blocks {
+        let mut w = [0u32; 64];
+        let (words, _) = block.as_chunks::<4>();
+        for (index, word) in words.iter().enumerate() {
+            w[index] = u32::from_be_bytes(*word);
         }
-        let Some(capture_dg_key) = parse_metric_value(capture_status_line, "dg_key=") else {
-            bail!("missing dg_key metric after serial capture input");
-        };
-        if capture_dg_key == 0 {
-            bail!(
-                "serial capture input did not produce bridge key events (dg_key={capture_dg_key})"
-            );
+        for index in 16..64 {
+            let s0 = w[index - 15].rotate_right(7)
+                ^ w[index - 15].rotate_right(18)
+                ^ (w[index - 15] >> 3);
+            let s1 = w[index - 2].rotate_right(17)
+                ^ w[index - 2].rotate_right(19)
+                ^ (w[index - 2] >> 10);
+            w[index] = w[index - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[index - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for index in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(K[index])
+                .wrapping_add(w[index]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
         }
+        for (slot, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *slot = slot.wrapping_add(value);
+        }
+    }
+    state.iter().map(|word| format!("{word:08x}")).collect()
+}
 
-        send_serial_command(stdin, "doom mouse y on\n")?;
-        wait_for_log(
-            &log,
-            "doom: mouse y mapping enabled",
-            Duration::from_secs(8),
-            "doom mouse y on",
-        )?;
+fn run_qemu() -> Result<()> {
+    // Si appoggia a scripts/qemu.sh per semplicità
+    let status = Command::new("bash")
+        .args(["scripts/qemu.sh"])
+        .status()
+        .context("qemu run failed")?;
+    if !status.success() {
+        bail!("qemu exited with error");
+    }
+    Ok(())
+}
 
-        send_serial_command(stdin, "doom mouse turn 5\n")?;
-        wait_for_log(
-            &log,
-            "doom: mouse turn threshold set to 5",
-            Duration::from_secs(8),
-            "doom mouse turn",
-        )?;
+fn size_report() -> Result<()> {
+    let kernel_binary = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/{KERNEL_PACKAGE}"));
+    if !kernel_binary.exists() {
+        bail!(
+            "missing kernel binary at {}; run `cargo xtask build` first",
+            kernel_binary.display()
+        );
+    }
+    let budget_kib = match std::env::var(SIZE_BUDGET_ENV) {
+        Ok(value) => value
+            .trim()
+            .parse::<u64>()
+            .with_context(|| format!("{SIZE_BUDGET_ENV} must be a KiB count, got `{value}`"))?,
+        Err(_) => DEFAULT_KERNEL_SIZE_BUDGET_KIB,
+    };
 
-        send_serial_command(stdin, "doom mouse move 7\n")?;
-        wait_for_log(
-            &log,
-            "doom: mouse move threshold set to 7",
-            Duration::from_secs(8),
-            "doom mouse move",
-        )?;
+    let kernel = print_binary_size(&kernel_binary)?;
+    for package in [USER_INIT_PACKAGE, USER_DOOM_PACKAGE] {
+        let binary = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/{package}"));
+        if binary.exists() {
+            print_binary_size(&binary)?;
+        } else {
+            println!(
+                "size: {package} skipped (no binary at {})",
+                binary.display()
+            );
+        }
+    }
 
-        send_serial_command(stdin, "doom status\n")?;
-        wait_for_log(
-            &log,
-            "doom: app=doom engine=",
-            Duration::from_secs(8),
-            "doom status line",
-        )?;
-        wait_for_log(
-            &log,
-            "mouse_cfg=(turn:5 move:7 y:true)",
-            Duration::from_secs(8),
-            "doom mouse config status",
-        )?;
+    let budget = budget_kib * 1024;
+    println!(
+        "size: kernel image={} budget={} ({SIZE_BUDGET_ENV}={budget_kib})",
+        kernel.total(),
+        budget
+    );
+    if kernel.total() > budget {
+        bail!(
+            "kernel image is {} bytes, over the {} byte budget by {}",
+            kernel.total(),
+            budget,
+            kernel.total() - budget
+        );
+    }
+    Ok(())
+}
 
-        send_serial_command(stdin, "doom key left\n")?;
-        wait_for_log(
-            &log,
-            "doom: injected key 0x61",
-            Duration::from_secs(8),
-            "doom key injection",
-        )?;
+/// Prints section totals, a per-module breakdown and the largest symbols of one ELF.
+fn print_binary_size(binary: &PathBuf) -> Result<SectionSizes> {
+    let output = Command::new("nm")
+        .args(["--size-sort", "--print-size", "--demangle"])
+        .arg(binary)
+        .output()
+        .context("failed to run nm (binutils is required for `cargo xtask size`)")?;
+    if !output.status.success() {
+        bail!(
+            "nm failed for {}: {}",
+            binary.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let symbols = parse_nm_sizes(&String::from_utf8_lossy(&output.stdout));
+    let file_size = std::fs::metadata(binary)
+        .map(|meta| meta.len())
+        .unwrap_or(0);
 
-        send_serial_command(stdin, "doom keyup left\n")?;
-        wait_for_log(
-            &log,
-            "doom: injected keyup 0x61",
-            Duration::from_secs(8),
-            "doom keyup injection",
-        )?;
+    let mut totals = SectionSizes::default();
+    let mut modules: Vec<(String, SectionSizes)> = Vec::new();
+    for symbol in &symbols {
+        totals.add(symbol.kind, symbol.size);
+        let module = symbol_module(&symbol.name);
+        match modules.iter_mut().find(|(name, _)| *name == module) {
+            Some((_, sizes)) => sizes.add(symbol.kind, symbol.size),
+            None => {
+                let mut sizes = SectionSizes::default();
+                sizes.add(symbol.kind, symbol.size);
+                modules.push((module, sizes));
+            }
+        }
+    }
+    modules.sort_by_key(|(_, sizes)| std::cmp::Reverse(sizes.total()));
 
-        send_serial_command(stdin, "doom key fire\n")?;
+    println!(
+        "size: {} file={} code={} rodata={} data={} bss={} total={}",
+        binary.display(),
+        file_size,
+        totals.code,
+        totals.rodata,
+        totals.data,
+        totals.bss,
+        totals.total()
+    );
+    println!(
+        "size:   {:<40} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "module", "code", "rodata", "data", "bss", "total"
+    );
+    for (module, sizes) in modules.iter().take(SIZE_REPORT_MODULES) {
+        println!(
+            "size:   {:<40} {:>9} {:>9} {:>9} {:>9} {:>9}",
+            module,
+            sizes.code,
+            sizes.rodata,
+            sizes.data,
+            sizes.bss,
+            sizes.total()
+        );
+    }
+    if modules.len() > SIZE_REPORT_MODULES {
+        println!(
+            "size:   ... {} smaller modules",
+            modules.len() - SIZE_REPORT_MODULES
+        );
+    }
+    println!("size:   largest symbols:");
+    for symbol in symbols.iter().rev().take(SIZE_REPORT_SYMBOLS) {
+        println!("size:   {:>9} {} {}", symbol.size, symbol.kind, symbol.name);
+    }
+    Ok(totals)
+}
+
+/// Parses `nm --size-sort --print-size` lines (`addr size kind name`), keeping ascending order.
+fn parse_nm_sizes(listing: &str) -> Vec<SymbolSize> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ' ');
+            let _addr = fields.next()?;
+            let size = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let kind = fields.next()?.chars().next()?;
+            let name = fields.next()?.to_string();
+            Some(SymbolSize { name, kind, size })
+        })
+        .collect()
+}
+
+/// Groups a demangled symbol by crate, plus the top-level module for this workspace's crates
+/// (`arrost_kernel::net`, `arrost_kernel::doom_bridge`, ...).
+fn symbol_module(name: &str) -> String {
+    // Peel `<&mut [T] as Trait>` / `<*const T>` wrappers down to the self type's path.
+    let mut path = name;
+    loop {
+        let peeled = path.trim_start_matches(['<', '&', '[', '*']);
+        let peeled = ["mut ", "const ", "dyn "]
+            .iter()
+            .find_map(|prefix| peeled.strip_prefix(prefix))
+            .unwrap_or(peeled);
+        if peeled == path {
+            break;
+        }
+        path = peeled;
+    }
+    let path = path
+        .split([' ', '<', '>', '(', ';', ']'])
+        .next()
+        .unwrap_or(path);
+    let mut segments = path.split("::").filter(|segment| !segment.is_empty());
+    let Some(krate) = segments.next() else {
+        return String::from("?");
+    };
+    // Inherent impls on primitives (`<u8>::fmt`) live in core.
+    if PRIMITIVE_TYPES.contains(&krate) {
+        return String::from("core");
+    }
+    match segments.next() {
+        Some(module) if krate.starts_with("arrost") => format!("{krate}::{module}"),
+        _ => krate.to_string(),
+    }
+}
+
+fn smoke_qemu_command(smoke_name: &str, strict_virtio: bool) -> Command {
+    let mut qemu_cmd = Command::new("bash");
+    qemu_cmd
+        .args(["scripts/qemu.sh"])
+        .env("QEMU_DISPLAY", "none");
+    if strict_virtio {
+        qemu_cmd.env("QEMU_VIRTIO_SND", "on");
+        qemu_cmd.env("QEMU_PCSPK", "off");
+    }
+    if std::env::var_os("QEMU_AUDIO").is_none() {
+        qemu_cmd.env("QEMU_AUDIO", "wav");
+    }
+    if std::env::var_os("QEMU_AUDIO_WAV_PATH").is_none() {
+        qemu_cmd.env(
+            "QEMU_AUDIO_WAV_PATH",
+            format!("target/{KERNEL_TARGET}/debug/{smoke_name}.wav"),
+        );
+    }
+    qemu_cmd
+}
+
+fn smoke_snapshot_flag(args: impl Iterator<Item = String>) -> Result<bool> {
+    let mut snapshot = false;
+    for arg in args {
+        match arg.as_str() {
+            "--snapshot" => snapshot = true,
+            other => bail!("unknown smoke option `{other}` (expected --snapshot)"),
+        }
+    }
+    Ok(snapshot)
+}
+
+fn smoke_doom(snapshot: bool) -> Result<()> {
+    smoke_doom_impl(false, false, false, snapshot)
+}
+
+fn smoke_doom_long(snapshot: bool) -> Result<()> {
+    smoke_doom_impl(true, false, false, snapshot)
+}
+
+fn smoke_doom_virtio(snapshot: bool) -> Result<()> {
+    smoke_doom_impl(true, false, true, snapshot)
+}
+
+fn smoke_doom_fallback(snapshot: bool) -> Result<()> {
+    let reproducible = env_truthy(REPRODUCIBLE_ENV);
+    build_impl(true, reproducible, false, None)?;
+    let smoke_result = smoke_doom_impl(false, true, false, snapshot);
+    let restore_result = build_impl(false, reproducible, false, None);
+    match smoke_result {
+        Ok(()) => {
+            restore_result?;
+            Ok(())
+        }
+        Err(smoke_err) => {
+            if let Err(restore_err) = restore_result {
+                return Err(smoke_err.context(format!(
+                    "fallback smoke failed and restoring normal DoomGeneric build failed: {restore_err:#}"
+                )));
+            }
+            Err(smoke_err)
+        }
+    }
+}
+
+fn smoke_doom_impl(
+    long_run: bool,
+    force_fallback: bool,
+    strict_virtio: bool,
+    snapshot: bool,
+) -> Result<()> {
+    let smoke_name = if strict_virtio {
+        "smoke-doom-virtio"
+    } else if force_fallback {
+        "smoke-doom-fallback"
+    } else if long_run {
+        "smoke-doom-long"
+    } else {
+        "smoke-doom"
+    };
+
+    let kernel_image = PathBuf::from(format!(
+        "target/{KERNEL_TARGET}/debug/bootimage-{KERNEL_PACKAGE}.bin"
+    ));
+    if !kernel_image.exists() {
+        bail!(
+            "missing kernel image at {}; run `cargo xtask build` first",
+            kernel_image.display()
+        );
+    }
+
+    let data_image = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/m6-disk.img"));
+    if !data_image.exists() {
+        bail!(
+            "missing storage image at {}; run `cargo xtask build` first",
+            data_image.display()
+        );
+    }
+
+    let restore = if snapshot {
+        let snapshot = SmokeSnapshot::new(smoke_name);
+        let boot_log = snapshot.prepare(smoke_name, strict_virtio, &kernel_image, &data_image)?;
+        Some((snapshot, boot_log))
+    } else {
+        None
+    };
+
+    let mut qemu_cmd = smoke_qemu_command(smoke_name, strict_virtio);
+    if let Some((snapshot, _)) = &restore {
+        snapshot.apply(&mut qemu_cmd);
+        qemu_cmd.env("QEMU_LOADVM", SMOKE_SNAPSHOT_TAG);
+    }
+    let mut child = qemu_cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start qemu run for {smoke_name}"))?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to capture qemu stdout")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("failed to capture qemu stderr")?;
+
+    // A restored guest never prints its boot again; seed the log with the one recorded
+    // alongside the snapshot so the boot checks below still see it.
+    let boot_log = restore
+        .as_ref()
+        .map(|(_, boot_log)| boot_log.clone())
+        .unwrap_or_default();
+    let log = Arc::new(Mutex::new(boot_log));
+    let stdout_reader = spawn_log_reader(stdout, Arc::clone(&log));
+    let stderr_reader = spawn_log_reader(stderr, Arc::clone(&log));
+
+    let smoke_result = (|| -> Result<()> {
+        let stdin = child
+            .stdin
+            .as_mut()
+            .context("failed to capture qemu stdin")?;
+        if let Some((snapshot, _)) = &restore {
+            let restored = snapshot_log(&log).len();
+            send_serial_command(stdin, "\n")?;
+            if let Err(error) = wait_for_log_from(
+                &log,
+                restored,
+                "arrost> ",
+                Duration::from_secs(20),
+                "restored shell prompt",
+            ) {
+                snapshot.invalidate();
+                return Err(error.context("snapshot restore failed; it is re-recorded next run"));
+            }
+        }
+        wait_for_log(&log, "arrost> ", Duration::from_secs(40), "shell prompt")?;
+        let startup_snapshot = snapshot_log(&log);
+        check_boot_sysinfo(&startup_snapshot, smoke_name)?;
+        let software_accel_mode = startup_snapshot.contains("Using QEMU acceleration: tcg")
+            || startup_snapshot.contains("Using QEMU acceleration: none");
+
+        send_serial_command(stdin, "versionx\u{7f}\n")?;
         wait_for_log(
             &log,
-            "doom: injected key 0x20",
+            "version: ",
             Duration::from_secs(8),
-            "doom fire injection",
+            "serial backspace",
         )?;
-        thread::sleep(Duration::from_millis(220));
 
-        send_serial_command(stdin, "doom keyup fire\n")?;
+        let ready = snapshot_log(&log).contains("DoomGeneric: ready=true");
+        if force_fallback && ready {
+            bail!("expected DoomGeneric ready=false for fallback smoke");
+        }
+        if !force_fallback && !ready {
+            bail!(
+                "doomgeneric ready=false in smoke-doom; run `cargo xtask build` (or wait for fallback restore) and retry"
+            );
+        }
+        if snapshot_log(&log).contains("_verify=mismatch") {
+            bail!("DoomGeneric boot line reports an artifact manifest mismatch");
+        }
+
+        send_serial_command(stdin, "doom play\n")?;
+        let play_marker = if ready {
+            "doom: play mode started (doomgeneric)"
+        } else {
+            "doom: doomgeneric not ready; starting fallback runtime"
+        };
         wait_for_log(
             &log,
-            "doom: injected keyup 0x20",
-            Duration::from_secs(8),
-            "doom fire keyup injection",
+            play_marker,
+            Duration::from_secs(12),
+            "doom play confirmation",
         )?;
 
-        send_serial_command(stdin, "doom key enter\n")?;
+        if force_fallback {
+            wait_for_log(
+                &log,
+                "doom: capture unavailable (fallback mode)",
+                Duration::from_secs(8),
+                "doom fallback capture notice",
+            )?;
+
+            send_serial_command(stdin, "doom status\n")?;
+            wait_for_log(
+                &log,
+                "doom: app=doom engine=fallback-sim",
+                Duration::from_secs(8),
+                "doom fallback status line",
+            )?;
+            let fallback_snapshot = snapshot_log(&log);
+            let Some(fallback_line) =
+                last_matching_line(&fallback_snapshot, "doom: app=doom engine=fallback-sim")
+            else {
+                bail!("missing fallback status line");
+            };
+            if !(fallback_line.contains("doomgeneric=false")
+                || fallback_line.contains("bridge=stub"))
+            {
+                bail!("fallback status mismatch: expected bridge=stub or doomgeneric=false");
+            }
+
+            send_serial_command(stdin, "doom key left\n")?;
+            wait_for_log(
+                &log,
+                "doom: injected key 0x61",
+                Duration::from_secs(8),
+                "fallback key injection",
+            )?;
+
+            send_serial_command(stdin, "doom status\n")?;
+            wait_for_log(
+                &log,
+                "doom: app=doom engine=fallback-sim",
+                Duration::from_secs(8),
+                "fallback status post-input",
+            )?;
+
+            send_serial_command(stdin, "ui\n")?;
+            wait_for_log(
+                &log,
+                "ui: backend=uefi-gop ready=true",
+                Duration::from_secs(8),
+                "ui diagnostics line",
+            )?;
+
+            send_serial_command(stdin, "doom stop\n")?;
+            wait_for_log(
+                &log,
+                "doom: runtime stopped",
+                Duration::from_secs(8),
+                "doom stop confirmation",
+            )?;
+            return Ok(());
+        }
+
+        if ready {
+            wait_for_log(
+                &log,
+                "doom: capture enabled (press ESC to exit)",
+                Duration::from_secs(8),
+                "doom auto-capture enabled",
+            )?;
+            send_serial_command(stdin, "\u{1b}")?;
+            wait_for_log(
+                &log,
+                "doom: capture disabled",
+                Duration::from_secs(8),
+                "doom auto-capture escape",
+            )?;
+        }
+
+        wait_for_music_pcm_activity(&log, stdin, Duration::from_secs(10))?;
+
+        send_serial_command(stdin, "doom audio off\n")?;
         wait_for_log(
             &log,
-            "doom: injected key 0x0a",
+            "doom: audio mode set to off",
             Duration::from_secs(8),
-            "doom enter injection",
+            "doom audio off",
         )?;
 
-        send_serial_command(stdin, "doom keyup enter\n")?;
+        send_serial_command(stdin, "doom audio on\n")?;
         wait_for_log(
             &log,
-            "doom: injected keyup 0x0a",
+            "doom: audio mode set to ",
             Duration::from_secs(8),
-            "doom enter keyup injection",
+            "doom audio on",
         )?;
 
-        send_serial_command(stdin, "doom status\n")?;
+        if !long_run {
+            send_serial_command(stdin, "doom audio test\n")?;
+            wait_for_log(
+                &log,
+                "doom: audio test tone queued",
+                Duration::from_secs(8),
+                "doom audio test",
+            )?;
+        }
+
+        send_serial_command(stdin, "doom capture on\n")?;
+        wait_for_log(
+            &log,
+            "doom: capture enabled (press ESC to exit)",
+            Duration::from_secs(8),
+            "doom capture on",
+        )?;
+
+        send_serial_command(stdin, "ww  ww")?;
+        thread::sleep(Duration::from_millis(220));
+
+        send_serial_command(stdin, "\u{1b}")?;
+        wait_for_log(
+            &log,
+            "doom: capture disabled",
+            Duration::from_secs(8),
+            "doom capture escape",
+        )?;
+
+        send_serial_command(stdin, "doom status\n")?;
+        wait_for_log(
+            &log,
+            "doom: app=doom engine=",
+            Duration::from_secs(8),
+            "doom status post-capture",
+        )?;
+        let capture_snapshot = snapshot_log(&log);
+        let Some(capture_status_line) =
+            last_matching_line(&capture_snapshot, "doom: app=doom engine=")
+        else {
+            bail!("missing doom status line after serial capture input");
+        };
+        if !capture_status_line.contains("capture=false") {
+            bail!("doom capture did not return to false after ESC");
+        }
+        let Some(capture_dg_key) = parse_metric_value(capture_status_line, "dg_key=") else {
+            bail!("missing dg_key metric after serial capture input");
+        };
+        if capture_dg_key == 0 {
+            bail!(
+                "serial capture input did not produce bridge key events (dg_key={capture_dg_key})"
+            );
+        }
+
+        send_serial_command(stdin, "doom mouse y on\n")?;
+        wait_for_log(
+            &log,
+            "doom: mouse y mapping enabled",
+            Duration::from_secs(8),
+            "doom mouse y on",
+        )?;
+
+        send_serial_command(stdin, "doom mouse turn 5\n")?;
+        wait_for_log(
+            &log,
+            "doom: mouse turn threshold set to 5",
+            Duration::from_secs(8),
+            "doom mouse turn",
+        )?;
+
+        send_serial_command(stdin, "doom mouse move 7\n")?;
+        wait_for_log(
+            &log,
+            "doom: mouse move threshold set to 7",
+            Duration::from_secs(8),
+            "doom mouse move",
+        )?;
+
+        send_serial_command(stdin, "doom status\n")?;
+        wait_for_log(
+            &log,
+            "doom: app=doom engine=",
+            Duration::from_secs(8),
+            "doom status line",
+        )?;
+        wait_for_log(
+            &log,
+            "mouse_cfg=(turn:5 move:7 y:true)",
+            Duration::from_secs(8),
+            "doom mouse config status",
+        )?;
+
+        send_serial_command(stdin, "doom key left\n")?;
+        wait_for_log(
+            &log,
+            "doom: injected key 0x61",
+            Duration::from_secs(8),
+            "doom key injection",
+        )?;
+
+        send_serial_command(stdin, "doom keyup left\n")?;
+        wait_for_log(
+            &log,
+            "doom: injected keyup 0x61",
+            Duration::from_secs(8),
+            "doom keyup injection",
+        )?;
+
+        send_serial_command(stdin, "doom key fire\n")?;
+        wait_for_log(
+            &log,
+            "doom: injected key 0x20",
+            Duration::from_secs(8),
+            "doom fire injection",
+        )?;
+        thread::sleep(Duration::from_millis(220));
+
+        send_serial_command(stdin, "doom keyup fire\n")?;
+        wait_for_log(
+            &log,
+            "doom: injected keyup 0x20",
+            Duration::from_secs(8),
+            "doom fire keyup injection",
+        )?;
+
+        send_serial_command(stdin, "doom key enter\n")?;
+        wait_for_log(
+            &log,
+            "doom: injected key 0x0a",
+            Duration::from_secs(8),
+            "doom enter injection",
+        )?;
+
+        send_serial_command(stdin, "doom keyup enter\n")?;
+        wait_for_log(
+            &log,
+            "doom: injected keyup 0x0a",
+            Duration::from_secs(8),
+            "doom enter keyup injection",
+        )?;
+
+        send_serial_command(stdin, "doom status\n")?;
+        wait_for_log(
+            &log,
+            "last_key=0x0a",
+            Duration::from_secs(8),
+            "doom status post-input",
+        )?;
+        let status_snapshot = snapshot_log(&log);
+        let Some(status_line) = last_matching_line(&status_snapshot, "last_key=0x0a") else {
+            bail!("missing doom status line after input injections");
+        };
+        let Some(inputs) = parse_metric_value(status_line, "inputs=") else {
+            bail!("missing inputs metric in doom status line");
+        };
+        if inputs < 3 {
+            bail!("unexpected low doom input count after injections (inputs={inputs})");
+        }
+        let Some(dg_frames) = parse_metric_value(status_line, "dg_frames=") else {
+            bail!("missing dg_frames metric in doom status line");
+        };
+        if dg_frames < 2 {
+            bail!("unexpected low doom frame count after play start (dg_frames={dg_frames})");
+        }
+        let Some(dg_key) = parse_metric_value(status_line, "dg_key=") else {
+            bail!("missing dg_key metric in doom status line");
+        };
+        if dg_key == 0 {
+            bail!("doom bridge did not register key queue events (dg_key={dg_key})");
+        }
+        let Some(dg_nonzero) = parse_metric_value(status_line, "dg_nonzero=") else {
+            bail!("missing dg_nonzero metric in doom status line");
+        };
+        if dg_nonzero == 0 {
+            bail!("doom frame appears fully black after play start (dg_nonzero={dg_nonzero})");
+        }
+        let Some(dg_audio) = parse_metric_value(status_line, "dg_audio=") else {
+            bail!("missing dg_audio metric in doom status line");
+        };
+        if dg_audio == 0 {
+            bail!("doom audio backend stub did not receive callbacks (dg_audio={dg_audio})");
+        }
+        let Some(dg_audio_samples) = parse_metric_value(status_line, "dg_audio_samples=") else {
+            bail!("missing dg_audio_samples metric in doom status line");
+        };
+        let Some(pcm_samples) = parse_metric_value(status_line, "pcm_samples=") else {
+            bail!("missing pcm_samples metric in doom status line");
+        };
+        if pcm_samples == 0 {
+            bail!("pcm audio path inactive after play start (pcm_samples=0)");
+        }
+        let virtio_backend = status_line.contains("pcm_backend=virtio-snd");
+        if strict_virtio && !virtio_backend {
+            bail!("strict virtio smoke expected pcm_backend=virtio-snd");
+        }
+        if virtio_backend {
+            let Some(pcm_tx) = parse_metric_value(status_line, "pcm_tx=") else {
+                bail!("missing pcm_tx metric in virtio status line");
+            };
+            let Some(pcm_done) = parse_metric_value(status_line, "pcm_done=") else {
+                bail!("missing pcm_done metric in virtio status line");
+            };
+            if pcm_tx == 0 || pcm_done == 0 {
+                bail!("virtio-sound metrics inactive (pcm_tx={pcm_tx} pcm_done={pcm_done})");
+            }
+        } else {
+            let Some(pcm_sw) = parse_metric_value(status_line, "pcm_sw=") else {
+                bail!("missing pcm_sw metric in doom status line");
+            };
+            let Some(pcm_min) = parse_metric_value(status_line, "pcm_min=") else {
+                bail!("missing pcm_min metric in doom status line");
+            };
+            let Some(pcm_max) = parse_metric_value(status_line, "pcm_max=") else {
+                bail!("missing pcm_max metric in doom status line");
+            };
+            if pcm_min == 0 || pcm_max == 0 || pcm_max < pcm_min {
+                bail!("invalid pcm frequency window (pcm_min={pcm_min} pcm_max={pcm_max})");
+            }
+            if pcm_samples >= 2048 && pcm_sw == 0 && pcm_max == pcm_min {
+                bail!(
+                    "pcm tone appears fixed after play start (pcm_sw={pcm_sw} pcm_min={pcm_min} pcm_max={pcm_max})"
+                );
+            }
+        }
+        // `doom audio test` injects explicit diagnostic PCM outside the Doom mixer path.
+        // Skip it for long-run smokes and only account for it in short runs.
+        let audio_test_pcm_budget = if long_run { 0u64 } else { 4096u64 };
+        if dg_audio_samples > 0
+            && pcm_samples
+                > dg_audio_samples
+                    .saturating_mul(4)
+                    .saturating_add(audio_test_pcm_budget)
+        {
+            bail!(
+                "unexpected pcm sample growth (pcm_samples={pcm_samples} dg_audio_samples={dg_audio_samples})"
+            );
+        }
+        let dg_frames_before_progress = dg_frames;
+
+        send_serial_command(stdin, "doom key right\n")?;
+        wait_for_log(
+            &log,
+            "doom: injected key 0x64",
+            Duration::from_secs(8),
+            "doom right injection",
+        )?;
+        send_serial_command(stdin, "doom keyup right\n")?;
+        wait_for_log(
+            &log,
+            "doom: injected keyup 0x64",
+            Duration::from_secs(8),
+            "doom right keyup injection",
+        )?;
+
+        send_serial_command(stdin, "doom status\n")?;
+        wait_for_log(
+            &log,
+            "last_key=0x64",
+            Duration::from_secs(8),
+            "doom status frame progression",
+        )?;
+        let progression_snapshot = snapshot_log(&log);
+        let Some(progression_line) = last_matching_line(&progression_snapshot, "last_key=0x64")
+        else {
+            bail!("missing doom status line for frame progression check");
+        };
+        let Some(dg_frames_after_progress) = parse_metric_value(progression_line, "dg_frames=")
+        else {
+            bail!("missing dg_frames metric in progression status line");
+        };
+        if dg_frames_after_progress <= dg_frames_before_progress {
+            bail!(
+                "doom frame counter did not progress (before={dg_frames_before_progress} after={dg_frames_after_progress})"
+            );
+        }
+        let Some(dg_nonzero_after_progress) = parse_metric_value(progression_line, "dg_nonzero=")
+        else {
+            bail!("missing dg_nonzero metric in progression status line");
+        };
+        if dg_nonzero_after_progress == 0 {
+            bail!("doom progression frame is fully black (dg_nonzero={dg_nonzero_after_progress})");
+        }
+        let Some(dg_drop_before_long) = parse_metric_value(progression_line, "dg_drop=") else {
+            bail!("missing dg_drop metric in progression status line");
+        };
+        let Some(dg_audio_before_long) = parse_metric_value(progression_line, "dg_audio=") else {
+            bail!("missing dg_audio metric in progression status line");
+        };
+        let pcm_drop_frames_before_long = if virtio_backend {
+            let Some(value) = parse_metric_value(progression_line, "pcm_drop_frames=") else {
+                bail!("missing pcm_drop_frames metric in progression status line");
+            };
+            Some(value)
+        } else {
+            None
+        };
+        let pcm_done_before_long = if virtio_backend {
+            let Some(value) = parse_metric_value(progression_line, "pcm_done=") else {
+                bail!("missing pcm_done metric in progression status line");
+            };
+            Some(value)
+        } else {
+            None
+        };
+
+        if long_run {
+            let long_wait = Duration::from_secs(24);
+            let min_frame_progress = if software_accel_mode { 90u64 } else { 180u64 };
+            let max_drop_delta = 4u64;
+
+            thread::sleep(long_wait);
+            send_serial_command(stdin, "doom status\n")?;
+            let long_line = wait_for_status_with_frame_progress(
+                &log,
+                dg_frames_after_progress,
+                Duration::from_secs(8),
+                "doom status long-run",
+            )?;
+            let Some(dg_frames_long) = parse_metric_value(&long_line, "dg_frames=") else {
+                bail!("missing dg_frames metric in long-run status line");
+            };
+            if dg_frames_long <= dg_frames_after_progress {
+                bail!(
+                    "doom frame counter did not progress during long-run (before={dg_frames_after_progress} after={dg_frames_long})"
+                );
+            }
+            let frame_delta = dg_frames_long - dg_frames_after_progress;
+            if frame_delta < min_frame_progress {
+                bail!(
+                    "doom frame progression too low during long-run (delta={frame_delta}, waited={}s, min={min_frame_progress})",
+                    long_wait.as_secs()
+                );
+            }
+
+            let Some(dg_drop_long) = parse_metric_value(&long_line, "dg_drop=") else {
+                bail!("missing dg_drop metric in long-run status line");
+            };
+            let drop_delta = dg_drop_long.saturating_sub(dg_drop_before_long);
+            if drop_delta > max_drop_delta {
+                bail!(
+                    "doom key queue drops grew too much during long-run (delta={drop_delta}, max={max_drop_delta})"
+                );
+            }
+
+            let Some(dg_nonzero_long) = parse_metric_value(&long_line, "dg_nonzero=") else {
+                bail!("missing dg_nonzero metric in long-run status line");
+            };
+            if dg_nonzero_long == 0 {
+                bail!("doom long-run frame is fully black (dg_nonzero={dg_nonzero_long})");
+            }
+
+            let Some(dg_audio_long) = parse_metric_value(&long_line, "dg_audio=") else {
+                bail!("missing dg_audio metric in long-run status line");
+            };
+            if dg_audio_long <= dg_audio_before_long {
+                bail!(
+                    "doom audio hook did not progress during long-run (before={dg_audio_before_long} after={dg_audio_long})"
+                );
+            }
+
+            if virtio_backend {
+                let Some(pcm_drop_frames_long) = parse_metric_value(&long_line, "pcm_drop_frames=")
+                else {
+                    bail!("missing pcm_drop_frames metric in long-run status line");
+                };
+                let drop_frames_delta =
+                    pcm_drop_frames_long.saturating_sub(pcm_drop_frames_before_long.unwrap_or(0));
+                let max_drop_frames_delta = if strict_virtio { 512u64 } else { 1536u64 };
+                if drop_frames_delta > max_drop_frames_delta {
+                    bail!(
+                        "virtio pcm_drop_frames grew too much during long-run (delta={drop_frames_delta}, max={max_drop_frames_delta})"
+                    );
+                }
+
+                let Some(pcm_done_long) = parse_metric_value(&long_line, "pcm_done=") else {
+                    bail!("missing pcm_done metric in long-run status line");
+                };
+                let done_delta = pcm_done_long.saturating_sub(pcm_done_before_long.unwrap_or(0));
+                if done_delta == 0 {
+                    bail!("virtio completion counter did not progress during long-run (pcm_done)");
+                }
+            }
+        }
+
+        send_serial_command(stdin, "ui\n")?;
+        wait_for_log(
+            &log,
+            "ui: backend=uefi-gop ready=true",
+            Duration::from_secs(8),
+            "ui diagnostics line",
+        )?;
+
+        send_serial_command(stdin, "asserts\n")?;
+        wait_for_log(
+            &log,
+            "asserts: soft_asserts=",
+            Duration::from_secs(8),
+            "soft assert summary",
+        )?;
+        let log_snapshot = snapshot_log(&log);
+        if let Some(asserts_line) = last_matching_line(&log_snapshot, "asserts: soft_asserts=")
+            && let Some(soft_asserts) = parse_metric_value(asserts_line, "soft_asserts=")
+            && soft_asserts > 0
+            && env_truthy(SMOKE_STRICT_ASSERTS_ENV)
+        {
+            bail!("soft asserts fired during smoke run ({asserts_line})");
+        }
+
+        send_serial_command(stdin, "doom stop\n")?;
+        wait_for_log(
+            &log,
+            "doom: runtime stopped",
+            Duration::from_secs(8),
+            "doom stop confirmation",
+        )?;
+
+        Ok(())
+    })();
+
+    if child
+        .try_wait()
+        .context("failed to query qemu process status")?
+        .is_none()
+    {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+    let _ = stdout_reader.join();
+    let _ = stderr_reader.join();
+
+    let log_snapshot = snapshot_log(&log);
+    if let Err(error) = smoke_result {
+        eprintln!("{smoke_name} failed: {error}");
+        eprintln!("----- serial tail -----");
+        eprintln!("{}", log_tail(&log_snapshot, 80));
+        return Err(error);
+    }
+
+    println!("{smoke_name}: PASS");
+    if let Some(play_line) = last_matching_line(&log_snapshot, "doom: play mode started") {
+        println!("{smoke_name}: {play_line}");
+    }
+    if let Some(audio_line) = last_matching_line(&log_snapshot, "Audio: backend=") {
+        println!("{smoke_name}: {audio_line}");
+    }
+    if let Some(status_line) = last_matching_line(&log_snapshot, "doom: app=doom engine=") {
+        println!("{smoke_name}: {status_line}");
+    }
+    if let Some(key_line) = last_matching_line(&log_snapshot, "doom: injected key 0x61") {
+        println!("{smoke_name}: {key_line}");
+    }
+    if let Some(keyup_line) = last_matching_line(&log_snapshot, "doom: injected keyup 0x61") {
+        println!("{smoke_name}: {keyup_line}");
+    }
+    if let Some(fire_line) = last_matching_line(&log_snapshot, "doom: injected key 0x20") {
+        println!("{smoke_name}: {fire_line}");
+    }
+    if let Some(enter_line) = last_matching_line(&log_snapshot, "doom: injected key 0x0a") {
+        println!("{smoke_name}: {enter_line}");
+    }
+    if let Some(ui_line) = last_matching_line(&log_snapshot, "ui: backend=uefi-gop ready=true") {
+        println!("{smoke_name}: {ui_line}");
+    }
+    if let Some(asserts_line) = last_matching_line(&log_snapshot, "asserts: soft_asserts=") {
+        println!("{smoke_name}: {asserts_line}");
+    }
+    for assert_line in log_snapshot
+        .lines()
+        .filter(|line| line.contains("soft-assert: "))
+    {
+        println!("{smoke_name}: {assert_line}");
+    }
+    Ok(())
+}
+
+impl SmokeSnapshot {
+    fn new(smoke_name: &str) -> Self {
+        Self {
+            dir: PathBuf::from(format!(
+                "target/{KERNEL_TARGET}/debug/smoke-snapshot/{smoke_name}"
+            )),
+        }
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.dir.join("key")
+    }
+
+    fn boot_log_path(&self) -> PathBuf {
+        self.dir.join("boot.log")
+    }
+
+    fn monitor_path(&self) -> PathBuf {
+        self.dir.join("monitor.sock")
+    }
+
+    /// Points `scripts/qemu.sh` at this snapshot's drive copies.
+    fn apply(&self, qemu_cmd: &mut Command) {
+        qemu_cmd.env("QEMU_QCOW2_DIR", &self.dir);
+    }
+
+    /// Drops the key so the next `--snapshot` run records afresh.
+    fn invalidate(&self) {
+        let _ = std::fs::remove_file(self.key_path());
+    }
+
+    /// Returns the recorded boot log, recording the snapshot first when it is missing or
+    /// was taken from other images, another QEMU or another machine setup.
+    fn prepare(
+        &self,
+        smoke_name: &str,
+        strict_virtio: bool,
+        kernel_image: &Path,
+        data_image: &Path,
+    ) -> Result<Vec<u8>> {
+        let key = smoke_snapshot_key(smoke_name, kernel_image, data_image)?;
+        let recorded = std::fs::read_to_string(self.key_path()).unwrap_or_default();
+        if recorded.trim() == key
+            && let Ok(boot_log) = std::fs::read(self.boot_log_path())
+        {
+            println!("{smoke_name}: restoring snapshot {}", self.dir.display());
+            return Ok(boot_log);
+        }
+        println!("{smoke_name}: recording snapshot {}", self.dir.display());
+        self.record(smoke_name, strict_virtio)?;
+        std::fs::write(self.key_path(), format!("{key}\n"))
+            .with_context(|| format!("failed to write {}", self.key_path().display()))?;
+        std::fs::read(self.boot_log_path())
+            .with_context(|| format!("failed to read {}", self.boot_log_path().display()))
+    }
+
+    /// Boots fresh drive copies to the first prompt, saves the VM over the monitor and quits.
+    fn record(&self, smoke_name: &str, strict_virtio: bool) -> Result<()> {
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)
+                .with_context(|| format!("failed to clear {}", self.dir.display()))?;
+        }
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+
+        let mut qemu_cmd = smoke_qemu_command(smoke_name, strict_virtio);
+        self.apply(&mut qemu_cmd);
+        let mut child = qemu_cmd
+            .env("QEMU_MONITOR", self.monitor_path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to start qemu to record {smoke_name} snapshot"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .context("failed to capture qemu stdout")?;
+        let stderr = child
+            .stderr
+            .take()
+            .context("failed to capture qemu stderr")?;
+        let log = Arc::new(Mutex::new(Vec::<u8>::new()));
+        let readers = [
+            spawn_log_reader(stdout, Arc::clone(&log)),
+            spawn_log_reader(stderr, Arc::clone(&log)),
+        ];
+
+        let record_result = (|| -> Result<Vec<u8>> {
+            wait_for_log(&log, "arrost> ", Duration::from_secs(40), "shell prompt")?;
+            // Copy the log before pausing so nothing printed after the save leaks into it.
+            let boot_log = log.lock().map(|bytes| bytes.clone()).unwrap_or_default();
+            let mut monitor = connect_monitor(&self.monitor_path(), Duration::from_secs(5))?;
+            let reply = monitor_command(
+                &mut monitor,
+                &format!("savevm {SMOKE_SNAPSHOT_TAG}"),
+                Duration::from_secs(120),
+            )?;
+            if reply.contains("Error") || reply.contains("error") {
+                bail!("savevm failed: {}", reply.trim());
+            }
+            let _ = monitor_command(&mut monitor, "quit", Duration::from_secs(5));
+            Ok(boot_log)
+        })();
+
+        if child
+            .try_wait()
+            .context("failed to query qemu process status")?
+            .is_none()
+        {
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+        for reader in readers {
+            let _ = reader.join();
+        }
+        let _ = std::fs::remove_file(self.monitor_path());
+
+        match record_result {
+            Ok(boot_log) => std::fs::write(self.boot_log_path(), boot_log)
+                .with_context(|| format!("failed to write {}", self.boot_log_path().display())),
+            Err(error) => {
+                eprintln!("{smoke_name}: snapshot recording failed: {error}");
+                eprintln!("----- serial tail -----");
+                eprintln!("{}", log_tail(&snapshot_log(&log), 80));
+                Err(error)
+            }
+        }
+    }
+}
+
+/// Hashes everything a restored snapshot depends on: both images, the launcher script, the
+/// QEMU build and the environment that shapes the machine.
+fn smoke_snapshot_key(smoke_name: &str, kernel_image: &Path, data_image: &Path) -> Result<String> {
+    let mut hash = fnv1a64(FNV_OFFSET_BASIS, smoke_name.as_bytes());
+    for path in [kernel_image, data_image, Path::new("scripts/qemu.sh")] {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        hash = fnv1a64(hash, &bytes);
+    }
+    let version = Command::new("qemu-system-x86_64")
+        .arg("--version")
+        .output()
+        .context("failed to run qemu-system-x86_64 --version")?;
+    hash = fnv1a64(hash, &version.stdout);
+    for name in SMOKE_SNAPSHOT_ENV {
+        let value = std::env::var(name).unwrap_or_default();
+        hash = fnv1a64(hash, format!("{name}={value};").as_bytes());
+    }
+    Ok(format!("{hash:016x}"))
+}
+
+fn connect_monitor(path: &Path, timeout: Duration) -> Result<UnixStream> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match UnixStream::connect(path) {
+            Ok(stream) => {
+                stream
+                    .set_read_timeout(Some(Duration::from_millis(200)))
+                    .context("failed to set monitor read timeout")?;
+                let mut stream = stream;
+                read_monitor_reply(&mut stream, timeout)?;
+                return Ok(stream);
+            }
+            Err(error) if Instant::now() >= deadline => {
+                return Err(error)
+                    .with_context(|| format!("failed to connect to monitor {}", path.display()));
+            }
+            Err(_) => thread::sleep(Duration::from_millis(50)),
+        }
+    }
+}
+
+/// Runs one human-monitor command and returns what QEMU printed before the next prompt.
+fn monitor_command(stream: &mut UnixStream, command: &str, timeout: Duration) -> Result<String> {
+    stream
+        .write_all(format!("{command}\n").as_bytes())
+        .with_context(|| format!("failed to send monitor command `{command}`"))?;
+    read_monitor_reply(stream, timeout).with_context(|| format!("monitor command `{command}`"))
+}
+
+fn read_monitor_reply(stream: &mut UnixStream, timeout: Duration) -> Result<String> {
+    let deadline = Instant::now() + timeout;
+    let mut reply = Vec::new();
+    let mut buffer = [0u8; 512];
+    loop {
+        match stream.read(&mut buffer) {
+            // `quit` closes the socket instead of prompting again.
+            Ok(0) => return Ok(String::from_utf8_lossy(&reply).into_owned()),
+            Ok(len) => {
+                reply.extend_from_slice(&buffer[..len]);
+                if reply.ends_with(b"(qemu) ") {
+                    return Ok(String::from_utf8_lossy(&reply).into_owned());
+                }
+            }
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(error) => return Err(error).context("failed to read monitor reply"),
+        }
+        if Instant::now() >= deadline {
+            bail!("timeout waiting for the monitor prompt");
+        }
+    }
+}
+
+fn soak(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut minutes = SOAK_DEFAULT_MINUTES;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--minutes" => {
+                let Some(value) = args.next() else {
+                    bail!("--minutes needs a value");
+                };
+                minutes = value
+                    .trim()
+                    .parse::<u64>()
+                    .with_context(|| format!("--minutes must be a whole number, got `{value}`"))?;
+                if minutes == 0 {
+                    bail!("--minutes must be at least 1");
+                }
+            }
+            other => bail!("unknown soak argument `{other}`"),
+        }
+    }
+    let max_drops = match std::env::var(SOAK_MAX_DROPS_ENV) {
+        Ok(value) => value
+            .trim()
+            .parse::<u64>()
+            .with_context(|| format!("{SOAK_MAX_DROPS_ENV} must be a count, got `{value}`"))?,
+        Err(_) => SOAK_DEFAULT_MAX_DROPS,
+    };
+
+    let kernel_image = PathBuf::from(format!(
+        "target/{KERNEL_TARGET}/debug/bootimage-{KERNEL_PACKAGE}.bin"
+    ));
+    let data_image = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/m6-disk.img"));
+    for image in [&kernel_image, &data_image] {
+        if !image.exists() {
+            bail!(
+                "missing image at {}; run `cargo xtask build` first",
+                image.display()
+            );
+        }
+    }
+
+    let csv_path = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/soak.csv"));
+    let mut csv = std::fs::File::create(&csv_path)
+        .with_context(|| format!("failed to create {}", csv_path.display()))?;
+    writeln!(csv, "{SOAK_CSV_HEADER}")
+        .with_context(|| format!("failed to write {}", csv_path.display()))?;
+
+    let mut qemu_cmd = Command::new("bash");
+    qemu_cmd
+        .args(["scripts/qemu.sh"])
+        .env("QEMU_DISPLAY", "none");
+    if std::env::var_os("QEMU_AUDIO").is_none() {
+        qemu_cmd.env("QEMU_AUDIO", "none");
+    }
+    let mut child = qemu_cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to start qemu run for soak")?;
+
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to capture qemu stdout")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("failed to capture qemu stderr")?;
+
+    let log = Arc::new(Mutex::new(Vec::<u8>::new()));
+    let stdout_reader = spawn_log_reader(stdout, Arc::clone(&log));
+    let stderr_reader = spawn_log_reader(stderr, Arc::clone(&log));
+
+    let mut samples = Vec::new();
+    let soak_result = (|| -> Result<()> {
+        wait_for_log(&log, "arrost> ", Duration::from_secs(40), "shell prompt")?;
+        let stdin = child
+            .stdin
+            .as_mut()
+            .context("failed to capture qemu stdin")?;
+
+        if !snapshot_log(&log).contains("DoomGeneric: ready=true") {
+            bail!("doomgeneric ready=false; soak needs the real engine, run `cargo xtask build`");
+        }
+        send_serial_command(stdin, "doom play\n")?;
+        wait_for_log(
+            &log,
+            "doom: play mode started (doomgeneric)",
+            Duration::from_secs(12),
+            "doom play confirmation",
+        )?;
+        wait_for_log(
+            &log,
+            "doom: capture enabled (press ESC to exit)",
+            Duration::from_secs(8),
+            "doom auto-capture enabled",
+        )?;
+        send_serial_command(stdin, "\u{1b}")?;
+        wait_for_log(
+            &log,
+            "doom: capture disabled",
+            Duration::from_secs(8),
+            "doom auto-capture escape",
+        )?;
+
+        let started = Instant::now();
+        let deadline = started + Duration::from_secs(minutes * 60);
+        loop {
+            let sample = soak_sample(&log, stdin, started.elapsed().as_secs())?;
+            writeln!(csv, "{}", sample.csv_row())
+                .with_context(|| format!("failed to write {}", csv_path.display()))?;
+            println!("soak: {}", sample.csv_row());
+            samples.push(sample);
+            if Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(SOAK_SAMPLE_INTERVAL.min(deadline - Instant::now()));
+        }
+
+        send_serial_command(stdin, "doom stop\n")?;
+        wait_for_log(
+            &log,
+            "doom: runtime stopped",
+            Duration::from_secs(8),
+            "doom stop confirmation",
+        )?;
+        Ok(())
+    })()
+    .and_then(|()| soak_check_trends(&samples, max_drops));
+
+    if child
+        .try_wait()
+        .context("failed to query qemu process status")?
+        .is_none()
+    {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+    let _ = stdout_reader.join();
+    let _ = stderr_reader.join();
+
+    if let Err(error) = soak_result {
+        eprintln!("soak failed: {error}");
+        eprintln!("soak: samples in {}", csv_path.display());
+        eprintln!("----- serial tail -----");
+        eprintln!("{}", log_tail(&snapshot_log(&log), 80));
+        return Err(error);
+    }
+
+    println!(
+        "soak: PASS minutes={minutes} samples={} csv={}",
+        samples.len(),
+        csv_path.display()
+    );
+    Ok(())
+}
+
+/// Queries doom, ui and net status once and waits for a fresh copy of each line, so an
+/// older line already in the log is never mistaken for the current sample.
+fn soak_sample(
+    log: &Arc<Mutex<Vec<u8>>>,
+    stdin: &mut ChildStdin,
+    elapsed_s: u64,
+) -> Result<SoakSample> {
+    let doom = query_status_line(log, stdin, "doom status\n", "doom: app=doom engine=")?;
+    let ui = query_status_line(log, stdin, "ui\n", "ui: backend=uefi-gop ready=true")?;
+    let net = query_status_line(log, stdin, "net\n", "net: backend=")?;
+    let metric = |line: &str, key: &str| {
+        parse_metric_value(line, key)
+            .with_context(|| format!("missing `{key}` in status line: {line}"))
+    };
+    Ok(SoakSample {
+        elapsed_s,
+        dg_frames: metric(&doom, "dg_frames=")?,
+        dg_drop: metric(&doom, "dg_drop=")?,
+        dg_audio_drop: metric(&doom, "dg_audio_drop=")?,
+        pcm_samples: metric(&doom, "pcm_samples=")?,
+        pcm_drop: metric(&doom, "pcm_drop=")?,
+        pcm_drop_frames: metric(&doom, "pcm_drop_frames=")?,
+        ui_frames: metric(&ui, " frames=")?,
+        ui_dropped: metric(&ui, " dropped=")?,
+        stdout_skipped: metric(&ui, "stdout_skipped=")?,
+        net_rx: metric(&net, " rx=")?,
+        net_tx: metric(&net, " tx=")?,
+        net_drop: metric(&net, " drop=")?,
+    })
+}
+
+fn query_status_line(
+    log: &Arc<Mutex<Vec<u8>>>,
+    stdin: &mut ChildStdin,
+    command: &str,
+    marker: &str,
+) -> Result<String> {
+    let seen = snapshot_log(log)
+        .lines()
+        .filter(|line| line.contains(marker))
+        .count();
+    send_serial_command(stdin, command)?;
+    let deadline = Instant::now() + Duration::from_secs(8);
+    loop {
+        let snapshot = snapshot_log(log);
+        let mut lines = snapshot.lines().filter(|line| line.contains(marker));
+        if let Some(line) = lines.nth(seen) {
+            return Ok(line.to_string());
+        }
+        if Instant::now() >= deadline {
+            bail!(
+                "timeout waiting for `{}`: expected `{marker}`",
+                command.trim_end()
+            );
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn soak_check_trends(samples: &[SoakSample], max_drops: u64) -> Result<()> {
+    let (Some(first), Some(last)) = (samples.first(), samples.last()) else {
+        bail!("soak collected no samples");
+    };
+    for pair in samples.windows(2) {
+        let (before, after) = (&pair[0], &pair[1]);
+        if after.dg_frames <= before.dg_frames {
+            bail!(
+                "doom frame counter stalled between t={}s and t={}s (dg_frames {} -> {})",
+                before.elapsed_s,
+                after.elapsed_s,
+                before.dg_frames,
+                after.dg_frames
+            );
+        }
+    }
+    let drops = last.drops().saturating_sub(first.drops());
+    if drops > max_drops {
+        bail!(
+            "doom/audio drops grew by {drops} over the soak, above {SOAK_MAX_DROPS_ENV}={max_drops}"
+        );
+    }
+    println!(
+        "soak: dg_frames {} -> {} drops +{drops} (max {max_drops}) net_drop {} -> {}",
+        first.dg_frames, last.dg_frames, first.net_drop, last.net_drop
+    );
+    Ok(())
+}
+
+/// Boots two guests on a QEMU multicast-socket LAN (no slirp, no DHCP), gives them static
+/// addresses, pings in both directions and sends one UDP datagram, then checks that each
+/// side's ARP/ICMP/UDP receive counters moved.
+fn smoke_net_duo() -> Result<()> {
+    let kernel_image = PathBuf::from(format!(
+        "target/{KERNEL_TARGET}/debug/bootimage-{KERNEL_PACKAGE}.bin"
+    ));
+    let data_image = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/m6-disk.img"));
+    for image in [&kernel_image, &data_image] {
+        if !image.exists() {
+            bail!(
+                "missing image at {}; run `cargo xtask build` first",
+                image.display()
+            );
+        }
+    }
+    let mcast =
+        std::env::var(NET_DUO_MCAST_ENV).unwrap_or_else(|_| NET_DUO_DEFAULT_MCAST.to_string());
+    let netdev = format!("socket,mcast={mcast},localaddr=127.0.0.1");
+
+    let mut guests = Vec::new();
+    let duo_result = (|| -> Result<(DuoNetCounters, DuoNetCounters)> {
+        guests.push(spawn_duo_guest(
+            "a",
+            "10.0.9.1",
+            "52:54:00:a7:00:01",
+            &netdev,
+        )?);
+        // The second instance starts once the first has set up the shared OVMF vars copy.
+        wait_for_log(
+            &guests[0].log,
+            "Using firmware vars:",
+            Duration::from_secs(10),
+            "guest a firmware setup",
+        )?;
+        guests.push(spawn_duo_guest(
+            "b",
+            "10.0.9.2",
+            "52:54:00:a7:00:02",
+            &netdev,
+        )?);
+
+        for guest in guests.iter_mut() {
+            // No DHCP server on the socket LAN, so boot includes the DHCP timeout.
+            wait_for_log(
+                &guest.log,
+                "arrost> ",
+                Duration::from_secs(60),
+                &format!("guest {} shell prompt", guest.name),
+            )?;
+            let command = format!("ifconfig addr {}\n", guest.ip);
+            let marker = format!("ifconfig: eth0 inet={} cfg=static", guest.ip);
+            let line = duo_query(guest, &command, "ifconfig: ")?;
+            if !line.contains(&marker) {
+                bail!("guest {} rejected its address: {line}", guest.name);
+            }
+        }
+
+        let before_a = duo_net_counters(&mut guests[0])?;
+        let before_b = duo_net_counters(&mut guests[1])?;
+
+        let (a_ip, b_ip) = (guests[0].ip, guests[1].ip);
+        duo_ping(&mut guests[0], b_ip)?;
+        duo_ping(&mut guests[1], a_ip)?;
+
+        let send = format!("udp send {b_ip} {NET_DUO_UDP_PORT} {NET_DUO_PAYLOAD}\n");
+        let line = duo_query(&mut guests[0], &send, "udp: ")?;
+        if !line.contains("udp: sent") {
+            bail!("guest a udp send failed: {line}");
+        }
+        let expected = format!(
+            "udp: last src={a_ip}:7777 dst_port={NET_DUO_UDP_PORT} len={} preview=`{NET_DUO_PAYLOAD}`",
+            NET_DUO_PAYLOAD.len()
+        );
+        let deadline = Instant::now() + Duration::from_secs(8);
+        loop {
+            let line = duo_query(&mut guests[1], "udp last\n", "udp: ")?;
+            if line.contains(&expected) {
+                break;
+            }
+            if Instant::now() >= deadline {
+                bail!("guest b never saw the datagram: expected `{expected}`, last `{line}`");
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+
+        let after_a = duo_net_counters(&mut guests[0])?;
+        let after_b = duo_net_counters(&mut guests[1])?;
+        // Each side received an ARP request or reply, an echo request and an echo reply.
+        for (name, before, after) in [("a", &before_a, &after_a), ("b", &before_b, &after_b)] {
+            if after.arp <= before.arp {
+                bail!(
+                    "guest {name} saw no ARP traffic (arp={} -> {})",
+                    before.arp,
+                    after.arp
+                );
+            }
+            if after.icmp < before.icmp + 2 {
+                bail!(
+                    "guest {name} expected >=2 new ICMP packets (icmp={} -> {})",
+                    before.icmp,
+                    after.icmp
+                );
+            }
+            if after.rx <= before.rx {
+                bail!(
+                    "guest {name} rx did not move (rx={} -> {})",
+                    before.rx,
+                    after.rx
+                );
+            }
+        }
+        if after_b.udp <= before_b.udp {
+            bail!(
+                "guest b udp counter did not move (udp={} -> {})",
+                before_b.udp,
+                after_b.udp
+            );
+        }
+        Ok((after_a, after_b))
+    })();
+
+    for guest in guests.iter_mut() {
+        if guest
+            .child
+            .try_wait()
+            .context("failed to query qemu process status")?
+            .is_none()
+        {
+            let _ = guest.child.kill();
+        }
+        let _ = guest.child.wait();
+        for reader in guest.readers.drain(..) {
+            let _ = reader.join();
+        }
+    }
+
+    match duo_result {
+        Ok((a, b)) => {
+            println!("smoke-net-duo: PASS mcast={mcast}");
+            for (guest, counters) in guests.iter().zip([a, b]) {
+                println!(
+                    "smoke-net-duo: guest {} ip={} rx={} arp={} icmp={} udp={} drop={}",
+                    guest.name,
+                    guest.ip,
+                    counters.rx,
+                    counters.arp,
+                    counters.icmp,
+                    counters.udp,
+                    counters.drop
+                );
+                let log = snapshot_log(&guest.log);
+                if let Some(ping_line) = last_matching_line(&log, "ping: reply from") {
+                    println!("smoke-net-duo: guest {} {ping_line}", guest.name);
+                }
+            }
+            Ok(())
+        }
+        Err(error) => {
+            eprintln!("smoke-net-duo failed: {error}");
+            for guest in &guests {
+                eprintln!("----- guest {} serial tail -----", guest.name);
+                eprintln!("{}", log_tail(&snapshot_log(&guest.log), 40));
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Boots one slirp guest, starts `net bench rx` on a forwarded UDP port and floods it from the
+/// host as fast as slirp takes datagrams, then prints the guest's throughput and loss counters.
+/// Fails only when nothing arrived; the numbers are a baseline, not a threshold.
+fn smoke_net_flood() -> Result<()> {
+    let kernel_image = PathBuf::from(format!(
+        "target/{KERNEL_TARGET}/debug/bootimage-{KERNEL_PACKAGE}.bin"
+    ));
+    let data_image = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/m6-disk.img"));
+    for image in [&kernel_image, &data_image] {
+        if !image.exists() {
+            bail!(
+                "missing image at {}; run `cargo xtask build` first",
+                image.display()
+            );
+        }
+    }
+    let host_port = match std::env::var(NET_FLOOD_PORT_ENV) {
+        Ok(value) => value
+            .parse::<u16>()
+            .with_context(|| format!("invalid {NET_FLOOD_PORT_ENV}: {value}"))?,
+        Err(_) => NET_FLOOD_DEFAULT_PORT,
+    };
+    let host_port_env = host_port.to_string();
+    let guest_port_env = NET_FLOOD_GUEST_PORT.to_string();
+    let env = [
+        ("QEMU_SNAPSHOT", "1"),
+        ("ARR_UDP_FWD_PORT", host_port_env.as_str()),
+        ("ARR_UDP_FWD_GUEST_PORT", guest_port_env.as_str()),
+    ];
+
+    let (sent, report) = with_headless_shell_env(&env, "smoke-net-flood", |log, stdin| {
+        // Slirp only forwards once the guest holds its 10.0.2.15 lease.
+        let deadline = Instant::now() + Duration::from_secs(20);
+        loop {
+            let dhcp = query_status_line(log, stdin, "dhcp info\n", "dhcp: cfg=")?;
+            if dhcp.contains("bound=true") {
+                break;
+            }
+            if Instant::now() >= deadline {
+                bail!("guest has no dhcp lease: {dhcp}");
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+
+        let start = snapshot_log(log).len();
+        send_serial_command(
+            stdin,
+            &format!("net bench rx {NET_FLOOD_SECONDS} {NET_FLOOD_GUEST_PORT}\n"),
+        )?;
+        wait_for_log_from(
+            log,
+            start,
+            "net bench: rx listening",
+            Duration::from_secs(8),
+            "net bench rx start",
+        )?;
+
+        let socket =
+            std::net::UdpSocket::bind("127.0.0.1:0").context("failed to bind udp socket")?;
+        socket
+            .connect(("127.0.0.1", host_port))
+            .with_context(|| format!("failed to connect to 127.0.0.1:{host_port}"))?;
+        let mut payload = vec![0u8; NET_FLOOD_PAYLOAD_BYTES];
+        let mut sent = 0u64;
+        let flood_end = Instant::now() + Duration::from_secs(NET_FLOOD_SECONDS);
+        while Instant::now() < flood_end {
+            payload[..8].copy_from_slice(&sent.to_be_bytes());
+            match socket.send(&payload) {
+                Ok(_) => sent += 1,
+                // A full host socket buffer is slirp being slower than us; keep going.
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(error) => return Err(error).context("flood send failed"),
+            }
+        }
+
+        wait_for_log_from(
+            log,
+            start,
+            "net bench: rx port=",
+            Duration::from_secs(NET_FLOOD_SECONDS + 10),
+            "net bench rx result",
+        )?;
+        let snapshot = snapshot_log(log);
+        let report = last_matching_line(&snapshot, "net bench: rx port=")
+            .context("missing net bench rx result")?
+            .to_string();
+        if parse_metric_value(&report, "datagrams=").unwrap_or(0) == 0 {
+            bail!("guest received no flood datagrams (sent={sent}): {report}");
+        }
+        Ok((sent, report))
+    })?;
+
+    let metric = |key: &str| parse_metric_value(&report, key).unwrap_or(0);
+    println!(
+        "smoke-net-flood: PASS sent={sent} datagrams={} lost={} dropped={} pps={} kbit_s={}",
+        metric("datagrams="),
+        metric("lost="),
+        metric("dropped="),
+        metric("pps="),
+        metric("kbit_s=")
+    );
+    println!("smoke-net-flood: {}", report.trim());
+    Ok(())
+}
+
+fn spawn_duo_guest(
+    name: &'static str,
+    ip: &'static str,
+    mac: &str,
+    netdev: &str,
+) -> Result<DuoGuest> {
+    let mut child = Command::new("bash")
+        .args(["scripts/qemu.sh"])
+        .env("QEMU_DISPLAY", "none")
+        .env("QEMU_AUDIO", "none")
+        .env("QEMU_SNAPSHOT", "1")
+        .env("QEMU_NETDEV", netdev)
+        .env("QEMU_MAC", mac)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start qemu for duo guest {name}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to capture qemu stdout")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("failed to capture qemu stderr")?;
+    let log = Arc::new(Mutex::new(Vec::<u8>::new()));
+    let readers = vec![
+        spawn_log_reader(stdout, Arc::clone(&log)),
+        spawn_log_reader(stderr, Arc::clone(&log)),
+    ];
+    Ok(DuoGuest {
+        name,
+        ip,
+        child,
+        log,
+        readers,
+    })
+}
+
+/// Sends `command` to `guest` and returns the first new line containing `marker`.
+fn duo_query(guest: &mut DuoGuest, command: &str, marker: &str) -> Result<String> {
+    let stdin = guest
+        .child
+        .stdin
+        .as_mut()
+        .with_context(|| format!("failed to capture qemu stdin for guest {}", guest.name))?;
+    query_status_line(&guest.log, stdin, command, marker)
+}
+
+fn duo_ping(guest: &mut DuoGuest, target: &str) -> Result<()> {
+    let command = format!("ping {target}\n");
+    let line = duo_query(guest, &command, "ping: ")?;
+    if !line.contains(&format!("ping: reply from {target}")) {
+        bail!("guest {} ping {target} failed: {line}", guest.name);
+    }
+    Ok(())
+}
+
+fn duo_net_counters(guest: &mut DuoGuest) -> Result<DuoNetCounters> {
+    let line = duo_query(guest, "net\n", "net: backend=")?;
+    let metric = |key: &str| {
+        parse_metric_value(&line, key)
+            .with_context(|| format!("missing `{key}` in guest {} net line: {line}", guest.name))
+    };
+    Ok(DuoNetCounters {
+        rx: metric(" rx=")?,
+        arp: metric(" arp=")?,
+        icmp: metric(" icmp=")?,
+        udp: metric(" udp=")?,
+        drop: metric(" drop=")?,
+    })
+}
+
+fn fuzz(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let mut corpus = PathBuf::from(FUZZ_DEFAULT_CORPUS);
+    let mut generate = 0usize;
+    let mut seed = FUZZ_DEFAULT_SEED;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .with_context(|| format!("missing value for {name}"))
+        };
+        match arg.as_str() {
+            "--corpus" => corpus = PathBuf::from(value("--corpus")?),
+            "--generate" => {
+                generate = value("--generate")?
+                    .parse()
+                    .context("--generate expects a case count")?;
+            }
+            "--seed" => {
+                seed = value("--seed")?
+                    .parse()
+                    .context("--seed expects an integer")?;
+            }
+            other => bail!("unknown fuzz argument `{other}`"),
+        }
+    }
+    if generate > 0 {
+        fuzz_generate_corpus(&corpus, generate, seed)?;
+    }
+    let cases = fuzz_load_corpus(&corpus)?;
+    if cases.is_empty() {
+        bail!(
+            "empty corpus at {}; add cases or pass --generate N",
+            corpus.display()
+        );
+    }
+
+    // Fuzz commands only exist in `--features fuzz` kernels; rebuild the normal image after.
+    let reproducible = env_truthy(REPRODUCIBLE_ENV);
+    build_impl(
+        env_truthy(DOOM_FORCE_FALLBACK_ENV),
+        reproducible,
+        true,
+        None,
+    )?;
+    let fuzz_result = fuzz_replay(&cases);
+    let restore_result = build_impl(
+        env_truthy(DOOM_FORCE_FALLBACK_ENV),
+        reproducible,
+        false,
+        None,
+    );
+    match fuzz_result {
+        Ok(()) => restore_result,
+        Err(fuzz_err) => {
+            if let Err(restore_err) = restore_result {
+                return Err(fuzz_err.context(format!(
+                    "restoring the normal kernel build failed: {restore_err:#}"
+                )));
+            }
+            Err(fuzz_err)
+        }
+    }
+}
+
+fn fuzz_load_corpus(corpus: &Path) -> Result<Vec<FuzzCase>> {
+    let mut cases = Vec::new();
+    for target in FuzzTarget::ALL {
+        let dir = corpus.join(target.as_str());
+        if !dir.exists() {
+            continue;
+        }
+        let mut paths = std::fs::read_dir(&dir)
+            .with_context(|| format!("failed to list {}", dir.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("failed to list {}", dir.display()))?;
+        paths.sort();
+        for path in paths.into_iter().filter(|path| path.is_file()) {
+            let bytes = std::fs::read(&path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            cases.push(FuzzCase {
+                target,
+                path,
+                bytes,
+            });
+        }
+    }
+    Ok(cases)
+}
+
+/// Writes `count` mutants of the built-in seeds per target; the same seed gives the same corpus.
+fn fuzz_generate_corpus(corpus: &Path, count: usize, seed: u64) -> Result<()> {
+    let mut rng = XorShift64(seed.max(1));
+    for target in FuzzTarget::ALL {
+        let dir = corpus.join(target.as_str());
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+        let seeds = target.seeds();
+        for (index, bytes) in seeds.iter().enumerate() {
+            std::fs::write(dir.join(format!("seed-{index:02}")), bytes)
+                .with_context(|| format!("failed to write seed into {}", dir.display()))?;
+        }
+        for index in 0..count {
+            let mut bytes = seeds[rng.below(seeds.len())].clone();
+            for _ in 0..=rng.below(4) {
+                fuzz_mutate(&mut bytes, &mut rng);
+            }
+            std::fs::write(dir.join(format!("gen-{seed:016x}-{index:05}")), &bytes)
+                .with_context(|| format!("failed to write case into {}", dir.display()))?;
+        }
+    }
+    println!(
+        "fuzz: generated {count} cases per target into {} (seed={seed})",
+        corpus.display()
+    );
+    Ok(())
+}
+
+fn fuzz_mutate(bytes: &mut Vec<u8>, rng: &mut XorShift64) {
+    const INTERESTING: [u8; 6] = [0x00, 0x01, 0x7f, 0x80, 0xfe, 0xff];
+    if bytes.is_empty() {
+        bytes.push(rng.next() as u8);
+        return;
+    }
+    let at = rng.below(bytes.len());
+    match rng.below(5) {
+        0 => bytes[at] ^= 1 << rng.below(8),
+        1 => bytes[at] = INTERESTING[rng.below(INTERESTING.len())],
+        2 => bytes.truncate(at),
+        3 => {
+            let len = rng.below(bytes.len() - at) + 1;
+            let chunk = bytes[at..at + len].to_vec();
+            let insert_at = rng.below(bytes.len() + 1);
+            bytes.splice(insert_at..insert_at, chunk);
+        }
+        _ => bytes[at] = rng.next() as u8,
+    }
+}
+
+fn fuzz_replay(cases: &[FuzzCase]) -> Result<()> {
+    let mac = FUZZ_GUEST_MAC.map(|byte| format!("{byte:02x}")).join(":");
+    let mut guest =
+        spawn_headless_guest(&[("QEMU_SNAPSHOT", "1"), ("QEMU_MAC", &mac)], "fuzz replay")?;
+
+    let mut stats = FuzzStats::default();
+    let replay_result = (|| -> Result<()> {
+        let stdin = guest
+            .child
+            .stdin
+            .as_mut()
+            .context("failed to capture qemu stdin")?;
+        wait_for_log(
+            &guest.log,
+            "arrost> ",
+            Duration::from_secs(60),
+            "fuzz guest shell prompt",
+        )?;
+        for case in cases {
+            fuzz_replay_case(&guest.log, stdin, case, &mut stats)
+                .with_context(|| format!("case {}", case.path.display()))?;
+        }
+        Ok(())
+    })();
+    let log = Arc::clone(&guest.log);
+    stop_headless_guest(guest)?;
+
+    match replay_result {
+        Ok(()) => {
+            println!(
+                "fuzz: PASS cases={} net={} shell={} errors={} soft_asserts={} asserting_cases={}",
+                cases.len(),
+                stats.net,
+                stats.shell,
+                stats.errors,
+                stats.soft_asserts,
+                stats.asserting_cases
+            );
+            if !stats.drops.is_empty() {
+                let drops: Vec<String> = stats
+                    .drops
+                    .iter()
+                    .map(|(reason, count)| format!("{reason}={count}"))
+                    .collect();
+                println!("fuzz: drops {}", drops.join(" "));
+            }
+            Ok(())
+        }
+        Err(error) => {
+            eprintln!("fuzz failed: {error:#}");
+            eprintln!("----- serial tail -----");
+            eprintln!("{}", log_tail(&snapshot_log(&log), 40));
+            Err(error)
+        }
+    }
+}
+
+/// Stages the case with `fuzz append` chunks, injects it and waits for `fuzz: done`. A kernel
+/// panic or a missing `done` line counts as a crash and the case is copied to `FUZZ_CRASH_DIR`.
+fn fuzz_replay_case(
+    log: &Arc<Mutex<Vec<u8>>>,
+    stdin: &mut ChildStdin,
+    case: &FuzzCase,
+    stats: &mut FuzzStats,
+) -> Result<()> {
+    let crashed = |reason: String| -> Result<()> {
+        let name = case
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "case".to_string());
+        let saved = PathBuf::from(FUZZ_CRASH_DIR).join(format!("{}-{name}", case.target.as_str()));
+        std::fs::create_dir_all(FUZZ_CRASH_DIR)
+            .with_context(|| format!("failed to create {FUZZ_CRASH_DIR}"))?;
+        std::fs::write(&saved, &case.bytes)
+            .with_context(|| format!("failed to save crash case {}", saved.display()))?;
+        bail!("{reason}; case saved to {}", saved.display())
+    };
+
+    for chunk in case.bytes.chunks(FUZZ_APPEND_CHUNK) {
+        let hex: String = chunk.iter().map(|byte| format!("{byte:02x}")).collect();
+        let line = query_status_line(log, stdin, &format!("fuzz append {hex}\n"), "fuzz: ")?;
+        if !line.contains("fuzz: cases=") {
+            bail!("staging failed: {line}");
+        }
+    }
+    let command = format!("fuzz inject {}\n", case.target.as_str());
+    let line = match query_status_line(log, stdin, &command, "fuzz: done ") {
+        Ok(line) => line,
+        Err(error) => {
+            let snapshot = snapshot_log(log);
+            let reason = if snapshot.contains("KERNEL PANIC") {
+                "kernel panic".to_string()
+            } else {
+                format!("no result ({error})")
+            };
+            return crashed(reason);
+        }
+    };
+    match case.target {
+        FuzzTarget::Net => stats.net += 1,
+        FuzzTarget::Shell => stats.shell += 1,
+    }
+    if !line.contains("result=ok") {
+        stats.errors += 1;
+    }
+    let asserts = parse_metric_value(&line, " soft_asserts=").unwrap_or(0);
+    stats.soft_asserts += asserts;
+    if asserts > 0 {
+        stats.asserting_cases += 1;
+    }
+    if let Some(drops) = line
+        .split(" drops=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+    {
+        for reason in drops.split(',').filter(|reason| *reason != "none") {
+            *stats.drops.entry(reason.to_string()).or_insert(0) += 1;
+        }
+    }
+    Ok(())
+}
+
+fn ethernet_frame(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + payload.len());
+    frame.extend_from_slice(&FUZZ_GUEST_MAC);
+    frame.extend_from_slice(&[0x52, 0x55, 0x0a, 0x00, 0x02, 0x02]);
+    frame.extend_from_slice(&ethertype.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+fn ipv4_frame(protocol: u8, payload: &[u8]) -> Vec<u8> {
+    let total_len = (20 + payload.len()) as u16;
+    let mut header = vec![0x45, 0x00];
+    header.extend_from_slice(&total_len.to_be_bytes());
+    header.extend_from_slice(&[0x00, 0x01, 0x40, 0x00, 0x40, protocol, 0x00, 0x00]);
+    header.extend_from_slice(&[10, 0, 2, 2]);
+    header.extend_from_slice(&FUZZ_GUEST_IP);
+    let checksum = internet_checksum(&header);
+    header[10..12].copy_from_slice(&checksum.to_be_bytes());
+    header.extend_from_slice(payload);
+    ethernet_frame(0x0800, &header)
+}
+
+fn internet_checksum(bytes: &[u8]) -> u16 {
+    let mut sum = bytes
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FuzzTarget {
+    Net,
+    Shell,
+}
+
+impl FuzzTarget {
+    const ALL: [Self; 2] = [Self::Net, Self::Shell];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Net => "net",
+            Self::Shell => "shell",
+        }
+    }
+
+    fn seeds(self) -> Vec<Vec<u8>> {
+        match self {
+            Self::Net => {
+                let mut arp = vec![0x00, 0x01, 0x08, 0x00, 0x06, 0x04, 0x00, 0x01];
+                arp.extend_from_slice(&[0x52, 0x55, 0x0a, 0x00, 0x02, 0x02, 10, 0, 2, 2]);
+                arp.extend_from_slice(&[0; 6]);
+                arp.extend_from_slice(&FUZZ_GUEST_IP);
+                let mut icmp = vec![0x08, 0x00, 0x00, 0x00, 0x12, 0x34, 0x00, 0x01];
+                icmp.extend_from_slice(b"arrost-fuzz");
+                let checksum = internet_checksum(&icmp);
+                icmp[2..4].copy_from_slice(&checksum.to_be_bytes());
+                let mut udp = Vec::new();
+                udp.extend_from_slice(&40000u16.to_be_bytes());
+                udp.extend_from_slice(&9000u16.to_be_bytes());
+                udp.extend_from_slice(&(8 + 5u16).to_be_bytes());
+                udp.extend_from_slice(&[0, 0]);
+                udp.extend_from_slice(b"fuzz!");
+                let mut tcp_syn = Vec::new();
+                tcp_syn.extend_from_slice(&40001u16.to_be_bytes());
+                tcp_syn.extend_from_slice(&7777u16.to_be_bytes());
+                tcp_syn.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x02, 0xff, 0xff]);
+                tcp_syn.extend_from_slice(&[0, 0, 0, 0]);
+                let mut bad_udp = udp.clone();
+                bad_udp[6..8].copy_from_slice(&[0xde, 0xad]);
+                let mut fragment = ipv4_frame(17, &udp);
+                fragment[20..22].copy_from_slice(&0x2001u16.to_be_bytes());
+                fragment[24..26].copy_from_slice(&[0, 0]);
+                let checksum = internet_checksum(&fragment[14..34]);
+                fragment[24..26].copy_from_slice(&checksum.to_be_bytes());
+                vec![
+                    ethernet_frame(0x0806, &arp),
+                    ipv4_frame(1, &icmp),
+                    ipv4_frame(17, &udp),
+                    ipv4_frame(6, &tcp_syn),
+                    ipv4_frame(17, &bad_udp),
+                    fragment,
+                    ethernet_frame(0x86dd, &[0x60, 0, 0, 0]),
+                    vec![0xff; 10],
+                ]
+            }
+            Self::Shell => [
+                "udp send 10.0.2.2 9000 hello",
+                "echo fuzz > /FUZZ.TXT",
+                "echo fuzz >> /FUZZ.TXT",
+                "doom key left",
+                "fm copy /A.TXT /B.TXT",
+                "ifconfig addr 10.0.2.15",
+                "wol 52:54:00:12:34:56 9",
+                "log quiet net on",
+                "doom autostart kiosk",
+                "!!",
+                "!12",
+                "abc\x08\x08\x7fdef\x1b[A",
+            ]
+            .iter()
+            .map(|seed| seed.as_bytes().to_vec())
+            .collect(),
+        }
+    }
+}
+
+struct FuzzCase {
+    target: FuzzTarget,
+    path: PathBuf,
+    bytes: Vec<u8>,
+}
+
+#[derive(Default)]
+struct FuzzStats {
+    net: u64,
+    shell: u64,
+    errors: u64,
+    soft_asserts: u64,
+    asserting_cases: u64,
+    /// Cases per `net: rx_drops` reason, from the `drops=` field of each `fuzz: done` line.
+    drops: std::collections::BTreeMap<String, u64>,
+}
+
+/// Small deterministic PRNG for corpus generation; no `rand` dependency for one generator.
+struct XorShift64(u64);
+
+impl XorShift64 {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+fn spawn_headless_guest(env: &[(&str, &str)], purpose: &str) -> Result<HeadlessGuest> {
+    let mut child = Command::new("bash")
+        .args(["scripts/qemu.sh"])
+        .env("QEMU_DISPLAY", "none")
+        .env("QEMU_AUDIO", "none")
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to start qemu for {purpose}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to capture qemu stdout")?;
+    let stderr = child
+        .stderr
+        .take()
+        .context("failed to capture qemu stderr")?;
+    let log = Arc::new(Mutex::new(Vec::<u8>::new()));
+    let readers = vec![
+        spawn_log_reader(stdout, Arc::clone(&log)),
+        spawn_log_reader(stderr, Arc::clone(&log)),
+    ];
+    Ok(HeadlessGuest {
+        child,
+        log,
+        readers,
+    })
+}
+
+fn stop_headless_guest(mut guest: HeadlessGuest) -> Result<()> {
+    if guest
+        .child
+        .try_wait()
+        .context("failed to query qemu process status")?
+        .is_none()
+    {
+        let _ = guest.child.kill();
+    }
+    let _ = guest.child.wait();
+    for reader in guest.readers {
+        let _ = reader.join();
+    }
+    Ok(())
+}
+
+/// Boots the built image, waits for the shell and runs `session` against its serial console;
+/// the guest is stopped afterwards and its serial tail printed on failure.
+fn with_headless_shell<T>(
+    purpose: &str,
+    session: impl FnOnce(&Arc<Mutex<Vec<u8>>>, &mut ChildStdin) -> Result<T>,
+) -> Result<T> {
+    with_headless_shell_env(&[], purpose, session)
+}
+
+/// `with_headless_shell` with extra `scripts/qemu.sh` environment, e.g. host forwarding.
+fn with_headless_shell_env<T>(
+    env: &[(&str, &str)],
+    purpose: &str,
+    session: impl FnOnce(&Arc<Mutex<Vec<u8>>>, &mut ChildStdin) -> Result<T>,
+) -> Result<T> {
+    let mut guest = spawn_headless_guest(env, purpose)?;
+    let result = (|| -> Result<T> {
+        let stdin = guest
+            .child
+            .stdin
+            .as_mut()
+            .context("failed to capture qemu stdin")?;
         wait_for_log(
-            &log,
-            "last_key=0x0a",
-            Duration::from_secs(8),
-            "doom status post-input",
+            &guest.log,
+            "arrost> ",
+            Duration::from_secs(60),
+            &format!("{purpose} shell prompt"),
         )?;
-        let status_snapshot = snapshot_log(&log);
-        let Some(status_line) = last_matching_line(&status_snapshot, "last_key=0x0a") else {
-            bail!("missing doom status line after input injections");
-        };
-        let Some(inputs) = parse_metric_value(status_line, "inputs=") else {
-            bail!("missing inputs metric in doom status line");
-        };
-        if inputs < 3 {
-            bail!("unexpected low doom input count after injections (inputs={inputs})");
-        }
-        let Some(dg_frames) = parse_metric_value(status_line, "dg_frames=") else {
-            bail!("missing dg_frames metric in doom status line");
-        };
-        if dg_frames < 2 {
-            bail!("unexpected low doom frame count after play start (dg_frames={dg_frames})");
-        }
-        let Some(dg_key) = parse_metric_value(status_line, "dg_key=") else {
-            bail!("missing dg_key metric in doom status line");
-        };
-        if dg_key == 0 {
-            bail!("doom bridge did not register key queue events (dg_key={dg_key})");
+        if !env_truthy("ARROST_SERIAL_PLAIN") {
+            negotiate_serial_compression(&guest.log, stdin)?;
         }
-        let Some(dg_nonzero) = parse_metric_value(status_line, "dg_nonzero=") else {
-            bail!("missing dg_nonzero metric in doom status line");
+        session(&guest.log, stdin)
+    })();
+    let log = Arc::clone(&guest.log);
+    stop_headless_guest(guest)?;
+    if let Err(error) = &result {
+        eprintln!("{purpose} failed: {error:#}");
+        eprintln!("----- serial tail -----");
+        eprintln!("{}", log_tail(&snapshot_log(&log), 40));
+    }
+    result
+}
+
+/// Imports diskfs files with interior holes, then checks `fm stat` and `fm verify` before and
+/// after a `reload` and deletes them again. `/HOLE.BIN` has one zero sector between two data
+/// sectors; `/SPAN.BIN` has 12 sectors in two-sector spans, with the third span zeroed.
+fn smoke_fs() -> Result<()> {
+    let mut hole = vec![b'A'; 512];
+    hole.extend([0; 512]);
+    hole.extend([b'B'; 512]);
+    let mut span = vec![b'C'; 12 * 512];
+    span[4 * 512..6 * 512].fill(0);
+    let files = [
+        ("/HOLE.BIN", hole, "size=1536 allocated=1024 holes=1"),
+        ("/SPAN.BIN", span, "size=6144 allocated=5120 holes=2"),
+    ];
+
+    with_headless_shell("smoke-fs", |log, stdin| {
+        let check = |stdin: &mut ChildStdin, stage: &str| -> Result<()> {
+            for (name, data, expected) in &files {
+                let stat =
+                    query_status_line(log, stdin, &format!("fm stat {name}\n"), "fm: stat ")?;
+                if !stat.contains(expected) {
+                    bail!("{stage}: expected `{expected}` for {name}, got: {stat}");
+                }
+                let verify =
+                    query_status_line(log, stdin, &format!("fm verify {name}\n"), "fm: verify /")?;
+                let verified = format!("fm: verify {name} ok crc={:08x}", crc32(data));
+                if !verify.contains(&verified) {
+                    bail!("{stage}: expected `{verified}`, got: {verify}");
+                }
+            }
+            Ok(())
         };
-        if dg_nonzero == 0 {
-            bail!("doom frame appears fully black after play start (dg_nonzero={dg_nonzero})");
+        for (name, data, _) in &files {
+            serial_import(log, stdin, name, data)?;
         }
-        let Some(dg_audio) = parse_metric_value(status_line, "dg_audio=") else {
-            bail!("missing dg_audio metric in doom status line");
-        };
-        if dg_audio == 0 {
-            bail!("doom audio backend stub did not receive callbacks (dg_audio={dg_audio})");
+        check(stdin, "after import")?;
+        let reload = query_status_line(log, stdin, "reload\n", "reload: ")?;
+        if !reload.contains("reload: diskfs remounted") {
+            bail!("guest reload failed (ramfs fallback?): {reload}");
         }
-        let Some(dg_audio_samples) = parse_metric_value(status_line, "dg_audio_samples=") else {
-            bail!("missing dg_audio_samples metric in doom status line");
-        };
-        let Some(pcm_samples) = parse_metric_value(status_line, "pcm_samples=") else {
-            bail!("missing pcm_samples metric in doom status line");
-        };
-        if pcm_samples == 0 {
-            bail!("pcm audio path inactive after play start (pcm_samples=0)");
+        check(stdin, "after reload")?;
+        for (name, _, _) in &files {
+            let deleted =
+                query_status_line(log, stdin, &format!("fm delete {name}\n"), "fm: delete")?;
+            if !deleted.contains("fm: deleted") {
+                bail!("cleanup failed: {deleted}");
+            }
         }
-        let virtio_backend = status_line.contains("pcm_backend=virtio-snd");
-        if strict_virtio && !virtio_backend {
-            bail!("strict virtio smoke expected pcm_backend=virtio-snd");
+        Ok(())
+    })?;
+    println!("smoke-fs: interior holes survive reload");
+    Ok(())
+}
+
+/// Fills the shell history with 99 numbered commands and `reboot`s without a `sync`, then
+/// checks that the next boot reloads all 100 entries, the oldest and newest by `!n`.
+fn smoke_history() -> Result<()> {
+    with_headless_shell("smoke-history", |log, stdin| {
+        query_status_line(log, stdin, "history clear\n", "history: cleared")?;
+        for number in 1..=99 {
+            let marker = format!("history-{number:03}");
+            query_status_line(log, stdin, &format!("echo {marker}\n"), &marker)?;
         }
-        if virtio_backend {
-            let Some(pcm_tx) = parse_metric_value(status_line, "pcm_tx=") else {
-                bail!("missing pcm_tx metric in virtio status line");
-            };
-            let Some(pcm_done) = parse_metric_value(status_line, "pcm_done=") else {
-                bail!("missing pcm_done metric in virtio status line");
-            };
-            if pcm_tx == 0 || pcm_done == 0 {
-                bail!("virtio-sound metrics inactive (pcm_tx={pcm_tx} pcm_done={pcm_done})");
+        send_serial_command(stdin, "reboot\n")?;
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let loaded = loop {
+            let snapshot = snapshot_log(log);
+            let rebooted = snapshot
+                .rfind("reboot: going down")
+                .map(|at| &snapshot[at..])
+                .filter(|tail| tail.contains("arrost> "));
+            if let Some(line) = rebooted.and_then(|tail| last_matching_line(tail, "Shell: history"))
+            {
+                break line.to_string();
             }
-        } else {
-            let Some(pcm_sw) = parse_metric_value(status_line, "pcm_sw=") else {
-                bail!("missing pcm_sw metric in doom status line");
-            };
-            let Some(pcm_min) = parse_metric_value(status_line, "pcm_min=") else {
-                bail!("missing pcm_min metric in doom status line");
-            };
-            let Some(pcm_max) = parse_metric_value(status_line, "pcm_max=") else {
-                bail!("missing pcm_max metric in doom status line");
-            };
-            if pcm_min == 0 || pcm_max == 0 || pcm_max < pcm_min {
-                bail!("invalid pcm frequency window (pcm_min={pcm_min} pcm_max={pcm_max})");
+            if Instant::now() >= deadline {
+                bail!("guest did not come back from `reboot` with its history");
             }
-            if pcm_samples >= 2048 && pcm_sw == 0 && pcm_max == pcm_min {
-                bail!(
-                    "pcm tone appears fixed after play start (pcm_sw={pcm_sw} pcm_min={pcm_min} pcm_max={pcm_max})"
-                );
+            thread::sleep(Duration::from_millis(50));
+        };
+        if !loaded.contains("history loaded 100 commands") {
+            bail!("expected 100 saved commands after reboot, got: {loaded}");
+        }
+        for number in [1, 99] {
+            let line = query_status_line(log, stdin, &format!("!{number}\n"), "echo history-")?;
+            if !line
+                .trim_end()
+                .ends_with(&format!("echo history-{number:03}"))
+            {
+                bail!("history entry {number} did not survive the reboot: {line}");
             }
         }
-        // `doom audio test` injects explicit diagnostic PCM outside the Doom mixer path.
-        // Skip it for long-run smokes and only account for it in short runs.
-        let audio_test_pcm_budget = if long_run { 0u64 } else { 4096u64 };
-        if dg_audio_samples > 0
-            && pcm_samples
-                > dg_audio_samples
-                    .saturating_mul(4)
-                    .saturating_add(audio_test_pcm_budget)
-        {
-            bail!(
-                "unexpected pcm sample growth (pcm_samples={pcm_samples} dg_audio_samples={dg_audio_samples})"
-            );
+        Ok(())
+    })?;
+    println!("smoke-history: 100 entries saved on reboot and reloaded");
+    Ok(())
+}
+
+/// Copies a host file into the guest fs with `fs import` and `sync`s it to the data disk.
+fn fs_import(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let host = PathBuf::from(
+        args.next()
+            .context("usage: cargo xtask fs-import <host-file> [name]")?,
+    );
+    let name = match args.next() {
+        Some(name) => name,
+        None => default_guest_name(&host)?,
+    };
+    let data = read_importable(&host)?;
+    let crc = crc32(&data);
+
+    with_headless_shell("fs-import", |log, stdin| {
+        serial_import(log, stdin, &name, &data)?;
+        let sync = query_status_line(log, stdin, "sync\n", "sync: ")?;
+        if !sync.contains("sync: diskfs metadata saved") {
+            bail!("guest sync failed (ramfs fallback?): {sync}");
         }
-        let dg_frames_before_progress = dg_frames;
+        Ok(())
+    })?;
+    println!(
+        "fs-import: {} -> {name} bytes={} crc32={crc:08x}",
+        host.display(),
+        data.len()
+    );
+    Ok(())
+}
 
-        send_serial_command(stdin, "doom key right\n")?;
-        wait_for_log(
-            &log,
-            "doom: injected key 0x64",
-            Duration::from_secs(8),
-            "doom right injection",
-        )?;
-        send_serial_command(stdin, "doom keyup right\n")?;
-        wait_for_log(
-            &log,
-            "doom: injected keyup 0x64",
-            Duration::from_secs(8),
-            "doom right keyup injection",
-        )?;
+/// `/<FILE NAME IN UPPERCASE>`, the guest name `fs-import` and `:import` use by default.
+fn default_guest_name(host: &Path) -> Result<String> {
+    Ok(format!(
+        "/{}",
+        host.file_name()
+            .context("host path has no file name")?
+            .to_string_lossy()
+            .to_ascii_uppercase()
+    ))
+}
 
-        send_serial_command(stdin, "doom status\n")?;
+fn read_importable(host: &Path) -> Result<Vec<u8>> {
+    let data = std::fs::read(host).with_context(|| format!("failed to read {}", host.display()))?;
+    if data.len() > FS_MAX_FILE_BYTES {
+        bail!(
+            "{} is {} bytes; guest files hold at most {FS_MAX_FILE_BYTES}",
+            host.display(),
+            data.len()
+        );
+    }
+    Ok(data)
+}
+
+/// Runs the `fs import` protocol on a shell at its prompt: the header with size and CRC, then
+/// one base64 line per acknowledgement until the guest reports the file written.
+fn serial_import(
+    log: &Arc<Mutex<Vec<u8>>>,
+    stdin: &mut ChildStdin,
+    name: &str,
+    data: &[u8],
+) -> Result<()> {
+    let crc = crc32(data);
+    let command = format!("fs import {name} {} {crc:08x}\n", data.len());
+    let mut line = query_status_line(log, stdin, &command, "fs: import ")?;
+    if !line.contains("fs: import ready") {
+        bail!("guest refused the import: {line}");
+    }
+    for chunk in data.chunks(FS_TRANSFER_LINE_BYTES) {
+        line = query_status_line(
+            log,
+            stdin,
+            &format!("{}\n", base64_encode(chunk)),
+            "fs: import ",
+        )?;
+        if !line.contains("fs: import got=") && !line.contains("fs: import done") {
+            bail!("import failed: {line}");
+        }
+    }
+    if data.is_empty() {
+        // Empty files complete as soon as the import starts.
         wait_for_log(
-            &log,
-            "last_key=0x64",
+            log,
+            "fs: import done",
             Duration::from_secs(8),
-            "doom status frame progression",
+            "fs import result",
         )?;
-        let progression_snapshot = snapshot_log(&log);
-        let Some(progression_line) = last_matching_line(&progression_snapshot, "last_key=0x64")
-        else {
-            bail!("missing doom status line for frame progression check");
-        };
-        let Some(dg_frames_after_progress) = parse_metric_value(progression_line, "dg_frames=")
-        else {
-            bail!("missing dg_frames metric in progression status line");
-        };
-        if dg_frames_after_progress <= dg_frames_before_progress {
-            bail!(
-                "doom frame counter did not progress (before={dg_frames_before_progress} after={dg_frames_after_progress})"
-            );
-        }
-        let Some(dg_nonzero_after_progress) = parse_metric_value(progression_line, "dg_nonzero=")
-        else {
-            bail!("missing dg_nonzero metric in progression status line");
-        };
-        if dg_nonzero_after_progress == 0 {
-            bail!("doom progression frame is fully black (dg_nonzero={dg_nonzero_after_progress})");
-        }
-        let Some(dg_drop_before_long) = parse_metric_value(progression_line, "dg_drop=") else {
-            bail!("missing dg_drop metric in progression status line");
-        };
-        let Some(dg_audio_before_long) = parse_metric_value(progression_line, "dg_audio=") else {
-            bail!("missing dg_audio metric in progression status line");
-        };
-        let pcm_drop_frames_before_long = if virtio_backend {
-            let Some(value) = parse_metric_value(progression_line, "pcm_drop_frames=") else {
-                bail!("missing pcm_drop_frames metric in progression status line");
-            };
-            Some(value)
-        } else {
-            None
-        };
-        let pcm_done_before_long = if virtio_backend {
-            let Some(value) = parse_metric_value(progression_line, "pcm_done=") else {
-                bail!("missing pcm_done metric in progression status line");
-            };
-            Some(value)
-        } else {
-            None
-        };
-
-        if long_run {
-            let long_wait = Duration::from_secs(24);
-            let min_frame_progress = if software_accel_mode { 90u64 } else { 180u64 };
-            let max_drop_delta = 4u64;
-
-            thread::sleep(long_wait);
-            send_serial_command(stdin, "doom status\n")?;
-            let long_line = wait_for_status_with_frame_progress(
-                &log,
-                dg_frames_after_progress,
-                Duration::from_secs(8),
-                "doom status long-run",
-            )?;
-            let Some(dg_frames_long) = parse_metric_value(&long_line, "dg_frames=") else {
-                bail!("missing dg_frames metric in long-run status line");
-            };
-            if dg_frames_long <= dg_frames_after_progress {
-                bail!(
-                    "doom frame counter did not progress during long-run (before={dg_frames_after_progress} after={dg_frames_long})"
-                );
-            }
-            let frame_delta = dg_frames_long - dg_frames_after_progress;
-            if frame_delta < min_frame_progress {
-                bail!(
-                    "doom frame progression too low during long-run (delta={frame_delta}, waited={}s, min={min_frame_progress})",
-                    long_wait.as_secs()
-                );
-            }
+        line = last_matching_line(&snapshot_log(log), "fs: import done")
+            .unwrap_or_default()
+            .to_string();
+    }
+    let expected = format!(
+        "fs: import done name={name} bytes={} crc32={crc:08x}",
+        data.len()
+    );
+    if !line.contains(&expected) {
+        bail!("unexpected import result: {line}");
+    }
+    Ok(())
+}
 
-            let Some(dg_drop_long) = parse_metric_value(&long_line, "dg_drop=") else {
-                bail!("missing dg_drop metric in long-run status line");
-            };
-            let drop_delta = dg_drop_long.saturating_sub(dg_drop_before_long);
-            if drop_delta > max_drop_delta {
-                bail!(
-                    "doom key queue drops grew too much during long-run (delta={drop_delta}, max={max_drop_delta})"
-                );
-            }
+/// Reads a guest file with `fs export` and writes it to the host after checking size and CRC.
+fn fs_export(mut args: impl Iterator<Item = String>) -> Result<()> {
+    let name = args
+        .next()
+        .context("usage: cargo xtask fs-export <name> [host-file]")?;
+    let host = match args.next() {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(name.trim_start_matches('/')),
+    };
 
-            let Some(dg_nonzero_long) = parse_metric_value(&long_line, "dg_nonzero=") else {
-                bail!("missing dg_nonzero metric in long-run status line");
-            };
-            if dg_nonzero_long == 0 {
-                bail!("doom long-run frame is fully black (dg_nonzero={dg_nonzero_long})");
+    let data = with_headless_shell("fs-export", |log, stdin| {
+        let header = query_status_line(log, stdin, &format!("fs export {name}\n"), "fs: export ")?;
+        let (Some(size), Some(crc)) = (
+            parse_metric_value(&header, " size="),
+            header
+                .split_once(" crc32=")
+                .and_then(|(_, rest)| rest.split_whitespace().next())
+                .and_then(|crc| u32::from_str_radix(crc, 16).ok()),
+        ) else {
+            bail!("guest export failed: {header}");
+        };
+        let end = format!("fs: export end name={name}");
+        // Plain base64 moves roughly 2.5 KiB of file per second at 38400 baud.
+        let timeout = Duration::from_secs(8 + size / 2048);
+        wait_for_log(log, &end, timeout, "fs export end marker")?;
+        let snapshot = snapshot_log(log);
+        let start = snapshot
+            .rfind(header.trim_end())
+            .context("export header vanished from the serial log")?;
+        let mut data = Vec::new();
+        for line in snapshot[start..].lines().skip(1) {
+            let line = line.trim();
+            if line.starts_with(&end) {
+                break;
             }
-
-            let Some(dg_audio_long) = parse_metric_value(&long_line, "dg_audio=") else {
-                bail!("missing dg_audio metric in long-run status line");
-            };
-            if dg_audio_long <= dg_audio_before_long {
-                bail!(
-                    "doom audio hook did not progress during long-run (before={dg_audio_before_long} after={dg_audio_long})"
+            if let Some(encoded) = line.strip_prefix("fs: b64 ") {
+                data.extend(
+                    base64_decode(encoded)
+                        .with_context(|| format!("bad base64 line `{encoded}`"))?,
                 );
             }
+        }
+        if data.len() as u64 != size || crc32(&data) != crc {
+            bail!(
+                "export corrupted in transit: got {} bytes crc32={:08x}, expected {size} bytes crc32={crc:08x}",
+                data.len(),
+                crc32(&data)
+            );
+        }
+        Ok(data)
+    })?;
+    std::fs::write(&host, &data).with_context(|| format!("failed to write {}", host.display()))?;
+    println!(
+        "fs-export: {name} -> {} bytes={} crc32={:08x}",
+        host.display(),
+        data.len(),
+        crc32(&data)
+    );
+    Ok(())
+}
 
-            if virtio_backend {
-                let Some(pcm_drop_frames_long) = parse_metric_value(&long_line, "pcm_drop_frames=")
-                else {
-                    bail!("missing pcm_drop_frames metric in long-run status line");
-                };
-                let drop_frames_delta =
-                    pcm_drop_frames_long.saturating_sub(pcm_drop_frames_before_long.unwrap_or(0));
-                let max_drop_frames_delta = if strict_virtio { 512u64 } else { 1536u64 };
-                if drop_frames_delta > max_drop_frames_delta {
-                    bail!(
-                        "virtio pcm_drop_frames grew too much during long-run (delta={drop_frames_delta}, max={max_drop_frames_delta})"
-                    );
-                }
-
-                let Some(pcm_done_long) = parse_metric_value(&long_line, "pcm_done=") else {
-                    bail!("missing pcm_done metric in long-run status line");
-                };
-                let done_delta = pcm_done_long.saturating_sub(pcm_done_before_long.unwrap_or(0));
-                if done_delta == 0 {
-                    bail!("virtio completion counter did not progress during long-run (pcm_done)");
-                }
+impl ConsoleTranscript {
+    fn guest_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if byte == b'\n' {
+                let line = std::mem::take(&mut self.guest_line);
+                let text = String::from_utf8_lossy(&line);
+                self.line('<', text.trim_end_matches('\r'));
+            } else {
+                self.guest_line.push(byte);
             }
         }
+    }
 
-        send_serial_command(stdin, "ui\n")?;
-        wait_for_log(
-            &log,
-            "ui: backend=uefi-gop ready=true",
-            Duration::from_secs(8),
-            "ui diagnostics line",
-        )?;
+    /// `<` guest output, `>` typed lines, `#` notes from the console itself.
+    fn line(&mut self, direction: char, text: &str) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let _ = writeln!(self.file, "[{elapsed:>10.3}] {direction} {text}");
+    }
+}
 
-        let log_snapshot = snapshot_log(&log);
-        if let Some(ui_line) = last_matching_line(&log_snapshot, "ui: backend=uefi-gop ready=true")
-            && let Some(stdout_dropped) = parse_metric_value(ui_line, "stdout_dropped=")
-            && stdout_dropped > 0
-        {
-            bail!("stdout mirror dropped bytes during smoke run (stdout_dropped={stdout_dropped})");
+/// Boots the image with its serial console on this terminal. Lines are edited by the host
+/// terminal and sent whole; lines starting with `:` are console commands (`:import`, `:quit`).
+/// Both directions are timestamped into a transcript.
+fn console(mut args: impl Iterator<Item = String>) -> Result<()> {
+    const USAGE: &str = "usage: cargo xtask console [--log FILE]";
+    let mut log_path = PathBuf::from(CONSOLE_DEFAULT_LOG);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--log" => log_path = PathBuf::from(args.next().context(USAGE)?),
+            other => bail!("unknown console argument `{other}` ({USAGE})"),
         }
+    }
+    if let Some(parent) = log_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let file = std::fs::File::create(&log_path)
+        .with_context(|| format!("failed to create {}", log_path.display()))?;
+    let transcript = Arc::new(Mutex::new(ConsoleTranscript {
+        file,
+        start: Instant::now(),
+        guest_line: Vec::new(),
+    }));
 
-        send_serial_command(stdin, "doom stop\n")?;
-        wait_for_log(
-            &log,
-            "doom: runtime stopped",
-            Duration::from_secs(8),
-            "doom stop confirmation",
-        )?;
-
-        Ok(())
-    })();
+    let mut child = Command::new("bash")
+        .args(["scripts/qemu.sh"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("failed to start qemu for console")?;
+    let stdout = child
+        .stdout
+        .take()
+        .context("failed to capture qemu stdout")?;
+    let log = Arc::new(Mutex::new(Vec::<u8>::new()));
+    let reader = spawn_console_reader(stdout, Arc::clone(&log), Arc::clone(&transcript));
 
+    let result = console_session(&mut child, &log, &transcript);
     if child
         .try_wait()
         .context("failed to query qemu process status")?
@@ -1181,45 +4100,447 @@ fn smoke_doom_impl(long_run: bool, force_fallback: bool, strict_virtio: bool) ->
         let _ = child.kill();
     }
     let _ = child.wait();
-    let _ = stdout_reader.join();
-    let _ = stderr_reader.join();
+    let _ = reader.join();
+    if let Ok(mut transcript) = transcript.lock() {
+        let rest = std::mem::take(&mut transcript.guest_line);
+        if !rest.is_empty() {
+            transcript.line('<', &String::from_utf8_lossy(&rest));
+        }
+    }
+    eprintln!("console: transcript saved to {}", log_path.display());
+    result
+}
 
-    let log_snapshot = snapshot_log(&log);
-    if let Err(error) = smoke_result {
-        eprintln!("{smoke_name} failed: {error}");
-        eprintln!("----- serial tail -----");
-        eprintln!("{}", log_tail(&log_snapshot, 80));
-        return Err(error);
+fn console_session(
+    child: &mut Child,
+    log: &Arc<Mutex<Vec<u8>>>,
+    transcript: &Arc<Mutex<ConsoleTranscript>>,
+) -> Result<()> {
+    let mut stdin = child.stdin.take().context("failed to capture qemu stdin")?;
+    eprintln!("console: `:help` lists host commands; `:quit` or Ctrl-D leaves");
+    let mut input = String::new();
+    loop {
+        input.clear();
+        if std::io::stdin()
+            .read_line(&mut input)
+            .context("failed to read the terminal")?
+            == 0
+        {
+            return Ok(());
+        }
+        if child
+            .try_wait()
+            .context("failed to query qemu process status")?
+            .is_some()
+        {
+            return Ok(());
+        }
+        let line = input.trim_end_matches(['\r', '\n']);
+        console_note(transcript, '>', line);
+        match line.strip_prefix(':') {
+            // `::` sends a guest line that itself starts with `:`.
+            Some(rest) if rest.starts_with(':') => {
+                send_serial_command(&mut stdin, &format!("{rest}\n"))?
+            }
+            Some(command) => {
+                if !console_command(command, &mut stdin, log, transcript) {
+                    return Ok(());
+                }
+            }
+            None => send_serial_command(&mut stdin, &format!("{line}\n"))?,
+        }
     }
+}
 
-    println!("{smoke_name}: PASS");
-    if let Some(play_line) = last_matching_line(&log_snapshot, "doom: play mode started") {
-        println!("{smoke_name}: {play_line}");
+/// Runs one `:` command; returns false once the session should end. A failed import is
+/// reported and the session carries on.
+fn console_command(
+    command: &str,
+    stdin: &mut ChildStdin,
+    log: &Arc<Mutex<Vec<u8>>>,
+    transcript: &Arc<Mutex<ConsoleTranscript>>,
+) -> bool {
+    let mut words = command.split_whitespace();
+    let note = match words.next() {
+        Some("quit") | Some("q") => return false,
+        Some("import") => match words.next() {
+            Some(host) => {
+                let host = PathBuf::from(host);
+                let result = (|| -> Result<String> {
+                    let name = match words.next() {
+                        Some(name) => name.to_string(),
+                        None => default_guest_name(&host)?,
+                    };
+                    let data = read_importable(&host)?;
+                    serial_import(log, stdin, &name, &data)?;
+                    Ok(format!(
+                        "console: imported {} -> {name} bytes={} crc32={:08x} (run `sync` to keep it)",
+                        host.display(),
+                        data.len(),
+                        crc32(&data)
+                    ))
+                })();
+                result.unwrap_or_else(|error| format!("console: import failed: {error:#}"))
+            }
+            None => "usage: :import <host-file> [name]".to_string(),
+        },
+        _ => "console: :import <host-file> [name] sends a file with `fs import`, :quit stops qemu, `::` sends a literal `:`".to_string(),
+    };
+    eprintln!("{note}");
+    console_note(transcript, '#', &note);
+    true
+}
+
+fn console_note(transcript: &Arc<Mutex<ConsoleTranscript>>, direction: char, text: &str) {
+    if let Ok(mut transcript) = transcript.lock() {
+        transcript.line(direction, text);
     }
-    if let Some(audio_line) = last_matching_line(&log_snapshot, "Audio: backend=") {
-        println!("{smoke_name}: {audio_line}");
+}
+
+/// Like `spawn_log_reader`, but also echoes the serial output to this terminal and stamps it
+/// into the transcript. Prints a note once qemu closes its end.
+fn spawn_console_reader<R: Read + Send + 'static>(
+    mut reader: R,
+    log: Arc<Mutex<Vec<u8>>>,
+    transcript: Arc<Mutex<ConsoleTranscript>>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buffer = [0u8; 2048];
+        let mut out = std::io::stdout();
+        while let Ok(len @ 1..) = reader.read(&mut buffer) {
+            let _ = out.write_all(&buffer[..len]);
+            let _ = out.flush();
+            if let Ok(mut bytes) = log.lock() {
+                bytes.extend_from_slice(&buffer[..len]);
+            }
+            if let Ok(mut transcript) = transcript.lock() {
+                transcript.guest_bytes(&buffer[..len]);
+            }
+        }
+        eprintln!("\nconsole: qemu closed the serial console; press Enter to leave");
+    })
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = u32::from(chunk[0]) << 16
+            | u32::from(*chunk.get(1).unwrap_or(&0)) << 8
+            | u32::from(*chunk.get(2).unwrap_or(&0));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(char::from(
+                    BASE64_ALPHABET[(word >> (18 - 6 * index) & 0x3f) as usize],
+                ));
+            } else {
+                out.push('=');
+            }
+        }
     }
-    if let Some(status_line) = last_matching_line(&log_snapshot, "doom: app=doom engine=") {
-        println!("{smoke_name}: {status_line}");
+    out
+}
+
+fn base64_decode(text: &str) -> Result<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        bail!("length {} is not a multiple of 4", text.len());
     }
-    if let Some(key_line) = last_matching_line(&log_snapshot, "doom: injected key 0x61") {
-        println!("{smoke_name}: {key_line}");
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for group in text.chunks(4) {
+        let padding = group.iter().rev().take_while(|byte| **byte == b'=').count();
+        let mut word = 0u32;
+        for byte in &group[..4 - padding] {
+            let value = BASE64_ALPHABET
+                .iter()
+                .position(|candidate| candidate == byte)
+                .with_context(|| format!("invalid character `{}`", char::from(*byte)))?;
+            word = word << 6 | value as u32;
+        }
+        word <<= 6 * padding as u32;
+        out.extend_from_slice(&word.to_be_bytes()[1..4 - padding]);
     }
-    if let Some(keyup_line) = last_matching_line(&log_snapshot, "doom: injected keyup 0x61") {
-        println!("{smoke_name}: {keyup_line}");
+    Ok(out)
+}
+
+/// Codec `serial compress` must report for xtask to turn framing on.
+const SERIAL_CODEC: &str = "rle1";
+/// Start of a compressed frame line: `~z1 <raw_len> <crc32> <base64>`.
+const SERIAL_FRAME_PREFIX: &str = "~z1 ";
+/// Runs shorter than this are sent as literals; matches the kernel's `serial/frame.rs`.
+const SERIAL_MIN_RUN: usize = 4;
+
+/// Turns on `serial compress` when the guest speaks `SERIAL_CODEC`, so bulk dumps arrive as
+/// frames that `snapshot_log` expands. Set `ARROST_SERIAL_PLAIN=1` to keep plain text.
+fn negotiate_serial_compression(log: &Arc<Mutex<Vec<u8>>>, stdin: &mut ChildStdin) -> Result<()> {
+    let line = query_status_line(log, stdin, "serial compress on\n", "serial: compress=")?;
+    if line.contains(&format!("codec={SERIAL_CODEC} ")) {
+        return Ok(());
     }
-    if let Some(fire_line) = last_matching_line(&log_snapshot, "doom: injected key 0x20") {
-        println!("{smoke_name}: {fire_line}");
+    eprintln!("serial: guest codec unsupported, keeping plain output: {line}");
+    query_status_line(log, stdin, "serial compress off\n", "serial: compress=")?;
+    Ok(())
+}
+
+/// Replaces every complete `~z1` frame line with the text it carries. Lines that fail to
+/// decode are kept verbatim so a check on them reports the damage.
+fn expand_serial_frames(log: &str) -> String {
+    if !log.contains(SERIAL_FRAME_PREFIX) {
+        return log.to_string();
     }
-    if let Some(enter_line) = last_matching_line(&log_snapshot, "doom: injected key 0x0a") {
-        println!("{smoke_name}: {enter_line}");
+    let mut out = String::with_capacity(log.len());
+    for line in log.split_inclusive('\n') {
+        let decoded = line
+            .strip_suffix('\n')
+            .and_then(|line| line.find(SERIAL_FRAME_PREFIX).map(|start| (line, start)))
+            .and_then(|(line, start)| {
+                let text = decode_serial_frame(line[start..].trim_end())?;
+                Some((&line[..start], text))
+            });
+        match decoded {
+            Some((plain, text)) => {
+                out.push_str(plain);
+                out.push_str(&String::from_utf8_lossy(&text));
+            }
+            None => out.push_str(line),
+        }
     }
-    if let Some(ui_line) = last_matching_line(&log_snapshot, "ui: backend=uefi-gop ready=true") {
-        println!("{smoke_name}: {ui_line}");
+    out
+}
+
+fn decode_serial_frame(line: &str) -> Option<Vec<u8>> {
+    let mut fields = line.strip_prefix(SERIAL_FRAME_PREFIX)?.split(' ');
+    let raw_len = fields.next()?.parse::<usize>().ok()?;
+    let crc = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let encoded = base64_decode(fields.next()?).ok()?;
+    let text = rle_decode(&encoded)?;
+    (text.len() == raw_len && crc32(&text) == crc).then_some(text)
+}
+
+/// Inverse of the kernel's frame codec: varint headers, even `2n` for `n` literal bytes, odd
+/// `2n + 1` for one byte repeated `n + SERIAL_MIN_RUN` times.
+fn rle_decode(mut bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    while !bytes.is_empty() {
+        let mut header = 0u64;
+        let mut shift = 0;
+        loop {
+            let (&byte, rest) = bytes.split_first()?;
+            bytes = rest;
+            header |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+            if shift >= 64 {
+                return None;
+            }
+        }
+        let count = usize::try_from(header >> 1).ok()?;
+        if header & 1 == 0 {
+            let literal = bytes.get(..count)?;
+            out.extend_from_slice(literal);
+            bytes = &bytes[count..];
+        } else {
+            let (&byte, rest) = bytes.split_first()?;
+            bytes = rest;
+            out.resize(out.len() + count + SERIAL_MIN_RUN, byte);
+        }
+    }
+    Some(out)
+}
+
+/// Expands the `~z1` frames in a saved serial log, e.g. one captured from `cargo xtask run`
+/// after `serial compress on`.
+fn serial_decode(mut args: impl Iterator<Item = String>) -> Result<()> {
+    const USAGE: &str = "usage: cargo xtask serial-decode <log> [out]";
+    let input = PathBuf::from(args.next().context(USAGE)?);
+    let bytes =
+        std::fs::read(&input).with_context(|| format!("failed to read {}", input.display()))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let frames = text
+        .lines()
+        .filter(|line| line.contains(SERIAL_FRAME_PREFIX))
+        .count();
+    let expanded = expand_serial_frames(&text);
+    let damaged = expanded
+        .lines()
+        .filter(|line| line.contains(SERIAL_FRAME_PREFIX))
+        .count();
+    match args.next() {
+        Some(out) => {
+            std::fs::write(&out, &expanded).with_context(|| format!("failed to write {out}"))?;
+            println!(
+                "serial-decode: {} -> {out} frames={frames} damaged={damaged} bytes={}->{}",
+                input.display(),
+                bytes.len(),
+                expanded.len()
+            );
+        }
+        None => print!("{expanded}"),
+    }
+    if damaged > 0 {
+        bail!("{damaged} of {frames} frames failed their length or crc32 check");
+    }
+    Ok(())
+}
+
+/// Prefix of the kernel's `evlog dump` record lines (matches the kernel's `evlog::LINE_PREFIX`).
+const EVLOG_LINE_PREFIX: &str = "~ev1 ";
+const EVLOG_RECORD_BYTES: usize = 16;
+
+/// Decodes an `evlog dump` captured in a serial log into one text line per record, using the
+/// dump's own `def` lines for names so the decoder does not need to track the kernel's event list.
+fn evlog_decode(mut args: impl Iterator<Item = String>) -> Result<()> {
+    const USAGE: &str = "usage: cargo xtask evlog-decode <log> [out]";
+    let input = PathBuf::from(args.next().context(USAGE)?);
+    let bytes =
+        std::fs::read(&input).with_context(|| format!("failed to read {}", input.display()))?;
+    let text = expand_serial_frames(&String::from_utf8_lossy(&bytes));
+
+    let mut hz = 0u64;
+    let mut dumps = 0usize;
+    let mut names = std::collections::HashMap::<(u8, u8), (String, String, String)>::new();
+    let mut output = String::new();
+    let mut records = 0usize;
+    let mut damaged = 0usize;
+    for line in text.lines() {
+        if let Some(at) = line.find("evlog: dump version=") {
+            // A later dump in the same log replaces the earlier one.
+            dumps += 1;
+            names.clear();
+            output.clear();
+            records = 0;
+            damaged = 0;
+            hz = line[at..]
+                .split_whitespace()
+                .find_map(|field| field.strip_prefix("hz="))
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(0);
+            output.push_str(line[at..].trim_end());
+            output.push('\n');
+        } else if let Some(at) = line.find("evlog: def ") {
+            let fields = line[at + "evlog: def ".len()..]
+                .split_whitespace()
+                .collect::<Vec<_>>();
+            if let [sub, event, name, a_name, b_name] = fields[..]
+                && let (Ok(sub), Ok(event)) = (sub.parse(), event.parse())
+            {
+                names.insert(
+                    (sub, event),
+                    (name.to_string(), a_name.to_string(), b_name.to_string()),
+                );
+            }
+        } else if let Some(at) = line.find(EVLOG_LINE_PREFIX) {
+            let Some(payload) = evlog_line_payload(&line[at + EVLOG_LINE_PREFIX.len()..]) else {
+                damaged += 1;
+                continue;
+            };
+            let (line_records, partial) = payload.as_chunks::<EVLOG_RECORD_BYTES>();
+            if !partial.is_empty() {
+                damaged += 1;
+                continue;
+            }
+            for record in line_records {
+                let word = |at: usize| {
+                    u32::from_le_bytes([record[at], record[at + 1], record[at + 2], record[at + 3]])
+                };
+                let (tick, a, b) = (word(4), word(8), word(12));
+                let ms = (u64::from(tick) * 1000).checked_div(hz).unwrap_or(0);
+                match names.get(&(record[0], record[1])) {
+                    Some((name, a_name, b_name)) => output.push_str(&format!(
+                        "tick={tick} ms={ms} {name} {a_name}={a:#x} {b_name}={b:#x}\n"
+                    )),
+                    None => output.push_str(&format!(
+                        "tick={tick} ms={ms} sub{}.event{} a={a:#x} b={b:#x}\n",
+                        record[0], record[1]
+                    )),
+                }
+                records += 1;
+            }
+        }
+    }
+    if dumps == 0 {
+        bail!("no `evlog: dump` in {}", input.display());
+    }
+    match args.next() {
+        Some(out) => {
+            std::fs::write(&out, &output).with_context(|| format!("failed to write {out}"))?;
+            println!(
+                "evlog-decode: {} -> {out} records={records} damaged={damaged} hz={hz}",
+                input.display()
+            );
+        }
+        None => print!("{output}"),
+    }
+    if damaged > 0 {
+        bail!("{damaged} evlog lines failed their count or crc32 check");
     }
     Ok(())
 }
 
+/// `<count> <crc32> <base64>` from one `~ev1` line, checked against both.
+fn evlog_line_payload(line: &str) -> Option<Vec<u8>> {
+    let mut fields = line.split_whitespace();
+    let count = fields.next()?.parse::<usize>().ok()?;
+    let crc = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let payload = base64_decode(fields.next()?).ok()?;
+    (payload.len() == count * EVLOG_RECORD_BYTES && crc32(&payload) == crc).then_some(payload)
+}
+
+/// Sends one `<token> <command>` request to a guest netconsole (usually through QEMU
+/// `hostfwd`) and prints the reply; fails unless the guest reports `status=ok`.
+fn netconsole(mut args: impl Iterator<Item = String>) -> Result<()> {
+    const USAGE: &str = "usage: cargo xtask netconsole <host:port> <token> <command>";
+    let target = args.next().context(USAGE)?;
+    let token = args.next().context(USAGE)?;
+    let command = args.collect::<Vec<_>>().join(" ");
+    if command.is_empty() {
+        bail!(USAGE);
+    }
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0").context("failed to bind udp socket")?;
+    socket
+        .connect(&target)
+        .with_context(|| format!("failed to resolve {target}"))?;
+    socket
+        .send(format!("{token} {command}").as_bytes())
+        .with_context(|| format!("failed to send to {target}"))?;
+
+    let deadline = Instant::now() + NETCONSOLE_TIMEOUT;
+    let mut buffer = [0u8; 2048];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            bail!("no `netconsole: end` from {target} within {NETCONSOLE_TIMEOUT:?}");
+        }
+        socket
+            .set_read_timeout(Some(remaining))
+            .context("failed to set socket timeout")?;
+        let len = match socket.recv(&mut buffer) {
+            Ok(len) => len,
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                continue;
+            }
+            Err(error) => return Err(error).with_context(|| format!("recv from {target}")),
+        };
+        let text = String::from_utf8_lossy(&buffer[..len]);
+        if let Some(end) = text.strip_prefix("netconsole: end ") {
+            if !end.contains("status=ok") {
+                bail!("netconsole request failed: {}", end.trim());
+            }
+            return Ok(());
+        }
+        print!("{text}");
+    }
+}
+
 fn env_truthy(name: &str) -> bool {
     matches!(
         std::env::var(name).ok().as_deref(),
@@ -1256,6 +4577,30 @@ fn wait_for_log(
     }
 }
 
+/// Like `wait_for_log`, but only looks at what the log gained after its first `start` bytes.
+fn wait_for_log_from(
+    log: &Arc<Mutex<Vec<u8>>>,
+    start: usize,
+    needle: &str,
+    timeout: Duration,
+    stage: &str,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let snapshot = snapshot_log(log);
+        if snapshot
+            .get(start..)
+            .is_some_and(|tail| tail.contains(needle))
+        {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            bail!("timeout waiting for {stage}: expected `{needle}`");
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
 fn wait_for_status_with_frame_progress(
     log: &Arc<Mutex<Vec<u8>>>,
     min_frames: u64,
@@ -1330,11 +4675,107 @@ fn spawn_log_reader<R: Read + Send + 'static>(
 
 fn snapshot_log(log: &Arc<Mutex<Vec<u8>>>) -> String {
     if let Ok(bytes) = log.lock() {
-        return String::from_utf8_lossy(&bytes).into_owned();
+        return expand_serial_frames(&String::from_utf8_lossy(&bytes));
     }
     String::new()
 }
 
+/// Sections the kernel's `sysinfo` block is expected to carry.
+const SYSINFO_SECTIONS: [&str; 8] = [
+    "mem", "net", "storage", "fs", "gfx", "audio", "tasks", "doom",
+];
+
+/// Parses the last `sysinfo: begin` .. `sysinfo: end` block into `section.key` -> value.
+/// Per-task lines are keyed by pid, e.g. `task1.name`.
+fn parse_sysinfo(log: &str) -> Result<std::collections::BTreeMap<String, String>> {
+    let begin = log
+        .rfind("sysinfo: begin")
+        .context("missing `sysinfo: begin` block")?;
+    let mut fields = std::collections::BTreeMap::new();
+    for line in log[begin..].lines() {
+        let Some(rest) = line.trim_end().strip_prefix("sysinfo: ") else {
+            continue;
+        };
+        let (section, pairs) = rest.split_once(' ').unwrap_or((rest, ""));
+        let section = if section == "task" {
+            let pid = pairs
+                .split_whitespace()
+                .find_map(|pair| pair.strip_prefix("pid="))
+                .context("sysinfo task line without pid")?;
+            format!("task{pid}")
+        } else {
+            section.to_string()
+        };
+        for (key, value) in pairs
+            .split_whitespace()
+            .filter_map(|pair| pair.split_once('='))
+        {
+            fields.insert(format!("{section}.{key}"), value.to_string());
+        }
+        if section == "end" {
+            return Ok(fields);
+        }
+    }
+    bail!("sysinfo block has no `sysinfo: end` line")
+}
+
+/// One parse of the boot-time `sysinfo` block covers every subsystem's basic health.
+fn check_boot_sysinfo(log: &str, smoke_name: &str) -> Result<()> {
+    let info = parse_sysinfo(log)?;
+    let field = |key: &str| -> Result<&str> {
+        info.get(key)
+            .map(String::as_str)
+            .with_context(|| format!("sysinfo missing `{key}`"))
+    };
+    let number = |key: &str| -> Result<u64> {
+        field(key)?
+            .parse::<u64>()
+            .with_context(|| format!("sysinfo `{key}` is not a number"))
+    };
+
+    let missing: Vec<&str> = SYSINFO_SECTIONS
+        .into_iter()
+        .filter(|section| {
+            let prefix = format!("{section}.");
+            !info.keys().any(|key| key.starts_with(&prefix))
+        })
+        .collect();
+    if !missing.is_empty() {
+        bail!("sysinfo block is missing sections: {}", missing.join(", "));
+    }
+    if number("end.sections")? != SYSINFO_SECTIONS.len() as u64 {
+        bail!(
+            "sysinfo reports {} sections, expected {}",
+            field("end.sections")?,
+            SYSINFO_SECTIONS.len()
+        );
+    }
+    if number("mem.total_mib")? == 0 {
+        bail!("sysinfo mem.total_mib=0");
+    }
+    if number("fs.files")? == 0 {
+        bail!("sysinfo fs.files=0 (fs not seeded)");
+    }
+    if number("tasks.count")? < 2 {
+        bail!("sysinfo tasks.count < 2 (init and sh expected)");
+    }
+    if field("gfx.ready")? != "true" {
+        bail!("sysinfo gfx.ready={}", field("gfx.ready")?);
+    }
+    println!(
+        "{smoke_name}: sysinfo version={} mem_total_mib={} net_ready={} fs={}/{} files gfx={} audio={} tasks={}",
+        field("begin.version")?,
+        field("mem.total_mib")?,
+        field("net.ready")?,
+        field("fs.backend")?,
+        field("fs.files")?,
+        field("gfx.mode")?,
+        field("audio.backend")?,
+        field("tasks.count")?
+    );
+    Ok(())
+}
+
 fn last_matching_line<'a>(log: &'a str, marker: &str) -> Option<&'a str> {
     log.lines().rev().find(|line| line.contains(marker))
 }
@@ -1357,15 +4798,92 @@ fn log_tail(log: &str, lines: usize) -> String {
     tail.join("\n")
 }
 
+/// Size and SHA-256 of every artifact that exists on disk; missing ones are left out.
+fn collect_artifact_manifest(
+    user_init: &UserArtifact,
+    user_doom: &UserArtifact,
+    doom_c_backend: &DoomCBackendArtifact,
+    doom_generic: &DoomGenericArtifact,
+) -> Result<Vec<ManifestEntry>> {
+    let mut candidates = vec![
+        ("init", &user_init.hint),
+        ("doom", &user_doom.hint),
+        ("doom_c_backend", &doom_c_backend.object),
+        ("doom_core", &doom_generic.core_object),
+        ("doom_port", &doom_generic.port_object),
+    ];
+    if doom_generic.wad_present {
+        candidates.push(("doom_wad", &doom_generic.wad_hint));
+    }
+
+    let mut entries = Vec::new();
+    for (name, path) in candidates {
+        if !path.is_file() {
+            continue;
+        }
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        entries.push(ManifestEntry {
+            name,
+            path: path.clone(),
+            size: bytes.len() as u64,
+            sha256: sha256_hex(&bytes),
+        });
+    }
+    Ok(entries)
+}
+
+/// Single-line JSON, so it fits one `manifest=` line of the initramfs.
+fn artifact_manifest_json(entries: &[ManifestEntry]) -> String {
+    let artifacts = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"name\":{},\"path\":{},\"size\":{},\"sha256\":{}}}",
+                json_string(entry.name),
+                json_string(&entry.path.display().to_string()),
+                entry.size,
+                json_string(&entry.sha256)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{{\"version\":{ARTIFACT_MANIFEST_VERSION},\"artifacts\":[{artifacts}]}}")
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            ch if u32::from(ch) < 0x20 => out.push_str(&format!("\\u{:04x}", u32::from(ch))),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
 fn create_ramdisk_image(
     user_init: &UserArtifact,
     user_doom: &UserArtifact,
     doom_c_backend: &DoomCBackendArtifact,
     doom_generic: &DoomGenericArtifact,
+    manifest: &[ManifestEntry],
 ) -> Result<PathBuf> {
     let ramdisk_path = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/ramdisk"));
+    let manifest_json = artifact_manifest_json(manifest);
+    let manifest_path = PathBuf::from(format!("target/{KERNEL_TARGET}/debug/artifacts.json"));
+    std::fs::write(&manifest_path, format!("{manifest_json}\n"))
+        .with_context(|| format!("failed to write {}", manifest_path.display()))?;
+    println!(
+        "ArrOSt artifact manifest: entries={} path={}",
+        manifest.len(),
+        manifest_path.display()
+    );
     let payload = format!(
-        "ARR0ST_INITRAMFS_V4\ninit_app=init\ninit_artifact_hint={}\ninit_artifact_size={}\ndoom_app=doom\ndoom_artifact_hint={}\ndoom_artifact_size={}\ndoom_c_backend_object={}\ndoom_c_backend_size={}\ndoom_c_backend_ready={}\ndoom_generic_root={}\ndoom_generic_core_source={}\ndoom_generic_core_object={}\ndoom_generic_core_size={}\ndoom_generic_core_ready={}\ndoom_generic_port_object={}\ndoom_generic_port_size={}\ndoom_generic_port_ready={}\ndoom_generic_ready={}\ndoom_wad_hint={}\ndoom_wad_present={}\n",
+        "ARR0ST_INITRAMFS_V5\ninit_app=init\ninit_artifact_hint={}\ninit_artifact_size={}\ndoom_app=doom\ndoom_artifact_hint={}\ndoom_artifact_size={}\ndoom_c_backend_object={}\ndoom_c_backend_size={}\ndoom_c_backend_ready={}\ndoom_generic_root={}\ndoom_generic_core_source={}\ndoom_generic_core_object={}\ndoom_generic_core_size={}\ndoom_generic_core_ready={}\ndoom_generic_port_object={}\ndoom_generic_port_size={}\ndoom_generic_port_ready={}\ndoom_generic_ready={}\ndoom_wad_hint={}\ndoom_wad_present={}\nmanifest={}\n",
         user_init.hint.display(),
         user_init.size,
         user_doom.hint.display(),
@@ -1383,7 +4901,8 @@ fn create_ramdisk_image(
         doom_generic.port_ready,
         doom_generic.ready,
         doom_generic.wad_hint.display(),
-        doom_generic.wad_present
+        doom_generic.wad_present,
+        manifest_json
     );
     std::fs::write(&ramdisk_path, payload.as_bytes())
         .with_context(|| format!("failed to write {}", ramdisk_path.display()))?;